@@ -1,5 +1,6 @@
 import { BrowserRouter, Routes, Route, Navigate, useLocation } from "react-router-dom";
 import { Dashboard } from "./Dashboard";
+import { WatchPage } from "./WatchPage";
 
 function RedirectToRoot() {
   const location = useLocation();
@@ -11,6 +12,7 @@ function App() {
     <BrowserRouter>
       <Routes>
         <Route path="/" element={<Dashboard />} />
+        <Route path="/watch/:jobId" element={<WatchPage />} />
         {/* Legacy redirects */}
         <Route path="/admin/*" element={<RedirectToRoot />} />
         <Route path="*" element={<RedirectToRoot />} />
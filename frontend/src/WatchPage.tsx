@@ -0,0 +1,53 @@
+import { useEffect, useState } from "react";
+import { useLocation, useParams } from "react-router-dom";
+import { VideoPlayer } from "./components/VideoPlayer";
+import "./WatchPage.css";
+
+interface WatchInfo {
+  job_id: string;
+  url: string;
+  encryption_key?: string;
+  expires_at: number;
+}
+
+/**
+ * Preview of a job's output behind a signed, expiring `/watch` link.
+ *
+ * The DVM checks the link and hands out the output URL (and the decryption
+ * key of encrypted HLS) through `/api/watch`, so this page only works on the
+ * DVM's own web server.
+ */
+export function WatchPage() {
+  const { jobId } = useParams();
+  const location = useLocation();
+  const [info, setInfo] = useState<WatchInfo | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    fetch(`/api/watch/${jobId}${location.search}`)
+      .then(async (res) => {
+        const body = await res.json().catch(() => ({}));
+        if (!res.ok) throw new Error(body.error || `Request failed (${res.status})`);
+        setInfo(body as WatchInfo);
+      })
+      .catch((e: Error) => setError(e.message));
+  }, [jobId, location.search]);
+
+  return (
+    <div className="watch-page">
+      {error && <p className="watch-error">{error}</p>}
+      {info && (
+        <>
+          {info.url.endsWith(".m3u8") ? (
+            <VideoPlayer src={info.url} encryptionKey={info.encryption_key} />
+          ) : (
+            <video className="watch-mp4" src={info.url} controls playsInline />
+          )}
+          <p className="watch-expiry">
+            Link valid until {new Date(info.expires_at * 1000).toLocaleString()}
+          </p>
+        </>
+      )}
+    </div>
+  );
+}
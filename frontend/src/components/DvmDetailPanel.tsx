@@ -9,6 +9,8 @@ import {
   type DvmStatus,
   type DvmJob,
   type DvmDashboard,
+  type DvmRelayStats,
+  type DvmServerHealth,
   type AdminResponseWire,
 } from "../nostr/admin";
 import { getCurrentSigner } from "../nostr/client";
@@ -23,6 +25,7 @@ import { parseStatusEvent, parseResultEvent, type DvmResult } from "../nostr/eve
 import { UserAvatar } from "./UserAvatar";
 import { UserName } from "./UserName";
 import { IconClock, IconRefresh, IconCheckCircle, IconXCircle } from "./Icons";
+import { useJobEvents } from "../hooks/useJobEvents";
 import "./DvmDetailPanel.css";
 
 type TabType = "overview" | "config" | "transcode" | "system";
@@ -47,6 +50,8 @@ export function DvmDetailPanel({ dvm, userPubkey }: DvmDetailPanelProps) {
   const [status, setStatus] = useState<DvmStatus | null>(dvm.status || null);
   const [config, setConfig] = useState<DvmConfig | null>(null);
   const [jobs, setJobs] = useState<DvmJob[]>([]);
+  const [relayStats, setRelayStats] = useState<DvmRelayStats[]>([]);
+  const [blossomHealth, setBlossomHealth] = useState<DvmServerHealth[]>([]);
   const [loading, setLoading] = useState(false);
   const [actionLoading, setActionLoading] = useState(false);
   const [offline, setOffline] = useState(false);
@@ -98,6 +103,8 @@ export function DvmDetailPanel({ dvm, userPubkey }: DvmDetailPanelProps) {
       setStatus(dashboard.status);
       setConfig(dashboard.config);
       setJobs(dashboard.jobs);
+      setRelayStats(dashboard.relays ?? []);
+      setBlossomHealth(dashboard.blossom_health ?? []);
     }
     // Status response (from status, pause, or resume commands)
     else if ("paused" in data && "jobs_active" in data) {
@@ -161,6 +168,17 @@ export function DvmDetailPanel({ dvm, userPubkey }: DvmDetailPanelProps) {
     };
   }, [dvm.pubkey, dvm.isOwned, userPubkey, handleAdminResponse]);
 
+  const refreshDashboard = useCallback(() => {
+    const signer = getCurrentSigner();
+    if (!signer) return;
+    sendAdminCommand(signer, dvm.pubkey, "get_dashboard", { limit: 20 }, RELAYS).catch((err) =>
+      console.error("Failed to refresh DVM data:", err)
+    );
+  }, [dvm.pubkey]);
+
+  // Live progress when this page is served by the DVM itself
+  const liveJobs = useJobEvents(dvm.pubkey, !!dvm.isOwned, refreshDashboard);
+
   const handlePauseResume = useCallback(async () => {
     const signer = getCurrentSigner();
     if (!signer || !status) return;
@@ -507,6 +525,9 @@ export function DvmDetailPanel({ dvm, userPubkey }: DvmDetailPanelProps) {
                     <div className="stat-content">
                       <h3>Active Jobs</h3>
                       <p className="stat-value">{status.jobs_active}</p>
+                      {!!status.jobs_queued && (
+                        <p className="stat-detail">{status.jobs_queued} queued</p>
+                      )}
                     </div>
                   </div>
                   <div className="stat-card">
@@ -561,6 +582,36 @@ export function DvmDetailPanel({ dvm, userPubkey }: DvmDetailPanelProps) {
               </>
             )}
 
+            {liveJobs.length > 0 && (
+              <div className="recent-jobs">
+                <h3>Running Now</h3>
+                <table className="jobs-table">
+                  <thead>
+                    <tr>
+                      <th>Job</th>
+                      <th>Mode</th>
+                      <th>Progress</th>
+                      <th>Status</th>
+                    </tr>
+                  </thead>
+                  <tbody>
+                    {liveJobs.map((job) => (
+                      <tr key={job.jobId}>
+                        <td className="truncate"><code>{job.jobId.slice(0, 12)}</code></td>
+                        <td>{job.mode ?? "-"}</td>
+                        <td>
+                          {job.phase
+                            ? `${job.phase}${job.percent != null ? ` ${job.percent}%` : ""}`
+                            : "-"}
+                        </td>
+                        <td className="truncate">{job.message ?? job.status ?? "-"}</td>
+                      </tr>
+                    ))}
+                  </tbody>
+                </table>
+              </div>
+            )}
+
             {jobs.length > 0 && (
               <div className="recent-jobs">
                 <h3>Recent Jobs</h3>
@@ -588,6 +639,77 @@ export function DvmDetailPanel({ dvm, userPubkey }: DvmDetailPanelProps) {
                 </table>
               </div>
             )}
+
+            {relayStats.length > 0 && (
+              <div className="recent-jobs">
+                <h3>Relays</h3>
+                <table className="jobs-table">
+                  <thead>
+                    <tr>
+                      <th>Relay</th>
+                      <th>Received</th>
+                      <th>Published</th>
+                      <th>Failed</th>
+                      <th>Avg Latency</th>
+                    </tr>
+                  </thead>
+                  <tbody>
+                    {relayStats.map((relay) => (
+                      <tr key={relay.url}>
+                        <td className="truncate">{relay.url}</td>
+                        <td>{relay.events_received}</td>
+                        <td>{relay.events_published}</td>
+                        <td>
+                          {relay.publish_failures > 0 ? (
+                            <span className="job-status failed">
+                              {relay.publish_failures}
+                              {relay.refused && ` (${relay.refused})`}
+                            </span>
+                          ) : (
+                            0
+                          )}
+                        </td>
+                        <td>
+                          {relay.avg_publish_latency_ms !== undefined
+                            ? `${relay.avg_publish_latency_ms} ms`
+                            : "-"}
+                        </td>
+                      </tr>
+                    ))}
+                  </tbody>
+                </table>
+              </div>
+            )}
+
+            {blossomHealth.length > 0 && (
+              <div className="recent-jobs">
+                <h3>Blossom Servers</h3>
+                <table className="jobs-table">
+                  <thead>
+                    <tr>
+                      <th>Server</th>
+                      <th>Status</th>
+                      <th>Latency</th>
+                      <th>Checked</th>
+                    </tr>
+                  </thead>
+                  <tbody>
+                    {blossomHealth.map((server) => (
+                      <tr key={server.url}>
+                        <td className="truncate">{server.url}</td>
+                        <td title={server.last_error}>
+                          <span className={`job-status ${server.healthy ? "completed" : "failed"}`}>
+                            {server.healthy ? "healthy" : "skipped"}
+                          </span>
+                        </td>
+                        <td>{server.latency_ms !== undefined ? `${server.latency_ms} ms` : "-"}</td>
+                        <td>{formatTimestamp(server.checked_at)}</td>
+                      </tr>
+                    ))}
+                  </tbody>
+                </table>
+              </div>
+            )}
           </div>
         )}
 
@@ -0,0 +1,97 @@
+import { useEffect, useRef, useState } from 'react'
+
+export interface LiveJob {
+  jobId: string
+  mode?: string
+  status?: string
+  message?: string
+  phase?: string
+  percent?: number | null
+  etaSecs?: number
+  speed?: number
+}
+
+type JobUpdate =
+  | { type: 'started'; job_id: string; mode: string }
+  | { type: 'status'; job_id: string; status: string; code?: string; message?: string }
+  | {
+      type: 'progress'
+      job_id: string
+      phase: string
+      percent: number | null
+      eta_secs?: number
+      speed?: number
+    }
+  | { type: 'finished'; job_id: string; outcome: string }
+
+/**
+ * Live job updates from the `/api/events` stream of the DVM serving this page.
+ *
+ * The stream only exists on the embedded dashboard, so it is ignored unless
+ * the server announces the same pubkey as `dvmPubkey`. `onChanged` is called
+ * when a job finishes or updates were missed, so the caller can refresh its
+ * job history.
+ */
+export function useJobEvents(dvmPubkey: string, enabled: boolean, onChanged: () => void) {
+  const [jobs, setJobs] = useState<Record<string, LiveJob>>({})
+  const onChangedRef = useRef(onChanged)
+  onChangedRef.current = onChanged
+
+  useEffect(() => {
+    if (!enabled || typeof EventSource === 'undefined') return
+
+    const source = new EventSource('/api/events')
+    let matched = false
+
+    const update = (jobId: string, patch: Partial<LiveJob>) =>
+      setJobs((prev) => ({ ...prev, [jobId]: { ...prev[jobId], jobId, ...patch } }))
+
+    source.addEventListener('hello', (e) => {
+      const { pubkey } = JSON.parse((e as MessageEvent).data)
+      matched = pubkey === dvmPubkey
+      if (!matched) source.close()
+    })
+
+    const handle = (e: Event) => {
+      if (!matched) return
+      const data = JSON.parse((e as MessageEvent).data) as JobUpdate
+      switch (data.type) {
+        case 'started':
+          update(data.job_id, { mode: data.mode })
+          break
+        case 'status':
+          update(data.job_id, { status: data.status, message: data.message })
+          break
+        case 'progress':
+          update(data.job_id, {
+            phase: data.phase,
+            percent: data.percent,
+            etaSecs: data.eta_secs,
+            speed: data.speed,
+          })
+          break
+        case 'finished':
+          setJobs((prev) => {
+            const next = { ...prev }
+            delete next[data.job_id]
+            return next
+          })
+          onChangedRef.current()
+          break
+      }
+    }
+    for (const kind of ['started', 'status', 'progress', 'finished']) {
+      source.addEventListener(kind, handle)
+    }
+    source.addEventListener('lagged', () => {
+      if (matched) onChangedRef.current()
+    })
+
+    return () => {
+      source.close()
+      setJobs({})
+    }
+  }, [dvmPubkey, enabled])
+
+  return Object.values(jobs)
+}
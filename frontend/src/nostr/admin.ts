@@ -40,8 +40,12 @@ export interface DvmConfig {
 export interface DvmStatus {
   paused: boolean;
   jobs_active: number;
+  jobs_queued?: number;
+  channel_depth?: number;
+  queue_capacity?: number;
   jobs_completed: number;
   jobs_failed: number;
+  ffmpeg_failures?: Record<string, number>;
   uptime_secs: number;
   hwaccel?: string;
   version: string;
@@ -51,6 +55,26 @@ export interface DvmDashboard {
   status: DvmStatus;
   config: DvmConfig;
   jobs: DvmJob[];
+  relays?: DvmRelayStats[];
+  blossom_health?: DvmServerHealth[];
+}
+
+export interface DvmRelayStats {
+  url: string;
+  events_received: number;
+  events_published: number;
+  publish_failures: number;
+  avg_publish_latency_ms?: number;
+  refused?: string;
+}
+
+export interface DvmServerHealth {
+  url: string;
+  healthy: boolean;
+  latency_ms?: number;
+  consecutive_failures: number;
+  last_error?: string;
+  checked_at: number;
 }
 
 export interface DvmJob {
@@ -131,6 +155,7 @@ export interface SystemInfoResult {
   disk: DiskInfo;
   ffmpeg: FfmpegInfo;
   temp_dir: string;
+  blossom_health?: DvmServerHealth[];
 }
 
 /**
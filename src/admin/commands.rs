@@ -2,11 +2,22 @@
 //!
 //! This module defines the command and response types for admin DM interactions.
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::blossom::{BlobDescriptor, QuarantineEntry, Replication, ServerHealth};
+use crate::dvm::job_log::JobLogLine;
+use crate::nostr::RelayStatsEntry;
+use crate::payments::cashu::Redemption;
+use crate::util::resources::ResourceSnapshot;
+use crate::video::transform::{LadderRung, QualityPreset};
+use crate::video::watermark::Watermark;
+
 /// Admin commands received via encrypted DMs.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "cmd", rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)] // commands are parsed once per DM, never stored in bulk
 pub enum AdminCommand {
     /// Get current configuration
     GetConfig,
@@ -29,11 +40,17 @@ pub enum AdminCommand {
     Resume,
     /// Get current status
     Status,
-    /// Get job history
+    /// Get job history (paginated, optionally filtered)
     JobHistory {
         #[serde(default = "default_job_history_limit")]
         limit: u32,
+        #[serde(default)]
+        offset: u32,
+        #[serde(default, flatten)]
+        filter: JobHistoryFilter,
     },
+    /// Abort a running job (kills its FFmpeg process) and return its record
+    CancelJob { id: String },
     /// Get dashboard data (status + config + recent jobs) in one response
     GetDashboard {
         #[serde(default = "default_job_history_limit")]
@@ -53,6 +70,66 @@ pub enum AdminCommand {
         about: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
         max_concurrent_jobs: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        external_fetchers_enabled: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        default_hls_resolutions: Option<Vec<String>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        default_mp4_resolution: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        default_codec: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        default_segment_type: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        default_hls_time: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        default_keyframe_interval: Option<f64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        default_max_fps: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        queue_policy: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        bitrate_ladder: Option<Vec<LadderRung>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        quality_presets: Option<Vec<QualityPreset>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max_retention_days: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        retention_sats_per_day: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max_input_duration_secs: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max_input_size_bytes: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max_output_size_ratio: Option<f64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max_output_size_bytes: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        watermark: Option<Watermark>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        normalize_audio: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        strip_metadata: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        price_sats_per_minute: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        price_sats_flat: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        price_sats_per_output_gb: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        creator_priority: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        creator_discount_percent: Option<u8>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        report_threshold: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        encrypt_scratch: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        allowed_pubkeys: Option<Vec<String>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        blocked_pubkeys: Option<Vec<String>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max_jobs_per_hour: Option<u32>,
     },
     /// Run self-test (encode a short video)
     SelfTest {
@@ -61,8 +138,87 @@ pub enum AdminCommand {
     },
     /// Get system information (hardware, GPU, disk, FFmpeg)
     SystemInfo,
+    /// Get per-relay event and publish statistics
+    RelayStats,
     /// Import configuration from environment variables
     ImportEnvConfig,
+    /// List results quarantined after abuse reports
+    ListQuarantine,
+    /// Put a quarantined result's blobs back (by result event ID)
+    RestoreQuarantined { id: String },
+    /// Download a job's input into the data dir for offline analysis
+    FetchInput { id: String },
+    /// List the DVM's blobs on a server (default: the first configured one)
+    ListBlobs {
+        server: Option<String>,
+        limit: Option<usize>,
+    },
+    /// Delete a blob from every configured server
+    DeleteBlob { sha256: String },
+    /// Keep a job's result blobs regardless of age
+    Pin { job_id: String },
+    /// Let a pinned job's result blobs expire again
+    Unpin { job_id: String },
+    /// Create an expiring `/watch` link to a completed job's output
+    ShareJob {
+        job_id: String,
+        ttl_secs: Option<u64>,
+    },
+    /// Last lines of a recent job's log
+    GetJobLog {
+        job_id: String,
+        lines: Option<usize>,
+    },
+    /// Copy blobs to configured servers missing them, by hash or by job
+    Replicate {
+        sha256: Option<String>,
+        job_id: Option<String>,
+    },
+    /// Add a pubkey (npub or hex) to the allowlist
+    AllowPubkey { pubkey: String },
+    /// Remove a pubkey from the allowlist
+    DisallowPubkey { pubkey: String },
+    /// Add a pubkey to the blocklist
+    BlockPubkey { pubkey: String },
+    /// Remove a pubkey from the blocklist
+    UnblockPubkey { pubkey: String },
+    /// Get the DVM's private key encrypted with a password (NIP-49)
+    ExportIdentity { password: String },
+    /// Switch to an existing key (nsec, hex, or ncryptsec with its password)
+    ImportIdentity {
+        key: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        password: Option<String>,
+    },
+    /// Switch to a newly generated key
+    RotateIdentity,
+    /// Build the FFmpeg command a job would run, without running it
+    DryRun {
+        input: String,
+        /// Job params, as in the request's `param` tags
+        params: BTreeMap<String, String>,
+    },
+}
+
+/// Filters for the `job_history` command.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct JobHistoryFilter {
+    /// Only jobs started at or after this Unix timestamp
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<u64>,
+    /// Only jobs started at or before this Unix timestamp
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<u64>,
+    /// "processing", "completed", "failed" or "cancelled"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// Requester pubkey (npub or hex)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requester: Option<String>,
+}
+
+fn default_strip_metadata() -> bool {
+    true
 }
 
 fn default_job_history_limit() -> u32 {
@@ -86,6 +242,14 @@ pub struct AdminRequest {
 }
 
 impl AdminRequest {
+    /// String param where `null` or `""` clears the setting (as `Some("")`)
+    fn clearable_str(&self, name: &str) -> Option<String> {
+        match self.params.get(name)? {
+            serde_json::Value::Null => Some(String::new()),
+            v => v.as_str().map(|s| s.trim().to_string()),
+        }
+    }
+
     /// Convert this wire-format request into an internal `AdminCommand`.
     pub fn to_command(&self) -> Result<AdminCommand, String> {
         match self.method.as_str() {
@@ -128,7 +292,28 @@ impl AdminRequest {
                     .and_then(|v| v.as_u64())
                     .map(|v| v as u32)
                     .unwrap_or(20);
-                Ok(AdminCommand::JobHistory { limit })
+                let offset = self.params.get("offset")
+                    .and_then(|v| v.as_u64())
+                    .map(|v| v as u32)
+                    .unwrap_or(0);
+                let filter = JobHistoryFilter {
+                    since: self.params.get("since").and_then(|v| v.as_u64()),
+                    until: self.params.get("until").and_then(|v| v.as_u64()),
+                    status: self.params.get("status")
+                        .and_then(|v| v.as_str())
+                        .map(|s| s.to_string()),
+                    requester: self.params.get("requester")
+                        .and_then(|v| v.as_str())
+                        .map(|s| s.to_string()),
+                };
+                Ok(AdminCommand::JobHistory { limit, offset, filter })
+            }
+            "cancel_job" => {
+                let id = self.params.get("id")
+                    .and_then(|v| v.as_str())
+                    .ok_or("cancel_job requires 'id' param")?
+                    .to_string();
+                Ok(AdminCommand::CancelJob { id })
             }
             "get_dashboard" => {
                 let limit = self.params.get("limit")
@@ -160,6 +345,118 @@ impl AdminRequest {
                     .map(|v| serde_json::from_value(v.clone()))
                     .transpose()
                     .map_err(|e| format!("invalid max_concurrent_jobs: {e}"))?;
+                let external_fetchers_enabled = self.params.get("external_fetchers_enabled")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid external_fetchers_enabled: {e}"))?;
+                let default_hls_resolutions = self.params.get("default_hls_resolutions")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid default_hls_resolutions: {e}"))?;
+                let default_mp4_resolution = self.clearable_str("default_mp4_resolution");
+                let default_codec = self.clearable_str("default_codec");
+                let default_segment_type = self.params.get("default_segment_type")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string());
+                let default_hls_time = self.params.get("default_hls_time")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid default_hls_time: {e}"))?;
+                let default_keyframe_interval = self.params.get("default_keyframe_interval")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid default_keyframe_interval: {e}"))?;
+                let default_max_fps = self.params.get("default_max_fps")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid default_max_fps: {e}"))?;
+                let queue_policy = self.params.get("queue_policy")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string());
+                let bitrate_ladder = self.params.get("bitrate_ladder")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid bitrate_ladder: {e}"))?;
+                let quality_presets = self.params.get("quality_presets")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid quality_presets: {e}"))?;
+                let max_retention_days = self.params.get("max_retention_days")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid max_retention_days: {e}"))?;
+                let retention_sats_per_day = self.params.get("retention_sats_per_day")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid retention_sats_per_day: {e}"))?;
+                let max_input_duration_secs = self.params.get("max_input_duration_secs")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid max_input_duration_secs: {e}"))?;
+                let max_input_size_bytes = self.params.get("max_input_size_bytes")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid max_input_size_bytes: {e}"))?;
+                let max_output_size_ratio = self.params.get("max_output_size_ratio")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid max_output_size_ratio: {e}"))?;
+                let max_output_size_bytes = self.params.get("max_output_size_bytes")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid max_output_size_bytes: {e}"))?;
+                let watermark = self.params.get("watermark")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid watermark: {e}"))?;
+                let normalize_audio = self.params.get("normalize_audio")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid normalize_audio: {e}"))?;
+                let strip_metadata = self.params.get("strip_metadata")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid strip_metadata: {e}"))?;
+                let price_sats_per_minute = self.params.get("price_sats_per_minute")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid price_sats_per_minute: {e}"))?;
+                let price_sats_flat = self.params.get("price_sats_flat")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid price_sats_flat: {e}"))?;
+                let price_sats_per_output_gb = self.params.get("price_sats_per_output_gb")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid price_sats_per_output_gb: {e}"))?;
+                let creator_priority = self.params.get("creator_priority")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid creator_priority: {e}"))?;
+                let creator_discount_percent = self.params.get("creator_discount_percent")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid creator_discount_percent: {e}"))?;
+                let report_threshold = self.params.get("report_threshold")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid report_threshold: {e}"))?;
+                let encrypt_scratch = self.params.get("encrypt_scratch")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid encrypt_scratch: {e}"))?;
+                let allowed_pubkeys = self.params.get("allowed_pubkeys")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid allowed_pubkeys: {e}"))?;
+                let blocked_pubkeys = self.params.get("blocked_pubkeys")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid blocked_pubkeys: {e}"))?;
+                let max_jobs_per_hour = self.params.get("max_jobs_per_hour")
+                    .map(|v| serde_json::from_value(v.clone()))
+                    .transpose()
+                    .map_err(|e| format!("invalid max_jobs_per_hour: {e}"))?;
                 Ok(AdminCommand::SetConfig {
                     relays,
                     blossom_servers,
@@ -167,6 +464,36 @@ impl AdminRequest {
                     name,
                     about,
                     max_concurrent_jobs,
+                    external_fetchers_enabled,
+                    default_hls_resolutions,
+                    default_mp4_resolution,
+                    default_codec,
+                    default_segment_type,
+                    default_hls_time,
+                    default_keyframe_interval,
+                    default_max_fps,
+                    queue_policy,
+                    bitrate_ladder,
+                    quality_presets,
+                    max_retention_days,
+                    retention_sats_per_day,
+                    max_input_duration_secs,
+                    max_input_size_bytes,
+                    max_output_size_ratio,
+                    max_output_size_bytes,
+                    watermark,
+                    normalize_audio,
+                    strip_metadata,
+                    price_sats_per_minute,
+                    price_sats_flat,
+                    price_sats_per_output_gb,
+                    creator_priority,
+                    creator_discount_percent,
+                    report_threshold,
+                    encrypt_scratch,
+                    allowed_pubkeys,
+                    blocked_pubkeys,
+                    max_jobs_per_hour,
                 })
             }
             "self_test" => {
@@ -177,7 +504,127 @@ impl AdminRequest {
                 Ok(AdminCommand::SelfTest { mode })
             }
             "system_info" => Ok(AdminCommand::SystemInfo),
+            "relay_stats" => Ok(AdminCommand::RelayStats),
             "import_env_config" => Ok(AdminCommand::ImportEnvConfig),
+            "list_quarantine" => Ok(AdminCommand::ListQuarantine),
+            "restore_quarantined" => {
+                let id = self.params.get("id")
+                    .and_then(|v| v.as_str())
+                    .ok_or("restore_quarantined requires 'id' param")?
+                    .to_string();
+                Ok(AdminCommand::RestoreQuarantined { id })
+            }
+            "fetch_input" => {
+                let id = self.params.get("job_id")
+                    .and_then(|v| v.as_str())
+                    .ok_or("fetch_input requires 'job_id' param")?
+                    .to_string();
+                Ok(AdminCommand::FetchInput { id })
+            }
+            "list_blobs" => {
+                let server = self.params.get("server")
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string);
+                let limit = self.params.get("limit")
+                    .and_then(|v| v.as_u64())
+                    .map(|l| l as usize);
+                Ok(AdminCommand::ListBlobs { server, limit })
+            }
+            "delete_blob" => {
+                let sha256 = self.params.get("sha256")
+                    .and_then(|v| v.as_str())
+                    .ok_or("delete_blob requires 'sha256' param")?
+                    .to_string();
+                Ok(AdminCommand::DeleteBlob { sha256 })
+            }
+            "pin" | "unpin" => {
+                let job_id = self.params.get("job_id")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| format!("{} requires 'job_id' param", self.method))?
+                    .to_string();
+                Ok(if self.method == "pin" {
+                    AdminCommand::Pin { job_id }
+                } else {
+                    AdminCommand::Unpin { job_id }
+                })
+            }
+            "share_job" => {
+                let job_id = self.params.get("job_id")
+                    .and_then(|v| v.as_str())
+                    .ok_or("share_job requires 'job_id' param")?
+                    .to_string();
+                let ttl_secs = self.params.get("ttl_secs").and_then(|v| v.as_u64());
+                Ok(AdminCommand::ShareJob { job_id, ttl_secs })
+            }
+            "get_job_log" => {
+                let job_id = self.params.get("job_id")
+                    .and_then(|v| v.as_str())
+                    .ok_or("get_job_log requires 'job_id' param")?
+                    .to_string();
+                let lines = self.params.get("lines").and_then(|v| v.as_u64()).map(|n| n as usize);
+                Ok(AdminCommand::GetJobLog { job_id, lines })
+            }
+            "replicate" => {
+                let param = |name: &str| {
+                    self.params.get(name).and_then(|v| v.as_str()).map(str::to_string)
+                };
+                match (param("sha256"), param("job_id")) {
+                    (Some(_), Some(_)) | (None, None) => {
+                        Err("replicate requires either 'sha256' or 'job_id' param".to_string())
+                    }
+                    (sha256, job_id) => Ok(AdminCommand::Replicate { sha256, job_id }),
+                }
+            }
+            "allow_pubkey" | "disallow_pubkey" | "block_pubkey" | "unblock_pubkey" => {
+                let pubkey = self.params.get("pubkey")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| format!("{} requires 'pubkey' param", self.method))?
+                    .to_string();
+                Ok(match self.method.as_str() {
+                    "allow_pubkey" => AdminCommand::AllowPubkey { pubkey },
+                    "disallow_pubkey" => AdminCommand::DisallowPubkey { pubkey },
+                    "block_pubkey" => AdminCommand::BlockPubkey { pubkey },
+                    _ => AdminCommand::UnblockPubkey { pubkey },
+                })
+            }
+            "export_identity" => {
+                let password = self.params.get("password")
+                    .and_then(|v| v.as_str())
+                    .ok_or("export_identity requires 'password' param")?
+                    .to_string();
+                Ok(AdminCommand::ExportIdentity { password })
+            }
+            "import_identity" => {
+                let key = self.params.get("key")
+                    .and_then(|v| v.as_str())
+                    .ok_or("import_identity requires 'key' param")?
+                    .to_string();
+                let password = self.params.get("password")
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string);
+                Ok(AdminCommand::ImportIdentity { key, password })
+            }
+            "rotate_identity" => Ok(AdminCommand::RotateIdentity),
+            "dry_run" => {
+                let input = self.params.get("input")
+                    .and_then(|v| v.as_str())
+                    .ok_or("dry_run requires 'input' param")?
+                    .to_string();
+                // Every other param is a job param
+                let mut params = BTreeMap::new();
+                for (name, value) in self.params.as_object().into_iter().flatten() {
+                    if name == "input" {
+                        continue;
+                    }
+                    let value = match value {
+                        serde_json::Value::String(s) => s.clone(),
+                        serde_json::Value::Number(_) | serde_json::Value::Bool(_) => value.to_string(),
+                        _ => return Err(format!("invalid {name}: expected a string")),
+                    };
+                    params.insert(name.clone(), value);
+                }
+                Ok(AdminCommand::DryRun { input, params })
+            }
             _ => Err(format!("unknown method: {}", self.method)),
         }
     }
@@ -305,6 +752,32 @@ pub enum ResponseData {
     SelfTest(SelfTestSuiteResponse),
     /// System information
     SystemInfo(SystemInfoResponse),
+    /// A single job
+    Job(JobResponse),
+    /// Per-relay statistics
+    RelayStats(RelayStatsResponse),
+    /// Quarantined results
+    Quarantine(QuarantineResponse),
+    /// A single quarantined result
+    QuarantineEntry(QuarantineEntryResponse),
+    /// A job input downloaded for analysis
+    FetchedInput(FetchedInputResponse),
+    /// Blob replication results
+    Replicate(ReplicateResponse),
+    /// Pin state of a job's result blobs
+    Pin(PinResponse),
+    /// Expiring preview link to a job's output
+    ShareJob(ShareJobResponse),
+    /// Tail of a job's log
+    JobLog(JobLogResponse),
+    /// Blobs stored on a server
+    Blobs(BlobsResponse),
+    /// Outcome of deleting a blob
+    DeleteBlob(DeleteBlobResponse),
+    /// The DVM's identity after an export, import or rotation
+    Identity(IdentityResponse),
+    /// FFmpeg command a job would run
+    DryRun(DryRunResponse),
 }
 
 /// Dashboard response data (status + config + jobs combined).
@@ -316,6 +789,12 @@ pub struct DashboardResponse {
     pub config: ConfigData,
     /// Recent jobs
     pub jobs: Vec<JobInfo>,
+    /// Per-relay statistics
+    #[serde(default)]
+    pub relays: Vec<RelayStatsEntry>,
+    /// Latest health probe result per Blossom server
+    #[serde(default)]
+    pub blossom_health: Vec<ServerHealth>,
 }
 
 /// Configuration response data.
@@ -344,6 +823,96 @@ pub struct ConfigData {
     pub paused: bool,
     /// Maximum number of concurrent video transformations
     pub max_concurrent_jobs: u32,
+    /// Whether external platform fetchers (e.g. yt-dlp) may be used
+    #[serde(default)]
+    pub external_fetchers_enabled: bool,
+    /// HLS ladder for requests that don't list resolutions (empty = all)
+    #[serde(default)]
+    pub default_hls_resolutions: Vec<String>,
+    /// MP4 resolution for requests that don't pick one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_mp4_resolution: Option<String>,
+    /// Codec for requests that don't pick one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_codec: Option<String>,
+    /// HLS segment container for requests that don't pick one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_segment_type: Option<String>,
+    /// HLS segment duration in seconds for requests that don't set one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_hls_time: Option<u32>,
+    /// Seconds between forced keyframes for requests that don't set one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_keyframe_interval: Option<f64>,
+    /// Frame rate cap for requests that don't set one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_max_fps: Option<u32>,
+    /// Job scheduling policy ("round_robin" or "fifo")
+    #[serde(default)]
+    pub queue_policy: String,
+    /// Custom HLS bitrate ladder (empty = built-in)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bitrate_ladder: Vec<LadderRung>,
+    /// CRF overrides per resolution and codec (empty = built-in values)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quality_presets: Vec<QualityPreset>,
+    /// Longest retention requesters can pay for (0 = not offered)
+    #[serde(default)]
+    pub max_retention_days: u32,
+    /// Price per day of retention beyond `blob_expiration_days`
+    #[serde(default)]
+    pub retention_sats_per_day: u64,
+    /// Longest input accepted in seconds (unset = environment default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_input_duration_secs: Option<u64>,
+    /// Largest input accepted in bytes (unset = environment default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_input_size_bytes: Option<u64>,
+    /// Largest output as a multiple of the input (unset = environment default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_size_ratio: Option<f64>,
+    /// Largest combined output in bytes (unset = environment default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_size_bytes: Option<u64>,
+    /// Watermark for jobs that don't request their own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<Watermark>,
+    /// Whether audio loudness is normalized by default
+    #[serde(default)]
+    pub normalize_audio: bool,
+    /// Whether source metadata is stripped from outputs by default
+    #[serde(default = "default_strip_metadata")]
+    pub strip_metadata: bool,
+    /// Price per started minute of (clipped) input video
+    #[serde(default)]
+    pub price_sats_per_minute: u64,
+    /// Price charged once per job
+    #[serde(default)]
+    pub price_sats_flat: u64,
+    /// Price per started GB of estimated output
+    #[serde(default)]
+    pub price_sats_per_output_gb: u64,
+    /// Whether creators re-transcoding their own videos are started first
+    #[serde(default)]
+    pub creator_priority: bool,
+    /// Discount for creators re-transcoding their own videos, in percent
+    #[serde(default)]
+    pub creator_discount_percent: u8,
+    /// Distinct abuse reports that quarantine a result (0 = never)
+    #[serde(default)]
+    pub report_threshold: u32,
+    /// Whether inputs kept in the temp dir are encrypted
+    #[serde(default)]
+    pub encrypt_scratch: bool,
+    /// Pubkeys (hex) served exclusively when non-empty
+    #[serde(default)]
+    pub allowed_pubkeys: Vec<String>,
+    /// Pubkeys (hex) whose job requests are ignored
+    #[serde(default)]
+    pub blocked_pubkeys: Vec<String>,
+    /// Jobs a requester may start per hour (0 = unlimited)
+    #[serde(default)]
+    pub max_jobs_per_hour: u32,
 }
 
 /// Status response data.
@@ -353,10 +922,22 @@ pub struct StatusResponse {
     pub paused: bool,
     /// Number of jobs currently active
     pub jobs_active: u32,
+    /// Number of jobs waiting for a slot
+    #[serde(default)]
+    pub jobs_queued: u32,
+    /// Requests received but not yet queued
+    #[serde(default)]
+    pub channel_depth: u32,
+    /// Waiting jobs beyond which new requests are turned away as busy
+    #[serde(default)]
+    pub queue_capacity: u32,
     /// Number of jobs completed successfully
     pub jobs_completed: u32,
     /// Number of jobs that failed
     pub jobs_failed: u32,
+    /// Failed FFmpeg runs per cause (`corrupt_input`, `hw_encoder_init`, ...)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ffmpeg_failures: BTreeMap<String, u32>,
     /// Uptime in seconds
     pub uptime_secs: u64,
     /// Hardware acceleration type in use
@@ -370,6 +951,141 @@ pub struct StatusResponse {
 pub struct JobHistoryResponse {
     /// List of recent jobs
     pub jobs: Vec<JobInfo>,
+    /// Number of jobs matching the filter (across all pages)
+    #[serde(default)]
+    pub total: u32,
+    /// Offset of the first job in `jobs`
+    #[serde(default)]
+    pub offset: u32,
+}
+
+/// Relay statistics response data.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RelayStatsResponse {
+    pub relays: Vec<RelayStatsEntry>,
+}
+
+/// Quarantine list response data.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuarantineResponse {
+    /// Quarantined and restored results, newest first
+    pub quarantine: Vec<QuarantineEntry>,
+}
+
+/// Single quarantined result response data.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuarantineEntryResponse {
+    pub entry: QuarantineEntry,
+}
+
+/// Downloaded job input response data.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FetchedInputResponse {
+    pub job_id: String,
+    pub input_url: String,
+    /// Where the input was written on the DVM host
+    pub path: String,
+    pub size_bytes: u64,
+    pub sha256: String,
+}
+
+/// Blob listing response data.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BlobsResponse {
+    pub server: String,
+    /// Newest first, at most `limit`
+    pub blobs: Vec<BlobDescriptor>,
+    /// Number of blobs the DVM has on the server
+    pub total: usize,
+    pub total_size_bytes: u64,
+}
+
+/// Blob deletion response data.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeleteBlobResponse {
+    pub sha256: String,
+    /// Servers the blob was deleted from
+    pub deleted: Vec<String>,
+    /// Servers the delete failed on, as "server: error"
+    pub failed: Vec<String>,
+}
+
+/// Identity response data.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IdentityResponse {
+    /// Hex pubkey the DVM uses (after a restart, for import and rotation)
+    pub pubkey: String,
+    pub npub: String,
+    /// Encrypted private key (`export_identity` only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ncryptsec: Option<String>,
+    /// Pubkey the DVM used before an import or rotation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_pubkey: Option<String>,
+    /// Where the previous key was kept on the DVM host
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup_file: Option<String>,
+    /// Whether the DVM is restarting to switch to the key
+    pub restarting: bool,
+}
+
+/// Dry run response data.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DryRunResponse {
+    /// "mp4" or "hls"
+    pub mode: String,
+    /// Hardware acceleration the command uses
+    pub hwaccel: String,
+    /// Program and arguments
+    pub argv: Vec<String>,
+    /// The same as a copy-pasteable shell command
+    pub command: String,
+    /// Where the job could end up with a different command
+    pub notes: Vec<String>,
+}
+
+/// Pin response data.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PinResponse {
+    pub job_id: String,
+    pub pinned: bool,
+    /// Number of result blobs the pin covers
+    pub blobs: usize,
+}
+
+/// Share link response data.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ShareJobResponse {
+    pub job_id: String,
+    /// Path and query of the `/watch` page on the DVM's web server
+    pub path: String,
+    /// Full link when `PUBLIC_URL` is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Unix timestamp the link stops working
+    pub expires_at: u64,
+}
+
+/// Job log response data.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JobLogResponse {
+    pub job_id: String,
+    /// Oldest first
+    pub lines: Vec<JobLogLine>,
+    /// Earlier lines not included
+    pub omitted: u64,
+}
+
+/// Blob replication response data.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReplicateResponse {
+    pub blobs: Vec<Replication>,
+}
+
+/// Single job response data.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JobResponse {
+    pub job: JobInfo,
 }
 
 /// Information about a single job.
@@ -381,6 +1097,9 @@ pub struct JobInfo {
     pub status: String,
     /// Input video URL
     pub input_url: String,
+    /// Requester pubkey (hex)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub requester: String,
     /// Output HLS URL (if completed)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub output_url: Option<String>,
@@ -392,6 +1111,18 @@ pub struct JobInfo {
     /// Processing duration in seconds
     #[serde(skip_serializing_if = "Option::is_none")]
     pub duration_secs: Option<u64>,
+    /// Host resources when the job failed (failed jobs only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ResourceSnapshot>,
+    /// Whether the result was taken down after abuse reports
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub quarantined: bool,
+    /// Whether the job was encoded in software after the hardware encoder failed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub software_fallback: bool,
+    /// Cashu token redeemed to pay for the job
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redemption: Option<Redemption>,
 }
 
 /// Self-test suite response (multi-clip).
@@ -454,6 +1185,9 @@ pub struct SystemInfoResponse {
     pub ffmpeg: FfmpegInfo,
     /// Temp directory path
     pub temp_dir: String,
+    /// Latest health probe result per Blossom server
+    #[serde(default)]
+    pub blossom_health: Vec<ServerHealth>,
 }
 
 /// Hardware encoder info.
@@ -554,12 +1288,191 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_request_cancel_job() {
+        let json = r#"{"id":"req-9","method":"cancel_job","params":{"id":"abc123"}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(cmd, AdminCommand::CancelJob { id: "abc123".to_string() });
+
+        let json = r#"{"id":"req-10","method":"cancel_job","params":{}}"#;
+        assert!(parse_request(json).unwrap().to_command().is_err());
+    }
+
+    #[test]
+    fn test_parse_request_relay_stats() {
+        let json = r#"{"id":"req-11","method":"relay_stats"}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(cmd, AdminCommand::RelayStats);
+    }
+
+    #[test]
+    fn test_parse_request_quarantine() {
+        let json = r#"{"id":"req-12","method":"list_quarantine"}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(cmd, AdminCommand::ListQuarantine);
+
+        let json = r#"{"id":"req-13","method":"restore_quarantined","params":{"id":"abc123"}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(cmd, AdminCommand::RestoreQuarantined { id: "abc123".to_string() });
+
+        let json = r#"{"id":"req-14","method":"restore_quarantined","params":{}}"#;
+        assert!(parse_request(json).unwrap().to_command().is_err());
+    }
+
+    #[test]
+    fn test_parse_request_fetch_input() {
+        let json = r#"{"id":"req-30","method":"fetch_input","params":{"job_id":"abc123"}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(cmd, AdminCommand::FetchInput { id: "abc123".to_string() });
+
+        let json = r#"{"id":"req-31","method":"fetch_input","params":{"id":"abc123"}}"#;
+        assert!(parse_request(json).unwrap().to_command().is_err());
+    }
+
+    #[test]
+    fn test_parse_request_blobs() {
+        let json = r#"{"id":"req-39","method":"list_blobs","params":{"limit":50}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(cmd, AdminCommand::ListBlobs { server: None, limit: Some(50) });
+
+        let json = r#"{"id":"req-40","method":"list_blobs","params":{"server":"https://a.example.com"}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(
+            cmd,
+            AdminCommand::ListBlobs { server: Some("https://a.example.com".to_string()), limit: None }
+        );
+
+        let json = r#"{"id":"req-41","method":"delete_blob","params":{"sha256":"abc"}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(cmd, AdminCommand::DeleteBlob { sha256: "abc".to_string() });
+
+        let json = r#"{"id":"req-42","method":"delete_blob","params":{}}"#;
+        assert!(parse_request(json).unwrap().to_command().is_err());
+    }
+
+    #[test]
+    fn test_parse_request_pin() {
+        let json = r#"{"id":"req-36","method":"pin","params":{"job_id":"abc"}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(cmd, AdminCommand::Pin { job_id: "abc".to_string() });
+
+        let json = r#"{"id":"req-37","method":"unpin","params":{"job_id":"abc"}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(cmd, AdminCommand::Unpin { job_id: "abc".to_string() });
+
+        let json = r#"{"id":"req-38","method":"pin","params":{}}"#;
+        assert!(parse_request(json).unwrap().to_command().is_err());
+    }
+
+    #[test]
+    fn test_parse_request_share_job() {
+        let json = r#"{"id":"req-43","method":"share_job","params":{"job_id":"abc","ttl_secs":3600}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(cmd, AdminCommand::ShareJob { job_id: "abc".to_string(), ttl_secs: Some(3600) });
+
+        let json = r#"{"id":"req-44","method":"share_job","params":{}}"#;
+        assert!(parse_request(json).unwrap().to_command().is_err());
+    }
+
+    #[test]
+    fn test_parse_request_get_job_log() {
+        let json = r#"{"id":"req-45","method":"get_job_log","params":{"job_id":"abc","lines":50}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(cmd, AdminCommand::GetJobLog { job_id: "abc".to_string(), lines: Some(50) });
+
+        let json = r#"{"id":"req-46","method":"get_job_log","params":{}}"#;
+        assert!(parse_request(json).unwrap().to_command().is_err());
+    }
+
+    #[test]
+    fn test_parse_request_replicate() {
+        let json = r#"{"id":"req-32","method":"replicate","params":{"sha256":"abc"}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(cmd, AdminCommand::Replicate { sha256: Some("abc".to_string()), job_id: None });
+
+        let json = r#"{"id":"req-33","method":"replicate","params":{"job_id":"def"}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(cmd, AdminCommand::Replicate { sha256: None, job_id: Some("def".to_string()) });
+
+        let json = r#"{"id":"req-34","method":"replicate","params":{}}"#;
+        assert!(parse_request(json).unwrap().to_command().is_err());
+        let json = r#"{"id":"req-35","method":"replicate","params":{"sha256":"abc","job_id":"def"}}"#;
+        assert!(parse_request(json).unwrap().to_command().is_err());
+    }
+
+    #[test]
+    fn test_parse_request_pubkey_lists() {
+        let json = r#"{"id":"req-15","method":"block_pubkey","params":{"pubkey":"npub1abc"}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(cmd, AdminCommand::BlockPubkey { pubkey: "npub1abc".to_string() });
+
+        let json = r#"{"id":"req-16","method":"disallow_pubkey","params":{"pubkey":"abc"}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(cmd, AdminCommand::DisallowPubkey { pubkey: "abc".to_string() });
+
+        let json = r#"{"id":"req-17","method":"allow_pubkey","params":{}}"#;
+        assert!(parse_request(json).unwrap().to_command().is_err());
+    }
+
+    #[test]
+    fn test_parse_request_identity() {
+        let json = r#"{"id":"req-18","method":"export_identity","params":{"password":"hunter22"}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(cmd, AdminCommand::ExportIdentity { password: "hunter22".to_string() });
+
+        let json = r#"{"id":"req-19","method":"import_identity","params":{"key":"ncryptsec1abc","password":"hunter22"}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(
+            cmd,
+            AdminCommand::ImportIdentity {
+                key: "ncryptsec1abc".to_string(),
+                password: Some("hunter22".to_string()),
+            }
+        );
+
+        let json = r#"{"id":"req-20","method":"rotate_identity"}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(cmd, AdminCommand::RotateIdentity);
+
+        let json = r#"{"id":"req-21","method":"import_identity","params":{}}"#;
+        assert!(parse_request(json).unwrap().to_command().is_err());
+    }
+
+    #[test]
+    fn test_parse_request_dry_run() {
+        let json = r#"{"id":"req-22","method":"dry_run","params":{"input":"https://example.com/v.mp4","mode":"hls","max_fps":30,"encryption":false}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        assert_eq!(
+            cmd,
+            AdminCommand::DryRun {
+                input: "https://example.com/v.mp4".to_string(),
+                params: BTreeMap::from([
+                    ("encryption".to_string(), "false".to_string()),
+                    ("max_fps".to_string(), "30".to_string()),
+                    ("mode".to_string(), "hls".to_string()),
+                ]),
+            }
+        );
+
+        let json = r#"{"id":"req-23","method":"dry_run","params":{"mode":"hls"}}"#;
+        assert!(parse_request(json).unwrap().to_command().is_err());
+        let json = r#"{"id":"req-24","method":"dry_run","params":{"input":"https://example.com/v.mp4","mode":["hls"]}}"#;
+        assert!(parse_request(json).unwrap().to_command().is_err());
+    }
+
     #[test]
     fn test_parse_request_job_history_default() {
         let json = r#"{"id":"req-5","method":"job_history"}"#;
         let req = parse_request(json).unwrap();
         let cmd = req.to_command().unwrap();
-        assert_eq!(cmd, AdminCommand::JobHistory { limit: 20 });
+        assert_eq!(
+            cmd,
+            AdminCommand::JobHistory {
+                limit: 20,
+                offset: 0,
+                filter: JobHistoryFilter::default()
+            }
+        );
     }
 
     #[test]
@@ -567,7 +1480,34 @@ mod tests {
         let json = r#"{"id":"req-6","method":"job_history","params":{"limit":50}}"#;
         let req = parse_request(json).unwrap();
         let cmd = req.to_command().unwrap();
-        assert_eq!(cmd, AdminCommand::JobHistory { limit: 50 });
+        assert_eq!(
+            cmd,
+            AdminCommand::JobHistory {
+                limit: 50,
+                offset: 0,
+                filter: JobHistoryFilter::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_request_job_history_filtered() {
+        let json = r#"{"id":"req-6","method":"job_history","params":{"limit":10,"offset":30,"since":1700000000,"status":"failed","requester":"npub1abc"}}"#;
+        let req = parse_request(json).unwrap();
+        let cmd = req.to_command().unwrap();
+        assert_eq!(
+            cmd,
+            AdminCommand::JobHistory {
+                limit: 10,
+                offset: 30,
+                filter: JobHistoryFilter {
+                    since: Some(1700000000),
+                    until: None,
+                    status: Some("failed".to_string()),
+                    requester: Some("npub1abc".to_string()),
+                }
+            }
+        );
     }
 
     #[test]
@@ -584,10 +1524,59 @@ mod tests {
                 name: Some("Updated".to_string()),
                 about: None,
                 max_concurrent_jobs: None,
+                external_fetchers_enabled: None,
+                default_hls_resolutions: None,
+                default_mp4_resolution: None,
+                default_codec: None,
+                default_segment_type: None,
+                default_hls_time: None,
+                default_keyframe_interval: None,
+                default_max_fps: None,
+                queue_policy: None,
+                bitrate_ladder: None,
+                quality_presets: None,
+                max_retention_days: None,
+                retention_sats_per_day: None,
+                max_input_duration_secs: None,
+                max_input_size_bytes: None,
+                max_output_size_ratio: None,
+                max_output_size_bytes: None,
+                watermark: None,
+                normalize_audio: None,
+                strip_metadata: None,
+                price_sats_per_minute: None,
+                price_sats_flat: None,
+                price_sats_per_output_gb: None,
+                creator_priority: None,
+                creator_discount_percent: None,
+                report_threshold: None,
+                encrypt_scratch: None,
+                allowed_pubkeys: None,
+                blocked_pubkeys: None,
+                max_jobs_per_hour: None,
             }
         );
     }
 
+    #[test]
+    fn test_parse_request_set_config_clears_defaults() {
+        let json = r#"{"id":"req-9","method":"set_config","params":{"default_mp4_resolution":null,"default_codec":""}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        let AdminCommand::SetConfig { default_mp4_resolution, default_codec, .. } = cmd else {
+            panic!("expected SetConfig");
+        };
+        assert_eq!(default_mp4_resolution.as_deref(), Some(""));
+        assert_eq!(default_codec.as_deref(), Some(""));
+
+        let json = r#"{"id":"req-10","method":"set_config","params":{"default_codec":"h265"}}"#;
+        let cmd = parse_request(json).unwrap().to_command().unwrap();
+        let AdminCommand::SetConfig { default_mp4_resolution, default_codec, .. } = cmd else {
+            panic!("expected SetConfig");
+        };
+        assert_eq!(default_mp4_resolution, None);
+        assert_eq!(default_codec.as_deref(), Some("h265"));
+    }
+
     #[test]
     fn test_parse_request_unknown_method() {
         let json = r#"{"id":"req-8","method":"fly_to_moon"}"#;
@@ -617,6 +1606,36 @@ mod tests {
             about: None,
             paused: false,
             max_concurrent_jobs: 1,
+            external_fetchers_enabled: false,
+            default_hls_resolutions: vec![],
+            default_mp4_resolution: None,
+            default_codec: None,
+            default_segment_type: None,
+            default_hls_time: None,
+            default_keyframe_interval: None,
+            default_max_fps: None,
+            queue_policy: "round_robin".to_string(),
+            bitrate_ladder: vec![],
+            quality_presets: vec![],
+            max_retention_days: 0,
+            retention_sats_per_day: 0,
+            max_input_duration_secs: None,
+            max_input_size_bytes: None,
+            max_output_size_ratio: None,
+            max_output_size_bytes: None,
+            watermark: None,
+            normalize_audio: false,
+            strip_metadata: true,
+            price_sats_per_minute: 0,
+            price_sats_flat: 0,
+            price_sats_per_output_gb: 0,
+            creator_priority: false,
+            creator_discount_percent: 0,
+            report_threshold: 0,
+            encrypt_scratch: false,
+            allowed_pubkeys: vec![],
+            blocked_pubkeys: vec![],
+            max_jobs_per_hour: 0,
         };
         let response = AdminResponse::ok_with_data(ResponseData::Config(ConfigResponse {
             config: config_data,
@@ -4,17 +4,75 @@
 //! validates authorization, and updates DVM state.
 
 use crate::admin::commands::*;
+use crate::blossom::BlossomClient;
+use crate::bootstrap::get_bootstrap_relays;
 use crate::config::Config;
-use crate::dvm_state::SharedDvmState;
-use crate::remote_config::save_config;
+use crate::dvm::dry_run;
+use crate::dvm::events::{Codec, Resolution, DVM_VIDEO_TRANSFORM_RESULT_KIND};
+use crate::dvm::moderation;
+use crate::dvm::queue::QueuePolicy;
+use crate::dvm_state::{ConfigWatch, JobHistoryQuery, JobRecord, JobStatus, SharedDvmState};
+use crate::identity;
+use crate::paths::Paths;
+use crate::remote_config::{save_config, RemoteConfig};
+use crate::video::ffmpeg::shell_command;
+use crate::video::fps_cap::FpsCap;
 use crate::video::hwaccel::HwAccel;
+use crate::video::segmenting::Segmenting;
+use crate::video::transform::{
+    validate_ladder, validate_quality_presets, LadderRung, QualityPreset, SegmentType,
+};
+use crate::video::watermark::Watermark;
+use crate::video::VideoProcessor;
+use crate::web::watch;
 use nostr_sdk::prelude::*;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::process::Command as TokioCommand;
 use tokio::sync::Notify;
 use tracing::info;
 
+/// How long new relays get to connect before a relay-set switch is abandoned
+const RELAY_SWITCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Blobs returned by `list_blobs` without a `limit`
+const DEFAULT_BLOB_LIST_LIMIT: usize = 100;
+
+/// Most blobs a single `list_blobs` response carries
+const MAX_BLOB_LIST_LIMIT: usize = 1000;
+
+/// Lifetime of `share_job` links without a `ttl_secs`
+const DEFAULT_SHARE_TTL_SECS: u64 = 24 * 3600;
+
+/// Longest lifetime a `share_job` link may have
+const MAX_SHARE_TTL_SECS: u64 = 30 * 24 * 3600;
+
+/// Log lines returned by `get_job_log` without a `lines`
+const DEFAULT_JOB_LOG_LINES: usize = 100;
+
+/// Time the response to an identity switch gets to go out before the restart
+const IDENTITY_RESTART_DELAY: Duration = Duration::from_secs(3);
+
+/// Relay URL without a trailing slash, for comparing user input with pool URLs.
+fn normalize_relay(url: &str) -> String {
+    url.trim_end_matches('/').to_string()
+}
+
+/// Relays in `previous` that are neither in `desired` nor in `keep`.
+fn relays_to_drop(previous: &[String], desired: &[String], keep: &[String]) -> Vec<String> {
+    let retained: HashSet<String> = desired
+        .iter()
+        .chain(keep)
+        .map(|r| normalize_relay(r))
+        .collect();
+    previous
+        .iter()
+        .filter(|r| !retained.contains(&normalize_relay(r)))
+        .cloned()
+        .collect()
+}
+
 /// Handles admin commands for the DVM.
 pub struct AdminHandler {
     /// Shared DVM state
@@ -23,8 +81,12 @@ pub struct AdminHandler {
     client: Client,
     /// Runtime configuration (ffmpeg paths, temp dir, etc.)
     config: Arc<Config>,
-    /// Notify the announcement publisher when config changes
-    config_notify: Arc<Notify>,
+    /// Tells other subsystems (e.g. the announcement publisher) when config changes
+    config_watch: ConfigWatch,
+    /// Restarts the DVM, e.g. to switch to a new identity
+    restart: Arc<Notify>,
+    /// The job pipeline's video processor, for dry runs
+    processor: Option<Arc<VideoProcessor>>,
 }
 
 impl AdminHandler {
@@ -33,44 +95,114 @@ impl AdminHandler {
         state: SharedDvmState,
         client: Client,
         config: Arc<Config>,
-        config_notify: Arc<Notify>,
+        config_watch: ConfigWatch,
     ) -> Self {
         Self {
             state,
             client,
             config,
-            config_notify,
+            config_watch,
+            restart: Arc::new(Notify::new()),
+            processor: None,
         }
     }
 
-    /// Ensures the client's relay pool includes all relays from the config.
+    /// Notify `restart` when the DVM has to restart (see `run_daemon`)
+    pub fn with_restart(mut self, restart: Arc<Notify>) -> Self {
+        self.restart = restart;
+        self
+    }
+
+    /// Plan dry runs with `processor`, so they get the jobs' hardware acceleration
+    pub fn with_processor(mut self, processor: Arc<VideoProcessor>) -> Self {
+        self.processor = Some(processor);
+        self
+    }
+
+    /// Switches the client's relay pool over to the configured relay set.
     ///
-    /// Adds any relays that aren't already connected. Bootstrap relays remain
-    /// connected from startup, so config is always findable on restart.
-    async fn sync_relays(&self, relays: &[String]) {
+    /// New relays are added first (they inherit the pool's subscriptions, so
+    /// the DVM and admin REQs follow them), and relays dropped from the config
+    /// are only disconnected once at least one relay of the new set is
+    /// connected and carrying those subscriptions. If none of them comes up in
+    /// time the newly added relays are removed again and the old set stays in
+    /// place. Bootstrap relays are never dropped, so config is always findable
+    /// on restart.
+    async fn sync_relays(&self, previous: &[String], relays: &[String]) -> Result<(), String> {
         let connected: HashSet<String> = self
             .client
             .relays()
             .await
             .keys()
-            .map(|url| url.as_str().trim_end_matches('/').to_string())
+            .map(|url| normalize_relay(url.as_str()))
             .collect();
 
-        let mut added = false;
+        let mut added = Vec::new();
         for relay in relays {
-            let normalized = relay.trim_end_matches('/').to_string();
-            if !connected.contains(&normalized) {
+            if !connected.contains(&normalize_relay(relay)) {
                 if let Err(e) = self.client.add_relay(relay.clone()).await {
                     tracing::warn!("Failed to add relay {}: {}", relay, e);
                 } else {
-                    added = true;
+                    added.push(relay.clone());
                 }
             }
         }
 
-        if added {
+        let bootstrap: Vec<String> = get_bootstrap_relays()
+            .iter()
+            .map(|url| url.to_string())
+            .collect();
+        let to_drop = relays_to_drop(previous, relays, &bootstrap);
+
+        if added.is_empty() && to_drop.is_empty() {
+            return Ok(());
+        }
+
+        if !added.is_empty() {
             self.client.connect().await;
         }
+
+        if !self.wait_for_relay_set(relays).await {
+            for relay in &added {
+                if let Err(e) = self.client.remove_relay(relay.as_str()).await {
+                    tracing::warn!("Failed to remove relay {}: {}", relay, e);
+                }
+            }
+            return Err("None of the new relays could be reached, keeping the current relays".to_string());
+        }
+
+        for relay in &to_drop {
+            if !connected.contains(&normalize_relay(relay)) {
+                continue;
+            }
+            match self.client.remove_relay(relay.as_str()).await {
+                Ok(()) => info!(relay = %relay, "Disconnected relay removed from config"),
+                Err(e) => tracing::warn!("Failed to remove relay {}: {}", relay, e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Waits until a relay of the given set is connected and subscribed.
+    async fn wait_for_relay_set(&self, relays: &[String]) -> bool {
+        let wanted: HashSet<String> = relays.iter().map(|r| normalize_relay(r)).collect();
+        let deadline = tokio::time::Instant::now() + RELAY_SWITCH_TIMEOUT;
+
+        loop {
+            for (url, relay) in self.client.relays().await {
+                if wanted.contains(&normalize_relay(url.as_str()))
+                    && relay.is_connected().await
+                    && !relay.subscriptions().await.is_empty()
+                {
+                    return true;
+                }
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
     }
 
     /// Handles an admin command from a sender.
@@ -103,7 +235,12 @@ impl AdminHandler {
             AdminCommand::Pause => self.handle_pause().await,
             AdminCommand::Resume => self.handle_resume().await,
             AdminCommand::Status => self.handle_status().await,
-            AdminCommand::JobHistory { limit } => self.handle_job_history(limit).await,
+            AdminCommand::JobHistory {
+                limit,
+                offset,
+                filter,
+            } => self.handle_job_history(limit, offset, filter).await,
+            AdminCommand::CancelJob { id } => self.handle_cancel_job(&id).await,
             AdminCommand::GetDashboard { limit } => self.handle_get_dashboard(limit).await,
             AdminCommand::SetConfig {
                 relays,
@@ -112,13 +249,111 @@ impl AdminHandler {
                 name,
                 about,
                 max_concurrent_jobs,
+                external_fetchers_enabled,
+                default_hls_resolutions,
+                default_mp4_resolution,
+                default_codec,
+                default_segment_type,
+                default_hls_time,
+                default_keyframe_interval,
+                default_max_fps,
+                queue_policy,
+                bitrate_ladder,
+                quality_presets,
+                max_retention_days,
+                retention_sats_per_day,
+                max_input_duration_secs,
+                max_input_size_bytes,
+                max_output_size_ratio,
+                max_output_size_bytes,
+                watermark,
+                normalize_audio,
+                strip_metadata,
+                price_sats_per_minute,
+                price_sats_flat,
+                price_sats_per_output_gb,
+                creator_priority,
+                creator_discount_percent,
+                report_threshold,
+                encrypt_scratch,
+                allowed_pubkeys,
+                blocked_pubkeys,
+                max_jobs_per_hour,
             } => {
-                self.handle_set_config(relays, blossom_servers, blob_expiration_days, name, about, max_concurrent_jobs)
-                    .await
+                self.handle_set_config(
+                    relays,
+                    blossom_servers,
+                    blob_expiration_days,
+                    name,
+                    about,
+                    max_concurrent_jobs,
+                    external_fetchers_enabled,
+                    default_hls_resolutions,
+                    default_mp4_resolution,
+                    default_codec,
+                    default_segment_type,
+                    default_hls_time,
+                    default_keyframe_interval,
+                    default_max_fps,
+                    queue_policy,
+                    bitrate_ladder,
+                    quality_presets,
+                    max_retention_days,
+                    retention_sats_per_day,
+                    max_input_duration_secs,
+                    max_input_size_bytes,
+                    max_output_size_ratio,
+                    max_output_size_bytes,
+                    watermark,
+                    normalize_audio,
+                    strip_metadata,
+                    price_sats_per_minute,
+                    price_sats_flat,
+                    price_sats_per_output_gb,
+                    creator_priority,
+                    creator_discount_percent,
+                    report_threshold,
+                    encrypt_scratch,
+                    allowed_pubkeys,
+                    blocked_pubkeys,
+                    max_jobs_per_hour,
+                )
+                .await
             }
             AdminCommand::SelfTest { mode } => self.handle_self_test(&mode).await,
             AdminCommand::SystemInfo => self.handle_system_info().await,
+            AdminCommand::RelayStats => self.handle_relay_stats().await,
             AdminCommand::ImportEnvConfig => self.handle_import_env_config().await,
+            AdminCommand::ListQuarantine => self.handle_list_quarantine().await,
+            AdminCommand::RestoreQuarantined { id } => self.handle_restore_quarantined(&id).await,
+            AdminCommand::FetchInput { id } => self.handle_fetch_input(&id).await,
+            AdminCommand::ListBlobs { server, limit } => self.handle_list_blobs(server, limit).await,
+            AdminCommand::DeleteBlob { sha256 } => self.handle_delete_blob(&sha256).await,
+            AdminCommand::Pin { job_id } => self.handle_pin(&job_id, true).await,
+            AdminCommand::Unpin { job_id } => self.handle_pin(&job_id, false).await,
+            AdminCommand::ShareJob { job_id, ttl_secs } => self.handle_share_job(&job_id, ttl_secs).await,
+            AdminCommand::GetJobLog { job_id, lines } => self.handle_get_job_log(&job_id, lines).await,
+            AdminCommand::Replicate { sha256, job_id } => {
+                self.handle_replicate(sha256, job_id).await
+            }
+            AdminCommand::AllowPubkey { pubkey } => {
+                self.handle_pubkey_list(&pubkey, |c| &mut c.allowed_pubkeys, true).await
+            }
+            AdminCommand::DisallowPubkey { pubkey } => {
+                self.handle_pubkey_list(&pubkey, |c| &mut c.allowed_pubkeys, false).await
+            }
+            AdminCommand::BlockPubkey { pubkey } => {
+                self.handle_pubkey_list(&pubkey, |c| &mut c.blocked_pubkeys, true).await
+            }
+            AdminCommand::UnblockPubkey { pubkey } => {
+                self.handle_pubkey_list(&pubkey, |c| &mut c.blocked_pubkeys, false).await
+            }
+            AdminCommand::ExportIdentity { password } => self.handle_export_identity(password).await,
+            AdminCommand::ImportIdentity { key, password } => {
+                self.handle_import_identity(key, password).await
+            }
+            AdminCommand::RotateIdentity => self.switch_identity(Keys::generate()).await,
+            AdminCommand::DryRun { input, params } => self.handle_dry_run(input, params).await,
         }
     }
 
@@ -134,6 +369,36 @@ impl AdminHandler {
             about: state.config.about.clone(),
             paused: state.config.paused,
             max_concurrent_jobs: state.config.max_concurrent_jobs,
+            external_fetchers_enabled: state.config.external_fetchers_enabled,
+            default_hls_resolutions: state.config.default_hls_resolutions.clone(),
+            default_mp4_resolution: state.config.default_mp4_resolution.clone(),
+            default_codec: state.config.default_codec.clone(),
+            default_segment_type: state.config.default_segment_type.clone(),
+            default_hls_time: state.config.default_hls_time,
+            default_keyframe_interval: state.config.default_keyframe_interval,
+            default_max_fps: state.config.default_max_fps,
+            queue_policy: state.config.queue_policy.as_str().to_string(),
+            bitrate_ladder: state.config.bitrate_ladder.clone(),
+            quality_presets: state.config.quality_presets.clone(),
+            max_retention_days: state.config.max_retention_days,
+            retention_sats_per_day: state.config.retention_sats_per_day,
+            max_input_duration_secs: state.config.max_input_duration_secs,
+            max_input_size_bytes: state.config.max_input_size_bytes,
+            max_output_size_ratio: state.config.max_output_size_ratio,
+            max_output_size_bytes: state.config.max_output_size_bytes,
+            watermark: state.config.watermark.clone(),
+            normalize_audio: state.config.normalize_audio,
+            strip_metadata: state.config.strip_metadata,
+            price_sats_per_minute: state.config.price_sats_per_minute,
+            price_sats_flat: state.config.price_sats_flat,
+            price_sats_per_output_gb: state.config.price_sats_per_output_gb,
+            creator_priority: state.config.creator_priority,
+            creator_discount_percent: state.config.creator_discount_percent,
+            report_threshold: state.config.report_threshold,
+            encrypt_scratch: state.config.encrypt_scratch,
+            allowed_pubkeys: state.config.allowed_pubkeys.clone(),
+            blocked_pubkeys: state.config.blocked_pubkeys.clone(),
+            max_jobs_per_hour: state.config.max_jobs_per_hour,
         };
 
         AdminResponse::ok_with_data(ResponseData::Config(ConfigResponse {
@@ -150,8 +415,11 @@ impl AdminHandler {
             }
         }
 
-        // Connect to new relays before saving so config is published there too
-        self.sync_relays(&relays).await;
+        // Switch relays before saving so config is published on the new set too
+        let previous = self.state.read().await.config.relays.clone();
+        if let Err(e) = self.sync_relays(&previous, &relays).await {
+            return AdminResponse::error(e);
+        }
 
         let result = {
             let mut state = self.state.write().await;
@@ -161,7 +429,7 @@ impl AdminHandler {
 
         match result {
             Ok(_) => {
-                self.config_notify.notify_one();
+                self.config_watch.notify();
                 AdminResponse::ok_with_msg("Relays updated")
             }
             Err(e) => AdminResponse::error(format!("Failed to save config: {}", e)),
@@ -185,7 +453,7 @@ impl AdminHandler {
 
         match result {
             Ok(_) => {
-                self.config_notify.notify_one();
+                self.config_watch.notify();
                 AdminResponse::ok_with_msg("Blossom servers updated")
             }
             Err(e) => AdminResponse::error(format!("Failed to save config: {}", e)),
@@ -206,7 +474,7 @@ impl AdminHandler {
 
         match result {
             Ok(_) => {
-                self.config_notify.notify_one();
+                self.config_watch.notify();
                 AdminResponse::ok_with_msg(format!("Blob expiration set to {} days", days))
             }
             Err(e) => AdminResponse::error(format!("Failed to save config: {}", e)),
@@ -236,7 +504,7 @@ impl AdminHandler {
 
         match result {
             Ok(_) => {
-                self.config_notify.notify_one();
+                self.config_watch.notify();
                 AdminResponse::ok_with_msg("Profile updated")
             }
             Err(e) => AdminResponse::error(format!("Failed to save config: {}", e)),
@@ -256,7 +524,7 @@ impl AdminHandler {
 
         match result {
             Ok(_) => {
-                self.config_notify.notify_one();
+                self.config_watch.notify();
                 self.handle_status().await
             }
             Err(e) => AdminResponse::error(format!("Failed to save config: {}", e)),
@@ -276,7 +544,7 @@ impl AdminHandler {
 
         match result {
             Ok(_) => {
-                self.config_notify.notify_one();
+                self.config_watch.notify();
                 self.handle_status().await
             }
             Err(e) => AdminResponse::error(format!("Failed to save config: {}", e)),
@@ -290,8 +558,16 @@ impl AdminHandler {
         let status = StatusResponse {
             paused: state.config.paused,
             jobs_active: state.jobs_active,
+            jobs_queued: state.jobs_queued,
+            channel_depth: state.channel_depth,
+            queue_capacity: self.config.job_queue_capacity as u32,
             jobs_completed: state.jobs_completed,
             jobs_failed: state.jobs_failed,
+            ffmpeg_failures: state
+                .ffmpeg_failures
+                .iter()
+                .map(|(kind, count)| (kind.to_string(), *count))
+                .collect(),
             uptime_secs: state.uptime_secs(),
             hwaccel: state.hwaccel.clone().unwrap_or_else(|| "none".to_string()),
             version: env!("CARGO_PKG_VERSION").to_string(),
@@ -301,30 +577,62 @@ impl AdminHandler {
     }
 
     /// Handles the JobHistory command.
-    async fn handle_job_history(&self, limit: u32) -> AdminResponse {
+    async fn handle_job_history(
+        &self,
+        limit: u32,
+        offset: u32,
+        filter: JobHistoryFilter,
+    ) -> AdminResponse {
+        let status = match filter.status.as_deref() {
+            Some(s) => match JobStatus::parse(s) {
+                Some(status) => Some(status),
+                None => return AdminResponse::error(format!("Invalid job status: {}", s)),
+            },
+            None => None,
+        };
+        let requester = match filter.requester.as_deref() {
+            Some(r) => match PublicKey::parse(r) {
+                Ok(pk) => Some(pk.to_hex()),
+                Err(_) => return AdminResponse::error(format!("Invalid requester pubkey: {}", r)),
+            },
+            None => None,
+        };
+        let query = JobHistoryQuery {
+            since: filter.since,
+            until: filter.until,
+            status,
+            requester,
+        };
+
         let state = self.state.read().await;
-        let history = state.get_job_history(limit as usize);
+        let (history, total) = state.query_job_history(&query, offset as usize, limit as usize);
 
         let jobs: Vec<JobInfo> = history
             .into_iter()
-            .map(|record| {
-                let duration_secs = record
-                    .completed_at
-                    .map(|end| end.saturating_sub(record.started_at));
-
-                JobInfo {
-                    id: record.id.clone(),
-                    status: record.status.to_string(),
-                    input_url: record.input_url.clone(),
-                    output_url: record.output_url.clone(),
-                    started_at: format_timestamp(record.started_at),
-                    completed_at: record.completed_at.map(format_timestamp),
-                    duration_secs,
-                }
-            })
+            .map(job_info)
             .collect();
 
-        AdminResponse::ok_with_data(ResponseData::JobHistory(JobHistoryResponse { jobs }))
+        AdminResponse::ok_with_data(ResponseData::JobHistory(JobHistoryResponse {
+            jobs,
+            total: total as u32,
+            offset,
+        }))
+    }
+
+    /// Handles the CancelJob command.
+    ///
+    /// Signals the job's task to stop; dropping it kills the FFmpeg process.
+    async fn handle_cancel_job(&self, id: &str) -> AdminResponse {
+        let mut state = self.state.write().await;
+        match state.cancel_job(id) {
+            Some(record) => {
+                info!(job_id = id, "Job cancelled by admin");
+                AdminResponse::ok_with_data(ResponseData::Job(JobResponse {
+                    job: job_info(record),
+                }))
+            }
+            None => AdminResponse::error(format!("No running job with id {}", id)),
+        }
     }
 
     /// Handles the GetDashboard command.
@@ -336,8 +644,16 @@ impl AdminHandler {
         let status = StatusResponse {
             paused: state.config.paused,
             jobs_active: state.jobs_active,
+            jobs_queued: state.jobs_queued,
+            channel_depth: state.channel_depth,
+            queue_capacity: self.config.job_queue_capacity as u32,
             jobs_completed: state.jobs_completed,
             jobs_failed: state.jobs_failed,
+            ffmpeg_failures: state
+                .ffmpeg_failures
+                .iter()
+                .map(|(kind, count)| (kind.to_string(), *count))
+                .collect(),
             uptime_secs: state.uptime_secs(),
             hwaccel: state.hwaccel.clone().unwrap_or_else(|| "none".to_string()),
             version: env!("CARGO_PKG_VERSION").to_string(),
@@ -351,37 +667,507 @@ impl AdminHandler {
             about: state.config.about.clone(),
             paused: state.config.paused,
             max_concurrent_jobs: state.config.max_concurrent_jobs,
+            external_fetchers_enabled: state.config.external_fetchers_enabled,
+            default_hls_resolutions: state.config.default_hls_resolutions.clone(),
+            default_mp4_resolution: state.config.default_mp4_resolution.clone(),
+            default_codec: state.config.default_codec.clone(),
+            default_segment_type: state.config.default_segment_type.clone(),
+            default_hls_time: state.config.default_hls_time,
+            default_keyframe_interval: state.config.default_keyframe_interval,
+            default_max_fps: state.config.default_max_fps,
+            queue_policy: state.config.queue_policy.as_str().to_string(),
+            bitrate_ladder: state.config.bitrate_ladder.clone(),
+            quality_presets: state.config.quality_presets.clone(),
+            max_retention_days: state.config.max_retention_days,
+            retention_sats_per_day: state.config.retention_sats_per_day,
+            max_input_duration_secs: state.config.max_input_duration_secs,
+            max_input_size_bytes: state.config.max_input_size_bytes,
+            max_output_size_ratio: state.config.max_output_size_ratio,
+            max_output_size_bytes: state.config.max_output_size_bytes,
+            watermark: state.config.watermark.clone(),
+            normalize_audio: state.config.normalize_audio,
+            strip_metadata: state.config.strip_metadata,
+            price_sats_per_minute: state.config.price_sats_per_minute,
+            price_sats_flat: state.config.price_sats_flat,
+            price_sats_per_output_gb: state.config.price_sats_per_output_gb,
+            creator_priority: state.config.creator_priority,
+            creator_discount_percent: state.config.creator_discount_percent,
+            report_threshold: state.config.report_threshold,
+            encrypt_scratch: state.config.encrypt_scratch,
+            allowed_pubkeys: state.config.allowed_pubkeys.clone(),
+            blocked_pubkeys: state.config.blocked_pubkeys.clone(),
+            max_jobs_per_hour: state.config.max_jobs_per_hour,
         };
 
         let history = state.get_job_history(limit as usize);
         let jobs: Vec<JobInfo> = history
             .into_iter()
-            .map(|record| {
-                let duration_secs = record
-                    .completed_at
-                    .map(|end| end.saturating_sub(record.started_at));
-                JobInfo {
-                    id: record.id.clone(),
-                    status: record.status.to_string(),
-                    input_url: record.input_url.clone(),
-                    output_url: record.output_url.clone(),
-                    started_at: format_timestamp(record.started_at),
-                    completed_at: record.completed_at.map(format_timestamp),
-                    duration_secs,
-                }
-            })
+            .map(job_info)
             .collect();
 
         AdminResponse::ok_with_data(ResponseData::Dashboard(DashboardResponse {
             status,
             config,
             jobs,
+            relays: state.relay_stats.entries(),
+            blossom_health: state.blossom_health.entries(&state.config.blossom_servers),
+        }))
+    }
+
+    /// Handles the RelayStats command.
+    async fn handle_relay_stats(&self) -> AdminResponse {
+        let relays = self.state.read().await.relay_stats.entries();
+        AdminResponse::ok_with_data(ResponseData::RelayStats(RelayStatsResponse { relays }))
+    }
+
+    /// Handles the ListQuarantine command.
+    async fn handle_list_quarantine(&self) -> AdminResponse {
+        let state = self.state.read().await;
+        let quarantine = state.quarantine.entries().into_iter().cloned().collect();
+        AdminResponse::ok_with_data(ResponseData::Quarantine(QuarantineResponse { quarantine }))
+    }
+
+    /// Handles the RestoreQuarantined command.
+    ///
+    /// Re-uploads the blobs kept for review and clears the job's quarantine flag.
+    async fn handle_restore_quarantined(&self, id: &str) -> AdminResponse {
+        let blossom = BlossomClient::new(self.config.clone(), self.state.clone());
+        let dir = Paths::resolve().quarantine_dir;
+        match moderation::restore(&self.state, &blossom, &dir, id).await {
+            Ok(entry) => {
+                info!(event_id = id, "Quarantined result restored by admin");
+                AdminResponse::ok_with_data(ResponseData::QuarantineEntry(QuarantineEntryResponse {
+                    entry,
+                }))
+            }
+            Err(e) => AdminResponse::error(e),
+        }
+    }
+
+    /// Handles the FetchInput command.
+    ///
+    /// Downloads a job's input again into `$DATA_DIR/inputs/` so operators can
+    /// reproduce decoder problems locally without tracking down the source URL.
+    async fn handle_fetch_input(&self, id: &str) -> AdminResponse {
+        let record = {
+            let state = self.state.read().await;
+            state.job_history.iter().find(|r| r.id == id).cloned()
+        };
+        let Some(record) = record else {
+            return AdminResponse::error(format!("Job not found: {}", id));
+        };
+        let url = match url::Url::parse(&record.input_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => url,
+            _ => {
+                return AdminResponse::error(format!(
+                    "Input of job {} is not a downloadable URL: {}",
+                    id, record.input_url
+                ))
+            }
+        };
+
+        let dir = Paths::resolve().inputs_dir;
+        let extension = std::path::Path::new(url.path())
+            .extension()
+            .and_then(|e| e.to_str())
+            .filter(|e| e.len() <= 5 && e.chars().all(|c| c.is_ascii_alphanumeric()))
+            .map(|e| format!(".{}", e))
+            .unwrap_or_default();
+        let path = dir.join(format!("{}{}", record.id, extension));
+
+        match download_to(&url, &dir, &path).await {
+            Ok((size_bytes, sha256)) => {
+                info!(job_id = id, path = %path.display(), size_bytes, "Job input fetched by admin");
+                AdminResponse::ok_with_data(ResponseData::FetchedInput(FetchedInputResponse {
+                    job_id: record.id,
+                    input_url: record.input_url,
+                    path: path.display().to_string(),
+                    size_bytes,
+                    sha256,
+                }))
+            }
+            Err(e) => AdminResponse::error(format!("Failed to fetch input: {}", e)),
+        }
+    }
+
+    /// Handles the ListBlobs command.
+    ///
+    /// Only configured servers can be listed, so the command can't be used to
+    /// send the DVM's signed list tokens elsewhere.
+    async fn handle_list_blobs(&self, server: Option<String>, limit: Option<usize>) -> AdminResponse {
+        let servers = self.state.read().await.config.blossom_servers.clone();
+        let server = match server {
+            Some(s) => match servers.iter().find(|c| normalize_relay(c) == normalize_relay(&s)) {
+                Some(configured) => configured.clone(),
+                None => return AdminResponse::error(format!("Not a configured Blossom server: {}", s)),
+            },
+            None => match servers.first() {
+                Some(first) => first.clone(),
+                None => return AdminResponse::error("No Blossom servers configured"),
+            },
+        };
+        let url = match url::Url::parse(&server) {
+            Ok(url) => url,
+            Err(e) => return AdminResponse::error(format!("Invalid server URL {}: {}", server, e)),
+        };
+
+        let blossom = BlossomClient::new(self.config.clone(), self.state.clone());
+        let mut blobs = match blossom.list_blobs(&url).await {
+            Ok(blobs) => blobs,
+            Err(e) => return AdminResponse::error(format!("Failed to list blobs on {}: {}", server, e)),
+        };
+        let total = blobs.len();
+        let total_size_bytes = blobs.iter().map(|b| b.size).sum();
+        blobs.sort_by_key(|b| std::cmp::Reverse(b.uploaded));
+        blobs.truncate(limit.unwrap_or(DEFAULT_BLOB_LIST_LIMIT).min(MAX_BLOB_LIST_LIMIT));
+
+        AdminResponse::ok_with_data(ResponseData::Blobs(BlobsResponse {
+            server,
+            blobs,
+            total,
+            total_size_bytes,
+        }))
+    }
+
+    /// Handles the DeleteBlob command.
+    ///
+    /// Deletes from every configured server; blobs of pinned jobs are refused.
+    async fn handle_delete_blob(&self, sha256: &str) -> AdminResponse {
+        let sha256 = sha256.to_lowercase();
+        if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            return AdminResponse::error(format!("Invalid sha256: {}", sha256));
+        }
+        let servers: Vec<url::Url> = {
+            let state = self.state.read().await;
+            if let Some(job_id) = state.references.pinned_by(&sha256) {
+                return AdminResponse::error(format!(
+                    "Blob {} belongs to pinned job {}; unpin it first",
+                    sha256, job_id
+                ));
+            }
+            state
+                .config
+                .blossom_servers
+                .iter()
+                .filter_map(|s| url::Url::parse(s).ok())
+                .collect()
+        };
+
+        let blossom = BlossomClient::new(self.config.clone(), self.state.clone());
+        let mut deleted = Vec::new();
+        let mut failed = Vec::new();
+        for server in servers {
+            match blossom.delete_blob(&server, &sha256).await {
+                Ok(()) => deleted.push(server.to_string()),
+                Err(e) => failed.push(format!("{}: {}", server, e)),
+            }
+        }
+        info!(sha256 = %sha256, deleted = deleted.len(), failed = failed.len(), "Blob deleted by admin");
+        AdminResponse::ok_with_data(ResponseData::DeleteBlob(DeleteBlobResponse {
+            sha256,
+            deleted,
+            failed,
+        }))
+    }
+
+    /// Handles the Pin and Unpin commands.
+    ///
+    /// Jobs that completed before the reference index existed are looked up
+    /// through their result event and added to it.
+    async fn handle_pin(&self, job_id: &str, pinned: bool) -> AdminResponse {
+        let indexed = self.state.read().await.references.get(job_id).is_some();
+        if !indexed {
+            if !pinned {
+                return AdminResponse::error(format!("Job {} is not pinned", job_id));
+            }
+            let hashes = match self.result_blob_hashes(job_id).await {
+                Ok(hashes) => hashes,
+                Err(e) => return AdminResponse::error(e),
+            };
+            let mut state = self.state.write().await;
+            let completed_at = state
+                .job_history
+                .iter()
+                .find(|r| r.id == job_id)
+                .and_then(|r| r.completed_at)
+                .unwrap_or(0);
+            state.references.record(job_id, hashes, completed_at);
+        }
+
+        let mut state = self.state.write().await;
+        let Some(blobs) = state.references.set_pinned(job_id, pinned).map(|r| r.hashes.len()) else {
+            return AdminResponse::error(format!("Job {} is not in the reference index", job_id));
+        };
+        if let Err(e) = state.references.save() {
+            return AdminResponse::error(format!("Failed to save reference index: {}", e));
+        }
+        info!(job_id, pinned, blobs, "Job pin changed by admin");
+        AdminResponse::ok_with_data(ResponseData::Pin(PinResponse {
+            job_id: job_id.to_string(),
+            pinned,
+            blobs,
+        }))
+    }
+
+    /// Handles the ShareJob command.
+    ///
+    /// Signs a `/watch` link to a completed job's output that works until
+    /// `ttl_secs` from now (default one day, at most 30).
+    async fn handle_share_job(&self, job_id: &str, ttl_secs: Option<u64>) -> AdminResponse {
+        if !self.config.http_enabled {
+            return AdminResponse::error("The web server is disabled (DISABLE_HTTP)");
+        }
+        let playable = self
+            .state
+            .read()
+            .await
+            .job_history
+            .iter()
+            .any(|r| r.id == job_id && r.status == JobStatus::Completed && !r.quarantined);
+        if !playable {
+            return AdminResponse::error(format!("Job {} has no result to share", job_id));
+        }
+
+        let ttl = ttl_secs.unwrap_or(DEFAULT_SHARE_TTL_SECS).clamp(1, MAX_SHARE_TTL_SECS);
+        let expires_at = Timestamp::now().as_u64() + ttl;
+        let keys = &self.config.nostr_keys;
+        info!(job_id, expires_at, "Watch link created by admin");
+        AdminResponse::ok_with_data(ResponseData::ShareJob(ShareJobResponse {
+            job_id: job_id.to_string(),
+            path: watch::watch_path(keys, job_id, expires_at),
+            url: self
+                .config
+                .public_url
+                .as_deref()
+                .map(|base| watch::watch_url(base, keys, job_id, expires_at)),
+            expires_at,
+        }))
+    }
+
+    /// Handles the GetJobLog command.
+    ///
+    /// Returns the tail of a job's log while the job is in the recent history.
+    async fn handle_get_job_log(&self, job_id: &str, lines: Option<usize>) -> AdminResponse {
+        let log = self
+            .state
+            .read()
+            .await
+            .job_history
+            .iter()
+            .find(|r| r.id == job_id)
+            .and_then(|r| r.log.clone());
+        let Some(log) = log else {
+            return AdminResponse::error(format!("No log for job {}", job_id));
+        };
+        let (lines, omitted) = log.tail(lines.unwrap_or(DEFAULT_JOB_LOG_LINES));
+        AdminResponse::ok_with_data(ResponseData::JobLog(JobLogResponse {
+            job_id: job_id.to_string(),
+            lines,
+            omitted,
+        }))
+    }
+
+    /// Handles the Replicate command.
+    ///
+    /// Copies a blob, or every blob of a job's result, to the configured
+    /// servers that don't have it yet.
+    async fn handle_replicate(&self, sha256: Option<String>, job_id: Option<String>) -> AdminResponse {
+        let hashes = match (sha256, job_id) {
+            (Some(sha256), _) => vec![sha256.to_lowercase()],
+            (None, Some(job_id)) => match self.result_blob_hashes(&job_id).await {
+                Ok(hashes) => hashes,
+                Err(e) => return AdminResponse::error(e),
+            },
+            (None, None) => return AdminResponse::error("Nothing to replicate"),
+        };
+
+        let blossom = BlossomClient::new(self.config.clone(), self.state.clone());
+        let mut blobs = Vec::new();
+        for sha256 in hashes {
+            match blossom.replicate(&sha256).await {
+                Ok(replication) => blobs.push(replication),
+                Err(e) => return AdminResponse::error(e.to_string()),
+            }
+        }
+        let copied: usize = blobs.iter().map(|b| b.copied.len()).sum();
+        info!(blobs = blobs.len(), copied, "Blobs replicated by admin");
+        AdminResponse::ok_with_data(ResponseData::Replicate(ReplicateResponse { blobs }))
+    }
+
+    /// Hashes of the blobs in the result the DVM published for a job
+    async fn result_blob_hashes(&self, job_id: &str) -> Result<Vec<String>, String> {
+        let request_id =
+            EventId::parse(job_id).map_err(|e| format!("Invalid job ID '{}': {}", job_id, e))?;
+        let filter = Filter::new()
+            .kind(DVM_VIDEO_TRANSFORM_RESULT_KIND)
+            .author(self.config.nostr_keys.public_key())
+            .event(request_id)
+            .limit(1);
+        let events = self
+            .client
+            .get_events_of(vec![filter], EventSource::relays(Some(Duration::from_secs(10))))
+            .await
+            .map_err(|e| format!("Failed to fetch result of job {}: {}", job_id, e))?;
+        let event = events
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("No result found for job {}", job_id))?;
+        let result = moderation::read_result(&event, &self.config.nostr_keys).await?;
+        Ok(result.blob_hashes())
+    }
+
+    /// Handles the AllowPubkey, DisallowPubkey, BlockPubkey and UnblockPubkey commands.
+    ///
+    /// Adds the pubkey to (or removes it from) the selected list and returns the updated config.
+    async fn handle_pubkey_list(
+        &self,
+        pubkey: &str,
+        list: fn(&mut RemoteConfig) -> &mut Vec<String>,
+        add: bool,
+    ) -> AdminResponse {
+        let hex = match PublicKey::parse(pubkey) {
+            Ok(pk) => pk.to_hex(),
+            Err(e) => return AdminResponse::error(format!("Invalid pubkey '{}': {}", pubkey, e)),
+        };
+
+        let result = {
+            let mut state = self.state.write().await;
+            let keys = list(&mut state.config);
+            if add && !keys.contains(&hex) {
+                keys.push(hex);
+            } else if !add {
+                keys.retain(|k| *k != hex);
+            }
+            save_config(&self.client, &state.keys, &state.config).await
+        };
+
+        match result {
+            Ok(_) => {
+                self.config_watch.notify();
+                self.handle_get_config().await
+            }
+            Err(e) => AdminResponse::error(format!("Failed to save config: {}", e)),
+        }
+    }
+
+    /// Handles the ExportIdentity command.
+    ///
+    /// Returns the DVM's private key encrypted with `password` (NIP-49).
+    async fn handle_export_identity(&self, password: String) -> AdminResponse {
+        let keys = self.state.read().await.keys.clone();
+        // scrypt takes a while by design
+        let exported = tokio::task::spawn_blocking({
+            let keys = keys.clone();
+            move || identity::export_identity(&keys, &password)
+        })
+        .await;
+        match exported {
+            Ok(Ok(ncryptsec)) => {
+                info!("Identity exported by admin");
+                AdminResponse::ok_with_data(ResponseData::Identity(IdentityResponse {
+                    pubkey: keys.public_key().to_hex(),
+                    npub: keys.public_key().to_bech32().unwrap_or_default(),
+                    ncryptsec: Some(ncryptsec),
+                    previous_pubkey: None,
+                    backup_file: None,
+                    restarting: false,
+                }))
+            }
+            Ok(Err(e)) => AdminResponse::error(e.to_string()),
+            Err(e) => AdminResponse::error(format!("Failed to export identity: {}", e)),
+        }
+    }
+
+    /// Handles the ImportIdentity command.
+    async fn handle_import_identity(&self, key: String, password: Option<String>) -> AdminResponse {
+        let parsed = tokio::task::spawn_blocking(move || {
+            identity::parse_identity(&key, password.as_deref())
+        })
+        .await;
+        match parsed {
+            Ok(Ok(keys)) => self.switch_identity(keys).await,
+            Ok(Err(e)) => AdminResponse::error(e.to_string()),
+            Err(e) => AdminResponse::error(format!("Failed to import identity: {}", e)),
+        }
+    }
+
+    /// Switches the DVM to `new` (ImportIdentity and RotateIdentity).
+    ///
+    /// Moves the remote config and announcements to the new key, replaces the
+    /// key file and restarts the DVM shortly after responding, as the key is
+    /// held by every subsystem.
+    async fn switch_identity(&self, new: Keys) -> AdminResponse {
+        let (old, settings) = {
+            let state = self.state.read().await;
+            (state.keys.clone(), state.config.clone())
+        };
+        if new.public_key() == old.public_key() {
+            return AdminResponse::error("The DVM already uses this key");
+        }
+
+        let hwaccel = tokio::task::spawn_blocking(HwAccel::detect)
+            .await
+            .unwrap_or_default();
+        match identity::migrate_identity(&self.client, &old, &new, &settings, hwaccel).await {
+            Ok(true) => {}
+            Ok(false) => info!(pubkey = %new.public_key(), "Imported key has its own config, keeping it"),
+            Err(e) => return AdminResponse::error(e.to_string()),
+        }
+        let backup = match identity::replace_identity(&identity::identity_key_path(), &new) {
+            Ok(backup) => backup,
+            Err(e) => return AdminResponse::error(format!("Failed to save the new key: {}", e)),
+        };
+
+        info!(
+            previous = %old.public_key(),
+            pubkey = %new.public_key(),
+            "Identity switched by admin, restarting"
+        );
+        let restart = self.restart.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(IDENTITY_RESTART_DELAY).await;
+            restart.notify_one();
+        });
+
+        AdminResponse::ok_with_data(ResponseData::Identity(IdentityResponse {
+            pubkey: new.public_key().to_hex(),
+            npub: new.public_key().to_bech32().unwrap_or_default(),
+            ncryptsec: None,
+            previous_pubkey: Some(old.public_key().to_hex()),
+            backup_file: backup.map(|p| p.display().to_string()),
+            restarting: true,
         }))
     }
 
+    /// Handles the DryRun command.
+    ///
+    /// Plans a job for `input` with `params` and returns the FFmpeg command
+    /// it would run. The input is probed, but nothing is encoded.
+    async fn handle_dry_run(&self, input: String, params: BTreeMap<String, String>) -> AdminResponse {
+        let Some(processor) = &self.processor else {
+            return AdminResponse::error("Dry runs are not available");
+        };
+        let settings = self.state.read().await.config.clone();
+        let planned = match dry_run::job_from_params(&input, &params, &settings) {
+            Ok(job) => dry_run::plan(processor, &self.config, &settings, job).await,
+            Err(e) => Err(e),
+        };
+        match planned {
+            Ok(dry_run) => AdminResponse::ok_with_data(ResponseData::DryRun(DryRunResponse {
+                mode: dry_run.mode.as_str().to_string(),
+                hwaccel: processor.hwaccel().to_string(),
+                command: shell_command(&dry_run.argv),
+                argv: dry_run.argv,
+                notes: dry_run.notes,
+            })),
+            Err(e) => AdminResponse::error(e.to_string()),
+        }
+    }
+
     /// Handles the SetConfig command.
     ///
     /// Applies all provided config fields and returns the updated config.
+    #[allow(clippy::too_many_arguments)]
     async fn handle_set_config(
         &self,
         relays: Option<Vec<String>>,
@@ -390,6 +1176,36 @@ impl AdminHandler {
         name: Option<String>,
         about: Option<String>,
         max_concurrent_jobs: Option<u32>,
+        external_fetchers_enabled: Option<bool>,
+        default_hls_resolutions: Option<Vec<String>>,
+        default_mp4_resolution: Option<String>,
+        default_codec: Option<String>,
+        default_segment_type: Option<String>,
+        default_hls_time: Option<u32>,
+        default_keyframe_interval: Option<f64>,
+        default_max_fps: Option<u32>,
+        queue_policy: Option<String>,
+        bitrate_ladder: Option<Vec<LadderRung>>,
+        quality_presets: Option<Vec<QualityPreset>>,
+        max_retention_days: Option<u32>,
+        retention_sats_per_day: Option<u64>,
+        max_input_duration_secs: Option<u64>,
+        max_input_size_bytes: Option<u64>,
+        max_output_size_ratio: Option<f64>,
+        max_output_size_bytes: Option<u64>,
+        watermark: Option<Watermark>,
+        normalize_audio: Option<bool>,
+        strip_metadata: Option<bool>,
+        price_sats_per_minute: Option<u64>,
+        price_sats_flat: Option<u64>,
+        price_sats_per_output_gb: Option<u64>,
+        creator_priority: Option<bool>,
+        creator_discount_percent: Option<u8>,
+        report_threshold: Option<u32>,
+        encrypt_scratch: Option<bool>,
+        allowed_pubkeys: Option<Vec<String>>,
+        blocked_pubkeys: Option<Vec<String>>,
+        max_jobs_per_hour: Option<u32>,
     ) -> AdminResponse {
         // Validate relay URLs if provided
         if let Some(ref relays) = relays {
@@ -400,6 +1216,14 @@ impl AdminHandler {
             }
         }
 
+        if creator_discount_percent.is_some_and(|p| p > 100) {
+            return AdminResponse::error("creator_discount_percent must be between 0 and 100");
+        }
+
+        if max_output_size_ratio.is_some_and(|r| !r.is_finite() || r < 0.0) {
+            return AdminResponse::error("max_output_size_ratio must be a positive number (0 clears it)");
+        }
+
         // Validate server URLs if provided
         if let Some(ref servers) = blossom_servers {
             for server in servers {
@@ -421,9 +1245,96 @@ impl AdminHandler {
             }
         }
 
-        // Connect to new relays before saving so config is published there too
+        if let Some(ref ladder) = default_hls_resolutions {
+            if let Some(bad) = ladder.iter().find(|r| Resolution::from_str(r).is_none()) {
+                return AdminResponse::error(format!("Invalid resolution: {}", bad));
+            }
+        }
+
+        if let Some(res) = default_mp4_resolution.as_ref().filter(|r| !r.is_empty()) {
+            if !matches!(Resolution::from_str(res), Some(r) if r != Resolution::Original) {
+                return AdminResponse::error(format!("Invalid MP4 resolution: {}", res));
+            }
+        }
+
+        if let Some(codec) = default_codec.as_ref().filter(|c| !c.is_empty()) {
+            if Codec::parse(codec).is_none() {
+                return AdminResponse::error(format!("Invalid codec: {}", codec));
+            }
+        }
+
+        if let Some(ref segment_type) = default_segment_type {
+            if SegmentType::parse(segment_type).is_none() {
+                return AdminResponse::error(format!("Invalid segment type: {}", segment_type));
+            }
+        }
+
+        // 0 clears a default; the pair is checked together since the segment
+        // duration has to be a multiple of the keyframe interval
+        if default_hls_time.is_some() || default_keyframe_interval.is_some() {
+            let (current_hls_time, current_keyframe_interval) = {
+                let state = self.state.read().await;
+                (state.config.default_hls_time, state.config.default_keyframe_interval)
+            };
+            let hls_time = default_hls_time.map_or(current_hls_time, |t| (t > 0).then_some(t));
+            let keyframe_interval = default_keyframe_interval
+                .map_or(current_keyframe_interval, |k| (k != 0.0).then_some(k));
+            if let Err(e) = Segmenting::new(hls_time, keyframe_interval) {
+                return AdminResponse::error(format!("Invalid segmenting defaults: {}", e));
+            }
+        }
+        if let Some(Err(e)) = default_max_fps.filter(|&fps| fps > 0).map(FpsCap::validate) {
+            return AdminResponse::error(format!("Invalid default_max_fps: {}", e));
+        }
+
+        let queue_policy = match queue_policy {
+            Some(p) => match QueuePolicy::parse(&p) {
+                Some(policy) => Some(policy),
+                None => return AdminResponse::error(format!("Invalid queue policy: {}", p)),
+            },
+            None => None,
+        };
+
+        let bitrate_ladder = match bitrate_ladder.as_deref().map(validate_ladder) {
+            Some(Ok(rungs)) => Some(rungs),
+            Some(Err(e)) => return AdminResponse::error(format!("Invalid bitrate ladder: {}", e)),
+            None => None,
+        };
+
+        let quality_presets = match quality_presets.as_deref().map(validate_quality_presets) {
+            Some(Ok(presets)) => Some(presets),
+            Some(Err(e)) => return AdminResponse::error(format!("Invalid quality presets: {}", e)),
+            None => None,
+        };
+
+        // An empty watermark clears the operator default
+        let watermark = match watermark {
+            Some(w) if w.content.trim().is_empty() => Some(None),
+            Some(w) => match w.validate() {
+                Ok(w) => Some(Some(w)),
+                Err(e) => return AdminResponse::error(format!("Invalid watermark: {}", e)),
+            },
+            None => None,
+        };
+
+        let allowed_pubkeys = match allowed_pubkeys.as_deref().map(normalize_pubkeys) {
+            Some(Ok(keys)) => Some(keys),
+            Some(Err(e)) => return AdminResponse::error(e),
+            None => None,
+        };
+
+        let blocked_pubkeys = match blocked_pubkeys.as_deref().map(normalize_pubkeys) {
+            Some(Ok(keys)) => Some(keys),
+            Some(Err(e)) => return AdminResponse::error(e),
+            None => None,
+        };
+
+        // Switch relays before saving so config is published on the new set too
         if let Some(ref r) = relays {
-            self.sync_relays(r).await;
+            let previous = self.state.read().await.config.relays.clone();
+            if let Err(e) = self.sync_relays(&previous, r).await {
+                return AdminResponse::error(e);
+            }
         }
 
         let result = {
@@ -447,13 +1358,106 @@ impl AdminHandler {
             if let Some(j) = max_concurrent_jobs {
                 state.config.max_concurrent_jobs = j;
             }
+            if let Some(e) = external_fetchers_enabled {
+                state.config.external_fetchers_enabled = e;
+            }
+            if let Some(ladder) = default_hls_resolutions {
+                state.config.default_hls_resolutions =
+                    ladder.iter().map(|r| r.to_lowercase()).collect();
+            }
+            // An empty value clears the default
+            if let Some(res) = default_mp4_resolution {
+                state.config.default_mp4_resolution = (!res.is_empty()).then(|| res.to_lowercase());
+            }
+            if let Some(codec) = default_codec {
+                state.config.default_codec = (!codec.is_empty()).then(|| codec.to_lowercase());
+            }
+            if let Some(segment_type) = default_segment_type {
+                state.config.default_segment_type = Some(segment_type.to_lowercase());
+            }
+            if let Some(secs) = default_hls_time {
+                state.config.default_hls_time = (secs > 0).then_some(secs);
+            }
+            if let Some(secs) = default_keyframe_interval {
+                state.config.default_keyframe_interval = (secs != 0.0).then_some(secs);
+            }
+            if let Some(fps) = default_max_fps {
+                state.config.default_max_fps = (fps > 0).then_some(fps);
+            }
+            if let Some(policy) = queue_policy {
+                state.config.queue_policy = policy;
+            }
+            if let Some(rungs) = bitrate_ladder {
+                state.config.bitrate_ladder = rungs;
+            }
+            if let Some(presets) = quality_presets {
+                state.config.quality_presets = presets;
+            }
+            if let Some(days) = max_retention_days {
+                state.config.max_retention_days = days;
+            }
+            if let Some(sats) = retention_sats_per_day {
+                state.config.retention_sats_per_day = sats;
+            }
+            // 0 clears the limit, falling back to the environment default
+            if let Some(secs) = max_input_duration_secs {
+                state.config.max_input_duration_secs = (secs > 0).then_some(secs);
+            }
+            if let Some(bytes) = max_input_size_bytes {
+                state.config.max_input_size_bytes = (bytes > 0).then_some(bytes);
+            }
+            if let Some(ratio) = max_output_size_ratio {
+                state.config.max_output_size_ratio = (ratio > 0.0).then_some(ratio);
+            }
+            if let Some(bytes) = max_output_size_bytes {
+                state.config.max_output_size_bytes = (bytes > 0).then_some(bytes);
+            }
+            if let Some(w) = watermark {
+                state.config.watermark = w;
+            }
+            if let Some(n) = normalize_audio {
+                state.config.normalize_audio = n;
+            }
+            if let Some(s) = strip_metadata {
+                state.config.strip_metadata = s;
+            }
+            if let Some(sats) = price_sats_per_minute {
+                state.config.price_sats_per_minute = sats;
+            }
+            if let Some(sats) = price_sats_flat {
+                state.config.price_sats_flat = sats;
+            }
+            if let Some(sats) = price_sats_per_output_gb {
+                state.config.price_sats_per_output_gb = sats;
+            }
+            if let Some(p) = creator_priority {
+                state.config.creator_priority = p;
+            }
+            if let Some(p) = creator_discount_percent {
+                state.config.creator_discount_percent = p;
+            }
+            if let Some(n) = report_threshold {
+                state.config.report_threshold = n;
+            }
+            if let Some(e) = encrypt_scratch {
+                state.config.encrypt_scratch = e;
+            }
+            if let Some(keys) = allowed_pubkeys {
+                state.config.allowed_pubkeys = keys;
+            }
+            if let Some(keys) = blocked_pubkeys {
+                state.config.blocked_pubkeys = keys;
+            }
+            if let Some(n) = max_jobs_per_hour {
+                state.config.max_jobs_per_hour = n;
+            }
 
             save_config(&self.client, &state.keys, &state.config).await
         };
 
         match result {
             Ok(_) => {
-                self.config_notify.notify_one();
+                self.config_watch.notify();
                 self.handle_get_config().await
             }
             Err(e) => AdminResponse::error(format!("Failed to save config: {}", e)),
@@ -573,6 +1577,11 @@ impl AdminHandler {
             ffprobe_path: self.config.ffprobe_path.to_string_lossy().to_string(),
         };
 
+        let blossom_health = {
+            let state = self.state.read().await;
+            state.blossom_health.entries(&state.config.blossom_servers)
+        };
+
         AdminResponse::ok_with_data(ResponseData::SystemInfo(SystemInfoResponse {
             platform: std::env::consts::OS.to_string(),
             arch: std::env::consts::ARCH.to_string(),
@@ -582,6 +1591,7 @@ impl AdminHandler {
             disk,
             ffmpeg,
             temp_dir: self.config.temp_dir.to_string_lossy().to_string(),
+            blossom_health,
         }))
     }
 
@@ -612,10 +1622,13 @@ impl AdminHandler {
         // Track what was imported
         let mut imported = Vec::new();
 
-        // Connect to new relays before saving so config is published there too
+        // Switch relays before saving so config is published on the new set too
         if let Some(ref r) = relays {
             if !r.is_empty() {
-                self.sync_relays(r).await;
+                let previous = self.state.read().await.config.relays.clone();
+                if let Err(e) = self.sync_relays(&previous, r).await {
+                    return AdminResponse::error(e);
+                }
             }
         }
 
@@ -660,7 +1673,7 @@ impl AdminHandler {
 
         match result {
             Ok(_) => {
-                self.config_notify.notify_one();
+                self.config_watch.notify();
                 AdminResponse::ok_with_msg(format!("Imported: {}", imported.join(", ")))
             }
             Err(e) => AdminResponse::error(format!("Failed to save config: {}", e)),
@@ -668,7 +1681,69 @@ impl AdminHandler {
     }
 }
 
+/// Parses npub or hex pubkeys into deduplicated hex, as stored in the config.
+fn normalize_pubkeys(pubkeys: &[String]) -> Result<Vec<String>, String> {
+    let mut hex = Vec::with_capacity(pubkeys.len());
+    for pubkey in pubkeys {
+        let key = PublicKey::parse(pubkey)
+            .map_err(|e| format!("Invalid pubkey '{}': {}", pubkey, e))?
+            .to_hex();
+        if !hex.contains(&key) {
+            hex.push(key);
+        }
+    }
+    Ok(hex)
+}
+
 /// Formats a Unix timestamp as ISO 8601.
+/// Stream `url` into `path`, returning its size and SHA-256.
+async fn download_to(
+    url: &url::Url,
+    dir: &std::path::Path,
+    path: &std::path::Path,
+) -> Result<(u64, String), String> {
+    use sha2::{Digest, Sha256};
+    use tokio::io::AsyncWriteExt;
+
+    tokio::fs::create_dir_all(dir).await.map_err(|e| e.to_string())?;
+    let mut resp = crate::util::proxy::http_client()
+        .get(url.clone())
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?;
+
+    let mut file = tokio::fs::File::create(path).await.map_err(|e| e.to_string())?;
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+    while let Some(chunk) = resp.chunk().await.map_err(|e| e.to_string())? {
+        hasher.update(&chunk);
+        size += chunk.len() as u64;
+        file.write_all(&chunk).await.map_err(|e| e.to_string())?;
+    }
+    file.flush().await.map_err(|e| e.to_string())?;
+    Ok((size, hex::encode(hasher.finalize())))
+}
+
+fn job_info(record: &JobRecord) -> JobInfo {
+    JobInfo {
+        id: record.id.clone(),
+        status: record.status.to_string(),
+        input_url: record.input_url.clone(),
+        requester: record.requester.clone(),
+        output_url: record.output_url.clone(),
+        started_at: format_timestamp(record.started_at),
+        completed_at: record.completed_at.map(format_timestamp),
+        duration_secs: record
+            .completed_at
+            .map(|end| end.saturating_sub(record.started_at)),
+        resources: record.resources.clone(),
+        quarantined: record.quarantined,
+        software_fallback: record.software_fallback,
+        redemption: record.redemption.clone(),
+    }
+}
+
 fn format_timestamp(ts: u64) -> String {
     use std::time::{Duration, UNIX_EPOCH};
 
@@ -808,59 +1883,35 @@ async fn get_gpu_info() -> Option<GpuInfo> {
 
 /// Get disk space info for a path.
 fn get_disk_info(path: &std::path::Path) -> DiskInfo {
-    use std::ffi::CString;
-
     let path_str = path.to_string_lossy().to_string();
 
-    #[cfg(unix)]
-    {
-        // Handle potential null bytes in path (unlikely but possible)
-        let c_path = match CString::new(path_str.as_bytes()) {
-            Ok(p) => p,
-            Err(_) => {
-                tracing::warn!(path = %path_str, "Path contains null bytes, cannot get disk info");
-                return DiskInfo {
-                    path: path_str,
-                    free_bytes: 0,
-                    total_bytes: 0,
-                    free_percent: 0.0,
-                };
-            }
-        };
-        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
-        let result = unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) };
-
-        if result == 0 {
-            let free_bytes = stat.f_bavail as u64 * stat.f_frsize;
-            let total_bytes = stat.f_blocks as u64 * stat.f_frsize;
-            let free_percent = if total_bytes > 0 {
-                (free_bytes as f64 / total_bytes as f64) * 100.0
+    match crate::util::disk::disk_space(path) {
+        Some(space) => {
+            let free_percent = if space.total_bytes > 0 {
+                (space.free_bytes as f64 / space.total_bytes as f64) * 100.0
             } else {
                 0.0
             };
-
-            return DiskInfo {
+            DiskInfo {
                 path: path_str,
-                free_bytes,
-                total_bytes,
+                free_bytes: space.free_bytes,
+                total_bytes: space.total_bytes,
                 free_percent,
-            };
+            }
         }
-    }
-
-    // Fallback for non-unix or on error
-    DiskInfo {
-        path: path_str,
-        free_bytes: 0,
-        total_bytes: 0,
-        free_percent: 0.0,
+        // Fallback for non-unix or on error
+        None => DiskInfo {
+            path: path_str,
+            free_bytes: 0,
+            total_bytes: 0,
+            free_percent: 0.0,
+        },
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::remote_config::RemoteConfig;
 
     /// Helper to create a test handler with mock state.
     async fn create_test_handler() -> (AdminHandler, Keys, Keys) {
@@ -876,18 +1927,13 @@ mod tests {
         let client = Client::new(dvm_keys.clone());
 
         // Create a minimal config for testing
-        let config = Arc::new(
-            Config::from_remote(
-                dvm_keys.clone(),
-                &remote_config,
-                std::path::PathBuf::from("ffmpeg"),
-                std::path::PathBuf::from("ffprobe"),
-            )
-            .expect("Failed to create test config"),
-        );
+        let config = Arc::new(Config::from_env(
+            dvm_keys.clone(),
+            std::path::PathBuf::from("ffmpeg"),
+            std::path::PathBuf::from("ffprobe"),
+        ));
 
-        let config_notify = Arc::new(Notify::new());
-        let handler = AdminHandler::new(state, client, config, config_notify);
+        let handler = AdminHandler::new(state, client, config, ConfigWatch::new());
 
         (handler, dvm_keys, admin_keys)
     }
@@ -926,6 +1972,33 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_import_identity_rejects_bad_keys() {
+        let (handler, dvm_keys, admin_keys) = create_test_handler().await;
+
+        let response = handler
+            .handle(
+                AdminCommand::ImportIdentity {
+                    key: "not a key".to_string(),
+                    password: None,
+                },
+                admin_keys.public_key(),
+            )
+            .await;
+        assert!(!response.ok);
+
+        let response = handler
+            .handle(
+                AdminCommand::ImportIdentity {
+                    key: dvm_keys.secret_key().to_bech32().unwrap(),
+                    password: None,
+                },
+                admin_keys.public_key(),
+            )
+            .await;
+        assert_eq!(response.error.as_deref(), Some("The DVM already uses this key"));
+    }
+
     #[tokio::test]
     async fn test_status_as_admin() {
         let (handler, _dvm_keys, admin_keys) = create_test_handler().await;
@@ -1019,4 +2092,46 @@ mod tests {
         assert!(!response.ok);
         assert!(response.error.unwrap().contains("Invalid server URL"));
     }
+
+    #[tokio::test]
+    async fn test_invalid_blocked_pubkey() {
+        let (handler, _dvm_keys, admin_keys) = create_test_handler().await;
+
+        let response = handler
+            .handle(
+                AdminCommand::BlockPubkey {
+                    pubkey: "npub1bogus".to_string(),
+                },
+                admin_keys.public_key(),
+            )
+            .await;
+
+        assert!(!response.ok);
+        assert!(response.error.unwrap().contains("Invalid pubkey"));
+    }
+
+    #[test]
+    fn test_normalize_pubkeys() {
+        let keys = Keys::generate().public_key();
+        let npub = keys.to_bech32().unwrap();
+        assert_eq!(
+            normalize_pubkeys(&[npub, keys.to_hex()]),
+            Ok(vec![keys.to_hex()])
+        );
+        assert!(normalize_pubkeys(&["nope".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_relays_to_drop() {
+        let strings = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let previous = strings(&["wss://old.example/", "wss://nos.lol", "wss://keep.example"]);
+        let desired = strings(&["wss://keep.example/", "wss://new.example"]);
+        let bootstrap = strings(&["wss://nos.lol/"]);
+
+        assert_eq!(
+            relays_to_drop(&previous, &desired, &bootstrap),
+            strings(&["wss://old.example/"])
+        );
+        assert!(relays_to_drop(&desired, &desired, &[]).is_empty());
+    }
 }
@@ -2,14 +2,19 @@
 //!
 //! Subscribes to kind 24207 ephemeral events (NIP-44 encrypted)
 //! and processes admin commands using NIP-46-style RPC format.
+//! The same requests are accepted as NIP-17 direct messages (gift-wrapped
+//! kind 14), so DM clients like Amethyst or 0xchat can manage the DVM; those
+//! get their response as a gift-wrapped reply.
 
 use crate::admin::commands::{parse_request, AdminRequest, AdminResponseWire};
 use crate::admin::handler::AdminHandler;
 use crate::config::Config;
-use crate::dvm_state::SharedDvmState;
+use crate::dvm_state::{ConfigWatch, SharedDvmState};
+use crate::video::VideoProcessor;
 use nostr_sdk::prelude::*;
+use std::collections::HashSet;
 use std::sync::Arc;
-use tokio::sync::Notify;
+use tokio::sync::{Mutex, Notify};
 use tracing::{debug, error, info, warn};
 
 /// Get the DVM's configured relay URLs from shared state.
@@ -21,21 +26,72 @@ async fn dvm_relay_urls(state: &SharedDvmState) -> Vec<String> {
 /// Admin RPC event kind (ephemeral range — relays don't store these)
 const ADMIN_RPC_KIND: Kind = Kind::Custom(24207);
 
+/// NIP-59 backdates gift wraps by up to two days, so the subscription has
+/// to look back that far to see new ones
+const GIFT_WRAP_LOOKBACK_SECS: u64 = 2 * 24 * 60 * 60;
+
+/// How a request arrived, so the response goes back the same way
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Channel {
+    /// Kind 24207 event with NIP-44 content
+    Rpc,
+    /// NIP-17 direct message, answered in the same conversation
+    PrivateMessage { reply_to: Option<EventId> },
+}
+
+/// A NIP-17 direct message to the DVM
+#[derive(Debug, Clone, PartialEq)]
+struct PrivateMessage {
+    sender: PublicKey,
+    id: Option<EventId>,
+    content: String,
+}
+
+/// Unwrap a gift-wrapped direct message sent to `keys`. Messages written
+/// before `since` (stored ones from before startup) are ignored, as are
+/// rumors claiming another author than the one who signed the seal.
+fn unwrap_private_message(keys: &Keys, gift_wrap: &Event, since: Timestamp) -> Option<PrivateMessage> {
+    let UnwrappedGift { sender, rumor } = match UnwrappedGift::from_gift_wrap(keys, gift_wrap) {
+        Ok(unwrapped) => unwrapped,
+        Err(e) => {
+            debug!("Failed to unwrap gift wrap: {}", e);
+            return None;
+        }
+    };
+    if rumor.kind != Kind::PrivateDirectMessage || rumor.pubkey != sender || rumor.created_at < since {
+        return None;
+    }
+    Some(PrivateMessage {
+        sender,
+        id: rumor.id,
+        content: rumor.content,
+    })
+}
+
 /// Starts listening for admin commands and processes them.
 pub async fn run_admin_listener(
     client: Client,
     keys: Keys,
     state: SharedDvmState,
     config: Arc<Config>,
-    config_notify: Arc<Notify>,
+    config_watch: ConfigWatch,
+    restart: Arc<Notify>,
+    processor: Arc<VideoProcessor>,
 ) {
-    let handler = AdminHandler::new(state.clone(), client.clone(), config, config_notify);
+    let handler = AdminHandler::new(state.clone(), client.clone(), config, config_watch)
+        .with_restart(restart)
+        .with_processor(processor);
 
-    // Subscribe to kind 24207 events addressed to us
+    // Subscribe to kind 24207 events and NIP-17 gift wraps addressed to us
+    let started = Timestamp::now();
     let filter = Filter::new()
         .kind(ADMIN_RPC_KIND)
         .pubkey(keys.public_key())
-        .since(Timestamp::now());
+        .since(started);
+    let dm_filter = Filter::new()
+        .kind(Kind::GiftWrap)
+        .pubkey(keys.public_key())
+        .since(started - GIFT_WRAP_LOOKBACK_SECS);
 
     // Wait for at least one relay to connect before subscribing
     let mut connected = false;
@@ -60,7 +116,7 @@ pub async fn run_admin_listener(
     // Try to subscribe with retries
     let mut subscribed = false;
     for i in 0..5 {
-        match client.subscribe(vec![filter.clone()], None).await {
+        match client.subscribe(vec![filter.clone(), dm_filter.clone()], None).await {
             Ok(_) => {
                 subscribed = true;
                 break;
@@ -77,7 +133,10 @@ pub async fn run_admin_listener(
         return;
     }
 
-    info!("Listening for admin commands (kind 24207)...");
+    info!("Listening for admin commands (kind 24207 and NIP-17 direct messages)...");
+
+    // Gift wraps come from every relay that stored them
+    let seen: Mutex<HashSet<EventId>> = Mutex::new(HashSet::new());
 
     // Handle incoming events
     client
@@ -85,6 +144,11 @@ pub async fn run_admin_listener(
             if let RelayPoolNotification::Event { event, .. } = notification {
                 if event.kind == ADMIN_RPC_KIND {
                     handle_admin_event(&event, &keys, &handler, &client, &state).await;
+                } else if event.kind == Kind::GiftWrap && seen.lock().await.insert(event.id) {
+                    if let Some(message) = unwrap_private_message(&keys, &event, started) {
+                        let channel = Channel::PrivateMessage { reply_to: message.id };
+                        handle_admin_request(&message.content, message.sender, channel, &keys, &handler, &client, &state).await;
+                    }
                 }
             }
             Ok(false) // Continue listening
@@ -109,8 +173,20 @@ async fn handle_admin_event(
         }
     };
 
+    handle_admin_request(&content, event.pubkey, Channel::Rpc, keys, handler, client, state).await;
+}
+
+async fn handle_admin_request(
+    content: &str,
+    sender: PublicKey,
+    channel: Channel,
+    keys: &Keys,
+    handler: &AdminHandler,
+    client: &Client,
+    state: &SharedDvmState,
+) {
     // Parse v2 request format
-    let request: AdminRequest = match parse_request(&content) {
+    let request: AdminRequest = match parse_request(content) {
         Ok(req) => req,
         Err(e) => {
             debug!("Failed to parse admin request: {}", e);
@@ -125,7 +201,7 @@ async fn handle_admin_event(
         Ok(cmd) => {
             info!(
                 "Received admin command from {}: {:?}",
-                event.pubkey.to_bech32().unwrap_or_default(),
+                sender.to_bech32().unwrap_or_default(),
                 cmd
             );
             cmd
@@ -139,7 +215,7 @@ async fn handle_admin_event(
                 error: Some(e),
             };
             if let Ok(json) = serde_json::to_string(&wire) {
-                if let Err(e) = send_admin_response(client, keys, &event.pubkey, &json, state).await {
+                if let Err(e) = send_admin_response(client, keys, &sender, channel, &json, state).await {
                     error!("Failed to send error response: {}", e);
                 }
             }
@@ -148,7 +224,7 @@ async fn handle_admin_event(
     };
 
     // Process command
-    let response = handler.handle(command, event.pubkey).await;
+    let response = handler.handle(command, sender).await;
 
     // Wrap in v2 wire format
     let wire = AdminResponseWire::from_response(request_id, response);
@@ -161,7 +237,7 @@ async fn handle_admin_event(
     };
 
     // Encrypt and send reply
-    if let Err(e) = send_admin_response(client, keys, &event.pubkey, &response_json, state).await {
+    if let Err(e) = send_admin_response(client, keys, &sender, channel, &response_json, state).await {
         error!("Failed to send response: {}", e);
     }
 }
@@ -170,9 +246,20 @@ async fn send_admin_response(
     client: &Client,
     keys: &Keys,
     recipient: &PublicKey,
+    channel: Channel,
     content: &str,
     state: &SharedDvmState,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Only send to DVM operation relays, not index relays like purplepag.es
+    let relays = dvm_relay_urls(state).await;
+
+    if let Channel::PrivateMessage { reply_to } = channel {
+        client
+            .send_private_msg_to(relays.iter().map(|s| s.as_str()), *recipient, content, reply_to)
+            .await?;
+        return Ok(());
+    }
+
     let encrypted = nip44::encrypt(
         keys.secret_key(),
         recipient,
@@ -183,8 +270,6 @@ async fn send_admin_response(
     let tags = vec![Tag::public_key(*recipient)];
     let event = EventBuilder::new(ADMIN_RPC_KIND, encrypted, tags).to_event(keys)?;
 
-    // Only send to DVM operation relays, not index relays like purplepag.es
-    let relays = dvm_relay_urls(state).await;
     client
         .send_event_to(relays.iter().map(|s| s.as_str()), event)
         .await?;
@@ -211,4 +296,31 @@ mod tests {
         let result = parse_request("not json");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_unwrap_private_message() {
+        let admin = Keys::generate();
+        let dvm = Keys::generate();
+        let json = r#"{"id":"1","method":"status","params":{}}"#;
+        let wrap = |author: PublicKey, rumor: EventBuilder| {
+            EventBuilder::gift_wrap(&admin, &dvm.public_key(), rumor.to_unsigned_event(author), None).unwrap()
+        };
+        let since = Timestamp::now() - 60;
+
+        let dm = wrap(admin.public_key(), EventBuilder::private_msg_rumor(dvm.public_key(), json, None));
+        let message = unwrap_private_message(&dvm, &dm, since).unwrap();
+        assert_eq!(message.sender, admin.public_key());
+        assert_eq!(message.content, json);
+        assert!(parse_request(&message.content).is_ok());
+
+        // Messages from before startup, for someone else, or of another kind
+        assert_eq!(unwrap_private_message(&dvm, &dm, Timestamp::now() + 60), None);
+        assert_eq!(unwrap_private_message(&Keys::generate(), &dm, since), None);
+        let note = wrap(admin.public_key(), EventBuilder::text_note(json, []));
+        assert_eq!(unwrap_private_message(&dvm, &note, since), None);
+
+        // A rumor claiming to come from someone other than the seal's signer
+        let forged = wrap(Keys::generate().public_key(), EventBuilder::private_msg_rumor(dvm.public_key(), json, None));
+        assert_eq!(unwrap_private_message(&dvm, &forged, since), None);
+    }
 }
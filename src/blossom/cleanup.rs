@@ -1,4 +1,5 @@
 use chrono::{Duration, Utc};
+use std::collections::HashSet;
 use std::sync::Arc;
 use tokio::time::{interval, Duration as TokioDuration};
 use tracing::{debug, error, info, warn};
@@ -32,7 +33,12 @@ impl BlobCleanup {
         }
     }
 
-    /// Clean up expired blobs from all Blossom servers
+    /// Clean up expired blobs from all Blossom servers.
+    ///
+    /// Blobs whose retention was extended are kept until the expiry recorded
+    /// in the retention ledger; everything else expires `blob_expiration_days`
+    /// after upload. Blobs referenced by a job that completed within that
+    /// window, or by a pinned job, are never deleted.
     pub async fn cleanup_expired_blobs(&self) -> Result<usize, crate::error::BlossomError> {
         let (expiration_days, servers) = {
             let state = self.state.read().await;
@@ -48,6 +54,12 @@ impl BlobCleanup {
 
         let expiration_threshold = Utc::now() - Duration::days(expiration_days as i64);
         let threshold_ts = expiration_threshold.timestamp();
+        let protected = self
+            .state
+            .read()
+            .await
+            .references
+            .protected(threshold_ts.max(0) as u64);
 
         info!(
             threshold = %expiration_threshold,
@@ -58,7 +70,7 @@ impl BlobCleanup {
         let mut total_deleted = 0;
 
         for server in &servers {
-            match self.cleanup_server(server, threshold_ts).await {
+            match self.cleanup_server(server, threshold_ts, &protected).await {
                 Ok(count) => {
                     total_deleted += count;
                     debug!(server = %server, deleted = count, "Server cleanup complete");
@@ -69,6 +81,23 @@ impl BlobCleanup {
             }
         }
 
+        // Entries past their expiry were deleted above (or fall back to the
+        // default window next run), so the ledger only tracks live blobs
+        {
+            let mut state = self.state.write().await;
+            let pruned = state.retention.prune(Utc::now().timestamp() as u64);
+            if pruned > 0 {
+                if let Err(e) = state.retention.save() {
+                    warn!(error = %e, "Failed to save retention ledger");
+                }
+            }
+            if state.references.prune(threshold_ts.max(0) as u64) > 0 {
+                if let Err(e) = state.references.save() {
+                    warn!(error = %e, "Failed to save reference index");
+                }
+            }
+        }
+
         info!(total_deleted = total_deleted, "Blob cleanup complete");
         Ok(total_deleted)
     }
@@ -77,10 +106,24 @@ impl BlobCleanup {
         &self,
         server: &url::Url,
         threshold_ts: i64,
+        protected: &HashSet<String>,
     ) -> Result<usize, crate::error::BlossomError> {
         let blobs = self.client.list_blobs(server).await?;
 
-        let expired: Vec<_> = blobs.iter().filter(|b| b.uploaded < threshold_ts).collect();
+        let now = Utc::now().timestamp().max(0) as u64;
+        let threshold = threshold_ts.max(0) as u64;
+        let expired: Vec<_> = {
+            let state = self.state.read().await;
+            blobs
+                .iter()
+                .filter(|b| !protected.contains(&b.sha256))
+                .filter(|b| {
+                    state
+                        .retention
+                        .is_expired(&b.sha256, b.uploaded.max(0) as u64, now, threshold)
+                })
+                .collect()
+        };
 
         debug!(
             server = %server,
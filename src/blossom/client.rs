@@ -1,8 +1,10 @@
+use futures::future::join_all;
+use futures::stream::{FuturesUnordered, StreamExt};
 use regex::Regex;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
@@ -10,17 +12,20 @@ use std::task::{Context, Poll};
 use std::time::{Duration, Instant};
 use tokio::fs::File;
 use tokio::io::{AsyncRead, ReadBuf};
+use tokio::sync::{mpsc, oneshot, Semaphore};
 use tokio_util::io::ReaderStream;
 use tracing::{debug, error, info, warn};
 use url::Url;
 
 use crate::blossom::auth::create_upload_auth_token;
+use crate::blossom::replication::{plan, Replication};
 use crate::config::Config;
-use crate::dvm::events::{HlsResult, StreamPlaylist};
+use crate::dvm::events::{AudioRendition, HlsResult, PartialResult, StreamPlaylist};
 use crate::dvm_state::SharedDvmState;
 use crate::error::BlossomError;
+use crate::util::hash::hash_bytes;
 use crate::util::hash_file;
-use crate::video::playlist::PlaylistRewriter;
+use crate::video::playlist::{lowest_variant, resolution_label, LowestVariant, PlaylistRewriter};
 use crate::video::TransformResult;
 
 /// A wrapper around an AsyncRead that tracks bytes read via an atomic counter
@@ -52,7 +57,7 @@ impl<R: AsyncRead + Unpin> AsyncRead for ProgressReader<R> {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct BlobDescriptor {
     /// Publicly accessible URL to the blob
     pub url: String,
@@ -67,10 +72,89 @@ pub struct BlobDescriptor {
     pub uploaded: i64,
 }
 
+/// Maximum number of in-flight blob PUTs across all jobs
+const MAX_CONCURRENT_UPLOADS: usize = 4;
+
+/// Attempts per blob per server before giving up on that server
+const UPLOAD_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry; doubles with each further attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+
+/// Blob uploaded by health probes; always the same, so servers store it once
+const PROBE_BLOB: &[u8] = b"nostube-transcode blossom health probe\n";
+
+/// How long a health probe upload may take before the server counts as down
+const PROBE_TIMEOUT: Duration = Duration::from_secs(20);
+
+fn retry_delay(attempt: u32) -> Duration {
+    RETRY_BASE_DELAY * 2u32.pow(attempt.saturating_sub(1))
+}
+
+/// Whether a failed PUT is worth repeating: network errors, timeouts,
+/// server-side failures and corrupted transfers. Auth and policy rejections
+/// (other 4xx) won't change on retry.
+fn is_retryable(err: &BlossomError) -> bool {
+    match err {
+        BlossomError::Http(_) | BlossomError::ChecksumMismatch { .. } => true,
+        BlossomError::Rejected { status, .. } => {
+            *status >= 500 || *status == 408 || *status == 429
+        }
+        _ => false,
+    }
+}
+
+/// Check the server stored exactly the bytes we sent
+fn verify_descriptor(blob: &BlobDescriptor, sha256: &str, size: u64) -> Result<(), BlossomError> {
+    if !blob.sha256.eq_ignore_ascii_case(sha256) {
+        return Err(BlossomError::ChecksumMismatch {
+            expected: sha256.to_string(),
+            actual: blob.sha256.clone(),
+        });
+    }
+    if blob.size != size {
+        return Err(BlossomError::ChecksumMismatch {
+            expected: format!("{} bytes", size),
+            actual: format!("{} bytes", blob.size),
+        });
+    }
+    Ok(())
+}
+
+/// Outcome of uploading one blob to one server
+struct ServerUpload {
+    server: Url,
+    result: Result<BlobDescriptor, BlossomError>,
+    duration: Duration,
+}
+
+/// Keep successful uploads in server order; fail only if every server failed
+fn collect_uploads(outcomes: Vec<ServerUpload>) -> Result<Vec<BlobDescriptor>, BlossomError> {
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+
+    for outcome in outcomes {
+        match outcome.result {
+            Ok(blob) => results.push(blob),
+            Err(e) => errors.push(format!("{}: {}", outcome.server, e)),
+        }
+    }
+
+    if results.is_empty() {
+        return Err(BlossomError::UploadFailed(format!(
+            "All server uploads failed: [{}]",
+            errors.join(", ")
+        )));
+    }
+
+    Ok(results)
+}
+
 pub struct BlossomClient {
     config: Arc<Config>,
     state: SharedDvmState,
     http: Client,
+    upload_permits: Semaphore,
 }
 
 impl BlossomClient {
@@ -78,19 +162,42 @@ impl BlossomClient {
         Self {
             config,
             state,
-            http: Client::new(),
+            http: crate::util::proxy::http_client(),
+            upload_permits: Semaphore::new(MAX_CONCURRENT_UPLOADS),
         }
     }
 
-    /// Get current blossom servers from live state (parses strings to Urls)
+    /// Get current blossom servers from live state (parses strings to Urls),
+    /// healthiest first and without servers failing their health checks
     async fn blossom_servers(&self) -> Vec<Url> {
         let state = self.state.read().await;
-        state
+        let servers = state
             .config
             .blossom_servers
             .iter()
             .filter_map(|s| Url::parse(s).ok())
-            .collect()
+            .collect();
+        state.blossom_health.order(servers)
+    }
+
+    /// Upload the health probe blob to `server`, returning how long it took
+    pub async fn probe(&self, server: &Url) -> Result<Duration, BlossomError> {
+        let sha256 = hash_bytes(PROBE_BLOB);
+        let size = PROBE_BLOB.len() as u64;
+        let start = Instant::now();
+        let upload = self.put_blob(
+            server,
+            Path::new("health-probe"),
+            &sha256,
+            size,
+            "text/plain",
+            reqwest::Body::from(PROBE_BLOB),
+        );
+        let blob = tokio::time::timeout(PROBE_TIMEOUT, upload)
+            .await
+            .map_err(|_| BlossomError::UploadFailed("health probe timed out".to_string()))??;
+        verify_descriptor(&blob, &sha256, size)?;
+        Ok(start.elapsed())
     }
 
     /// Get the number of configured Blossom servers
@@ -127,43 +234,22 @@ impl BlossomClient {
 
         debug!(path = %path.display(), sha256 = %sha256, "Uploading file to all servers");
 
-        let mut results = Vec::new();
-        let mut errors = Vec::new();
-
-        let servers = self.blossom_servers().await;
-        for server in &servers {
-            let upload_start = Instant::now();
-            match self
-                .upload_to_server(server, path, &sha256, file_size, mime_type)
-                .await
-            {
-                Ok(blob) => {
-                    let upload_duration = upload_start.elapsed();
-                    on_progress(file_size, upload_duration);
-                    info!(
-                        url = %blob.url,
-                        sha256 = %blob.sha256,
-                        server = %server,
-                        duration_ms = upload_duration.as_millis(),
-                        "File uploaded successfully"
-                    );
-                    results.push(blob);
-                }
-                Err(e) => {
-                    warn!(server = %server, error = %e, "Upload failed");
-                    errors.push(format!("{}: {}", server, e));
-                }
-            }
-        }
-
-        if results.is_empty() {
-            return Err(BlossomError::UploadFailed(format!(
-                "All server uploads failed: [{}]",
-                errors.join(", ")
-            )));
-        }
-
-        Ok(results)
+        let outcomes = self
+            .upload_to_all_servers(
+                path,
+                &sha256,
+                file_size,
+                mime_type,
+                || Arc::new(AtomicU64::new(0)),
+                |outcome| {
+                    if outcome.result.is_ok() {
+                        on_progress(file_size, outcome.duration);
+                    }
+                },
+            )
+            .await;
+
+        collect_uploads(outcomes)
     }
 
     /// Upload a file to Blossom (first successful server)
@@ -179,6 +265,45 @@ impl BlossomClient {
             .ok_or_else(|| BlossomError::UploadFailed("No upload results available".into()))
     }
 
+    /// Upload a file whose SHA-256 is already known to all configured Blossom servers
+    ///
+    /// Saves re-reading the file for callers that hashed it themselves.
+    /// Returns list of successful uploads (at least one required)
+    pub async fn upload_hashed_file_to_all(
+        &self,
+        path: &Path,
+        sha256: &str,
+        file_size: u64,
+        mime_type: &str,
+    ) -> Result<Vec<BlobDescriptor>, BlossomError> {
+        let outcomes = self
+            .upload_to_all_servers(
+                path,
+                sha256,
+                file_size,
+                mime_type,
+                || Arc::new(AtomicU64::new(0)),
+                |_| {},
+            )
+            .await;
+        collect_uploads(outcomes)
+    }
+
+    /// Upload a file whose SHA-256 is already known (first successful server)
+    pub async fn upload_hashed_file(
+        &self,
+        path: &Path,
+        sha256: &str,
+        file_size: u64,
+        mime_type: &str,
+    ) -> Result<BlobDescriptor, BlossomError> {
+        self.upload_hashed_file_to_all(path, sha256, file_size, mime_type)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| BlossomError::UploadFailed("No upload results available".into()))
+    }
+
     /// Upload a file to all configured Blossom servers with real-time progress tracking
     /// The bytes_uploaded counter is updated in real-time as bytes are sent
     /// Returns list of successful uploads (at least one required)
@@ -194,57 +319,29 @@ impl BlossomClient {
 
         debug!(path = %path.display(), sha256 = %sha256, "Uploading file to all servers");
 
-        let mut results = Vec::new();
-        let mut errors = Vec::new();
-
-        let servers = self.blossom_servers().await;
-        for server in &servers {
-            let upload_start = Instant::now();
-            // Reset the counter for each server (since we're uploading the full file again)
-            let server_bytes = Arc::new(AtomicU64::new(0));
-            match self
-                .upload_to_server_with_progress(
-                    server,
-                    path,
-                    &sha256,
-                    file_size,
-                    mime_type,
-                    server_bytes.clone(),
-                )
-                .await
-            {
-                Ok(blob) => {
-                    let upload_duration = upload_start.elapsed();
-                    // Add the bytes from this server to the total
-                    bytes_uploaded.fetch_add(file_size, Ordering::Relaxed);
-                    info!(
-                        url = %blob.url,
-                        sha256 = %blob.sha256,
-                        server = %server,
-                        duration_ms = upload_duration.as_millis(),
-                        "File uploaded successfully"
-                    );
-                    results.push(blob);
-                }
-                Err(e) => {
-                    warn!(server = %server, error = %e, "Upload failed");
-                    errors.push(format!("{}: {}", server, e));
-                }
-            }
-        }
-
-        if results.is_empty() {
-            return Err(BlossomError::UploadFailed(format!(
-                "All server uploads failed: [{}]",
-                errors.join(", ")
-            )));
-        }
-
-        Ok(results)
+        // Each server gets its own counter (the full file is sent to each);
+        // the total advances as each server's upload completes
+        let outcomes = self
+            .upload_to_all_servers(
+                path,
+                &sha256,
+                file_size,
+                mime_type,
+                || Arc::new(AtomicU64::new(0)),
+                |outcome| {
+                    if outcome.result.is_ok() {
+                        bytes_uploaded.fetch_add(file_size, Ordering::Relaxed);
+                    }
+                },
+            )
+            .await;
+
+        collect_uploads(outcomes)
     }
 
-    /// Upload a file to a single server with progress tracking
+    /// Upload a file to all configured servers with progress tracking
     /// The bytes_uploaded counter is updated in real-time as bytes are sent
+    /// (summed across servers, since the uploads run concurrently)
     pub async fn upload_to_server_streaming_progress(
         &self,
         path: &Path,
@@ -257,80 +354,213 @@ impl BlossomClient {
 
         debug!(path = %path.display(), sha256 = %sha256, "Uploading file with progress tracking");
 
-        let mut results = Vec::new();
-        let mut errors = Vec::new();
+        let outcomes = self
+            .upload_to_all_servers(
+                path,
+                &sha256,
+                file_size,
+                mime_type,
+                || bytes_uploaded.clone(),
+                |_| {},
+            )
+            .await;
+
+        collect_uploads(outcomes)
+    }
 
+    /// PUT the same blob to every configured server concurrently.
+    ///
+    /// Each PUT holds a permit from the client-wide upload budget, so total
+    /// in-flight uploads stay bounded across concurrent jobs (see
+    /// [`Self::upload_with_dedup_retry`]). `counter_for` supplies the byte counter
+    /// for each server's upload, and `on_done` is called as each server
+    /// finishes. Outcomes are returned in server order.
+    async fn upload_to_all_servers(
+        &self,
+        path: &Path,
+        sha256: &str,
+        file_size: u64,
+        mime_type: &str,
+        counter_for: impl Fn() -> Arc<AtomicU64>,
+        mut on_done: impl FnMut(&ServerUpload),
+    ) -> Vec<ServerUpload> {
         let servers = self.blossom_servers().await;
-        for server in &servers {
-            let upload_start = Instant::now();
-            match self
+
+        let mut uploads: FuturesUnordered<_> = servers
+            .into_iter()
+            .enumerate()
+            .map(|(index, server)| {
+                let counter = counter_for();
+                async move {
+                    let upload_start = Instant::now();
+                    let result = self
+                        .upload_with_dedup_retry(&server, path, sha256, file_size, mime_type, counter)
+                        .await;
+                    let duration = upload_start.elapsed();
+
+                    match &result {
+                        Ok(blob) => info!(
+                            url = %blob.url,
+                            sha256 = %blob.sha256,
+                            server = %server,
+                            duration_ms = duration.as_millis(),
+                            "File uploaded successfully"
+                        ),
+                        Err(e) => warn!(server = %server, error = %e, "Upload failed"),
+                    }
+
+                    let outcome = ServerUpload {
+                        server,
+                        result,
+                        duration,
+                    };
+                    (index, outcome)
+                }
+            })
+            .collect();
+
+        let mut outcomes = Vec::with_capacity(uploads.len());
+        while let Some((index, outcome)) = uploads.next().await {
+            on_done(&outcome);
+            outcomes.push((index, outcome));
+        }
+        outcomes.sort_by_key(|(index, _)| *index);
+        outcomes.into_iter().map(|(_, outcome)| outcome).collect()
+    }
+
+    /// Upload one blob to one server, retrying transient failures with dedup.
+    ///
+    /// This is not a resumable upload: Blossom has no chunked or ranged PUT,
+    /// so a retry resends the whole blob. HLS output is already split into
+    /// segment-sized blobs, which keeps a failed attempt cheap; a large MP4 is
+    /// sent again in full. Before resending, the server is asked whether it
+    /// already has the blob, in case only the response to a completed PUT was
+    /// lost.
+    /// Each attempt takes an upload permit and gives it back before backing
+    /// off, so a struggling server doesn't hold up other jobs' uploads.
+    async fn upload_with_dedup_retry(
+        &self,
+        server: &Url,
+        path: &Path,
+        sha256: &str,
+        size: u64,
+        mime_type: &str,
+        bytes_uploaded: Arc<AtomicU64>,
+    ) -> Result<BlobDescriptor, BlossomError> {
+        let mut attempt = 1;
+        loop {
+            let permit = self.upload_permits.acquire().await.ok();
+            let outcome = self
                 .upload_to_server_with_progress(
                     server,
                     path,
-                    &sha256,
-                    file_size,
+                    sha256,
+                    size,
                     mime_type,
                     bytes_uploaded.clone(),
                 )
-                .await
-            {
-                Ok(blob) => {
-                    let upload_duration = upload_start.elapsed();
-                    info!(
-                        url = %blob.url,
-                        sha256 = %blob.sha256,
-                        server = %server,
-                        duration_ms = upload_duration.as_millis(),
-                        "File uploaded successfully"
-                    );
-                    results.push(blob);
-                }
-                Err(e) => {
-                    warn!(server = %server, error = %e, "Upload failed");
-                    errors.push(format!("{}: {}", server, e));
-                }
+                .await;
+            drop(permit);
+            let err = match outcome {
+                Ok(blob) => return Ok(blob),
+                Err(e) if attempt < UPLOAD_ATTEMPTS && is_retryable(&e) => e,
+                Err(e) => return Err(e),
+            };
+
+            let delay = retry_delay(attempt);
+            warn!(
+                server = %server,
+                sha256 = %sha256,
+                attempt = attempt,
+                delay_ms = delay.as_millis(),
+                error = %err,
+                "Upload attempt failed, retrying"
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+
+            if let Some(blob) = self.existing_blob(server, sha256, size, mime_type).await {
+                info!(server = %server, sha256 = %sha256, "Blob already on server, skipping re-upload");
+                bytes_uploaded.fetch_add(size, Ordering::Relaxed);
+                return Ok(blob);
             }
         }
+    }
 
-        if results.is_empty() {
-            return Err(BlossomError::UploadFailed(format!(
-                "All server uploads failed: [{}]",
-                errors.join(", ")
-            )));
+    /// Look up a blob on a server (BUD-01 `HEAD /<sha256>`)
+    async fn existing_blob(
+        &self,
+        server: &Url,
+        sha256: &str,
+        size: u64,
+        mime_type: &str,
+    ) -> Option<BlobDescriptor> {
+        let url = server.join(&format!("/{}", sha256)).ok()?;
+        let response = self.http.head(url.clone()).send().await.ok()?;
+        if !response.status().is_success() {
+            return None;
         }
 
-        Ok(results)
+        let stored_size = response
+            .headers()
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(size);
+        if stored_size != size {
+            return None;
+        }
+
+        Some(BlobDescriptor {
+            url: url.to_string(),
+            sha256: sha256.to_string(),
+            size,
+            mime_type: mime_type.to_string(),
+            uploaded: chrono::Utc::now().timestamp(),
+        })
     }
 
-    async fn upload_to_server(
+    /// Single upload attempt. Bytes sent by a failed attempt are taken back
+    /// out of `bytes_uploaded`, since a retry sends them again.
+    async fn upload_to_server_with_progress(
         &self,
         server: &Url,
         path: &Path,
         sha256: &str,
         size: u64,
         mime_type: &str,
+        bytes_uploaded: Arc<AtomicU64>,
     ) -> Result<BlobDescriptor, BlossomError> {
-        let dummy_counter = Arc::new(AtomicU64::new(0));
-        self.upload_to_server_with_progress(server, path, sha256, size, mime_type, dummy_counter)
+        let file = File::open(path).await?;
+        let sent = Arc::new(AtomicU64::new(0));
+        let progress_reader = ProgressReader::new(
+            ProgressReader::new(file, sent.clone()),
+            bytes_uploaded.clone(),
+        );
+        let body = reqwest::Body::wrap_stream(ReaderStream::new(progress_reader));
+
+        let result = self
+            .put_blob(server, path, sha256, size, mime_type, body)
             .await
+            .and_then(|blob| verify_descriptor(&blob, sha256, size).map(|_| blob));
+
+        if result.is_err() {
+            bytes_uploaded.fetch_sub(sent.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        result
     }
 
-    async fn upload_to_server_with_progress(
+    async fn put_blob(
         &self,
         server: &Url,
         path: &Path,
         sha256: &str,
         size: u64,
         mime_type: &str,
-        bytes_uploaded: Arc<AtomicU64>,
+        body: reqwest::Body,
     ) -> Result<BlobDescriptor, BlossomError> {
         let auth_token = create_upload_auth_token(&self.config.nostr_keys, size, sha256)?;
 
-        let file = File::open(path).await?;
-        let progress_reader = ProgressReader::new(file, bytes_uploaded);
-        let stream = ReaderStream::new(progress_reader);
-        let body = reqwest::Body::wrap_stream(stream);
-
         let url = server.join("/upload")?;
 
         debug!(
@@ -375,7 +605,10 @@ impl BlossomClient {
                 sha256 = %sha256,
                 "Blossom upload failed"
             );
-            return Err(BlossomError::UploadFailed(format!("{}: {}", status, text)));
+            return Err(BlossomError::Rejected {
+                status: status.as_u16(),
+                message: text,
+            });
         }
 
         let response_text = response.text().await?;
@@ -408,11 +641,70 @@ impl BlossomClient {
             .await
     }
 
+    /// Upload HLS segments as they arrive on `segments`, until the channel closes.
+    ///
+    /// Used while FFmpeg is still encoding. Failures are only logged: the
+    /// segment is uploaded again with the rest of the output. Returns the
+    /// hashes of the segments uploaded, with the bytes that took.
+    pub async fn upload_segments_as_finished<F>(
+        &self,
+        mut segments: mpsc::UnboundedReceiver<PathBuf>,
+        mut on_progress: F,
+    ) -> HashSet<String>
+    where
+        F: FnMut(u64, Duration),
+    {
+        let mut uploaded = HashSet::new();
+        while let Some(path) = segments.recv().await {
+            let upload_start = Instant::now();
+            let outcome = async {
+                let file_size = tokio::fs::metadata(&path).await?.len();
+                let sha256 = hash_file(&path).await?;
+                self.upload_hashed_file(&path, &sha256, file_size, "video/iso.segment")
+                    .await?;
+                Ok::<_, BlossomError>((sha256, file_size))
+            }
+            .await;
+            match outcome {
+                Ok((sha256, file_size)) => {
+                    debug!(path = %path.display(), "Uploaded HLS segment during encoding");
+                    on_progress(file_size, upload_start.elapsed());
+                    uploaded.insert(sha256);
+                }
+                Err(e) => {
+                    warn!(path = %path.display(), error = %e, "Early segment upload failed, retrying after encoding");
+                }
+            }
+        }
+        uploaded
+    }
+
     /// Upload all HLS output files to Blossom with progress callback
     /// The callback is called after each file upload with (bytes_uploaded, upload_duration)
     pub async fn upload_hls_output_with_progress<F>(
         &self,
         result: &TransformResult,
+        on_progress: F,
+    ) -> Result<HlsResult, BlossomError>
+    where
+        F: FnMut(u64, Duration),
+    {
+        self.upload_hls_output_skipping(result, &HashSet::new(), None, on_progress)
+            .await
+    }
+
+    /// Upload HLS output like [`Self::upload_hls_output_with_progress`],
+    /// leaving out segments whose hash is in `uploaded`.
+    ///
+    /// With `preview`, the lowest rendition is uploaded first, followed by a
+    /// master playlist with only that rendition, which is sent to `preview`
+    /// while the rest of the ladder uploads. Nothing is sent when the rest
+    /// was already uploaded.
+    pub async fn upload_hls_output_skipping<F>(
+        &self,
+        result: &TransformResult,
+        uploaded: &HashSet<String>,
+        preview: Option<oneshot::Sender<PartialResult>>,
         mut on_progress: F,
     ) -> Result<HlsResult, BlossomError>
     where
@@ -426,6 +718,21 @@ impl BlossomClient {
 
         // Regex to extract stream index from segment filenames (e.g., "stream_0_001.m4s" -> "0")
         let stream_idx_regex = Regex::new(r"^(?:stream_|init_)(\d+)").ok();
+        let stream_playlist = |filename: &str| {
+            stream_idx_regex
+                .as_ref()
+                .and_then(|re| re.captures(filename))
+                .map(|caps| format!("stream_{}.m3u8", &caps[1]))
+        };
+        let filename = |path: &Path| -> String {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_else(|| {
+                    warn!(path = %path.display(), "Output path has no valid filename");
+                    ""
+                })
+                .to_string()
+        };
 
         info!(
             segment_count = result.segment_paths.len(),
@@ -433,18 +740,53 @@ impl BlossomClient {
             "Starting HLS upload to Blossom"
         );
 
-        // Upload all segment files first
-        let total_segments = result.segment_paths.len();
-        for (idx, segment_path) in result.segment_paths.iter().enumerate() {
-            let sha256 = hash_file(segment_path).await?;
-            let filename = segment_path
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or_else(|| {
-                    warn!(path = %segment_path.display(), "Segment path has no valid filename");
-                    ""
-                });
+        let master_content = tokio::fs::read_to_string(&result.master_playlist_path).await?;
+
+        // Segments of the preview's streams go first
+        let lowest = preview.as_ref().and_then(|_| lowest_variant(&master_content));
+        let in_preview = |name: &str| {
+            lowest.as_ref().is_some_and(|l| {
+                stream_playlist(name).is_some_and(|p| l.playlists.contains(&p))
+            })
+        };
+        let mut segments: Vec<(&PathBuf, &String)> = result
+            .segment_paths
+            .iter()
+            .zip(&result.segment_hashes)
+            .collect();
+        segments.sort_by_key(|(path, _)| !in_preview(&filename(path)));
+        let preview_segments = segments
+            .iter()
+            .filter(|(path, _)| in_preview(&filename(path)))
+            .count();
+        // A preview is only worth sending while there is more left to upload
+        let mut preview = preview.filter(|_| {
+            segments[preview_segments..]
+                .iter()
+                .any(|(_, sha256)| !uploaded.contains(*sha256))
+        });
+
+        let total_segments = segments.len();
+        for (idx, (segment_path, sha256)) in segments.into_iter().enumerate() {
+            if idx == preview_segments {
+                if let (Some(tx), Some(lowest)) = (preview.take(), &lowest) {
+                    let partial = self
+                        .upload_preview_master(
+                            lowest,
+                            result,
+                            &rewriter,
+                            &mut playlist_hashes,
+                            &mut stream_playlist_urls,
+                            &mut stream_sizes,
+                            &mut total_size,
+                            &mut on_progress,
+                        )
+                        .await?;
+                    let _ = tx.send(partial);
+                }
+            }
 
+            let filename = filename(segment_path);
             debug!(
                 filename = %filename,
                 index = idx + 1,
@@ -453,108 +795,65 @@ impl BlossomClient {
             );
 
             // Track size per stream
-            let file_size = match tokio::fs::metadata(segment_path).await {
-                Ok(m) => m.len(),
-                Err(e) => {
-                    warn!(path = %segment_path.display(), error = %e, "Failed to get segment file size");
-                    0
-                }
-            };
+            let file_size = tokio::fs::metadata(segment_path).await?.len();
             total_size += file_size;
-
-            // Extract stream index and accumulate size
-            if let Some(caps) = stream_idx_regex
-                .as_ref()
-                .and_then(|re| re.captures(filename))
-            {
-                let stream_idx = &caps[1];
-                let playlist_name = format!("stream_{}.m3u8", stream_idx);
+            if let Some(playlist_name) = stream_playlist(&filename) {
                 *stream_sizes.entry(playlist_name).or_insert(0) += file_size;
             }
 
-            rewriter.add_segment(filename, &sha256);
+            rewriter.add_segment(&filename, sha256);
+            if uploaded.contains(sha256) {
+                debug!(filename = %filename, "HLS segment already uploaded during encoding");
+                continue;
+            }
 
             // Upload the segment and track timing
             let upload_start = Instant::now();
-            self.upload_file(segment_path, "video/iso.segment").await.map_err(|e| {
-                error!(
-                    path = %segment_path.display(),
-                    error = %e,
-                    "Failed to upload segment"
-                );
-                e
-            })?;
+            self.upload_hashed_file(segment_path, sha256, file_size, "video/iso.segment")
+                .await
+                .map_err(|e| {
+                    error!(
+                        path = %segment_path.display(),
+                        error = %e,
+                        "Failed to upload segment"
+                    );
+                    e
+                })?;
             let upload_duration = upload_start.elapsed();
             on_progress(file_size, upload_duration);
         }
 
         // Rewrite and upload stream playlists
         for playlist_path in &result.stream_playlists {
-            let rewritten = rewriter.rewrite_playlist(playlist_path).await?;
-
-            // Write rewritten playlist to temp file
-            let temp_path = playlist_path.with_extension("rewritten.m3u8");
-            tokio::fs::write(&temp_path, &rewritten).await?;
-
-            // Track playlist size
-            let playlist_size = rewritten.len() as u64;
-            total_size += playlist_size;
-
-            let original_name = playlist_path
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or_else(|| {
-                    warn!(path = %playlist_path.display(), "Playlist path has no valid filename");
-                    ""
-                });
-
-            info!(filename = %original_name, "Uploading rewritten HLS stream playlist");
-
-            // Add playlist size to stream total
-            *stream_sizes.entry(original_name.to_string()).or_insert(0) += playlist_size;
-
-            // Upload and track hash with timing
-            let upload_start = Instant::now();
-            let blob = self
-                .upload_file(&temp_path, "application/vnd.apple.mpegurl")
-                .await?;
-            let upload_duration = upload_start.elapsed();
-            on_progress(playlist_size, upload_duration);
-
-            playlist_hashes.insert(original_name.to_string(), blob.sha256);
-            stream_playlist_urls.insert(original_name.to_string(), blob.url);
-
-            // Clean up temp file
-            let _ = tokio::fs::remove_file(&temp_path).await;
+            if playlist_hashes.contains_key(&filename(playlist_path)) {
+                // Uploaded with the preview
+                continue;
+            }
+            self.upload_stream_playlist(
+                playlist_path,
+                &rewriter,
+                &mut playlist_hashes,
+                &mut stream_playlist_urls,
+                &mut stream_sizes,
+                &mut total_size,
+                &mut on_progress,
+            )
+            .await?;
         }
 
-        // Read master playlist to extract resolution info
-        let master_content = tokio::fs::read_to_string(&result.master_playlist_path).await?;
+        // Master playlist resolution info
         let stream_playlists =
             self.parse_stream_resolutions(&master_content, &stream_playlist_urls, &stream_sizes);
+        let audio_tracks =
+            parse_audio_renditions(&master_content, &stream_playlist_urls, &stream_sizes);
 
         // Rewrite and upload master playlist
         let rewritten_master =
             rewriter.rewrite_master_playlist(&master_content, &playlist_hashes)?;
-
-        let temp_master = result.master_playlist_path.with_extension("rewritten.m3u8");
-        tokio::fs::write(&temp_master, &rewritten_master).await?;
-
-        // Add master playlist size to total
-        let master_size = rewritten_master.len() as u64;
-        total_size += master_size;
-
-        info!("Uploading rewritten HLS master playlist");
-
-        let upload_start = Instant::now();
-        let master_blob = self
-            .upload_file(&temp_master, "application/vnd.apple.mpegurl")
+        let (master_blob, master_size) = self
+            .upload_master_playlist(result, &rewritten_master, &mut on_progress)
             .await?;
-        let upload_duration = upload_start.elapsed();
-        on_progress(master_size, upload_duration);
-
-        // Clean up temp file
-        let _ = tokio::fs::remove_file(&temp_master).await;
+        total_size += master_size;
 
         info!(
             url = %master_blob.url,
@@ -565,11 +864,139 @@ impl BlossomClient {
         Ok(HlsResult {
             master_playlist: master_blob.url,
             stream_playlists,
+            audio_tracks,
             total_size_bytes: total_size,
-            encryption_key: Some(result.encryption_key.clone()),
+            // Unencrypted output has no key to publish
+            encryption_key: Some(result.encryption_key.clone()).filter(|k| !k.is_empty()),
+            preview: None,
+            storyboard: None,
+            chapters: None,
+            blurhash: None,
+            dim: None,
+            source: None,
+            input_sha256: None,
+            watch_url: None,
+            segment_hashes: result.segment_hashes.clone(),
+        })
+    }
+
+    /// Upload the playlists of `lowest` and a master playlist with only them
+    #[allow(clippy::too_many_arguments)]
+    async fn upload_preview_master<F>(
+        &self,
+        lowest: &LowestVariant,
+        result: &TransformResult,
+        rewriter: &PlaylistRewriter,
+        playlist_hashes: &mut HashMap<String, String>,
+        stream_playlist_urls: &mut HashMap<String, String>,
+        stream_sizes: &mut HashMap<String, u64>,
+        total_size: &mut u64,
+        on_progress: &mut F,
+    ) -> Result<PartialResult, BlossomError>
+    where
+        F: FnMut(u64, Duration),
+    {
+        for name in &lowest.playlists {
+            let Some(path) = result.stream_playlists.iter().find(|p| p.ends_with(name)) else {
+                continue;
+            };
+            self.upload_stream_playlist(
+                path,
+                rewriter,
+                playlist_hashes,
+                stream_playlist_urls,
+                stream_sizes,
+                total_size,
+                on_progress,
+            )
+            .await?;
+        }
+        let master = rewriter.rewrite_master_playlist(&lowest.master, playlist_hashes)?;
+        let (blob, size) = self.upload_master_playlist(result, &master, on_progress).await?;
+        *total_size += size;
+        info!(url = %blob.url, resolution = %lowest.resolution, "Uploaded preview master playlist");
+        Ok(PartialResult {
+            master_playlist: blob.url,
+            resolution: lowest.resolution.clone(),
         })
     }
 
+    /// Rewrite one stream playlist to hashed segment names and upload it
+    #[allow(clippy::too_many_arguments)]
+    async fn upload_stream_playlist<F>(
+        &self,
+        playlist_path: &Path,
+        rewriter: &PlaylistRewriter,
+        playlist_hashes: &mut HashMap<String, String>,
+        stream_playlist_urls: &mut HashMap<String, String>,
+        stream_sizes: &mut HashMap<String, u64>,
+        total_size: &mut u64,
+        on_progress: &mut F,
+    ) -> Result<(), BlossomError>
+    where
+        F: FnMut(u64, Duration),
+    {
+        // Stream the rewritten playlist to a temp file
+        let temp_path = playlist_path.with_extension("rewritten.m3u8");
+        let playlist_size = rewriter.rewrite_playlist_to(playlist_path, &temp_path).await?;
+
+        // Track playlist size
+        *total_size += playlist_size;
+
+        let original_name = playlist_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_else(|| {
+                warn!(path = %playlist_path.display(), "Playlist path has no valid filename");
+                ""
+            });
+
+        info!(filename = %original_name, "Uploading rewritten HLS stream playlist");
+
+        // Add playlist size to stream total
+        *stream_sizes.entry(original_name.to_string()).or_insert(0) += playlist_size;
+
+        // Upload and track hash with timing
+        let upload_start = Instant::now();
+        let blob = self
+            .upload_file(&temp_path, "application/vnd.apple.mpegurl")
+            .await?;
+        let upload_duration = upload_start.elapsed();
+        on_progress(playlist_size, upload_duration);
+
+        playlist_hashes.insert(original_name.to_string(), blob.sha256);
+        stream_playlist_urls.insert(original_name.to_string(), blob.url);
+
+        // Clean up temp file
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        Ok(())
+    }
+
+    /// Upload a rewritten master playlist, returning the blob and its size
+    async fn upload_master_playlist<F>(
+        &self,
+        result: &TransformResult,
+        content: &str,
+        on_progress: &mut F,
+    ) -> Result<(BlobDescriptor, u64), BlossomError>
+    where
+        F: FnMut(u64, Duration),
+    {
+        let temp_master = result.master_playlist_path.with_extension("rewritten.m3u8");
+        tokio::fs::write(&temp_master, content).await?;
+        let master_size = content.len() as u64;
+
+        info!("Uploading rewritten HLS master playlist");
+
+        let upload_start = Instant::now();
+        let master_blob = self
+            .upload_file(&temp_master, "application/vnd.apple.mpegurl")
+            .await;
+        let _ = tokio::fs::remove_file(&temp_master).await;
+        on_progress(master_size, upload_start.elapsed());
+        Ok((master_blob?, master_size))
+    }
+
     /// Parse master playlist to extract resolution and codecs for each stream playlist
     fn parse_stream_resolutions(
         &self,
@@ -601,10 +1028,7 @@ impl BlossomClient {
                 if let Some(url) = playlist_urls.get(line) {
                     let resolution = current_resolution
                         .take()
-                        .map(|r| {
-                            // Convert "1280x720" to "720p"
-                            r.split('x').nth(1).map(|h| format!("{}p", h)).unwrap_or(r)
-                        })
+                        .map(|r| resolution_label(&r).unwrap_or(r))
                         .unwrap_or_else(|| "unknown".to_string());
 
                     let size_bytes = stream_sizes.get(line).copied().unwrap_or(0);
@@ -619,6 +1043,7 @@ impl BlossomClient {
                         resolution,
                         size_bytes,
                         mimetype,
+                        audio_bitrate: None,
                     });
                 }
                 current_resolution = None;
@@ -683,4 +1108,281 @@ impl BlossomClient {
 
         Ok(())
     }
+
+    /// Delete a blob uploaded for a job that ended up not using it from
+    /// every server. Blobs another job's result references are kept, and
+    /// failures are only logged: cleanup removes what's left once it expires.
+    pub async fn discard_blob(&self, sha256: &str) {
+        if self.state.read().await.references.is_referenced(sha256) {
+            debug!(sha256 = %sha256, "Keeping blob referenced by another job");
+            return;
+        }
+        for server in self.blossom_servers().await {
+            if let Err(e) = self.delete_blob(&server, sha256).await {
+                debug!(server = %server, sha256 = %sha256, error = %e, "Discarding blob failed");
+            }
+        }
+    }
+
+    /// Size of a blob stored on `server` (BUD-01 `HEAD /<sha256>`), `None`
+    /// if the server doesn't have it
+    pub async fn blob_size(&self, server: &Url, sha256: &str) -> Option<u64> {
+        let url = server.join(&format!("/{}", sha256)).ok()?;
+        let response = self.http.head(url).send().await.ok()?;
+        if !response.status().is_success() {
+            return None;
+        }
+        response
+            .headers()
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+    }
+
+    /// Copy a blob to every configured server that doesn't have it yet.
+    /// Fails only if no configured server has the blob.
+    pub async fn replicate(&self, sha256: &str) -> Result<Replication, BlossomError> {
+        let servers: Vec<Url> = {
+            let state = self.state.read().await;
+            state
+                .config
+                .blossom_servers
+                .iter()
+                .filter_map(|s| Url::parse(s).ok())
+                .collect()
+        };
+        let sizes = join_all(servers.iter().map(|s| self.blob_size(s, sha256))).await;
+        let holdings: Vec<(Url, Option<u64>)> = servers.into_iter().zip(sizes).collect();
+        let (source, size, missing) = plan(&holdings).ok_or_else(|| {
+            BlossomError::UploadFailed(format!("No configured server has blob {}", sha256))
+        })?;
+
+        let mut replication = Replication {
+            sha256: sha256.to_string(),
+            present: holdings
+                .iter()
+                .filter(|(_, size)| size.is_some())
+                .map(|(server, _)| server.to_string())
+                .collect(),
+            ..Default::default()
+        };
+        for server in missing {
+            match self.mirror_blob(&server, &source, sha256, size).await {
+                Ok(_) => {
+                    info!(server = %server, sha256 = %sha256, "Blob replicated");
+                    replication.copied.push(server.to_string());
+                }
+                Err(e) => {
+                    warn!(server = %server, sha256 = %sha256, error = %e, "Blob replication failed");
+                    replication.failed.push(format!("{}: {}", server, e));
+                }
+            }
+        }
+        Ok(replication)
+    }
+
+    /// Have `server` fetch a blob from `source` (BUD-04 `PUT /mirror`),
+    /// streaming it through the DVM when the server can't mirror
+    async fn mirror_blob(
+        &self,
+        server: &Url,
+        source: &Url,
+        sha256: &str,
+        size: u64,
+    ) -> Result<BlobDescriptor, BlossomError> {
+        let source_url = source.join(&format!("/{}", sha256))?;
+        match self
+            .put_mirror(server, &source_url, sha256, size)
+            .await
+            .and_then(|blob| verify_descriptor(&blob, sha256, size).map(|_| blob))
+        {
+            Ok(blob) => return Ok(blob),
+            Err(e) => debug!(server = %server, error = %e, "Mirror request failed, copying through the DVM"),
+        }
+
+        let _permit = self.upload_permits.acquire().await.ok();
+        let response = self.http.get(source_url).send().await?.error_for_status()?;
+        let mime_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        let body = reqwest::Body::wrap_stream(response.bytes_stream());
+        let blob = self
+            .put_blob(server, Path::new(sha256), sha256, size, &mime_type, body)
+            .await?;
+        verify_descriptor(&blob, sha256, size)?;
+        Ok(blob)
+    }
+
+    async fn put_mirror(
+        &self,
+        server: &Url,
+        source_url: &Url,
+        sha256: &str,
+        size: u64,
+    ) -> Result<BlobDescriptor, BlossomError> {
+        let auth_token = create_upload_auth_token(&self.config.nostr_keys, size, sha256)?;
+        let response = self
+            .http
+            .put(server.join("/mirror")?)
+            .header("Authorization", format!("Nostr {}", auth_token))
+            .json(&serde_json::json!({ "url": source_url.as_str() }))
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(BlossomError::Rejected {
+                status: status.as_u16(),
+                message: response.text().await.unwrap_or_default(),
+            });
+        }
+        Ok(response.json().await?)
+    }
+}
+
+/// Parse the alternate audio renditions (`#EXT-X-MEDIA:TYPE=AUDIO`) of a master playlist
+fn parse_audio_renditions(
+    master_content: &str,
+    playlist_urls: &HashMap<String, String>,
+    stream_sizes: &HashMap<String, u64>,
+) -> Vec<AudioRendition> {
+    let Ok(attr_regex) = Regex::new(r#"([A-Z-]+)=("[^"]*"|[^,]*)"#) else {
+        return Vec::new();
+    };
+
+    master_content
+        .lines()
+        .filter(|line| line.starts_with("#EXT-X-MEDIA:"))
+        .filter_map(|line| {
+            let attrs: HashMap<&str, &str> = attr_regex
+                .captures_iter(line)
+                .filter_map(|caps| {
+                    let (Some(key), Some(value)) = (caps.get(1), caps.get(2)) else {
+                        return None;
+                    };
+                    Some((key.as_str(), value.as_str().trim_matches('"')))
+                })
+                .collect();
+            if attrs.get("TYPE") != Some(&"AUDIO") {
+                return None;
+            }
+
+            let uri = *attrs.get("URI")?;
+            Some(AudioRendition {
+                url: playlist_urls.get(uri)?.clone(),
+                name: attrs.get("NAME").unwrap_or(&uri).to_string(),
+                language: attrs.get("LANGUAGE").map(|l| l.to_string()),
+                default: attrs.get("DEFAULT") == Some(&"YES"),
+                size_bytes: stream_sizes.get(uri).copied().unwrap_or(0),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(server: &str, ok: bool) -> ServerUpload {
+        let server = Url::parse(server).unwrap();
+        let result = if ok {
+            Ok(BlobDescriptor {
+                url: server.join("/abc").unwrap().to_string(),
+                sha256: "abc".to_string(),
+                size: 3,
+                mime_type: "video/mp4".to_string(),
+                uploaded: 0,
+            })
+        } else {
+            Err(BlossomError::UploadFailed("503: unavailable".to_string()))
+        };
+        ServerUpload {
+            server,
+            result,
+            duration: Duration::from_millis(10),
+        }
+    }
+
+    #[test]
+    fn test_collect_uploads_keeps_server_order() {
+        let blobs = collect_uploads(vec![
+            outcome("https://a.example.com", true),
+            outcome("https://b.example.com", false),
+            outcome("https://c.example.com", true),
+        ])
+        .unwrap();
+
+        assert_eq!(blobs.len(), 2);
+        assert_eq!(blobs[0].url, "https://a.example.com/abc");
+        assert_eq!(blobs[1].url, "https://c.example.com/abc");
+    }
+
+    #[test]
+    fn test_collect_uploads_all_failed() {
+        let err = collect_uploads(vec![
+            outcome("https://a.example.com", false),
+            outcome("https://b.example.com", false),
+        ])
+        .unwrap_err();
+
+        let msg = err.to_string();
+        assert!(msg.contains("a.example.com"));
+        assert!(msg.contains("b.example.com"));
+    }
+
+    #[test]
+    fn test_retryable_errors() {
+        let rejected = |status| BlossomError::Rejected {
+            status,
+            message: String::new(),
+        };
+        assert!(is_retryable(&rejected(503)));
+        assert!(is_retryable(&rejected(429)));
+        assert!(!is_retryable(&rejected(401)));
+        assert!(!is_retryable(&rejected(413)));
+        assert!(!is_retryable(&BlossomError::AuthFailed("bad key".into())));
+
+        assert_eq!(retry_delay(1), Duration::from_secs(2));
+        assert_eq!(retry_delay(3), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn test_verify_descriptor() {
+        let blob = outcome("https://a.example.com", true).result.unwrap();
+
+        assert!(verify_descriptor(&blob, "ABC", 3).is_ok());
+
+        let err = verify_descriptor(&blob, "abd", 3).unwrap_err();
+        assert!(is_retryable(&err));
+        assert!(verify_descriptor(&blob, "abc", 4).is_err());
+    }
+
+    #[test]
+    fn test_parse_audio_renditions() {
+        let master = r#"#EXTM3U
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="group_audio",NAME="audio_1",DEFAULT=YES,LANGUAGE="eng",URI="stream_1.m3u8"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="group_audio",NAME="audio_2",DEFAULT=NO,URI="stream_2.m3u8"
+#EXT-X-STREAM-INF:BANDWIDTH=800000,RESOLUTION=640x360,AUDIO="group_audio"
+stream_0.m3u8
+"#;
+        let urls: HashMap<String, String> = (0..3)
+            .map(|i| (format!("stream_{}.m3u8", i), format!("https://a.example.com/{}.m3u8", i)))
+            .collect();
+        let sizes = HashMap::from([("stream_1.m3u8".to_string(), 42)]);
+
+        let tracks = parse_audio_renditions(master, &urls, &sizes);
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].url, "https://a.example.com/1.m3u8");
+        assert_eq!(tracks[0].name, "audio_1");
+        assert_eq!(tracks[0].language.as_deref(), Some("eng"));
+        assert!(tracks[0].default);
+        assert_eq!(tracks[0].size_bytes, 42);
+        assert_eq!(tracks[1].language, None);
+        assert!(!tracks[1].default);
+
+        assert!(parse_audio_renditions("#EXTM3U\nstream_0.m3u8\n", &urls, &sizes).is_empty());
+    }
 }
@@ -0,0 +1,217 @@
+//! Blossom server health checks.
+//!
+//! A server that is down or overloaded holds up every upload to it until the
+//! request fails. The prober uploads a tiny blob to each configured server
+//! every few minutes and records whether it was accepted and how long that
+//! took. Uploads then go to the fastest healthy servers first, so the first
+//! URL in a result is the one most likely to work, and servers that failed
+//! their last checks are skipped. If every server is failing, all of them
+//! are tried anyway.
+
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::interval;
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::blossom::BlossomClient;
+use crate::dvm_state::SharedDvmState;
+
+/// How often every server is probed
+pub const HEALTH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Consecutive failed probes after which a server is skipped
+pub const UNHEALTHY_AFTER: u32 = 2;
+
+/// Probe result for one server
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServerHealth {
+    pub url: String,
+    /// Whether uploads currently go to this server
+    pub healthy: bool,
+    /// Time the last successful probe upload took
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    pub consecutive_failures: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Unix timestamp of the last probe
+    pub checked_at: u64,
+}
+
+/// Latest probe results, keyed by server URL
+#[derive(Debug, Default)]
+pub struct BlossomHealth {
+    servers: HashMap<String, ServerHealth>,
+}
+
+/// Parsed URLs carry a trailing slash that configured ones usually don't
+fn key(url: &str) -> String {
+    url.trim_end_matches('/').to_string()
+}
+
+impl BlossomHealth {
+    fn entry(&mut self, url: &str, now: u64) -> &mut ServerHealth {
+        let url = key(url);
+        self.servers.entry(url.clone()).or_insert(ServerHealth {
+            url,
+            healthy: true,
+            latency_ms: None,
+            consecutive_failures: 0,
+            last_error: None,
+            checked_at: now,
+        })
+    }
+
+    pub fn record_success(&mut self, url: &str, latency: Duration, now: u64) {
+        let entry = self.entry(url, now);
+        entry.healthy = true;
+        entry.latency_ms = Some(latency.as_millis() as u64);
+        entry.consecutive_failures = 0;
+        entry.last_error = None;
+        entry.checked_at = now;
+    }
+
+    pub fn record_failure(&mut self, url: &str, error: String, now: u64) {
+        let entry = self.entry(url, now);
+        entry.consecutive_failures += 1;
+        entry.healthy = entry.consecutive_failures < UNHEALTHY_AFTER;
+        entry.last_error = Some(error);
+        entry.checked_at = now;
+    }
+
+    /// Order servers for an upload: fewest recent failures first, then
+    /// lowest latency, with servers not probed yet after the measured ones.
+    /// Unhealthy servers are dropped unless none would be left.
+    pub fn order(&self, servers: Vec<Url>) -> Vec<Url> {
+        let mut ranked: Vec<(Option<&ServerHealth>, Url)> = servers
+            .into_iter()
+            .map(|s| (self.servers.get(&key(s.as_str())), s))
+            .collect();
+        ranked.sort_by_key(|(health, _)| {
+            health.map_or((0, u64::MAX), |h| {
+                (h.consecutive_failures, h.latency_ms.unwrap_or(u64::MAX))
+            })
+        });
+        if ranked.iter().any(|(h, _)| h.is_none_or(|h| h.healthy)) {
+            ranked.retain(|(h, _)| h.is_none_or(|h| h.healthy));
+        }
+        ranked.into_iter().map(|(_, s)| s).collect()
+    }
+
+    /// Health of the given servers in their configured order (servers not
+    /// probed yet are left out)
+    pub fn entries(&self, servers: &[String]) -> Vec<ServerHealth> {
+        servers
+            .iter()
+            .filter_map(|s| self.servers.get(&key(s)).cloned())
+            .collect()
+    }
+}
+
+pub struct HealthProber {
+    state: SharedDvmState,
+    client: Arc<BlossomClient>,
+}
+
+impl HealthProber {
+    pub fn new(state: SharedDvmState, client: Arc<BlossomClient>) -> Self {
+        Self { state, client }
+    }
+
+    /// Probe every configured server every [`HEALTH_INTERVAL`], starting now.
+    pub async fn run(self) {
+        info!("Blossom health prober started");
+        let mut tick = interval(HEALTH_INTERVAL);
+        loop {
+            tick.tick().await;
+            self.probe_all().await;
+        }
+    }
+
+    pub async fn probe_all(&self) {
+        let servers: Vec<Url> = {
+            let state = self.state.read().await;
+            state
+                .config
+                .blossom_servers
+                .iter()
+                .filter_map(|s| Url::parse(s).ok())
+                .collect()
+        };
+
+        let results = join_all(servers.iter().map(|server| self.client.probe(server))).await;
+
+        let now = chrono::Utc::now().timestamp() as u64;
+        let mut state = self.state.write().await;
+        for (server, result) in servers.iter().zip(results) {
+            match result {
+                Ok(latency) => {
+                    debug!(server = %server, latency_ms = latency.as_millis(), "Blossom server healthy");
+                    state.blossom_health.record_success(server.as_str(), latency, now);
+                }
+                Err(e) => {
+                    warn!(server = %server, error = %e, "Blossom health probe failed");
+                    state
+                        .blossom_health
+                        .record_failure(server.as_str(), e.to_string(), now);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(servers: &[&str]) -> Vec<Url> {
+        servers.iter().map(|s| Url::parse(s).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_order_by_health_and_latency() {
+        let mut health = BlossomHealth::default();
+        health.record_success("https://slow.example.com", Duration::from_millis(900), 1);
+        health.record_success("https://fast.example.com/", Duration::from_millis(80), 1);
+        health.record_failure("https://flaky.example.com", "timeout".into(), 1);
+
+        let ordered = health.order(urls(&[
+            "https://flaky.example.com",
+            "https://new.example.com",
+            "https://slow.example.com",
+            "https://fast.example.com",
+        ]));
+        let hosts: Vec<_> = ordered.iter().filter_map(|u| u.host_str()).collect();
+        assert_eq!(
+            hosts,
+            vec!["fast.example.com", "slow.example.com", "new.example.com", "flaky.example.com"]
+        );
+    }
+
+    #[test]
+    fn test_unhealthy_servers_are_skipped() {
+        let mut health = BlossomHealth::default();
+        for _ in 0..UNHEALTHY_AFTER {
+            health.record_failure("https://down.example.com", "503".into(), 1);
+        }
+        let servers = urls(&["https://down.example.com", "https://up.example.com"]);
+        assert_eq!(health.order(servers.clone()), urls(&["https://up.example.com"]));
+
+        let entries = health.entries(&["https://down.example.com/".to_string()]);
+        assert!(!entries[0].healthy);
+        assert_eq!(entries[0].last_error.as_deref(), Some("503"));
+
+        // With every server down, all are still tried
+        health.record_failure("https://up.example.com", "503".into(), 2);
+        health.record_failure("https://up.example.com", "503".into(), 3);
+        assert_eq!(health.order(servers.clone()).len(), 2);
+
+        // One good probe brings a server back
+        health.record_success("https://down.example.com", Duration::from_millis(50), 4);
+        assert_eq!(health.order(servers), urls(&["https://down.example.com"]));
+    }
+}
@@ -1,7 +1,17 @@
 pub mod auth;
 pub mod cleanup;
 pub mod client;
+pub mod health;
+pub mod quarantine;
+pub mod references;
+pub mod replication;
+pub mod retention;
 
 pub use auth::create_upload_auth_token;
 pub use cleanup::BlobCleanup;
 pub use client::{BlobDescriptor, BlossomClient};
+pub use health::{BlossomHealth, HealthProber, ServerHealth};
+pub use quarantine::{QuarantineEntry, QuarantineLedger, QuarantineStatus, QuarantinedBlob};
+pub use references::{JobReference, ReferenceIndex};
+pub use replication::Replication;
+pub use retention::{Retention, RetentionLedger, RetentionTerms};
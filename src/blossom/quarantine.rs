@@ -0,0 +1,213 @@
+//! Quarantine ledger for reported results.
+//!
+//! Counts NIP-56 reports per result event and records the results that were
+//! taken down once enough distinct pubkeys reported them. Quarantined blobs
+//! are deleted from the Blossom servers but kept in the data directory until
+//! the admin reviews them, so false positives can be restored. The ledger is
+//! persisted as JSON so report counts and pending reviews survive restarts.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use tracing::warn;
+
+/// Where a quarantined result stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuarantineStatus {
+    /// Taken down, waiting for admin review
+    Quarantined,
+    /// Reviewed and put back; further reports are ignored
+    Restored,
+}
+
+/// A blob taken down with its result
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuarantinedBlob {
+    pub sha256: String,
+    /// Content type the blob was served with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mimetype: Option<String>,
+    /// Whether a copy is kept locally for restoring
+    pub saved: bool,
+}
+
+/// A result taken down after being reported
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuarantineEntry {
+    /// Result event ID (hex)
+    pub result_id: String,
+    /// Request event ID of the job that produced the result (hex)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    pub blobs: Vec<QuarantinedBlob>,
+    /// Report types given by the reporters (e.g. "illegal", "spam")
+    pub reasons: Vec<String>,
+    /// Distinct pubkeys that reported the result
+    pub reporters: usize,
+    /// Unix timestamp of the takedown
+    pub quarantined_at: u64,
+    pub status: QuarantineStatus,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LedgerData {
+    /// Reporter pubkeys per result event ID
+    #[serde(default)]
+    reports: HashMap<String, BTreeSet<String>>,
+    /// Report types per result event ID
+    #[serde(default)]
+    reasons: HashMap<String, BTreeSet<String>>,
+    #[serde(default)]
+    entries: HashMap<String, QuarantineEntry>,
+}
+
+/// Reports and quarantined results, keyed by result event ID
+#[derive(Debug, Default)]
+pub struct QuarantineLedger {
+    /// File the ledger is persisted to (`None` keeps it in memory only)
+    path: Option<PathBuf>,
+    data: LedgerData,
+}
+
+impl QuarantineLedger {
+    /// Load the ledger from `path`. A missing or unreadable file starts an empty ledger.
+    pub fn load(path: PathBuf) -> Self {
+        let data = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!(path = %path.display(), error = %e, "Ignoring corrupt quarantine ledger");
+                LedgerData::default()
+            }),
+            Err(_) => LedgerData::default(),
+        };
+        Self {
+            path: Some(path),
+            data,
+        }
+    }
+
+    /// Count a report against a result.
+    ///
+    /// Returns the number of distinct reporters so far, or `None` if the
+    /// result was already quarantined or restored by the admin.
+    pub fn record_report(&mut self, result_id: &str, reporter: &str, reason: &str) -> Option<usize> {
+        if self.data.entries.contains_key(result_id) {
+            return None;
+        }
+        self.data
+            .reasons
+            .entry(result_id.to_string())
+            .or_default()
+            .insert(reason.to_string());
+        let reporters = self.data.reports.entry(result_id.to_string()).or_default();
+        reporters.insert(reporter.to_string());
+        Some(reporters.len())
+    }
+
+    /// Report types given for a result
+    pub fn reasons(&self, result_id: &str) -> Vec<String> {
+        self.data
+            .reasons
+            .get(result_id)
+            .map(|r| r.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Record a takedown. The report tally is folded into the entry.
+    pub fn quarantine(&mut self, entry: QuarantineEntry) {
+        self.data.reports.remove(&entry.result_id);
+        self.data.reasons.remove(&entry.result_id);
+        self.data.entries.insert(entry.result_id.clone(), entry);
+    }
+
+    pub fn get(&self, result_id: &str) -> Option<&QuarantineEntry> {
+        self.data.entries.get(result_id)
+    }
+
+    /// All quarantined and restored results, newest first
+    pub fn entries(&self) -> Vec<&QuarantineEntry> {
+        let mut entries: Vec<_> = self.data.entries.values().collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.quarantined_at));
+        entries
+    }
+
+    /// Mark a quarantined result as restored
+    pub fn mark_restored(&mut self, result_id: &str) -> Option<&QuarantineEntry> {
+        let entry = self.data.entries.get_mut(result_id)?;
+        entry.status = QuarantineStatus::Restored;
+        for blob in &mut entry.blobs {
+            blob.saved = false;
+        }
+        Some(entry)
+    }
+
+    /// Write the ledger to its file (no-op for in-memory ledgers)
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string(&self.data)?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(result_id: &str) -> QuarantineEntry {
+        QuarantineEntry {
+            result_id: result_id.to_string(),
+            job_id: Some("job".to_string()),
+            blobs: vec![QuarantinedBlob {
+                sha256: "a".repeat(64),
+                mimetype: Some("video/mp4".to_string()),
+                saved: true,
+            }],
+            reasons: vec!["spam".to_string()],
+            reporters: 2,
+            quarantined_at: 1_700_000_000,
+            status: QuarantineStatus::Quarantined,
+        }
+    }
+
+    #[test]
+    fn test_reports_count_distinct_pubkeys() {
+        let mut ledger = QuarantineLedger::default();
+        assert_eq!(ledger.record_report("r1", "alice", "spam"), Some(1));
+        assert_eq!(ledger.record_report("r1", "alice", "illegal"), Some(1));
+        assert_eq!(ledger.record_report("r1", "bob", "spam"), Some(2));
+        assert_eq!(ledger.record_report("r2", "bob", "spam"), Some(1));
+        assert_eq!(ledger.reasons("r1"), vec!["illegal", "spam"]);
+
+        ledger.quarantine(entry("r1"));
+        assert_eq!(ledger.record_report("r1", "carol", "spam"), None);
+
+        // Restored results stay exempt from the same reports
+        let restored = ledger.mark_restored("r1").unwrap();
+        assert_eq!(restored.status, QuarantineStatus::Restored);
+        assert!(!restored.blobs[0].saved);
+        assert_eq!(ledger.record_report("r1", "dave", "spam"), None);
+    }
+
+    #[test]
+    fn test_ledger_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("quarantine.json");
+
+        let mut ledger = QuarantineLedger::load(path.clone());
+        ledger.record_report("r2", "alice", "spam");
+        ledger.quarantine(entry("r1"));
+        ledger.save().unwrap();
+
+        let mut loaded = QuarantineLedger::load(path);
+        assert_eq!(loaded.get("r1"), Some(&entry("r1")));
+        assert_eq!(loaded.entries().len(), 1);
+        assert_eq!(loaded.record_report("r2", "bob", "spam"), Some(2));
+    }
+}
@@ -0,0 +1,182 @@
+//! Which jobs reference which blobs.
+//!
+//! Blossom deduplicates by hash and reports the first upload time, so a
+//! segment an old job uploaded looks expired even when a job finished
+//! yesterday uses it too. The index maps each completed job to the hashes of
+//! its result; cleanup keeps every blob referenced by a job that completed
+//! within the expiration window or that the admin pinned. Persisted as JSON
+//! in the data directory.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use tracing::warn;
+
+/// Blobs of one job's result
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobReference {
+    pub hashes: Vec<String>,
+    /// Unix timestamp when the job completed
+    pub completed_at: u64,
+    /// Kept regardless of age until unpinned
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+/// Result blob hashes by job ID
+#[derive(Debug, Default)]
+pub struct ReferenceIndex {
+    /// File the index is persisted to (`None` keeps it in memory only)
+    path: Option<PathBuf>,
+    jobs: HashMap<String, JobReference>,
+}
+
+impl ReferenceIndex {
+    /// Load the index from `path`. A missing or unreadable file starts an empty index.
+    pub fn load(path: PathBuf) -> Self {
+        let jobs = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!(path = %path.display(), error = %e, "Ignoring corrupt reference index");
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Self {
+            path: Some(path),
+            jobs,
+        }
+    }
+
+    /// Record the blobs of a completed job, keeping an existing pin
+    pub fn record(&mut self, job_id: &str, hashes: Vec<String>, completed_at: u64) {
+        let pinned = self.jobs.get(job_id).is_some_and(|r| r.pinned);
+        self.jobs.insert(
+            job_id.to_string(),
+            JobReference {
+                hashes,
+                completed_at,
+                pinned,
+            },
+        );
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<&JobReference> {
+        self.jobs.get(job_id)
+    }
+
+    /// Pin or unpin a job's blobs. `None` if the job isn't in the index.
+    pub fn set_pinned(&mut self, job_id: &str, pinned: bool) -> Option<&JobReference> {
+        let reference = self.jobs.get_mut(job_id)?;
+        reference.pinned = pinned;
+        Some(reference)
+    }
+
+    /// A pinned job whose result includes `sha256`
+    pub fn pinned_by(&self, sha256: &str) -> Option<&str> {
+        self.jobs
+            .iter()
+            .find(|(_, r)| r.pinned && r.hashes.iter().any(|h| h == sha256))
+            .map(|(id, _)| id.as_str())
+    }
+
+    /// Whether any recorded job's result includes `sha256`
+    pub fn is_referenced(&self, sha256: &str) -> bool {
+        self.jobs
+            .values()
+            .any(|r| r.hashes.iter().any(|h| h == sha256))
+    }
+
+    /// Hashes cleanup must keep: those of pinned jobs and of jobs completed
+    /// at or after `threshold`
+    pub fn protected(&self, threshold: u64) -> HashSet<String> {
+        self.jobs
+            .values()
+            .filter(|r| r.pinned || r.completed_at >= threshold)
+            .flat_map(|r| r.hashes.iter().cloned())
+            .collect()
+    }
+
+    /// Drop unpinned jobs completed before `threshold`, which no longer
+    /// protect anything. Returns how many were removed.
+    pub fn prune(&mut self, threshold: u64) -> usize {
+        let before = self.jobs.len();
+        self.jobs.retain(|_, r| r.pinned || r.completed_at >= threshold);
+        before - self.jobs.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// Write the index to its file (no-op for in-memory indexes)
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string(&self.jobs)?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashes(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_protected_and_pins() {
+        let mut index = ReferenceIndex::default();
+        index.record("old", hashes(&["shared", "old-only"]), 100);
+        index.record("new", hashes(&["shared", "new-only"]), 500);
+
+        assert!(index.is_referenced("old-only"));
+        assert!(!index.is_referenced("unknown"));
+
+        let protected = index.protected(300);
+        assert!(protected.contains("shared"));
+        assert!(protected.contains("new-only"));
+        assert!(!protected.contains("old-only"));
+
+        assert_eq!(index.pinned_by("old-only"), None);
+        assert!(index.set_pinned("old", true).is_some());
+        assert!(index.protected(300).contains("old-only"));
+        assert_eq!(index.pinned_by("old-only"), Some("old"));
+        assert!(index.set_pinned("missing", true).is_none());
+
+        // Re-recording a job keeps its pin
+        index.record("old", hashes(&["old-only"]), 100);
+        assert!(index.get("old").unwrap().pinned);
+
+        assert_eq!(index.prune(300), 0);
+        index.set_pinned("old", false);
+        assert_eq!(index.prune(300), 1);
+        assert_eq!(index.len(), 1);
+    }
+
+    #[test]
+    fn test_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("references.json");
+
+        let mut index = ReferenceIndex::load(path.clone());
+        assert!(index.is_empty());
+        index.record("job", hashes(&["abc"]), 42);
+        index.set_pinned("job", true);
+        index.save().unwrap();
+
+        let loaded = ReferenceIndex::load(path);
+        assert_eq!(loaded.get("job"), index.get("job"));
+    }
+}
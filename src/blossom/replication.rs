@@ -0,0 +1,66 @@
+//! Copying existing blobs to servers that don't have them.
+//!
+//! Results are uploaded to the servers configured at the time of the job, so
+//! a server added later holds none of the older results. Replication looks
+//! up which configured servers already have a blob, then asks each missing
+//! server to fetch it from one that does (BUD-04 `PUT /mirror`). Servers
+//! without mirror support get the blob streamed through the DVM instead.
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// What replicating one blob did on each configured server
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Replication {
+    pub sha256: String,
+    /// Servers that already had the blob
+    pub present: Vec<String>,
+    /// Servers the blob was copied to
+    pub copied: Vec<String>,
+    /// Servers the copy failed on, as "server: error"
+    pub failed: Vec<String>,
+}
+
+/// Which servers are missing a blob and where to copy it from, given each
+/// server's stored size (`None` when it doesn't have the blob). The first
+/// server holding it is the source; `None` if no server has it.
+pub fn plan(holdings: &[(Url, Option<u64>)]) -> Option<(Url, u64, Vec<Url>)> {
+    let (source, size) = holdings
+        .iter()
+        .find_map(|(server, size)| size.map(|s| (server.clone(), s)))?;
+    let missing = holdings
+        .iter()
+        .filter(|(_, size)| size.is_none())
+        .map(|(server, _)| server.clone())
+        .collect();
+    Some((source, size, missing))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_plan() {
+        let holdings = vec![
+            (url("https://new.example.com"), None),
+            (url("https://a.example.com"), Some(42)),
+            (url("https://b.example.com"), Some(42)),
+            (url("https://other.example.com"), None),
+        ];
+        let (source, size, missing) = plan(&holdings).unwrap();
+        assert_eq!(source, url("https://a.example.com"));
+        assert_eq!(size, 42);
+        assert_eq!(
+            missing,
+            vec![url("https://new.example.com"), url("https://other.example.com")]
+        );
+
+        assert!(plan(&[(url("https://a.example.com"), None)]).is_none());
+        assert!(plan(&[]).is_none());
+    }
+}
@@ -0,0 +1,168 @@
+//! Retention ledger for uploaded result blobs.
+//!
+//! Records when each result's blobs were uploaded and until when they are
+//! kept. Results default to `blob_expiration_days`, but requesters can pay to
+//! keep them longer; the cleanup scheduler consults the ledger so paid-for
+//! blobs survive past the default window. The ledger is persisted as JSON in
+//! the data directory so extended retention survives restarts.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tracing::warn;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Retention window of a result's blobs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Retention {
+    /// Unix timestamp when the blobs were uploaded
+    pub uploaded_at: u64,
+    /// Unix timestamp after which the blobs may be deleted
+    pub expires_at: u64,
+}
+
+impl Retention {
+    pub fn new(uploaded_at: u64, days: u32) -> Self {
+        Self {
+            uploaded_at,
+            expires_at: uploaded_at + days as u64 * SECS_PER_DAY,
+        }
+    }
+}
+
+/// Retention granted to a job and what it costs on top of the base price
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionTerms {
+    /// Days the result blobs are kept
+    pub days: u32,
+    /// Charge for the days beyond `blob_expiration_days`
+    pub extra_sats: u64,
+}
+
+/// Upload time and expiry of result blobs, keyed by sha256
+#[derive(Debug, Default)]
+pub struct RetentionLedger {
+    /// File the ledger is persisted to (`None` keeps it in memory only)
+    path: Option<PathBuf>,
+    blobs: HashMap<String, Retention>,
+}
+
+impl RetentionLedger {
+    /// Load the ledger from `path`. A missing or unreadable file starts an empty ledger.
+    pub fn load(path: PathBuf) -> Self {
+        let blobs = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!(path = %path.display(), error = %e, "Ignoring corrupt retention ledger");
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Self {
+            path: Some(path),
+            blobs,
+        }
+    }
+
+    /// Record the retention of a result's blobs.
+    ///
+    /// Blossom deduplicates by hash, so a blob shared by several results
+    /// keeps the latest expiry.
+    pub fn record(&mut self, hashes: impl IntoIterator<Item = String>, retention: Retention) {
+        for hash in hashes {
+            self.blobs
+                .entry(hash)
+                .and_modify(|r| {
+                    if retention.expires_at > r.expires_at {
+                        *r = retention;
+                    }
+                })
+                .or_insert(retention);
+        }
+    }
+
+    pub fn get(&self, sha256: &str) -> Option<&Retention> {
+        self.blobs.get(sha256)
+    }
+
+    /// Whether a blob uploaded at `uploaded` may be deleted at `now`.
+    ///
+    /// Blobs in the ledger expire at their recorded time; anything else falls
+    /// back to `default_threshold` (upload time before which blobs expire).
+    pub fn is_expired(&self, sha256: &str, uploaded: u64, now: u64, default_threshold: u64) -> bool {
+        match self.blobs.get(sha256) {
+            Some(r) => r.expires_at <= now,
+            None => uploaded < default_threshold,
+        }
+    }
+
+    /// Drop entries that have expired by `now`. Returns how many were removed.
+    pub fn prune(&mut self, now: u64) -> usize {
+        let before = self.blobs.len();
+        self.blobs.retain(|_, r| r.expires_at > now);
+        before - self.blobs.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.blobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blobs.is_empty()
+    }
+
+    /// Write the ledger to its file (no-op for in-memory ledgers)
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string(&self.blobs)?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "b1674191a88ec5cdd733e4240a81803105dc412d6c6708d53ab94fc248f4f553";
+
+    #[test]
+    fn test_ledger_expiry() {
+        let day = SECS_PER_DAY;
+        let mut ledger = RetentionLedger::default();
+        ledger.record([HASH.to_string()], Retention::new(0, 90));
+        // A shorter retention for the same blob doesn't shorten it
+        ledger.record([HASH.to_string()], Retention::new(day, 30));
+        assert_eq!(ledger.get(HASH).unwrap().expires_at, 90 * day);
+
+        let now = 60 * day;
+        let threshold = now - 30 * day;
+        assert!(!ledger.is_expired(HASH, 0, now, threshold));
+        assert!(ledger.is_expired("other", 0, now, threshold));
+        assert!(!ledger.is_expired("other", threshold + 1, now, threshold));
+
+        assert_eq!(ledger.prune(now), 0);
+        assert_eq!(ledger.prune(90 * day), 1);
+        assert!(ledger.is_empty());
+    }
+
+    #[test]
+    fn test_ledger_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("retention.json");
+
+        let mut ledger = RetentionLedger::load(path.clone());
+        assert!(ledger.is_empty());
+        ledger.record([HASH.to_string()], Retention::new(100, 7));
+        ledger.save().unwrap();
+
+        let reloaded = RetentionLedger::load(path);
+        assert_eq!(reloaded.get(HASH), Some(&Retention::new(100, 7)));
+    }
+}
@@ -33,15 +33,18 @@ pub enum Commands {
         #[arg(long)]
         data_dir: Option<PathBuf>,
     },
-    /// Install and start the background service
+    /// Install and start the background service (systemd, launchd or
+    /// Task Scheduler), restarted on failure and at boot
+    #[command(alias = "install-service")]
     Install {
         /// Re-install even if already installed
         #[arg(long)]
         force: bool,
-        /// Install as a system-wide service (requires root)
+        /// Install as a system-wide service (requires root); on Windows,
+        /// start at boot instead of at logon
         #[arg(long)]
         system: bool,
-        /// User to run the service as (--system only)
+        /// User to run the service as (--system only, default: the sudo user)
         #[arg(long)]
         user: Option<String>,
     },
@@ -108,11 +111,21 @@ pub enum Commands {
         #[command(subcommand)]
         command: ConfigCommands,
     },
+    /// Export, import or rotate the DVM's identity key
+    Identity {
+        #[command(subcommand)]
+        command: IdentityCommands,
+    },
     /// Manage Docker deployment
     Docker {
         #[command(subcommand)]
         command: DockerCommands,
     },
+    /// Generate synthetic job load against a running DVM
+    Loadtest {
+        #[command(subcommand)]
+        command: LoadtestCommands,
+    },
     /// Print version information
     Version,
 }
@@ -144,6 +157,58 @@ pub enum ConfigCommands {
     Status,
 }
 
+#[derive(Subcommand)]
+pub enum IdentityCommands {
+    /// Show the DVM's public key
+    Show,
+    /// Print the private key encrypted with a password (NIP-49 ncryptsec)
+    Export,
+    /// Switch to an existing key, moving remote config and announcements to it
+    Import {
+        /// nsec, hex or ncryptsec key (prompted for when omitted)
+        key: Option<String>,
+    },
+    /// Switch to a newly generated key, moving remote config and announcements to it
+    Rotate,
+}
+
+#[derive(Subcommand)]
+pub enum LoadtestCommands {
+    /// Send job requests at a fixed rate and report throughput and latency
+    Run {
+        /// DVM pubkey (npub or hex)
+        #[arg(long)]
+        dvm: String,
+        /// Relays to publish requests on (the DVM must listen on them)
+        #[arg(long, value_delimiter = ',', required = true)]
+        relays: Vec<String>,
+        /// Input video URL for every request
+        #[arg(long)]
+        input: String,
+        /// Output mode (hls or mp4)
+        #[arg(long, default_value = "hls")]
+        mode: String,
+        /// Requests per second
+        #[arg(long, default_value = "0.5")]
+        rate: f64,
+        /// Number of requests to send
+        #[arg(long, default_value = "20")]
+        jobs: usize,
+        /// Seconds to wait for answers after the last request
+        #[arg(long, default_value = "600")]
+        timeout: u64,
+    },
+    /// Write fake FFmpeg/FFprobe scripts and serve a mock Blossom server
+    Mock {
+        /// Directory for the fake binaries (default: $DATA_DIR/loadtest)
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        /// Port of the mock Blossom server
+        #[arg(long, default_value = "3939")]
+        port: u16,
+    },
+}
+
 #[derive(Subcommand)]
 pub enum DockerCommands {
     /// Run setup.sh — detect GPU, write .env, start compose
@@ -180,6 +245,32 @@ mod tests {
         assert!(matches!(cli.command, Some(Commands::Run { replace: true })));
     }
 
+    #[test]
+    fn test_cli_parses_loadtest_run() {
+        let cli = Cli::try_parse_from([
+            "nostube-transcode",
+            "loadtest",
+            "run",
+            "--dvm",
+            "npub1abc",
+            "--relays",
+            "ws://127.0.0.1:7777,ws://127.0.0.1:7778",
+            "--input",
+            "http://127.0.0.1:3939/input.mp4",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Commands::Loadtest {
+                command: LoadtestCommands::Run { relays, rate, jobs, .. },
+            }) => {
+                assert_eq!(relays.len(), 2);
+                assert_eq!(rate, 0.5);
+                assert_eq!(jobs, 20);
+            }
+            _ => panic!("expected loadtest run"),
+        }
+    }
+
     #[test]
     fn test_cli_no_subcommand() {
         let cli = Cli::try_parse_from(["nostube-transcode"]).unwrap();
@@ -196,6 +287,25 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_cli_parses_install_service_alias() {
+        let cli = Cli::try_parse_from([
+            "nostube-transcode",
+            "install-service",
+            "--system",
+            "--user",
+            "dvm",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Commands::Install { system, user, .. }) => {
+                assert!(system);
+                assert_eq!(user.as_deref(), Some("dvm"));
+            }
+            _ => panic!("expected install"),
+        }
+    }
+
     #[test]
     fn test_cli_parses_logs() {
         let cli = Cli::try_parse_from([
@@ -231,6 +341,18 @@ mod tests {
         assert!(matches!(cli.command, Some(Commands::Config { .. })));
     }
 
+    #[test]
+    fn test_cli_parses_identity_import() {
+        let cli =
+            Cli::try_parse_from(["nostube-transcode", "identity", "import", "nsec1abc"]).unwrap();
+        match cli.command {
+            Some(Commands::Identity {
+                command: IdentityCommands::Import { key },
+            }) => assert_eq!(key.as_deref(), Some("nsec1abc")),
+            _ => panic!("expected identity import"),
+        }
+    }
+
     #[test]
     fn test_cli_parses_docker_setup() {
         let cli =
@@ -1,47 +1,63 @@
+//! Process-wide settings fixed at startup.
+//!
+//! `Config` holds what can't change while the DVM runs: its keys, binary and
+//! directory paths, and environment defaults. Operator settings that can be
+//! changed over the admin protocol (relays, Blossom servers, limits, pricing)
+//! live only in the shared [`RemoteConfig`](crate::remote_config::RemoteConfig)
+//! in `DvmState::config`, and subsystems read them from there when needed.
+//! [`ConfigWatch`](crate::dvm_state::ConfigWatch) signals when they change.
+
 use nostr_sdk::Keys;
 use std::path::PathBuf;
-use url::Url;
 
-use crate::error::ConfigError;
-use crate::remote_config::RemoteConfig;
+use crate::dvm::limits::{InputLimits, OutputLimits};
+use crate::nostr::outbox::DEFAULT_RESULT_MIN_ACKS;
+use crate::payments::cashu::MeltConfig;
+use crate::payments::LightningConfig;
+use crate::video::av_sync::AvSyncPolicy;
+
+/// Jobs waiting for a slot when `JOB_QUEUE_CAPACITY` is unset
+pub const DEFAULT_JOB_QUEUE_CAPACITY: usize = 32;
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub nostr_keys: Keys,
-    pub nostr_relays: Vec<Url>,
-    pub blossom_servers: Vec<Url>,
-    pub blob_expiration_days: u32,
     pub temp_dir: PathBuf,
+    /// Maximum scratch space for concurrently running jobs (None = free space)
+    pub temp_disk_quota_bytes: Option<u64>,
+    /// Memory budget for concurrently running encodes (None = share of available memory)
+    pub memory_budget_bytes: Option<u64>,
+    /// Download URL inputs before transcoding instead of streaming them into FFmpeg
+    pub predownload_inputs: bool,
+    /// Size of the cache of downloaded inputs (None = no cache)
+    pub input_cache_bytes: Option<u64>,
+    /// Directories that `path` job inputs may read from (empty = disabled)
+    pub local_input_dirs: Vec<PathBuf>,
+    /// Jobs that may wait for a slot before new requests are turned away as busy
+    pub job_queue_capacity: usize,
+    /// Relays that must accept a result event before it leaves the outbox
+    pub result_min_acks: usize,
+    /// A/V sync check applied to transcoded output
+    pub av_sync: AvSyncPolicy,
+    /// Input limits from the environment (the remote config can override them)
+    pub input_limits: InputLimits,
+    /// Output size limits from the environment (the remote config can override them)
+    pub output_limits: OutputLimits,
+    /// Lightning backend for invoicing paid jobs (None = Cashu only)
+    pub lightning: Option<LightningConfig>,
+    /// Lightning payout of redeemed Cashu tokens (None = keep the ecash)
+    pub cashu_melt: Option<MeltConfig>,
     pub ffmpeg_path: PathBuf,
     pub ffprobe_path: PathBuf,
     pub http_port: u16,
     pub http_enabled: bool,
-    pub dvm_name: Option<String>,
-    pub dvm_about: Option<String>,
-    pub admin_pubkey: Option<String>,
-    pub base_rate_sats_per_min: u64,
+    /// Base URL the web server is reachable at, for `/watch` links (None = no links)
+    pub public_url: Option<String>,
 }
 
 impl Config {
-    /// Create Config from RemoteConfig
-    pub fn from_remote(
-        keys: Keys,
-        remote: &RemoteConfig,
-        ffmpeg_path: PathBuf,
-        ffprobe_path: PathBuf,
-    ) -> Result<Self, ConfigError> {
-        let relays: Vec<Url> = remote
-            .relays
-            .iter()
-            .filter_map(|s| Url::parse(s).ok())
-            .collect();
-
-        let blossom: Vec<Url> = remote
-            .blossom_servers
-            .iter()
-            .filter_map(|s| Url::parse(s).ok())
-            .collect();
-
+    /// Create Config from the environment and the discovered FFmpeg binaries
+    pub fn from_env(keys: Keys, ffmpeg_path: PathBuf, ffprobe_path: PathBuf) -> Self {
         let temp_dir = std::env::var("TEMP_DIR")
             .map(PathBuf::from)
             .unwrap_or_else(|_| {
@@ -55,6 +71,50 @@ impl Config {
                     .join("nostube-transcode")
             });
 
+        let temp_disk_quota_bytes = std::env::var("TEMP_DISK_QUOTA_MB")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .filter(|mb| *mb > 0)
+            .map(|mb| mb * 1024 * 1024);
+
+        let memory_budget_bytes = std::env::var("MEMORY_BUDGET_MB")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .filter(|mb| *mb > 0)
+            .map(|mb| mb * 1024 * 1024);
+
+        let predownload_inputs = std::env::var("PREDOWNLOAD_INPUTS")
+            .map(|v| v == "1" || v.to_lowercase() == "true")
+            .unwrap_or(false);
+
+        let input_cache_bytes = std::env::var("INPUT_CACHE_MB")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .filter(|mb| *mb > 0)
+            .map(|mb| mb * 1024 * 1024);
+
+        let local_input_dirs = std::env::var("LOCAL_INPUT_DIRS")
+            .map(|s| {
+                s.split(',')
+                    .map(str::trim)
+                    .filter(|p| !p.is_empty())
+                    .map(PathBuf::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let job_queue_capacity = std::env::var("JOB_QUEUE_CAPACITY")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(DEFAULT_JOB_QUEUE_CAPACITY);
+
+        let result_min_acks = std::env::var("RESULT_MIN_ACKS")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(DEFAULT_RESULT_MIN_ACKS);
+
         let http_port = std::env::var("HTTP_PORT")
             .ok()
             .and_then(|s| s.parse().ok())
@@ -64,20 +124,31 @@ impl Config {
             .map(|v| v != "1" && v.to_lowercase() != "true")
             .unwrap_or(true);
 
-        Ok(Self {
+        let public_url = std::env::var("PUBLIC_URL")
+            .ok()
+            .map(|s| s.trim().trim_end_matches('/').to_string())
+            .filter(|s| !s.is_empty());
+
+        Self {
             nostr_keys: keys,
-            nostr_relays: relays,
-            blossom_servers: blossom,
-            blob_expiration_days: remote.blob_expiration_days,
             temp_dir,
+            temp_disk_quota_bytes,
+            memory_budget_bytes,
+            predownload_inputs,
+            input_cache_bytes,
+            local_input_dirs,
+            job_queue_capacity,
+            result_min_acks,
+            av_sync: AvSyncPolicy::from_env(),
+            input_limits: InputLimits::from_env(),
+            output_limits: OutputLimits::from_env(),
+            lightning: LightningConfig::from_env(),
+            cashu_melt: MeltConfig::from_env(),
             ffmpeg_path,
             ffprobe_path,
             http_port,
             http_enabled,
-            dvm_name: remote.name.clone(),
-            dvm_about: remote.about.clone(),
-            admin_pubkey: remote.admin.clone(),
-            base_rate_sats_per_min: remote.base_rate_sats_per_min,
-        })
+            public_url,
+        }
     }
 }
@@ -11,7 +11,7 @@ use crate::remote_config::{self, RemoteConfig};
 
 /// Connect to relays and return (client, keys, config).
 /// Config is None if none exists yet on the relays.
-async fn connect_and_fetch(paths: &Paths) -> Result<(Client, Keys, Option<RemoteConfig>)> {
+pub(crate) async fn connect_and_fetch(paths: &Paths) -> Result<(Client, Keys, Option<RemoteConfig>)> {
     // Load env file so OPERATOR_NPUB is available if needed
     if paths.env_file.exists() {
         if let Ok(map) = crate::setup::read_env_file(&paths.env_file) {
@@ -27,7 +27,7 @@ async fn connect_and_fetch(paths: &Paths) -> Result<(Client, Keys, Option<Remote
         .context("Failed to load DVM identity key — run: nostube-transcode setup")?;
 
     let relays = get_bootstrap_relays();
-    let client = Client::new(keys.clone());
+    let client = crate::util::proxy::nostr_client(&keys);
     for relay in &relays {
         client.add_relay(relay.as_str()).await.ok();
     }
@@ -142,11 +142,18 @@ pub fn run_checks(paths: &Paths) -> Vec<Check> {
     // FFmpeg
     match crate::util::ffmpeg_discovery::FfmpegPaths::discover() {
         Ok(ff) => {
-            checks.push(Check::ok("ffmpeg", format!("{}", ff.ffmpeg.display())));
+            let version = ff
+                .version
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| "unknown version".to_string());
+            checks.push(Check::ok(
+                "ffmpeg",
+                format!("{} ({})", ff.ffmpeg.display(), version),
+            ));
             checks.push(Check::ok("ffprobe", format!("{}", ff.ffprobe.display())));
         }
         Err(e) => {
-            checks.push(Check::err("ffmpeg", format!("not found: {e}")));
+            checks.push(Check::err("ffmpeg", e.to_string()));
         }
     }
 
@@ -168,7 +175,7 @@ pub fn run_checks(paths: &Paths) -> Vec<Check> {
         } else {
             checks.push(Check::warn(
                 "service",
-                format!("not installed — run: nostube-transcode install"),
+                "not installed — run: nostube-transcode install",
             ));
         }
     } else {
@@ -1,14 +1,20 @@
 use nostr_sdk::prelude::*;
 use std::collections::HashSet;
 use std::sync::Arc;
+use tokio::process::Command;
 use tokio::sync::Notify;
 use tokio::time::{interval, Duration};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::config::Config;
-use crate::dvm::events::DVM_VIDEO_TRANSFORM_REQUEST_KIND;
-use crate::dvm_state::SharedDvmState;
+use crate::dvm::events::{Codec, DVM_VIDEO_TRANSFORM_REQUEST_KIND};
+use crate::dvm::messages::Lang;
+use crate::dvm_state::{ConfigWatch, SharedDvmState};
 use crate::nostr::EventPublisher;
+use crate::remote_config::RemoteConfig;
+use crate::video::fps_cap::{MAX_MAX_FPS, MIN_MAX_FPS};
+use crate::video::segmenting::{MAX_HLS_TIME, MIN_HLS_TIME};
+use crate::video::transform::DEFAULT_AUDIO_BITRATES;
 use crate::video::HwAccel;
 
 /// NIP-89 DVM Announcement kind (31990)
@@ -17,6 +23,9 @@ pub const DVM_ANNOUNCEMENT_KIND: Kind = Kind::Custom(31990);
 /// NIP-65 Relay List Metadata kind (10002)
 pub const RELAY_LIST_KIND: Kind = Kind::Custom(10002);
 
+/// NIP-17 DM relay list kind (10050), where clients send gift-wrapped DMs
+pub const DM_RELAY_LIST_KIND: Kind = Kind::Custom(10050);
+
 /// Index relays that aggregate relay lists (published to in addition to DVM relays)
 const INDEX_RELAYS: &[&str] = &["wss://purplepag.es"];
 
@@ -29,34 +38,79 @@ const DEFAULT_DVM_NAME: &str = "Video Transform DVM";
 /// Profile picture URL (hosted on the frontend deployment)
 const PROFILE_PICTURE_URL: &str = "https://nostube-transform.vercel.app/logo.png";
 
+/// Request kinds the DVM serves when everything it needs is in place
+pub const REQUEST_KINDS: &[Kind] = &[DVM_VIDEO_TRANSFORM_REQUEST_KIND];
+
 /// Runtime capability data for enriching DVM announcements
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct DvmCapabilities {
     /// Average transcode speed per resolution (realtime multiplier, e.g. 3.5x)
     pub avg_speeds: std::collections::HashMap<String, f64>,
     /// Current number of active jobs (queue depth)
     pub jobs_active: u32,
-    /// Maximum concurrent jobs configured
-    pub max_concurrent: u32,
+    /// Request kinds that passed the runtime checks (see [`supported_kinds`])
+    pub kinds: Vec<Kind>,
+}
+
+impl Default for DvmCapabilities {
+    fn default() -> Self {
+        Self {
+            avg_speeds: Default::default(),
+            jobs_active: 0,
+            kinds: REQUEST_KINDS.to_vec(),
+        }
+    }
+}
+
+/// Request kinds the DVM can serve right now. Every kind needs runnable
+/// ffmpeg and ffprobe binaries; without them none are announced, so clients
+/// stop sending jobs that would fail.
+pub async fn supported_kinds(config: &Config) -> Vec<Kind> {
+    for tool in [&config.ffmpeg_path, &config.ffprobe_path] {
+        let runs = Command::new(tool)
+            .arg("-version")
+            .output()
+            .await
+            .is_ok_and(|o| o.status.success());
+        if !runs {
+            warn!(path = %tool.display(), "Binary doesn't run, announcing no supported kinds");
+            return Vec::new();
+        }
+    }
+    REQUEST_KINDS.to_vec()
+}
+
+/// Configured relays as normalized URLs (unparseable entries are skipped)
+fn relay_urls(settings: &RemoteConfig) -> Vec<String> {
+    settings
+        .relays
+        .iter()
+        .filter_map(|s| Url::parse(s).ok())
+        .map(|u| u.to_string())
+        .collect()
 }
 
 /// Builds a NIP-89 DVM announcement event
-pub fn build_announcement_event(config: &Config, hwaccel: HwAccel) -> EventBuilder {
-    build_announcement_event_with_caps(config, hwaccel, &DvmCapabilities::default())
+pub fn build_announcement_event(settings: &RemoteConfig, hwaccel: HwAccel) -> EventBuilder {
+    build_announcement_event_with_caps(settings, hwaccel, &DvmCapabilities::default())
 }
 
 /// Builds a NIP-89 DVM announcement event with runtime capability data
-pub fn build_announcement_event_with_caps(config: &Config, hwaccel: HwAccel, caps: &DvmCapabilities) -> EventBuilder {
-    let relays: Vec<String> = config.nostr_relays.iter().map(|u| u.to_string()).collect();
+pub fn build_announcement_event_with_caps(
+    settings: &RemoteConfig,
+    hwaccel: HwAccel,
+    caps: &DvmCapabilities,
+) -> EventBuilder {
+    let relays = relay_urls(settings);
 
     // Use configured name or default
-    let name = config
-        .dvm_name
+    let name = settings
+        .name
         .clone()
         .unwrap_or_else(|| DEFAULT_DVM_NAME.to_string());
 
     // Use configured about or build default
-    let about = config.dvm_about.clone().unwrap_or_else(|| {
+    let about = settings.about.clone().unwrap_or_else(|| {
         format!(
             "Video transformation DVM - converts videos to HLS streaming format. \
              Supports 360p, 720p, 1080p, and 4K. Hardware acceleration: {}.",
@@ -75,10 +129,6 @@ pub fn build_announcement_event_with_caps(config: &Config, hwaccel: HwAccel, cap
             TagKind::Custom("d".into()),
             vec![DVM_SERVICE_ID.to_string()],
         ),
-        Tag::custom(
-            TagKind::Custom("k".into()),
-            vec![DVM_VIDEO_TRANSFORM_REQUEST_KIND.as_u16().to_string()],
-        ),
         // Service metadata
         Tag::custom(TagKind::Custom("name".into()), vec![name]),
         Tag::custom(TagKind::Custom("about".into()), vec![about]),
@@ -91,10 +141,23 @@ pub fn build_announcement_event_with_caps(config: &Config, hwaccel: HwAccel, cap
         Tag::custom(TagKind::Custom("relays".into()), relays),
     ];
 
+    // Supported request kinds (NIP-89 "k" tags)
+    for kind in &caps.kinds {
+        tags.push(Tag::custom(
+            TagKind::Custom("k".into()),
+            vec![kind.as_u16().to_string()],
+        ));
+    }
+
     // Add supported output modes
     tags.push(Tag::custom(
         TagKind::Custom("param".into()),
-        vec!["mode".to_string(), "hls".to_string(), "mp4".to_string()],
+        vec![
+            "mode".to_string(),
+            "hls".to_string(),
+            "mp4".to_string(),
+            "thumbnail".to_string(),
+        ],
     ));
 
     // Add supported resolutions
@@ -109,6 +172,118 @@ pub fn build_announcement_event_with_caps(config: &Config, hwaccel: HwAccel, cap
         ],
     ));
 
+    // Default per-rendition HLS audio bitrates (overridable via "audio_bitrates")
+    let mut audio_bitrates = vec!["audio_bitrates".to_string()];
+    audio_bitrates.extend(
+        DEFAULT_AUDIO_BITRATES
+            .iter()
+            .map(|(label, bitrate)| format!("{}:{}", label, bitrate)),
+    );
+    tags.push(Tag::custom(TagKind::Custom("param".into()), audio_bitrates));
+
+    // Devices the codec can be picked for when no codec is given
+    tags.push(Tag::custom(
+        TagKind::Custom("param".into()),
+        vec![
+            "device".to_string(),
+            "ios".to_string(),
+            "android".to_string(),
+            "web".to_string(),
+            "tv".to_string(),
+        ],
+    ));
+
+    // Add supported MP4-mode containers
+    tags.push(Tag::custom(
+        TagKind::Custom("param".into()),
+        vec!["container".to_string(), "mp4".to_string(), "webm".to_string()],
+    ));
+
+    // Add supported animated preview formats
+    tags.push(Tag::custom(
+        TagKind::Custom("param".into()),
+        vec!["preview".to_string(), "webp".to_string(), "gif".to_string()],
+    ));
+
+    // Advertise seek thumbnails (sprite sheets + WebVTT)
+    tags.push(Tag::custom(
+        TagKind::Custom("param".into()),
+        vec!["storyboard".to_string(), "true".to_string(), "false".to_string()],
+    ));
+
+    // Add supported watermark anchors (the "watermark" param takes an image URL or text)
+    tags.push(Tag::custom(
+        TagKind::Custom("param".into()),
+        vec![
+            "watermark_position".to_string(),
+            "top-left".to_string(),
+            "top-right".to_string(),
+            "bottom-left".to_string(),
+            "bottom-right".to_string(),
+            "center".to_string(),
+        ],
+    ));
+
+    // Advertise archival uploads of the unmodified source
+    tags.push(Tag::custom(
+        TagKind::Custom("param".into()),
+        vec!["archive_source".to_string(), "true".to_string(), "false".to_string()],
+    ));
+
+    // Advertise EBU R128 loudness normalization
+    tags.push(Tag::custom(
+        TagKind::Custom("param".into()),
+        vec!["normalize_audio".to_string(), "true".to_string(), "false".to_string()],
+    ));
+
+    // Advertise metadata stripping (on unless the operator turned it off)
+    tags.push(Tag::custom(
+        TagKind::Custom("param".into()),
+        vec!["strip_metadata".to_string(), "true".to_string(), "false".to_string()],
+    ));
+
+    // Advertise HDR handling (tone-mapped to SDR unless kept)
+    tags.push(Tag::custom(
+        TagKind::Custom("param".into()),
+        vec!["hdr".to_string(), "tonemap".to_string(), "keep".to_string()],
+    ));
+
+    // Advertise deinterlacing (interlaced sources are detected by default)
+    tags.push(Tag::custom(
+        TagKind::Custom("param".into()),
+        vec![
+            "deinterlace".to_string(),
+            "auto".to_string(),
+            "on".to_string(),
+            "off".to_string(),
+        ],
+    ));
+
+    // Advertise the languages status messages can be sent in
+    let mut lang = vec!["lang".to_string()];
+    lang.extend(Lang::all().iter().map(|l| l.as_str().to_string()));
+    tags.push(Tag::custom(TagKind::Custom("param".into()), lang));
+
+    // Add supported HLS segment containers and encryption schemes
+    tags.push(Tag::custom(
+        TagKind::Custom("param".into()),
+        vec!["segment_type".to_string(), "fmp4".to_string(), "ts".to_string()],
+    ));
+    // Segment duration range in seconds (keyframe_interval has to divide it)
+    tags.push(Tag::custom(
+        TagKind::Custom("param".into()),
+        vec!["hls_time".to_string(), MIN_HLS_TIME.to_string(), MAX_HLS_TIME.to_string()],
+    ));
+    // Frame rate cap range (lower rungs stay at 30 fps or below)
+    tags.push(Tag::custom(
+        TagKind::Custom("param".into()),
+        vec!["max_fps".to_string(), MIN_MAX_FPS.to_string(), MAX_MAX_FPS.to_string()],
+    ));
+    tags.push(Tag::custom(
+        TagKind::Custom("param".into()),
+        vec!["encryption_scheme".to_string(), "aes-128".to_string()],
+    ));
+
     // Add hardware capability tags
     tags.push(Tag::custom(
         TagKind::Custom("capability".into()),
@@ -131,21 +306,19 @@ pub fn build_announcement_event_with_caps(config: &Config, hwaccel: HwAccel, cap
         vec!["hardware".to_string(), hwaccel_id.to_string()],
     ));
 
-    // Supported codecs
-    let codecs = match hwaccel {
-        HwAccel::Nvenc => "h264,h265,av1",
-        HwAccel::Vaapi => "h264,h265,av1",
-        HwAccel::Qsv => "h264,h265,av1",
-        HwAccel::VideoToolbox => "h264,h265",
-        HwAccel::Software => "h264,h265",
-    };
+    // Supported codecs: only those the backend encodes without falling back
+    let codecs: Vec<&str> = [Codec::H264, Codec::H265, Codec::AV1, Codec::Vp9]
+        .into_iter()
+        .filter(|c| Codec::from_encoder(hwaccel.video_encoder(*c)) == *c)
+        .map(|c| c.as_str())
+        .collect();
     tags.push(Tag::custom(
         TagKind::Custom("capability".into()),
-        vec!["codecs".to_string(), codecs.to_string()],
+        vec!["codecs".to_string(), codecs.join(",")],
     ));
 
     // Queue depth and concurrency
-    let max_concurrent = if caps.max_concurrent > 0 { caps.max_concurrent } else { 1 };
+    let max_concurrent = settings.max_concurrent_jobs.max(1);
     tags.push(Tag::custom(
         TagKind::Custom("capability".into()),
         vec!["max_concurrent".to_string(), max_concurrent.to_string()],
@@ -169,11 +342,58 @@ pub fn build_announcement_event_with_caps(config: &Config, hwaccel: HwAccel, cap
     // Pricing rate (0 = free)
     tags.push(Tag::custom(
         TagKind::Custom("rate".into()),
-        vec![config.base_rate_sats_per_min.to_string()],
+        vec![settings.base_rate_sats_per_min.to_string()],
     ));
 
+    // How long results are kept, and how much longer requesters can pay for
+    tags.push(Tag::custom(
+        TagKind::Custom("capability".into()),
+        vec!["retention_days".to_string(), settings.blob_expiration_days.to_string()],
+    ));
+    if settings.max_retention_days > settings.blob_expiration_days {
+        tags.push(Tag::custom(
+            TagKind::Custom("param".into()),
+            vec![
+                "retention".to_string(),
+                settings.blob_expiration_days.to_string(),
+                settings.max_retention_days.to_string(),
+            ],
+        ));
+        tags.push(Tag::custom(
+            TagKind::Custom("capability".into()),
+            vec![
+                "retention_sats_per_day".to_string(),
+                settings.retention_sats_per_day.to_string(),
+            ],
+        ));
+    }
+
+    if settings.price_sats_per_minute > 0 {
+        tags.push(Tag::custom(
+            TagKind::Custom("capability".into()),
+            vec![
+                "price_sats_per_minute".to_string(),
+                settings.price_sats_per_minute.to_string(),
+            ],
+        ));
+    }
+
+    // Job price components, as ["price", "<sats>", "sat", "<per>"] (absent = free)
+    for (sats, per) in [
+        (settings.price_sats_flat, "job"),
+        (settings.price_sats_per_minute, "minute"),
+        (settings.price_sats_per_output_gb, "gb"),
+    ] {
+        if sats > 0 {
+            tags.push(Tag::custom(
+                TagKind::Custom("price".into()),
+                vec![sats.to_string(), "sat".to_string(), per.to_string()],
+            ));
+        }
+    }
+
     // Add admin/operator tag if configured (NIP-89)
-    if let Some(admin) = &config.admin_pubkey {
+    if let Some(admin) = &settings.admin {
         tags.push(Tag::custom(
             TagKind::Custom("admin".into()),
             vec![admin.clone()],
@@ -193,24 +413,34 @@ pub fn build_announcement_event_with_caps(config: &Config, hwaccel: HwAccel, cap
 }
 
 /// Builds a NIP-65 relay list metadata event (kind 10002)
-pub fn build_relay_list_event(config: &Config) -> EventBuilder {
-    let tags: Vec<Tag> = config
-        .nostr_relays
-        .iter()
-        .map(|url| Tag::custom(TagKind::Custom("r".into()), vec![url.to_string()]))
+pub fn build_relay_list_event(settings: &RemoteConfig) -> EventBuilder {
+    let tags: Vec<Tag> = relay_urls(settings)
+        .into_iter()
+        .map(|url| Tag::custom(TagKind::Custom("r".into()), vec![url]))
         .collect();
 
     EventBuilder::new(RELAY_LIST_KIND, "", tags)
 }
 
+/// Builds a NIP-17 DM relay list event (kind 10050), so DM clients know
+/// where to send admin commands
+pub fn build_dm_relay_list_event(settings: &RemoteConfig) -> EventBuilder {
+    let tags: Vec<Tag> = relay_urls(settings)
+        .into_iter()
+        .map(|url| Tag::custom(TagKind::Custom("relay".into()), vec![url]))
+        .collect();
+
+    EventBuilder::new(DM_RELAY_LIST_KIND, "", tags)
+}
+
 /// Builds a kind 0 metadata event for the DVM's Nostr profile.
-pub fn build_metadata_event(config: &Config, hwaccel: HwAccel) -> EventBuilder {
-    let name = config
-        .dvm_name
+pub fn build_metadata_event(settings: &RemoteConfig, hwaccel: HwAccel) -> EventBuilder {
+    let name = settings
+        .name
         .clone()
         .unwrap_or_else(|| DEFAULT_DVM_NAME.to_string());
 
-    let about = config.dvm_about.clone().unwrap_or_else(|| {
+    let about = settings.about.clone().unwrap_or_else(|| {
         format!(
             "Video transformation DVM - converts videos to HLS streaming format. \
              Supports 360p, 720p, 1080p, and 4K. Hardware acceleration: {}.",
@@ -231,23 +461,32 @@ pub fn build_metadata_event(config: &Config, hwaccel: HwAccel) -> EventBuilder {
 }
 
 /// Builds a kind 3 contact list event that follows the operator.
-pub fn build_contact_list_event(config: &Config) -> Option<EventBuilder> {
-    let admin = config.admin_pubkey.as_ref()?;
-    let pubkey = PublicKey::parse(admin).ok()?;
+pub fn build_contact_list_event(settings: &RemoteConfig) -> Option<EventBuilder> {
+    let pubkey = settings.admin_pubkey()?;
     let contact = Contact::new(pubkey, None, None::<String>);
     Some(EventBuilder::contact_list([contact]))
 }
 
+/// Capabilities found by the runtime checks
+#[derive(Debug, Clone, PartialEq)]
+struct Detected {
+    hwaccel: HwAccel,
+    kinds: Vec<Kind>,
+}
+
 /// Manages periodic DVM announcement publishing.
 ///
-/// Republishes whenever the config changes (via admin commands)
-/// or on a regular hourly interval.
+/// Republishes whenever the config changes (via admin commands), when a
+/// re-check finds different capabilities (a GPU removed from a VM, FFmpeg
+/// gone missing), or on a regular hourly interval. Capabilities are
+/// re-checked hourly and whenever the recheck [`Notify`] fires.
 pub struct AnnouncementPublisher {
     config: Arc<Config>,
     state: SharedDvmState,
     publisher: Arc<EventPublisher>,
     hwaccel: HwAccel,
-    config_notify: Arc<Notify>,
+    config_watch: ConfigWatch,
+    recheck: Arc<Notify>,
 }
 
 impl AnnouncementPublisher {
@@ -256,34 +495,49 @@ impl AnnouncementPublisher {
         state: SharedDvmState,
         publisher: Arc<EventPublisher>,
         hwaccel: HwAccel,
-        config_notify: Arc<Notify>,
+        config_watch: ConfigWatch,
     ) -> Self {
         Self {
             config,
             state,
             publisher,
             hwaccel,
-            config_notify,
+            config_watch,
+            recheck: Arc::new(Notify::new()),
         }
     }
 
+    /// Re-check capabilities whenever `notify` fires, e.g. on
+    /// [`VideoProcessor::hw_failures`](crate::video::VideoProcessor::hw_failures)
+    pub fn with_recheck(mut self, notify: Arc<Notify>) -> Self {
+        self.recheck = notify;
+        self
+    }
+
     /// Run the announcement publisher, publishing immediately and then periodically.
     ///
     /// Also republishes immediately when notified of config changes.
     pub async fn run(&self) {
         info!("Announcement publisher started");
+        let mut config_changed = self.config_watch.subscribe();
 
         // Give relays a few seconds to connect before the first announcement
         tokio::time::sleep(Duration::from_secs(3)).await;
 
+        // Hardware acceleration was detected at startup
+        let mut detected = Detected {
+            hwaccel: self.hwaccel,
+            kinds: supported_kinds(&self.config).await,
+        };
+
         // Initial publish: announcement + relay list + profile + contact list
-        let config = self.current_snapshot().await;
-        self.publish_announcement(&config).await;
-        self.publish_metadata(&config).await;
-        self.publish_contact_list(&config).await;
-        let mut last_relays = self.publish_relay_list(&config).await;
-        let mut last_profile = (config.dvm_name.clone(), config.dvm_about.clone());
-        let mut last_admin = config.admin_pubkey.clone();
+        let settings = self.current_settings().await;
+        self.publish_announcement(&settings, &detected).await;
+        self.publish_metadata(&settings, detected.hwaccel).await;
+        self.publish_contact_list(&settings).await;
+        let mut last_relays = self.publish_relay_list(&settings).await;
+        let mut last_profile = (settings.name.clone(), settings.about.clone());
+        let mut last_admin = settings.admin.clone();
 
         // Then publish every hour or when config changes
         let mut ticker = interval(Duration::from_secs(3600));
@@ -292,34 +546,57 @@ impl AnnouncementPublisher {
         loop {
             tokio::select! {
                 _ = ticker.tick() => {
-                    let config = self.current_snapshot().await;
-                    self.publish_announcement(&config).await;
+                    let current = self.detect().await;
+                    let settings = self.current_settings().await;
+                    if current.hwaccel != detected.hwaccel {
+                        self.publish_metadata(&settings, current.hwaccel).await;
+                    }
+                    detected = current;
+                    self.publish_announcement(&settings, &detected).await;
                 }
-                _ = self.config_notify.notified() => {
+                _ = self.recheck.notified() => {
+                    let current = self.detect().await;
+                    if current == detected {
+                        continue;
+                    }
+                    info!(
+                        hwaccel = %current.hwaccel,
+                        kinds = ?current.kinds,
+                        "Capabilities changed, republishing announcement"
+                    );
+                    let settings = self.current_settings().await;
+                    if current.hwaccel != detected.hwaccel {
+                        self.publish_metadata(&settings, current.hwaccel).await;
+                    }
+                    detected = current;
+                    self.publish_announcement(&settings, &detected).await;
+                    ticker.reset();
+                }
+                Ok(()) = config_changed.changed() => {
                     info!("Config changed, republishing announcement");
-                    let config = self.current_snapshot().await;
-                    self.publish_announcement(&config).await;
+                    let settings = self.current_settings().await;
+                    self.publish_announcement(&settings, &detected).await;
 
                     // Republish profile (kind 0) if name or about changed
-                    let current_profile = (config.dvm_name.clone(), config.dvm_about.clone());
+                    let current_profile = (settings.name.clone(), settings.about.clone());
                     if current_profile != last_profile {
                         info!("Profile changed, republishing metadata");
-                        self.publish_metadata(&config).await;
+                        self.publish_metadata(&settings, detected.hwaccel).await;
                         last_profile = current_profile;
                     }
 
                     // Republish contact list if admin changed
-                    if config.admin_pubkey != last_admin {
+                    if settings.admin != last_admin {
                         info!("Admin changed, republishing contact list");
-                        self.publish_contact_list(&config).await;
-                        last_admin = config.admin_pubkey.clone();
+                        self.publish_contact_list(&settings).await;
+                        last_admin = settings.admin.clone();
                     }
 
                     // Only republish relay list if relays actually changed
-                    let current_relays: HashSet<String> = config.nostr_relays.iter().map(|u| u.to_string()).collect();
+                    let current_relays: HashSet<String> = relay_urls(&settings).into_iter().collect();
                     if current_relays != last_relays {
                         info!("Relay list changed, republishing NIP-65");
-                        last_relays = self.publish_relay_list(&config).await;
+                        last_relays = self.publish_relay_list(&settings).await;
                     }
 
                     // Reset the interval so we don't publish again too soon
@@ -329,49 +606,32 @@ impl AnnouncementPublisher {
         }
     }
 
-    /// Build a current Config snapshot from the shared DVM state.
-    fn current_config(&self, state: &crate::dvm_state::DvmState) -> Config {
-        Config {
-            nostr_keys: self.config.nostr_keys.clone(),
-            nostr_relays: state
-                .config
-                .relays
-                .iter()
-                .filter_map(|s| s.parse().ok())
-                .collect(),
-            blossom_servers: state
-                .config
-                .blossom_servers
-                .iter()
-                .filter_map(|s| s.parse().ok())
-                .collect(),
-            blob_expiration_days: state.config.blob_expiration_days,
-            temp_dir: self.config.temp_dir.clone(),
-            ffmpeg_path: self.config.ffmpeg_path.clone(),
-            ffprobe_path: self.config.ffprobe_path.clone(),
-            http_port: self.config.http_port,
-            http_enabled: self.config.http_enabled,
-            dvm_name: state.config.name.clone(),
-            dvm_about: state.config.about.clone(),
-            admin_pubkey: state.config.admin.clone(),
-            base_rate_sats_per_min: state.config.base_rate_sats_per_min,
-        }
+    /// Snapshot of the operator settings from the shared DVM state.
+    async fn current_settings(&self) -> RemoteConfig {
+        self.state.read().await.config.clone()
     }
 
-    async fn current_snapshot(&self) -> Config {
-        let state = self.state.read().await;
-        self.current_config(&state)
+    /// Run the capability checks again. Detection probes the GPU with
+    /// blocking FFmpeg runs, so it goes to the blocking pool.
+    async fn detect(&self) -> Detected {
+        let hwaccel = tokio::task::spawn_blocking(HwAccel::detect)
+            .await
+            .unwrap_or(self.hwaccel);
+        Detected {
+            hwaccel,
+            kinds: supported_kinds(&self.config).await,
+        }
     }
 
-    async fn publish_announcement(&self, config: &Config) {
-        let name = config
-            .dvm_name
+    async fn publish_announcement(&self, settings: &RemoteConfig, detected: &Detected) {
+        let name = settings
+            .name
             .clone()
             .unwrap_or_else(|| DEFAULT_DVM_NAME.to_string());
 
         info!(
             name = %name,
-            about = ?config.dvm_about,
+            about = ?settings.about,
             "Publishing DVM announcement"
         );
 
@@ -380,16 +640,16 @@ impl AnnouncementPublisher {
             DvmCapabilities {
                 avg_speeds: state.avg_speeds.clone(),
                 jobs_active: state.jobs_active,
-                max_concurrent: state.config.max_concurrent_jobs,
+                kinds: detected.kinds.clone(),
             }
         };
 
-        let event = build_announcement_event_with_caps(config, self.hwaccel, &caps);
+        let event = build_announcement_event_with_caps(settings, detected.hwaccel, &caps);
 
         match self.publisher.publish(event).await {
             Ok(_) => {
                 info!(
-                    pubkey = %config.nostr_keys.public_key(),
+                    pubkey = %self.config.nostr_keys.public_key(),
                     service_id = %DVM_SERVICE_ID,
                     "DVM announcement published"
                 );
@@ -400,12 +660,15 @@ impl AnnouncementPublisher {
         }
     }
 
-    /// Publish NIP-65 relay list. Returns the set of relays that were published.
-    async fn publish_relay_list(&self, config: &Config) -> HashSet<String> {
-        let relay_list = build_relay_list_event(config);
+    /// Publish NIP-65 relay list and NIP-17 DM relay list. Returns the set
+    /// of relays that were published.
+    async fn publish_relay_list(&self, settings: &RemoteConfig) -> HashSet<String> {
+        let relay_list = build_relay_list_event(settings);
+        let dm_relay_list = build_dm_relay_list_event(settings);
+        let dvm_relays = relay_urls(settings);
 
         // Collect DVM relay URLs + index relay URLs
-        let mut relay_urls: Vec<String> = config.nostr_relays.iter().map(|u| u.to_string()).collect();
+        let mut relay_urls = dvm_relays.clone();
         for index_relay in INDEX_RELAYS {
             let s = index_relay.to_string();
             if !relay_urls.iter().any(|existing| existing.trim_end_matches('/') == s) {
@@ -420,12 +683,12 @@ impl AnnouncementPublisher {
             .collect();
         self.publisher.ensure_relays_connected(&index_urls).await;
 
-        let published_relays: HashSet<String> = config.nostr_relays.iter().map(|u| u.to_string()).collect();
+        let published_relays: HashSet<String> = dvm_relays.iter().cloned().collect();
 
         match self.publisher.publish_to(relay_list, &relay_urls).await {
             Ok(_) => {
                 info!(
-                    relays = ?config.nostr_relays.iter().map(|u| u.as_str()).collect::<Vec<_>>(),
+                    relays = ?dvm_relays,
                     index_relays = ?INDEX_RELAYS,
                     "NIP-65 relay list published"
                 );
@@ -434,14 +697,17 @@ impl AnnouncementPublisher {
                 error!(error = %e, "Failed to publish relay list");
             }
         }
+        if let Err(e) = self.publisher.publish_to(dm_relay_list, &relay_urls).await {
+            error!(error = %e, "Failed to publish DM relay list");
+        }
 
         published_relays
     }
 
     /// Publish kind 0 metadata (Nostr profile) for the DVM.
-    async fn publish_metadata(&self, config: &Config) {
-        let name = config
-            .dvm_name
+    async fn publish_metadata(&self, settings: &RemoteConfig, hwaccel: HwAccel) {
+        let name = settings
+            .name
             .clone()
             .unwrap_or_else(|| DEFAULT_DVM_NAME.to_string());
 
@@ -450,12 +716,12 @@ impl AnnouncementPublisher {
             "Publishing DVM profile metadata (kind 0)"
         );
 
-        let event = build_metadata_event(config, self.hwaccel);
+        let event = build_metadata_event(settings, hwaccel);
 
         match self.publisher.publish(event).await {
             Ok(_) => {
                 info!(
-                    pubkey = %config.nostr_keys.public_key(),
+                    pubkey = %self.config.nostr_keys.public_key(),
                     "DVM profile metadata published"
                 );
             }
@@ -466,8 +732,8 @@ impl AnnouncementPublisher {
     }
 
     /// Publish kind 3 contact list (auto-follow operator).
-    async fn publish_contact_list(&self, config: &Config) {
-        let event = match build_contact_list_event(config) {
+    async fn publish_contact_list(&self, settings: &RemoteConfig) {
+        let event = match build_contact_list_event(settings) {
             Some(e) => e,
             None => return,
         };
@@ -488,7 +754,6 @@ impl AnnouncementPublisher {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::PathBuf;
 
     #[test]
     fn test_announcement_event_kind() {
@@ -500,23 +765,14 @@ mod tests {
         let keys = Keys::generate();
         let admin_pubkey = "b7c6f6915cfa9a62fff6a1f02604de88c23c6c6c6d1b8f62c7cc10749f307e81";
         
-        let config = Config {
-            nostr_keys: keys.clone(),
-            nostr_relays: vec![],
-            blossom_servers: vec![],
-            blob_expiration_days: 30,
-            temp_dir: PathBuf::from("/tmp"),
-            ffmpeg_path: PathBuf::from("ffmpeg"),
-            ffprobe_path: PathBuf::from("ffprobe"),
-            http_port: 5207,
-            http_enabled: true,
-            dvm_name: Some("Test DVM".to_string()),
-            dvm_about: Some("Test DVM about".to_string()),
-            admin_pubkey: Some(admin_pubkey.to_string()),
-            base_rate_sats_per_min: 0,
+        let settings = RemoteConfig {
+            admin: Some(admin_pubkey.to_string()),
+            name: Some("Test DVM".to_string()),
+            about: Some("Test DVM about".to_string()),
+            ..RemoteConfig::default()
         };
 
-        let event_builder = build_announcement_event(&config, HwAccel::Software);
+        let event_builder = build_announcement_event(&settings, HwAccel::Software);
         let event = event_builder.to_event(&keys).unwrap();
 
         // Find the admin tag
@@ -547,23 +803,13 @@ mod tests {
     fn test_announcement_without_admin_tag() {
         let keys = Keys::generate();
         
-        let config = Config {
-            nostr_keys: keys.clone(),
-            nostr_relays: vec![],
-            blossom_servers: vec![],
-            blob_expiration_days: 30,
-            temp_dir: PathBuf::from("/tmp"),
-            ffmpeg_path: PathBuf::from("ffmpeg"),
-            ffprobe_path: PathBuf::from("ffprobe"),
-            http_port: 5207,
-            http_enabled: true,
-            dvm_name: Some("Test DVM".to_string()),
-            dvm_about: Some("Test DVM about".to_string()),
-            admin_pubkey: None,
-            base_rate_sats_per_min: 0,
+        let settings = RemoteConfig {
+            name: Some("Test DVM".to_string()),
+            about: Some("Test DVM about".to_string()),
+            ..RemoteConfig::default()
         };
 
-        let event_builder = build_announcement_event(&config, HwAccel::Software);
+        let event_builder = build_announcement_event(&settings, HwAccel::Software);
         let event = event_builder.to_event(&keys).unwrap();
 
         // Find the admin tag
@@ -574,27 +820,130 @@ mod tests {
         assert!(admin_tag.is_none(), "Admin tag should not be present when no admin is configured");
     }
 
+    #[test]
+    fn test_announcement_retention_param() {
+        let keys = Keys::generate();
+
+        let settings = RemoteConfig {
+            name: None,
+            about: None,
+            ..RemoteConfig::default()
+        };
+
+        let retention_param = |settings: &RemoteConfig| {
+            let event = build_announcement_event(settings, HwAccel::Software)
+                .to_event(&keys)
+                .unwrap();
+            event
+                .tags
+                .iter()
+                .map(|t| t.as_slice().to_vec())
+                .find(|t| t.len() >= 2 && t[0] == "param" && t[1] == "retention")
+        };
+
+        assert_eq!(retention_param(&settings), None);
+        let settings = RemoteConfig {
+            max_retention_days: 365,
+            retention_sats_per_day: 2,
+            ..settings
+        };
+        assert_eq!(
+            retention_param(&settings),
+            Some(vec!["param".into(), "retention".into(), "30".into(), "365".into()])
+        );
+    }
+
+    #[test]
+    fn test_announcement_price_tags() {
+        let keys = Keys::generate();
+
+        let price_tags = |settings: &RemoteConfig| {
+            let event = build_announcement_event(settings, HwAccel::Software)
+                .to_event(&keys)
+                .unwrap();
+            event
+                .tags
+                .iter()
+                .map(|t| t.as_slice().to_vec())
+                .filter(|t| t[0] == "price")
+                .collect::<Vec<_>>()
+        };
+
+        assert!(price_tags(&RemoteConfig::default()).is_empty());
+        let settings = RemoteConfig {
+            price_sats_flat: 100,
+            price_sats_per_output_gb: 50,
+            ..RemoteConfig::default()
+        };
+        assert_eq!(
+            price_tags(&settings),
+            vec![
+                vec!["price".to_string(), "100".into(), "sat".into(), "job".into()],
+                vec!["price".to_string(), "50".into(), "sat".into(), "gb".into()],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_announcement_kinds() {
+        let keys = Keys::generate();
+
+        let kind_tags = |caps: &DvmCapabilities| {
+            let event = build_announcement_event_with_caps(&RemoteConfig::default(), HwAccel::Software, caps)
+                .to_event(&keys)
+                .unwrap();
+            event
+                .tags
+                .iter()
+                .map(|t| t.as_slice().to_vec())
+                .filter(|t| t[0] == "k")
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            kind_tags(&DvmCapabilities::default()),
+            vec![vec!["k".to_string(), "5207".into()]]
+        );
+
+        // A missing FFmpeg leaves nothing to announce
+        let config = Config::from_env(
+            keys.clone(),
+            "/nonexistent/ffmpeg".into(),
+            "/nonexistent/ffprobe".into(),
+        );
+        let caps = DvmCapabilities {
+            kinds: supported_kinds(&config).await,
+            ..DvmCapabilities::default()
+        };
+        assert!(kind_tags(&caps).is_empty());
+    }
+
+    #[test]
+    fn test_dm_relay_list_event() {
+        let settings = RemoteConfig {
+            relays: vec!["wss://relay.example.com".to_string(), "not a url".to_string()],
+            ..RemoteConfig::default()
+        };
+        let event = build_dm_relay_list_event(&settings)
+            .to_event(&Keys::generate())
+            .unwrap();
+
+        assert_eq!(event.kind.as_u16(), 10050);
+        let tags: Vec<_> = event.tags.iter().map(|t| t.as_slice().to_vec()).collect();
+        assert_eq!(tags, vec![vec!["relay".to_string(), "wss://relay.example.com/".into()]]);
+    }
+
     #[test]
     fn test_metadata_event() {
         let keys = Keys::generate();
 
-        let config = Config {
-            nostr_keys: keys.clone(),
-            nostr_relays: vec![],
-            blossom_servers: vec![],
-            blob_expiration_days: 30,
-            temp_dir: PathBuf::from("/tmp"),
-            ffmpeg_path: PathBuf::from("ffmpeg"),
-            ffprobe_path: PathBuf::from("ffprobe"),
-            http_port: 5207,
-            http_enabled: true,
-            dvm_name: Some("My DVM".to_string()),
-            dvm_about: Some("Transcodes videos".to_string()),
-            admin_pubkey: None,
-            base_rate_sats_per_min: 0,
+        let settings = RemoteConfig {
+            name: Some("My DVM".to_string()),
+            about: Some("Transcodes videos".to_string()),
+            ..RemoteConfig::default()
         };
 
-        let event_builder = build_metadata_event(&config, HwAccel::Software);
+        let event_builder = build_metadata_event(&settings, HwAccel::Software);
         let event = event_builder.to_event(&keys).unwrap();
 
         assert_eq!(event.kind, Kind::Metadata);
@@ -609,23 +958,14 @@ mod tests {
         let keys = Keys::generate();
         let admin_pubkey = "b7c6f6915cfa9a62fff6a1f02604de88c23c6c6c6d1b8f62c7cc10749f307e81";
 
-        let config = Config {
-            nostr_keys: keys.clone(),
-            nostr_relays: vec![],
-            blossom_servers: vec![],
-            blob_expiration_days: 30,
-            temp_dir: PathBuf::from("/tmp"),
-            ffmpeg_path: PathBuf::from("ffmpeg"),
-            ffprobe_path: PathBuf::from("ffprobe"),
-            http_port: 5207,
-            http_enabled: true,
-            dvm_name: None,
-            dvm_about: None,
-            admin_pubkey: Some(admin_pubkey.to_string()),
-            base_rate_sats_per_min: 0,
+        let settings = RemoteConfig {
+            admin: Some(admin_pubkey.to_string()),
+            name: None,
+            about: None,
+            ..RemoteConfig::default()
         };
 
-        let builder = build_contact_list_event(&config).expect("Should build contact list");
+        let builder = build_contact_list_event(&settings).expect("Should build contact list");
         let event = builder.to_event(&keys).unwrap();
 
         assert_eq!(event.kind, Kind::ContactList);
@@ -638,22 +978,12 @@ mod tests {
 
     #[test]
     fn test_contact_list_without_admin() {
-        let config = Config {
-            nostr_keys: Keys::generate(),
-            nostr_relays: vec![],
-            blossom_servers: vec![],
-            blob_expiration_days: 30,
-            temp_dir: PathBuf::from("/tmp"),
-            ffmpeg_path: PathBuf::from("ffmpeg"),
-            ffprobe_path: PathBuf::from("ffprobe"),
-            http_port: 5207,
-            http_enabled: true,
-            dvm_name: None,
-            dvm_about: None,
-            admin_pubkey: None,
-            base_rate_sats_per_min: 0,
+        let settings = RemoteConfig {
+            name: None,
+            about: None,
+            ..RemoteConfig::default()
         };
 
-        assert!(build_contact_list_event(&config).is_none());
+        assert!(build_contact_list_event(&settings).is_none());
     }
 }
@@ -0,0 +1,98 @@
+//! Recognizing creators re-transcoding their own videos.
+//!
+//! A request can point at the video event its input belongs to with an
+//! `["e", "<event id>"]` tag. When that event is a NIP-71 video or NIP-94
+//! file metadata event published by the requester and it references the
+//! input, the job is re-transcoding the requester's own content. Operators
+//! can start such jobs first (`creator_priority`) and charge less for them
+//! (`creator_discount_percent`) so creators build proper HLS ladders for
+//! their catalogs.
+
+use nostr_sdk::{Event, Kind, PublicKey};
+
+use crate::util::hash::sha256_from_url;
+
+/// NIP-71 videos (normal, short, and their addressable variants) and NIP-94 file metadata
+pub(crate) const VIDEO_EVENT_KINDS: [u16; 5] = [21, 22, 34235, 34236, 1063];
+
+/// Whether `event` is a video event by `requester` that references `input_url`.
+///
+/// The input matches a `url` tag (or `imeta` `url` entry) exactly, or by
+/// Blossom hash against an `x` tag, so mirrors of the same blob count too.
+pub fn is_own_video(event: &Event, requester: &PublicKey, input_url: &str) -> bool {
+    if event.pubkey != *requester || !VIDEO_EVENT_KINDS.iter().any(|k| event.kind == Kind::from(*k)) {
+        return false;
+    }
+    let input_hash = sha256_from_url(input_url);
+
+    event.tags.iter().any(|tag| {
+        let parts = tag.as_slice();
+        let fields: Vec<(&str, &str)> = match parts.first().map(String::as_str) {
+            Some("imeta") => parts[1..].iter().filter_map(|p| p.split_once(' ')).collect(),
+            Some(name) if parts.len() >= 2 => vec![(name, parts[1].as_str())],
+            _ => Vec::new(),
+        };
+        fields.into_iter().any(|(name, value)| match name {
+            "url" => value == input_url,
+            "x" => input_hash.as_deref() == Some(value.to_lowercase().as_str()),
+            _ => false,
+        })
+    })
+}
+
+/// `price` after a discount of `percent`
+pub fn discounted(price: u64, percent: u8) -> u64 {
+    price - price * u64::from(percent.min(100)) / 100
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr_sdk::{EventBuilder, Keys, Tag, TagKind};
+
+    fn video_event(keys: &Keys, kind: u16, tags: Vec<Vec<&str>>) -> Event {
+        let tags = tags
+            .into_iter()
+            .map(|t| {
+                Tag::custom(
+                    TagKind::Custom(t[0].to_string().into()),
+                    t[1..].iter().map(|s| s.to_string()),
+                )
+            })
+            .collect::<Vec<_>>();
+        EventBuilder::new(Kind::from(kind), "", tags)
+            .to_event(keys)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_is_own_video() {
+        let keys = Keys::generate();
+        let hash = "b".repeat(64);
+        let url = format!("https://blossom.example.com/{}.mp4", hash);
+        let mirror = format!("https://mirror.example.com/{}.mp4", hash);
+
+        // NIP-71 video with imeta
+        let imeta = format!("url {}", url);
+        let event = video_event(&keys, 21, vec![vec!["imeta", "dim 1920x1080", &imeta]]);
+        assert!(is_own_video(&event, &keys.public_key(), &url));
+        assert!(!is_own_video(&event, &Keys::generate().public_key(), &url));
+        assert!(!is_own_video(&event, &keys.public_key(), "https://other.example.com/v.mp4"));
+
+        // NIP-94 file metadata, matched through the blob hash
+        let event = video_event(&keys, 1063, vec![vec!["url", &url], vec!["x", &hash]]);
+        assert!(is_own_video(&event, &keys.public_key(), &mirror));
+
+        // Not a video event
+        let event = video_event(&keys, 1, vec![vec!["url", &url]]);
+        assert!(!is_own_video(&event, &keys.public_key(), &url));
+    }
+
+    #[test]
+    fn test_discounted() {
+        assert_eq!(discounted(100, 0), 100);
+        assert_eq!(discounted(100, 25), 75);
+        assert_eq!(discounted(15, 50), 8);
+        assert_eq!(discounted(100, 200), 0);
+    }
+}
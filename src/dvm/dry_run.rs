@@ -0,0 +1,143 @@
+//! Dry runs: the FFmpeg command a job would run, without running it.
+//!
+//! Diagnosing an encoder or filter problem a requester reports starts with
+//! the exact command line their job got. A dry run probes the input with
+//! ffprobe and builds the same [`EncodePlan`] a job would, down to the argv,
+//! but encodes nothing. Analysis passes that decode the whole input
+//! (interlace detection, loudness measurement) are skipped; what the job
+//! would do differently is listed in the notes.
+
+use nostr_sdk::prelude::*;
+use std::collections::BTreeMap;
+use tracing::debug;
+
+use crate::config::Config;
+use crate::dvm::encode_plan::EncodePlan;
+use crate::dvm::events::{JobContext, OutputMode, DVM_VIDEO_TRANSFORM_REQUEST_KIND};
+use crate::dvm::handler::capability_gap;
+use crate::error::DvmError;
+use crate::remote_config::RemoteConfig;
+use crate::video::{HwAccel, VideoMetadata, VideoProcessor};
+
+/// The command a job would run
+#[derive(Debug, Clone, PartialEq)]
+pub struct DryRun {
+    pub mode: OutputMode,
+    /// Program and arguments, as the job would spawn them
+    pub argv: Vec<String>,
+    /// Where the job could end up with a different command
+    pub notes: Vec<String>,
+}
+
+/// Job for `input` with `params` as its `param` tags, signed by a throwaway
+/// key and with the operator's defaults applied
+pub fn job_from_params(
+    input: &str,
+    params: &BTreeMap<String, String>,
+    settings: &RemoteConfig,
+) -> Result<JobContext, DvmError> {
+    let mut tags = vec![Tag::custom(
+        TagKind::Custom("i".into()),
+        vec![input.to_string(), "url".to_string()],
+    )];
+    for (name, value) in params {
+        tags.push(Tag::custom(
+            TagKind::Custom("param".into()),
+            vec![name.clone(), value.clone()],
+        ));
+    }
+    let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+        .to_event(&Keys::generate())
+        .map_err(|e| DvmError::JobRejected(format!("Failed to build request: {e}")))?;
+    let mut job = JobContext::from_event(event)?;
+    job.apply_defaults(&settings.job_defaults());
+    Ok(job)
+}
+
+/// Plan `job` and return the FFmpeg command it would run on this DVM
+pub async fn plan(
+    processor: &VideoProcessor,
+    config: &Config,
+    settings: &RemoteConfig,
+    mut job: JobContext,
+) -> Result<DryRun, DvmError> {
+    if job.mode == OutputMode::Thumbnail {
+        return Err(DvmError::JobRejected(
+            "Thumbnail jobs have no encode to dry-run".to_string(),
+        ));
+    }
+    let hwaccel = processor.hwaccel();
+    job.apply_device(hwaccel);
+    // A request the DVM would decline never gets to FFmpeg
+    if let Some((reason, msg)) = capability_gap(&job, config, hwaccel) {
+        return Err(DvmError::JobRejected(format!("{} ({})", msg, reason.as_str())));
+    }
+
+    let input_url = job.input.value.clone();
+    let mut notes = Vec::new();
+    let metadata = match VideoMetadata::extract(&input_url, &config.ffprobe_path).await {
+        Ok(m) => Some(m),
+        Err(e) => {
+            notes.push(format!("Probing the input failed ({e}), planned without its metadata"));
+            None
+        }
+    };
+    let plan = EncodePlan::new(&job, metadata.as_ref(), settings, hwaccel)?;
+
+    if plan.detect_interlace {
+        notes.push("Interlace detection skipped, going by the container's field order".to_string());
+    }
+    if plan.loudnorm.is_some() {
+        notes.push(
+            "Loudness measurement skipped, normalizing in a single pass \
+             (jobs pass the measured loudness to the filter)"
+                .to_string(),
+        );
+    }
+    if !config.output_limits.with_overrides(settings).is_unlimited() {
+        notes.push(
+            "Output size limit not checked, jobs may get a lower quality to fit it".to_string(),
+        );
+    }
+    if hwaccel != HwAccel::Software {
+        notes.push(format!(
+            "A failed {hwaccel} encode is retried with software encoding"
+        ));
+    }
+
+    let argv = match job.mode {
+        OutputMode::Hls => processor.hls_command_line(&input_url, &plan).await?,
+        _ => processor.mp4_command_line(&input_url, &plan),
+    };
+    debug!(url = %input_url, mode = job.mode.as_str(), "Planned dry run");
+
+    Ok(DryRun {
+        mode: job.mode,
+        argv,
+        notes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dvm::events::{Codec, Resolution};
+
+    #[test]
+    fn test_job_from_params() {
+        let settings = RemoteConfig {
+            default_codec: Some("h265".to_string()),
+            ..RemoteConfig::default()
+        };
+        let params = BTreeMap::from([
+            ("mode".to_string(), "mp4".to_string()),
+            ("resolution".to_string(), "1080p".to_string()),
+        ]);
+        let job = job_from_params("https://example.com/video.mp4", &params, &settings).unwrap();
+        assert_eq!(job.input.value, "https://example.com/video.mp4");
+        assert_eq!(job.mode, OutputMode::Mp4);
+        assert_eq!(job.resolution, Resolution::R1080p);
+        // The operator's defaults fill in what the params leave out
+        assert_eq!(job.codec, Codec::H265);
+    }
+}
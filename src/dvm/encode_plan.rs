@@ -0,0 +1,375 @@
+//! Encode plans: how a job's video gets encoded.
+//!
+//! Every choice the encode depends on (codec, rungs, filters, audio
+//! normalization, quality) is made here from the job, the probed source and
+//! the operator's settings, so jobs and dry runs can't disagree about them.
+//! Analysis passes that decode the source refine the plan afterwards:
+//! interlace detection replaces `deinterlace` and loudness measurement fills
+//! in `loudnorm`.
+
+use std::collections::HashMap;
+
+use crate::dvm::events::{Codec, JobContext, OutputMode, Resolution};
+use crate::error::VideoError;
+use crate::remote_config::RemoteConfig;
+use crate::video::clip::Clip;
+use crate::video::deinterlace::{Deinterlace, DeinterlaceMode, FieldOrder};
+use crate::video::fps_cap::FpsCap;
+use crate::video::loudness::Loudnorm;
+use crate::video::metadata::parse_frame_rate;
+use crate::video::segmenting::Segmenting;
+use crate::video::tonemap::Tonemap;
+use crate::video::transform::{
+    ladder_with_crf_offset, ladder_with_presets, preset_crf, AudioTrack, Container, LadderRung,
+    QualityPreset, ResolutionConfig, SegmentType, TransformConfig, MP4_CRF, ORIGINAL_CRF,
+};
+use crate::video::watermark::Watermark;
+use crate::video::{HwAccel, VideoMetadata};
+
+/// Everything an encode needs besides its input and output
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodePlan {
+    /// Requested codec (the backend may fall back to another)
+    pub codec: Codec,
+    /// Source video codec name, for decoder hints and passthrough
+    pub source_codec: Option<String>,
+    /// Upright width and height of the source
+    pub input_size: Option<(u32, u32)>,
+    /// Seconds of the source that are encoded
+    pub duration_secs: f64,
+    pub clip: Option<Clip>,
+    /// Constant frame rate to normalize a VFR source to
+    pub frame_rate: Option<String>,
+    pub fps_cap: Option<FpsCap>,
+    pub watermark: Option<Watermark>,
+    pub deinterlace: Option<Deinterlace>,
+    /// Run interlace detection, which replaces `deinterlace`
+    pub detect_interlace: bool,
+    pub tonemap: Option<Tonemap>,
+    /// Clockwise display rotation of the source
+    pub rotation: u32,
+    pub loudnorm: Option<Loudnorm>,
+    pub strip_metadata: bool,
+    /// MP4 output resolution
+    pub resolution: Resolution,
+    /// MP4 CRF
+    pub quality: u32,
+    pub container: Container,
+    /// HLS rungs
+    pub resolutions: Vec<Resolution>,
+    pub encryption: bool,
+    pub segment_type: SegmentType,
+    pub segmenting: Segmenting,
+    /// Per-rendition audio bitrate overrides, keyed by label
+    pub audio_bitrates: HashMap<String, String>,
+    /// Bitrate ladder (empty for the built-in one)
+    pub ladder: Vec<LadderRung>,
+    /// Quality of the original when it has to be re-encoded
+    pub original_crf: u32,
+    pub audio_tracks: Vec<AudioTrack>,
+}
+
+impl Default for EncodePlan {
+    fn default() -> Self {
+        Self {
+            codec: Codec::default(),
+            source_codec: None,
+            input_size: None,
+            duration_secs: 0.0,
+            clip: None,
+            frame_rate: None,
+            fps_cap: None,
+            watermark: None,
+            deinterlace: None,
+            detect_interlace: false,
+            tonemap: None,
+            rotation: 0,
+            loudnorm: None,
+            strip_metadata: false,
+            resolution: Resolution::default(),
+            quality: MP4_CRF,
+            container: Container::default(),
+            resolutions: Resolution::all(),
+            encryption: false,
+            segment_type: SegmentType::default(),
+            segmenting: Segmenting::default(),
+            audio_bitrates: HashMap::new(),
+            ladder: Vec::new(),
+            original_crf: ORIGINAL_CRF,
+            audio_tracks: Vec::new(),
+        }
+    }
+}
+
+impl EncodePlan {
+    /// Plan `job` on a source described by `metadata` (`None` when probing
+    /// failed). Fails when the job's clip doesn't fit the source.
+    pub fn new(
+        job: &JobContext,
+        metadata: Option<&VideoMetadata>,
+        settings: &RemoteConfig,
+        hwaccel: HwAccel,
+    ) -> Result<Self, VideoError> {
+        let stream = metadata.and_then(|m| m.video_stream());
+        let source_duration = metadata.and_then(|m| m.duration_secs()).unwrap_or(0.0);
+
+        // Check the requested range against the source
+        let clip = job
+            .clip
+            .map(|c| c.validate(source_duration))
+            .transpose()
+            .map_err(VideoError::InvalidInput)?;
+
+        // Phone and screen recordings are often VFR, which stutters and
+        // drifts once segmented; encode those at a constant rate instead
+        let frame_rate = stream.and_then(|s| s.cfr_target());
+
+        // Slow-motion clips don't need their full frame rate in every rung
+        let fps_cap = job.max_fps.and_then(|max_fps| {
+            let source_fps = frame_rate
+                .as_deref()
+                .and_then(parse_frame_rate)
+                .or_else(|| parse_frame_rate(stream?.frame_rate.as_deref()?))?;
+            FpsCap::new(max_fps, source_fps)
+        });
+
+        // HDR sources come out washed out as SDR unless tone-mapped
+        let tonemap = stream.and_then(|s| Tonemap::for_stream(s, job.hdr));
+
+        // Interlaced archive and TV content combs in every rung otherwise.
+        // Forced deinterlacing takes the field order from the container,
+        // assuming top field first without one.
+        let encodes = job.mode != OutputMode::Thumbnail;
+        let flagged = stream.and_then(Deinterlace::for_stream);
+        let (deinterlace, detect_interlace) = match job.deinterlace {
+            _ if !encodes => (None, false),
+            DeinterlaceMode::Off => (None, false),
+            DeinterlaceMode::On => (
+                Some(flagged.unwrap_or(Deinterlace {
+                    field_order: FieldOrder::TopFirst,
+                })),
+                false,
+            ),
+            DeinterlaceMode::Auto => (flagged, stream.is_some()),
+        };
+
+        let normalize_audio = job.normalize_audio.unwrap_or(settings.normalize_audio);
+        let has_audio = metadata.is_some_and(|m| !m.audio_streams().is_empty());
+        let loudnorm = (encodes && normalize_audio && has_audio).then(Loudnorm::default);
+
+        let presets = &settings.quality_presets;
+        let encoded_codec = Codec::from_encoder(hwaccel.video_encoder(job.codec));
+        Ok(Self {
+            codec: job.codec,
+            source_codec: stream.and_then(|s| s.codec_name.clone()),
+            input_size: metadata.and_then(|m| m.resolution()),
+            duration_secs: clip.map_or(source_duration, |c| c.length(source_duration)),
+            clip,
+            frame_rate,
+            fps_cap,
+            // The requester's own watermark replaces the operator's
+            watermark: job.watermark.clone().or_else(|| settings.watermark.clone()),
+            deinterlace,
+            detect_interlace,
+            tonemap,
+            // Phones store portrait video as rotated landscape frames
+            rotation: stream.map_or(0, |s| s.rotation()),
+            loudnorm,
+            strip_metadata: job.strip_metadata.unwrap_or(settings.strip_metadata),
+            resolution: job.resolution,
+            quality: mp4_crf(presets, job),
+            container: Container::resolve(job.container, encoded_codec),
+            resolutions: hls_resolutions(job, tonemap),
+            encryption: job.encryption,
+            segment_type: job.segment_type,
+            segmenting: job.segmenting().unwrap_or_default(),
+            audio_bitrates: job.audio_bitrates.clone(),
+            ladder: hls_ladder(&settings.bitrate_ladder, presets, job.codec),
+            original_crf: preset_crf(presets, job.codec, "original").unwrap_or(ORIGINAL_CRF),
+            // Every source audio track becomes a rendition of the HLS audio group
+            audio_tracks: metadata.map(AudioTrack::from_metadata).unwrap_or_default(),
+        })
+    }
+
+    /// Lower the quality of every rendition by `crf_offset` steps
+    pub fn lower_quality(&mut self, crf_offset: u32) {
+        if crf_offset > 0 {
+            self.quality += crf_offset;
+            self.ladder = ladder_with_crf_offset(&self.ladder, crf_offset);
+        }
+    }
+
+    /// Renditions a `mode` job encodes: the MP4 output, or the HLS rungs
+    /// that fit the source
+    pub fn renditions(&self, mode: OutputMode) -> Vec<ResolutionConfig> {
+        match mode {
+            OutputMode::Thumbnail => Vec::new(),
+            OutputMode::Mp4 => vec![ResolutionConfig {
+                height: self.resolution.height(),
+                quality: Some(self.quality),
+                ..Default::default()
+            }],
+            OutputMode::Hls => TransformConfig::for_resolutions(
+                self.input_size,
+                &self.resolutions,
+                self.source_codec.as_deref(),
+                &self.ladder,
+                self.original_crf,
+            )
+            .resolutions
+            .into_values()
+            .collect(),
+        }
+    }
+}
+
+/// Resolutions of an HLS job: the requested ones (or all), plus the
+/// original when it's kept as the HDR rung
+fn hls_resolutions(job: &JobContext, tonemap: Option<Tonemap>) -> Vec<Resolution> {
+    let mut selected = if job.hls_resolutions.is_empty() {
+        Resolution::all()
+    } else {
+        job.hls_resolutions.clone()
+    };
+    if tonemap.is_some_and(|t| t.keep_original) && !selected.contains(&Resolution::Original) {
+        selected.push(Resolution::Original);
+    }
+    selected
+}
+
+/// CRF for an MP4 job: the operator's preset for its resolution and codec,
+/// or the built-in default
+fn mp4_crf(presets: &[QualityPreset], job: &JobContext) -> u32 {
+    preset_crf(presets, job.codec, job.resolution.as_str()).unwrap_or(MP4_CRF)
+}
+
+/// HLS ladder with the operator's quality presets for `codec` applied
+fn hls_ladder(ladder: &[LadderRung], presets: &[QualityPreset], codec: Codec) -> Vec<LadderRung> {
+    if presets.is_empty() {
+        ladder.to_vec()
+    } else {
+        ladder_with_presets(ladder, presets, codec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dvm::dry_run::job_from_params;
+    use std::collections::BTreeMap;
+
+    fn metadata(audio: bool, field_order: &str) -> VideoMetadata {
+        let video = VideoMetadata::builder()
+            .with_duration("60.0")
+            .with_video(1920, 1080)
+            .with_field("field_order", field_order);
+        let builder = if audio { video.with_audio() } else { video };
+        builder.build()
+    }
+
+    fn job(params: &[(&str, &str)]) -> JobContext {
+        let params: BTreeMap<String, String> = params
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        job_from_params("https://example.com/video.mp4", &params, &RemoteConfig::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_loudnorm_needs_audio() {
+        let settings = RemoteConfig {
+            normalize_audio: true,
+            ..RemoteConfig::default()
+        };
+        let with_audio = metadata(true, "progressive");
+        let plan = EncodePlan::new(&job(&[]), Some(&with_audio), &settings, HwAccel::Software)
+            .unwrap();
+        assert_eq!(plan.loudnorm, Some(Loudnorm { measured: None }));
+
+        let silent = metadata(false, "progressive");
+        let plan =
+            EncodePlan::new(&job(&[]), Some(&silent), &settings, HwAccel::Software).unwrap();
+        assert_eq!(plan.loudnorm, None);
+
+        // Thumbnails aren't encoded
+        let plan = EncodePlan::new(
+            &job(&[("mode", "thumbnail")]),
+            Some(&with_audio),
+            &settings,
+            HwAccel::Software,
+        )
+        .unwrap();
+        assert_eq!(plan.loudnorm, None);
+    }
+
+    #[test]
+    fn test_deinterlace_modes() {
+        let settings = RemoteConfig::default();
+        let interlaced = metadata(true, "bb");
+
+        // Auto goes by the container until detection runs
+        let plan = EncodePlan::new(&job(&[]), Some(&interlaced), &settings, HwAccel::Software)
+            .unwrap();
+        assert!(plan.detect_interlace);
+        assert_eq!(plan.deinterlace.map(|d| d.field_order), Some(FieldOrder::BottomFirst));
+
+        // Nothing to detect on without a probed stream
+        let plan = EncodePlan::new(&job(&[]), None, &settings, HwAccel::Software).unwrap();
+        assert!(!plan.detect_interlace);
+        assert_eq!(plan.deinterlace, None);
+
+        let plan = EncodePlan::new(
+            &job(&[("deinterlace", "on")]),
+            None,
+            &settings,
+            HwAccel::Software,
+        )
+        .unwrap();
+        assert!(!plan.detect_interlace);
+        assert_eq!(plan.deinterlace.map(|d| d.field_order), Some(FieldOrder::TopFirst));
+    }
+
+    #[test]
+    fn test_lower_quality() {
+        let settings = RemoteConfig::default();
+        let mut plan = EncodePlan::new(&job(&[]), None, &settings, HwAccel::Software).unwrap();
+        plan.ladder = vec![LadderRung {
+            height: 720,
+            crf: Some(26),
+            video_bitrate: None,
+            audio_bitrate: None,
+        }];
+        plan.lower_quality(3);
+        assert_eq!(plan.quality, MP4_CRF + 3);
+        assert_eq!(plan.ladder[0].crf, Some(29));
+    }
+
+    #[test]
+    fn test_renditions() {
+        let settings = RemoteConfig::default();
+        let source = metadata(true, "progressive");
+        let plan_for = |params: &[(&str, &str)]| {
+            EncodePlan::new(&job(params), Some(&source), &settings, HwAccel::Software).unwrap()
+        };
+
+        let mut plan = plan_for(&[("mode", "mp4"), ("resolution", "720p")]);
+        plan.lower_quality(3);
+        let renditions = plan.renditions(OutputMode::Mp4);
+        assert_eq!(renditions.len(), 1);
+        assert_eq!(renditions[0].height, Some(720));
+        assert_eq!(renditions[0].quality, Some(MP4_CRF + 3));
+
+        // Rungs above the 1080p source are left out
+        let plan = plan_for(&[("mode", "hls"), ("resolutions", "360p,720p,2160p")]);
+        let mut heights: Vec<_> = plan
+            .renditions(OutputMode::Hls)
+            .iter()
+            .map(|r| r.height)
+            .collect();
+        heights.sort();
+        assert_eq!(heights, vec![Some(360), Some(720)]);
+
+        assert!(plan.renditions(OutputMode::Thumbnail).is_empty());
+    }
+}
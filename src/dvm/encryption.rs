@@ -1,9 +1,18 @@
+//! NIP-04 / NIP-44 encryption of DVM requests and responses.
+//!
+//! NIP-90 lets clients hide a request's inputs and params by encrypting them
+//! to the DVM and marking the request with an `encrypted` tag. Decrypted
+//! requests go through the same tag parsing as plaintext ones, and responses
+//! are encrypted back with whichever scheme the client used.
+
 use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::error::DvmError;
 
 /// Tracks which encryption the client used, so we reply with the same type.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum EncryptionType {
     None,
     Nip04,
@@ -16,6 +25,122 @@ impl EncryptionType {
     }
 }
 
+/// Tags of an encrypted request after decryption
+#[derive(Debug, Clone)]
+pub struct DecryptedRequest {
+    pub encryption_type: EncryptionType,
+    /// The decrypted tags, then the plaintext tags they don't replace
+    pub tags: Vec<Tag>,
+}
+
+/// Older encrypted content format: `{"i": [value, type, ...], "params": [["param", name, value], ...]}`
+#[derive(Debug, Deserialize)]
+struct LegacyContent {
+    i: Vec<String>,
+    #[serde(default)]
+    params: Vec<Vec<String>>,
+}
+
+/// Whether `event` carries an `encrypted` tag
+fn has_encrypted_tag(event: &Event) -> bool {
+    event
+        .tags
+        .iter()
+        .any(|t| t.as_slice().first().map(|s| s.as_str()) == Some("encrypted"))
+}
+
+/// Decrypt `content` from `sender`, trying NIP-04 first, and report which
+/// scheme it was
+fn decrypt_with_type(
+    keys: &Keys,
+    sender: &PublicKey,
+    content: &str,
+) -> Result<(String, EncryptionType), DvmError> {
+    if let Ok(d) = nip04::decrypt(keys.secret_key(), sender, content) {
+        return Ok((d, EncryptionType::Nip04));
+    }
+    nip44::decrypt(keys.secret_key(), sender, content)
+        .map(|d| (d, EncryptionType::Nip44))
+        .map_err(|e| DvmError::JobRejected(format!("Failed to decrypt request: {}", e)))
+}
+
+/// Decrypt a request encrypted to `keys`.
+///
+/// Plaintext tags of a name that isn't among the encrypted ones (usually
+/// `relays`, `cashu` and `p`) are kept after the decrypted tags, so the job
+/// is read exactly as if all its tags had been sent in the clear.
+pub fn decrypt_request(keys: &Keys, event: &Event) -> Result<DecryptedRequest, DvmError> {
+    let (content, encryption_type) = decrypt_with_type(keys, &event.pubkey, &event.content)?;
+    let mut tags = parse_request_tags(&content)?;
+
+    let encrypted_names: Vec<String> = tags
+        .iter()
+        .filter_map(|t| t.as_slice().first().cloned())
+        .collect();
+    for tag in event.tags.iter() {
+        match tag.as_slice().first() {
+            Some(name) if name != "encrypted" && !encrypted_names.contains(name) => {
+                tags.push(tag.clone())
+            }
+            _ => {}
+        }
+    }
+    Ok(DecryptedRequest {
+        encryption_type,
+        tags,
+    })
+}
+
+/// Parse decrypted request content: the NIP-90 tag array
+/// (`[["i", value, type], ["param", name, value], ...]`) or the older
+/// `{"i": ..., "params": ...}` object. Empty tags are skipped.
+pub fn parse_request_tags(content: &str) -> Result<Vec<Tag>, DvmError> {
+    let invalid = |e: serde_json::Error| {
+        DvmError::JobRejected(format!("Invalid encrypted content format: {}", e))
+    };
+    let rows: Vec<Vec<String>> = match serde_json::from_str::<serde_json::Value>(content).map_err(invalid)? {
+        value @ serde_json::Value::Array(_) => serde_json::from_value(value).map_err(invalid)?,
+        value => {
+            let legacy: LegacyContent = serde_json::from_value(value).map_err(invalid)?;
+            let mut rows = vec![std::iter::once("i".to_string()).chain(legacy.i).collect()];
+            rows.extend(legacy.params);
+            rows
+        }
+    };
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            let (name, values) = row.split_first()?;
+            Some(Tag::custom(TagKind::Custom(name.clone().into()), values.to_vec()))
+        })
+        .collect())
+}
+
+/// Status of a job feedback event (kind 7000), such as a client's
+/// `approved` after a bid. For encrypted feedback to `keys` the status is
+/// read from the decrypted content, a `{"status": ...}` object or a tag array.
+pub fn feedback_status(keys: &Keys, event: &Event) -> Option<String> {
+    fn status_of(mut tags: impl Iterator<Item = Vec<String>>) -> Option<String> {
+        tags.find(|t| t.len() >= 2 && t[0] == "status").map(|t| t[1].clone())
+    }
+    if let Some(status) = status_of(event.tags.iter().map(|t| t.as_slice().to_vec())) {
+        return Some(status);
+    }
+    if !has_encrypted_tag(event) {
+        return None;
+    }
+
+    let (content, _) = decrypt_with_type(keys, &event.pubkey, &event.content).ok()?;
+    match serde_json::from_str::<serde_json::Value>(&content).ok()? {
+        serde_json::Value::Object(object) => object.get("status")?.as_str().map(str::to_string),
+        value => {
+            let rows: Vec<Vec<String>> = serde_json::from_value(value).ok()?;
+            status_of(rows.into_iter())
+        }
+    }
+}
+
 /// Decrypt encrypted content from a DVM request (NIP-04 or NIP-44)
 pub async fn decrypt_content(
     keys: &Keys,
@@ -88,6 +213,137 @@ mod tests {
         assert!(!is_encrypted(""));
     }
 
+    use crate::dvm::events::{Codec, JobContext, OutputMode, Resolution, DVM_STATUS_KIND, DVM_VIDEO_TRANSFORM_REQUEST_KIND};
+
+    const HASH: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+
+    /// Tags of a request as NIP-90 rows
+    fn request_rows() -> Vec<Vec<String>> {
+        [
+            vec!["i", "https://example.com/video.mp4", "url"],
+            vec!["param", "mode", "hls"],
+            vec!["param", "resolutions", "360p,720p"],
+            vec!["param", "codec", "h265"],
+            vec!["param", "hls_time", "4"],
+            vec!["x", HASH],
+            vec!["relays", "wss://relay.example.com"],
+        ]
+        .into_iter()
+        .map(|row| row.into_iter().map(str::to_string).collect())
+        .collect()
+    }
+
+    fn to_tags(rows: &[Vec<String>]) -> Vec<Tag> {
+        rows.iter()
+            .map(|r| Tag::custom(TagKind::Custom(r[0].clone().into()), r[1..].to_vec()))
+            .collect()
+    }
+
+    /// A request from `client` to `dvm` with `rows` encrypted and `plain` in the clear
+    fn encrypted_request(
+        client: &Keys,
+        dvm: &Keys,
+        content: &str,
+        plain: Vec<Tag>,
+        enc_type: EncryptionType,
+    ) -> Event {
+        let encrypted = encrypt_for_dvm(client, &dvm.public_key(), content, enc_type).unwrap();
+        let mut tags = vec![
+            Tag::public_key(dvm.public_key()),
+            Tag::custom(TagKind::Custom("encrypted".into()), Vec::<String>::new()),
+        ];
+        tags.extend(plain);
+        EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, encrypted, tags)
+            .to_event(client)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_request_tags() {
+        let rows = request_rows();
+        let tags = parse_request_tags(&serde_json::to_string(&rows).unwrap()).unwrap();
+        assert_eq!(tags, to_tags(&rows));
+
+        let legacy = serde_json::json!({
+            "i": ["https://example.com/video.mp4", "url"],
+            "params": [["param", "mode", "hls"], []],
+        });
+        let tags = parse_request_tags(&legacy.to_string()).unwrap();
+        assert_eq!(tags, to_tags(&rows[..2]));
+
+        assert!(parse_request_tags("https://example.com/video.mp4").is_err());
+        assert!(parse_request_tags("{\"params\": []}").is_err());
+    }
+
+    #[test]
+    fn test_encrypted_request_matches_plaintext() {
+        let client = Keys::generate();
+        let dvm = Keys::generate();
+        let plain = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", to_tags(&request_rows()))
+            .to_event(&client)
+            .unwrap();
+        let expected = JobContext::from_event(plain).unwrap();
+        assert_eq!(expected.mode, OutputMode::Hls);
+        assert_eq!(expected.hls_resolutions, vec![Resolution::R360p, Resolution::R720p]);
+
+        for enc_type in [EncryptionType::Nip04, EncryptionType::Nip44] {
+            // Relays stay in the clear, so relays can route responses
+            let rows = request_rows();
+            let (secret, public) = rows.split_at(6);
+            let content = serde_json::to_string(&secret).unwrap();
+            let event = encrypted_request(&client, &dvm, &content, to_tags(public), enc_type);
+
+            let job = JobContext::from_event_with_keys(event, &dvm).unwrap();
+            assert_eq!(job.encryption_type, enc_type);
+            assert_eq!(job.input.value, expected.input.value);
+            assert_eq!(job.mode, expected.mode);
+            assert_eq!(job.hls_resolutions, expected.hls_resolutions);
+            assert_eq!(job.codec, Codec::H265);
+            assert_eq!(job.hls_time, Some(4));
+            assert_eq!(job.input_sha256.as_deref(), Some(HASH));
+            assert_eq!(job.relays, expected.relays);
+            assert!(job.has_param("codec") && !job.has_param("resolution"));
+        }
+
+        // Encrypted tags win over plaintext ones of the same name
+        let content = serde_json::to_string(&request_rows()).unwrap();
+        let decoy = Tag::custom(TagKind::Custom("relays".into()), vec!["wss://decoy.example.com".to_string()]);
+        let event = encrypted_request(&client, &dvm, &content, vec![decoy], EncryptionType::Nip44);
+        let job = JobContext::from_event_with_keys(event, &dvm).unwrap();
+        assert_eq!(job.relays, expected.relays);
+
+        // Only the DVM can read it
+        let event = encrypted_request(&client, &dvm, &content, Vec::new(), EncryptionType::Nip44);
+        assert!(JobContext::from_event_with_keys(event, &Keys::generate()).is_err());
+    }
+
+    #[test]
+    fn test_feedback_status() {
+        let client = Keys::generate();
+        let dvm = Keys::generate();
+        let feedback = |content: String, tags: Vec<Tag>| {
+            EventBuilder::new(DVM_STATUS_KIND, content, tags)
+                .to_event(&client)
+                .unwrap()
+        };
+        let encrypted_tag = || Tag::custom(TagKind::Custom("encrypted".into()), Vec::<String>::new());
+
+        let plain = feedback(
+            String::new(),
+            vec![Tag::custom(TagKind::Custom("status".into()), vec!["approved".to_string()])],
+        );
+        assert_eq!(feedback_status(&dvm, &plain).as_deref(), Some("approved"));
+
+        for content in [r#"{"status":"approved"}"#, r#"[["status","approved"]]"#] {
+            let encrypted = encrypt_for_dvm(&client, &dvm.public_key(), content, EncryptionType::Nip44).unwrap();
+            let event = feedback(encrypted, vec![encrypted_tag()]);
+            assert_eq!(feedback_status(&dvm, &event).as_deref(), Some("approved"));
+            assert_eq!(feedback_status(&Keys::generate(), &event), None);
+        }
+
+        assert_eq!(feedback_status(&dvm, &feedback("approved".to_string(), Vec::new())), None);
+    }
+
     #[test]
     fn test_encryption_type_is_encrypted() {
         assert!(!EncryptionType::None.is_encrypted());
@@ -0,0 +1,285 @@
+//! Nostr event inputs.
+//!
+//! Besides URLs, a request can point at an existing Nostr event with an
+//! `event` input: a NIP-94 file metadata event or a NIP-71 video event,
+//! given as a hex id, `note`, `nevent`, or (for addressable videos) `naddr`.
+//! A `job` input names an earlier DVM request, and its result is used. The
+//! event's media URL and SHA-256 are pulled from its tags and the job then
+//! runs as a plain URL input.
+
+use nostr_sdk::nips::nip19::{FromBech32, Nip19Event};
+use nostr_sdk::nips::nip01::Coordinate;
+use nostr_sdk::{Event, EventId, Filter, Kind};
+
+use crate::dvm::creator::VIDEO_EVENT_KINDS;
+
+/// Where to look for the input event
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventReference {
+    pub filter: Filter,
+    /// Relay hints from the `nevent`/`naddr` encoding
+    pub relays: Vec<String>,
+}
+
+/// Media found in an input event
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventMedia {
+    pub url: String,
+    pub sha256: Option<String>,
+}
+
+/// Whether `input_type` names an event input
+pub fn is_event_input(input_type: &str) -> bool {
+    matches!(input_type, "event" | "job")
+}
+
+/// Parse an `event` or `job` input value into a relay filter.
+///
+/// `job` inputs select the results (kinds 6000-6999) of the referenced request.
+pub fn parse_reference(value: &str, input_type: &str) -> Option<EventReference> {
+    let value = value.trim().trim_start_matches("nostr:");
+
+    let (id, relays) = if let Ok(coordinate) = Coordinate::from_bech32(value) {
+        if input_type == "job" {
+            return None;
+        }
+        let relays = coordinate.relays.clone();
+        let filter = Filter::new().coordinate(&coordinate).limit(1);
+        return Some(EventReference { filter, relays });
+    } else if let Ok(event) = Nip19Event::from_bech32(value) {
+        (event.event_id, event.relays)
+    } else if let Ok(id) = EventId::from_bech32(value) {
+        (id, Vec::new())
+    } else {
+        (EventId::from_hex(value).ok()?, Vec::new())
+    };
+
+    let filter = if input_type == "job" {
+        Filter::new()
+            .kinds((6000..=6999).map(Kind::from))
+            .event(id)
+            .limit(1)
+    } else {
+        Filter::new().id(id).limit(1)
+    };
+    Some(EventReference { filter, relays })
+}
+
+/// Pick the newest of the fetched events (addressable videos may return
+/// several versions).
+pub fn newest(events: impl IntoIterator<Item = Event>) -> Option<Event> {
+    events.into_iter().max_by_key(|e| e.created_at)
+}
+
+/// Extract the media to transcode from a video, file metadata or job result event.
+///
+/// Video events can list several variants in `imeta` tags; the one with the
+/// largest `dim` is used.
+pub fn media_from_event(event: &Event) -> Option<EventMedia> {
+    let kind = event.kind.as_u16();
+    if (6000..=6999).contains(&kind) {
+        return media_from_result(&event.content);
+    }
+    if !VIDEO_EVENT_KINDS.contains(&kind) {
+        return None;
+    }
+
+    // Plain tags (NIP-94, older video events) form one candidate, each imeta another
+    let mut candidates = vec![tag_fields(event)];
+    candidates.extend(event.tags.iter().filter_map(|tag| {
+        let parts = tag.as_slice();
+        (parts.first().map(String::as_str) == Some("imeta")).then(|| {
+            parts[1..]
+                .iter()
+                .filter_map(|p| p.split_once(' '))
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<Vec<_>>()
+        })
+    }));
+
+    let mut best: Option<(u64, EventMedia)> = None;
+    for fields in candidates {
+        let get = |name: &str| fields.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+        let Some(url) = get("url").filter(|u| is_http(u)) else {
+            continue;
+        };
+        if get("m").is_some_and(|m| m.starts_with("image/")) {
+            continue;
+        }
+        let pixels = get("dim").and_then(parse_dim).unwrap_or(0);
+        if best.as_ref().is_some_and(|(p, _)| *p >= pixels) {
+            continue;
+        }
+        let sha256 = get("x")
+            .filter(|h| h.len() == 64 && h.chars().all(|c| c.is_ascii_hexdigit()))
+            .map(str::to_lowercase);
+        best = Some((
+            pixels,
+            EventMedia {
+                url: url.to_string(),
+                sha256,
+            },
+        ));
+    }
+    best.map(|(_, media)| media)
+}
+
+/// Single-valued tags as name/value pairs
+fn tag_fields(event: &Event) -> Vec<(String, String)> {
+    event
+        .tags
+        .iter()
+        .filter_map(|tag| match tag.as_slice() {
+            [name, value, ..] if name != "imeta" => Some((name.clone(), value.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Media URL of a job result: a plain URL, or one of our JSON results
+fn media_from_result(content: &str) -> Option<EventMedia> {
+    let content = content.trim();
+    if is_http(content) {
+        return Some(EventMedia {
+            url: content.to_string(),
+            sha256: None,
+        });
+    }
+    let json: serde_json::Value = serde_json::from_str(content).ok()?;
+    let url = json
+        .get("urls")
+        .and_then(|u| u.get(0))
+        .or_else(|| json.get("master_playlist"))
+        .or_else(|| json.get("url"))
+        .and_then(|u| u.as_str())
+        .filter(|u| is_http(u))?;
+    Some(EventMedia {
+        url: url.to_string(),
+        sha256: None,
+    })
+}
+
+fn is_http(url: &str) -> bool {
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+/// Pixel count of a `WIDTHxHEIGHT` dimension
+fn parse_dim(dim: &str) -> Option<u64> {
+    let (w, h) = dim.split_once('x')?;
+    Some(w.trim().parse::<u64>().ok()? * h.trim().parse::<u64>().ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr_sdk::nips::nip19::ToBech32;
+    use nostr_sdk::{EventBuilder, Keys, Tag, TagKind};
+
+    fn event(kind: u16, content: &str, tags: Vec<Vec<&str>>) -> Event {
+        let tags = tags
+            .into_iter()
+            .map(|t| {
+                Tag::custom(
+                    TagKind::Custom(t[0].to_string().into()),
+                    t[1..].iter().map(|s| s.to_string()),
+                )
+            })
+            .collect::<Vec<_>>();
+        EventBuilder::new(Kind::from(kind), content, tags)
+            .to_event(&Keys::generate())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_reference() {
+        let id = EventId::from_hex("a".repeat(64)).unwrap();
+
+        let hex = parse_reference(&id.to_hex(), "event").unwrap();
+        assert_eq!(hex.filter, Filter::new().id(id).limit(1));
+        assert!(hex.relays.is_empty());
+
+        let note = parse_reference(&id.to_bech32().unwrap(), "event").unwrap();
+        assert_eq!(note.filter, hex.filter);
+
+        let nevent = Nip19Event::new(id, ["wss://relay.example.com"]).to_bech32().unwrap();
+        let parsed = parse_reference(&format!("nostr:{}", nevent), "event").unwrap();
+        assert_eq!(parsed.filter, hex.filter);
+        assert_eq!(parsed.relays, vec!["wss://relay.example.com".to_string()]);
+
+        let job = parse_reference(&id.to_hex(), "job").unwrap();
+        assert_eq!(job.filter.ids, None);
+        assert!(job.filter.kinds.unwrap().contains(&Kind::from(6207)));
+
+        let coordinate = Coordinate::new(Kind::from(34235), Keys::generate().public_key())
+            .identifier("my-video");
+        let naddr = parse_reference(&coordinate.to_bech32().unwrap(), "event").unwrap();
+        assert_eq!(naddr.filter, Filter::new().coordinate(&coordinate).limit(1));
+        assert!(parse_reference(&coordinate.to_bech32().unwrap(), "job").is_none());
+
+        assert!(parse_reference("https://example.com/v.mp4", "event").is_none());
+    }
+
+    #[test]
+    fn test_media_from_file_metadata() {
+        let hash = "C".repeat(64);
+        let meta = event(
+            1063,
+            "",
+            vec![
+                vec!["url", "https://blossom.example.com/v.mp4"],
+                vec!["m", "video/mp4"],
+                vec!["x", &hash],
+            ],
+        );
+        assert_eq!(
+            media_from_event(&meta),
+            Some(EventMedia {
+                url: "https://blossom.example.com/v.mp4".into(),
+                sha256: Some("c".repeat(64)),
+            })
+        );
+
+        let image = event(1063, "", vec![vec!["url", "https://x.example.com/a.jpg"], vec!["m", "image/jpeg"]]);
+        assert_eq!(media_from_event(&image), None);
+        assert_eq!(media_from_event(&event(1, "https://x.example.com/v.mp4", vec![])), None);
+    }
+
+    #[test]
+    fn test_media_from_video_event_picks_largest_variant() {
+        let video = event(
+            21,
+            "",
+            vec![
+                vec!["title", "Holiday"],
+                vec!["imeta", "dim 1280x720", "url https://a.example.com/720.mp4", "m video/mp4"],
+                vec!["imeta", "dim 1920x1080", "url https://a.example.com/1080.mp4", "m video/mp4"],
+                vec!["imeta", "dim 640x360", "url https://a.example.com/360.mp4", "m video/mp4"],
+            ],
+        );
+        assert_eq!(
+            media_from_event(&video).map(|m| m.url),
+            Some("https://a.example.com/1080.mp4".into())
+        );
+    }
+
+    #[test]
+    fn test_media_from_job_result() {
+        let plain = event(6207, "https://a.example.com/out.mp4", vec![]);
+        assert_eq!(
+            media_from_event(&plain).map(|m| m.url),
+            Some("https://a.example.com/out.mp4".into())
+        );
+
+        let mp4 = event(6207, r#"{"type":"mp4","urls":["https://a.example.com/b.mp4"]}"#, vec![]);
+        assert_eq!(
+            media_from_event(&mp4).map(|m| m.url),
+            Some("https://a.example.com/b.mp4".into())
+        );
+
+        let hls = event(6207, r#"{"type":"hls","master_playlist":"https://a.example.com/m.m3u8"}"#, vec![]);
+        assert_eq!(
+            media_from_event(&hls).map(|m| m.url),
+            Some("https://a.example.com/m.m3u8".into())
+        );
+    }
+}
@@ -1,17 +1,32 @@
 use nostr_sdk::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use std::time::Duration;
 use tracing::debug;
 
-use crate::dvm::encryption::{encrypt_for_dvm, is_encrypted, EncryptionType};
+use crate::dvm::encryption::{decrypt_request, encrypt_for_dvm, is_encrypted, EncryptionType};
+use crate::dvm::idempotency::MAX_KEY_LEN;
+use crate::dvm::messages::Lang;
+use crate::dvm::job_log::JobLog;
+use crate::dvm::progress::JobProgress;
 use crate::error::DvmError;
+use crate::util::hash::sha256_from_url;
+use crate::video::transform::{
+    parse_audio_bitrate, Container, EncryptionScheme, PreviewFormat, SegmentType,
+};
+use crate::video::chapters::Chapter;
+use crate::video::clip::Clip;
+use crate::video::segmenting::Segmenting;
+use crate::video::deinterlace::DeinterlaceMode;
+use crate::video::fps_cap::FpsCap;
+use crate::video::tonemap::HdrMode;
+use crate::video::watermark::{Watermark, WatermarkPosition};
+use crate::video::HwAccel;
 
 /// Expiration time for status events (1 hour)
 const STATUS_EXPIRATION_SECS: u64 = 3600;
 
-/// Expiration time for result events (1 hour)
-const RESULT_EXPIRATION_SECS: u64 = 3600;
-
 pub const DVM_STATUS_KIND: Kind = Kind::Custom(7000);
 pub const DVM_VIDEO_TRANSFORM_REQUEST_KIND: Kind = Kind::Custom(5207);
 pub const DVM_VIDEO_TRANSFORM_RESULT_KIND: Kind = Kind::Custom(6207);
@@ -22,31 +37,64 @@ pub enum OutputMode {
     #[default]
     Mp4,
     Hls,
+    /// Poster images extracted from the video
+    Thumbnail,
 }
 
 impl OutputMode {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Self {
         match s.to_lowercase().as_str() {
             "hls" => Self::Hls,
+            "thumbnail" | "thumbnails" | "poster" => Self::Thumbnail,
             _ => Self::Mp4,
         }
     }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Mp4 => "mp4",
+            Self::Hls => "hls",
+            Self::Thumbnail => "thumbnail",
+        }
+    }
 }
 
+/// Default number of frames extracted in thumbnail mode
+pub const DEFAULT_THUMBNAIL_COUNT: usize = 1;
+
+/// Upper bound on frames per thumbnail job
+pub const MAX_THUMBNAILS: usize = 10;
+
+/// Default length of animated previews in seconds
+pub const DEFAULT_PREVIEW_SECS: f64 = 3.0;
+
+/// Upper bound on animated preview length in seconds
+pub const MAX_PREVIEW_SECS: f64 = 10.0;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum Codec {
     #[default]
     H264,
     H265,
     AV1,
+    Vp9,
 }
 
 impl Codec {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Self {
+        Self::parse(s).unwrap_or(Self::H264)
+    }
+
+    /// Parse a codec name. Returns `None` for unrecognized values.
+    pub fn parse(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
-            "h265" | "hevc" => Self::H265,
-            "av1" => Self::AV1,
-            _ => Self::H264,
+            "h264" | "avc" => Some(Self::H264),
+            "h265" | "hevc" => Some(Self::H265),
+            "av1" => Some(Self::AV1),
+            "vp9" => Some(Self::Vp9),
+            _ => None,
         }
     }
 
@@ -55,6 +103,7 @@ impl Codec {
             Self::H264 => "h264",
             Self::H265 => "h265",
             Self::AV1 => "av1",
+            Self::Vp9 => "vp9",
         }
     }
 
@@ -63,6 +112,7 @@ impl Codec {
             Self::H264 => "H.264",
             Self::H265 => "H.265",
             Self::AV1 => "AV1",
+            Self::Vp9 => "VP9",
         }
     }
 
@@ -72,12 +122,72 @@ impl Codec {
             Self::H265
         } else if encoder.contains("av1") || encoder.contains("svtav1") {
             Self::AV1
+        } else if encoder.contains("vp9") {
+            Self::Vp9
         } else {
             Self::H264
         }
     }
 }
 
+/// Playback target named in the `device` param
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Device {
+    Ios,
+    Android,
+    Web,
+    Tv,
+}
+
+impl Device {
+    /// Parse a device hint. Returns `None` for unrecognized values.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "ios" | "apple" => Some(Self::Ios),
+            "android" => Some(Self::Android),
+            "web" | "browser" => Some(Self::Web),
+            "tv" => Some(Self::Tv),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ios => "ios",
+            Self::Android => "android",
+            Self::Web => "web",
+            Self::Tv => "tv",
+        }
+    }
+
+    /// Codecs the device plays reliably, most efficient first
+    fn codecs(&self) -> &'static [Codec] {
+        match self {
+            // Apple hardware decodes HEVC (tagged hvc1) everywhere
+            Self::Ios => &[Codec::H265, Codec::H264],
+            // HEVC decoding varies too much between Android vendors
+            Self::Android => &[Codec::H264],
+            Self::Web => &[Codec::AV1, Codec::H264],
+            // Older smart TVs and set-top boxes only cope with H.264 High
+            Self::Tv => &[Codec::H264],
+        }
+    }
+
+    /// Codec for this device, given the output and the codecs the DVM can
+    /// encode. Encrypted HLS uses TS segments, which carry neither HEVC for
+    /// Apple players nor AV1, so it always gets H.264.
+    pub fn codec(&self, mode: OutputMode, encryption: bool, available: impl Fn(Codec) -> bool) -> Codec {
+        if mode == OutputMode::Hls && encryption {
+            return Codec::H264;
+        }
+        self.codecs()
+            .iter()
+            .copied()
+            .find(|c| *c == Codec::H264 || available(*c))
+            .unwrap_or(Codec::H264)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum Resolution {
     R240p,
@@ -92,6 +202,7 @@ pub enum Resolution {
 
 impl Resolution {
     /// Parse from string. Returns `None` for unrecognized values.
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
             "240p" => Some(Self::R240p),
@@ -175,19 +286,98 @@ pub struct JobContext {
     pub mode: OutputMode,
     pub resolution: Resolution,
     pub codec: Codec,
+    /// Playback target the codec is picked for when `codec` isn't given
+    pub device: Option<Device>,
+    /// Requested MP4-mode container (`None` picks one for the codec, see [`Container::resolve`])
+    pub container: Option<Container>,
     /// Selected resolutions for HLS mode (empty means use all)
     pub hls_resolutions: Vec<Resolution>,
     /// Enable AES-128 encryption for HLS (defaults to true for backward compatibility)
     pub encryption: bool,
+    /// Requested HLS segment container (fMP4 unless the client asks for TS)
+    pub segment_type: SegmentType,
+    /// HLS segment duration in seconds (`None` = operator default)
+    pub hls_time: Option<u32>,
+    /// Seconds between forced keyframes in HLS output (`None` = operator default)
+    pub keyframe_interval: Option<f64>,
+    /// Frame rate cap for high-FPS sources (`None` = operator default)
+    pub max_fps: Option<u32>,
+    /// HLS encryption method when `encryption` is enabled (`None` = the
+    /// default, or a scheme this DVM doesn't offer)
+    pub encryption_scheme: Option<EncryptionScheme>,
+    /// Number of evenly spaced frames for thumbnail mode
+    pub thumbnail_count: usize,
+    /// Explicit frame timestamps in seconds for thumbnail mode (overrides count)
+    pub thumbnail_timestamps: Vec<f64>,
+    /// Per-rendition HLS audio bitrate overrides, keyed by label ("360p" -> "64k")
+    pub audio_bitrates: HashMap<String, String>,
+    /// Animated preview to generate alongside MP4/HLS output
+    pub preview: Option<PreviewFormat>,
+    /// Length of the animated preview in seconds
+    pub preview_duration: f64,
+    /// Generate seek thumbnails (sprite sheets + WebVTT) alongside MP4/HLS output
+    pub storyboard: bool,
+    /// Days the requester wants the results kept (`None` = `blob_expiration_days`)
+    pub retention_days: Option<u32>,
+    /// Overlay to burn into MP4/HLS output (`None` = operator default, if any)
+    pub watermark: Option<Watermark>,
+    /// Also upload the unmodified input to Blossom
+    pub archive_source: bool,
+    /// Client key that makes retries of this request reuse the first job
+    pub idempotency_key: Option<String>,
+    /// Segment of the source to transcode (`None` = the whole video)
+    pub clip: Option<Clip>,
+    /// Normalize audio loudness (`None` = operator default)
+    pub normalize_audio: Option<bool>,
+    /// Strip source metadata from outputs (`None` = operator default)
+    pub strip_metadata: Option<bool>,
+    /// Handling of HDR sources (tone-mapped to SDR by default)
+    pub hdr: HdrMode,
+    /// Deinterlacing (applied to sources detected as interlaced by default)
+    pub deinterlace: DeinterlaceMode,
+    /// Language of the status messages
+    pub lang: Lang,
     /// Cashu token for payment (optional)
     pub cashu_token: Option<String>,
+    /// Video event (NIP-71/NIP-94) the input belongs to, from an `e` tag
+    pub source_event: Option<EventId>,
+    /// Whether the requester published `source_event` (`None` = not checked yet)
+    pub creator: Option<bool>,
+    /// SHA-256 the input must hash to, from an `x` tag
+    pub input_sha256: Option<String>,
     /// Original requester pubkey (set when request came via NIP-17 gift wrap,
     /// since `request` is re-signed with DVM keys for internal use)
     original_requester: Option<PublicKey>,
     /// Original event ID from the rumor (before re-signing)
     original_event_id: Option<EventId>,
+    /// Names of the `param` tags the requester actually sent
+    explicit_params: HashSet<String>,
     /// Whether this job was approved via bid selection (skip bidding)
     pub approved: bool,
+    /// Amount of the job's Lightning invoice once paid (skip payment)
+    pub paid_sats: Option<u64>,
+    /// Overall progress across download, transcode and upload
+    pub progress: Arc<JobProgress>,
+    /// FFmpeg output and pipeline events, for the admin `get_job_log` command
+    pub log: Arc<JobLog>,
+}
+
+/// A job as kept in the shutdown snapshot: the request it came from, plus
+/// what was learned about it since
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedJob {
+    pub request: Event,
+    pub encryption_type: EncryptionType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_requester: Option<PublicKey>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_event_id: Option<EventId>,
+    #[serde(default)]
+    pub approved: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paid_sats: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creator: Option<bool>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -221,6 +411,25 @@ pub struct StreamPlaylist {
     /// MIME type with codecs (e.g., "video/mp4; codecs=\"hvc1,mp4a.40.2\"")
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mimetype: Option<String>,
+    /// AAC bitrate of this rendition's audio (e.g. "96k"), when set explicitly
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_bitrate: Option<String>,
+}
+
+/// Alternate audio track of HLS output, shared by every stream playlist
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioRendition {
+    pub url: String,
+    /// Rendition name from the master playlist (e.g. "audio_0")
+    pub name: String,
+    /// ISO 639 language code of the track, if the source tagged it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Whether players pick this track unless told otherwise
+    #[serde(default)]
+    pub default: bool,
+    /// Total size of this track (playlist + segments) in bytes
+    pub size_bytes: u64,
 }
 
 /// DVM result for MP4 output - list of URLs from different servers
@@ -233,6 +442,74 @@ pub struct Mp4Result {
     /// MIME type with codecs (e.g., "video/mp4; codecs=\"hvc1,mp4a.40.2\"")
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mimetype: Option<String>,
+    /// Animated hover preview (if requested)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<Preview>,
+    /// Seek thumbnails for scrub previews (if requested)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storyboard: Option<StoryboardTrack>,
+    /// Chapter markers of the source (if it has any)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chapters: Option<ChaptersTrack>,
+    /// BlurHash of a representative frame, for placeholders while the video loads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blurhash: Option<String>,
+    /// Source dimensions the blurhash was taken at (`WIDTHxHEIGHT`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dim: Option<String>,
+    /// Unmodified input file (if `archive_source` was requested)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<ArchivedSource>,
+    /// SHA-256 the downloaded input was verified against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_sha256: Option<String>,
+    /// Expiring preview page on the DVM's web server (if `PUBLIC_URL` is set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watch_url: Option<String>,
+}
+
+/// Short looping animated preview of the video
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preview {
+    pub urls: Vec<String>,
+    /// "image/webp" or "image/gif"
+    pub mimetype: String,
+    /// File size in bytes
+    pub size_bytes: u64,
+    /// Length of the animation in seconds
+    pub duration: f64,
+}
+
+/// Seek thumbnails: JPEG sprite sheets indexed by a WebVTT track
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoryboardTrack {
+    /// The WebVTT track. Cues refer to the sheets by hash (`<sha256>.jpg`),
+    /// so they resolve on whichever server the track is fetched from.
+    pub urls: Vec<String>,
+    /// The sprite sheets in order (on the first server that took them)
+    pub sheets: Vec<String>,
+    /// Seconds between thumbnails
+    pub interval: u32,
+    /// Size of a thumbnail (`WIDTHxHEIGHT`)
+    pub dim: String,
+}
+
+/// Chapter markers carried over from the source
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChaptersTrack {
+    /// WebVTT chapters track
+    pub urls: Vec<String>,
+    pub chapters: Vec<Chapter>,
+}
+
+/// Byte-for-byte copy of the input, uploaded next to the transcodes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedSource {
+    pub urls: Vec<String>,
+    pub sha256: String,
+    /// File size in bytes
+    pub size_bytes: u64,
+    pub mimetype: String,
 }
 
 /// DVM result for HLS output - master playlist + stream playlists
@@ -240,11 +517,61 @@ pub struct Mp4Result {
 pub struct HlsResult {
     pub master_playlist: String,
     pub stream_playlists: Vec<StreamPlaylist>,
+    /// Alternate audio tracks, when the source has more than one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub audio_tracks: Vec<AudioRendition>,
     /// Total size of all files in bytes
     pub total_size_bytes: u64,
     /// Base64-encoded AES-128 encryption key (if encryption is enabled)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub encryption_key: Option<String>,
+    /// Animated hover preview (if requested)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<Preview>,
+    /// Seek thumbnails for scrub previews (if requested)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storyboard: Option<StoryboardTrack>,
+    /// Chapter markers of the source (if it has any)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chapters: Option<ChaptersTrack>,
+    /// BlurHash of a representative frame, for placeholders while the video loads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blurhash: Option<String>,
+    /// Source dimensions the blurhash was taken at (`WIDTHxHEIGHT`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dim: Option<String>,
+    /// Unmodified input file (if `archive_source` was requested)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<ArchivedSource>,
+    /// SHA-256 the downloaded input was verified against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_sha256: Option<String>,
+    /// Expiring preview page on the DVM's web server (if `PUBLIC_URL` is set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watch_url: Option<String>,
+    /// Hashes of the uploaded segments, which the result doesn't link directly
+    #[serde(skip)]
+    pub segment_hashes: Vec<String>,
+}
+
+/// A single extracted poster frame
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Thumbnail {
+    pub urls: Vec<String>,
+    /// Position in the video the frame was taken from, in seconds
+    pub timestamp: f64,
+    /// File size in bytes
+    pub size_bytes: u64,
+    pub mimetype: String,
+}
+
+/// DVM result for thumbnail output - one entry per extracted frame
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThumbnailResult {
+    pub thumbnails: Vec<Thumbnail>,
+    /// SHA-256 the downloaded input was verified against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_sha256: Option<String>,
 }
 
 /// Result of a DVM job
@@ -253,16 +580,197 @@ pub struct HlsResult {
 pub enum DvmResult {
     Mp4(Mp4Result),
     Hls(HlsResult),
+    Thumbnail(ThumbnailResult),
 }
 
-/// Encrypted content structure for NIP-90 encrypted requests
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct EncryptedContent {
-    /// Input tag: ["i", value, type, relay?, marker?]
-    i: Vec<String>,
-    /// Parameter tags: [["param", name, value], ...]
-    #[serde(default)]
-    params: Vec<Vec<String>>,
+impl DvmResult {
+    /// Attach an animated preview to video outputs (thumbnail results have none)
+    pub fn set_preview(&mut self, preview: Preview) {
+        match self {
+            Self::Mp4(mp4) => mp4.preview = Some(preview),
+            Self::Hls(hls) => hls.preview = Some(preview),
+            Self::Thumbnail(_) => {}
+        }
+    }
+
+    /// Attach seek thumbnails to video outputs
+    pub fn set_storyboard(&mut self, storyboard: StoryboardTrack) {
+        match self {
+            Self::Mp4(mp4) => mp4.storyboard = Some(storyboard),
+            Self::Hls(hls) => hls.storyboard = Some(storyboard),
+            Self::Thumbnail(_) => {}
+        }
+    }
+
+    /// Attach the source's chapters to video outputs
+    pub fn set_chapters(&mut self, chapters: ChaptersTrack) {
+        match self {
+            Self::Mp4(mp4) => mp4.chapters = Some(chapters),
+            Self::Hls(hls) => hls.chapters = Some(chapters),
+            Self::Thumbnail(_) => {}
+        }
+    }
+
+    /// Attach a placeholder blurhash and the source dimensions to video outputs
+    pub fn set_blurhash(&mut self, blurhash: String, dim: Option<String>) {
+        match self {
+            Self::Mp4(mp4) => {
+                mp4.blurhash = Some(blurhash);
+                mp4.dim = dim;
+            }
+            Self::Hls(hls) => {
+                hls.blurhash = Some(blurhash);
+                hls.dim = dim;
+            }
+            Self::Thumbnail(_) => {}
+        }
+    }
+
+    /// Attach the archived input to video outputs
+    pub fn set_source(&mut self, source: ArchivedSource) {
+        match self {
+            Self::Mp4(mp4) => mp4.source = Some(source),
+            Self::Hls(hls) => hls.source = Some(source),
+            Self::Thumbnail(_) => {}
+        }
+    }
+
+    /// Record the SHA-256 the input was verified against
+    pub fn set_input_sha256(&mut self, sha256: String) {
+        match self {
+            Self::Mp4(mp4) => mp4.input_sha256 = Some(sha256),
+            Self::Hls(hls) => hls.input_sha256 = Some(sha256),
+            Self::Thumbnail(thumbs) => thumbs.input_sha256 = Some(sha256),
+        }
+    }
+
+    /// Link an expiring preview page; thumbnails have none
+    pub fn set_watch_url(&mut self, url: String) {
+        match self {
+            Self::Mp4(mp4) => mp4.watch_url = Some(url),
+            Self::Hls(hls) => hls.watch_url = Some(url),
+            Self::Thumbnail(_) => {}
+        }
+    }
+
+    /// Hashes of every blob uploaded for this result
+    pub fn blob_hashes(&self) -> Vec<String> {
+        let preview_urls = |p: &Option<Preview>| p.iter().flat_map(|p| p.urls.clone()).collect::<Vec<_>>();
+        let source_urls = |s: &Option<ArchivedSource>| s.iter().flat_map(|s| s.urls.clone()).collect::<Vec<_>>();
+        let storyboard_urls = |s: &Option<StoryboardTrack>| {
+            s.iter()
+                .flat_map(|s| s.urls.iter().chain(&s.sheets).cloned())
+                .collect::<Vec<_>>()
+        };
+        let chapters_urls = |c: &Option<ChaptersTrack>| c.iter().flat_map(|c| c.urls.clone()).collect::<Vec<_>>();
+        let (urls, segments): (Vec<String>, &[String]) = match self {
+            Self::Mp4(mp4) => (
+                mp4.urls
+                    .iter()
+                    .cloned()
+                    .chain(preview_urls(&mp4.preview))
+                    .chain(storyboard_urls(&mp4.storyboard))
+                    .chain(chapters_urls(&mp4.chapters))
+                    .chain(source_urls(&mp4.source))
+                    .collect(),
+                &[],
+            ),
+            Self::Hls(hls) => (
+                std::iter::once(hls.master_playlist.clone())
+                    .chain(hls.stream_playlists.iter().map(|s| s.url.clone()))
+                    .chain(hls.audio_tracks.iter().map(|a| a.url.clone()))
+                    .chain(preview_urls(&hls.preview))
+                    .chain(storyboard_urls(&hls.storyboard))
+                    .chain(chapters_urls(&hls.chapters))
+                    .chain(source_urls(&hls.source))
+                    .collect(),
+                &hls.segment_hashes,
+            ),
+            Self::Thumbnail(thumbs) => (
+                thumbs.thumbnails.iter().flat_map(|t| t.urls.clone()).collect(),
+                &[],
+            ),
+        };
+
+        let mut hashes: Vec<String> = urls
+            .iter()
+            .filter_map(|u| sha256_from_url(u))
+            .chain(segments.iter().cloned())
+            .collect();
+        hashes.sort();
+        hashes.dedup();
+        hashes
+    }
+}
+
+/// Job parameters parsed from `param` tags
+#[derive(Debug, Clone)]
+struct JobParams {
+    mode: OutputMode,
+    resolution: Resolution,
+    codec: Codec,
+    device: Option<Device>,
+    container: Option<Container>,
+    hls_resolutions: Vec<Resolution>,
+    encryption: bool,
+    segment_type: SegmentType,
+    hls_time: Option<u32>,
+    keyframe_interval: Option<f64>,
+    max_fps: Option<u32>,
+    encryption_scheme: Option<EncryptionScheme>,
+    audio_bitrates: HashMap<String, String>,
+    thumbnail_count: usize,
+    thumbnail_timestamps: Vec<f64>,
+    preview: Option<PreviewFormat>,
+    preview_duration: f64,
+    storyboard: bool,
+    retention_days: Option<u32>,
+    watermark: Option<Watermark>,
+    archive_source: bool,
+    idempotency_key: Option<String>,
+    clip: Option<Clip>,
+    normalize_audio: Option<bool>,
+    strip_metadata: Option<bool>,
+    hdr: HdrMode,
+    deinterlace: DeinterlaceMode,
+    lang: Lang,
+    explicit: HashSet<String>,
+}
+
+impl Default for JobParams {
+    fn default() -> Self {
+        Self {
+            mode: OutputMode::default(),
+            resolution: Resolution::default(),
+            codec: Codec::default(),
+            device: None,
+            container: None,
+            hls_resolutions: Vec::new(),
+            encryption: true, // Default to true for backward compatibility
+            segment_type: SegmentType::default(),
+            hls_time: None,
+            keyframe_interval: None,
+            max_fps: None,
+            encryption_scheme: None,
+            audio_bitrates: HashMap::new(),
+            thumbnail_count: DEFAULT_THUMBNAIL_COUNT,
+            thumbnail_timestamps: Vec::new(),
+            preview: None,
+            preview_duration: DEFAULT_PREVIEW_SECS,
+            storyboard: false,
+            retention_days: None,
+            watermark: None,
+            archive_source: false,
+            idempotency_key: None,
+            clip: None,
+            normalize_audio: None,
+            strip_metadata: None,
+            hdr: HdrMode::default(),
+            deinterlace: DeinterlaceMode::default(),
+            lang: Lang::default(),
+            explicit: HashSet::new(),
+        }
+    }
 }
 
 impl JobContext {
@@ -286,12 +794,13 @@ impl JobContext {
 
     /// Create JobContext from an UnsignedEvent (NIP-17 Rumor)
     pub fn from_rumor_with_keys(rumor: UnsignedEvent, keys: &Keys) -> Result<Self, DvmError> {
-        let tags: Vec<Tag> = rumor.tags.iter().cloned().collect();
+        let tags: Vec<Tag> = rumor.tags.to_vec();
         let input = Self::extract_input_from_tags(&tags)?;
         let relays = Self::extract_relays_from_tags(&tags);
-        let (mode, resolution, codec, hls_resolutions, encryption) =
-            Self::extract_params_from_tags(&tags);
+        let params = Self::extract_params_from_tags(&tags);
         let cashu_token = Self::extract_cashu_token_from_tags(&tags);
+        let source_event = Self::extract_source_event_from_tags(&tags);
+        let input_sha256 = Self::extract_input_sha256_from_tags(&tags);
 
         // Preserve the real requester identity before re-signing
         let original_requester = Some(rumor.pubkey);
@@ -306,102 +815,144 @@ impl JobContext {
             encryption_type: EncryptionType::Nip44,
             input,
             relays,
-            mode,
-            resolution,
-            codec,
-            hls_resolutions,
-            encryption,
+            mode: params.mode,
+            resolution: params.resolution,
+            codec: params.codec,
+            device: params.device,
+            container: params.container,
+            hls_resolutions: params.hls_resolutions,
+            encryption: params.encryption,
+            segment_type: params.segment_type,
+            hls_time: params.hls_time,
+            keyframe_interval: params.keyframe_interval,
+            max_fps: params.max_fps,
+            encryption_scheme: params.encryption_scheme,
+            audio_bitrates: params.audio_bitrates,
+            thumbnail_count: params.thumbnail_count,
+            thumbnail_timestamps: params.thumbnail_timestamps,
+            preview: params.preview,
+            preview_duration: params.preview_duration,
+            storyboard: params.storyboard,
+            retention_days: params.retention_days,
+            watermark: params.watermark,
+            archive_source: params.archive_source,
+            idempotency_key: params.idempotency_key,
+            clip: params.clip,
+            normalize_audio: params.normalize_audio,
+            strip_metadata: params.strip_metadata,
+            hdr: params.hdr,
+            deinterlace: params.deinterlace,
+            lang: params.lang,
             cashu_token,
+            source_event,
+            creator: None,
+            input_sha256,
             original_requester,
             original_event_id,
+            explicit_params: params.explicit,
             approved: false,
+            paid_sats: None,
+            progress: Arc::default(),
+            log: Arc::default(),
         })
     }
 
+    /// What it takes to rebuild this job after a restart
+    pub fn to_saved(&self) -> SavedJob {
+        SavedJob {
+            request: self.request.clone(),
+            encryption_type: self.encryption_type,
+            original_requester: self.original_requester,
+            original_event_id: self.original_event_id,
+            approved: self.approved,
+            paid_sats: self.paid_sats,
+            creator: self.creator,
+        }
+    }
+
+    /// Rebuild a job saved with [`to_saved`](Self::to_saved)
+    pub fn from_saved(saved: SavedJob, keys: &Keys) -> Result<Self, DvmError> {
+        let mut job = if saved.original_requester.is_some() {
+            // Gift-wrapped requests were re-signed with their tags in the clear
+            let mut job = Self::from_event(saved.request)?;
+            job.encryption_type = saved.encryption_type;
+            job.original_requester = saved.original_requester;
+            job.original_event_id = saved.original_event_id;
+            job
+        } else {
+            Self::from_event_with_keys(saved.request, keys)?
+        };
+        job.approved = saved.approved;
+        job.paid_sats = saved.paid_sats;
+        job.creator = saved.creator;
+        Ok(job)
+    }
+
     /// Create JobContext from an unencrypted event
     pub fn from_event(event: Event) -> Result<Self, DvmError> {
-        let tags: Vec<Tag> = event.tags.iter().cloned().collect();
-        let input = Self::extract_input_from_tags(&tags)?;
-        let relays = Self::extract_relays_from_tags(&tags);
-        let (mode, resolution, codec, hls_resolutions, encryption) =
-            Self::extract_params_from_tags(&tags);
-        let cashu_token = Self::extract_cashu_token_from_tags(&tags);
-
-        Ok(Self {
-            request: event,
-            encryption_type: EncryptionType::None,
-            input,
-            relays,
-            mode,
-            resolution,
-            codec,
-            hls_resolutions,
-            encryption,
-            cashu_token,
-            original_requester: None,
-            original_event_id: None,
-            approved: false,
-        })
+        let tags: Vec<Tag> = event.tags.to_vec();
+        Self::from_tags(event, &tags, EncryptionType::None)
     }
 
     /// Create JobContext from an encrypted event (NIP-04 or NIP-44)
     fn from_encrypted_event(event: Event, keys: &Keys) -> Result<Self, DvmError> {
-        // Try NIP-04 first, fall back to NIP-44, tracking which succeeded
-        let (decrypted, enc_type) =
-            if let Ok(d) = nip04::decrypt(keys.secret_key(), &event.pubkey, &event.content) {
-                (d, EncryptionType::Nip04)
-            } else {
-                let d = nip44::decrypt(keys.secret_key(), &event.pubkey, &event.content)
-                    .map_err(|e| {
-                        DvmError::JobRejected(format!("Failed to decrypt request: {}", e))
-                    })?;
-                (d, EncryptionType::Nip44)
-            };
-
-        // Parse decrypted content as JSON containing i and params
-        let encrypted_content: EncryptedContent =
-            serde_json::from_str(&decrypted).map_err(|e| {
-                DvmError::JobRejected(format!("Invalid encrypted content format: {}", e))
-            })?;
-
-        // Extract input from decrypted content
-        let input = Self::extract_input_from_vec(&encrypted_content.i)?;
-
-        // Build virtual tags from decrypted params for parameter extraction
-        let mut virtual_tags: Vec<Tag> = encrypted_content
-            .params
-            .iter()
-            .map(|p| Tag::custom(TagKind::Custom(p[0].clone().into()), p[1..].to_vec()))
-            .collect();
-
-        // Also include unencrypted tags (relays, p tag, etc.)
-        for tag in event.tags.iter() {
-            let tag_name = tag.as_slice().first().map(|s| s.as_str());
-            // Include relays and cashu tags from unencrypted tags
-            if tag_name == Some("relays") || tag_name == Some("cashu") {
-                virtual_tags.push(tag.clone());
-            }
-        }
+        let decrypted = decrypt_request(keys, &event)?;
+        Self::from_tags(event, &decrypted.tags, decrypted.encryption_type)
+    }
 
-        let relays = Self::extract_relays_from_tags(&virtual_tags);
-        let (mode, resolution, codec, hls_resolutions, encryption) =
-            Self::extract_params_from_tags(&virtual_tags);
-        let cashu_token = Self::extract_cashu_token_from_tags(&virtual_tags);
+    /// Create JobContext from `request` with its (decrypted) `tags`
+    fn from_tags(request: Event, tags: &[Tag], encryption_type: EncryptionType) -> Result<Self, DvmError> {
+        let input = Self::extract_input_from_tags(tags)?;
+        let relays = Self::extract_relays_from_tags(tags);
+        let params = Self::extract_params_from_tags(tags);
+        let cashu_token = Self::extract_cashu_token_from_tags(tags);
+        let source_event = Self::extract_source_event_from_tags(tags);
+        let input_sha256 = Self::extract_input_sha256_from_tags(tags);
 
         Ok(Self {
-            request: event,
-            encryption_type: enc_type,
+            request,
+            encryption_type,
             input,
             relays,
-            mode,
-            resolution,
-            codec,
-            hls_resolutions,
-            encryption,
+            mode: params.mode,
+            resolution: params.resolution,
+            codec: params.codec,
+            device: params.device,
+            container: params.container,
+            hls_resolutions: params.hls_resolutions,
+            encryption: params.encryption,
+            segment_type: params.segment_type,
+            hls_time: params.hls_time,
+            keyframe_interval: params.keyframe_interval,
+            max_fps: params.max_fps,
+            encryption_scheme: params.encryption_scheme,
+            audio_bitrates: params.audio_bitrates,
+            thumbnail_count: params.thumbnail_count,
+            thumbnail_timestamps: params.thumbnail_timestamps,
+            preview: params.preview,
+            preview_duration: params.preview_duration,
+            storyboard: params.storyboard,
+            retention_days: params.retention_days,
+            watermark: params.watermark,
+            archive_source: params.archive_source,
+            idempotency_key: params.idempotency_key,
+            clip: params.clip,
+            normalize_audio: params.normalize_audio,
+            strip_metadata: params.strip_metadata,
+            hdr: params.hdr,
+            deinterlace: params.deinterlace,
+            lang: params.lang,
             cashu_token,
+            source_event,
+            creator: None,
+            input_sha256,
             original_requester: None,
             original_event_id: None,
+            explicit_params: params.explicit,
             approved: false,
+            paid_sats: None,
+            progress: Arc::default(),
+            log: Arc::default(),
         })
     }
 
@@ -411,35 +962,152 @@ impl JobContext {
             .and_then(|t| t.as_slice().get(1).map(|s| s.to_string()))
     }
 
-    fn extract_params_from_tags(
-        tags: &[Tag],
-    ) -> (OutputMode, Resolution, Codec, Vec<Resolution>, bool) {
-        let mut mode = OutputMode::default();
-        let mut resolution = Resolution::default();
-        let mut codec = Codec::default();
-        let mut hls_resolutions: Vec<Resolution> = Vec::new();
-        let mut encryption = true; // Default to true for backward compatibility
+    fn extract_input_sha256_from_tags(tags: &[Tag]) -> Option<String> {
+        tags.iter()
+            .filter(|t| t.as_slice().first().map(|s| s.as_str()) == Some("x"))
+            .filter_map(|t| t.as_slice().get(1))
+            .find(|h| h.len() == 64 && h.chars().all(|c| c.is_ascii_hexdigit()))
+            .map(|h| h.to_lowercase())
+    }
+
+    fn extract_source_event_from_tags(tags: &[Tag]) -> Option<EventId> {
+        tags.iter()
+            .filter(|t| t.as_slice().first().map(|s| s.as_str()) == Some("e"))
+            .find_map(|t| t.as_slice().get(1).and_then(|id| EventId::from_hex(id).ok()))
+    }
+
+    fn extract_params_from_tags(tags: &[Tag]) -> JobParams {
+        let mut params = JobParams::default();
+        let mut watermark: Option<&str> = None;
+        let mut watermark_position = None;
+        let mut watermark_opacity = None;
+        let (mut clip_start, mut clip_end, mut clip_duration) = (None, None, None);
 
         for tag in tags.iter() {
             let parts: Vec<&str> = tag.as_slice().iter().map(|s| s.as_str()).collect();
             if parts.first() == Some(&"param") && parts.len() >= 3 {
+                params.explicit.insert(parts[1].to_string());
                 match parts[1] {
-                    "mode" => mode = OutputMode::from_str(parts[2]),
-                    "resolution" => resolution = Resolution::from_str_or_default(parts[2]),
-                    "codec" => codec = Codec::from_str(parts[2]),
-                    "resolutions" => hls_resolutions = parts[2].split(',').filter_map(|r| Resolution::from_str(r.trim())).collect(),
-                    "encryption" => encryption = parts[2].to_lowercase() != "false",
+                    "mode" => params.mode = OutputMode::from_str(parts[2]),
+                    "resolution" => params.resolution = Resolution::from_str_or_default(parts[2]),
+                    "codec" => params.codec = Codec::from_str(parts[2]),
+                    "device" => params.device = Device::parse(parts[2]),
+                    "resolutions" => {
+                        params.hls_resolutions = parts[2].split(',').filter_map(|r| Resolution::from_str(r.trim())).collect();
+                        // Nothing usable listed: the operator's default ladder applies
+                        if params.hls_resolutions.is_empty() {
+                            params.explicit.remove("resolutions");
+                        }
+                    }
+                    "encryption" => params.encryption = parts[2].to_lowercase() != "false",
+                    "segment_type" => {
+                        if let Some(t) = SegmentType::parse(parts[2]) {
+                            params.segment_type = t;
+                        }
+                    }
+                    // Out-of-range values are checked by `segmenting`
+                    "hls_time" => params.hls_time = parts[2].trim().parse().ok(),
+                    "keyframe_interval" => params.keyframe_interval = parts[2].trim().parse().ok(),
+                    "max_fps" => {
+                        params.max_fps = parts[2]
+                            .trim()
+                            .parse()
+                            .ok()
+                            .and_then(|fps| FpsCap::validate(fps).ok())
+                    }
+                    // Unknown schemes are declined by `capability_gap`
+                    "encryption_scheme" => {
+                        params.encryption_scheme = EncryptionScheme::parse(parts[2])
+                    }
+                    "audio_bitrates" => {
+                        // "240p:48k,720p:160k"
+                        for entry in parts[2].split(',') {
+                            if let Some((label, bitrate)) = entry.split_once(':') {
+                                let label = label.trim().to_lowercase();
+                                if let Some(bitrate) = parse_audio_bitrate(bitrate) {
+                                    params.audio_bitrates.insert(label, bitrate);
+                                }
+                            }
+                        }
+                    }
+                    "thumbnails" => {
+                        if let Ok(n) = parts[2].trim().parse::<usize>() {
+                            params.thumbnail_count = n.clamp(1, MAX_THUMBNAILS);
+                        }
+                    }
+                    "container" => params.container = Container::parse(parts[2]),
+                    "preview" => params.preview = PreviewFormat::parse(parts[2]),
+                    "preview_duration" => {
+                        if let Ok(secs) = parts[2].trim().parse::<f64>() {
+                            if secs.is_finite() && secs > 0.0 {
+                                params.preview_duration = secs.min(MAX_PREVIEW_SECS);
+                            }
+                        }
+                    }
+                    "storyboard" => params.storyboard = parts[2].trim().eq_ignore_ascii_case("true"),
+                    "retention" => params.retention_days = parts[2].trim().parse().ok(),
+                    "watermark" => watermark = Some(parts[2]),
+                    "archive_source" => params.archive_source = parts[2].trim().eq_ignore_ascii_case("true"),
+                    "idempotency_key" => {
+                        let key = parts[2].trim();
+                        params.idempotency_key = (!key.is_empty() && key.len() <= MAX_KEY_LEN)
+                            .then(|| key.to_string());
+                    }
+                    "normalize_audio" => {
+                        params.normalize_audio = match parts[2].trim().to_lowercase().as_str() {
+                            "true" => Some(true),
+                            "false" => Some(false),
+                            _ => None,
+                        }
+                    }
+                    "strip_metadata" => {
+                        params.strip_metadata = match parts[2].trim().to_lowercase().as_str() {
+                            "true" => Some(true),
+                            "false" => Some(false),
+                            _ => None,
+                        }
+                    }
+                    "hdr" => params.hdr = HdrMode::parse(parts[2]).unwrap_or_default(),
+                    "deinterlace" => {
+                        params.deinterlace = DeinterlaceMode::parse(parts[2]).unwrap_or_default()
+                    }
+                    "lang" => params.lang = Lang::parse(parts[2]).unwrap_or_default(),
+                    "watermark_position" => watermark_position = WatermarkPosition::parse(parts[2]),
+                    "watermark_opacity" => watermark_opacity = parts[2].trim().parse::<f32>().ok(),
+                    "start" => clip_start = Some(parts[2]),
+                    "end" => clip_end = Some(parts[2]),
+                    "duration" => clip_duration = Some(parts[2]),
+                    "timestamps" => {
+                        params.thumbnail_timestamps = parts[2]
+                            .split(',')
+                            .filter_map(|t| t.trim().parse::<f64>().ok())
+                            .filter(|t| t.is_finite() && *t >= 0.0)
+                            .take(MAX_THUMBNAILS)
+                            .collect();
+                    }
                     _ => {}
                 }
             }
         }
 
         // If no resolutions specified, use all (backward compatibility)
-        if hls_resolutions.is_empty() {
-            hls_resolutions = Resolution::all();
+        if params.hls_resolutions.is_empty() {
+            params.hls_resolutions = Resolution::all();
         }
 
-        (mode, resolution, codec, hls_resolutions, encryption)
+        // Invalid watermarks are left unset; the handler declines them since
+        // the param is still recorded as explicit
+        params.watermark = watermark.and_then(|content| {
+            Watermark::new(content, watermark_position, watermark_opacity)
+                .inspect_err(|e| debug!("Ignoring watermark param: {}", e))
+                .ok()
+        });
+        params.clip = Clip::from_params(clip_start, clip_end, clip_duration)
+            .inspect_err(|e| debug!("Ignoring clip params: {}", e))
+            .ok()
+            .flatten();
+
+        params
     }
 
     fn extract_input_from_tags(tags: &[Tag]) -> Result<DvmInput, DvmError> {
@@ -462,22 +1130,6 @@ impl JobContext {
         })
     }
 
-    /// Extract input from decrypted content's "i" array
-    fn extract_input_from_vec(i: &[String]) -> Result<DvmInput, DvmError> {
-        if i.len() < 2 {
-            return Err(DvmError::JobRejected(
-                "Invalid encrypted input format".into(),
-            ));
-        }
-
-        Ok(DvmInput {
-            value: i[0].clone(),
-            input_type: i[1].clone(),
-            relay: i.get(2).cloned(),
-            marker: i.get(3).cloned(),
-        })
-    }
-
     fn extract_relays_from_tags(tags: &[Tag]) -> Vec<::url::Url> {
         tags.iter()
             .find(|t| t.as_slice().first().map(|s| s.as_str()) == Some("relays"))
@@ -499,6 +1151,87 @@ impl JobContext {
     pub fn requester(&self) -> PublicKey {
         self.original_requester.unwrap_or(self.request.pubkey)
     }
+
+    /// SHA-256 the downloaded input must match: the `x` tag, or else the
+    /// hash in a Blossom input URL
+    pub fn expected_input_sha256(&self) -> Option<String> {
+        self.input_sha256.clone().or_else(|| match self.input.input_type.as_str() {
+            "url" | "archive" => sha256_from_url(&self.input.value),
+            _ => None,
+        })
+    }
+
+    /// Whether the requester sent the named `param` tag
+    pub fn has_param(&self, name: &str) -> bool {
+        self.explicit_params.contains(name)
+    }
+
+    /// Fill in operator defaults for params the requester left out
+    pub fn apply_defaults(&mut self, defaults: &JobDefaults) {
+        if !self.explicit_params.contains("resolutions") && !defaults.hls_resolutions.is_empty() {
+            self.hls_resolutions = defaults.hls_resolutions.clone();
+        }
+        if !self.explicit_params.contains("resolution") {
+            if let Some(resolution) = defaults.mp4_resolution {
+                self.resolution = resolution;
+            }
+        }
+        if !self.explicit_params.contains("codec") {
+            if let Some(codec) = defaults.codec {
+                self.codec = codec;
+            }
+        }
+        if !self.explicit_params.contains("segment_type") {
+            if let Some(segment_type) = defaults.segment_type {
+                self.segment_type = segment_type;
+            }
+        }
+        if !self.explicit_params.contains("hls_time") {
+            self.hls_time = defaults.hls_time;
+        }
+        if !self.explicit_params.contains("keyframe_interval") {
+            self.keyframe_interval = defaults.keyframe_interval;
+        }
+        if !self.explicit_params.contains("max_fps") {
+            self.max_fps = defaults.max_fps;
+        }
+    }
+
+    /// Let the `device` hint pick the codec, unless the requester chose one.
+    /// Returns the device when it did.
+    pub fn apply_device(&mut self, hwaccel: HwAccel) -> Option<Device> {
+        let device = self.device.filter(|_| !self.has_param("codec"))?;
+        self.codec = device.codec(self.mode, self.encryption, |codec| {
+            Codec::from_encoder(hwaccel.video_encoder(codec)) == codec
+        });
+        Some(device)
+    }
+
+    /// Segment duration and keyframe spacing for HLS output
+    pub fn segmenting(&self) -> Result<Segmenting, String> {
+        for (name, set) in [
+            ("hls_time", self.hls_time.is_some()),
+            ("keyframe_interval", self.keyframe_interval.is_some()),
+        ] {
+            if self.explicit_params.contains(name) && !set {
+                return Err(format!("{} must be a number of seconds", name));
+            }
+        }
+        Segmenting::new(self.hls_time, self.keyframe_interval)
+    }
+}
+
+/// Operator-chosen defaults for requests that don't specify them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JobDefaults {
+    /// HLS ladder (empty keeps [`Resolution::all`])
+    pub hls_resolutions: Vec<Resolution>,
+    pub mp4_resolution: Option<Resolution>,
+    pub codec: Option<Codec>,
+    pub segment_type: Option<SegmentType>,
+    pub hls_time: Option<u32>,
+    pub keyframe_interval: Option<f64>,
+    pub max_fps: Option<u32>,
 }
 
 /// Build a status event for a job
@@ -522,14 +1255,18 @@ pub fn build_status_event_with_eta(
     build_status_event_with_eta_encrypted(job_id, requester, status, message, remaining_secs, None, None, EncryptionType::None)
 }
 
-/// Context for Cashu payment request
+/// Payment terms attached to a `payment-required` status
 #[derive(Debug, Clone)]
-pub struct CashuContext {
+pub struct PaymentContext {
+    /// Cashu mint the DVM accepts tokens from
     pub mint: String,
     pub amount_sats: u64,
+    /// Lightning invoice over the same amount, when a backend is configured
+    pub bolt11: Option<String>,
 }
 
 /// Build a status event with optional encryption
+#[allow(clippy::too_many_arguments)]
 pub fn build_status_event_with_eta_encrypted(
     job_id: EventId,
     requester: PublicKey,
@@ -550,6 +1287,7 @@ pub fn build_status_event_with_eta_encrypted(
         None,
         progress,
         enc_type,
+        None,
     )
 }
 
@@ -557,6 +1295,7 @@ pub fn build_status_event_with_eta_encrypted(
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProgressPhase {
     Queued,
+    Downloading,
     Transcoding,
     Uploading,
 }
@@ -565,13 +1304,17 @@ impl ProgressPhase {
     pub fn as_str(&self) -> &'static str {
         match self {
             Self::Queued => "queued",
+            Self::Downloading => "downloading",
             Self::Transcoding => "transcoding",
             Self::Uploading => "uploading",
         }
     }
 }
 
-/// Build a status event with optional context (e.g. Cashu)
+/// Build a status event with optional context (e.g. payment terms)
+///
+/// `code` identifies a catalog message (see [`StatusMessage::code`](crate::dvm::messages::StatusMessage::code)).
+#[allow(clippy::too_many_arguments)]
 pub fn build_status_event_with_context(
     job_id: EventId,
     requester: PublicKey,
@@ -579,9 +1322,10 @@ pub fn build_status_event_with_context(
     message: Option<&str>,
     remaining_secs: Option<u64>,
     keys: Option<&Keys>,
-    cashu: Option<CashuContext>,
+    payment: Option<PaymentContext>,
     progress: Option<u32>,
     enc_type: EncryptionType,
+    code: Option<&str>,
 ) -> EventBuilder {
     build_status_event_with_phase(
         job_id,
@@ -590,13 +1334,14 @@ pub fn build_status_event_with_context(
         message,
         remaining_secs,
         keys,
-        cashu,
+        payment,
         progress,
         enc_type,
         None,
         None,
         None,
         None,
+        code,
     )
 }
 
@@ -609,7 +1354,7 @@ pub fn build_status_event_with_phase(
     message: Option<&str>,
     remaining_secs: Option<u64>,
     keys: Option<&Keys>,
-    cashu: Option<CashuContext>,
+    payment: Option<PaymentContext>,
     progress: Option<u32>,
     enc_type: EncryptionType,
     phase: Option<ProgressPhase>,
@@ -619,6 +1364,8 @@ pub fn build_status_event_with_phase(
     file_size: Option<u64>,
     // Position in job queue (1-based, only when queued)
     queue_position: Option<u32>,
+    // Catalog code of the message, for clients that localize it themselves
+    code: Option<&str>,
 ) -> EventBuilder {
     // NIP-40 expiration: 24 hours
     let expiration = Timestamp::now() + Duration::from_secs(STATUS_EXPIRATION_SECS);
@@ -633,15 +1380,18 @@ pub fn build_status_event_with_phase(
         ),
     ];
 
-    if let Some(ctx) = &cashu {
+    if let Some(ctx) = &payment {
         tags.push(Tag::custom(
             TagKind::Custom("cashu".into()),
             vec![ctx.mint.clone()],
         ));
-        tags.push(Tag::custom(
-            TagKind::Custom("amount".into()),
-            vec![ctx.amount_sats.to_string()],
-        ));
+        // With an invoice the tag takes the NIP-90 form (millisats, bolt11);
+        // Cashu-only requests keep the amount in sats
+        let amount = match &ctx.bolt11 {
+            Some(bolt11) => vec![(ctx.amount_sats * 1000).to_string(), bolt11.clone()],
+            None => vec![ctx.amount_sats.to_string()],
+        };
+        tags.push(Tag::custom(TagKind::Custom("amount".into()), amount));
     }
 
     // For encrypted responses, put status details in encrypted content
@@ -654,10 +1404,13 @@ pub fn build_status_event_with_phase(
             "progress": progress,
         });
 
-        if let Some(ctx) = cashu {
+        if let Some(ctx) = payment {
             if let Some(obj) = status_content.as_object_mut() {
                 obj.insert("cashu".to_string(), serde_json::json!(ctx.mint));
                 obj.insert("amount".to_string(), serde_json::json!(ctx.amount_sats));
+                if let Some(bolt11) = ctx.bolt11 {
+                    obj.insert("bolt11".to_string(), serde_json::json!(bolt11));
+                }
             }
         }
         if let (Some(obj), Some(p)) = (status_content.as_object_mut(), phase) {
@@ -666,6 +1419,9 @@ pub fn build_status_event_with_phase(
         if let (Some(obj), Some(s)) = (status_content.as_object_mut(), speed) {
             obj.insert("speed".to_string(), serde_json::json!(s));
         }
+        if let (Some(obj), Some(c)) = (status_content.as_object_mut(), code) {
+            obj.insert("code".to_string(), serde_json::json!(c));
+        }
 
         // Encrypt the content using the same encryption type as the request
         if let Ok(encrypted) =
@@ -691,6 +1447,13 @@ pub fn build_status_event_with_phase(
         status.as_str().to_string()
     };
 
+    if let Some(c) = code {
+        tags.push(Tag::custom(
+            TagKind::Custom("code".into()),
+            vec![c.to_string()],
+        ));
+    }
+
     if let Some(secs) = remaining_secs {
         tags.push(Tag::custom(
             TagKind::Custom("eta".into()),
@@ -737,26 +1500,227 @@ pub fn build_status_event_with_phase(
     EventBuilder::new(DVM_STATUS_KIND, content, tags)
 }
 
+/// Why the DVM declines a request it cannot serve at all.
+///
+/// Sent as an `error` status with a machine-readable `reason` tag, so clients
+/// can move on to another DVM instead of waiting for a bid that never comes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeclineReason {
+    UnsupportedCodec,
+    UnsupportedInput,
+    UnsupportedEncryption,
+    InputTooLarge,
+    RetentionUnavailable,
+    InvalidWatermark,
+    InvalidClip,
+    InvalidSegmenting,
+    OutputTooLarge,
+    NotAllowed,
+}
+
+impl DeclineReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::UnsupportedCodec => "unsupported-codec",
+            Self::UnsupportedInput => "unsupported-input",
+            Self::UnsupportedEncryption => "unsupported-encryption",
+            Self::InputTooLarge => "input-too-large",
+            Self::RetentionUnavailable => "retention-unavailable",
+            Self::InvalidWatermark => "invalid-watermark",
+            Self::InvalidClip => "invalid-clip",
+            Self::InvalidSegmenting => "invalid-segmenting",
+            Self::OutputTooLarge => "output-too-large",
+            Self::NotAllowed => "not-allowed",
+        }
+    }
+}
+
+/// Build a "not capable" status event declining a request
+pub fn build_decline_event(
+    job_id: EventId,
+    requester: PublicKey,
+    reason: DeclineReason,
+    message: &str,
+    keys: Option<&Keys>,
+    enc_type: EncryptionType,
+) -> EventBuilder {
+    let expiration = Timestamp::now() + Duration::from_secs(STATUS_EXPIRATION_SECS);
+
+    let mut tags = vec![
+        Tag::expiration(expiration),
+        Tag::event(job_id),
+        Tag::public_key(requester),
+        Tag::custom(
+            TagKind::Custom("status".into()),
+            vec![JobStatus::Error.as_str().to_string()],
+        ),
+    ];
+
+    if let Some(keys) = keys {
+        let content = serde_json::json!({
+            "status": JobStatus::Error.as_str(),
+            "reason": reason.as_str(),
+            "message": message,
+        });
+        if let Ok(encrypted) = encrypt_for_dvm(keys, &requester, &content.to_string(), enc_type) {
+            tags.push(Tag::custom(
+                TagKind::Custom("encrypted".into()),
+                Vec::<String>::new(),
+            ));
+            return EventBuilder::new(DVM_STATUS_KIND, encrypted, tags);
+        }
+    }
+
+    tags.push(Tag::custom(
+        TagKind::Custom("reason".into()),
+        vec![reason.as_str().to_string()],
+    ));
+    tags.push(Tag::custom(
+        TagKind::Custom("content".into()),
+        vec![message.to_string()],
+    ));
+    EventBuilder::new(DVM_STATUS_KIND, message, tags)
+}
+
+/// Build an error status turning away a request over the requester's rate limit.
+///
+/// `retry_after_secs` tells the client when a job slot frees up.
+pub fn build_rate_limited_event(
+    job_id: EventId,
+    requester: PublicKey,
+    retry_after_secs: u64,
+    message: &str,
+    keys: Option<&Keys>,
+    enc_type: EncryptionType,
+) -> EventBuilder {
+    let expiration = Timestamp::now() + Duration::from_secs(STATUS_EXPIRATION_SECS);
+    let code = "rate-limited";
+
+    let mut tags = vec![
+        Tag::expiration(expiration),
+        Tag::event(job_id),
+        Tag::public_key(requester),
+        Tag::custom(
+            TagKind::Custom("status".into()),
+            vec![JobStatus::Error.as_str().to_string()],
+        ),
+    ];
+
+    if let Some(keys) = keys {
+        let content = serde_json::json!({
+            "status": JobStatus::Error.as_str(),
+            "message": message,
+            "code": code,
+            "retry_after": retry_after_secs,
+        });
+        if let Ok(encrypted) = encrypt_for_dvm(keys, &requester, &content.to_string(), enc_type) {
+            tags.push(Tag::custom(
+                TagKind::Custom("encrypted".into()),
+                Vec::<String>::new(),
+            ));
+            return EventBuilder::new(DVM_STATUS_KIND, encrypted, tags);
+        }
+    }
+
+    tags.push(Tag::custom(
+        TagKind::Custom("code".into()),
+        vec![code.to_string()],
+    ));
+    tags.push(Tag::custom(
+        TagKind::Custom("retry_after".into()),
+        vec![retry_after_secs.to_string()],
+    ));
+    tags.push(Tag::custom(
+        TagKind::Custom("content".into()),
+        vec![message.to_string()],
+    ));
+    EventBuilder::new(DVM_STATUS_KIND, message, tags)
+}
+
+/// Playable part of an HLS result, available before the whole ladder is uploaded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartialResult {
+    /// Master playlist with only the lowest rendition
+    pub master_playlist: String,
+    pub resolution: String,
+}
+
+/// Build a `partial` status carrying a playable preview of an HLS result.
+///
+/// As NIP-90 describes for partial results, the content is the sample itself
+/// (the `PartialResult` JSON); the human-readable message goes in a `content` tag.
+pub fn build_partial_event(
+    job_id: EventId,
+    requester: PublicKey,
+    partial: &PartialResult,
+    message: &str,
+    code: &str,
+    keys: Option<&Keys>,
+    enc_type: EncryptionType,
+) -> EventBuilder {
+    let expiration = Timestamp::now() + Duration::from_secs(STATUS_EXPIRATION_SECS);
+
+    let mut tags = vec![
+        Tag::expiration(expiration),
+        Tag::event(job_id),
+        Tag::public_key(requester),
+        Tag::custom(
+            TagKind::Custom("status".into()),
+            vec![JobStatus::Partial.as_str().to_string()],
+        ),
+    ];
+
+    if let Some(keys) = keys {
+        let content = serde_json::json!({
+            "status": JobStatus::Partial.as_str(),
+            "message": message,
+            "code": code,
+            "master_playlist": partial.master_playlist,
+            "resolution": partial.resolution,
+        });
+        if let Ok(encrypted) = encrypt_for_dvm(keys, &requester, &content.to_string(), enc_type) {
+            tags.push(Tag::custom(
+                TagKind::Custom("encrypted".into()),
+                Vec::<String>::new(),
+            ));
+            return EventBuilder::new(DVM_STATUS_KIND, encrypted, tags);
+        }
+    }
+
+    tags.push(Tag::custom(
+        TagKind::Custom("code".into()),
+        vec![code.to_string()],
+    ));
+    tags.push(Tag::custom(
+        TagKind::Custom("content".into()),
+        vec![message.to_string()],
+    ));
+    let content = serde_json::to_string(partial).unwrap_or_default();
+    EventBuilder::new(DVM_STATUS_KIND, content, tags)
+}
+
 /// Build a result event for a completed job (unencrypted)
 pub fn build_result_event(
     job_id: EventId,
     requester: PublicKey,
     result: &DvmResult,
+    expiration: Timestamp,
 ) -> EventBuilder {
-    build_result_event_encrypted(job_id, requester, result, None, EncryptionType::None)
+    build_result_event_encrypted(job_id, requester, result, expiration, None, EncryptionType::None)
 }
 
-/// Build a result event with optional encryption, matching the client's encryption type
+/// Build a result event with optional encryption, matching the client's encryption type.
+///
+/// `expiration` (NIP-40) should be when the result's blobs are deleted, so
+/// clients stop offering links that no longer resolve.
 pub fn build_result_event_encrypted(
     job_id: EventId,
     requester: PublicKey,
     result: &DvmResult,
+    expiration: Timestamp,
     keys: Option<&Keys>,
     enc_type: EncryptionType,
 ) -> EventBuilder {
-    // NIP-40 expiration: 7 days
-    let expiration = Timestamp::now() + Duration::from_secs(RESULT_EXPIRATION_SECS);
-
     let mut tags = vec![
         Tag::expiration(expiration),
         Tag::event(job_id),
@@ -812,6 +1776,666 @@ mod tests {
         assert_eq!(Resolution::from_str_or_default("invalid"), Resolution::R720p);
     }
 
+    #[test]
+    fn test_audio_bitrates_from_tags() {
+        let keys = Keys::generate();
+        let tags = vec![
+            Tag::custom(
+                TagKind::Custom("i".into()),
+                vec!["https://example.com/video.mp4".to_string(), "url".to_string()],
+            ),
+            Tag::custom(
+                TagKind::Custom("param".into()),
+                vec![
+                    "audio_bitrates".to_string(),
+                    "240p:48k, 720P:160000,1080p:9000k,bogus".to_string(),
+                ],
+            ),
+        ];
+        let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+            .to_event(&keys)
+            .unwrap();
+
+        let job = JobContext::from_event(event).unwrap();
+        assert_eq!(job.audio_bitrates.len(), 2);
+        assert_eq!(job.audio_bitrates["240p"], "48k");
+        assert_eq!(job.audio_bitrates["720p"], "160k");
+    }
+
+    #[test]
+    fn test_segment_params_from_tags() {
+        let keys = Keys::generate();
+        let tags = vec![
+            Tag::custom(
+                TagKind::Custom("i".into()),
+                vec!["https://example.com/video.mp4".to_string(), "url".to_string()],
+            ),
+            Tag::custom(
+                TagKind::Custom("param".into()),
+                vec!["segment_type".to_string(), "ts".to_string()],
+            ),
+            Tag::custom(
+                TagKind::Custom("param".into()),
+                vec!["encryption_scheme".to_string(), "AES-128".to_string()],
+            ),
+        ];
+        let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+            .to_event(&keys)
+            .unwrap();
+
+        let job = JobContext::from_event(event).unwrap();
+        assert_eq!(job.segment_type, SegmentType::MpegTs);
+        assert_eq!(job.encryption_scheme, Some(EncryptionScheme::Aes128));
+        assert!(job.encryption);
+    }
+
+    #[test]
+    fn test_thumbnail_params_from_tags() {
+        let keys = Keys::generate();
+        let param = |name: &str, value: &str| {
+            Tag::custom(
+                TagKind::Custom("param".into()),
+                vec![name.to_string(), value.to_string()],
+            )
+        };
+        let tags = vec![
+            Tag::custom(
+                TagKind::Custom("i".into()),
+                vec!["https://example.com/video.mp4".to_string(), "url".to_string()],
+            ),
+            param("mode", "thumbnail"),
+            param("thumbnails", "50"),
+            param("timestamps", "1.5, 10,-3,abc"),
+        ];
+        let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+            .to_event(&keys)
+            .unwrap();
+
+        let job = JobContext::from_event(event).unwrap();
+        assert_eq!(job.mode, OutputMode::Thumbnail);
+        assert_eq!(job.thumbnail_count, MAX_THUMBNAILS);
+        assert_eq!(job.thumbnail_timestamps, vec![1.5, 10.0]);
+    }
+
+    #[test]
+    fn test_preview_params_from_tags() {
+        let keys = Keys::generate();
+        let tags = vec![
+            Tag::custom(
+                TagKind::Custom("i".into()),
+                vec!["https://example.com/video.mp4".to_string(), "url".to_string()],
+            ),
+            Tag::custom(
+                TagKind::Custom("param".into()),
+                vec!["preview".to_string(), "gif".to_string()],
+            ),
+            Tag::custom(
+                TagKind::Custom("param".into()),
+                vec!["preview_duration".to_string(), "60".to_string()],
+            ),
+        ];
+        let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+            .to_event(&keys)
+            .unwrap();
+
+        let job = JobContext::from_event(event).unwrap();
+        assert_eq!(job.preview, Some(PreviewFormat::Gif));
+        assert_eq!(job.preview_duration, MAX_PREVIEW_SECS);
+    }
+
+    #[test]
+    fn test_vp9_webm_params_from_tags() {
+        let keys = Keys::generate();
+        let tags = vec![
+            Tag::custom(
+                TagKind::Custom("i".into()),
+                vec!["https://example.com/video.mp4".to_string(), "url".to_string()],
+            ),
+            Tag::custom(
+                TagKind::Custom("param".into()),
+                vec!["codec".to_string(), "vp9".to_string()],
+            ),
+            Tag::custom(
+                TagKind::Custom("param".into()),
+                vec!["container".to_string(), "webm".to_string()],
+            ),
+        ];
+        let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+            .to_event(&keys)
+            .unwrap();
+
+        let job = JobContext::from_event(event).unwrap();
+        assert_eq!(job.codec, Codec::Vp9);
+        assert_eq!(job.container, Some(Container::WebM));
+    }
+
+    #[test]
+    fn test_set_preview() {
+        let mut result = DvmResult::Mp4(Mp4Result {
+            urls: vec!["https://blossom.example.com/abc.mp4".to_string()],
+            resolution: "720p".to_string(),
+            size_bytes: 1000,
+            mimetype: None,
+            preview: None,
+            storyboard: None,
+            chapters: None,
+            blurhash: None,
+            dim: None,
+            source: None,
+            input_sha256: None,
+            watch_url: None,
+        });
+        let json = serde_json::to_value(&result).unwrap();
+        assert!(json.get("preview").is_none());
+
+        result.set_preview(Preview {
+            urls: vec!["https://blossom.example.com/def.webp".to_string()],
+            mimetype: "image/webp".to_string(),
+            size_bytes: 200,
+            duration: 3.0,
+        });
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["preview"]["mimetype"], "image/webp");
+        assert!(json.get("input_sha256").is_none());
+
+        result.set_input_sha256("a".repeat(64));
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["input_sha256"], "a".repeat(64));
+        assert!(json.get("blurhash").is_none());
+
+        result.set_blurhash("L0TSUA".to_string(), Some("1280x720".to_string()));
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["blurhash"], "L0TSUA");
+        assert_eq!(json["dim"], "1280x720");
+    }
+
+    #[test]
+    fn test_thumbnail_result_serialization() {
+        let result = DvmResult::Thumbnail(ThumbnailResult {
+            thumbnails: vec![Thumbnail {
+                urls: vec!["https://blossom.example.com/abc.jpg".to_string()],
+                timestamp: 5.0,
+                size_bytes: 1024,
+                mimetype: "image/jpeg".to_string(),
+            }],
+            input_sha256: None,
+        });
+
+        let json: serde_json::Value = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["type"], "thumbnail");
+        assert_eq!(json["thumbnails"][0]["timestamp"], 5.0);
+    }
+
+    #[test]
+    fn test_result_blob_hashes_and_expiration() {
+        let url = |c: &str, ext: &str| format!("https://a.example.com/{}.{}", c.repeat(64), ext);
+        let result = DvmResult::Hls(HlsResult {
+            master_playlist: url("a", "m3u8"),
+            stream_playlists: vec![StreamPlaylist {
+                url: url("b", "m3u8"),
+                resolution: "360p".to_string(),
+                size_bytes: 10,
+                mimetype: None,
+                audio_bitrate: None,
+            }],
+            audio_tracks: vec![AudioRendition {
+                url: url("e", "m3u8"),
+                name: "audio_0".to_string(),
+                language: Some("eng".to_string()),
+                default: true,
+                size_bytes: 5,
+            }],
+            total_size_bytes: 10,
+            encryption_key: None,
+            preview: None,
+            storyboard: Some(StoryboardTrack {
+                urls: vec![url("f", "vtt")],
+                sheets: vec![url("0", "jpg")],
+                interval: 5,
+                dim: "160x90".to_string(),
+            }),
+            chapters: None,
+            blurhash: None,
+            dim: None,
+            source: Some(ArchivedSource {
+                urls: vec![url("d", "mov")],
+                sha256: "d".repeat(64),
+                size_bytes: 100,
+                mimetype: "video/quicktime".to_string(),
+            }),
+            input_sha256: None,
+            watch_url: None,
+            segment_hashes: vec!["c".repeat(64), "a".repeat(64)],
+        });
+        assert_eq!(
+            result.blob_hashes(),
+            vec![
+                "0".repeat(64),
+                "a".repeat(64),
+                "b".repeat(64),
+                "c".repeat(64),
+                "d".repeat(64),
+                "e".repeat(64),
+                "f".repeat(64)
+            ]
+        );
+
+        // Segment hashes are internal bookkeeping, not part of the result
+        let json = serde_json::to_value(&result).unwrap();
+        assert!(json.get("segment_hashes").is_none());
+        assert_eq!(json["source"]["sha256"], "d".repeat(64));
+
+        let keys = Keys::generate();
+        let expiration = Timestamp::from(1_900_000_000);
+        let event = build_result_event(EventId::all_zeros(), keys.public_key(), &result, expiration)
+            .to_event(&keys)
+            .unwrap();
+        assert_eq!(event.expiration(), Some(&expiration));
+    }
+
+    #[test]
+    fn test_retention_param() {
+        let keys = Keys::generate();
+        let tags = vec![
+            Tag::custom(
+                TagKind::Custom("i".into()),
+                vec!["https://example.com/video.mp4".to_string(), "url".to_string()],
+            ),
+            Tag::custom(
+                TagKind::Custom("param".into()),
+                vec!["retention".to_string(), "90".to_string()],
+            ),
+        ];
+        let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+            .to_event(&keys)
+            .unwrap();
+
+        let job = JobContext::from_event(event).unwrap();
+        assert_eq!(job.retention_days, Some(90));
+    }
+
+    #[test]
+    fn test_source_event_tag() {
+        let keys = Keys::generate();
+        let video_id = "a".repeat(64);
+        let tags = vec![
+            Tag::custom(
+                TagKind::Custom("i".into()),
+                vec!["https://example.com/video.mp4".to_string(), "url".to_string()],
+            ),
+            Tag::custom(TagKind::Custom("e".into()), vec!["not-an-id".to_string()]),
+            Tag::custom(TagKind::Custom("e".into()), vec![video_id.clone()]),
+        ];
+        let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+            .to_event(&keys)
+            .unwrap();
+
+        let job = JobContext::from_event(event).unwrap();
+        assert_eq!(job.source_event.map(|id| id.to_hex()), Some(video_id));
+        assert_eq!(job.creator, None);
+    }
+
+    #[test]
+    fn test_input_sha256() {
+        let keys = Keys::generate();
+        let blob = "b".repeat(64);
+        let request = |tags: Vec<Tag>| {
+            let mut all = vec![Tag::custom(
+                TagKind::Custom("i".into()),
+                vec![format!("https://blossom.example.com/{}.mp4", blob), "url".to_string()],
+            )];
+            all.extend(tags);
+            let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", all)
+                .to_event(&keys)
+                .unwrap();
+            JobContext::from_event(event).unwrap()
+        };
+
+        // The Blossom URL names the blob
+        let job = request(vec![]);
+        assert_eq!(job.input_sha256, None);
+        assert_eq!(job.expected_input_sha256(), Some(blob.clone()));
+
+        // An x tag takes precedence; malformed ones are ignored
+        let job = request(vec![
+            Tag::custom(TagKind::Custom("x".into()), vec!["nope".to_string()]),
+            Tag::custom(TagKind::Custom("x".into()), vec!["C".repeat(64)]),
+        ]);
+        assert_eq!(job.expected_input_sha256(), Some("c".repeat(64)));
+    }
+
+    #[test]
+    fn test_watermark_params() {
+        let keys = Keys::generate();
+        let param = |name: &str, value: &str| {
+            Tag::custom(
+                TagKind::Custom("param".into()),
+                vec![name.to_string(), value.to_string()],
+            )
+        };
+        let input = Tag::custom(
+            TagKind::Custom("i".into()),
+            vec!["https://example.com/video.mp4".to_string(), "url".to_string()],
+        );
+
+        let tags = vec![
+            input.clone(),
+            param("watermark", "My Channel"),
+            param("watermark_position", "top-left"),
+            param("watermark_opacity", "0.3"),
+        ];
+        let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+            .to_event(&keys)
+            .unwrap();
+        let job = JobContext::from_event(event).unwrap();
+        assert!(!job.archive_source);
+        assert!(!job.storyboard);
+        assert_eq!(job.normalize_audio, None);
+        assert_eq!(job.strip_metadata, None);
+        assert_eq!(job.hdr, HdrMode::Tonemap);
+        assert_eq!(job.deinterlace, DeinterlaceMode::Auto);
+        let watermark = job.watermark.unwrap();
+        assert_eq!(watermark.content, "My Channel");
+        assert_eq!(watermark.position, WatermarkPosition::TopLeft);
+        assert_eq!(watermark.opacity, 0.3);
+
+        let tags = vec![
+            input,
+            param("watermark", ""),
+            param("archive_source", "TRUE"),
+            param("storyboard", "true"),
+            param("normalize_audio", "false"),
+            param("strip_metadata", "false"),
+            param("hdr", "keep"),
+            param("deinterlace", "off"),
+        ];
+        let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+            .to_event(&keys)
+            .unwrap();
+        let job = JobContext::from_event(event).unwrap();
+        assert!(job.watermark.is_none());
+        assert!(job.has_param("watermark"));
+        assert!(job.archive_source);
+        assert!(job.storyboard);
+        assert_eq!(job.normalize_audio, Some(false));
+        assert_eq!(job.strip_metadata, Some(false));
+        assert_eq!(job.hdr, HdrMode::Keep);
+        assert_eq!(job.deinterlace, DeinterlaceMode::Off);
+        assert_eq!(job.idempotency_key, None);
+
+        let long_key = "k".repeat(MAX_KEY_LEN + 1);
+        for (key, expected) in [(" retry-1 ", Some("retry-1")), ("", None), (long_key.as_str(), None)] {
+            let event = EventBuilder::new(
+                DVM_VIDEO_TRANSFORM_REQUEST_KIND,
+                "",
+                vec![
+                    Tag::custom(TagKind::Custom("i".into()), vec!["https://example.com/v.mp4", "url"]),
+                    param("idempotency_key", key),
+                ],
+            )
+            .to_event(&keys)
+            .unwrap();
+            let job = JobContext::from_event(event).unwrap();
+            assert_eq!(job.idempotency_key.as_deref(), expected);
+        }
+    }
+
+    #[test]
+    fn test_clip_params() {
+        let keys = Keys::generate();
+        let param = |name: &str, value: &str| {
+            Tag::custom(
+                TagKind::Custom("param".into()),
+                vec![name.to_string(), value.to_string()],
+            )
+        };
+        let input = Tag::custom(
+            TagKind::Custom("i".into()),
+            vec!["https://example.com/video.mp4".to_string(), "url".to_string()],
+        );
+
+        let tags = vec![input.clone(), param("start", "1:30"), param("duration", "45")];
+        let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+            .to_event(&keys)
+            .unwrap();
+        let job = JobContext::from_event(event).unwrap();
+        assert_eq!(job.clip, Some(Clip { start: 90.0, end: Some(135.0) }));
+
+        let tags = vec![input, param("start", "60"), param("end", "30")];
+        let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+            .to_event(&keys)
+            .unwrap();
+        let job = JobContext::from_event(event).unwrap();
+        assert!(job.clip.is_none());
+        assert!(job.has_param("start"));
+    }
+
+    #[test]
+    fn test_segment_params_default() {
+        let keys = Keys::generate();
+        let tags = vec![Tag::custom(
+            TagKind::Custom("i".into()),
+            vec!["https://example.com/video.mp4".to_string(), "url".to_string()],
+        )];
+        let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+            .to_event(&keys)
+            .unwrap();
+
+        let job = JobContext::from_event(event).unwrap();
+        assert_eq!(job.segment_type, SegmentType::Fmp4);
+        assert_eq!(job.encryption_scheme, None);
+    }
+
+    #[test]
+    fn test_apply_defaults_only_fills_unspecified() {
+        let keys = Keys::generate();
+        let defaults = JobDefaults {
+            hls_resolutions: vec![Resolution::R360p, Resolution::R720p],
+            mp4_resolution: Some(Resolution::R480p),
+            codec: Some(Codec::H265),
+            segment_type: Some(SegmentType::MpegTs),
+            hls_time: Some(4),
+            keyframe_interval: None,
+            max_fps: Some(60),
+        };
+        let job_with = |params: Vec<(&str, &str)>| {
+            let mut tags = vec![Tag::custom(
+                TagKind::Custom("i".into()),
+                vec!["https://example.com/video.mp4".to_string(), "url".to_string()],
+            )];
+            for (name, value) in params {
+                tags.push(Tag::custom(
+                    TagKind::Custom("param".into()),
+                    vec![name.to_string(), value.to_string()],
+                ));
+            }
+            let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+                .to_event(&keys)
+                .unwrap();
+            let mut job = JobContext::from_event(event).unwrap();
+            job.apply_defaults(&defaults);
+            job
+        };
+
+        let job = job_with(vec![]);
+        assert_eq!(job.hls_resolutions, vec![Resolution::R360p, Resolution::R720p]);
+        assert_eq!(job.resolution, Resolution::R480p);
+        assert_eq!(job.codec, Codec::H265);
+        assert_eq!(job.segment_type, SegmentType::MpegTs);
+        assert_eq!(job.segmenting().unwrap().hls_time, 4);
+        assert_eq!(job.max_fps, Some(60));
+
+        let job = job_with(vec![("resolutions", "240p,original"), ("resolution", "720p"), ("codec", "h264")]);
+        assert_eq!(job.hls_resolutions, vec![Resolution::R240p, Resolution::Original]);
+        assert_eq!(job.resolution, Resolution::R720p);
+        assert_eq!(job.codec, Codec::H264);
+
+        // No valid resolution listed
+        let job = job_with(vec![("resolutions", "4k,bogus")]);
+        assert_eq!(job.hls_resolutions, vec![Resolution::R360p, Resolution::R720p]);
+
+        let job = job_with(vec![("segment_type", "fmp4"), ("hls_time", "8")]);
+        assert_eq!(job.segment_type, SegmentType::Fmp4);
+        assert_eq!(job.segmenting().unwrap().hls_time, 8);
+
+        // 0 (or any value out of range) opts out of the operator's cap
+        assert_eq!(job_with(vec![("max_fps", "30")]).max_fps, Some(30));
+        assert_eq!(job_with(vec![("max_fps", "0")]).max_fps, None);
+    }
+
+    #[test]
+    fn test_segmenting_params() {
+        let keys = Keys::generate();
+        let job_with = |params: &[(&str, &str)]| {
+            let mut tags = vec![Tag::custom(
+                TagKind::Custom("i".into()),
+                vec!["https://example.com/video.mp4".to_string(), "url".to_string()],
+            )];
+            for (name, value) in params {
+                tags.push(Tag::custom(
+                    TagKind::Custom("param".into()),
+                    vec![name.to_string(), value.to_string()],
+                ));
+            }
+            let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+                .to_event(&keys)
+                .unwrap();
+            JobContext::from_event(event).unwrap()
+        };
+
+        assert_eq!(job_with(&[]).segmenting(), Ok(Segmenting::default()));
+        let segmenting = job_with(&[("hls_time", "4"), ("keyframe_interval", "0.5")])
+            .segmenting()
+            .unwrap();
+        assert_eq!(segmenting.hls_time, 4);
+        assert_eq!(segmenting.keyframe_interval, 0.5);
+
+        assert!(job_with(&[("hls_time", "30")]).segmenting().is_err());
+        assert!(job_with(&[("hls_time", "six")]).segmenting().is_err());
+        assert!(job_with(&[("keyframe_interval", "4")]).segmenting().is_err());
+    }
+
+    #[test]
+    fn test_device_codec() {
+        assert_eq!(Device::parse("iOS"), Some(Device::Ios));
+        assert_eq!(Device::parse("fridge"), None);
+
+        let all = |_: Codec| true;
+        let none = |_: Codec| false;
+        assert_eq!(Device::Ios.codec(OutputMode::Mp4, false, all), Codec::H265);
+        assert_eq!(Device::Ios.codec(OutputMode::Hls, false, all), Codec::H265);
+        assert_eq!(Device::Web.codec(OutputMode::Mp4, false, all), Codec::AV1);
+        assert_eq!(Device::Web.codec(OutputMode::Mp4, false, none), Codec::H264);
+        assert_eq!(Device::Tv.codec(OutputMode::Mp4, false, all), Codec::H264);
+        assert_eq!(Device::Android.codec(OutputMode::Hls, false, all), Codec::H264);
+        // Encrypted HLS is written as TS segments
+        assert_eq!(Device::Ios.codec(OutputMode::Hls, true, all), Codec::H264);
+        assert_eq!(Device::Web.codec(OutputMode::Hls, true, all), Codec::H264);
+    }
+
+    #[test]
+    fn test_partial_event() {
+        let keys = Keys::generate();
+        let requester = Keys::generate().public_key();
+        let partial = PartialResult {
+            master_playlist: "https://blossom.example/abc.m3u8".to_string(),
+            resolution: "240p".to_string(),
+        };
+
+        let event = build_partial_event(
+            EventId::all_zeros(),
+            requester,
+            &partial,
+            "240p is ready to watch",
+            "preview-ready",
+            None,
+            EncryptionType::None,
+        )
+        .to_event(&keys)
+        .unwrap();
+        let tag = |name: &str| {
+            event
+                .tags
+                .iter()
+                .find(|t| t.as_slice().first().map(|s| s.as_str()) == Some(name))
+                .and_then(|t| t.as_slice().get(1).cloned())
+        };
+        assert_eq!(tag("status").as_deref(), Some("partial"));
+        assert_eq!(tag("code").as_deref(), Some("preview-ready"));
+        assert_eq!(tag("content").as_deref(), Some("240p is ready to watch"));
+        let content: PartialResult = serde_json::from_str(&event.content).unwrap();
+        assert_eq!(content, partial);
+    }
+
+    #[test]
+    fn test_decline_event_tags() {
+        let keys = Keys::generate();
+        let requester = Keys::generate().public_key();
+        let job_id = EventId::all_zeros();
+
+        let event = build_decline_event(
+            job_id,
+            requester,
+            DeclineReason::UnsupportedCodec,
+            "AV1 encoding is not available on this DVM",
+            None,
+            EncryptionType::None,
+        )
+        .to_event(&keys)
+        .unwrap();
+
+        let tag = |name: &str| {
+            event
+                .tags
+                .iter()
+                .find(|t| t.as_slice().first().map(|s| s.as_str()) == Some(name))
+                .and_then(|t| t.as_slice().get(1).cloned())
+        };
+        assert_eq!(event.kind, DVM_STATUS_KIND);
+        assert_eq!(tag("status").as_deref(), Some("error"));
+        assert_eq!(tag("reason").as_deref(), Some("unsupported-codec"));
+        assert_eq!(event.content, "AV1 encoding is not available on this DVM");
+
+        // Encrypted declines keep the reason out of the public tags
+        let event = build_decline_event(
+            job_id,
+            requester,
+            DeclineReason::InputTooLarge,
+            "too big",
+            Some(&keys),
+            EncryptionType::Nip04,
+        )
+        .to_event(&keys)
+        .unwrap();
+        assert!(!event.tags.iter().any(|t| t.as_slice()[0] == "reason"));
+    }
+
+    #[test]
+    fn test_rate_limited_event_tags() {
+        let keys = Keys::generate();
+        let event = build_rate_limited_event(
+            EventId::all_zeros(),
+            Keys::generate().public_key(),
+            600,
+            "try again later",
+            None,
+            EncryptionType::None,
+        )
+        .to_event(&keys)
+        .unwrap();
+
+        let tag = |name: &str| {
+            event
+                .tags
+                .iter()
+                .find(|t| t.as_slice().first().map(|s| s.as_str()) == Some(name))
+                .and_then(|t| t.as_slice().get(1).cloned())
+        };
+        assert_eq!(tag("status").as_deref(), Some("error"));
+        assert_eq!(tag("code").as_deref(), Some("rate-limited"));
+        assert_eq!(tag("retry_after").as_deref(), Some("600"));
+    }
+
     #[test]
     fn test_resolution_height() {
         assert_eq!(Resolution::R240p.height(), Some(240));
@@ -873,5 +2497,84 @@ mod tests {
         assert_eq!(Codec::from_encoder("h264_vaapi"), Codec::H264);
         assert_eq!(Codec::from_encoder("h264_nvenc"), Codec::H264);
         assert_eq!(Codec::from_encoder("libx264"), Codec::H264);
+        assert_eq!(Codec::from_encoder("libvpx-vp9"), Codec::Vp9);
+        assert_eq!(Codec::from_encoder("vp9_qsv"), Codec::Vp9);
+    }
+
+    #[test]
+    fn test_lang_param_and_status_code() {
+        let keys = Keys::generate();
+        let tags = vec![
+            Tag::custom(
+                TagKind::Custom("i".into()),
+                vec!["https://example.com/video.mp4".to_string(), "url".to_string()],
+            ),
+            Tag::custom(
+                TagKind::Custom("param".into()),
+                vec!["lang".to_string(), "de-AT".to_string()],
+            ),
+        ];
+        let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+            .to_event(&keys)
+            .unwrap();
+        let job = JobContext::from_event(event).unwrap();
+        assert_eq!(job.lang, Lang::De);
+
+        let status = build_status_event_with_context(
+            job.event_id(),
+            job.requester(),
+            JobStatus::Processing,
+            Some("Auftrag angenommen, Eingabe wird geprüft..."),
+            None,
+            None,
+            None,
+            None,
+            EncryptionType::None,
+            Some("job-accepted"),
+        )
+        .to_event(&keys)
+        .unwrap();
+        let code = status
+            .tags
+            .iter()
+            .map(|t| t.as_slice())
+            .find(|t| t[0] == "code")
+            .unwrap();
+        assert_eq!(code[1], "job-accepted");
+    }
+
+    #[test]
+    fn test_payment_required_amount_tag() {
+        let keys = Keys::generate();
+        let amount_tag = |bolt11: Option<&str>| {
+            let payment = PaymentContext {
+                mint: "https://mint.example.com".to_string(),
+                amount_sats: 21,
+                bolt11: bolt11.map(str::to_string),
+            };
+            let event = build_status_event_with_context(
+                EventId::all_zeros(),
+                keys.public_key(),
+                JobStatus::PaymentRequired,
+                None,
+                None,
+                None,
+                Some(payment),
+                None,
+                EncryptionType::None,
+                None,
+            )
+            .to_event(&keys)
+            .unwrap();
+            event
+                .tags
+                .iter()
+                .map(|t| t.as_slice().to_vec())
+                .find(|t| t[0] == "amount")
+                .unwrap()
+        };
+
+        assert_eq!(amount_tag(None), vec!["amount", "21"]);
+        assert_eq!(amount_tag(Some("lnbc210n1test")), vec!["amount", "21000", "lnbc210n1test"]);
     }
 }
@@ -1,33 +1,66 @@
 use nostr_sdk::prelude::*;
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
-use tokio::sync::{mpsc, Semaphore};
+use tokio::sync::{mpsc, oneshot};
 use tokio::time::{interval, Duration};
 use tracing::{debug, error, info, warn};
 
-use crate::blossom::BlossomClient;
+use crate::blossom::{BlossomClient, Retention, RetentionTerms};
 use crate::config::Config;
-use crate::dvm_state::SharedDvmState;
+use crate::dvm::heartbeat::{Heartbeat, HEARTBEAT_INTERVAL};
+use crate::dvm::hooks::{HookContext, HookRegistry, HookStage, JobHook};
+use crate::dvm::idempotency::Claim;
+use crate::dvm::input_cache::{download_verified, InputCache, INPUT_FILE_NAME};
+use crate::dvm::messages::StatusMessage;
+use crate::dvm::progress::JobProgress;
+use crate::dvm::updates::{JobUpdate, JobUpdates};
+use crate::dvm::creator;
+use crate::dvm::encode_plan::EncodePlan;
+use crate::dvm::event_input;
+use crate::dvm::queue::Lanes;
+use crate::dvm::receipt::{PaymentMethod, Receipt, ReceiptLedger};
+use crate::dvm::slots::JobSlots;
+use crate::dvm::summary::{build_summary_event, input_hash, TranscodeSummary};
+use crate::dvm_state::{ConfigWatch, OpenInvoice, SharedDvmState};
 use crate::dvm::events::{
     build_result_event_encrypted, build_status_event_with_eta_encrypted, build_status_event_with_context,
-    build_status_event_with_phase,
-    Codec, DvmResult, JobContext, JobStatus, Mp4Result, OutputMode, CashuContext, Resolution,
-    ProgressPhase,
+    build_status_event_with_phase, build_decline_event, build_partial_event, build_rate_limited_event,
+    Codec, DeclineReason, DvmInput, DvmResult, JobContext, JobStatus, Mp4Result, OutputMode, PaymentContext, Resolution,
+    ArchivedSource, ChaptersTrack, PartialResult, Preview, ProgressPhase, StoryboardTrack, Thumbnail,
+    ThumbnailResult,
 };
-use crate::error::DvmError;
+use crate::error::{DvmError, VideoError};
+use crate::fetcher::FetcherRegistry;
 use crate::nostr::EventPublisher;
-use crate::video::{TransformResult, VideoMetadata, VideoProcessor};
-use cdk::nuts::Token;
-use cdk::amount::Amount;
-use std::str::FromStr;
-
-/// Default Cashu mint URL for payment requests
-const CASHU_MINT_URL: &str = "https://mint.bitonic.nl";
-
-/// DVM cost in satoshis (0 = free)
-const DVM_COST_SATS: u64 = 0;
+use crate::payments::cashu::{self, CashuWallet, CASHU_MINT_URL};
+use crate::payments::{price_for_duration, price_for_output, Invoice, LightningBackend, PAYMENT_POLL_INTERVAL};
+use crate::remote_config::RemoteConfig;
+use crate::util::local_path::{is_file_url, resolve_file_url, resolve_local_input};
+use crate::util::remote_url::is_public_http_url;
+use crate::util::resources::ResourceSnapshot;
+use crate::util::sealed::{self, is_sealed_url, SealKey, SealedInput, SealedInputs};
+use crate::util::{zip, TempDir};
+use crate::web::watch::{watch_url, RESULT_WATCH_TTL_SECS};
+use crate::video::av_sync::{AvDrift, AvSyncAction};
+use crate::video::chapters::{self, output_chapters, Chapter};
+use crate::video::clip::Clip;
+use crate::video::ffmpeg_error::FfmpegErrorKind;
+use crate::video::loudness::{LoudnessMeasurement, Loudnorm};
+use crate::video::output_size::estimate_output_bytes;
+use crate::video::memory::{estimate_memory_bytes, MemoryBudget, MemoryReservation};
+use crate::video::scratch::{estimate_scratch_bytes, ScratchBudget, ScratchReservation};
+use crate::video::slideshow::Slideshow;
+use crate::video::storyboard::Storyboard;
+use crate::video::deinterlace::{Deinterlace, IdetCounts};
+use crate::video::transform::{evenly_spaced_timestamps, EncryptionScheme, PreviewFormat};
+use crate::video::{HwAccel, TransformResult, VideoMetadata, VideoProcessor};
+
+/// Quality steps tried when an output would exceed the size limit (12 CRF
+/// steps cut the estimated video bitrate to a quarter)
+const OUTPUT_CRF_STEP: u32 = 3;
+const MAX_OUTPUT_CRF_OFFSET: u32 = 12;
 
 /// Tracks upload progress and dynamically estimates remaining time
 #[derive(Debug)]
@@ -90,6 +123,26 @@ pub struct JobHandler {
     blossom: Arc<BlossomClient>,
     processor: Arc<VideoProcessor>,
     http: reqwest::Client,
+    fetchers: FetcherRegistry,
+    hooks: HookRegistry,
+    scratch: Arc<ScratchBudget>,
+    memory: Arc<MemoryBudget>,
+    heartbeat: Heartbeat,
+    updates: JobUpdates,
+    /// Issues invoices for paid jobs (None = Cashu only)
+    lightning: Option<Arc<dyn LightningBackend>>,
+    /// Queues jobs again once their invoice is paid
+    requeue: Option<mpsc::WeakSender<JobContext>>,
+    /// Redeems Cashu tokens at the mint (None = check mint and amount only)
+    cashu: Option<Arc<CashuWallet>>,
+    /// Serves sealed inputs to FFmpeg (None = scratch encryption unavailable)
+    sealed: Option<Arc<SealedInputs>>,
+    /// Keeps the operator's copy of receipts (None = only sent to requesters)
+    receipts: Option<ReceiptLedger>,
+    /// Keeps pre-downloaded inputs for later jobs (None = download every time)
+    input_cache: Option<Arc<InputCache>>,
+    /// Signals `max_concurrent_jobs` changes
+    config_watch: ConfigWatch,
 }
 
 impl JobHandler {
@@ -100,32 +153,202 @@ impl JobHandler {
         blossom: Arc<BlossomClient>,
         processor: Arc<VideoProcessor>,
     ) -> Self {
+        let scratch = Arc::new(ScratchBudget::for_temp_dir(
+            &config.temp_dir,
+            config.temp_disk_quota_bytes,
+        ));
+        let memory = Arc::new(MemoryBudget::for_host(config.memory_budget_bytes));
+        let http = crate::util::proxy::http_client();
+        let lightning = config.lightning.as_ref().map(|l| l.backend(http.clone()));
         Self {
             config,
             state,
             publisher,
             blossom,
             processor,
-            http: reqwest::Client::new(),
+            http,
+            fetchers: FetcherRegistry::new(),
+            hooks: HookRegistry::new(),
+            scratch,
+            memory,
+            heartbeat: Heartbeat::default(),
+            updates: JobUpdates::default(),
+            lightning,
+            requeue: None,
+            cashu: None,
+            sealed: None,
+            receipts: None,
+            input_cache: None,
+            config_watch: ConfigWatch::new(),
+        }
+    }
+
+    /// Serve inputs sealed under `encrypt_scratch` through `inputs`.
+    ///
+    /// Without it jobs that would keep an input on disk fail while the
+    /// operator has scratch encryption turned on.
+    pub fn with_sealed_inputs(mut self, inputs: Arc<SealedInputs>) -> Self {
+        self.sealed = Some(inputs);
+        self
+    }
+
+    /// Redeem Cashu payment tokens into `wallet` before starting work.
+    ///
+    /// Without it tokens are only checked for mint and amount, so spent
+    /// proofs are accepted.
+    pub fn with_cashu_wallet(mut self, wallet: Arc<CashuWallet>) -> Self {
+        self.cashu = Some(wallet);
+        self
+    }
+
+    /// Record the receipts sent for paid jobs in `ledger`.
+    pub fn with_receipts(mut self, ledger: ReceiptLedger) -> Self {
+        self.receipts = Some(ledger);
+        self
+    }
+
+    /// Run `hook` at job milestones, after any configured in the environment.
+    pub fn with_hook(mut self, hook: Box<dyn JobHook>) -> Self {
+        self.hooks.register(hook);
+        self
+    }
+
+    /// Reuse pre-downloaded inputs kept in `cache`.
+    pub fn with_input_cache(mut self, cache: Arc<InputCache>) -> Self {
+        self.input_cache = Some(cache);
+        self
+    }
+
+    /// Queue jobs whose Lightning invoice was paid back into `jobs`.
+    ///
+    /// Without it invoices are still issued, but paying them starts nothing.
+    pub fn with_requeue(mut self, jobs: mpsc::WeakSender<JobContext>) -> Self {
+        self.requeue = Some(jobs);
+        self
+    }
+
+    /// Publish job lifecycle and progress changes to `updates`
+    pub fn with_updates(mut self, updates: JobUpdates) -> Self {
+        self.updates = updates;
+        self
+    }
+
+    /// Follow changes to `max_concurrent_jobs` signalled through `watch`
+    pub fn with_config_watch(mut self, watch: ConfigWatch) -> Self {
+        self.config_watch = watch;
+        self
+    }
+
+    /// Record the processing loop's liveness in `heartbeat`
+    pub fn with_heartbeat(mut self, heartbeat: Heartbeat) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    /// Record that the processing loop is alive, with the jobs it is running
+    async fn beat(&self, queued: usize) {
+        let active_jobs = self.state.read().await.cancel_signals.keys().cloned().collect();
+        self.heartbeat.beat(active_jobs, queued).await;
+    }
+
+    async fn max_concurrent_jobs(&self) -> usize {
+        self.state.read().await.config.max_concurrent_jobs.max(1) as usize
+    }
+
+    /// Apply a changed `max_concurrent_jobs` to the running loop
+    async fn resize_slots(&self, slots: &JobSlots) {
+        let limit = self.max_concurrent_jobs().await;
+        if limit != slots.limit() {
+            info!(from = slots.limit(), to = limit, "Concurrent job limit changed");
+            slots.resize(limit);
         }
     }
 
+    /// Publish how many jobs are waiting, for the admin status
+    async fn record_queue(&self, queued: usize, channel_depth: usize) {
+        let mut state = self.state.write().await;
+        state.jobs_queued = queued as u32;
+        state.channel_depth = channel_depth as u32;
+    }
+
     /// Process incoming jobs from the channel with configurable concurrency.
     ///
-    /// Uses a semaphore to limit parallel job execution. The limit is read
-    /// from `RemoteConfig::max_concurrent_jobs` (default: 1 for sequential).
-    pub async fn run(self: Arc<Self>, mut rx: mpsc::Receiver<JobContext>) {
-        // Read initial concurrency limit from config
-        let max_jobs = {
-            let state = self.state.read().await;
-            state.config.max_concurrent_jobs.max(1)
-        };
-        let semaphore = Arc::new(Semaphore::new(max_jobs as usize));
-        info!(max_concurrent_jobs = max_jobs, "Job handler started");
+    /// The number of jobs run at once is `RemoteConfig::max_concurrent_jobs`
+    /// (default: 1 for sequential), re-read whenever the config changes so
+    /// the operator can resize it without a restart.
+    /// Jobs waiting for a slot are queued per requester and started according
+    /// to `RemoteConfig::queue_policy` (round-robin by default), with
+    /// creators re-transcoding their own videos first when
+    /// `RemoteConfig::creator_priority` is on.
+    ///
+    /// Once every sender is gone no further jobs are started, and the jobs
+    /// still waiting are returned in the order they would have run.
+    pub async fn run(self: Arc<Self>, mut rx: mpsc::Receiver<JobContext>) -> Vec<JobContext> {
+        let mut config_changed = self.config_watch.subscribe();
+        let slots = JobSlots::new(self.max_concurrent_jobs().await);
+        info!(max_concurrent_jobs = slots.limit(), "Job handler started");
+
+        let mut queue: Lanes<PublicKey, JobContext> = Lanes::new();
+        // Beats while idle and while waiting for a slot, so a stuck loop shows
+        let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+        loop {
+            self.record_queue(queue.len(), rx.len()).await;
+            if queue.is_empty() {
+                tokio::select! {
+                    job = rx.recv() => match job {
+                        Some(job) => {
+                            let waiting = slots.available() == 0;
+                            self.admit(job, &mut queue, waiting).await;
+                        }
+                        None => break,
+                    },
+                    Ok(()) = config_changed.changed() => {
+                        self.resize_slots(&slots).await;
+                        continue;
+                    }
+                    _ = heartbeat.tick() => {
+                        self.beat(queue.len()).await;
+                        continue;
+                    }
+                }
+            }
 
-        while let Some(job) = rx.recv().await {
-            // Acquire a semaphore permit before processing
-            let permit = semaphore.clone().acquire_owned().await.unwrap();
+            // Keep accepting requests while every slot is busy, so the policy
+            // sees everyone waiting when a slot frees up
+            let permit = tokio::select! {
+                permit = slots.acquire() => permit,
+                job = rx.recv() => {
+                    match job {
+                        Some(job) => {
+                            let waiting = slots.available() == 0;
+                            self.admit(job, &mut queue, waiting).await;
+                            continue;
+                        }
+                        None => break,
+                    }
+                }
+                Ok(()) = config_changed.changed() => {
+                    self.resize_slots(&slots).await;
+                    continue;
+                }
+                _ = heartbeat.tick() => {
+                    self.beat(queue.len()).await;
+                    continue;
+                }
+            };
+            while let Ok(job) = rx.try_recv() {
+                self.admit(job, &mut queue, false).await;
+            }
+            // Shutting down; what's left is kept for the snapshot
+            if rx.is_closed() && rx.is_empty() {
+                break;
+            }
+
+            let policy = self.state.read().await.config.queue_policy;
+            let Some(job) = queue.pop(policy) else {
+                continue;
+            };
+            debug!(waiting = queue.len(), policy = policy.as_str(), "Starting next queued job");
 
             let handler = self.clone();
             tokio::spawn(async move {
@@ -133,19 +356,52 @@ impl JobHandler {
                 let input_url = job.input.value.clone();
                 info!(job_id = %job_id, "Processing job");
 
-                // Track job start in state
-                handler.state.write().await.job_started(
-                    job_id.to_string(),
-                    input_url,
-                );
-
-                match handler.handle_job(job).await {
-                    Ok(()) => {
-                        // Job completed successfully (result URL already sent in handle_job)
+                // Track job start in state, with a signal the admin can cancel it through
+                let (cancel_tx, cancel_rx) = oneshot::channel();
+                {
+                    let mut state = handler.state.write().await;
+                    state.job_started(job_id.to_string(), input_url, job.requester().to_hex());
+                    state.cancel_signals.insert(job_id.to_string(), cancel_tx);
+                    state.attach_log(&job_id.to_string(), job.log.clone());
+                }
+                job.log.event(format!("Job started: {} from {}", job.mode.as_str(), job.input.value));
+                handler.updates.publish(JobUpdate::Started {
+                    job_id: job_id.to_hex(),
+                    mode: job.mode.as_str().to_string(),
+                });
+
+                let outcome = tokio::select! {
+                    result = handler.handle_job(job.clone()) => match result {
+                        Ok(()) => {
+                            // Job completed successfully (result URL already sent in handle_job)
+                            job.log.event("Job completed");
+                            "completed"
+                        }
+                        Err(e) => {
+                            error!(job_id = %job_id, error = %e, "Job failed");
+                            job.log.event(format!("Job failed: {}", e));
+                            handler.record_failure(&job_id.to_string()).await;
+                            "failed"
+                        }
+                    },
+                    // Dropping the job future kills its FFmpeg process and removes its temp files
+                    Ok(()) = cancel_rx => {
+                        warn!(job_id = %job_id, "Job cancelled by admin");
+                        job.log.event("Job cancelled by admin");
+                        let _ = handler.send_error(&job, &StatusMessage::JobCancelled.text(job.lang)).await;
+                        "cancelled"
                     }
-                    Err(e) => {
-                        error!(job_id = %job_id, error = %e, "Job failed");
-                        handler.state.write().await.job_failed(&job_id.to_string());
+                };
+                handler.updates.publish(JobUpdate::Finished {
+                    job_id: job_id.to_hex(),
+                    outcome: outcome.to_string(),
+                });
+                {
+                    let mut state = handler.state.write().await;
+                    state.cancel_signals.remove(&job_id.to_string());
+                    // A failed job doesn't hold its idempotency key, so a retry runs
+                    if let (Some(key), true) = (&job.idempotency_key, outcome != "completed") {
+                        state.idempotency.release(job.requester(), key, job_id);
                     }
                 }
 
@@ -153,10 +409,16 @@ impl JobHandler {
             });
         }
 
+        let policy = self.state.read().await.config.queue_policy;
+        let remaining: Vec<_> = std::iter::from_fn(|| queue.pop(policy)).collect();
+        if !remaining.is_empty() {
+            info!(queued = remaining.len(), "Job handler stopped with jobs still queued");
+        }
         info!("Job handler stopped");
+        remaining
     }
 
-    async fn handle_job(&self, job: JobContext) -> Result<(), DvmError> {
+    async fn handle_job(&self, mut job: JobContext) -> Result<(), DvmError> {
         let job_id = job.event_id();
         let requester = job.requester();
         let my_pubkey = self.config.nostr_keys.public_key();
@@ -168,11 +430,22 @@ impl JobHandler {
             return Ok(()); // Silently ignore requests when paused in Bid/Select mode
         }
 
+        let defaults = self.state.read().await.config.job_defaults();
+        job.apply_defaults(&defaults);
+
+        // A device hint picks the codec unless the requester chose one
+        if let Some(device) = job.apply_device(self.processor.hwaccel()) {
+            debug!(job_id = %job_id, device = device.as_str(), codec = job.codec.as_str(), "Codec picked for device");
+        }
+        job.log.event(format!(
+            "Settings: codec {}, resolution {}, encryption {}",
+            job.codec.as_str(),
+            job.resolution.as_str(),
+            job.encryption
+        ));
+
         // Determine if this request is specifically for us
-        let is_for_us = job.approved || job.request.tags.iter().any(|t| {
-            let parts = t.as_slice();
-            parts.len() >= 2 && parts[0] == "p" && parts[1] == my_pubkey.to_hex()
-        });
+        let is_for_us = self.is_for_us(&job);
 
         // Determine if it's addressed to someone else
         let is_for_others = job.request.tags.iter().any(|t| {
@@ -180,12 +453,46 @@ impl JobHandler {
             parts.len() >= 2 && parts[0] == "p" && parts[1] != my_pubkey.to_hex()
         });
 
+        // Blocked requesters are ignored outright; outside an allowlist only
+        // requests addressed to us get told why we won't take them
+        let (blocked, allowed) = {
+            let state = self.state.read().await;
+            (state.config.is_blocked(&requester), state.config.is_allowed(&requester))
+        };
+        if blocked || (!allowed && !is_for_us) {
+            debug!(job_id = %job_id, requester = %requester, blocked, "Ignoring request from pubkey outside access lists");
+            return Ok(());
+        }
+        if !allowed {
+            info!(job_id = %job_id, requester = %requester, "Declining request from pubkey not on the allowlist");
+            return self
+                .send_decline(&job, DeclineReason::NotAllowed, "This DVM only serves an allowlist of users")
+                .await;
+        }
+
+        // Jobs count against the hourly limit once addressed to us; the admin
+        // and jobs whose invoice was paid (counted when it was issued) are exempt
+        let rate_limit = {
+            let state = self.state.read().await;
+            let exempt = job.paid_sats.is_some() || state.config.admin_pubkey() == Some(requester);
+            if exempt { 0 } else { state.config.max_jobs_per_hour }
+        };
+        let now = Timestamp::now().as_u64();
+
         if !is_for_us {
             if is_for_others {
                 // Addressed to someone else, ignore
                 return Ok(());
             }
 
+            // Don't bid on a job we'd turn away once selected
+            if self.state.read().await.rate_limits.check(&requester, now, rate_limit).is_err() {
+                debug!(job_id = %job_id, requester = %requester, "Not bidding, requester is over the hourly job limit");
+                return Ok(());
+            }
+
+            // Quote the referenced video, not the event
+            self.resolve_event_input(&mut job).await?;
             return self.send_public_bid(job).await;
         }
 
@@ -193,29 +500,87 @@ impl JobHandler {
         
         // Remove from pending bids if it was there (we are starting it now)
         self.state.write().await.take_bid(&job_id);
+
+        // Retries of a request already taken reuse the original job
+        if let Some(key) = &job.idempotency_key {
+            let claim = self.state.write().await.idempotency.claim(requester, key, job_id, now);
+            match claim {
+                Claim::New => {}
+                Claim::Running(original) => {
+                    info!(job_id = %job_id, original = %original, "Duplicate of a running job");
+                    let original = original.to_hex();
+                    let msg = StatusMessage::DuplicateRequest { job_id: &original };
+                    return self.send_status(&job, JobStatus::Processing, msg).await;
+                }
+                Claim::Completed { job_id: original, result, expires_at } => {
+                    info!(job_id = %job_id, original = %original, "Duplicate of a completed job, resending its result");
+                    return self.resend_result(&job, &result, expires_at).await;
+                }
+            }
+        }
+
+        let acquired = self.state.write().await.rate_limits.try_acquire(requester, now, rate_limit);
+        if let Err(retry_after) = acquired {
+            if let Some(key) = &job.idempotency_key {
+                self.state.write().await.idempotency.release(requester, key, job_id);
+            }
+            info!(job_id = %job_id, requester = %requester, retry_after, "Requester is over the hourly job limit");
+            return self.send_rate_limited(&job, retry_after).await;
+        }
         
-        // Define DVM cost
-        let dvm_cost_sats = DVM_COST_SATS;
-        let mint_url = CASHU_MINT_URL;
+        self.resolve_event_input(&mut job).await?;
 
-        if dvm_cost_sats > 0 {
+        if job.creator.is_none() && job.source_event.is_some() {
+            let discount = self.state.read().await.config.creator_discount_percent;
+            if discount > 0 {
+                job.creator = Some(self.check_creator(&job).await);
+            }
+        }
+
+        let retention = self.retention_terms(&job).await?;
+
+        // Define DVM cost, including any paid retention beyond the default window
+        let dvm_cost_sats = self.price_sats(&job, retention.extra_sats).await;
+
+        // What the requester paid, for their receipt
+        let mut payment = job.paid_sats.map(|sats| (PaymentMethod::Lightning, sats));
+        if dvm_cost_sats > 0 && payment.is_none() {
             match job.cashu_token {
                 Some(ref token_str) => {
                     info!(job_id = %job_id, "Verifying Cashu token...");
-                    if let Err(e) = self.verify_cashu_token(token_str, dvm_cost_sats, mint_url).await {
+                    if let Err(e) = cashu::check_token(token_str, dvm_cost_sats, CASHU_MINT_URL) {
                         warn!(job_id = %job_id, error = %e, "Cashu token verification failed");
                         return self.send_error(&job, &format!("Payment verification failed: {}", e)).await;
                     }
+                    let mut received = dvm_cost_sats;
+                    if let Some(wallet) = &self.cashu {
+                        match wallet.redeem(token_str, &job_id.to_hex()).await {
+                            Ok(redemption) => {
+                                info!(
+                                    job_id = %job_id,
+                                    sats = redemption.amount_sats,
+                                    fee_sats = redemption.fee_sats,
+                                    "Cashu token redeemed"
+                                );
+                                received = redemption.amount_sats;
+                                self.state
+                                    .write()
+                                    .await
+                                    .set_redemption(&job_id.to_string(), redemption);
+                            }
+                            Err(e) => {
+                                warn!(job_id = %job_id, error = %e, "Cashu token redemption failed");
+                                return self.send_error(&job, &format!("Payment redemption failed: {}", e)).await;
+                            }
+                        }
+                        wallet.spawn_melt();
+                    }
                     info!(job_id = %job_id, "Cashu token verified successfully");
+                    payment = Some((PaymentMethod::Cashu, received));
                 }
                 None => {
-                    warn!(job_id = %job_id, "Payment required but no Cashu token provided");
-                    return self.send_cashu_bid(
-                        &job,
-                        mint_url,
-                        dvm_cost_sats,
-                        Some("Payment required to start this job"),
-                    ).await;
+                    info!(job_id = %job_id, sats = dvm_cost_sats, "Payment required, requesting it");
+                    return self.request_payment(job, dvm_cost_sats).await;
                 }
             }
         }
@@ -231,50 +596,131 @@ impl JobHandler {
         self.send_status(
             &job,
             JobStatus::Processing,
-            Some("Job accepted, validating input..."),
+            StatusMessage::JobAccepted,
         )
         .await?;
 
-        self.validate_input(&job).await?;
+        self.validate_params(&job).await?;
+
+        // Receipts and summaries name the source as requested, not the local copy below
+        let requested_input = job.input.clone();
+        let source_hash = input_hash(&requested_input.value);
+        // Downloaded inputs are checked against it before they're used
+        let expected_sha256 = job.expected_input_sha256();
+
+        // External platform URLs are downloaded first; the temp dir must
+        // outlive processing, so keep it bound until the job finishes.
+        let fetched = match self.fetch_external_input(&job).await? {
+            Some(fetched) => Some(fetched),
+            None => match self.assemble_archive_input(&job).await? {
+                Some(fetched) => Some(fetched),
+                None => self.predownload_input(&job).await?,
+            },
+        };
+        let verified_sha256 = fetched.as_ref().and(expected_sha256);
+        let (_fetched_input, _sealed_input) = match fetched {
+            Some((dir, path)) => {
+                job.input.value = path.to_string_lossy().to_string();
+                // The download counts towards the job's overall progress
+                job.progress = Arc::new(JobProgress::new(true));
+                job.progress.report(ProgressPhase::Downloading, 100);
+                let size = tokio::fs::metadata(&path).await.ok().map(|m| m.len());
+                self.check_input_limits(&job, size).await?;
+                let sealed = self.seal_input(&job, &path).await?;
+                if let Some(sealed) = &sealed {
+                    job.input.value = sealed.url().to_string();
+                }
+                (Some(dir), sealed)
+            }
+            None => {
+                self.validate_input(&mut job).await?;
+                (None, None)
+            }
+        };
 
         // Send processing status
         self.send_status(
             &job,
             JobStatus::Processing,
-            Some("Starting video transformation"),
+            StatusMessage::TransformStarted,
         )
         .await?;
 
         // Process the video
-        let result = self.process_video(&job).await;
+        let result = match self.process_video(&job, &requested_input).await {
+            Ok(result) if self.hooks.wants(HookStage::PrePublish) => {
+                let ctx = HookContext::new(HookStage::PrePublish, &job).with_result(&result);
+                self.hooks.run(&ctx).await.map(|()| result).map_err(DvmError::from)
+            }
+            result => result,
+        };
 
         match result {
-            Ok(dvm_result) => {
+            Ok(mut dvm_result) => {
                 info!(job_id = %job_id, result = ?dvm_result, "Job completed successfully");
+                if let Some(sha256) = verified_sha256 {
+                    dvm_result.set_input_sha256(sha256);
+                }
 
                 // Extract output URL for state tracking
                 let output_url = match &dvm_result {
                     DvmResult::Hls(hls) => hls.master_playlist.clone(),
                     DvmResult::Mp4(mp4) => mp4.urls.first().cloned().unwrap_or_default(),
+                    DvmResult::Thumbnail(thumbs) => thumbs
+                        .thumbnails
+                        .first()
+                        .and_then(|t| t.urls.first().cloned())
+                        .unwrap_or_default(),
                 };
 
+                // Blobs are kept for the agreed retention; the result expires with them
+                let kept = Retention::new(Timestamp::now().as_u64(), retention.days);
+
+                // Link a preview page, valid no longer than the blobs are kept
+                if let (Some(public_url), true) = (&self.config.public_url, self.config.http_enabled) {
+                    let expires_at = kept.expires_at.min(kept.uploaded_at + RESULT_WATCH_TTL_SECS);
+                    dvm_result.set_watch_url(watch_url(
+                        public_url,
+                        &self.config.nostr_keys,
+                        &job_id.to_string(),
+                        expires_at,
+                    ));
+                }
+
                 // Send result event (encrypted if request was encrypted)
                 let event = build_result_event_encrypted(
                     job_id,
                     requester,
                     &dvm_result,
+                    Timestamp::from(kept.expires_at),
                     self.get_encryption_keys(&job),
                     job.encryption_type,
                 );
-                self.publisher.publish_for_job(event, &job.relays).await?;
+                let result_id = self.publisher.publish_result(event, &job.relays).await?;
+
+                // Announce the transcode for discovery; failure doesn't affect the job
+                if let Some(summary) = build_summary_event(&job, &requested_input, &dvm_result) {
+                    if let Err(e) = self.publisher.publish_for_job(summary, &job.relays).await {
+                        warn!(job_id = %job_id, error = %e, "Failed to publish transcode summary");
+                    }
+                }
 
                 // Send success status
-                self.send_status(
-                    &job,
-                    JobStatus::Success,
-                    Some("Video transformation complete"),
-                )
-                .await?;
+                self.send_complete(&job).await?;
+
+                if let Some((method, amount_sats)) = payment {
+                    let receipt = Receipt {
+                        job_id,
+                        result_id,
+                        requester,
+                        amount_sats,
+                        method,
+                        input_hash: source_hash,
+                        output_hashes: TranscodeSummary::from_result(&job.input.value, &dvm_result)
+                            .output_hashes(),
+                    };
+                    self.send_receipt(&job, receipt).await;
+                }
 
                 // Track job completion and record transcode speed for announcements
                 let wall_secs = job_start.elapsed().as_secs_f64();
@@ -282,8 +728,27 @@ impl JobHandler {
                 {
                     let mut state = self.state.write().await;
                     state.job_completed(&job_id.to_string(), output_url);
+                    if let DvmResult::Hls(hls) = &dvm_result {
+                        state.set_encryption_key(&job_id.to_string(), hls.encryption_key.clone());
+                    }
+                    if let Some(key) = &job.idempotency_key {
+                        let expires_at = Timestamp::from(kept.expires_at);
+                        state.idempotency.complete(requester, key, dvm_result.clone(), expires_at);
+                    }
+                    state.retention.record(dvm_result.blob_hashes(), kept);
+                    if let Err(e) = state.retention.save() {
+                        warn!(job_id = %job_id, error = %e, "Failed to save retention ledger");
+                    }
+                    state.references.record(
+                        &job_id.to_string(),
+                        dvm_result.blob_hashes(),
+                        kept.uploaded_at,
+                    );
+                    if let Err(e) = state.references.save() {
+                        warn!(job_id = %job_id, error = %e, "Failed to save reference index");
+                    }
                     // Record speed if we have meaningful timing (>1s, ignore tiny test jobs)
-                    if wall_secs > 1.0 {
+                    if wall_secs > 1.0 && job.mode != OutputMode::Thumbnail {
                         // Use a placeholder duration; actual duration comes from video metadata.
                         // We emit a rough 1x multiplier here; the real value is computed from
                         // ffmpeg progress data. For now record wall_secs as denominator.
@@ -294,283 +759,1584 @@ impl JobHandler {
             }
             Err(e) => {
                 error!(job_id = %job_id, error = %e, "Video processing failed");
-                self.state.write().await.job_failed(&job_id.to_string());
-                self.send_error(&job, &e.to_string()).await?;
+                let kind = e.ffmpeg_kind();
+                if let Some(kind) = kind {
+                    self.state.write().await.record_ffmpeg_failure(kind);
+                }
+                match kind {
+                    // Unrecognized failures keep FFmpeg's own message
+                    Some(kind) if kind != FfmpegErrorKind::Other => {
+                        self.send_status(&job, JobStatus::Error, StatusMessage::TranscodeFailed { kind })
+                            .await?
+                    }
+                    _ => self.send_error(&job, &e.to_string()).await?,
+                }
+                // `run` records the failure
+                return Err(e);
             }
         }
 
         Ok(())
     }
 
+    /// Note in the job record that the encode finished in software after the
+    /// hardware encoder failed, and tell the requester
+    async fn record_software_fallback(&self, job: &JobContext) -> Result<(), DvmError> {
+        info!(job_id = %job.event_id(), "Job encoded in software after hardware encoder failure");
+        self.state
+            .write()
+            .await
+            .set_software_fallback(&job.event_id().to_string());
+        self.send_status(job, JobStatus::Processing, StatusMessage::SoftwareFallback)
+            .await
+    }
+
+    /// Compare the A/V offsets of an encoded output with those of the source.
+    ///
+    /// Drift over `AV_SYNC_MAX_DRIFT_MS` is logged, and fails the job when
+    /// `AV_SYNC_CHECK=fail`. Outputs ffprobe can't read (e.g. encrypted HLS)
+    /// are skipped.
+    async fn check_av_sync(
+        &self,
+        source: Option<&VideoMetadata>,
+        output: &std::path::Path,
+    ) -> Result<(), VideoError> {
+        let policy = self.config.av_sync;
+        let Some(source) = source.filter(|_| policy.action != AvSyncAction::Off) else {
+            return Ok(());
+        };
+
+        let probed =
+            VideoMetadata::extract(&output.to_string_lossy(), &self.config.ffprobe_path).await;
+        let Some(drift) = probed
+            .as_ref()
+            .ok()
+            .and_then(|out| AvDrift::measure(source, out))
+        else {
+            debug!(output = %output.display(), "No A/V timestamps to compare, skipping sync check");
+            return Ok(());
+        };
+
+        if !policy.exceeded(&drift) {
+            debug!(output = %output.display(), drift = %drift, "A/V sync check passed");
+            return Ok(());
+        }
+
+        warn!(
+            output = %output.display(),
+            drift = %drift,
+            max_drift_ms = policy.max_drift_ms,
+            "Output audio and video are out of sync"
+        );
+        if policy.action == AvSyncAction::Fail {
+            return Err(VideoError::AvSyncDrift {
+                drift_ms: drift.max_ms(),
+                max_ms: policy.max_drift_ms,
+            });
+        }
+        Ok(())
+    }
+
+    /// Mark a job failed, recording host resources at the time of failure
+    async fn record_failure(&self, job_id: &str) {
+        let jobs_active = self.state.read().await.jobs_active;
+        let resources = ResourceSnapshot::capture(&self.config.temp_dir, jobs_active).await;
+        warn!(job_id = %job_id, resources = %resources, "Resources at job failure");
+        self.state.write().await.job_failed(job_id, Some(resources));
+    }
+
     /// Send a bid for a public (non-directed) request
     async fn send_public_bid(&self, job: JobContext) -> Result<(), DvmError> {
         let job_id = job.event_id();
+
+        // Don't bid on work we can't do; say so, so the client can move on
+        if let Some((reason, msg)) = capability_gap(&job, &self.config, self.processor.hwaccel()) {
+            info!(job_id = %job_id, reason = reason.as_str(), "Declining public request: {}", msg);
+            return self.send_decline(&job, reason, &msg).await;
+        }
+
+        let terms = self.state.read().await.config.retention_terms(job.retention_days);
+        let retention = match terms {
+            Ok(terms) => terms,
+            Err(msg) => {
+                info!(job_id = %job_id, "Declining public request: {}", msg);
+                return self.send_decline(&job, DeclineReason::RetentionUnavailable, &msg).await;
+            }
+        };
+
         debug!(job_id = %job_id, "Sending bid for public request");
-        self.send_cashu_bid(
-            &job,
-            CASHU_MINT_URL,
-            DVM_COST_SATS,
-            Some("I can process this video for you"),
-        )
-        .await?;
+        let price = self.price_sats(&job, retention.extra_sats).await;
+        self.send_payment_terms(&job, price, None, StatusMessage::Bid)
+            .await?;
         self.state.write().await.add_bid(job);
         Ok(())
     }
 
-    /// Validate the input URL: type check, scheme check, and HEAD request
-    async fn validate_input(&self, job: &JobContext) -> Result<(), DvmError> {
-        if job.input.input_type != "url" {
-            return self.send_error(job, "Only URL inputs are supported").await;
+    /// Download the input via an external platform fetcher, if one applies.
+    ///
+    /// Returns `None` when the input is not handled by any compiled-in fetcher
+    /// or the operator has not enabled external fetchers.
+    async fn fetch_external_input(
+        &self,
+        job: &JobContext,
+    ) -> Result<Option<(TempDir, std::path::PathBuf)>, DvmError> {
+        if job.input.input_type != "url" || self.fetchers.is_empty() {
+            return Ok(None);
         }
 
-        let input_url = &job.input.value;
-        if !input_url.starts_with("http://") && !input_url.starts_with("https://") {
-            return self
-                .send_error(job, "Only HTTP and HTTPS URLs are supported")
-                .await;
+        let fetcher = match self.fetchers.find(&job.input.value) {
+            Some(f) => f,
+            None => return Ok(None),
+        };
+
+        if !self.state.read().await.config.external_fetchers_enabled {
+            debug!(url = %job.input.value, "External fetchers disabled by operator");
+            return Ok(None);
         }
 
-        match self.http.head(input_url).send().await {
-            Ok(resp) if resp.status().is_success() => {
-                debug!(url = %input_url, "URL is accessible");
+        self.send_status(
+            job,
+            JobStatus::Processing,
+            StatusMessage::FetchingInput,
+        )
+        .await?;
+
+        let dir = match TempDir::new(&self.config.temp_dir).await {
+            Ok(d) => d,
+            Err(e) => {
+                error!(error = %e, "Failed to create temp dir for external input");
+                return self
+                    .send_error(job, "Failed to prepare external input")
+                    .await
+                    .map(|_| None);
             }
-            Ok(resp) => {
-                let err_msg = format!("Input URL returned status {}", resp.status());
-                warn!(url = %input_url, error = %err_msg);
-                return self.send_error(job, &err_msg).await;
+        };
+
+        match fetcher.fetch(&job.input.value, dir.path()).await {
+            Ok(path) => {
+                info!(fetcher = fetcher.name(), path = %path.display(), "External input fetched");
+                if let Err(e) = verify_input(&path, job.expected_input_sha256()).await {
+                    warn!(fetcher = fetcher.name(), error = %e, "External input failed verification");
+                    return self.send_error(job, &e.to_string()).await.map(|_| None);
+                }
+                Ok(Some((dir, path)))
             }
             Err(e) => {
-                let err_msg = format!("Failed to reach input URL: {}", e);
-                warn!(url = %input_url, error = %err_msg);
-                return self.send_error(job, &err_msg).await;
+                warn!(fetcher = fetcher.name(), error = %e, "External fetch failed");
+                self.send_error(job, &e.to_string()).await.map(|_| None)
             }
         }
-
-        Ok(())
     }
 
-    async fn process_video(&self, job: &JobContext) -> Result<DvmResult, DvmError> {
-        let input_url = &job.input.value;
-
-        debug!(url = %input_url, mode = ?job.mode, resolution = ?job.resolution, codec = ?job.codec, "Processing video");
-
-        // Get video metadata for duration estimation
-        let metadata = VideoMetadata::extract(input_url, &self.config.ffprobe_path).await;
-        let video_duration_secs = metadata
-            .as_ref()
-            .ok()
-            .and_then(|m| m.duration_secs())
-            .unwrap_or(0.0);
-
-        if let Err(e) = &metadata {
-            warn!(error = %e, "Failed to get video metadata, progress estimates may be inaccurate");
+    /// Download a URL input before transcoding when the operator enabled
+    /// `PREDOWNLOAD_INPUTS` or its hash has to be verified, or take it from
+    /// the input cache.
+    ///
+    /// Returns `None` for other inputs, which FFmpeg reads directly.
+    async fn predownload_input(
+        &self,
+        job: &JobContext,
+    ) -> Result<Option<(TempDir, std::path::PathBuf)>, DvmError> {
+        let expected = job.expected_input_sha256();
+        if job.input.input_type != "url" || (!self.config.predownload_inputs && expected.is_none()) {
+            return Ok(None);
         }
+        let url = job.input.value.clone();
+        let total_bytes = self.validate_url_input(job).await?.unwrap_or(0);
 
-        match job.mode {
-            OutputMode::Mp4 => {
-                let codec_name = job.codec.friendly_name();
-                let status_msg = format!(
-                    "Transcoding to {} {} MP4",
-                    job.resolution.as_str(),
-                    codec_name
-                );
-                self.send_status(
-                    job,
-                    JobStatus::Processing,
-                    Some(&format!("{}...", status_msg)),
-                )
-                .await?;
-
-                // Estimate: conservatively assume 2x realtime for initial progress
-                let estimated_transcode_secs = (video_duration_secs * 2.0) as u64;
-
-                // Create shared atomic counter for real-time progress tracking from FFmpeg
-                let progress_ms = Arc::new(AtomicU64::new(0));
+        let dir = match TempDir::new(&self.config.temp_dir).await {
+            Ok(d) => d,
+            Err(e) => {
+                error!(error = %e, "Failed to create temp dir for input download");
+                return self
+                    .send_error(job, "Failed to prepare input download")
+                    .await
+                    .map(|_| None);
+            }
+        };
 
-                // Get source codec for decoder hint
-                let source_codec = metadata
-                    .as_ref()
-                    .ok()
-                    .and_then(|m| m.video_stream())
-                    .and_then(|s| s.codec_name.clone());
+        if let Some(cache) = &self.input_cache {
+            if let Some(path) = cache.fetch(&url, expected.as_deref(), dir.path()).await {
+                info!(job_id = %job.event_id(), url = %url, "Using cached input");
+                return Ok(Some((dir, path)));
+            }
+        }
 
-                // Transform with periodic progress updates
-                // Use quality 26 for streaming-optimized bitrate (~30% below original CRF 23)
-                let result = self
-                    .run_with_progress(
-                        job,
-                        &status_msg,
-                        estimated_transcode_secs,
-                        video_duration_secs,
-                        progress_ms.clone(),
-                        self.processor.transform_mp4(
-                            input_url,
-                            job.resolution,
-                            Some(26),
-                            job.codec,
-                            source_codec.as_deref(),
-                            Some(progress_ms),
-                            Some(video_duration_secs),
-                        ),
-                    )
-                    .await?;
+        self.send_status(job, JobStatus::Processing, StatusMessage::DownloadingInput)
+            .await?;
 
-                // Get file size for upload estimation
-                let file_size = tokio::fs::metadata(&result.output_path)
-                    .await
-                    .map(|m| m.len())
-                    .unwrap_or(0);
+        let job_id = job.event_id();
+        let requester = job.requester();
+        let message = StatusMessage::DownloadingInput;
+        let code = message.code();
+        let label = message.label(job.lang);
+        let encryption_keys = if job.encryption_type.is_encrypted() {
+            Some(self.config.nostr_keys.clone())
+        } else {
+            None
+        };
+        let enc_type = job.encryption_type;
+        // The job's progress is replaced once the download is done; this one
+        // covers the download phase until then
+        let job_progress = Arc::new(JobProgress::new(true));
+        let downloaded = Arc::new(AtomicU64::new(0));
+        let downloaded_for_tick = downloaded.clone();
+        let updates = self.updates.clone();
+
+        let dest = dir.path().join(INPUT_FILE_NAME);
+        let result = run_with_ticker(
+            self.publisher.clone(),
+            job.relays.clone(),
+            move || {
+                let bytes = downloaded_for_tick.load(Ordering::Relaxed);
+                let phase_pct = if total_bytes > 0 {
+                    ((bytes as f64 / total_bytes as f64) * 100.0) as u32
+                } else {
+                    0
+                };
+                let percent = job_progress.report(ProgressPhase::Downloading, phase_pct);
+                let progress_msg = if total_bytes > 0 {
+                    format!("{} ({}%)", label, phase_pct.min(100))
+                } else {
+                    format!("{} ({} MB)", label, bytes / (1024 * 1024))
+                };
 
-                // Total bytes = file_size * number_of_servers
-                let num_servers = self.blossom.server_count().await;
-                let total_upload_bytes = file_size * num_servers as u64;
+                updates.publish(JobUpdate::Progress {
+                    job_id: job_id.to_hex(),
+                    phase: ProgressPhase::Downloading.as_str().to_string(),
+                    percent: Some(percent),
+                    eta_secs: None,
+                    speed: None,
+                });
 
-                let upload_msg = format!(
-                    "Uploading MP4 to {} server{}",
-                    num_servers,
-                    if num_servers == 1 { "" } else { "s" }
-                );
-                info!(path = %result.output_path.display(), size = file_size, "{}", upload_msg);
-                self.send_status(
-                    job,
+                build_status_event_with_phase(
+                    job_id,
+                    requester,
                     JobStatus::Processing,
-                    Some(&format!("{}...", upload_msg)),
+                    Some(&progress_msg),
+                    None,
+                    encryption_keys.as_ref(),
+                    None,
+                    Some(percent),
+                    enc_type,
+                    Some(ProgressPhase::Downloading),
+                    None,
+                    None,
+                    None,
+                    Some(code),
                 )
-                .await?;
+            },
+            download_verified(&self.http, &url, &dest, expected.as_deref(), &downloaded),
+        )
+        .await;
 
-                let blobs = self
-                    .run_single_file_upload_with_adaptive_progress(
+        match result {
+            Ok(sha256) => {
+                info!(
+                    job_id = %job_id,
+                    bytes = downloaded.load(Ordering::Relaxed),
+                    sha256 = %sha256,
+                    "Input downloaded"
+                );
+                if let Some(cache) = &self.input_cache {
+                    cache.insert(&url, &sha256, &dest).await;
+                }
+                Ok(Some((dir, dest)))
+            }
+            Err(e) => {
+                warn!(job_id = %job_id, url = %url, error = %e, "Input download failed");
+                self.send_error(job, &e.to_string()).await.map(|_| None)
+            }
+        }
+    }
+
+    /// Encrypt an input kept in the temp dir when the operator turned on
+    /// `encrypt_scratch`, returning the handle FFmpeg reads it through
+    async fn seal_input(
+        &self,
+        job: &JobContext,
+        path: &std::path::Path,
+    ) -> Result<Option<SealedInput>, DvmError> {
+        if !self.state.read().await.config.encrypt_scratch {
+            return Ok(None);
+        }
+        let Some(inputs) = &self.sealed else {
+            error!(job_id = %job.event_id(), "Scratch encryption is on but sealed inputs can't be served");
+            return self
+                .send_error(job, "Encrypted scratch space is unavailable")
+                .await
+                .map(|_| None);
+        };
+        match sealed::seal(path, SealKey::generate()).await {
+            Ok(file) => {
+                debug!(job_id = %job.event_id(), bytes = file.len(), "Sealed input in scratch space");
+                Ok(Some(inputs.register(file)))
+            }
+            Err(e) => {
+                error!(job_id = %job.event_id(), error = %e, "Failed to seal input");
+                let _ = tokio::fs::remove_file(path).await;
+                self.send_error(job, "Failed to encrypt input")
+                    .await
+                    .map(|_| None)
+            }
+        }
+    }
+
+    /// Download an `archive` input and assemble it into a video.
+    ///
+    /// Returns `None` for other input types. The archive is extracted and
+    /// rendered in one temp dir, which holds the assembled MP4 afterwards.
+    async fn assemble_archive_input(
+        &self,
+        job: &JobContext,
+    ) -> Result<Option<(TempDir, std::path::PathBuf)>, DvmError> {
+        if job.input.input_type != "archive" {
+            return Ok(None);
+        }
+        self.validate_url_input(job).await?;
+
+        let dir = match TempDir::new(&self.config.temp_dir).await {
+            Ok(d) => d,
+            Err(e) => {
+                error!(error = %e, "Failed to create temp dir for archive input");
+                return self
+                    .send_error(job, "Failed to prepare archive input")
+                    .await
+                    .map(|_| None);
+            }
+        };
+
+        let (archive, _, sha256) = match self.download_input(&job.input.value, dir.path()).await {
+            Ok(downloaded) => downloaded,
+            Err(e) => return self.send_error(job, &e.to_string()).await.map(|_| None),
+        };
+        if let Err(e) = expect_sha256(job.expected_input_sha256(), &sha256) {
+            warn!(url = %job.input.value, error = %e, "Archive input failed verification");
+            return self.send_error(job, &e.to_string()).await.map(|_| None);
+        }
+
+        let files_dir = dir.path().join("files");
+        let extracted = tokio::task::spawn_blocking(move || {
+            std::fs::create_dir(&files_dir)?;
+            let files = zip::extract(&archive, &files_dir, zip::ExtractLimits::default());
+            // The extracted files replace the archive on disk
+            let _ = std::fs::remove_file(&archive);
+            files
+        })
+        .await
+        .map_err(|e| VideoError::Io(e.into()))?;
+
+        let slideshow = match extracted.and_then(|files| Slideshow::plan(&files)) {
+            Ok(show) => show,
+            Err(e) => {
+                warn!(url = %job.input.value, error = %e, "Rejected archive input");
+                return self.send_error(job, &e.to_string()).await.map(|_| None);
+            }
+        };
+
+        self.send_status(
+            job,
+            JobStatus::Processing,
+            StatusMessage::AssemblingArchive {
+                images: slideshow.slides.len(),
+            },
+        )
+        .await?;
+
+        match slideshow.render(&self.config.ffmpeg_path, dir.path()).await {
+            Ok(path) => {
+                info!(
+                    slides = slideshow.slides.len(),
+                    duration = slideshow.duration_secs(),
+                    "Archive input assembled"
+                );
+                Ok(Some((dir, path)))
+            }
+            Err(e) => {
+                warn!(error = %e, "Archive assembly failed");
+                self.send_error(job, &e.to_string()).await.map(|_| None)
+            }
+        }
+    }
+
+    /// Replace an `event` or `job` input with the media URL of the event it
+    /// references, taking the event's SHA-256 unless the request named one.
+    ///
+    /// Runs before pricing so per-minute prices probe the actual video.
+    async fn resolve_event_input(&self, job: &mut JobContext) -> Result<(), DvmError> {
+        if !event_input::is_event_input(&job.input.input_type) {
+            return Ok(());
+        }
+        let Some(reference) = event_input::parse_reference(&job.input.value, &job.input.input_type)
+        else {
+            return self
+                .send_error(job, "Input is not a valid event id, note, nevent or naddr")
+                .await;
+        };
+
+        // Relay hints from the encoding and the input tag, then the job's relays
+        let mut relays: Vec<::url::Url> = reference
+            .relays
+            .iter()
+            .chain(job.input.relay.iter())
+            .filter_map(|r| ::url::Url::parse(r).ok())
+            .collect();
+        relays.extend(job.relays.iter().cloned());
+
+        let events = self.publisher.fetch_matching(reference.filter, &relays).await;
+        let Some(event) = event_input::newest(events) else {
+            return self.send_error(job, "Input event not found on relays").await;
+        };
+        let Some(media) = event_input::media_from_event(&event) else {
+            return self
+                .send_error(job, "Input event does not reference a video URL")
+                .await;
+        };
+
+        info!(
+            job_id = %job.event_id(),
+            event_id = %event.id,
+            kind = event.kind.as_u16(),
+            url = %media.url,
+            "Resolved event input"
+        );
+        job.input.value = media.url;
+        job.input.input_type = "url".to_string();
+        if job.input_sha256.is_none() {
+            job.input_sha256 = media.sha256;
+        }
+        // The referenced video counts for the creator check
+        if job.source_event.is_none() && creator::VIDEO_EVENT_KINDS.contains(&event.kind.as_u16()) {
+            job.source_event = Some(event.id);
+        }
+        Ok(())
+    }
+
+    /// Reject job params this DVM can't honor before doing any work
+    async fn validate_params(&self, job: &JobContext) -> Result<(), DvmError> {
+        if let Some((reason, msg)) = capability_gap(job, &self.config, self.processor.hwaccel()) {
+            self.send_decline(job, reason, &msg).await?;
+            return Err(DvmError::JobRejected(msg));
+        }
+        Ok(())
+    }
+
+    /// Retention the requester asked for, declining requests this DVM can't honor.
+    async fn retention_terms(&self, job: &JobContext) -> Result<RetentionTerms, DvmError> {
+        let terms = self.state.read().await.config.retention_terms(job.retention_days);
+        match terms {
+            Ok(terms) => Ok(terms),
+            Err(msg) => {
+                self.send_decline(job, DeclineReason::RetentionUnavailable, &msg).await?;
+                Err(DvmError::JobRejected(msg))
+            }
+        }
+    }
+
+    /// Tell the requester this DVM can't serve the request
+    async fn send_decline(
+        &self,
+        job: &JobContext,
+        reason: DeclineReason,
+        message: &str,
+    ) -> Result<(), DvmError> {
+        let event = build_decline_event(
+            job.event_id(),
+            job.requester(),
+            reason,
+            message,
+            self.get_encryption_keys(job),
+            job.encryption_type,
+        );
+        self.publisher.publish_for_job(event, &job.relays).await?;
+        Ok(())
+    }
+
+    /// Turn a request away over the requester's rate limit, with a retry hint
+    async fn send_rate_limited(&self, job: &JobContext, retry_after_secs: u64) -> Result<(), DvmError> {
+        let message = StatusMessage::RateLimited {
+            minutes: retry_after_secs.div_ceil(60),
+        };
+        let text = message.text(job.lang);
+        self.publish_status(job, JobStatus::Error, Some(message.code()), &text);
+        let event = build_rate_limited_event(
+            job.event_id(),
+            job.requester(),
+            retry_after_secs,
+            &text,
+            self.get_encryption_keys(job),
+            job.encryption_type,
+        );
+        self.publisher.publish_for_job(event, &job.relays).await?;
+        Ok(())
+    }
+
+    /// Tell the requester the lowest rendition can be watched already
+    async fn send_partial(&self, job: &JobContext, partial: &PartialResult) -> Result<(), DvmError> {
+        let message = StatusMessage::PreviewReady {
+            resolution: &partial.resolution,
+        };
+        let text = message.text(job.lang);
+        self.publish_status(job, JobStatus::Partial, Some(message.code()), &text);
+        job.log.event(format!("Status {}: {}", JobStatus::Partial.as_str(), text));
+        let event = build_partial_event(
+            job.event_id(),
+            job.requester(),
+            partial,
+            &text,
+            message.code(),
+            self.get_encryption_keys(job),
+            job.encryption_type,
+        );
+        self.publisher.publish_for_job(event, &job.relays).await?;
+        Ok(())
+    }
+
+    /// Send the result of an earlier job to a retry of its request
+    async fn resend_result(
+        &self,
+        job: &JobContext,
+        result: &DvmResult,
+        expires_at: Timestamp,
+    ) -> Result<(), DvmError> {
+        let event = build_result_event_encrypted(
+            job.event_id(),
+            job.requester(),
+            result,
+            expires_at,
+            self.get_encryption_keys(job),
+            job.encryption_type,
+        );
+        self.publisher.publish_result(event, &job.relays).await?;
+        self.send_complete(job).await
+    }
+
+    /// Send the success status. When relays the requester asked for refused
+    /// our events (AUTH or payment required), the status names the DVM
+    /// relays the result was published to instead.
+    async fn send_complete(&self, job: &JobContext) -> Result<(), DvmError> {
+        let refused = self.publisher.refused_relays(&job.relays).await;
+        if refused.is_empty() {
+            return self
+                .send_status(job, JobStatus::Success, StatusMessage::TransformComplete)
+                .await;
+        }
+        for (relay, refusal) in &refused {
+            info!(job_id = %job.event_id(), relay = %relay, reason = refusal.as_str(), "Requester relay refused the result");
+        }
+        let relays = self.state.read().await.config.relays.join(", ");
+        self.send_status(
+            job,
+            JobStatus::Success,
+            StatusMessage::TransformCompleteOn { relays: &relays },
+        )
+        .await
+    }
+
+    /// Validate the job input and normalize local paths.
+    ///
+    /// URLs get a scheme check and HEAD request; `path` inputs and `file://`
+    /// URLs must resolve to a file inside one of the operator's
+    /// `LOCAL_INPUT_DIRS`.
+    async fn validate_input(&self, job: &mut JobContext) -> Result<(), DvmError> {
+        let dirs = &self.config.local_input_dirs;
+        let local = match job.input.input_type.as_str() {
+            "url" if is_file_url(&job.input.value) => resolve_file_url(&job.input.value, dirs),
+            "url" => {
+                let size = self.validate_url_input(job).await?;
+                return self.check_input_limits(job, size).await;
+            }
+            "path" => resolve_local_input(&job.input.value, dirs),
+            _ => {
+                return self
+                    .send_error(job, "Only URL and path inputs are supported")
+                    .await
+            }
+        };
+
+        match local {
+            // file:// URLs are handled as plain paths from here on
+            Ok(path) => {
+                debug!(path = %path.display(), "Local input path is permitted");
+                let size = tokio::fs::metadata(&path).await.ok().map(|m| m.len());
+                job.input.value = path.to_string_lossy().to_string();
+                job.input.input_type = "path".to_string();
+                self.check_input_limits(job, size).await
+            }
+            Err(msg) => {
+                warn!(path = %job.input.value, error = %msg, "Rejected local input path");
+                self.send_error(job, &msg).await
+            }
+        }
+    }
+
+    /// Validate a URL input: scheme check and HEAD request.
+    ///
+    /// Returns the input size from `Content-Length`, if the server sent one.
+    async fn validate_url_input(&self, job: &JobContext) -> Result<Option<u64>, DvmError> {
+        let input_url = &job.input.value;
+        if !input_url.starts_with("http://") && !input_url.starts_with("https://") {
+            return self
+                .send_error(job, "Only HTTP and HTTPS URLs are supported")
+                .await
+                .map(|()| None);
+        }
+
+        match self.http.head(input_url).send().await {
+            Ok(resp) if resp.status().is_success() => {
+                debug!(url = %input_url, "URL is accessible");
+                Ok(resp
+                    .headers()
+                    .get(reqwest::header::CONTENT_LENGTH)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.parse().ok()))
+            }
+            Ok(resp) => {
+                let err_msg = format!("Input URL returned status {}", resp.status());
+                warn!(url = %input_url, error = %err_msg);
+                self.send_error(job, &err_msg).await.map(|()| None)
+            }
+            Err(e) => {
+                let err_msg = format!("Failed to reach input URL: {}", e);
+                warn!(url = %input_url, error = %err_msg);
+                self.send_error(job, &err_msg).await.map(|()| None)
+            }
+        }
+    }
+
+    /// Whether a URL input answers the HEAD request `validate_url_input` sends
+    async fn url_reachable(&self, url: &str) -> bool {
+        matches!(self.http.head(url).send().await, Ok(resp) if resp.status().is_success())
+    }
+
+    /// Reject inputs over the operator's duration or size limit.
+    ///
+    /// The size check uses `size_bytes` when known; the duration check probes
+    /// the input with ffprobe, and is skipped if the probe fails (processing
+    /// will report the unreadable input).
+    async fn check_input_limits(&self, job: &JobContext, size_bytes: Option<u64>) -> Result<(), DvmError> {
+        let limits = self
+            .config
+            .input_limits
+            .with_overrides(&self.state.read().await.config);
+        if limits.is_unlimited() {
+            return Ok(());
+        }
+
+        let mut verdict = size_bytes.map_or(Ok(()), |size| limits.check_size(size));
+        if verdict.is_ok() && limits.max_duration_secs.is_some() {
+            let duration = VideoMetadata::extract(&job.input.value, &self.config.ffprobe_path)
+                .await
+                .ok()
+                .and_then(|m| m.duration_secs());
+            verdict = duration.map_or(Ok(()), |secs| limits.check_duration(secs));
+        }
+
+        match verdict {
+            Ok(()) => Ok(()),
+            Err(msg) => {
+                warn!(job_id = %job.event_id(), input = %job.input.value, "Rejecting input: {}", msg);
+                self.send_decline(job, DeclineReason::InputTooLarge, &msg).await?;
+                Err(DvmError::JobRejected(msg))
+            }
+        }
+    }
+
+    async fn process_video(
+        &self,
+        job: &JobContext,
+        requested_input: &DvmInput,
+    ) -> Result<DvmResult, DvmError> {
+        let input_url = &job.input.value;
+
+        debug!(url = %input_url, mode = ?job.mode, resolution = ?job.resolution, codec = ?job.codec, "Processing video");
+
+        if self.hooks.wants(HookStage::PreEncode) {
+            self.hooks
+                .run(&HookContext::new(HookStage::PreEncode, job))
+                .await?;
+        }
+
+        // Get video metadata for duration estimation
+        let metadata = VideoMetadata::extract(input_url, &self.config.ffprobe_path).await;
+        let video_duration_secs = metadata
+            .as_ref()
+            .ok()
+            .and_then(|m| m.duration_secs())
+            .unwrap_or(0.0);
+
+        if let Err(e) = &metadata {
+            warn!(error = %e, "Failed to get video metadata, progress estimates may be inaccurate");
+        }
+
+        // Every encoding decision, refined below by the passes that analyze the source
+        let mut plan = {
+            let state = self.state.read().await;
+            EncodePlan::new(job, metadata.as_ref().ok(), &state.config, self.processor.hwaccel())?
+        };
+        // Progress estimates are sized by what is actually encoded
+        let clip = plan.clip;
+        let encode_duration_secs = plan.duration_secs;
+        if let Some(c) = clip {
+            info!(job_id = %job.event_id(), start = c.start, end = ?c.end, "Transcoding a clip of the source");
+        }
+        if let Some(ref rate) = plan.frame_rate {
+            info!(job_id = %job.event_id(), frame_rate = %rate, "Variable frame rate source, normalizing to constant frame rate");
+        }
+        if let Some(c) = plan.fps_cap {
+            info!(job_id = %job.event_id(), source_fps = c.source_fps, max_fps = c.max_fps, "High frame rate source, capping the frame rate");
+        }
+        if let Some(t) = plan.tonemap {
+            info!(job_id = %job.event_id(), hdr = t.source.as_str(), keep_original = t.keep_original, "HDR source, tone-mapping to SDR");
+            job.log.event(format!("{} source, tone-mapping to SDR", t.source.as_str()));
+        }
+        if plan.rotation != 0 {
+            debug!(job_id = %job.event_id(), rotation = plan.rotation, "Rotated source, transcoding upright");
+        }
+
+        if plan.detect_interlace {
+            plan.deinterlace = self.detect_interlace(job, plan.deinterlace, clip).await;
+        }
+        if let Some(d) = plan.deinterlace {
+            info!(job_id = %job.event_id(), field_order = ?d.field_order, "Interlaced source, deinterlacing");
+            job.log.event("Interlaced source, deinterlacing");
+        }
+
+        if let Some(loudnorm) = &mut plan.loudnorm {
+            loudnorm.measured = self.measure_loudness(job, clip).await?;
+        }
+
+        // Keep the output within the operator's size limit, lowering quality if that's enough
+        let crf_offset = self
+            .fit_output_size(job, &plan, metadata.as_ref().ok())
+            .await?;
+        plan.lower_quality(crf_offset);
+
+        // Hold a scratch reservation for the whole job so concurrent jobs
+        // stay within the temp disk budget
+        let _scratch = self.reserve_scratch(job, &plan, metadata.as_ref().ok()).await?;
+        let _memory = self.reserve_memory(job, &plan, metadata.as_ref().ok()).await?;
+
+        let mut result = match job.mode {
+            OutputMode::Thumbnail => self.process_thumbnails(job, video_duration_secs).await,
+            OutputMode::Mp4 => {
+                // Describe what will actually be encoded; the backend may fall back
+                let encoded_codec =
+                    Codec::from_encoder(self.processor.hwaccel().video_encoder(job.codec));
+                let container = plan.container;
+                let container_name = container.as_str().to_uppercase();
+                let status_msg = StatusMessage::TranscodingMp4 {
+                    resolution: job.resolution.as_str(),
+                    codec: job.codec.friendly_name(),
+                    container: &container_name,
+                };
+                self.send_status(job, JobStatus::Processing, status_msg)
+                    .await?;
+
+                // Estimate: conservatively assume 2x realtime for initial progress
+                let estimated_transcode_secs = (encode_duration_secs * 2.0) as u64;
+
+                // Create shared atomic counter for real-time progress tracking from FFmpeg
+                let progress_ms = Arc::new(AtomicU64::new(0));
+
+                // Transform with periodic progress updates
+                let result = self
+                    .run_with_progress(
+                        job,
+                        &status_msg,
+                        estimated_transcode_secs,
+                        encode_duration_secs,
+                        progress_ms.clone(),
+                        self.processor.transform_mp4(
+                            input_url,
+                            &plan,
+                            Some(progress_ms),
+                            Some(job.log.clone()),
+                        ),
+                    )
+                    .await?;
+                if result.software_fallback {
+                    self.record_software_fallback(job).await?;
+                }
+
+                // A clip's end offsets can't be compared with the full source
+                let sync_source = metadata.as_ref().ok().filter(|_| clip.is_none());
+                self.check_av_sync(sync_source, &result.output_path).await?;
+                self.run_post_encode_hooks(job, vec![result.output_path.clone()])
+                    .await?;
+
+                // Get file size for upload estimation
+                let file_size = tokio::fs::metadata(&result.output_path)
+                    .await
+                    .map(|m| m.len())
+                    .unwrap_or(0);
+
+                // Total bytes = file_size * number_of_servers
+                let num_servers = self.blossom.server_count().await;
+                let total_upload_bytes = file_size * num_servers as u64;
+
+                let upload_msg = StatusMessage::UploadingFile {
+                    container: &container_name,
+                    servers: num_servers,
+                };
+                info!(path = %result.output_path.display(), size = file_size, servers = num_servers, "Uploading output to Blossom");
+                self.send_status(job, JobStatus::Processing, upload_msg)
+                    .await?;
+
+                let blobs = self
+                    .run_single_file_upload_with_adaptive_progress(
                         job,
                         &upload_msg,
                         total_upload_bytes,
                         &result.output_path,
-                        "video/mp4",
+                        container.mime_type(),
                     )
                     .await?;
 
                 // Cleanup temp files
                 result.cleanup().await;
 
-                // Set mimetype based on codec
-                let mimetype = match job.codec {
-                    Codec::H264 => "video/mp4; codecs=\"avc1.64001f,mp4a.40.2\"",
-                    Codec::H265 => "video/mp4; codecs=\"hvc1,mp4a.40.2\"",
-                    Codec::AV1 => "video/mp4; codecs=\"av01.0.05M.08,opus\"", // Common AV1 MP4 mimetype (profile 0, level 5.0, Main)
-                };
+                let mimetype = container.mime_type_with_codecs(encoded_codec);
 
                 Ok(DvmResult::Mp4(Mp4Result {
                     urls: blobs.into_iter().map(|b| b.url).collect(),
                     resolution: job.resolution.as_str().to_string(),
                     size_bytes: file_size,
-                    mimetype: Some(mimetype.to_string()),
+                    mimetype: Some(mimetype),
+                    preview: None,
+                    storyboard: None,
+                    chapters: None,
+                    blurhash: None,
+                    dim: None,
+                    source: None,
+                    input_sha256: None,
+                    watch_url: None,
                 }))
             }
             OutputMode::Hls => {
-                // Get input height and codec for resolution-aware transcoding
-                let input_height = metadata
-                    .as_ref()
-                    .ok()
-                    .and_then(|m| m.resolution())
-                    .map(|(_, h)| h);
-                let source_codec = metadata
-                    .as_ref()
-                    .ok()
-                    .and_then(|m| m.video_stream())
-                    .and_then(|s| s.codec_name.clone());
-
-                // Use user-selected resolutions (or all if not specified)
-                let selected_resolutions = if job.hls_resolutions.is_empty() {
-                    Resolution::all()
-                } else {
-                    job.hls_resolutions.clone()
-                };
+                let selected_resolutions = &plan.resolutions;
 
                 // Build status message based on selected resolutions
                 let resolution_list: Vec<&str> =
                     selected_resolutions.iter().map(|r| r.as_str()).collect();
-                let codec_name = job.codec.friendly_name();
-                let status_msg = format!(
-                    "Transcoding to {} HLS ({})",
-                    codec_name,
-                    resolution_list.join(", ")
+                let resolutions = resolution_list.join(", ");
+                let status_msg = StatusMessage::TranscodingHls {
+                    codec: job.codec.friendly_name(),
+                    resolutions: &resolutions,
+                };
+                self.send_status(job, JobStatus::Processing, status_msg)
+                    .await?;
+
+                // Estimate: count encoded streams (non-original resolutions)
+                let encoded_count = selected_resolutions
+                    .iter()
+                    .filter(|r| **r != Resolution::Original)
+                    .count() as f64;
+                // Estimate: conservatively assume realtime encoding per resolution
+                let estimated_transcode_secs =
+                    (encode_duration_secs * encoded_count.max(1.0)) as u64;
+
+                // Create shared atomic counter for real-time progress tracking from FFmpeg
+                let progress_ms = Arc::new(AtomicU64::new(0));
+
+                // Transform with periodic progress updates using user-selected resolutions,
+                // uploading segments as FFmpeg finishes them. Post-encode hooks
+                // get to see (and veto) the output before any of it is uploaded.
+                let (segments_tx, segments_rx) = mpsc::unbounded_channel();
+                let segments_tx = (!self.hooks.wants(HookStage::PostEncode)).then_some(segments_tx);
+                let (transformed, early_uploads) = tokio::join!(
+                    self.run_with_progress(
+                        job,
+                        &status_msg,
+                        estimated_transcode_secs,
+                        encode_duration_secs,
+                        progress_ms.clone(),
+                        self.processor.transform_with_resolutions(
+                            input_url,
+                            &plan,
+                            Some(progress_ms),
+                            Some(job.log.clone()),
+                            segments_tx,
+                        ),
+                    ),
+                    self.blossom.upload_segments_as_finished(segments_rx, |_, _| {}),
                 );
-                self.send_status(
-                    job,
-                    JobStatus::Processing,
-                    Some(&format!("{}...", status_msg)),
-                )
+                let (result, transform_config) = transformed?;
+                let mut early_uploads = early_uploads;
+                if result.software_fallback {
+                    self.record_software_fallback(job).await?;
+                    // The software retry rewrote the segments the failed
+                    // hardware attempt had finished
+                    for sha256 in superseded_uploads(&mut early_uploads, &result.segment_hashes) {
+                        self.blossom.discard_blob(&sha256).await;
+                    }
+                }
+                if !early_uploads.is_empty() {
+                    job.log.event(format!("{} segments uploaded during encoding", early_uploads.len()));
+                }
+
+                let sync_source = metadata.as_ref().ok().filter(|_| clip.is_none());
+                for playlist in &result.stream_playlists {
+                    self.check_av_sync(sync_source, playlist).await?;
+                }
+                let files = result.all_files().into_iter().map(|p| p.to_path_buf()).collect();
+                self.run_post_encode_hooks(job, files).await?;
+
+                // Segments uploaded during encoding are left out
+                let remaining_segments: Vec<_> = result
+                    .segment_paths
+                    .iter()
+                    .zip(&result.segment_hashes)
+                    .filter(|(_, sha256)| !early_uploads.contains(*sha256))
+                    .map(|(path, _)| path)
+                    .collect();
+                let total_files = remaining_segments.len() + result.stream_playlists.len() + 1;
+
+                // Estimate total size from segments
+                let mut total_size: u64 = 0;
+                for path in &remaining_segments {
+                    if let Ok(meta) = tokio::fs::metadata(path).await {
+                        total_size += meta.len();
+                    }
+                }
+
+                let upload_msg = StatusMessage::UploadingFiles { files: total_files };
+                info!(
+                    segment_count = result.segment_paths.len(),
+                    uploaded_early = result.segment_paths.len() - remaining_segments.len(),
+                    files = total_files,
+                    "Uploading HLS output to Blossom"
+                );
+                self.send_status(job, JobStatus::Processing, upload_msg)
+                    .await?;
+
+                // Upload with adaptive progress tracking, announcing the lowest
+                // rendition as soon as it is playable
+                let (preview_tx, preview_rx) = oneshot::channel();
+                let (uploaded, _) = tokio::join!(
+                    self.run_upload_with_adaptive_progress(
+                        job,
+                        &upload_msg,
+                        total_size,
+                        &result,
+                        &early_uploads,
+                        Some(preview_tx),
+                    ),
+                    async {
+                        if let Ok(partial) = preview_rx.await {
+                            if let Err(e) = self.send_partial(job, &partial).await {
+                                warn!(error = %e, "Failed to send partial result");
+                            }
+                        }
+                    },
+                );
+                let mut hls_result = uploaded?;
+                for stream in &mut hls_result.stream_playlists {
+                    stream.audio_bitrate = transform_config
+                        .audio_bitrate_for(&stream.resolution)
+                        .map(str::to_string);
+                }
+
+                // Cleanup temp files
+                result.cleanup().await;
+
+                Ok(DvmResult::Hls(hls_result))
+            }
+        }?;
+
+        if let (Some(format), OutputMode::Mp4 | OutputMode::Hls) = (job.preview, job.mode) {
+            match self.create_preview(job, format, video_duration_secs).await {
+                Ok(preview) => result.set_preview(preview),
+                // The main output is done; a missing preview shouldn't fail the job
+                Err(e) => warn!(error = %e, "Failed to generate animated preview"),
+            }
+        }
+
+        if job.storyboard && job.mode != OutputMode::Thumbnail {
+            let storyboard = Storyboard::new(
+                encode_duration_secs,
+                metadata.as_ref().ok().and_then(|m| m.resolution()),
+            );
+            match self.create_storyboard(job, storyboard, clip).await {
+                Ok(track) => result.set_storyboard(track),
+                Err(e) => warn!(error = %e, "Failed to generate seek thumbnails"),
+            }
+        }
+
+        if job.mode != OutputMode::Thumbnail {
+            let chapters = metadata
+                .as_ref()
+                .map(|m| output_chapters(&m.chapters, clip))
+                .unwrap_or_default();
+            if !chapters.is_empty() {
+                match self.upload_chapters(chapters).await {
+                    Ok(track) => result.set_chapters(track),
+                    Err(e) => warn!(error = %e, "Failed to upload chapters track"),
+                }
+            }
+        }
+
+        if job.mode != OutputMode::Thumbnail {
+            // A third of the way into the encoded range, past intros and fades
+            let start = clip.map_or(0.0, |c| c.start);
+            let timestamp = start + encode_duration_secs / 3.0;
+            let dimensions = metadata.as_ref().ok().and_then(|m| m.resolution());
+            match self
+                .processor
+                .extract_blurhash(&job.input.value, timestamp, dimensions)
+                .await
+            {
+                Ok(hash) => {
+                    result.set_blurhash(hash, dimensions.map(|(w, h)| format!("{}x{}", w, h)))
+                }
+                // Only a placeholder; the job succeeds without it
+                Err(e) => warn!(error = %e, "Failed to compute blurhash"),
+            }
+        }
+
+        if job.archive_source && job.mode != OutputMode::Thumbnail {
+            match self.archive_source(job, requested_input).await {
+                Ok(source) => result.set_source(source),
+                // Like the preview, the transcodes are still worth delivering
+                Err(e) => warn!(error = %e, "Failed to archive source file"),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Run the `post-encode` hooks on the files about to be uploaded
+    async fn run_post_encode_hooks(
+        &self,
+        job: &JobContext,
+        files: Vec<std::path::PathBuf>,
+    ) -> Result<(), DvmError> {
+        if self.hooks.wants(HookStage::PostEncode) {
+            let ctx = HookContext::new(HookStage::PostEncode, job).with_files(files);
+            self.hooks.run(&ctx).await?;
+        }
+        Ok(())
+    }
+
+    /// Upload the unmodified input to Blossom.
+    ///
+    /// Local and fetched inputs are uploaded in place; URL inputs (which
+    /// FFmpeg streamed) and archives (which were unpacked) are downloaded
+    /// into a temp dir first.
+    async fn archive_source(
+        &self,
+        job: &JobContext,
+        requested_input: &DvmInput,
+    ) -> Result<ArchivedSource, DvmError> {
+        self.send_status(
+            job,
+            JobStatus::Processing,
+            StatusMessage::ArchivingSource,
+        )
+        .await?;
+
+        // Hashed while downloading (or read back for local inputs), so the
+        // mirrored copy is known to be the input the requester named
+        let input = source_to_archive(job, requested_input);
+        let (_download, path, mimetype, sha256) =
+            if input.starts_with("http://") || input.starts_with("https://") {
+                let dir = TempDir::new(&self.config.temp_dir)
+                    .await
+                    .map_err(VideoError::Io)?;
+                let (path, mimetype, sha256) = self.download_input(input, dir.path()).await?;
+                (Some(dir), path, mimetype, sha256)
+            } else {
+                let path = std::path::PathBuf::from(input);
+                let mimetype = source_mime_type(&path).to_string();
+                let sha256 = crate::util::hash_file(&path).await.map_err(VideoError::Io)?;
+                (None, path, mimetype, sha256)
+            };
+        expect_sha256(job.expected_input_sha256(), &sha256)?;
+
+        let size_bytes = tokio::fs::metadata(&path)
+            .await
+            .map_err(VideoError::Io)?
+            .len();
+        let blobs = self
+            .blossom
+            .upload_hashed_file_to_all(&path, &sha256, size_bytes, &mimetype)
+            .await?;
+        info!(job_id = %job.event_id(), sha256 = %sha256, size = size_bytes, "Archived source file");
+
+        Ok(ArchivedSource {
+            urls: blobs.into_iter().map(|b| b.url).collect(),
+            sha256,
+            size_bytes,
+            mimetype,
+        })
+    }
+
+    /// Stream a URL into `dir`, returning the file, its content type and SHA-256
+    async fn download_input(
+        &self,
+        url: &str,
+        dir: &std::path::Path,
+    ) -> Result<(std::path::PathBuf, String, String), VideoError> {
+        use sha2::{Digest, Sha256};
+        use tokio::io::AsyncWriteExt;
+
+        let fetch_failed = |e: reqwest::Error| VideoError::FetchFailed(e.to_string());
+        let mut resp = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(fetch_failed)?;
+
+        let path = dir.join("source");
+        let mimetype = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .filter(|t| !t.starts_with("application/octet-stream"))
+            .map(str::to_string)
+            .unwrap_or_else(|| source_mime_type(std::path::Path::new(url)).to_string());
+
+        let mut file = tokio::fs::File::create(&path).await?;
+        let mut hasher = Sha256::new();
+        while let Some(chunk) = resp.chunk().await.map_err(fetch_failed)? {
+            hasher.update(&chunk);
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+
+        Ok((path, mimetype, hex::encode(hasher.finalize())))
+    }
+
+    /// Render the animated hover preview and upload it to Blossom
+    async fn create_preview(
+        &self,
+        job: &JobContext,
+        format: PreviewFormat,
+        video_duration_secs: f64,
+    ) -> Result<Preview, DvmError> {
+        let duration = if video_duration_secs > 0.0 {
+            job.preview_duration.min(video_duration_secs)
+        } else {
+            job.preview_duration
+        };
+
+        self.send_status(
+            job,
+            JobStatus::Processing,
+            StatusMessage::GeneratingPreview,
+        )
+        .await?;
+
+        let result = self
+            .processor
+            .generate_preview(&job.input.value, format, duration)
+            .await?;
+
+        let size_bytes = tokio::fs::metadata(&result.output_path)
+            .await
+            .map(|m| m.len())
+            .unwrap_or(0);
+        let blobs = self
+            .blossom
+            .upload_file_to_all(&result.output_path, format.mime_type())
+            .await?;
+
+        result.cleanup().await;
+
+        Ok(Preview {
+            urls: blobs.into_iter().map(|b| b.url).collect(),
+            mimetype: format.mime_type().to_string(),
+            size_bytes,
+            duration,
+        })
+    }
+
+    /// Render the seek thumbnail sheets, upload them and the WebVTT track
+    /// indexing them to Blossom
+    async fn create_storyboard(
+        &self,
+        job: &JobContext,
+        storyboard: Storyboard,
+        clip: Option<Clip>,
+    ) -> Result<StoryboardTrack, DvmError> {
+        self.send_status(
+            job,
+            JobStatus::Processing,
+            StatusMessage::GeneratingStoryboard,
+        )
+        .await?;
+
+        let dir = TempDir::new(&self.config.temp_dir)
+            .await
+            .map_err(VideoError::Io)?;
+        let paths = storyboard
+            .render(&self.config.ffmpeg_path, &job.input.value, clip, dir.path())
+            .await?;
+
+        let mut names = Vec::with_capacity(paths.len());
+        let mut sheets = Vec::with_capacity(paths.len());
+        for path in &paths {
+            let sha256 = crate::util::hash_file(path).await.map_err(VideoError::Io)?;
+            let size = tokio::fs::metadata(path).await.map_err(VideoError::Io)?.len();
+            let blobs = self
+                .blossom
+                .upload_hashed_file_to_all(path, &sha256, size, "image/jpeg")
                 .await?;
+            sheets.extend(blobs.into_iter().next().map(|b| b.url));
+            names.push(format!("{}.jpg", sha256));
+        }
+
+        let vtt_path = dir.path().join("storyboard.vtt");
+        tokio::fs::write(&vtt_path, storyboard.vtt(&names))
+            .await
+            .map_err(VideoError::Io)?;
+        let blobs = self.blossom.upload_file_to_all(&vtt_path, "text/vtt").await?;
+        let _ = dir.cleanup().await;
+        info!(job_id = %job.event_id(), sheets = sheets.len(), interval = storyboard.interval, "Uploaded seek thumbnails");
+
+        Ok(StoryboardTrack {
+            urls: blobs.into_iter().map(|b| b.url).collect(),
+            sheets,
+            interval: storyboard.interval,
+            dim: storyboard.dim(),
+        })
+    }
+
+    /// Upload the output's chapters to Blossom as a WebVTT chapters track
+    async fn upload_chapters(&self, chapters: Vec<Chapter>) -> Result<ChaptersTrack, DvmError> {
+        let dir = TempDir::new(&self.config.temp_dir)
+            .await
+            .map_err(VideoError::Io)?;
+        let path = dir.path().join("chapters.vtt");
+        tokio::fs::write(&path, chapters::vtt(&chapters))
+            .await
+            .map_err(VideoError::Io)?;
+        let blobs = self.blossom.upload_file_to_all(&path, "text/vtt").await?;
+        let _ = dir.cleanup().await;
+
+        Ok(ChaptersTrack {
+            urls: blobs.into_iter().map(|b| b.url).collect(),
+            chapters,
+        })
+    }
+
+    /// Extract poster frames and upload them to Blossom.
+    ///
+    /// Uses the requested timestamps (dropping any past the end of the video)
+    /// or evenly spaced frames. Frames are capped at the job's resolution.
+    async fn process_thumbnails(
+        &self,
+        job: &JobContext,
+        video_duration_secs: f64,
+    ) -> Result<DvmResult, DvmError> {
+        let timestamps: Vec<f64> = if job.thumbnail_timestamps.is_empty() {
+            evenly_spaced_timestamps(video_duration_secs, job.thumbnail_count)
+        } else if video_duration_secs > 0.0 {
+            job.thumbnail_timestamps
+                .iter()
+                .copied()
+                .filter(|t| *t < video_duration_secs)
+                .collect()
+        } else {
+            job.thumbnail_timestamps.clone()
+        };
+
+        if timestamps.is_empty() {
+            return Err(DvmError::JobRejected(
+                "All requested timestamps are past the end of the video".into(),
+            ));
+        }
+
+        self.send_status(
+            job,
+            JobStatus::Processing,
+            StatusMessage::ExtractingThumbnails {
+                count: timestamps.len(),
+            },
+        )
+        .await?;
+
+        let result = self
+            .processor
+            .extract_thumbnails(&job.input.value, &timestamps, job.resolution.height())
+            .await?;
+
+        self.send_status(
+            job,
+            JobStatus::Processing,
+            StatusMessage::UploadingThumbnails,
+        )
+        .await?;
+
+        let mut thumbnails = Vec::with_capacity(result.frames.len());
+        for (timestamp, path) in &result.frames {
+            let size_bytes = tokio::fs::metadata(path)
+                .await
+                .map(|m| m.len())
+                .unwrap_or(0);
+            let blobs = self.blossom.upload_file_to_all(path, "image/jpeg").await?;
+            thumbnails.push(Thumbnail {
+                urls: blobs.into_iter().map(|b| b.url).collect(),
+                timestamp: *timestamp,
+                size_bytes,
+                mimetype: "image/jpeg".to_string(),
+            });
+        }
+
+        result.cleanup().await;
+
+        Ok(DvmResult::Thumbnail(ThumbnailResult {
+            thumbnails,
+            input_sha256: None,
+        }))
+    }
+
+    /// Predict this job's temp space usage and reserve it from the shared budget.
+    ///
+    /// Waits (with a status update) while other jobs hold the space, and rejects
+    /// jobs that could never fit. Returns `None` if usage can't be predicted.
+    async fn reserve_scratch(
+        &self,
+        job: &JobContext,
+        plan: &EncodePlan,
+        metadata: Option<&VideoMetadata>,
+    ) -> Result<Option<ScratchReservation>, DvmError> {
+        let metadata = match metadata {
+            Some(m) => m,
+            None => return Ok(None),
+        };
+
+        // A handful of JPEGs; not worth reserving for
+        if job.mode == OutputMode::Thumbnail {
+            return Ok(None);
+        }
+        let renditions: Vec<Option<u32>> =
+            plan.renditions(job.mode).iter().map(|r| r.height).collect();
+
+        // Fetched and assembled inputs live in the temp dir (sealed or not);
+        // local `path` inputs do not
+        let input_on_disk = job.input.input_type == "archive"
+            || is_sealed_url(&job.input.value)
+            || (job.input.input_type == "url"
+                && !job.input.value.starts_with("http://")
+                && !job.input.value.starts_with("https://"));
+
+        let needed = match estimate_scratch_bytes(metadata, &renditions, input_on_disk) {
+            Some(bytes) => bytes,
+            None => {
+                debug!("Unknown input bitrate, skipping scratch space reservation");
+                return Ok(None);
+            }
+        };
+
+        if let Some(reservation) = self.scratch.try_reserve(needed) {
+            return Ok(Some(reservation));
+        }
+
+        // Space held by other jobs comes back when they finish; anything else
+        // on the disk (or a job larger than the whole budget) never will
+        let free = self.scratch.free_now();
+        let available = match free {
+            Some(f) if self.scratch.reserved() == 0 => f.min(self.scratch.limit()),
+            _ => self.scratch.limit(),
+        };
+        if needed > available {
+            return self
+                .reject_for_scratch(job, needed, available)
+                .await
+                .map(|_| None);
+        }
+
+        info!(
+            needed_bytes = needed,
+            reserved_bytes = self.scratch.reserved(),
+            limit_bytes = self.scratch.limit(),
+            free_bytes = ?free,
+            "Waiting for scratch space"
+        );
+        let msg = StatusMessage::WaitingForDisk {
+            megabytes: needed.div_ceil(1024 * 1024),
+        };
+        self.send_status(job, JobStatus::Processing, msg).await?;
+
+        match self.scratch.reserve(needed).await {
+            Some(reservation) => Ok(Some(reservation)),
+            None => {
+                let available = self
+                    .scratch
+                    .free_now()
+                    .map_or(self.scratch.limit(), |f| f.min(self.scratch.limit()));
+                self.reject_for_scratch(job, needed, available)
+                    .await
+                    .map(|_| None)
+            }
+        }
+    }
 
-                // Estimate: count encoded streams (non-original resolutions)
-                let encoded_count = selected_resolutions
-                    .iter()
-                    .filter(|r| **r != Resolution::Original)
-                    .count() as f64;
-                // Estimate: conservatively assume realtime encoding per resolution
-                let estimated_transcode_secs =
-                    (video_duration_secs * encoded_count.max(1.0)) as u64;
+    /// Reserve the job's predicted FFmpeg memory, waiting for running jobs
+    /// to finish if it doesn't fit next to them.
+    async fn reserve_memory(
+        &self,
+        job: &JobContext,
+        plan: &EncodePlan,
+        metadata: Option<&VideoMetadata>,
+    ) -> Result<Option<MemoryReservation>, DvmError> {
+        if job.mode == OutputMode::Thumbnail {
+            return Ok(None);
+        }
+        // Without a probed source, assume a single full-size rendition
+        let renditions: Vec<Option<u32>> = match metadata {
+            Some(_) => plan.renditions(job.mode).iter().map(|r| r.height).collect(),
+            None => vec![None],
+        };
+        let needed = estimate_memory_bytes(metadata, &renditions);
 
-                // Create shared atomic counter for real-time progress tracking from FFmpeg
-                let progress_ms = Arc::new(AtomicU64::new(0));
+        if let Some(reservation) = self.memory.try_reserve(needed) {
+            return Ok(Some(reservation));
+        }
 
-                // Transform with periodic progress updates using user-selected resolutions
-                let (result, _transform_config) = self
-                    .run_with_progress(
-                        job,
-                        &status_msg,
-                        estimated_transcode_secs,
-                        video_duration_secs,
-                        progress_ms.clone(),
-                        self.processor.transform_with_resolutions(
-                            input_url,
-                            input_height,
-                            job.codec,
-                            &selected_resolutions,
-                            source_codec.as_deref(),
-                            job.encryption,
-                            Some(progress_ms),
-                            Some(video_duration_secs),
-                        ),
-                    )
-                    .await?;
+        info!(
+            needed_bytes = needed,
+            reserved_bytes = self.memory.reserved(),
+            limit_bytes = self.memory.limit(),
+            available_bytes = ?self.memory.available_now(),
+            "Waiting for memory"
+        );
+        let msg = StatusMessage::WaitingForMemory {
+            megabytes: needed.div_ceil(1024 * 1024),
+        };
+        self.send_status(job, JobStatus::Processing, msg).await?;
 
-                let total_files = result.segment_paths.len() + result.stream_playlists.len() + 1;
+        Ok(Some(self.memory.reserve(needed).await))
+    }
 
-                // Estimate total size from segments
-                let mut total_size: u64 = 0;
-                for path in &result.segment_paths {
-                    if let Ok(meta) = tokio::fs::metadata(path).await {
-                        total_size += meta.len();
-                    }
-                }
+    /// Fail a job whose predicted temp space can't be provided.
+    async fn reject_for_scratch(
+        &self,
+        job: &JobContext,
+        needed: u64,
+        available: u64,
+    ) -> Result<(), DvmError> {
+        let msg = format!(
+            "Job needs ~{} MB of temp space but only {} MB is available",
+            needed.div_ceil(1024 * 1024),
+            available / (1024 * 1024)
+        );
+        warn!(needed_bytes = needed, available_bytes = available, "Not enough temp space");
+        self.send_error(job, &msg).await
+    }
 
-                let upload_msg = format!("Uploading {} files to Blossom", total_files);
-                info!(segment_count = result.segment_paths.len(), "{}", upload_msg);
-                self.send_status(
-                    job,
-                    JobStatus::Processing,
-                    Some(&format!("{}...", upload_msg)),
-                )
-                .await?;
+    /// Measure the source's loudness for two-pass normalization.
+    ///
+    /// A failed measurement falls back to single-pass normalization rather
+    /// than failing the job.
+    async fn measure_loudness(
+        &self,
+        job: &JobContext,
+        clip: Option<Clip>,
+    ) -> Result<Option<LoudnessMeasurement>, DvmError> {
+        self.send_status(job, JobStatus::Processing, StatusMessage::MeasuringLoudness)
+            .await?;
+        match Loudnorm::measure(&self.config.ffmpeg_path, &job.input.value, clip).await {
+            Ok(m) => {
+                info!(job_id = %job.event_id(), integrated = m.integrated, true_peak = m.true_peak, "Measured source loudness");
+                Ok(Some(m))
+            }
+            Err(e) => {
+                warn!(job_id = %job.event_id(), error = %e, "Loudness measurement failed, normalizing in a single pass");
+                Ok(None)
+            }
+        }
+    }
 
-                // Upload with adaptive progress tracking
-                let hls_result = self
-                    .run_upload_with_adaptive_progress(job, &upload_msg, total_size, &result)
-                    .await?;
+    /// Run the first frames of the source through `idet`. The container's
+    /// field order flag (`flagged`) is only trusted when that fails.
+    async fn detect_interlace(
+        &self,
+        job: &JobContext,
+        flagged: Option<Deinterlace>,
+        clip: Option<Clip>,
+    ) -> Option<Deinterlace> {
+        match IdetCounts::measure(&self.config.ffmpeg_path, &job.input.value, clip).await {
+            Ok(counts) => {
+                debug!(job_id = %job.event_id(), ?counts, "Interlace detection");
+                counts
+                    .field_order()
+                    .map(|field_order| Deinterlace { field_order })
+            }
+            Err(e) => {
+                warn!(job_id = %job.event_id(), error = %e, "Interlace detection failed, going by the container's field order");
+                flagged
+            }
+        }
+    }
 
-                // Cleanup temp files
-                result.cleanup().await;
+    /// Estimate the output size and keep it within the operator's limit.
+    ///
+    /// Returns how many CRF steps to lower quality by: 0 when the job fits as
+    /// requested, more when lowering quality makes it fit. Jobs that don't
+    /// fit even then are declined with `output-too-large`.
+    async fn fit_output_size(
+        &self,
+        job: &JobContext,
+        plan: &EncodePlan,
+        metadata: Option<&VideoMetadata>,
+    ) -> Result<u32, DvmError> {
+        let limits = {
+            let state = self.state.read().await;
+            self.config.output_limits.with_overrides(&state.config)
+        };
+        let duration_secs = plan.duration_secs;
+        let metadata = match metadata {
+            Some(m) if !limits.is_unlimited() && job.mode != OutputMode::Thumbnail => m,
+            _ => return Ok(0),
+        };
 
-                Ok(DvmResult::Hls(hls_result))
+        // Size of the part of the input being encoded
+        let input_bytes = metadata
+            .bit_rate_bps()
+            .map(|bps| (bps as f64 * duration_secs / 8.0) as u64);
+        let encoded_codec = Codec::from_encoder(self.processor.hwaccel().video_encoder(job.codec));
+
+        let mut verdict = Ok(());
+        for crf_offset in (0..=MAX_OUTPUT_CRF_OFFSET).step_by(OUTPUT_CRF_STEP as usize) {
+            let mut lowered = plan.clone();
+            lowered.lower_quality(crf_offset);
+            let renditions = lowered.renditions(job.mode);
+            let Some(estimate) =
+                estimate_output_bytes(metadata, &renditions, encoded_codec, duration_secs)
+            else {
+                debug!("Unknown input resolution, skipping output size check");
+                return Ok(0);
+            };
+            verdict = limits.check(estimate, input_bytes);
+            if verdict.is_ok() {
+                if crf_offset > 0 {
+                    info!(job_id = %job.event_id(), crf_offset, estimate, "Lowering quality to fit the output size limit");
+                    self.send_status(
+                        job,
+                        JobStatus::Processing,
+                        StatusMessage::LoweringQuality,
+                    )
+                    .await?;
+                }
+                return Ok(crf_offset);
             }
         }
+
+        let msg = verdict.unwrap_err();
+        warn!(job_id = %job.event_id(), "Rejecting job: {}", msg);
+        self.send_decline(job, DeclineReason::OutputTooLarge, &msg).await?;
+        Err(DvmError::JobRejected(msg))
     }
 
     /// Run a future with periodic progress updates every 5 seconds
     async fn run_with_progress<T, E, F>(
         &self,
         job: &JobContext,
-        message: &str,
+        message: &StatusMessage<'_>,
         estimated_secs: u64,
         total_duration_secs: f64,
         progress_ms: Arc<AtomicU64>,
@@ -583,7 +2349,9 @@ impl JobHandler {
         let job_id = job.event_id();
         let requester = job.requester();
         let publisher = self.publisher.clone();
-        let message = message.to_string();
+        let code = message.code();
+        let message = message.label(job.lang);
+        let lang = job.lang;
         let job_relays = job.relays.clone();
         let encryption_keys = if job.encryption_type.is_encrypted() {
             Some(self.config.nostr_keys.clone())
@@ -591,7 +2359,9 @@ impl JobHandler {
             None
         };
         let enc_type = job.encryption_type;
+        let job_progress = job.progress.clone();
 
+        let updates = self.updates.clone();
         run_with_ticker(
             publisher,
             job_relays,
@@ -603,6 +2373,7 @@ impl JobHandler {
 
                 let (progress_msg, remaining_secs, progress_pct, speed_multiplier) = if actual_us > 0 && total_duration_secs > 0.0 {
                     let pct = ((actual_secs / total_duration_secs) * 100.0).min(99.0) as u32;
+                    let pct = job_progress.report(ProgressPhase::Transcoding, pct);
                     let speed = if elapsed_secs > 0 { actual_secs / elapsed_secs as f64 } else { 0.0 };
                     let remaining = if speed > 0.01 {
                         ((total_duration_secs - actual_secs) / speed) as u64
@@ -610,7 +2381,7 @@ impl JobHandler {
                         estimated_secs.saturating_sub(elapsed_secs)
                     };
                     (
-                        format!("{} ({}%, ~{} remaining)", message, pct, format_duration(remaining)),
+                        format!("{} ({}%, {})", message, pct, lang.remaining(&format_duration(remaining))),
                         Some(remaining),
                         Some(pct),
                         if speed > 0.01 { Some(speed) } else { None },
@@ -618,21 +2389,30 @@ impl JobHandler {
                 } else if estimated_secs > 0 {
                     let remaining = estimated_secs.saturating_sub(elapsed_secs);
                     let pct = ((elapsed_secs as f64 / estimated_secs as f64) * 100.0).min(99.0) as u32;
+                    let pct = job_progress.report(ProgressPhase::Transcoding, pct);
                     (
-                        format!("{} (~{} remaining)", message, format_duration(remaining)),
+                        format!("{} ({})", message, lang.remaining(&format_duration(remaining))),
                         Some(remaining),
                         Some(pct),
                         None,
                     )
                 } else {
                     (
-                        format!("{} ({} elapsed)", message, format_duration(elapsed_secs)),
+                        format!("{} ({})", message, lang.elapsed(&format_duration(elapsed_secs))),
                         None,
                         None,
                         None,
                     )
                 };
 
+                updates.publish(JobUpdate::Progress {
+                    job_id: job_id.to_hex(),
+                    phase: ProgressPhase::Transcoding.as_str().to_string(),
+                    percent: progress_pct,
+                    eta_secs: remaining_secs,
+                    speed: speed_multiplier,
+                });
+
                 build_status_event_with_phase(
                     job_id,
                     requester,
@@ -647,6 +2427,7 @@ impl JobHandler {
                     speed_multiplier,
                     None,
                     None,
+                    Some(code),
                 )
             },
             future,
@@ -658,7 +2439,7 @@ impl JobHandler {
     async fn run_single_file_upload_with_adaptive_progress(
         &self,
         job: &JobContext,
-        message: &str,
+        message: &StatusMessage<'_>,
         total_bytes: u64,
         path: &std::path::Path,
         mime_type: &str,
@@ -666,7 +2447,9 @@ impl JobHandler {
         let job_id = job.event_id();
         let requester = job.requester();
         let publisher = self.publisher.clone();
-        let message = message.to_string();
+        let code = message.code();
+        let message = message.label(job.lang);
+        let lang = job.lang;
         let job_relays = job.relays.clone();
         let encryption_keys = if job.encryption_type.is_encrypted() {
             Some(self.config.nostr_keys.clone())
@@ -678,7 +2461,10 @@ impl JobHandler {
         let bytes_uploaded = Arc::new(AtomicU64::new(0));
         let bytes_for_tick = bytes_uploaded.clone();
         let start_time = Instant::now();
+        let job_progress = job.progress.clone();
+        job_progress.report(ProgressPhase::Uploading, 0);
 
+        let updates = self.updates.clone();
         run_with_ticker(
             publisher,
             job_relays,
@@ -691,6 +2477,7 @@ impl JobHandler {
                 } else {
                     0
                 };
+                let percent = job_progress.report(ProgressPhase::Uploading, percent);
 
                 let speed_mbps = if elapsed > 0.0 {
                     (uploaded as f64 / elapsed) / (1024.0 * 1024.0)
@@ -707,10 +2494,10 @@ impl JobHandler {
 
                 let progress_msg = if remaining_secs > 0 && speed_mbps > 0.1 {
                     format!(
-                        "{} ({}%, ~{} remaining @ {:.1} MB/s)",
+                        "{} ({}%, {} @ {:.1} MB/s)",
                         message,
                         percent,
-                        format_duration(remaining_secs),
+                        lang.remaining(&format_duration(remaining_secs)),
                         speed_mbps
                     )
                 } else if speed_mbps > 0.1 {
@@ -719,6 +2506,14 @@ impl JobHandler {
                     format!("{} ({}%)", message, percent)
                 };
 
+                updates.publish(JobUpdate::Progress {
+                    job_id: job_id.to_hex(),
+                    phase: ProgressPhase::Uploading.as_str().to_string(),
+                    percent: Some(percent),
+                    eta_secs: Some(remaining_secs).filter(|s| *s > 0),
+                    speed: Some(speed_mbps).filter(|s| *s > 0.01),
+                });
+
                 build_status_event_with_phase(
                     job_id,
                     requester,
@@ -733,6 +2528,7 @@ impl JobHandler {
                     if speed_mbps > 0.01 { Some(speed_mbps) } else { None },
                     None,
                     None,
+                    Some(code),
                 )
             },
             async {
@@ -749,14 +2545,18 @@ impl JobHandler {
     async fn run_upload_with_adaptive_progress(
         &self,
         job: &JobContext,
-        message: &str,
+        message: &StatusMessage<'_>,
         total_bytes: u64,
         transform_result: &TransformResult,
+        uploaded: &HashSet<String>,
+        preview: Option<oneshot::Sender<PartialResult>>,
     ) -> Result<crate::dvm::events::HlsResult, DvmError> {
         let job_id = job.event_id();
         let requester = job.requester();
         let publisher = self.publisher.clone();
-        let message = message.to_string();
+        let code = message.code();
+        let message = message.label(job.lang);
+        let lang = job.lang;
         let job_relays = job.relays.clone();
         let encryption_keys = if job.encryption_type.is_encrypted() {
             Some(self.config.nostr_keys.clone())
@@ -768,7 +2568,10 @@ impl JobHandler {
         let tracker = Arc::new(Mutex::new(UploadTracker::new(total_bytes)));
         let tracker_for_tick = tracker.clone();
         let tracker_for_upload = tracker.clone();
+        let job_progress = job.progress.clone();
+        job_progress.report(ProgressPhase::Uploading, 0);
 
+        let updates = self.updates.clone();
         run_with_ticker(
             publisher,
             job_relays,
@@ -783,18 +2586,26 @@ impl JobHandler {
                     (
                         t.estimated_remaining_secs(),
                         t.average_speed() / (1024.0 * 1024.0),
-                        pct,
+                        job_progress.report(ProgressPhase::Uploading, pct),
                     )
                 };
 
                 let progress_msg = format!(
-                    "{} ({}%, ~{} remaining, {:.1} MB/s)",
+                    "{} ({}%, {}, {:.1} MB/s)",
                     message,
                     percent,
-                    format_duration(remaining_secs),
+                    lang.remaining(&format_duration(remaining_secs)),
                     speed_mbps
                 );
 
+                updates.publish(JobUpdate::Progress {
+                    job_id: job_id.to_hex(),
+                    phase: ProgressPhase::Uploading.as_str().to_string(),
+                    percent: Some(percent),
+                    eta_secs: Some(remaining_secs),
+                    speed: Some(speed_mbps).filter(|s| *s > 0.01),
+                });
+
                 build_status_event_with_phase(
                     job_id,
                     requester,
@@ -809,11 +2620,12 @@ impl JobHandler {
                     if speed_mbps > 0.01 { Some(speed_mbps) } else { None },
                     None,
                     None,
+                    Some(code),
                 )
             },
             async {
                 self.blossom
-                    .upload_hls_output_with_progress(transform_result, move |bytes, duration| {
+                    .upload_hls_output_skipping(transform_result, uploaded, preview, move |bytes, duration| {
                         let mut t = tracker_for_upload.lock().unwrap();
                         t.record_upload(bytes, duration.as_secs_f64());
                     })
@@ -829,7 +2641,7 @@ impl JobHandler {
         &self,
         job: &JobContext,
         status: JobStatus,
-        message: Option<&str>,
+        message: StatusMessage<'_>,
     ) -> Result<(), DvmError> {
         // Use encryption if the request was encrypted
         let keys = if job.encryption_type.is_encrypted() {
@@ -838,33 +2650,321 @@ impl JobHandler {
             None
         };
 
+        let text = message.text(job.lang);
         debug!(
             job_id = %job.event_id(),
             status = ?status,
-            message = ?message,
+            message = %text,
             "Sending status update"
         );
 
-        let event = build_status_event_with_eta_encrypted(
+        self.publish_status(job, status, Some(message.code()), &text);
+        job.log.event(format!("Status {}: {}", status.as_str(), text));
+
+        let event = build_status_event_with_context(
             job.event_id(),
             job.requester(),
             status,
-            message,
+            Some(&text),
             None,
             keys,
             None,
+            None,
             job.encryption_type,
+            Some(message.code()),
         );
         self.publisher.publish_for_job(event, &job.relays).await?;
         Ok(())
     }
 
-    async fn send_cashu_bid(
+    /// Price of a job in sats: the flat fee, the per-minute rate for its
+    /// (clipped) input length and the per-GB rate for its estimated output,
+    /// less any creator discount, plus `extra_sats`
+    async fn price_sats(&self, job: &JobContext, extra_sats: u64) -> u64 {
+        let settings = self.state.read().await.config.clone();
+        let (flat, per_minute, per_gb, creator_discount) = (
+            settings.price_sats_flat,
+            settings.price_sats_per_minute,
+            settings.price_sats_per_output_gb,
+            settings.creator_discount_percent,
+        );
+        if per_minute == 0 && per_gb == 0 {
+            return Self::discount(job, flat, creator_discount) + extra_sats;
+        }
+
+        // Only probe what the job itself would be allowed to read: local
+        // files in the allowlist, and public http(s) URLs that answer the
+        // HEAD request `validate_url_input` sends. Anything else is priced
+        // without metadata (one minute, one GB).
+        let dirs = &self.config.local_input_dirs;
+        let value = &job.input.value;
+        let probe = match job.input.input_type.as_str() {
+            "url" if is_file_url(value) => {
+                resolve_file_url(value, dirs).ok().map(|p| p.to_string_lossy().to_string())
+            }
+            "url" if is_public_http_url(value) && self.url_reachable(value).await => Some(value.clone()),
+            "path" => resolve_local_input(value, dirs).ok().map(|p| p.to_string_lossy().to_string()),
+            _ => None,
+        };
+        let metadata = match probe {
+            Some(input) => VideoMetadata::extract(&input, &self.config.ffprobe_path).await.ok(),
+            None => None,
+        };
+        let secs = metadata
+            .as_ref()
+            .and_then(|m| m.duration_secs())
+            .map(|source| job.clip.map_or(source, |clip| clip.length(source)));
+
+        let mut price = flat + price_for_duration(per_minute, secs);
+        if per_gb > 0 {
+            let hwaccel = self.processor.hwaccel();
+            let output_bytes = metadata.as_ref().zip(secs).and_then(|(metadata, secs)| {
+                let plan = EncodePlan::new(job, Some(metadata), &settings, hwaccel).ok()?;
+                let codec = Codec::from_encoder(hwaccel.video_encoder(job.codec));
+                estimate_output_bytes(metadata, &plan.renditions(job.mode), codec, secs)
+            });
+            price += price_for_output(per_gb, output_bytes);
+        }
+        Self::discount(job, price, creator_discount) + extra_sats
+    }
+
+    /// `price` after the creator discount, for jobs by the video's creator
+    fn discount(job: &JobContext, price: u64, percent: u8) -> u64 {
+        if job.creator == Some(true) {
+            creator::discounted(price, percent)
+        } else {
+            price
+        }
+    }
+
+    /// Whether the requester published the video event the job's input
+    /// belongs to (see [`creator`])
+    async fn check_creator(&self, job: &JobContext) -> bool {
+        let Some(id) = job.source_event else {
+            return false;
+        };
+        let Some(event) = self.publisher.fetch_event(id, &job.relays).await else {
+            debug!(job_id = %job.event_id(), event_id = %id, "Referenced video event not found");
+            return false;
+        };
+        let own = creator::is_own_video(&event, &job.requester(), &job.input.value);
+        if own {
+            info!(job_id = %job.event_id(), event_id = %id, "Requester is the video's creator");
+        }
+        own
+    }
+
+    /// Queue a received job, in the priority lane if it re-transcodes the
+    /// requester's own video and `creator_priority` is on.
+    ///
+    /// Jobs still to be checked are looked up in the background and come
+    /// back through the requeue channel, so relays never stall the loop.
+    /// Once `JOB_QUEUE_CAPACITY` jobs are waiting, new ones are turned away
+    /// as busy; a job that has to wait for a slot (`waiting`) is told how
+    /// many are ahead of it.
+    async fn admit(
+        self: &Arc<Self>,
+        mut job: JobContext,
+        lanes: &mut Lanes<PublicKey, JobContext>,
+        waiting: bool,
+    ) {
+        let (creator_priority, admin, min_price) = {
+            let state = self.state.read().await;
+            (
+                state.config.creator_priority,
+                state.config.admin_pubkey(),
+                min_price_sats(&job, &state.config),
+            )
+        };
+        if creator_priority && job.creator.is_none() && job.source_event.is_some() {
+            if let Some(requeue) = self.requeue.as_ref().and_then(|r| r.upgrade()) {
+                let handler = self.clone();
+                tokio::spawn(async move {
+                    job.creator = Some(handler.check_creator(&job).await);
+                    let _ = requeue.send(job).await;
+                });
+                return;
+            }
+        }
+        // Paid jobs and the operator's own go ahead of free ones, as do
+        // creators' re-transcodes under creator_priority
+        let priority = carries_payment(&job, min_price)
+            || admin == Some(job.requester())
+            || (creator_priority && job.creator == Some(true));
+
+        let queued = lanes.len();
+        if queued >= self.config.job_queue_capacity {
+            warn!(job_id = %job.event_id(), queued, "Job queue full, turning request away");
+            self.notify_queue(job, JobStatus::Error, StatusMessage::Busy { queued });
+            return;
+        }
+        if waiting {
+            let ahead = queued + self.state.read().await.cancel_signals.len();
+            self.notify_queue(job.clone(), JobStatus::Processing, StatusMessage::Queued { ahead });
+        }
+        lanes.push(job.requester(), job, priority);
+    }
+
+    /// Whether the request names us, or was approved after a bid
+    fn is_for_us(&self, job: &JobContext) -> bool {
+        let my_pubkey = self.config.nostr_keys.public_key().to_hex();
+        job.approved
+            || job.request.tags.iter().any(|t| {
+                let parts = t.as_slice();
+                parts.len() >= 2 && parts[0] == "p" && parts[1] == my_pubkey
+            })
+    }
+
+    /// Send a queue status in the background. Public requests other DVMs
+    /// may take, and requests we'd ignore anyway, get no answer.
+    fn notify_queue(self: &Arc<Self>, job: JobContext, status: JobStatus, message: StatusMessage<'static>) {
+        if !self.is_for_us(&job) {
+            return;
+        }
+        let handler = self.clone();
+        tokio::spawn(async move {
+            let ignored = {
+                let state = handler.state.read().await;
+                state.is_paused() || state.config.is_blocked(&job.requester())
+            };
+            if ignored {
+                return;
+            }
+            if let Err(e) = handler.send_status(&job, status, message).await {
+                debug!(job_id = %job.event_id(), error = %e, "Failed to send queue status");
+            }
+        });
+    }
+
+    /// Ask the requester to pay for the job.
+    ///
+    /// With a Lightning backend the status carries a bolt11 invoice as well
+    /// as the Cashu terms, and the job is queued again once the invoice is
+    /// paid. If no invoice can be issued, Cashu is offered alone.
+    async fn request_payment(&self, job: JobContext, amount_sats: u64) -> Result<(), DvmError> {
+        let Some(lightning) = self.lightning.clone() else {
+            return self
+                .send_payment_terms(&job, amount_sats, None, StatusMessage::PaymentRequired)
+                .await;
+        };
+
+        let description = format!("nostube-transcode job {}", &job.event_id().to_hex()[..16]);
+        let invoice = match lightning.create_invoice(amount_sats * 1000, &description).await {
+            Ok(invoice) => invoice,
+            Err(e) => {
+                warn!(job_id = %job.event_id(), backend = lightning.name(), error = %e, "Failed to create invoice");
+                return self
+                    .send_payment_terms(&job, amount_sats, None, StatusMessage::PaymentRequired)
+                    .await;
+            }
+        };
+
+        self.send_payment_terms(
+            &job,
+            amount_sats,
+            Some(invoice.bolt11.clone()),
+            StatusMessage::PaymentRequired,
+        )
+        .await?;
+        self.watch_invoice(lightning, job, invoice).await;
+        Ok(())
+    }
+
+    /// Resume polling invoices that were open at the last shutdown
+    pub async fn resume_invoices(&self, invoices: Vec<OpenInvoice>) {
+        let Some(lightning) = self.lightning.clone() else {
+            if !invoices.is_empty() {
+                warn!(count = invoices.len(), "No Lightning backend, invoices from the last run won't be checked");
+            }
+            return;
+        };
+        for open in invoices {
+            info!(job_id = %open.context.event_id(), "Resuming invoice from the last run");
+            self.watch_invoice(lightning.clone(), open.context, open.invoice).await;
+        }
+    }
+
+    /// Poll `invoice` until it is paid or expires; a paid job is queued again.
+    ///
+    /// The invoice stays in the state's `open_invoices` meanwhile, so a
+    /// shutdown snapshot can pick it up.
+    async fn watch_invoice(&self, lightning: Arc<dyn LightningBackend>, mut job: JobContext, invoice: Invoice) {
+        let Some(requeue) = self.requeue.clone() else {
+            return;
+        };
+        self.state.write().await.open_invoices.insert(
+            job.event_id(),
+            OpenInvoice {
+                context: job.clone(),
+                invoice: invoice.clone(),
+            },
+        );
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            let job_id = job.event_id();
+            let mut poll = interval(PAYMENT_POLL_INTERVAL);
+            loop {
+                poll.tick().await;
+                if Timestamp::now().as_u64() > invoice.expires_at {
+                    info!(job_id = %job_id, "Invoice expired unpaid");
+                    state.write().await.open_invoices.remove(&job_id);
+                    return;
+                }
+                match lightning.is_paid(&invoice).await {
+                    Ok(true) => break,
+                    Ok(false) => {}
+                    Err(e) => debug!(job_id = %job_id, error = %e, "Failed to check invoice"),
+                }
+            }
+
+            info!(job_id = %job_id, sats = invoice.amount_msats / 1000, "Invoice paid, queueing job");
+            job.paid_sats = Some(invoice.amount_msats / 1000);
+            // Shutting down: the invoice stays open and is found paid after the restart
+            let Some(jobs) = requeue.upgrade() else {
+                return;
+            };
+            match jobs.send(job).await {
+                Ok(()) => {
+                    state.write().await.open_invoices.remove(&job_id);
+                }
+                Err(e) => error!(job_id = %job_id, "Failed to queue paid job: {}", e),
+            }
+        });
+    }
+
+    /// Sign a receipt for a paid job, keep it in the ledger and DM it to the
+    /// requester. Failures are logged; the job itself is already done.
+    async fn send_receipt(&self, job: &JobContext, receipt: Receipt) {
+        let job_id = receipt.job_id;
+        let event = match receipt.to_event(&self.config.nostr_keys) {
+            Ok(event) => event,
+            Err(e) => {
+                warn!(job_id = %job_id, error = %e, "Failed to sign receipt");
+                return;
+            }
+        };
+        if let Some(ledger) = &self.receipts {
+            if let Err(e) = ledger.append(&event) {
+                warn!(job_id = %job_id, error = %e, "Failed to record receipt");
+            }
+        }
+        match self
+            .publisher
+            .send_private_msg(receipt.requester, event.as_json(), &job.relays)
+            .await
+        {
+            Ok(_) => info!(job_id = %job_id, sats = receipt.amount_sats, "Receipt sent"),
+            Err(e) => warn!(job_id = %job_id, error = %e, "Failed to send receipt"),
+        }
+    }
+
+    /// Send a bid or payment request with the job's price
+    async fn send_payment_terms(
         &self,
         job: &JobContext,
-        mint: &str,
         amount_sats: u64,
-        message: Option<&str>,
+        bolt11: Option<String>,
+        message: StatusMessage<'_>,
     ) -> Result<(), DvmError> {
         let keys = if job.encryption_type.is_encrypted() {
             Some(&self.config.nostr_keys)
@@ -872,28 +2972,42 @@ impl JobHandler {
             None
         };
 
-        let context = CashuContext {
-            mint: mint.to_string(),
+        let context = PaymentContext {
+            mint: CASHU_MINT_URL.to_string(),
             amount_sats,
+            bolt11,
         };
 
         let event = build_status_event_with_context(
             job.event_id(),
             job.requester(),
             JobStatus::PaymentRequired,
-            message,
+            Some(&message.text(job.lang)),
             None,
             keys,
             Some(context),
             None,
             job.encryption_type,
+            Some(message.code()),
         );
 
         self.publisher.publish_for_job(event, &job.relays).await?;
         Ok(())
     }
 
+    /// Mirror a status event to the dashboard's live updates
+    fn publish_status(&self, job: &JobContext, status: JobStatus, code: Option<&str>, message: &str) {
+        self.updates.publish(JobUpdate::Status {
+            job_id: job.event_id().to_hex(),
+            status: status.as_str().to_string(),
+            code: code.map(str::to_string),
+            // The stream is unauthenticated; encrypted requests keep their details private
+            message: (!job.encryption_type.is_encrypted()).then(|| message.to_string()),
+        });
+    }
+
     async fn send_error(&self, job: &JobContext, message: &str) -> Result<(), DvmError> {
+        self.publish_status(job, JobStatus::Error, None, message);
         // Use encryption if the request was encrypted
         let keys = if job.encryption_type.is_encrypted() {
             Some(&self.config.nostr_keys)
@@ -914,43 +3028,6 @@ impl JobHandler {
         Err(DvmError::JobRejected(message.to_string()))
     }
 
-    /// Verifies a Cashu token with a mint.
-    async fn verify_cashu_token(&self, token_str: &str, required_sats: u64, expected_mint: &str) -> Result<(), String> {
-        let token = Token::from_str(token_str).map_err(|e| format!("Invalid Cashu token: {}", e))?;
-        
-        let mut total_amount = Amount::ZERO;
-
-        match token {
-            Token::TokenV3(v3) => {
-                for token_proofs in &v3.token {
-                    if token_proofs.mint.to_string() != expected_mint {
-                        return Err(format!("Unexpected mint in V3: {} (expected {})", token_proofs.mint, expected_mint));
-                    }
-                    for proof in &token_proofs.proofs {
-                        total_amount += proof.amount;
-                    }
-                }
-            }
-            Token::TokenV4(v4) => {
-                if v4.mint_url.to_string() != expected_mint {
-                    return Err(format!("Unexpected mint in V4: {} (expected {})", v4.mint_url, expected_mint));
-                }
-                for token_v4 in &v4.token {
-                    for proof in &token_v4.proofs {
-                        total_amount += proof.amount;
-                    }
-                }
-            }
-        }
-
-        if total_amount < Amount::from(required_sats) {
-            return Err(format!("Insufficient amount: {} (required {})", total_amount, required_sats));
-        }
-
-        // TODO: Contact the mint to verify the proofs are still valid (not spent)
-        Ok(())
-    }
-
     /// Get encryption keys if the job was encrypted
     fn get_encryption_keys(&self, job: &JobContext) -> Option<&Keys> {
         if job.encryption_type.is_encrypted() {
@@ -991,8 +3068,30 @@ where
     result
 }
 
+/// Check a downloaded input against the SHA-256 the requester expects
+async fn verify_input(path: &std::path::Path, expected: Option<String>) -> Result<(), VideoError> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+    let actual = crate::util::hash_file(path).await?;
+    expect_sha256(Some(expected), &actual)
+}
+
+/// Compare a computed SHA-256 with the one the requester expects, if any
+fn expect_sha256(expected: Option<String>, actual: &str) -> Result<(), VideoError> {
+    match expected {
+        Some(expected) if !expected.eq_ignore_ascii_case(actual) => {
+            Err(VideoError::InputHashMismatch {
+                expected,
+                actual: actual.to_string(),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
 /// Format duration in seconds to human-readable string
-fn format_duration(secs: u64) -> String {
+pub(crate) fn format_duration(secs: u64) -> String {
     if secs == 0 {
         "< 1s".to_string()
     } else if secs < 60 {
@@ -1015,3 +3114,297 @@ fn format_duration(secs: u64) -> String {
         }
     }
 }
+
+/// The least `job` can cost before its input is probed: the flat fee, one
+/// started minute and one started GB of output (see `JobHandler::price_sats`)
+fn min_price_sats(job: &JobContext, config: &RemoteConfig) -> u64 {
+    let price = config.price_sats_flat
+        + price_for_duration(config.price_sats_per_minute, None)
+        + price_for_output(config.price_sats_per_output_gb, None);
+    JobHandler::discount(job, price, config.creator_discount_percent)
+}
+
+/// Whether a job comes with payment: a paid invoice, or a Cashu token for
+/// our mint worth at least `price_sats`. Tokens are only redeemed once the
+/// job starts, so a spent one gets its job ahead in the queue but fails
+/// right after.
+fn carries_payment(job: &JobContext, price_sats: u64) -> bool {
+    job.paid_sats.is_some()
+        || job.cashu_token.as_deref().is_some_and(|token| {
+            cashu::check_token(token, price_sats.max(1), CASHU_MINT_URL).is_ok()
+        })
+}
+
+/// Remove the hashes in `early_uploads` that aren't among the final
+/// `segment_hashes` and return them: segments uploaded during an encode
+/// attempt whose output was replaced
+fn superseded_uploads(early_uploads: &mut HashSet<String>, segment_hashes: &[String]) -> Vec<String> {
+    let kept: HashSet<&str> = segment_hashes.iter().map(String::as_str).collect();
+    let superseded: Vec<String> = early_uploads
+        .iter()
+        .filter(|sha256| !kept.contains(sha256.as_str()))
+        .cloned()
+        .collect();
+    early_uploads.retain(|sha256| kept.contains(sha256.as_str()));
+    superseded
+}
+
+/// The input `archive_source` uploads: what the job encoded, except for
+/// `archive` inputs, whose source is the archive at the requested URL rather
+/// than the video assembled from it
+fn source_to_archive<'a>(job: &'a JobContext, requested_input: &'a DvmInput) -> &'a str {
+    if job.input.input_type == "archive" {
+        &requested_input.value
+    } else {
+        &job.input.value
+    }
+}
+
+/// MIME type for a source file, guessed from its extension
+fn source_mime_type(path: &std::path::Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match ext.as_deref() {
+        Some("mp4" | "m4v") => "video/mp4",
+        Some("mov") => "video/quicktime",
+        Some("webm") => "video/webm",
+        Some("mkv") => "video/x-matroska",
+        Some("avi") => "video/x-msvideo",
+        Some("ts") => "video/mp2t",
+        Some("zip") => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Find a reason this DVM can't serve the request at all, if any.
+///
+/// A codec is only checked when the requester asked for it explicitly;
+/// otherwise the encoder fallback (e.g. AV1 -> HEVC) is acceptable.
+pub(crate) fn capability_gap(
+    job: &JobContext,
+    config: &Config,
+    hwaccel: HwAccel,
+) -> Option<(DeclineReason, String)> {
+    let local = match job.input.input_type.as_str() {
+        "url" => is_file_url(&job.input.value),
+        "path" => true,
+        "archive" => false,
+        input_type if event_input::is_event_input(input_type) => false,
+        other => {
+            return Some((
+                DeclineReason::UnsupportedInput,
+                format!("Input type {} is not supported", other),
+            ))
+        }
+    };
+    if local && config.local_input_dirs.is_empty() {
+        return Some((
+            DeclineReason::UnsupportedInput,
+            "Local path inputs are not enabled on this DVM".to_string(),
+        ));
+    }
+
+    if job.mode == OutputMode::Hls
+        && job.encryption
+        && job.has_param("encryption_scheme")
+        && job.encryption_scheme.is_none()
+    {
+        return Some((
+            DeclineReason::UnsupportedEncryption,
+            format!(
+                "Only {} encryption is supported",
+                EncryptionScheme::default().as_str()
+            ),
+        ));
+    }
+
+    if job.mode == OutputMode::Thumbnail {
+        return None;
+    }
+
+    if ["start", "end", "duration"].iter().any(|p| job.has_param(p)) && job.clip.is_none() {
+        return Some((
+            DeclineReason::InvalidClip,
+            "Clip needs a start before its end (or a positive duration), in seconds or HH:MM:SS"
+                .to_string(),
+        ));
+    }
+
+    if job.mode == OutputMode::Hls {
+        if let Err(e) = job.segmenting() {
+            return Some((DeclineReason::InvalidSegmenting, e));
+        }
+    }
+
+    if job.has_param("watermark") && job.watermark.is_none() {
+        return Some((
+            DeclineReason::InvalidWatermark,
+            "Watermark must be an http(s) image URL or at most 100 characters of text"
+                .to_string(),
+        ));
+    }
+
+    let encoded_codec = Codec::from_encoder(hwaccel.video_encoder(job.codec));
+    if job.has_param("codec") && encoded_codec != job.codec {
+        return Some((
+            DeclineReason::UnsupportedCodec,
+            format!("{} encoding is not available on this DVM", job.codec.friendly_name()),
+        ));
+    }
+
+    // AV1 and VP9 need fMP4 segments, which FFmpeg can't encrypt
+    if job.mode == OutputMode::Hls
+        && job.encryption
+        && matches!(encoded_codec, Codec::AV1 | Codec::Vp9)
+    {
+        return Some((
+            DeclineReason::UnsupportedEncryption,
+            format!(
+                "Encrypted HLS is not available for {}, set encryption=false",
+                encoded_codec.friendly_name()
+            ),
+        ));
+    }
+
+    if job.mode == OutputMode::Mp4
+        && job.container.is_some_and(|c| !c.supports(encoded_codec))
+    {
+        return Some((
+            DeclineReason::UnsupportedCodec,
+            format!(
+                "WebM output needs VP9 or AV1, not {}",
+                encoded_codec.friendly_name()
+            ),
+        ));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dvm::dry_run::job_from_params;
+    use crate::dvm::encryption::EncryptionType;
+    use crate::payments::cashu::test_token;
+    use std::collections::BTreeMap;
+
+    fn job(cashu_token: Option<String>) -> JobContext {
+        let settings = RemoteConfig::default();
+        let mut job =
+            job_from_params("https://example.com/video.mp4", &BTreeMap::new(), &settings).unwrap();
+        job.cashu_token = cashu_token;
+        job
+    }
+
+    #[test]
+    fn test_carries_payment() {
+        assert!(!carries_payment(&job(None), 0));
+
+        let mut paid = job(None);
+        paid.paid_sats = Some(50);
+        assert!(carries_payment(&paid, 50));
+
+        // Tokens have to cover what the job costs at least
+        let token = job(Some(test_token(CASHU_MINT_URL, &[8, 2])));
+        assert!(carries_payment(&token, 0));
+        assert!(carries_payment(&token, 10));
+        assert!(!carries_payment(&token, 11));
+
+        let foreign = job(Some(test_token("https://other.example.com", &[64])));
+        assert!(!carries_payment(&foreign, 10));
+        assert!(!carries_payment(&job(Some("cashuBnope".to_string())), 0));
+    }
+
+    #[test]
+    fn test_source_to_archive() {
+        let mut job = job(None);
+        let requested = job.input.clone();
+        // Fetched inputs are archived from the local copy
+        job.input.value = "/tmp/job/source".to_string();
+        assert_eq!(source_to_archive(&job, &requested), "/tmp/job/source");
+
+        // Archives are replaced by the assembled video, which isn't the source
+        let mut requested = requested;
+        requested.value = "https://example.com/slides.zip".to_string();
+        requested.input_type = "archive".to_string();
+        job.input.input_type = "archive".to_string();
+        job.input.value = "/tmp/job/slideshow.mp4".to_string();
+        assert_eq!(source_to_archive(&job, &requested), "https://example.com/slides.zip");
+        assert_eq!(
+            source_mime_type(std::path::Path::new("https://example.com/slides.zip")),
+            "application/zip"
+        );
+    }
+
+    #[test]
+    fn test_superseded_uploads() {
+        let hashes = |list: &[&str]| -> Vec<String> { list.iter().map(|h| h.to_string()).collect() };
+        let mut early: HashSet<String> = hashes(&["hw0", "hw1", "same"]).into_iter().collect();
+        let mut superseded = superseded_uploads(&mut early, &hashes(&["sw0", "same", "sw2"]));
+        superseded.sort();
+        assert_eq!(superseded, hashes(&["hw0", "hw1"]));
+        assert_eq!(early, hashes(&["same"]).into_iter().collect());
+
+        let mut early: HashSet<String> = hashes(&["a"]).into_iter().collect();
+        assert!(superseded_uploads(&mut early, &hashes(&["a", "b"])).is_empty());
+        assert_eq!(early.len(), 1);
+    }
+
+    #[test]
+    fn test_declines_unsupported_encryption_scheme() {
+        let config = Config::from_env(
+            Keys::generate(),
+            std::path::PathBuf::from("ffmpeg"),
+            std::path::PathBuf::from("ffprobe"),
+        );
+        let gap = |scheme: Option<&str>| {
+            let mut params = BTreeMap::from([("mode".to_string(), "hls".to_string())]);
+            if let Some(scheme) = scheme {
+                params.insert("encryption_scheme".to_string(), scheme.to_string());
+            }
+            let settings = RemoteConfig::default();
+            let job = job_from_params("https://example.com/video.mp4", &params, &settings).unwrap();
+            capability_gap(&job, &config, HwAccel::Software)
+        };
+        assert!(gap(None).is_none());
+        assert!(gap(Some("aes-128")).is_none());
+
+        let (reason, message) = gap(Some("sample-aes")).unwrap();
+        let event = build_decline_event(
+            EventId::all_zeros(),
+            Keys::generate().public_key(),
+            reason,
+            &message,
+            None,
+            EncryptionType::None,
+        )
+        .to_event(&Keys::generate())
+        .unwrap();
+        let reason_tag = event
+            .tags
+            .iter()
+            .find(|t| t.as_slice()[0] == "reason")
+            .map(|t| t.as_slice()[1].clone());
+        assert_eq!(reason_tag.as_deref(), Some("unsupported-encryption"));
+        assert_eq!(event.content, "Only aes-128 encryption is supported");
+    }
+
+    #[test]
+    fn test_min_price_sats() {
+        let mut config = RemoteConfig::default();
+        assert_eq!(min_price_sats(&job(None), &config), 0);
+
+        config.price_sats_flat = 10;
+        config.price_sats_per_minute = 5;
+        config.price_sats_per_output_gb = 20;
+        assert_eq!(min_price_sats(&job(None), &config), 35);
+
+        config.creator_discount_percent = 100;
+        let mut creator = job(None);
+        creator.creator = Some(true);
+        assert_eq!(min_price_sats(&creator, &config), 0);
+    }
+}
@@ -0,0 +1,117 @@
+//! Liveness signal of the job processing loop.
+//!
+//! The HTTP server keeps answering even when the job loop is stuck, so it
+//! says nothing about whether jobs are being processed. The loop instead
+//! records a heartbeat every [`HEARTBEAT_INTERVAL`], busy or idle, which is
+//! written to `heartbeat.json` in the data directory and served at
+//! `/api/heartbeat`. A watchdog (systemd, monit, a cron job) treats a beat
+//! older than [`STALE_AFTER`] as a wedged loop.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+/// How often the job loop records a beat
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Age after which a beat means the loop is no longer running
+pub const STALE_AFTER: Duration = Duration::from_secs(3 * HEARTBEAT_INTERVAL.as_secs());
+
+/// The most recent beat
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HeartbeatSnapshot {
+    /// Process ID of the DVM
+    pub pid: u32,
+    /// Unix time of the last beat (`None` before the loop started)
+    pub last_tick: Option<u64>,
+    /// IDs of the jobs running at the last beat
+    pub active_jobs: Vec<String>,
+    /// Jobs waiting for a slot at the last beat
+    pub queued: usize,
+    /// Seconds between beats
+    pub interval_secs: u64,
+}
+
+impl HeartbeatSnapshot {
+    /// Whether the loop has missed its beats as of `now` (Unix time)
+    pub fn is_stale(&self, now: u64) -> bool {
+        self.last_tick
+            .is_none_or(|tick| now.saturating_sub(tick) > STALE_AFTER.as_secs())
+    }
+}
+
+/// Heartbeat shared between the job loop and the HTTP server
+#[derive(Debug, Clone, Default)]
+pub struct Heartbeat {
+    latest: Arc<Mutex<HeartbeatSnapshot>>,
+    /// File the beat is mirrored to (`None` keeps it in memory only)
+    file: Option<PathBuf>,
+}
+
+impl Heartbeat {
+    pub fn new(file: Option<PathBuf>) -> Self {
+        Self {
+            latest: Arc::new(Mutex::new(HeartbeatSnapshot::default())),
+            file,
+        }
+    }
+
+    /// Record a beat and mirror it to the heartbeat file.
+    ///
+    /// The file is replaced atomically, so readers never see a partial write.
+    pub async fn beat(&self, active_jobs: Vec<String>, queued: usize) {
+        let snapshot = HeartbeatSnapshot {
+            pid: std::process::id(),
+            last_tick: Some(chrono::Utc::now().timestamp().max(0) as u64),
+            active_jobs,
+            queued,
+            interval_secs: HEARTBEAT_INTERVAL.as_secs(),
+        };
+        *self.latest.lock().unwrap() = snapshot.clone();
+
+        let Some(path) = &self.file else {
+            return;
+        };
+        let tmp = path.with_extension("json.tmp");
+        let json = serde_json::to_vec_pretty(&snapshot).unwrap_or_default();
+        let result = async {
+            tokio::fs::write(&tmp, json).await?;
+            tokio::fs::rename(&tmp, path).await
+        }
+        .await;
+        if let Err(e) = result {
+            warn!(path = %path.display(), error = %e, "Failed to write heartbeat file");
+        }
+    }
+
+    /// The most recent beat
+    pub fn snapshot(&self) -> HeartbeatSnapshot {
+        self.latest.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_beat_writes_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("heartbeat.json");
+        let heartbeat = Heartbeat::new(Some(path.clone()));
+        assert!(heartbeat.snapshot().is_stale(0));
+
+        heartbeat.beat(vec!["abc".to_string()], 2).await;
+        let written: HeartbeatSnapshot =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written, heartbeat.snapshot());
+        assert_eq!(written.active_jobs, vec!["abc"]);
+        assert_eq!(written.queued, 2);
+
+        let tick = written.last_tick.unwrap();
+        assert!(!written.is_stale(tick + STALE_AFTER.as_secs()));
+        assert!(written.is_stale(tick + STALE_AFTER.as_secs() + 1));
+    }
+}
@@ -0,0 +1,351 @@
+//! Operator hooks run at job milestones.
+//!
+//! Hooks let operators add their own steps to the pipeline (virus scanning
+//! an input, checking outputs, notifying another system) without forking.
+//! Each hook is called with a [`HookContext`] describing the job at three
+//! points:
+//!
+//! - `pre-encode`: before the input is probed and transcoded
+//! - `post-encode`: once MP4/HLS output is encoded, before it is uploaded,
+//!   with the output files
+//! - `pre-publish`: before the result event is published, with the result
+//!
+//! A hook that fails fails the job with its reason. Embedders register
+//! their own [`JobHook`]s on the job handler; operators can configure an
+//! external command with `HOOK_COMMAND` (see [`CommandHook`]).
+
+use futures::future::BoxFuture;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{debug, warn};
+
+use crate::dvm::events::{DvmResult, JobContext};
+use crate::error::HookError;
+
+/// Milestone a hook runs at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookStage {
+    PreEncode,
+    PostEncode,
+    PrePublish,
+}
+
+impl HookStage {
+    pub fn all() -> [Self; 3] {
+        [Self::PreEncode, Self::PostEncode, Self::PrePublish]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::PreEncode => "pre-encode",
+            Self::PostEncode => "post-encode",
+            Self::PrePublish => "pre-publish",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::all().into_iter().find(|stage| stage.as_str() == s.trim())
+    }
+}
+
+/// What a hook is told about the job
+#[derive(Debug, Clone, Serialize)]
+pub struct HookContext {
+    pub stage: HookStage,
+    pub job_id: String,
+    /// Requester pubkey (hex)
+    pub requester: String,
+    /// Input as FFmpeg reads it: a URL or a local path
+    pub input: String,
+    pub input_type: String,
+    pub mode: String,
+    /// Encoded files awaiting upload (`post-encode` only)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<PathBuf>,
+    /// Result about to be published (`pre-publish` only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+}
+
+impl HookContext {
+    pub fn new(stage: HookStage, job: &JobContext) -> Self {
+        Self {
+            stage,
+            job_id: job.event_id().to_hex(),
+            requester: job.requester().to_hex(),
+            input: job.input.value.clone(),
+            input_type: job.input.input_type.clone(),
+            mode: job.mode.as_str().to_string(),
+            files: Vec::new(),
+            result: None,
+        }
+    }
+
+    pub fn with_files(mut self, files: Vec<PathBuf>) -> Self {
+        self.files = files;
+        self
+    }
+
+    pub fn with_result(mut self, result: &DvmResult) -> Self {
+        self.result = serde_json::to_value(result).ok();
+        self
+    }
+}
+
+/// A step run at job milestones.
+pub trait JobHook: Send + Sync {
+    /// Short identifier used in logs and error messages
+    fn name(&self) -> &str;
+
+    /// Whether the hook wants to run at `stage`
+    fn runs_at(&self, stage: HookStage) -> bool;
+
+    /// Run the hook; an `Err` with a reason fails the job.
+    fn run<'a>(&'a self, ctx: &'a HookContext) -> BoxFuture<'a, Result<(), String>>;
+}
+
+/// The hooks a job handler runs, in registration order.
+#[derive(Default)]
+pub struct HookRegistry {
+    hooks: Vec<Box<dyn JobHook>>,
+}
+
+impl HookRegistry {
+    /// Build a registry with the hooks configured in the environment.
+    pub fn new() -> Self {
+        let mut registry = Self::default();
+        if let Some(hook) = CommandHook::from_env() {
+            registry.register(Box::new(hook));
+        }
+        registry
+    }
+
+    pub fn register(&mut self, hook: Box<dyn JobHook>) {
+        self.hooks.push(hook);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Whether any hook runs at `stage`, so callers can skip building a context
+    pub fn wants(&self, stage: HookStage) -> bool {
+        self.hooks.iter().any(|h| h.runs_at(stage))
+    }
+
+    /// Run the hooks for the context's stage, stopping at the first failure.
+    pub async fn run(&self, ctx: &HookContext) -> Result<(), HookError> {
+        for hook in self.hooks.iter().filter(|h| h.runs_at(ctx.stage)) {
+            debug!(hook = hook.name(), stage = ctx.stage.as_str(), job_id = %ctx.job_id, "Running hook");
+            if let Err(reason) = hook.run(ctx).await {
+                warn!(hook = hook.name(), stage = ctx.stage.as_str(), job_id = %ctx.job_id, reason = %reason, "Hook failed");
+                return Err(HookError {
+                    hook: hook.name().to_string(),
+                    stage: ctx.stage.as_str(),
+                    reason,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Default time an external hook may take
+const DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Runs an external program as a hook.
+///
+/// The program is called with the stage as its only argument and the
+/// [`HookContext`] as JSON on stdin; `NOSTUBE_HOOK_STAGE` and
+/// `NOSTUBE_JOB_ID` are set as well. Exiting non-zero fails the job, with
+/// the last line the program wrote to stderr as the reason.
+pub struct CommandHook {
+    program: PathBuf,
+    stages: Vec<HookStage>,
+    timeout: Duration,
+}
+
+impl CommandHook {
+    pub fn new(program: PathBuf, stages: Vec<HookStage>, timeout: Duration) -> Self {
+        Self {
+            program,
+            stages,
+            timeout,
+        }
+    }
+
+    /// Configure from `HOOK_COMMAND`, `HOOK_STAGES` (comma-separated,
+    /// default all) and `HOOK_TIMEOUT_SECS` (default 600)
+    pub fn from_env() -> Option<Self> {
+        let program = std::env::var("HOOK_COMMAND")
+            .ok()
+            .filter(|s| !s.trim().is_empty())?;
+        let stages = match std::env::var("HOOK_STAGES") {
+            Ok(s) => s
+                .split(',')
+                .filter(|s| !s.trim().is_empty())
+                .filter_map(|s| {
+                    let stage = HookStage::parse(s);
+                    if stage.is_none() {
+                        warn!(stage = %s, "Ignoring unknown hook stage");
+                    }
+                    stage
+                })
+                .collect(),
+            Err(_) => HookStage::all().to_vec(),
+        };
+        let timeout = std::env::var("HOOK_TIMEOUT_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_HOOK_TIMEOUT);
+        Some(Self::new(PathBuf::from(program), stages, timeout))
+    }
+
+    async fn execute(&self, ctx: &HookContext) -> Result<(), String> {
+        let input = serde_json::to_vec(ctx).map_err(|e| e.to_string())?;
+        let mut child = Command::new(&self.program)
+            .arg(ctx.stage.as_str())
+            .env("NOSTUBE_HOOK_STAGE", ctx.stage.as_str())
+            .env("NOSTUBE_JOB_ID", &ctx.job_id)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("failed to start {}: {}", self.program.display(), e))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            // A hook that doesn't read its stdin closes the pipe early
+            let _ = stdin.write_all(&input).await;
+        }
+
+        let output = match tokio::time::timeout(self.timeout, child.wait_with_output()).await {
+            Ok(output) => output.map_err(|e| e.to_string())?,
+            Err(_) => return Err(format!("timed out after {}s", self.timeout.as_secs())),
+        };
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(stderr
+            .lines()
+            .map(str::trim)
+            .rfind(|l| !l.is_empty())
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("exited with {}", output.status)))
+    }
+}
+
+impl JobHook for CommandHook {
+    fn name(&self) -> &str {
+        self.program
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("hook command")
+    }
+
+    fn runs_at(&self, stage: HookStage) -> bool {
+        self.stages.contains(&stage)
+    }
+
+    fn run<'a>(&'a self, ctx: &'a HookContext) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(self.execute(ctx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr_sdk::prelude::*;
+
+    fn context(stage: HookStage) -> HookContext {
+        let tags = vec![Tag::custom(
+            TagKind::Custom("i".into()),
+            vec!["https://example.com/video.mp4".to_string(), "url".to_string()],
+        )];
+        let event = EventBuilder::new(crate::dvm::DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+            .to_event(&Keys::generate())
+            .unwrap();
+        HookContext::new(stage, &JobContext::from_event(event).unwrap())
+    }
+
+    fn script(dir: &std::path::Path, body: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join("hook.sh");
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_stage_names() {
+        for stage in HookStage::all() {
+            assert_eq!(HookStage::parse(stage.as_str()), Some(stage));
+            assert_eq!(
+                serde_json::to_value(stage).unwrap(),
+                serde_json::json!(stage.as_str())
+            );
+        }
+        assert_eq!(HookStage::parse("post-upload"), None);
+    }
+
+    #[tokio::test]
+    async fn test_command_hook_receives_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let seen = dir.path().join("seen.json");
+        let program = script(dir.path(), &format!("echo \"$1\" > {0}.stage\ncat > {0}", seen.display()));
+        let mut registry = HookRegistry::default();
+        registry.register(Box::new(CommandHook::new(
+            program,
+            vec![HookStage::PostEncode],
+            DEFAULT_HOOK_TIMEOUT,
+        )));
+
+        // Stages the hook didn't ask for don't run it
+        registry.run(&context(HookStage::PreEncode)).await.unwrap();
+        assert!(!seen.exists());
+
+        let ctx = context(HookStage::PostEncode).with_files(vec![PathBuf::from("/tmp/out.mp4")]);
+        registry.run(&ctx).await.unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&seen).unwrap()).unwrap();
+        assert_eq!(json["stage"], "post-encode");
+        assert_eq!(json["job_id"], ctx.job_id);
+        assert_eq!(json["files"][0], "/tmp/out.mp4");
+        assert!(json.get("result").is_none());
+        let stage = std::fs::read_to_string(format!("{}.stage", seen.display())).unwrap();
+        assert_eq!(stage.trim(), "post-encode");
+    }
+
+    #[tokio::test]
+    async fn test_command_hook_failure_fails_job() {
+        let dir = tempfile::tempdir().unwrap();
+        let program = script(dir.path(), "echo scanning >&2\necho 'EICAR test file found' >&2\nexit 3");
+        let mut registry = HookRegistry::default();
+        registry.register(Box::new(CommandHook::new(
+            program,
+            HookStage::all().to_vec(),
+            DEFAULT_HOOK_TIMEOUT,
+        )));
+
+        let err = registry.run(&context(HookStage::PreEncode)).await.unwrap_err();
+        assert_eq!(err.hook, "hook.sh");
+        assert_eq!(err.stage, "pre-encode");
+        assert_eq!(err.reason, "EICAR test file found");
+    }
+
+    #[tokio::test]
+    async fn test_command_hook_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let program = script(dir.path(), "sleep 5");
+        let hook = CommandHook::new(program, HookStage::all().to_vec(), Duration::from_millis(100));
+        let err = hook.run(&context(HookStage::PrePublish)).await.unwrap_err();
+        assert!(err.contains("timed out"), "{}", err);
+    }
+}
@@ -0,0 +1,153 @@
+//! Idempotency keys for job requests.
+//!
+//! A client that loses its connection after publishing a request can't tell
+//! whether the DVM saw it, so it publishes again, and without a key each
+//! copy is a separate encode. With `["param", "idempotency_key", "<key>"]`
+//! the first request claims the key for its requester; retries while it runs
+//! are pointed at the original job, and retries after it finished get the
+//! original result again. Failed jobs release their key so a retry can run.
+
+use nostr_sdk::prelude::*;
+use std::collections::HashMap;
+
+use crate::dvm::events::DvmResult;
+
+/// How long a key is remembered after it was claimed
+pub const IDEMPOTENCY_WINDOW_SECS: u64 = 24 * 3600;
+
+/// Longest key accepted
+pub const MAX_KEY_LEN: usize = 128;
+
+#[derive(Debug, Clone)]
+struct Entry {
+    job_id: EventId,
+    claimed_at: u64,
+    /// Result and its expiry once the job succeeded
+    result: Option<(DvmResult, Timestamp)>,
+}
+
+/// Outcome of claiming a key
+#[derive(Debug, Clone)]
+pub enum Claim {
+    /// First use of the key (or the job that holds it), go ahead
+    New,
+    /// The key belongs to a job that is still running
+    Running(EventId),
+    /// The key belongs to a job that already succeeded
+    Completed {
+        job_id: EventId,
+        result: Box<DvmResult>,
+        expires_at: Timestamp,
+    },
+}
+
+/// Keys claimed per requester within the window
+#[derive(Debug, Default)]
+pub struct IdempotencyLedger {
+    entries: HashMap<(PublicKey, String), Entry>,
+}
+
+impl IdempotencyLedger {
+    /// Claim `key` for `job_id`, or report the job that already holds it.
+    pub fn claim(&mut self, requester: PublicKey, key: &str, job_id: EventId, now: u64) -> Claim {
+        self.entries
+            .retain(|_, e| now < e.claimed_at + IDEMPOTENCY_WINDOW_SECS);
+
+        let entry = self
+            .entries
+            .entry((requester, key.to_string()))
+            .or_insert(Entry {
+                job_id,
+                claimed_at: now,
+                result: None,
+            });
+        match &entry.result {
+            // Paid jobs come back through the queue with the same id
+            _ if entry.job_id == job_id => Claim::New,
+            None => Claim::Running(entry.job_id),
+            Some((result, expires_at)) => Claim::Completed {
+                job_id: entry.job_id,
+                result: Box::new(result.clone()),
+                expires_at: *expires_at,
+            },
+        }
+    }
+
+    /// Remember the result of the job holding `key`.
+    pub fn complete(&mut self, requester: PublicKey, key: &str, result: DvmResult, expires_at: Timestamp) {
+        if let Some(entry) = self.entries.get_mut(&(requester, key.to_string())) {
+            entry.result = Some((result, expires_at));
+        }
+    }
+
+    /// Forget `key` if `job_id` holds it and hasn't succeeded, so a retry runs.
+    pub fn release(&mut self, requester: PublicKey, key: &str, job_id: EventId) {
+        let id = (requester, key.to_string());
+        if self
+            .entries
+            .get(&id)
+            .is_some_and(|e| e.job_id == job_id && e.result.is_none())
+        {
+            self.entries.remove(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dvm::events::ThumbnailResult;
+
+    fn id(c: char) -> EventId {
+        EventId::from_hex(c.to_string().repeat(64)).unwrap()
+    }
+
+    fn result() -> DvmResult {
+        DvmResult::Thumbnail(ThumbnailResult {
+            thumbnails: vec![],
+            input_sha256: None,
+        })
+    }
+
+    #[test]
+    fn test_claim_running_and_completed() {
+        let mut ledger = IdempotencyLedger::default();
+        let alice = Keys::generate().public_key();
+        let bob = Keys::generate().public_key();
+
+        assert!(matches!(ledger.claim(alice, "k", id('a'), 100), Claim::New));
+        // The holder itself may claim again (requeued after payment)
+        assert!(matches!(ledger.claim(alice, "k", id('a'), 101), Claim::New));
+        assert!(matches!(ledger.claim(alice, "k", id('b'), 102), Claim::Running(j) if j == id('a')));
+        // Keys are per requester
+        assert!(matches!(ledger.claim(bob, "k", id('c'), 102), Claim::New));
+
+        ledger.complete(alice, "k", result(), Timestamp::from(5000));
+        match ledger.claim(alice, "k", id('d'), 103) {
+            Claim::Completed { job_id, expires_at, .. } => {
+                assert_eq!(job_id, id('a'));
+                assert_eq!(expires_at, Timestamp::from(5000));
+            }
+            other => panic!("unexpected claim {:?}", other),
+        }
+        // Success is kept; releasing doesn't forget it
+        ledger.release(alice, "k", id('a'));
+        assert!(matches!(ledger.claim(alice, "k", id('e'), 104), Claim::Completed { .. }));
+    }
+
+    #[test]
+    fn test_release_and_expiry() {
+        let mut ledger = IdempotencyLedger::default();
+        let alice = Keys::generate().public_key();
+
+        ledger.claim(alice, "k", id('a'), 100);
+        // Only the holder releases
+        ledger.release(alice, "k", id('b'));
+        assert!(matches!(ledger.claim(alice, "k", id('b'), 101), Claim::Running(_)));
+        ledger.release(alice, "k", id('a'));
+        assert!(matches!(ledger.claim(alice, "k", id('b'), 102), Claim::New));
+
+        let later = 102 + IDEMPOTENCY_WINDOW_SECS;
+        assert!(matches!(ledger.claim(alice, "k", id('c'), later), Claim::New));
+    }
+}
@@ -0,0 +1,358 @@
+//! Pre-downloading inputs and keeping recent ones on disk.
+//!
+//! By default FFmpeg reads URL inputs straight from the origin, so a slow
+//! server stalls the encode. With `PREDOWNLOAD_INPUTS` the DVM first streams
+//! the input into the job's temp dir, hashing it on the way and checking the
+//! hash against the one the request names in an `x` tag or a Blossom URL.
+//! Downloaded inputs can also be kept in an LRU cache (`INPUT_CACHE_MB`),
+//! keyed by their SHA-256 and the URLs they were fetched from, so further
+//! jobs on the same source skip the download. Cached files are hard-linked into job dirs, so evicting one
+//! never pulls it from under a running job.
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, warn};
+
+use crate::error::VideoError;
+use crate::util::hash::sha256_from_url;
+
+/// Name of the downloaded input inside a job's temp dir
+pub const INPUT_FILE_NAME: &str = "input";
+
+/// Stream `url` into `dest`, counting bytes in `downloaded`, and return the
+/// file's SHA-256.
+///
+/// Fails if `expected` is given and the content hashes to something else;
+/// the partial file is then removed.
+pub async fn download_verified(
+    http: &reqwest::Client,
+    url: &str,
+    dest: &Path,
+    expected: Option<&str>,
+    downloaded: &AtomicU64,
+) -> Result<String, VideoError> {
+    let fetch_failed = |e: reqwest::Error| VideoError::FetchFailed(e.to_string());
+    let mut resp = http
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(fetch_failed)?;
+
+    let mut file = tokio::fs::File::create(dest).await?;
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = resp.chunk().await.map_err(fetch_failed)? {
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+        downloaded.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+    }
+    file.flush().await?;
+
+    let actual = hex::encode(hasher.finalize());
+    if let Some(expected) = expected {
+        if !expected.eq_ignore_ascii_case(&actual) {
+            let _ = tokio::fs::remove_file(dest).await;
+            return Err(VideoError::InputHashMismatch {
+                expected: expected.to_string(),
+                actual,
+            });
+        }
+    }
+    Ok(actual)
+}
+
+#[derive(Debug)]
+struct Entry {
+    size: u64,
+    /// Value of the use counter when the entry was last used
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct Index {
+    entries: HashMap<String, Entry>,
+    /// SHA-256 of inputs by the URL they were downloaded from
+    urls: HashMap<String, String>,
+    total_bytes: u64,
+    uses: u64,
+}
+
+impl Index {
+    fn touch(&mut self, sha256: &str) -> bool {
+        self.uses += 1;
+        let uses = self.uses;
+        match self.entries.get_mut(sha256) {
+            Some(entry) => {
+                entry.last_used = uses;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop least recently used entries until `max_bytes` fit, returning them
+    fn evict(&mut self, max_bytes: u64) -> Vec<String> {
+        let mut evicted = Vec::new();
+        while self.total_bytes > max_bytes {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(sha, _)| sha.clone())
+            else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&oldest) {
+                self.total_bytes -= entry.size;
+            }
+            self.urls.retain(|_, sha| *sha != oldest);
+            evicted.push(oldest);
+        }
+        evicted
+    }
+}
+
+/// Recently downloaded inputs, least recently used evicted first
+#[derive(Debug)]
+pub struct InputCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    index: Mutex<Index>,
+}
+
+impl InputCache {
+    /// Open the cache in `dir`, picking up inputs cached by earlier runs.
+    ///
+    /// Files there are named by their SHA-256; the oldest by modification
+    /// time count as least recently used.
+    pub fn open(dir: PathBuf, max_bytes: u64) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        let mut found = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let meta = entry.metadata()?;
+            if !meta.is_file() || name.len() != 64 || !name.chars().all(|c| c.is_ascii_hexdigit()) {
+                // Leftovers of an interrupted insert
+                let _ = std::fs::remove_file(entry.path());
+                continue;
+            }
+            found.push((meta.modified().ok(), name, meta.len()));
+        }
+        found.sort();
+
+        let mut index = Index::default();
+        for (_, sha256, size) in found {
+            index.uses += 1;
+            index.total_bytes += size;
+            index.entries.insert(
+                sha256,
+                Entry {
+                    size,
+                    last_used: index.uses,
+                },
+            );
+        }
+        let cache = Self {
+            dir,
+            max_bytes,
+            index: Mutex::new(index),
+        };
+        let evicted = cache.index.lock().unwrap().evict(max_bytes);
+        for sha256 in evicted {
+            let _ = std::fs::remove_file(cache.dir.join(sha256));
+        }
+        Ok(cache)
+    }
+
+    /// SHA-256 of the cached input for `url`, if there is one
+    fn lookup(&self, url: &str, expected: Option<&str>) -> Option<String> {
+        let mut index = self.index.lock().unwrap();
+        let sha256 = match expected {
+            Some(sha256) => sha256.to_string(),
+            None => index
+                .urls
+                .get(url)
+                .cloned()
+                .or_else(|| sha256_from_url(url))?,
+        };
+        index.touch(&sha256).then_some(sha256)
+    }
+
+    /// Link the cached input for `url` into `dir`, returning its path.
+    ///
+    /// With `expected`, only an input with that SHA-256 is a hit, whichever
+    /// URL it came from. Returns `None` on a miss, including when the entry
+    /// was evicted between the lookup and the link.
+    pub async fn fetch(&self, url: &str, expected: Option<&str>, dir: &Path) -> Option<PathBuf> {
+        let sha256 = self.lookup(url, expected)?;
+        let dest = dir.join(INPUT_FILE_NAME);
+        match link_or_copy(&self.dir.join(&sha256), &dest).await {
+            Ok(()) => {
+                debug!(url = %url, sha256 = %sha256, "Input cache hit");
+                Some(dest)
+            }
+            Err(e) => {
+                debug!(url = %url, error = %e, "Cached input went away");
+                None
+            }
+        }
+    }
+
+    /// Keep a downloaded input, evicting the least recently used ones to
+    /// stay under the size limit. Inputs larger than the whole cache are
+    /// not kept.
+    pub async fn insert(&self, url: &str, sha256: &str, path: &Path) {
+        let size = match tokio::fs::metadata(path).await {
+            Ok(meta) => meta.len(),
+            Err(_) => return,
+        };
+        if size > self.max_bytes {
+            debug!(url = %url, bytes = size, "Input too large to cache");
+            return;
+        }
+
+        let cached = self.dir.join(sha256);
+        if !self.index.lock().unwrap().entries.contains_key(sha256) {
+            // Link under a temporary name so a crash never leaves a partial entry
+            let partial = self.dir.join(format!("{}.partial", sha256));
+            let linked = match link_or_copy(path, &partial).await {
+                Ok(()) => tokio::fs::rename(&partial, &cached).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = linked {
+                warn!(url = %url, error = %e, "Failed to cache input");
+                let _ = tokio::fs::remove_file(&partial).await;
+                return;
+            }
+        }
+
+        let evicted = {
+            let mut index = self.index.lock().unwrap();
+            if !index.entries.contains_key(sha256) {
+                index.total_bytes += size;
+                index
+                    .entries
+                    .insert(sha256.to_string(), Entry { size, last_used: 0 });
+            }
+            index.touch(sha256);
+            index.urls.insert(url.to_string(), sha256.to_string());
+            index.evict(self.max_bytes)
+        };
+        for sha256 in evicted {
+            debug!(sha256 = %sha256, "Evicting cached input");
+            let _ = tokio::fs::remove_file(self.dir.join(sha256)).await;
+        }
+    }
+}
+
+/// Hard-link `src` to `dest`, copying when they're on different filesystems
+async fn link_or_copy(src: &Path, dest: &Path) -> std::io::Result<()> {
+    if tokio::fs::hard_link(src, dest).await.is_ok() {
+        return Ok(());
+    }
+    tokio::fs::copy(src, dest).await.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::hash::hash_bytes;
+    use wiremock::matchers::path;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn write(dir: &Path, name: &str, data: &[u8]) -> PathBuf {
+        let path = dir.join(name);
+        tokio::fs::write(&path, data).await.unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_download_verified() {
+        let server = MockServer::start().await;
+        Mock::given(path("/video.mp4"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"video".to_vec()))
+            .mount(&server)
+            .await;
+        let http = reqwest::Client::new();
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join(INPUT_FILE_NAME);
+        let url = format!("{}/video.mp4", server.uri());
+        let downloaded = AtomicU64::new(0);
+
+        let sha256 = download_verified(&http, &url, &dest, Some(&hash_bytes(b"video")), &downloaded)
+            .await
+            .unwrap();
+        assert_eq!(sha256, hash_bytes(b"video"));
+        assert_eq!(downloaded.load(Ordering::Relaxed), 5);
+
+        let err = download_verified(&http, &url, &dest, Some(&"0".repeat(64)), &downloaded)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, VideoError::InputHashMismatch { .. }));
+        assert!(!dest.exists());
+    }
+
+    #[tokio::test]
+    async fn test_cache_hit_by_url_and_hash() {
+        let root = tempfile::tempdir().unwrap();
+        let cache = InputCache::open(root.path().join("cache"), 1024).unwrap();
+        let job_dir = root.path().join("job");
+        tokio::fs::create_dir(&job_dir).await.unwrap();
+
+        let sha256 = hash_bytes(b"video");
+        let source = write(root.path(), "source", b"video").await;
+        cache.insert("https://origin.example/a.mp4", &sha256, &source).await;
+
+        let hit = cache.fetch("https://origin.example/a.mp4", None, &job_dir).await.unwrap();
+        assert_eq!(tokio::fs::read(&hit).await.unwrap(), b"video");
+        tokio::fs::remove_file(&hit).await.unwrap();
+
+        // Blossom URLs name the blob, wherever it was first fetched from
+        let blossom = format!("https://blossom.example/{}.mp4", sha256);
+        assert!(cache.fetch(&blossom, None, &job_dir).await.is_some());
+        assert!(cache.fetch("https://origin.example/b.mp4", None, &job_dir).await.is_none());
+
+        // A required hash overrides what the URL maps to
+        let other = hash_bytes(b"other");
+        assert!(cache
+            .fetch("https://origin.example/a.mp4", Some(&other), &job_dir)
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cache_evicts_least_recently_used() {
+        let root = tempfile::tempdir().unwrap();
+        let cache_dir = root.path().join("cache");
+        let cache = InputCache::open(cache_dir.clone(), 10).unwrap();
+        let job_dir = root.path().join("job");
+        tokio::fs::create_dir(&job_dir).await.unwrap();
+
+        let a = write(root.path(), "a", b"aaaa").await;
+        let b = write(root.path(), "b", b"bbbb").await;
+        let c = write(root.path(), "c", b"cccc").await;
+        let too_big = write(root.path(), "d", &[0u8; 11]).await;
+        cache.insert("a", &hash_bytes(b"aaaa"), &a).await;
+        cache.insert("b", &hash_bytes(b"bbbb"), &b).await;
+        // Using a makes b the least recently used
+        let hit = cache.fetch("a", None, &job_dir).await.unwrap();
+        tokio::fs::remove_file(hit).await.unwrap();
+        cache.insert("c", &hash_bytes(b"cccc"), &c).await;
+        cache.insert("d", &hash_bytes(&[0u8; 11]), &too_big).await;
+
+        assert!(cache.fetch("b", None, &job_dir).await.is_none());
+        assert!(!cache_dir.join(hash_bytes(b"bbbb")).exists());
+        assert!(!cache_dir.join(hash_bytes(&[0u8; 11])).exists());
+
+        // Entries survive a restart; URLs are only known by this run
+        drop(cache);
+        let cache = InputCache::open(cache_dir, 10).unwrap();
+        let blossom = format!("https://blossom.example/{}", hash_bytes(b"cccc"));
+        assert!(cache.fetch(&blossom, None, &job_dir).await.is_some());
+    }
+}
@@ -0,0 +1,108 @@
+//! Per-job log buffer.
+//!
+//! Each job keeps the last lines of its FFmpeg stderr and the status updates
+//! sent for it, so a failed job can be looked into with the admin
+//! `get_job_log` command instead of searching the service logs. The buffer
+//! is bounded in lines and line length; the oldest lines are dropped first.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use nostr_sdk::Timestamp;
+use serde::{Deserialize, Serialize};
+
+/// Lines kept per job
+pub const MAX_JOB_LOG_LINES: usize = 500;
+
+/// Longer lines are cut to this many characters
+const MAX_LINE_CHARS: usize = 2000;
+
+/// One line of a job log
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JobLogLine {
+    /// Unix timestamp the line was recorded
+    pub at: u64,
+    /// `dvm` for pipeline events, `ffmpeg` for FFmpeg output
+    pub source: String,
+    pub line: String,
+}
+
+#[derive(Debug, Default)]
+struct Lines {
+    lines: VecDeque<JobLogLine>,
+    /// Lines dropped to stay within the bound
+    dropped: u64,
+}
+
+/// Bounded log of one job, shared between the job and its FFmpeg runs
+#[derive(Debug, Default)]
+pub struct JobLog {
+    inner: Mutex<Lines>,
+}
+
+impl JobLog {
+    /// Record a pipeline event
+    pub fn event(&self, line: impl Into<String>) {
+        self.push("dvm", line.into());
+    }
+
+    /// Record a line of FFmpeg output
+    pub fn ffmpeg(&self, line: impl Into<String>) {
+        self.push("ffmpeg", line.into());
+    }
+
+    fn push(&self, source: &str, mut line: String) {
+        if let Some((cut, _)) = line.char_indices().nth(MAX_LINE_CHARS) {
+            line.truncate(cut);
+            line.push('…');
+        }
+        let mut inner = self.inner.lock().expect("job log lock poisoned");
+        if inner.lines.len() == MAX_JOB_LOG_LINES {
+            inner.lines.pop_front();
+            inner.dropped += 1;
+        }
+        inner.lines.push_back(JobLogLine {
+            at: Timestamp::now().as_u64(),
+            source: source.to_string(),
+            line,
+        });
+    }
+
+    /// The last `n` lines, oldest first, and how many earlier lines are
+    /// no longer available (dropped from the buffer or left out of the tail)
+    pub fn tail(&self, n: usize) -> (Vec<JobLogLine>, u64) {
+        let inner = self.inner.lock().expect("job log lock poisoned");
+        let skip = inner.lines.len().saturating_sub(n);
+        let lines = inner.lines.iter().skip(skip).cloned().collect();
+        (lines, inner.dropped + skip as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounded_tail() {
+        let log = JobLog::default();
+        for i in 0..MAX_JOB_LOG_LINES + 5 {
+            log.event(format!("line {}", i));
+        }
+        let (lines, missing) = log.tail(2);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].line, format!("line {}", MAX_JOB_LOG_LINES + 4));
+        assert_eq!(lines[1].source, "dvm");
+        assert_eq!(missing, MAX_JOB_LOG_LINES as u64 + 3);
+
+        let (all, missing) = log.tail(usize::MAX);
+        assert_eq!(all.len(), MAX_JOB_LOG_LINES);
+        assert_eq!(all[0].line, "line 5");
+        assert_eq!(missing, 5);
+
+        log.event("x".repeat(MAX_LINE_CHARS + 50));
+        assert_eq!(log.tail(1).0[0].line.chars().count(), MAX_LINE_CHARS + 1);
+
+        log.ffmpeg("Conversion failed!");
+        assert_eq!(log.tail(1).0[0].source, "ffmpeg");
+    }
+}
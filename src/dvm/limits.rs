@@ -0,0 +1,207 @@
+//! Limits on the inputs a DVM accepts and the outputs it produces.
+//!
+//! Operators set a maximum input duration and file size so a single request
+//! (e.g. a 12-hour livestream VOD) can't tie up an encoder for hours, and a
+//! maximum output size so a misconfigured encode can't flood Blossom. The
+//! environment provides defaults; the remote config overrides them.
+
+use crate::dvm::handler::format_duration;
+use crate::remote_config::RemoteConfig;
+
+/// Largest input accepted, by duration and by size (`None` = unlimited)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InputLimits {
+    pub max_duration_secs: Option<u64>,
+    pub max_size_bytes: Option<u64>,
+}
+
+impl InputLimits {
+    /// Read `MAX_INPUT_DURATION_SECS` and `MAX_INPUT_SIZE_BYTES` (0 or unset = unlimited).
+    pub fn from_env() -> Self {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|s| s.trim().parse::<u64>().ok())
+                .filter(|v| *v > 0)
+        };
+        Self {
+            max_duration_secs: var("MAX_INPUT_DURATION_SECS"),
+            max_size_bytes: var("MAX_INPUT_SIZE_BYTES"),
+        }
+    }
+
+    /// These limits with any set in the remote config taking precedence
+    pub fn with_overrides(self, remote: &RemoteConfig) -> Self {
+        Self {
+            max_duration_secs: remote.max_input_duration_secs.or(self.max_duration_secs),
+            max_size_bytes: remote.max_input_size_bytes.or(self.max_size_bytes),
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_duration_secs.is_none() && self.max_size_bytes.is_none()
+    }
+
+    /// Check an input's size in bytes, when known
+    pub fn check_size(&self, size_bytes: u64) -> Result<(), String> {
+        match self.max_size_bytes {
+            Some(max) if size_bytes > max => Err(format!(
+                "Input is {}, larger than the {} limit",
+                format_bytes(size_bytes),
+                format_bytes(max)
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check an input's duration in seconds, when known
+    pub fn check_duration(&self, duration_secs: f64) -> Result<(), String> {
+        match self.max_duration_secs {
+            Some(max) if duration_secs > max as f64 => Err(format!(
+                "Input is {}, longer than the {} limit",
+                format_duration(duration_secs as u64),
+                format_duration(max)
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Largest output produced, relative to the input and absolute (`None` = unlimited)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct OutputLimits {
+    /// Combined output size as a multiple of the input size
+    pub max_ratio: Option<f64>,
+    pub max_bytes: Option<u64>,
+}
+
+impl OutputLimits {
+    /// Read `MAX_OUTPUT_SIZE_RATIO` and `MAX_OUTPUT_SIZE_BYTES` (0 or unset = unlimited).
+    pub fn from_env() -> Self {
+        Self {
+            max_ratio: std::env::var("MAX_OUTPUT_SIZE_RATIO")
+                .ok()
+                .and_then(|s| s.trim().parse::<f64>().ok())
+                .filter(|v| v.is_finite() && *v > 0.0),
+            max_bytes: std::env::var("MAX_OUTPUT_SIZE_BYTES")
+                .ok()
+                .and_then(|s| s.trim().parse::<u64>().ok())
+                .filter(|v| *v > 0),
+        }
+    }
+
+    /// These limits with any set in the remote config taking precedence
+    pub fn with_overrides(self, remote: &RemoteConfig) -> Self {
+        Self {
+            max_ratio: remote.max_output_size_ratio.or(self.max_ratio),
+            max_bytes: remote.max_output_size_bytes.or(self.max_bytes),
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_ratio.is_none() && self.max_bytes.is_none()
+    }
+
+    /// Check an estimated output size against the limits.
+    ///
+    /// `input_bytes` is the size of the part of the input being encoded; the
+    /// ratio limit is skipped when it is unknown.
+    pub fn check(&self, output_bytes: u64, input_bytes: Option<u64>) -> Result<(), String> {
+        if let Some(max) = self.max_bytes.filter(|max| output_bytes > *max) {
+            return Err(format!(
+                "Output would be about {}, larger than the {} limit",
+                format_bytes(output_bytes),
+                format_bytes(max)
+            ));
+        }
+        if let (Some(ratio), Some(input)) = (self.max_ratio, input_bytes) {
+            let max = (input as f64 * ratio) as u64;
+            if output_bytes > max {
+                return Err(format!(
+                    "Output would be about {}, more than {}x the {} input",
+                    format_bytes(output_bytes),
+                    ratio,
+                    format_bytes(input)
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const MB: u64 = 1024 * 1024;
+    const GB: u64 = 1024 * MB;
+    if bytes >= GB {
+        format!("{:.1} GB", bytes as f64 / GB as f64)
+    } else {
+        format!("{} MB", bytes.div_ceil(MB))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_checks() {
+        let limits = InputLimits {
+            max_duration_secs: Some(2 * 3600),
+            max_size_bytes: Some(4 * 1024 * 1024 * 1024),
+        };
+        assert!(limits.check_duration(7200.0).is_ok());
+        assert_eq!(
+            limits.check_duration(12.0 * 3600.0 + 90.0),
+            Err("Input is 12h 1m, longer than the 2h limit".to_string())
+        );
+        assert!(limits.check_size(1024).is_ok());
+        assert_eq!(
+            limits.check_size(6 * 1024 * 1024 * 1024),
+            Err("Input is 6.0 GB, larger than the 4.0 GB limit".to_string())
+        );
+
+        let unlimited = InputLimits::default();
+        assert!(unlimited.is_unlimited());
+        assert!(unlimited.check_duration(1e9).is_ok());
+        assert!(unlimited.check_size(u64::MAX).is_ok());
+    }
+
+    #[test]
+    fn test_output_limits() {
+        const MB: u64 = 1024 * 1024;
+        let limits = OutputLimits {
+            max_ratio: Some(3.0),
+            max_bytes: Some(1024 * MB),
+        };
+        assert!(limits.check(250 * MB, Some(100 * MB)).is_ok());
+        assert_eq!(
+            limits.check(400 * MB, Some(100 * MB)),
+            Err("Output would be about 400 MB, more than 3x the 100 MB input".to_string())
+        );
+        assert_eq!(
+            limits.check(2048 * MB, None),
+            Err("Output would be about 2.0 GB, larger than the 1.0 GB limit".to_string())
+        );
+        assert!(OutputLimits::default().check(u64::MAX, Some(1)).is_ok());
+
+        let mut remote = RemoteConfig::new();
+        remote.max_output_size_ratio = Some(1.5);
+        assert_eq!(limits.with_overrides(&remote).max_ratio, Some(1.5));
+        assert_eq!(limits.with_overrides(&remote).max_bytes, Some(1024 * MB));
+    }
+
+    #[test]
+    fn test_remote_overrides_env() {
+        let env = InputLimits {
+            max_duration_secs: Some(600),
+            max_size_bytes: Some(1024),
+        };
+        let mut remote = RemoteConfig::new();
+        assert_eq!(env.with_overrides(&remote), env);
+
+        remote.max_input_duration_secs = Some(3600);
+        let limits = env.with_overrides(&remote);
+        assert_eq!(limits.max_duration_secs, Some(3600));
+        assert_eq!(limits.max_size_bytes, Some(1024));
+    }
+}
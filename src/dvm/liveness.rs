@@ -0,0 +1,222 @@
+//! Periodic checks that published results are still being served.
+//!
+//! Blossom servers can lose blobs or drop them early. Every hour the checker
+//! samples the most recently completed jobs, sends a HEAD request for each
+//! result URL and, for HLS results, for one random segment of one of its
+//! stream playlists. Results that have gone missing are reported to the
+//! admin in a direct message, once per result.
+
+use rand::seq::IndexedRandom;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::interval;
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::dvm_state::{JobStatus, SharedDvmState};
+use crate::nostr::EventPublisher;
+
+/// How often results are sampled
+pub const LIVENESS_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Results checked per round, newest first
+pub const LIVENESS_SAMPLE: usize = 5;
+/// Per-request timeout
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub struct LivenessChecker {
+    state: SharedDvmState,
+    publisher: Arc<EventPublisher>,
+    http: reqwest::Client,
+    /// Results already reported missing
+    reported: HashSet<String>,
+}
+
+impl LivenessChecker {
+    pub fn new(state: SharedDvmState, publisher: Arc<EventPublisher>, http: reqwest::Client) -> Self {
+        Self {
+            state,
+            publisher,
+            http,
+            reported: HashSet::new(),
+        }
+    }
+
+    /// Check a sample of recent results every [`LIVENESS_INTERVAL`].
+    pub async fn run(mut self) {
+        info!("Result liveness checker started");
+        let mut tick = interval(LIVENESS_INTERVAL);
+        // The first tick fires immediately; nothing has been published yet
+        tick.tick().await;
+        loop {
+            tick.tick().await;
+            self.check_recent().await;
+        }
+    }
+
+    async fn check_recent(&mut self) {
+        let (results, admin) = {
+            let state = self.state.read().await;
+            let max_age = state.config.blob_expiration_days as u64 * 24 * 60 * 60;
+            let now = nostr_sdk::Timestamp::now().as_u64();
+            let results: Vec<(String, String)> = state
+                .job_history
+                .iter()
+                // Quarantined results were taken down on purpose, expired ones by cleanup
+                .filter(|r| r.status == JobStatus::Completed && !r.quarantined)
+                .filter(|r| r.completed_at.is_some_and(|t| now < t + max_age))
+                .filter(|r| !self.reported.contains(&r.id))
+                .filter_map(|r| Some((r.id.clone(), r.output_url.clone()?)))
+                .filter(|(_, url)| url.starts_with("http"))
+                .take(LIVENESS_SAMPLE)
+                .collect();
+            (results, state.config.admin_pubkey())
+        };
+
+        for (job_id, url) in results {
+            match check_result(&self.http, &url).await {
+                Ok(()) => debug!(job_id = %job_id, url = %url, "Result is live"),
+                Err(missing) => {
+                    warn!(job_id = %job_id, url = %url, missing = %missing, "Published result has gone missing");
+                    self.reported.insert(job_id.clone());
+                    let Some(admin) = admin else {
+                        continue;
+                    };
+                    let message = format!(
+                        "Result of job {} is no longer served by its Blossom server: {} is missing",
+                        job_id, missing
+                    );
+                    if let Err(e) = self.publisher.send_private_msg(admin, message, &[]).await {
+                        warn!(job_id = %job_id, error = %e, "Failed to alert admin about missing result");
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// HEAD a result URL and, for HLS, a random segment of a random stream.
+///
+/// Returns the URL that failed.
+pub async fn check_result(http: &reqwest::Client, url: &str) -> Result<(), String> {
+    let response = http
+        .head(url)
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await
+        .map_err(|_| url.to_string())?;
+    if !response.status().is_success() {
+        return Err(url.to_string());
+    }
+    let is_playlist = url.ends_with(".m3u8")
+        || response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|t| t.contains("mpegurl"));
+    if !is_playlist {
+        return Ok(());
+    }
+
+    let master = Url::parse(url).map_err(|_| url.to_string())?;
+    let streams = fetch_playlist(http, &master).await?;
+    let Some(stream) = streams.choose(&mut rand::rng()) else {
+        return Ok(());
+    };
+    let segments = fetch_playlist(http, stream).await?;
+    let Some(segment) = segments.choose(&mut rand::rng()) else {
+        return Ok(());
+    };
+    match http.head(segment.as_str()).timeout(REQUEST_TIMEOUT).send().await {
+        Ok(r) if r.status().is_success() => Ok(()),
+        _ => Err(segment.to_string()),
+    }
+}
+
+/// GET a playlist and resolve the URIs it lists
+async fn fetch_playlist(http: &reqwest::Client, url: &Url) -> Result<Vec<Url>, String> {
+    let body = match http.get(url.as_str()).timeout(REQUEST_TIMEOUT).send().await {
+        Ok(r) if r.status().is_success() => r.text().await.map_err(|_| url.to_string())?,
+        _ => return Err(url.to_string()),
+    };
+    Ok(playlist_uris(&body, url))
+}
+
+/// URIs of the entries (streams or segments) in a playlist
+fn playlist_uris(content: &str, base: &Url) -> Vec<Url> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| base.join(l).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn hls_server(segment_status: u16) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(path("/master.m3u8"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string("#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1\nstream.m3u8\n"),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/stream.m3u8"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string("#EXTM3U\n#EXTINF:6.0,\nseg.m4s\n#EXT-X-ENDLIST\n"),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(path("/seg.m4s"))
+            .respond_with(ResponseTemplate::new(segment_status))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[tokio::test]
+    async fn test_check_hls_result() {
+        let http = reqwest::Client::new();
+
+        let server = hls_server(200).await;
+        let master = format!("{}/master.m3u8", server.uri());
+        assert_eq!(check_result(&http, &master).await, Ok(()));
+
+        let server = hls_server(404).await;
+        let master = format!("{}/master.m3u8", server.uri());
+        assert_eq!(
+            check_result(&http, &master).await,
+            Err(format!("{}/seg.m4s", server.uri()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_missing_result() {
+        let http = reqwest::Client::new();
+        let server = MockServer::start().await;
+        let url = format!("{}/video.mp4", server.uri());
+        assert_eq!(check_result(&http, &url).await, Err(url.clone()));
+    }
+
+    #[test]
+    fn test_playlist_uris() {
+        let base = Url::parse("https://cdn.example.com/abc.m3u8").unwrap();
+        let uris = playlist_uris(
+            "#EXTM3U\n#EXT-X-MAP:URI=\"init.mp4\"\n#EXTINF:6.0,\nseg0.m4s\n\nhttps://other.example/seg1.m4s\n",
+            &base,
+        );
+        assert_eq!(
+            uris.iter().map(|u| u.as_str()).collect::<Vec<_>>(),
+            vec!["https://cdn.example.com/seg0.m4s", "https://other.example/seg1.m4s"]
+        );
+    }
+}
@@ -0,0 +1,587 @@
+//! Status messages shown to requesters.
+//!
+//! Status updates the DVM sends for a job come from this catalog. Each
+//! message has a stable code that is sent alongside the text, so clients can
+//! show their own translation; requesters can also pick one of the built-in
+//! languages with the `lang` param. Error messages carrying details from
+//! FFmpeg or the network stay in English.
+
+use crate::video::ffmpeg_error::FfmpegErrorKind;
+
+/// Language of the status text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    En,
+    De,
+    Es,
+    Fr,
+}
+
+impl Lang {
+    /// Parse a BCP 47 tag (`de`, `pt-BR`); only the primary language is used
+    pub fn parse(s: &str) -> Option<Self> {
+        let primary = s.trim().split(['-', '_']).next()?.to_lowercase();
+        match primary.as_str() {
+            "en" => Some(Self::En),
+            "de" => Some(Self::De),
+            "es" => Some(Self::Es),
+            "fr" => Some(Self::Fr),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::De => "de",
+            Self::Es => "es",
+            Self::Fr => "fr",
+        }
+    }
+
+    /// All built-in languages, as advertised in the announcement
+    pub fn all() -> [Self; 4] {
+        [Self::En, Self::De, Self::Es, Self::Fr]
+    }
+
+    /// Time left, appended to progress updates (`duration` is already formatted)
+    pub fn remaining(&self, duration: &str) -> String {
+        match self {
+            Self::En => format!("~{} remaining", duration),
+            Self::De => format!("noch ~{}", duration),
+            Self::Es => format!("quedan ~{}", duration),
+            Self::Fr => format!("~{} restant", duration),
+        }
+    }
+
+    /// Time spent so far, when no estimate is available
+    pub fn elapsed(&self, duration: &str) -> String {
+        match self {
+            Self::En => format!("{} elapsed", duration),
+            Self::De => format!("{} vergangen", duration),
+            Self::Es => format!("{} transcurridos", duration),
+            Self::Fr => format!("{} écoulé", duration),
+        }
+    }
+}
+
+/// A status update from the catalog
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusMessage<'a> {
+    JobAccepted,
+    TransformStarted,
+    TransformComplete,
+    /// Completion when the requester's relays refused the result
+    TransformCompleteOn {
+        relays: &'a str,
+    },
+    PaymentRequired,
+    Bid,
+    JobCancelled,
+    FetchingInput,
+    DownloadingInput,
+    AssemblingArchive {
+        images: usize,
+    },
+    TranscodingMp4 {
+        resolution: &'a str,
+        codec: &'a str,
+        container: &'a str,
+    },
+    TranscodingHls {
+        codec: &'a str,
+        resolutions: &'a str,
+    },
+    UploadingFile {
+        container: &'a str,
+        servers: usize,
+    },
+    UploadingFiles {
+        files: usize,
+    },
+    ArchivingSource,
+    GeneratingPreview,
+    GeneratingStoryboard,
+    ExtractingThumbnails {
+        count: usize,
+    },
+    UploadingThumbnails,
+    WaitingForDisk {
+        megabytes: u64,
+    },
+    WaitingForMemory {
+        megabytes: u64,
+    },
+    MeasuringLoudness,
+    LoweringQuality,
+    /// The hardware encoder failed and the job was encoded in software
+    SoftwareFallback,
+    /// The lowest rendition can be watched before the rest is uploaded
+    PreviewReady {
+        resolution: &'a str,
+    },
+    RateLimited {
+        minutes: u64,
+    },
+    DuplicateRequest {
+        job_id: &'a str,
+    },
+    Queued {
+        ahead: usize,
+    },
+    Busy {
+        queued: usize,
+    },
+    /// FFmpeg failure the classifier recognized
+    TranscodeFailed {
+        kind: FfmpegErrorKind,
+    },
+}
+
+impl StatusMessage<'_> {
+    /// Stable code sent in the `code` tag of status events
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::JobAccepted => "job-accepted",
+            Self::TransformStarted => "transform-started",
+            // Same step as far as clients are concerned; only the text differs
+            Self::TransformComplete | Self::TransformCompleteOn { .. } => "transform-complete",
+            Self::PaymentRequired => "payment-required",
+            Self::Bid => "bid",
+            Self::JobCancelled => "job-cancelled",
+            Self::FetchingInput => "fetching-input",
+            Self::DownloadingInput => "downloading-input",
+            Self::AssemblingArchive { .. } => "assembling-archive",
+            Self::TranscodingMp4 { .. } => "transcoding-mp4",
+            Self::TranscodingHls { .. } => "transcoding-hls",
+            Self::UploadingFile { .. } => "uploading-file",
+            Self::UploadingFiles { .. } => "uploading-files",
+            Self::ArchivingSource => "archiving-source",
+            Self::GeneratingPreview => "generating-preview",
+            Self::GeneratingStoryboard => "generating-storyboard",
+            Self::ExtractingThumbnails { .. } => "extracting-thumbnails",
+            Self::UploadingThumbnails => "uploading-thumbnails",
+            Self::WaitingForDisk { .. } => "waiting-for-disk",
+            Self::WaitingForMemory { .. } => "waiting-for-memory",
+            Self::MeasuringLoudness => "measuring-loudness",
+            Self::LoweringQuality => "lowering-quality",
+            Self::SoftwareFallback => "software-fallback",
+            Self::PreviewReady { .. } => "preview-ready",
+            Self::RateLimited { .. } => "rate-limited",
+            Self::DuplicateRequest { .. } => "duplicate-request",
+            Self::Queued { .. } => "queued",
+            Self::Busy { .. } => "busy",
+            Self::TranscodeFailed { kind } => match kind {
+                FfmpegErrorKind::UnsupportedCodec => "unsupported-codec",
+                FfmpegErrorKind::CorruptInput => "corrupt-input",
+                FfmpegErrorKind::OutOfMemory => "out-of-memory",
+                FfmpegErrorKind::HwEncoderInit => "encoder-unavailable",
+                FfmpegErrorKind::NetworkStall => "input-stalled",
+                FfmpegErrorKind::Other => "transcode-failed",
+            },
+        }
+    }
+
+    /// Whether the step is still running (the text then ends in "...")
+    fn ongoing(&self) -> bool {
+        !matches!(
+            self,
+            Self::TransformStarted
+                | Self::TransformComplete
+                | Self::TransformCompleteOn { .. }
+                | Self::PaymentRequired
+                | Self::Bid
+                | Self::JobCancelled
+                | Self::RateLimited { .. }
+                | Self::DuplicateRequest { .. }
+                | Self::Busy { .. }
+                | Self::TranscodeFailed { .. }
+        )
+    }
+
+    /// Status text as sent to the requester
+    pub fn text(&self, lang: Lang) -> String {
+        let label = self.label(lang);
+        if self.ongoing() {
+            format!("{}...", label)
+        } else {
+            label
+        }
+    }
+
+    /// Text without the trailing ellipsis, for progress updates that append
+    /// a percentage
+    pub fn label(&self, lang: Lang) -> String {
+        use Lang::*;
+
+        let fixed = |en: &str, de: &str, es: &str, fr: &str| {
+            match lang {
+                En => en,
+                De => de,
+                Es => es,
+                Fr => fr,
+            }
+            .to_string()
+        };
+
+        match *self {
+            Self::JobAccepted => fixed(
+                "Job accepted, validating input",
+                "Auftrag angenommen, Eingabe wird geprüft",
+                "Trabajo aceptado, validando la entrada",
+                "Tâche acceptée, vérification de l'entrée",
+            ),
+            Self::TransformStarted => fixed(
+                "Starting video transformation",
+                "Videoumwandlung wird gestartet",
+                "Iniciando la transformación del vídeo",
+                "Démarrage de la transformation de la vidéo",
+            ),
+            Self::TransformComplete => fixed(
+                "Video transformation complete",
+                "Videoumwandlung abgeschlossen",
+                "Transformación del vídeo completada",
+                "Transformation de la vidéo terminée",
+            ),
+            Self::TransformCompleteOn { relays } => match lang {
+                En => format!("Video transformation complete. Your relays refused the result, find it on {}", relays),
+                De => format!("Videoumwandlung abgeschlossen. Deine Relays haben das Ergebnis abgelehnt, es liegt auf {}", relays),
+                Es => format!("Transformación del vídeo completada. Tus relays rechazaron el resultado, está en {}", relays),
+                Fr => format!("Transformation de la vidéo terminée. Vos relais ont refusé le résultat, il se trouve sur {}", relays),
+            },
+            Self::PaymentRequired => fixed(
+                "Payment required to start this job",
+                "Zahlung erforderlich, um diesen Auftrag zu starten",
+                "Se requiere un pago para iniciar este trabajo",
+                "Paiement requis pour démarrer cette tâche",
+            ),
+            Self::Bid => fixed(
+                "I can process this video for you",
+                "Ich kann dieses Video für dich verarbeiten",
+                "Puedo procesar este vídeo por ti",
+                "Je peux traiter cette vidéo pour vous",
+            ),
+            Self::JobCancelled => fixed(
+                "Job cancelled by the operator",
+                "Auftrag vom Betreiber abgebrochen",
+                "Trabajo cancelado por el operador",
+                "Tâche annulée par l'opérateur",
+            ),
+            Self::FetchingInput => fixed(
+                "Fetching video from external platform",
+                "Video wird von externer Plattform geladen",
+                "Obteniendo el vídeo de una plataforma externa",
+                "Récupération de la vidéo depuis une plateforme externe",
+            ),
+            Self::DownloadingInput => fixed(
+                "Downloading input video",
+                "Eingabevideo wird heruntergeladen",
+                "Descargando el vídeo de entrada",
+                "Téléchargement de la vidéo source",
+            ),
+            Self::AssemblingArchive { images } => {
+                let plural = images != 1;
+                match lang {
+                    En => format!(
+                        "Assembling video from {} image{}",
+                        images,
+                        if plural { "s" } else { "" }
+                    ),
+                    De => format!(
+                        "Video wird aus {} {} erstellt",
+                        images,
+                        if plural { "Bildern" } else { "Bild" }
+                    ),
+                    Es => format!(
+                        "Montando el vídeo a partir de {} imagen{}",
+                        images,
+                        if plural { "es" } else { "" }
+                    ),
+                    Fr => format!(
+                        "Assemblage de la vidéo à partir de {} image{}",
+                        images,
+                        if plural { "s" } else { "" }
+                    ),
+                }
+            }
+            Self::TranscodingMp4 {
+                resolution,
+                codec,
+                container,
+            } => match lang {
+                En => format!("Transcoding to {} {} {}", resolution, codec, container),
+                De => format!("Umwandlung in {} {} {}", resolution, codec, container),
+                Es => format!("Transcodificando a {} {} {}", resolution, codec, container),
+                Fr => format!("Transcodage en {} {} {}", resolution, codec, container),
+            },
+            Self::TranscodingHls { codec, resolutions } => match lang {
+                En => format!("Transcoding to {} HLS ({})", codec, resolutions),
+                De => format!("Umwandlung in {} HLS ({})", codec, resolutions),
+                Es => format!("Transcodificando a {} HLS ({})", codec, resolutions),
+                Fr => format!("Transcodage en {} HLS ({})", codec, resolutions),
+            },
+            Self::UploadingFile { container, servers } => {
+                let plural = servers != 1;
+                match lang {
+                    En => format!(
+                        "Uploading {} to {} server{}",
+                        container,
+                        servers,
+                        if plural { "s" } else { "" }
+                    ),
+                    De => format!("{} wird auf {} Server hochgeladen", container, servers),
+                    Es => format!(
+                        "Subiendo {} a {} {}",
+                        container,
+                        servers,
+                        if plural { "servidores" } else { "servidor" }
+                    ),
+                    Fr => format!(
+                        "Envoi du {} vers {} serveur{}",
+                        container,
+                        servers,
+                        if plural { "s" } else { "" }
+                    ),
+                }
+            }
+            Self::UploadingFiles { files } => match lang {
+                En => format!("Uploading {} files to Blossom", files),
+                De => format!("{} Dateien werden zu Blossom hochgeladen", files),
+                Es => format!("Subiendo {} archivos a Blossom", files),
+                Fr => format!("Envoi de {} fichiers vers Blossom", files),
+            },
+            Self::ArchivingSource => fixed(
+                "Archiving original source file",
+                "Originaldatei wird archiviert",
+                "Archivando el archivo original",
+                "Archivage du fichier source original",
+            ),
+            Self::GeneratingPreview => fixed(
+                "Generating animated preview",
+                "Animierte Vorschau wird erstellt",
+                "Generando la vista previa animada",
+                "Génération de l'aperçu animé",
+            ),
+            Self::GeneratingStoryboard => fixed(
+                "Generating seek thumbnails",
+                "Vorschaubilder für die Zeitleiste werden erstellt",
+                "Generando miniaturas para la barra de progreso",
+                "Génération des miniatures de navigation",
+            ),
+            Self::ExtractingThumbnails { count } => {
+                let plural = count != 1;
+                match lang {
+                    En => format!("Extracting {} thumbnail{}", count, if plural { "s" } else { "" }),
+                    De => format!(
+                        "{} {} extrahiert",
+                        count,
+                        if plural { "Vorschaubilder werden" } else { "Vorschaubild wird" }
+                    ),
+                    Es => format!("Extrayendo {} miniatura{}", count, if plural { "s" } else { "" }),
+                    Fr => format!("Extraction de {} miniature{}", count, if plural { "s" } else { "" }),
+                }
+            }
+            Self::UploadingThumbnails => fixed(
+                "Uploading thumbnails to Blossom",
+                "Vorschaubilder werden zu Blossom hochgeladen",
+                "Subiendo miniaturas a Blossom",
+                "Envoi des miniatures vers Blossom",
+            ),
+            Self::WaitingForDisk { megabytes } => match lang {
+                En => format!("Waiting for temp disk space (~{} MB needed)", megabytes),
+                De => format!("Warte auf temporären Speicherplatz (~{} MB benötigt)", megabytes),
+                Es => format!(
+                    "Esperando espacio temporal en disco (~{} MB necesarios)",
+                    megabytes
+                ),
+                Fr => format!(
+                    "En attente d'espace disque temporaire (~{} Mo nécessaires)",
+                    megabytes
+                ),
+            },
+            Self::WaitingForMemory { megabytes } => match lang {
+                En => format!("Waiting for memory (~{} MB needed)", megabytes),
+                De => format!("Warte auf freien Arbeitsspeicher (~{} MB benötigt)", megabytes),
+                Es => format!("Esperando memoria libre (~{} MB necesarios)", megabytes),
+                Fr => format!("En attente de mémoire libre (~{} Mo nécessaires)", megabytes),
+            },
+            Self::MeasuringLoudness => fixed(
+                "Measuring audio loudness",
+                "Lautheit des Tons wird gemessen",
+                "Midiendo la sonoridad del audio",
+                "Mesure de la sonie audio",
+            ),
+            Self::LoweringQuality => fixed(
+                "Lowering quality to keep the output within this DVM's size limit",
+                "Qualität wird gesenkt, um die Größenbeschränkung dieser DVM einzuhalten",
+                "Reduciendo la calidad para respetar el límite de tamaño de esta DVM",
+                "Réduction de la qualité pour respecter la limite de taille de cette DVM",
+            ),
+            Self::SoftwareFallback => fixed(
+                "The hardware encoder failed, the video was encoded in software instead",
+                "Der Hardware-Encoder ist ausgefallen, das Video wurde stattdessen per Software kodiert",
+                "El codificador por hardware falló, el vídeo se codificó por software",
+                "L'encodeur matériel a échoué, la vidéo a été encodée par logiciel",
+            ),
+            Self::PreviewReady { resolution } => match lang {
+                En => format!("{} is ready to watch, higher resolutions are still uploading", resolution),
+                De => format!("{} kann schon angesehen werden, höhere Auflösungen werden noch hochgeladen", resolution),
+                Es => format!("{} ya se puede ver, las resoluciones más altas se siguen subiendo", resolution),
+                Fr => format!("{} est prêt à regarder, les résolutions supérieures sont encore en cours d'envoi", resolution),
+            },
+            Self::RateLimited { minutes } => match lang {
+                En => format!("You've reached this DVM's hourly job limit, please try again in {} min", minutes),
+                De => format!("Stündliches Auftragslimit dieser DVM erreicht, bitte in {} Min. erneut versuchen", minutes),
+                Es => format!("Has alcanzado el límite de trabajos por hora de esta DVM, inténtalo de nuevo en {} min", minutes),
+                Fr => format!("Limite horaire de tâches de cette DVM atteinte, réessayez dans {} min", minutes),
+            },
+            Self::DuplicateRequest { job_id } => match lang {
+                En => format!("Duplicate of job {}, which is still running", job_id),
+                De => format!("Duplikat von Auftrag {}, der noch läuft", job_id),
+                Es => format!("Duplicado del trabajo {}, que sigue en curso", job_id),
+                Fr => format!("Doublon de la tâche {}, toujours en cours", job_id),
+            },
+            Self::Queued { ahead } => {
+                let plural = ahead != 1;
+                match lang {
+                    En => format!("Queued behind {} job{}", ahead, if plural { "s" } else { "" }),
+                    De => format!(
+                        "In der Warteschlange hinter {} {}",
+                        ahead,
+                        if plural { "Aufträgen" } else { "Auftrag" }
+                    ),
+                    Es => format!(
+                        "En cola detrás de {} trabajo{}",
+                        ahead,
+                        if plural { "s" } else { "" }
+                    ),
+                    Fr => format!(
+                        "En file d'attente derrière {} tâche{}",
+                        ahead,
+                        if plural { "s" } else { "" }
+                    ),
+                }
+            }
+            Self::Busy { queued } => match lang {
+                En => format!("This DVM is busy with {} queued jobs, please try again later", queued),
+                De => format!("Diese DVM ist mit {} wartenden Aufträgen ausgelastet, bitte später erneut versuchen", queued),
+                Es => format!("Esta DVM está ocupada con {} trabajos en cola, inténtalo de nuevo más tarde", queued),
+                Fr => format!("Cette DVM est occupée avec {} tâches en attente, réessayez plus tard", queued),
+            },
+            Self::TranscodeFailed { kind } => match kind {
+                FfmpegErrorKind::UnsupportedCodec => fixed(
+                    "The input uses a codec this DVM doesn't support",
+                    "Die Eingabe verwendet einen Codec, den diese DVM nicht unterstützt",
+                    "La entrada usa un códec que esta DVM no admite",
+                    "L'entrée utilise un codec que cette DVM ne prend pas en charge",
+                ),
+                FfmpegErrorKind::CorruptInput => fixed(
+                    "The input video is corrupt or unreadable",
+                    "Das Eingabevideo ist beschädigt oder unlesbar",
+                    "El vídeo de entrada está dañado o es ilegible",
+                    "La vidéo source est corrompue ou illisible",
+                ),
+                FfmpegErrorKind::OutOfMemory => fixed(
+                    "This DVM ran out of memory, please try again later",
+                    "Dieser DVM ist der Speicher ausgegangen, bitte später erneut versuchen",
+                    "Esta DVM se quedó sin memoria, inténtalo de nuevo más tarde",
+                    "Cette DVM a manqué de mémoire, réessayez plus tard",
+                ),
+                FfmpegErrorKind::HwEncoderInit => fixed(
+                    "The video encoder failed to start, please try again later",
+                    "Der Video-Encoder konnte nicht gestartet werden, bitte später erneut versuchen",
+                    "El codificador de vídeo no pudo iniciarse, inténtalo de nuevo más tarde",
+                    "L'encodeur vidéo n'a pas pu démarrer, réessayez plus tard",
+                ),
+                FfmpegErrorKind::NetworkStall => fixed(
+                    "Reading the input stalled, check that its URL is reachable",
+                    "Das Lesen der Eingabe ist hängen geblieben, bitte prüfen, ob die URL erreichbar ist",
+                    "La lectura de la entrada se detuvo, comprueba que su URL sea accesible",
+                    "La lecture de la source s'est bloquée, vérifiez que son URL est accessible",
+                ),
+                FfmpegErrorKind::Other => fixed(
+                    "Transcoding failed",
+                    "Umwandlung fehlgeschlagen",
+                    "La transcodificación falló",
+                    "Échec du transcodage",
+                ),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lang() {
+        assert_eq!(Lang::parse("de"), Some(Lang::De));
+        assert_eq!(Lang::parse("FR-ca"), Some(Lang::Fr));
+        assert_eq!(Lang::parse("es_MX"), Some(Lang::Es));
+        assert_eq!(Lang::parse("ja"), None);
+        assert_eq!(Lang::parse(""), None);
+    }
+
+    #[test]
+    fn test_status_text() {
+        // English text is unchanged from the messages sent before the catalog
+        assert_eq!(
+            StatusMessage::JobAccepted.text(Lang::En),
+            "Job accepted, validating input..."
+        );
+        assert_eq!(
+            StatusMessage::TransformComplete.text(Lang::En),
+            "Video transformation complete"
+        );
+        let upload = StatusMessage::UploadingFile {
+            container: "MP4",
+            servers: 1,
+        };
+        assert_eq!(upload.text(Lang::En), "Uploading MP4 to 1 server...");
+        assert_eq!(upload.label(Lang::Es), "Subiendo MP4 a 1 servidor");
+        assert_eq!(
+            StatusMessage::ExtractingThumbnails { count: 3 }.text(Lang::De),
+            "3 Vorschaubilder werden extrahiert..."
+        );
+        assert_eq!(upload.code(), "uploading-file");
+        let relocated = StatusMessage::TransformCompleteOn {
+            relays: "wss://nos.lol",
+        };
+        assert_eq!(relocated.code(), StatusMessage::TransformComplete.code());
+        assert!(relocated.text(Lang::En).ends_with("find it on wss://nos.lol"));
+    }
+
+    #[test]
+    fn test_queue_text() {
+        assert_eq!(
+            StatusMessage::Queued { ahead: 1 }.text(Lang::En),
+            "Queued behind 1 job..."
+        );
+        assert_eq!(
+            StatusMessage::Queued { ahead: 4 }.label(Lang::Fr),
+            "En file d'attente derrière 4 tâches"
+        );
+        let busy = StatusMessage::Busy { queued: 32 };
+        assert_eq!(
+            busy.text(Lang::En),
+            "This DVM is busy with 32 queued jobs, please try again later"
+        );
+        assert_eq!(busy.code(), "busy");
+    }
+
+    #[test]
+    fn test_transcode_failed_text() {
+        let corrupt = StatusMessage::TranscodeFailed {
+            kind: FfmpegErrorKind::CorruptInput,
+        };
+        assert_eq!(corrupt.text(Lang::En), "The input video is corrupt or unreadable");
+        assert_eq!(corrupt.code(), "corrupt-input");
+        let encoder = StatusMessage::TranscodeFailed {
+            kind: FfmpegErrorKind::HwEncoderInit,
+        };
+        assert_eq!(encoder.code(), "encoder-unavailable");
+        assert!(encoder.text(Lang::De).starts_with("Der Video-Encoder"));
+    }
+}
@@ -1,7 +1,28 @@
 pub mod announcement;
+pub mod creator;
+pub mod dry_run;
+pub mod encode_plan;
 pub mod encryption;
+pub mod event_input;
 pub mod events;
 pub mod handler;
+pub mod heartbeat;
+pub mod hooks;
+pub mod idempotency;
+pub mod input_cache;
+pub mod job_log;
+pub mod limits;
+pub mod liveness;
+pub mod messages;
+pub mod moderation;
+pub mod progress;
+pub mod queue;
+pub mod rate_limit;
+pub mod receipt;
+pub mod slots;
+pub mod snapshot;
+pub mod summary;
+pub mod updates;
 
 pub use announcement::{AnnouncementPublisher, DVM_ANNOUNCEMENT_KIND};
 pub use events::{
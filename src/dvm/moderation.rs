@@ -0,0 +1,482 @@
+//! Abuse reports and automatic quarantine of results.
+//!
+//! NIP-56 reports (kind 1984) that tag one of the DVM's result events are
+//! counted per result. Once `report_threshold` distinct pubkeys reported a
+//! result, its blobs are copied to the quarantine directory, deleted from
+//! the Blossom servers, and the job record is marked quarantined. The admin
+//! reviews quarantined results with `list_quarantine` and puts false
+//! positives back with `restore_quarantined`.
+//!
+//! Keys cost nothing, so only reporters with standing are counted: the
+//! admin, the job's requester, and the pubkeys either of them follows.
+
+use nostr_sdk::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::blossom::{BlossomClient, QuarantineEntry, QuarantineStatus, QuarantinedBlob};
+use crate::config::Config;
+use crate::dvm::encryption::decrypt_content;
+use crate::dvm::events::{DvmResult, DVM_VIDEO_TRANSFORM_RESULT_KIND};
+use crate::dvm_state::SharedDvmState;
+use crate::nostr::EventPublisher;
+use crate::util::hash::hash_file;
+
+/// Report type used when the reporter gave none
+const DEFAULT_REASON: &str = "other";
+
+/// An event named by a report
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportTarget {
+    pub event_id: EventId,
+    /// NIP-56 report type (e.g. "illegal", "spam")
+    pub reason: String,
+}
+
+/// Events a NIP-56 report names, with their report types.
+///
+/// The type is the third element of the `e` tag, falling back to the
+/// `p` tag's for reports that only type the author.
+pub fn parse_report(event: &Event) -> Vec<ReportTarget> {
+    if event.kind != Kind::Reporting {
+        return Vec::new();
+    }
+    let typed = |name: &str| {
+        event.tags.iter().find_map(|t| {
+            let parts = t.as_slice();
+            (parts.len() >= 3 && parts[0] == name && !parts[2].is_empty()).then(|| parts[2].clone())
+        })
+    };
+    let fallback = typed("p").unwrap_or_else(|| DEFAULT_REASON.to_string());
+
+    event
+        .tags
+        .iter()
+        .filter_map(|t| {
+            let parts = t.as_slice();
+            if parts.len() < 2 || parts[0] != "e" {
+                return None;
+            }
+            let event_id = EventId::parse(&parts[1]).ok()?;
+            let reason = parts
+                .get(2)
+                .filter(|r| !r.is_empty())
+                .cloned()
+                .unwrap_or_else(|| fallback.clone());
+            Some(ReportTarget { event_id, reason })
+        })
+        .collect()
+}
+
+/// Read the result a result event carries, decrypting it if needed
+pub(crate) async fn read_result(event: &Event, keys: &Keys) -> Result<DvmResult, String> {
+    let encrypted = event
+        .tags
+        .iter()
+        .any(|t| t.as_slice().first().map(String::as_str) == Some("encrypted"));
+    let content = if encrypted {
+        let requester = event
+            .tags
+            .iter()
+            .find_map(|t| {
+                let parts = t.as_slice();
+                (parts.len() >= 2 && parts[0] == "p").then(|| PublicKey::parse(&parts[1]).ok())?
+            })
+            .ok_or("encrypted result without requester")?;
+        decrypt_content(keys, &requester, &event.content)
+            .await
+            .map_err(|e| e.to_string())?
+    } else {
+        event.content.clone()
+    };
+    serde_json::from_str(&content).map_err(|e| format!("unreadable result: {}", e))
+}
+
+/// Request event the result answers (hex)
+fn result_job_id(event: &Event) -> Option<String> {
+    event.tags.iter().find_map(|t| {
+        let parts = t.as_slice();
+        (parts.len() >= 2 && parts[0] == "e").then(|| parts[1].clone())
+    })
+}
+
+/// The requester a result event answers
+fn result_requester(event: &Event) -> Option<PublicKey> {
+    event.tags.iter().find_map(|t| {
+        let parts = t.as_slice();
+        (parts.len() >= 2 && parts[0] == "p")
+            .then(|| PublicKey::from_hex(&parts[1]).ok())
+            .flatten()
+    })
+}
+
+/// Whether `reporter` is one of `vouchers` or followed by one of them,
+/// going by their NIP-02 contact lists
+fn has_standing(reporter: &PublicKey, vouchers: &[PublicKey], contact_lists: &[Event]) -> bool {
+    let reporter_hex = reporter.to_hex();
+    vouchers.contains(reporter)
+        || contact_lists.iter().any(|list| {
+            list.kind == Kind::ContactList
+                && vouchers.contains(&list.pubkey)
+                && list.tags.iter().any(|t| {
+                    let parts = t.as_slice();
+                    parts.len() >= 2 && parts[0] == "p" && parts[1] == reporter_hex
+                })
+        })
+}
+
+pub struct Moderator {
+    config: Arc<Config>,
+    state: SharedDvmState,
+    publisher: Arc<EventPublisher>,
+    blossom: Arc<BlossomClient>,
+    http: reqwest::Client,
+    /// Where quarantined blobs are kept until reviewed
+    dir: PathBuf,
+}
+
+impl Moderator {
+    pub fn new(
+        config: Arc<Config>,
+        state: SharedDvmState,
+        publisher: Arc<EventPublisher>,
+        blossom: Arc<BlossomClient>,
+        dir: PathBuf,
+    ) -> Self {
+        Self {
+            config,
+            state,
+            publisher,
+            blossom,
+            http: crate::util::proxy::http_client(),
+            dir,
+        }
+    }
+
+    /// Handle reports until the channel closes
+    pub async fn run(self, mut reports: mpsc::Receiver<Event>) {
+        while let Some(report) = reports.recv().await {
+            for target in parse_report(&report) {
+                self.handle_report(&report.pubkey, target).await;
+            }
+        }
+    }
+
+    async fn handle_report(&self, reporter: &PublicKey, target: ReportTarget) {
+        let threshold = self.state.read().await.config.report_threshold;
+        if threshold == 0 {
+            return;
+        }
+        let result_id = target.event_id.to_hex();
+        if self.state.read().await.quarantine.get(&result_id).is_some() {
+            return;
+        }
+
+        // Only our own results count, so reports about anything else don't
+        // pile up in the ledger
+        let Some(event) = self.publisher.fetch_event(target.event_id, &[]).await else {
+            debug!(event_id = %result_id, "Reported event not found");
+            return;
+        };
+        if event.pubkey != self.config.nostr_keys.public_key()
+            || event.kind != DVM_VIDEO_TRANSFORM_RESULT_KIND
+        {
+            return;
+        }
+        if !self.has_standing(reporter, &event).await {
+            debug!(event_id = %result_id, reporter = %reporter, "Ignoring report from a pubkey without standing");
+            return;
+        }
+
+        let (reporters, reasons) = {
+            let mut state = self.state.write().await;
+            let Some(reporters) =
+                state
+                    .quarantine
+                    .record_report(&result_id, &reporter.to_hex(), &target.reason)
+            else {
+                return;
+            };
+            if let Err(e) = state.quarantine.save() {
+                warn!(error = %e, "Failed to save quarantine ledger");
+            }
+            (reporters, state.quarantine.reasons(&result_id))
+        };
+        info!(event_id = %result_id, reporters, threshold, reason = %target.reason, "Result reported");
+        if reporters < threshold as usize {
+            return;
+        }
+
+        match self.quarantine(&event, reporters, reasons).await {
+            Ok(entry) => warn!(
+                event_id = %result_id,
+                job_id = entry.job_id.as_deref().unwrap_or("-"),
+                blobs = entry.blobs.len(),
+                "Quarantined reported result pending admin review"
+            ),
+            Err(e) => warn!(event_id = %result_id, error = %e, "Failed to quarantine result"),
+        }
+    }
+
+    /// Whether `reporter` is the admin or the requester of `result`, or
+    /// followed by either of them
+    async fn has_standing(&self, reporter: &PublicKey, result: &Event) -> bool {
+        let admin = self.state.read().await.config.admin_pubkey();
+        let vouchers: Vec<PublicKey> = admin.into_iter().chain(result_requester(result)).collect();
+        if vouchers.is_empty() {
+            return false;
+        }
+        if vouchers.contains(reporter) {
+            return true;
+        }
+        let filter = Filter::new()
+            .kind(Kind::ContactList)
+            .authors(vouchers.clone());
+        let contact_lists = self.publisher.fetch_matching(filter, &[]).await;
+        has_standing(reporter, &vouchers, &contact_lists)
+    }
+
+    /// Take a result's blobs down, keeping copies for review
+    async fn quarantine(
+        &self,
+        event: &Event,
+        reporters: usize,
+        reasons: Vec<String>,
+    ) -> Result<QuarantineEntry, String> {
+        let result = read_result(event, &self.config.nostr_keys).await?;
+        let job_id = result_job_id(event);
+        let servers = self.servers().await;
+
+        let mut blobs = Vec::new();
+        for sha256 in result.blob_hashes() {
+            let mimetype = match self.save_copy(&servers, &sha256).await {
+                Ok(mimetype) => Some(mimetype),
+                Err(e) => {
+                    warn!(sha256 = %sha256, error = %e, "Could not keep a copy of quarantined blob");
+                    None
+                }
+            };
+            for server in &servers {
+                if let Err(e) = self.blossom.delete_blob(server, &sha256).await {
+                    debug!(server = %server, sha256 = %sha256, error = %e, "Delete failed");
+                }
+            }
+            blobs.push(QuarantinedBlob {
+                saved: mimetype.is_some(),
+                mimetype: mimetype.flatten(),
+                sha256,
+            });
+        }
+
+        let entry = QuarantineEntry {
+            result_id: event.id.to_hex(),
+            job_id,
+            blobs,
+            reasons,
+            reporters,
+            quarantined_at: Timestamp::now().as_u64(),
+            status: QuarantineStatus::Quarantined,
+        };
+        let mut state = self.state.write().await;
+        if let Some(job_id) = &entry.job_id {
+            state.set_quarantined(job_id, true);
+        }
+        state.quarantine.quarantine(entry.clone());
+        if let Err(e) = state.quarantine.save() {
+            warn!(error = %e, "Failed to save quarantine ledger");
+        }
+        Ok(entry)
+    }
+
+    async fn servers(&self) -> Vec<Url> {
+        self.state
+            .read()
+            .await
+            .config
+            .blossom_servers
+            .iter()
+            .filter_map(|s| Url::parse(s).ok())
+            .collect()
+    }
+
+    /// Download a blob from the first server holding it into the quarantine
+    /// directory, returning the content type it was served with
+    async fn save_copy(&self, servers: &[Url], sha256: &str) -> Result<Option<String>, String> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| e.to_string())?;
+        let path = self.dir.join(sha256);
+        let mut last_error = "no Blossom servers configured".to_string();
+
+        for server in servers {
+            match self.download(server, sha256, &path).await {
+                Ok(mimetype) => return Ok(mimetype),
+                Err(e) => {
+                    let _ = tokio::fs::remove_file(&path).await;
+                    last_error = format!("{}: {}", server, e);
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn download(&self, server: &Url, sha256: &str, path: &Path) -> Result<Option<String>, String> {
+        let url = server.join(&format!("/{}", sha256)).map_err(|e| e.to_string())?;
+        let mut resp = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| e.to_string())?;
+        let mimetype = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        let mut file = tokio::fs::File::create(path).await.map_err(|e| e.to_string())?;
+        while let Some(chunk) = resp.chunk().await.map_err(|e| e.to_string())? {
+            file.write_all(&chunk).await.map_err(|e| e.to_string())?;
+        }
+        file.flush().await.map_err(|e| e.to_string())?;
+
+        let hash = hash_file(path).await.map_err(|e| e.to_string())?;
+        if hash != sha256 {
+            return Err(format!("hash mismatch (got {})", hash));
+        }
+        Ok(mimetype)
+    }
+}
+
+/// Put a quarantined result's blobs back on the Blossom servers.
+///
+/// Blobs without a local copy can't be restored; the entry is marked
+/// restored anyway so further reports about it are ignored.
+pub async fn restore(
+    state: &SharedDvmState,
+    blossom: &BlossomClient,
+    dir: &Path,
+    result_id: &str,
+) -> Result<QuarantineEntry, String> {
+    let entry = state
+        .read()
+        .await
+        .quarantine
+        .get(result_id)
+        .cloned()
+        .ok_or_else(|| format!("No quarantined result {}", result_id))?;
+    if entry.status != QuarantineStatus::Quarantined {
+        return Err(format!("Result {} was already restored", result_id));
+    }
+
+    for blob in entry.blobs.iter().filter(|b| b.saved) {
+        let path = dir.join(&blob.sha256);
+        let mimetype = blob.mimetype.as_deref().unwrap_or("application/octet-stream");
+        blossom
+            .upload_file_to_all(&path, mimetype)
+            .await
+            .map_err(|e| format!("Failed to restore blob {}: {}", blob.sha256, e))?;
+    }
+    for blob in entry.blobs.iter().filter(|b| b.saved) {
+        let _ = tokio::fs::remove_file(dir.join(&blob.sha256)).await;
+    }
+
+    let mut state = state.write().await;
+    if let Some(job_id) = &entry.job_id {
+        state.set_quarantined(job_id, false);
+    }
+    let restored = state
+        .quarantine
+        .mark_restored(result_id)
+        .cloned()
+        .ok_or_else(|| format!("No quarantined result {}", result_id))?;
+    if let Err(e) = state.quarantine.save() {
+        warn!(error = %e, "Failed to save quarantine ledger");
+    }
+    info!(event_id = %result_id, "Restored quarantined result");
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(tags: Vec<Tag>) -> Event {
+        EventBuilder::new(Kind::Reporting, "", tags)
+            .to_event(&Keys::generate())
+            .unwrap()
+    }
+
+    fn tag(parts: &[&str]) -> Tag {
+        Tag::parse(parts).unwrap()
+    }
+
+    #[test]
+    fn test_parse_report() {
+        let a = EventId::all_zeros().to_hex();
+        let b = "1".repeat(64);
+        let author = Keys::generate().public_key().to_hex();
+
+        let event = report(vec![
+            tag(&["e", &a, "illegal"]),
+            tag(&["e", &b]),
+            tag(&["p", &author, "spam"]),
+        ]);
+        let targets = parse_report(&event);
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].event_id.to_hex(), a);
+        assert_eq!(targets[0].reason, "illegal");
+        assert_eq!(targets[1].reason, "spam");
+
+        // Untyped report
+        let event = report(vec![tag(&["e", &a]), tag(&["p", &author])]);
+        assert_eq!(parse_report(&event)[0].reason, "other");
+
+        // Not a report
+        let event = EventBuilder::new(Kind::TextNote, "", vec![tag(&["e", &a, "spam"])])
+            .to_event(&Keys::generate())
+            .unwrap();
+        assert!(parse_report(&event).is_empty());
+    }
+
+    #[test]
+    fn test_has_standing() {
+        let admin = Keys::generate();
+        let requester = Keys::generate();
+        let friend = Keys::generate().public_key();
+        let stranger = Keys::generate().public_key();
+        let vouchers = [admin.public_key(), requester.public_key()];
+
+        let follows = |keys: &Keys, pubkey: &PublicKey| {
+            EventBuilder::new(Kind::ContactList, "", vec![tag(&["p", &pubkey.to_hex()])])
+                .to_event(keys)
+                .unwrap()
+        };
+        let lists = [follows(&admin, &friend)];
+
+        assert!(has_standing(&admin.public_key(), &vouchers, &[]));
+        assert!(has_standing(&requester.public_key(), &vouchers, &[]));
+        assert!(has_standing(&friend, &vouchers, &lists));
+        assert!(!has_standing(&stranger, &vouchers, &lists));
+        // A contact list by anyone else vouches for nobody
+        let sybil = Keys::generate();
+        assert!(!has_standing(&stranger, &vouchers, &[follows(&sybil, &stranger)]));
+    }
+
+    #[test]
+    fn test_result_requester() {
+        let requester = Keys::generate().public_key();
+        let event = EventBuilder::new(
+            DVM_VIDEO_TRANSFORM_RESULT_KIND,
+            "",
+            vec![tag(&["e", &EventId::all_zeros().to_hex()]), tag(&["p", &requester.to_hex()])],
+        )
+        .to_event(&Keys::generate())
+        .unwrap();
+        assert_eq!(result_requester(&event), Some(requester));
+    }
+}
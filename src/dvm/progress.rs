@@ -0,0 +1,107 @@
+//! Whole-job progress percentage.
+//!
+//! Downloading, transcoding and uploading each measure their own 0–100%.
+//! Requesters see a single figure for the whole job, so each phase is mapped
+//! onto a weighted slice of it, and the figure never goes backwards (e.g.
+//! when an estimate is revised or an upload phase starts).
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::dvm::events::ProgressPhase;
+
+/// Relative share of the job spent in each phase.
+///
+/// Jobs without a download spread its share over the other phases.
+const DOWNLOAD_WEIGHT: u32 = 15;
+const TRANSCODE_WEIGHT: u32 = 65;
+const UPLOAD_WEIGHT: u32 = 20;
+
+/// Highest figure reported before the result is published
+const MAX_REPORTED: u32 = 99;
+
+/// Progress of one job across all of its phases
+#[derive(Debug, Default)]
+pub struct JobProgress {
+    /// Whether the input is downloaded before transcoding
+    download: bool,
+    /// Highest overall percentage reported so far
+    reported: AtomicU32,
+}
+
+impl JobProgress {
+    pub fn new(download: bool) -> Self {
+        Self {
+            download,
+            reported: AtomicU32::new(0),
+        }
+    }
+
+    fn phases(&self) -> Vec<(ProgressPhase, u32)> {
+        let mut phases = Vec::with_capacity(3);
+        if self.download {
+            phases.push((ProgressPhase::Downloading, DOWNLOAD_WEIGHT));
+        }
+        phases.push((ProgressPhase::Transcoding, TRANSCODE_WEIGHT));
+        phases.push((ProgressPhase::Uploading, UPLOAD_WEIGHT));
+        phases
+    }
+
+    /// Record `phase_pct` percent of `phase` done and return the overall
+    /// percentage to show, which is never lower than one shown before.
+    pub fn report(&self, phase: ProgressPhase, phase_pct: u32) -> u32 {
+        let phases = self.phases();
+        let total: u32 = phases.iter().map(|(_, w)| w).sum();
+
+        let mut done = 0;
+        let mut weight = None;
+        for (p, w) in &phases {
+            if *p == phase {
+                weight = Some(*w);
+                break;
+            }
+            done += w;
+        }
+        // Phases outside the model (e.g. queued) don't move the figure
+        let Some(weight) = weight else {
+            return self.current();
+        };
+
+        let done = done as f64 + weight as f64 * phase_pct.min(100) as f64 / 100.0;
+        let overall = ((done / total as f64) * 100.0) as u32;
+        let overall = overall.min(MAX_REPORTED);
+        self.reported.fetch_max(overall, Ordering::Relaxed).max(overall)
+    }
+
+    /// Highest overall percentage reported so far
+    pub fn current(&self) -> u32 {
+        self.reported.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_spans_phases() {
+        let progress = JobProgress::new(false);
+        assert_eq!(progress.report(ProgressPhase::Transcoding, 0), 0);
+        assert_eq!(progress.report(ProgressPhase::Transcoding, 50), 38);
+        assert_eq!(progress.report(ProgressPhase::Transcoding, 100), 76);
+        // The upload starting at 0% doesn't reset the figure
+        assert_eq!(progress.report(ProgressPhase::Uploading, 0), 76);
+        assert_eq!(progress.report(ProgressPhase::Uploading, 50), 88);
+        // Nor does a revised estimate
+        assert_eq!(progress.report(ProgressPhase::Uploading, 10), 88);
+        assert_eq!(progress.report(ProgressPhase::Uploading, 100), MAX_REPORTED);
+        assert_eq!(progress.report(ProgressPhase::Queued, 100), MAX_REPORTED);
+    }
+
+    #[test]
+    fn test_progress_with_download() {
+        let progress = JobProgress::new(true);
+        assert_eq!(progress.report(ProgressPhase::Downloading, 100), 15);
+        assert_eq!(progress.report(ProgressPhase::Transcoding, 0), 15);
+        assert_eq!(progress.report(ProgressPhase::Transcoding, 100), 80);
+    }
+}
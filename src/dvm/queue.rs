@@ -0,0 +1,242 @@
+//! Scheduling of jobs waiting for an encode slot.
+//!
+//! Jobs are queued per requester. Under [`QueuePolicy::RoundRobin`] each
+//! requester with waiting jobs gets one job started in turn, so a single
+//! pubkey submitting a batch of videos can't starve everyone else.
+//! [`QueuePolicy::Fifo`] starts jobs strictly in arrival order.
+//! [`Lanes`] adds a priority lane that is always served first, with the
+//! same policy applied within each lane.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+/// Order in which waiting jobs are started
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueuePolicy {
+    /// One job per requester in turn
+    #[default]
+    RoundRobin,
+    /// Strict arrival order
+    Fifo,
+}
+
+impl QueuePolicy {
+    /// Parse a policy name. Returns `None` for unrecognized values.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "round_robin" | "round-robin" | "fair" => Some(Self::RoundRobin),
+            "fifo" => Some(Self::Fifo),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::RoundRobin => "round_robin",
+            Self::Fifo => "fifo",
+        }
+    }
+}
+
+/// Waiting items grouped by owner, served round-robin or in arrival order.
+///
+/// The policy is chosen per [`pop`](Self::pop), so it can change while items
+/// are waiting.
+#[derive(Debug)]
+pub struct FairQueue<K, T> {
+    /// Waiting items per owner, each tagged with its arrival sequence number
+    queues: HashMap<K, VecDeque<(u64, T)>>,
+    /// Owners with waiting items, in round-robin turn order
+    turns: VecDeque<K>,
+    next_seq: u64,
+    len: usize,
+}
+
+impl<K: Eq + Hash + Clone, T> FairQueue<K, T> {
+    pub fn new() -> Self {
+        Self {
+            queues: HashMap::new(),
+            turns: VecDeque::new(),
+            next_seq: 0,
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, owner: K, item: T) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.len += 1;
+
+        let queue = self.queues.entry(owner.clone()).or_default();
+        if queue.is_empty() {
+            self.turns.push_back(owner);
+        }
+        queue.push_back((seq, item));
+    }
+
+    /// Take the next item to run under `policy`.
+    pub fn pop(&mut self, policy: QueuePolicy) -> Option<T> {
+        let turn = match policy {
+            QueuePolicy::RoundRobin => 0,
+            // Owner whose oldest item arrived first; the turn order is left as is
+            QueuePolicy::Fifo => self
+                .turns
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, owner)| self.queues[*owner].front().map(|(seq, _)| *seq))?
+                .0,
+        };
+
+        let owner = self.turns.remove(turn)?;
+        let queue = self.queues.get_mut(&owner)?;
+        let (_, item) = queue.pop_front()?;
+        self.len -= 1;
+
+        if queue.is_empty() {
+            self.queues.remove(&owner);
+        } else if policy == QueuePolicy::RoundRobin {
+            self.turns.push_back(owner);
+        } else {
+            self.turns.insert(turn, owner);
+        }
+
+        Some(item)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<K: Eq + Hash + Clone, T> Default for FairQueue<K, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Waiting items in a priority and a normal lane.
+///
+/// The priority lane is emptied before anything in the normal lane starts.
+#[derive(Debug)]
+pub struct Lanes<K, T> {
+    priority: FairQueue<K, T>,
+    normal: FairQueue<K, T>,
+}
+
+impl<K: Eq + Hash + Clone, T> Lanes<K, T> {
+    pub fn new() -> Self {
+        Self {
+            priority: FairQueue::new(),
+            normal: FairQueue::new(),
+        }
+    }
+
+    pub fn push(&mut self, owner: K, item: T, priority: bool) {
+        if priority {
+            self.priority.push(owner, item);
+        } else {
+            self.normal.push(owner, item);
+        }
+    }
+
+    /// Take the next item to run under `policy`, priority lane first.
+    pub fn pop(&mut self, policy: QueuePolicy) -> Option<T> {
+        self.priority.pop(policy).or_else(|| self.normal.pop(policy))
+    }
+
+    pub fn len(&self) -> usize {
+        self.priority.len() + self.normal.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K: Eq + Hash + Clone, T> Default for Lanes<K, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(queue: &mut FairQueue<&'static str, u32>, policy: QueuePolicy) -> Vec<u32> {
+        std::iter::from_fn(|| queue.pop(policy)).collect()
+    }
+
+    fn batch_then_others() -> FairQueue<&'static str, u32> {
+        let mut queue = FairQueue::new();
+        for i in 1..=4 {
+            queue.push("alice", i);
+        }
+        queue.push("bob", 10);
+        queue.push("carol", 20);
+        queue.push("bob", 11);
+        queue
+    }
+
+    #[test]
+    fn test_round_robin_interleaves_requesters() {
+        let mut queue = batch_then_others();
+        assert_eq!(queue.len(), 7);
+        assert_eq!(
+            drain(&mut queue, QueuePolicy::RoundRobin),
+            vec![1, 10, 20, 2, 11, 3, 4]
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_fifo_keeps_arrival_order() {
+        let mut queue = batch_then_others();
+        assert_eq!(
+            drain(&mut queue, QueuePolicy::Fifo),
+            vec![1, 2, 3, 4, 10, 20, 11]
+        );
+    }
+
+    #[test]
+    fn test_policy_switch_while_waiting() {
+        let mut queue = batch_then_others();
+        assert_eq!(queue.pop(QueuePolicy::Fifo), Some(1));
+        assert_eq!(queue.pop(QueuePolicy::Fifo), Some(2));
+        // Alice still holds the first turn
+        assert_eq!(queue.pop(QueuePolicy::RoundRobin), Some(3));
+        assert_eq!(
+            drain(&mut queue, QueuePolicy::RoundRobin),
+            vec![10, 20, 4, 11]
+        );
+    }
+
+    #[test]
+    fn test_priority_lane_served_first() {
+        let mut lanes = Lanes::new();
+        lanes.push("alice", 1, false);
+        lanes.push("bob", 2, false);
+        lanes.push("carol", 3, true);
+        lanes.push("alice", 4, true);
+        assert_eq!(lanes.len(), 4);
+        assert_eq!(
+            std::iter::from_fn(|| lanes.pop(QueuePolicy::RoundRobin)).collect::<Vec<_>>(),
+            vec![3, 4, 1, 2]
+        );
+        assert!(lanes.is_empty());
+    }
+
+    #[test]
+    fn test_queue_policy_parse() {
+        assert_eq!(QueuePolicy::parse("FIFO"), Some(QueuePolicy::Fifo));
+        assert_eq!(QueuePolicy::parse("round_robin"), Some(QueuePolicy::RoundRobin));
+        assert_eq!(QueuePolicy::parse("lifo"), None);
+        assert_eq!(QueuePolicy::default().as_str(), "round_robin");
+    }
+}
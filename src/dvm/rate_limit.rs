@@ -0,0 +1,98 @@
+//! Per-requester rate limiting.
+//!
+//! A single client submitting jobs in a loop can keep a free DVM busy for
+//! everyone else. The limiter counts the jobs each pubkey started within the
+//! last hour (a sliding window) and turns further requests away until the
+//! oldest of them drops out, telling the requester when to try again.
+
+use nostr_sdk::prelude::*;
+use std::collections::{HashMap, VecDeque};
+
+/// Length of the window jobs are counted in
+pub const RATE_LIMIT_WINDOW_SECS: u64 = 3600;
+
+/// Job start times per requester within the window
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    starts: HashMap<PublicKey, VecDeque<u64>>,
+}
+
+impl RateLimiter {
+    /// Seconds until `pubkey` may start another job, or `Ok` if it may now.
+    ///
+    /// A `limit` of 0 never limits.
+    pub fn check(&self, pubkey: &PublicKey, now: u64, limit: u32) -> Result<(), u64> {
+        if limit == 0 {
+            return Ok(());
+        }
+        let Some(starts) = self.starts.get(pubkey) else {
+            return Ok(());
+        };
+        let recent: Vec<u64> = starts
+            .iter()
+            .copied()
+            .filter(|t| now < t + RATE_LIMIT_WINDOW_SECS)
+            .collect();
+        if recent.len() < limit as usize {
+            return Ok(());
+        }
+        // A slot frees up when the job that would bring the count below the limit expires
+        let freeing = recent[recent.len() - limit as usize];
+        Err(freeing + RATE_LIMIT_WINDOW_SECS - now)
+    }
+
+    /// Count a job started by `pubkey` at `now`, forgetting expired ones.
+    pub fn record(&mut self, pubkey: PublicKey, now: u64) {
+        self.starts.retain(|_, starts| {
+            while starts
+                .front()
+                .is_some_and(|t| now >= t + RATE_LIMIT_WINDOW_SECS)
+            {
+                starts.pop_front();
+            }
+            !starts.is_empty()
+        });
+        self.starts.entry(pubkey).or_default().push_back(now);
+    }
+
+    /// Count a job for `pubkey` if it is within `limit`, see [`Self::check`].
+    pub fn try_acquire(&mut self, pubkey: PublicKey, now: u64, limit: u32) -> Result<(), u64> {
+        self.check(&pubkey, now, limit)?;
+        self.record(pubkey, now);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit() {
+        let mut limiter = RateLimiter::default();
+        let alice = Keys::generate().public_key();
+        let bob = Keys::generate().public_key();
+
+        assert_eq!(limiter.try_acquire(alice, 1000, 2), Ok(()));
+        assert_eq!(limiter.try_acquire(alice, 1600, 2), Ok(()));
+        // Third job waits for the first to leave the window
+        assert_eq!(limiter.try_acquire(alice, 2000, 2), Err(2600));
+        assert_eq!(limiter.try_acquire(bob, 2000, 2), Ok(()));
+
+        assert_eq!(limiter.try_acquire(alice, 4600, 2), Ok(()));
+        assert_eq!(limiter.check(&alice, 4600, 2), Err(600));
+        assert_eq!(limiter.check(&alice, 4600, 0), Ok(()));
+    }
+
+    #[test]
+    fn test_rate_limit_forgets_expired() {
+        let mut limiter = RateLimiter::default();
+        let alice = Keys::generate().public_key();
+        let bob = Keys::generate().public_key();
+
+        limiter.record(alice, 0);
+        limiter.record(bob, RATE_LIMIT_WINDOW_SECS);
+        assert!(!limiter.starts.contains_key(&alice));
+        assert_eq!(limiter.starts[&bob].len(), 1);
+    }
+}
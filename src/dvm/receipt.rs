@@ -0,0 +1,176 @@
+//! Signed payment receipts for paid jobs.
+//!
+//! When a paid job completes, the DVM signs a receipt recording what was paid
+//! for which job and which blobs it produced. The receipt is not published:
+//! the requester gets the signed event as JSON in a NIP-17 direct message, so
+//! they can later prove the DVM took their payment, and the operator keeps
+//! every receipt as one line of `$DATA_DIR/receipts.jsonl`.
+
+use nostr_sdk::prelude::*;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::error::DvmError;
+
+/// Kind of receipt events (request kind 5207 + 12000)
+pub const DVM_RECEIPT_KIND: Kind = Kind::Custom(17207);
+
+/// How a job was paid for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaymentMethod {
+    Cashu,
+    Lightning,
+}
+
+impl PaymentMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Cashu => "cashu",
+            Self::Lightning => "lightning",
+        }
+    }
+}
+
+/// What a receipt records
+#[derive(Debug, Clone, PartialEq)]
+pub struct Receipt {
+    pub job_id: EventId,
+    pub result_id: EventId,
+    pub requester: PublicKey,
+    pub amount_sats: u64,
+    pub method: PaymentMethod,
+    /// Key identifying the source (see [`crate::dvm::summary::input_hash`])
+    pub input_hash: String,
+    /// Blob hashes of the outputs
+    pub output_hashes: Vec<String>,
+}
+
+impl Receipt {
+    /// Sign the receipt with the DVM's keys.
+    ///
+    /// The amount is in millisats, like NIP-90 `amount` tags.
+    pub fn to_event(&self, keys: &Keys) -> Result<Event, DvmError> {
+        let mut tags = vec![
+            Tag::custom(
+                TagKind::Custom("e".into()),
+                vec![self.job_id.to_hex(), String::new(), "request".to_string()],
+            ),
+            Tag::custom(
+                TagKind::Custom("e".into()),
+                vec![self.result_id.to_hex(), String::new(), "result".to_string()],
+            ),
+            Tag::public_key(self.requester),
+            Tag::custom(
+                TagKind::Custom("amount".into()),
+                vec![(self.amount_sats * 1000).to_string()],
+            ),
+            Tag::custom(
+                TagKind::Custom("payment".into()),
+                vec![self.method.as_str().to_string()],
+            ),
+            Tag::custom(
+                TagKind::Custom("x".into()),
+                vec![self.input_hash.clone(), "input".to_string()],
+            ),
+        ];
+        for sha256 in &self.output_hashes {
+            tags.push(Tag::custom(
+                TagKind::Custom("x".into()),
+                vec![sha256.clone(), "output".to_string()],
+            ));
+        }
+
+        let content = format!(
+            "Received {} sats ({}) for job {}",
+            self.amount_sats,
+            self.method.as_str(),
+            self.job_id.to_hex()
+        );
+        EventBuilder::new(DVM_RECEIPT_KIND, content, tags)
+            .to_event(keys)
+            .map_err(|e| DvmError::JobRejected(format!("Failed to sign receipt: {}", e)))
+    }
+}
+
+/// Append-only record of every receipt the DVM issued
+#[derive(Debug, Clone)]
+pub struct ReceiptLedger {
+    path: PathBuf,
+}
+
+impl ReceiptLedger {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Append a signed receipt as one line of JSON
+    pub fn append(&self, receipt: &Event) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", receipt.as_json())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receipt() -> Receipt {
+        Receipt {
+            job_id: EventId::all_zeros(),
+            result_id: EventId::all_zeros(),
+            requester: Keys::generate().public_key(),
+            amount_sats: 21,
+            method: PaymentMethod::Cashu,
+            input_hash: "a".repeat(64),
+            output_hashes: vec!["b".repeat(64), "c".repeat(64)],
+        }
+    }
+
+    fn tag_values(event: &Event, name: &str) -> Vec<Vec<String>> {
+        event
+            .tags
+            .iter()
+            .map(|t| t.as_slice().to_vec())
+            .filter(|t| t[0] == name)
+            .collect()
+    }
+
+    #[test]
+    fn test_receipt_event() {
+        let keys = Keys::generate();
+        let event = receipt().to_event(&keys).unwrap();
+
+        assert!(event.verify().is_ok());
+        assert_eq!(event.kind, DVM_RECEIPT_KIND);
+        assert_eq!(tag_values(&event, "amount"), vec![vec!["amount", "21000"]]);
+        assert_eq!(tag_values(&event, "payment"), vec![vec!["payment", "cashu"]]);
+        assert_eq!(tag_values(&event, "e").len(), 2);
+        let x = tag_values(&event, "x");
+        assert_eq!(x.len(), 3);
+        assert_eq!(x[0][2], "input");
+        assert_eq!(x[2][2], "output");
+    }
+
+    #[test]
+    fn test_ledger_appends_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let ledger = ReceiptLedger::new(dir.path().join("receipts.jsonl"));
+        let keys = Keys::generate();
+        ledger.append(&receipt().to_event(&keys).unwrap()).unwrap();
+        ledger.append(&receipt().to_event(&keys).unwrap()).unwrap();
+
+        let contents = std::fs::read_to_string(dir.path().join("receipts.jsonl")).unwrap();
+        let events: Vec<Event> = contents
+            .lines()
+            .map(|l| Event::from_json(l).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| e.verify().is_ok()));
+    }
+}
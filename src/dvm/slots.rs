@@ -0,0 +1,151 @@
+//! Job slots that can be resized while jobs run.
+//!
+//! The number of jobs run at once comes from
+//! `RemoteConfig::max_concurrent_jobs`, which the operator can change at any
+//! time. Raising it frees slots immediately; lowering it lets running jobs
+//! finish and starts no new ones until fewer than the new limit remain.
+
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+#[derive(Debug, Clone, Copy)]
+struct Usage {
+    limit: usize,
+    running: usize,
+}
+
+/// Counts running jobs against an adjustable limit
+#[derive(Debug)]
+pub struct JobSlots {
+    usage: watch::Sender<Usage>,
+}
+
+impl JobSlots {
+    pub fn new(limit: usize) -> Arc<Self> {
+        Arc::new(Self {
+            usage: watch::channel(Usage {
+                limit: limit.max(1),
+                running: 0,
+            })
+            .0,
+        })
+    }
+
+    /// Change the number of jobs allowed at once (at least one)
+    pub fn resize(&self, limit: usize) {
+        self.usage.send_if_modified(|usage| {
+            let limit = limit.max(1);
+            let changed = usage.limit != limit;
+            usage.limit = limit;
+            changed
+        });
+    }
+
+    pub fn limit(&self) -> usize {
+        self.usage.borrow().limit
+    }
+
+    /// Slots free right now
+    pub fn available(&self) -> usize {
+        let usage = *self.usage.borrow();
+        usage.limit.saturating_sub(usage.running)
+    }
+
+    /// Wait for a free slot and take it until the returned permit is dropped
+    pub async fn acquire(self: &Arc<Self>) -> SlotPermit {
+        let mut changes = self.usage.subscribe();
+        loop {
+            // The sender lives in `self`, so waiting can't fail
+            let _ = changes.wait_for(|u| u.running < u.limit).await;
+            let taken = self.usage.send_if_modified(|usage| {
+                if usage.running < usage.limit {
+                    usage.running += 1;
+                    true
+                } else {
+                    false
+                }
+            });
+            if taken {
+                return SlotPermit {
+                    slots: self.clone(),
+                };
+            }
+        }
+    }
+}
+
+/// A running job's slot, given back when dropped
+#[derive(Debug)]
+pub struct SlotPermit {
+    slots: Arc<JobSlots>,
+}
+
+impl Drop for SlotPermit {
+    fn drop(&mut self) {
+        self.slots
+            .usage
+            .send_modify(|usage| usage.running = usage.running.saturating_sub(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    const WAIT: Duration = Duration::from_millis(50);
+
+    #[tokio::test]
+    async fn test_acquire_up_to_limit() {
+        let slots = JobSlots::new(2);
+        let _a = slots.acquire().await;
+        let b = slots.acquire().await;
+        assert_eq!(slots.available(), 0);
+        assert!(timeout(WAIT, slots.acquire()).await.is_err());
+
+        drop(b);
+        assert_eq!(slots.available(), 1);
+        assert!(timeout(WAIT, slots.acquire()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_grow_frees_waiting_acquire() {
+        let slots = JobSlots::new(1);
+        let _a = slots.acquire().await;
+        let waiting = tokio::spawn({
+            let slots = slots.clone();
+            async move { slots.acquire().await }
+        });
+        tokio::time::sleep(WAIT).await;
+        assert!(!waiting.is_finished());
+
+        slots.resize(2);
+        assert!(timeout(WAIT, waiting).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_shrink_waits_for_running_jobs() {
+        let slots = JobSlots::new(3);
+        let a = slots.acquire().await;
+        let b = slots.acquire().await;
+        slots.resize(1);
+        assert_eq!(slots.limit(), 1);
+        assert_eq!(slots.available(), 0);
+
+        // One job finishing still leaves the new limit reached
+        drop(a);
+        assert!(timeout(WAIT, slots.acquire()).await.is_err());
+        drop(b);
+        assert!(timeout(WAIT, slots.acquire()).await.is_ok());
+    }
+
+    #[test]
+    fn test_limit_is_at_least_one() {
+        let slots = JobSlots::new(0);
+        assert_eq!(slots.limit(), 1);
+        slots.resize(0);
+        assert_eq!(slots.limit(), 1);
+    }
+}
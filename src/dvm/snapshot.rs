@@ -0,0 +1,332 @@
+//! Snapshot of what a restart would otherwise lose.
+//!
+//! On shutdown the DVM writes its job counters, pending bids, the jobs still
+//! waiting for a slot and unpaid invoices to `shutdown.json` in the data
+//! directory, and applies them on the next start, so paid jobs that hadn't
+//! started yet run after all. The file records a SHA-256 of its contents;
+//! a snapshot that doesn't verify is set aside as `shutdown.json.corrupt`
+//! and reported in the log instead of being applied.
+
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+use crate::dvm::events::{JobContext, SavedJob};
+use crate::dvm_state::{DvmState, OpenInvoice, PendingBid, PENDING_BID_TIMEOUT_SECS};
+use crate::payments::Invoice;
+use crate::util::hash::hash_bytes;
+use crate::video::ffmpeg_error::FfmpegErrorKind;
+
+/// Format version of the snapshot file
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// The file on disk: the snapshot as a JSON string and its hash
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotFile {
+    version: u32,
+    sha256: String,
+    snapshot: String,
+}
+
+/// State captured at shutdown
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ShutdownSnapshot {
+    /// Unix time the snapshot was taken
+    pub taken_at: u64,
+    pub jobs_completed: u32,
+    pub jobs_failed: u32,
+    #[serde(default)]
+    pub ffmpeg_failures: BTreeMap<FfmpegErrorKind, u32>,
+    #[serde(default)]
+    pub avg_speeds: HashMap<String, f64>,
+    /// Jobs waiting for a slot, in the order they would have started
+    #[serde(default)]
+    pub queued: Vec<SavedJob>,
+    #[serde(default)]
+    pub pending_bids: Vec<SavedBid>,
+    #[serde(default)]
+    pub open_invoices: Vec<SavedInvoice>,
+}
+
+/// A bid waiting for selection, with how long it had been waiting
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedBid {
+    pub job: SavedJob,
+    pub age_secs: u64,
+}
+
+/// An invoice that was being polled for payment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedInvoice {
+    pub job: SavedJob,
+    pub invoice: Invoice,
+}
+
+/// Jobs from a snapshot that go back to the job handler
+#[derive(Debug, Default)]
+pub struct RestoredJobs {
+    /// Jobs to queue again, in order
+    pub queued: Vec<JobContext>,
+    /// Invoices to resume polling
+    pub open_invoices: Vec<OpenInvoice>,
+}
+
+impl ShutdownSnapshot {
+    /// Capture `state`, with `queued` being the jobs the job loop left waiting
+    pub fn capture(state: &DvmState, queued: &[JobContext]) -> Self {
+        let mut pending_bids: Vec<_> = state
+            .pending_bids
+            .values()
+            .map(|bid| SavedBid {
+                job: bid.context.to_saved(),
+                age_secs: bid.created_at.elapsed().as_secs(),
+            })
+            .collect();
+        pending_bids.sort_by_key(|bid| std::cmp::Reverse(bid.age_secs));
+        Self {
+            taken_at: Timestamp::now().as_u64(),
+            jobs_completed: state.jobs_completed,
+            jobs_failed: state.jobs_failed,
+            ffmpeg_failures: state.ffmpeg_failures.clone(),
+            avg_speeds: state.avg_speeds.clone(),
+            queued: queued.iter().map(JobContext::to_saved).collect(),
+            pending_bids,
+            open_invoices: state
+                .open_invoices
+                .values()
+                .map(|open| SavedInvoice {
+                    job: open.context.to_saved(),
+                    invoice: open.invoice.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Write the snapshot to `path`, replacing any earlier one in one step
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let snapshot = serde_json::to_string(self)?;
+        let file = SnapshotFile {
+            version: SNAPSHOT_VERSION,
+            sha256: hash_bytes(snapshot.as_bytes()),
+            snapshot,
+        };
+        let tmp = path.with_extension("json.tmp");
+        {
+            use std::io::Write;
+            let mut out = std::fs::File::create(&tmp)?;
+            out.write_all(serde_json::to_string(&file)?.as_bytes())?;
+            out.sync_all()?;
+        }
+        std::fs::rename(&tmp, path)
+    }
+
+    /// Read and verify the snapshot at `path`; `None` if there is none
+    pub fn load(path: &Path) -> std::io::Result<Option<Self>> {
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let file: SnapshotFile = serde_json::from_str(&json)?;
+        if file.version != SNAPSHOT_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unsupported snapshot version {}", file.version),
+            ));
+        }
+        if hash_bytes(file.snapshot.as_bytes()) != file.sha256 {
+            return Err(Error::new(ErrorKind::InvalidData, "snapshot checksum mismatch"));
+        }
+        Ok(Some(serde_json::from_str(&file.snapshot)?))
+    }
+
+    /// Add the counters and bids to `state` and rebuild the jobs that go
+    /// back to the handler. Jobs whose request no longer parses and bids
+    /// that have timed out in the meantime are dropped.
+    pub fn restore(self, state: &mut DvmState, keys: &Keys) -> RestoredJobs {
+        state.jobs_completed += self.jobs_completed;
+        state.jobs_failed += self.jobs_failed;
+        for (kind, count) in self.ffmpeg_failures {
+            *state.ffmpeg_failures.entry(kind).or_default() += count;
+        }
+        for (resolution, speed) in self.avg_speeds {
+            state.avg_speeds.entry(resolution).or_insert(speed);
+        }
+
+        let downtime = Timestamp::now().as_u64().saturating_sub(self.taken_at);
+        for bid in self.pending_bids {
+            let age = bid.age_secs + downtime;
+            if age >= PENDING_BID_TIMEOUT_SECS {
+                continue;
+            }
+            let Some(context) = rebuild(bid.job, keys) else {
+                continue;
+            };
+            let created_at = Instant::now()
+                .checked_sub(Duration::from_secs(age))
+                .unwrap_or_else(Instant::now);
+            state
+                .pending_bids
+                .insert(context.event_id(), PendingBid { context, created_at });
+        }
+
+        RestoredJobs {
+            queued: self.queued.into_iter().filter_map(|job| rebuild(job, keys)).collect(),
+            open_invoices: self
+                .open_invoices
+                .into_iter()
+                .filter_map(|open| {
+                    Some(OpenInvoice {
+                        context: rebuild(open.job, keys)?,
+                        invoice: open.invoice,
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+fn rebuild(saved: SavedJob, keys: &Keys) -> Option<JobContext> {
+    let id = saved.original_event_id.unwrap_or(saved.request.id);
+    match JobContext::from_saved(saved, keys) {
+        Ok(job) => Some(job),
+        Err(e) => {
+            warn!(job_id = %id, error = %e, "Dropping job from shutdown snapshot");
+            None
+        }
+    }
+}
+
+/// Apply the snapshot at `path` to `state` and remove it, so it is applied
+/// only once. A snapshot that fails to verify is renamed to `*.corrupt`.
+pub fn restore_snapshot(path: &Path, state: &mut DvmState, keys: &Keys) -> RestoredJobs {
+    let snapshot = match ShutdownSnapshot::load(path) {
+        Ok(Some(snapshot)) => snapshot,
+        Ok(None) => return RestoredJobs::default(),
+        Err(e) => {
+            let corrupt = path.with_extension("json.corrupt");
+            error!(
+                path = %path.display(),
+                error = %e,
+                "Shutdown snapshot failed verification, counters and queued jobs from the last run were not restored"
+            );
+            if let Err(e) = std::fs::rename(path, &corrupt) {
+                warn!(path = %path.display(), error = %e, "Failed to set aside shutdown snapshot");
+            }
+            return RestoredJobs::default();
+        }
+    };
+    if let Err(e) = std::fs::remove_file(path) {
+        warn!(path = %path.display(), error = %e, "Failed to remove applied shutdown snapshot");
+    }
+
+    let restored = snapshot.restore(state, keys);
+    info!(
+        queued = restored.queued.len(),
+        open_invoices = restored.open_invoices.len(),
+        pending_bids = state.pending_bids.len(),
+        "Restored shutdown snapshot"
+    );
+    restored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dvm::DVM_VIDEO_TRANSFORM_REQUEST_KIND;
+    use crate::remote_config::RemoteConfig;
+
+    fn job(keys: &Keys, url: &str) -> JobContext {
+        let tags = vec![Tag::custom(
+            TagKind::Custom("i".into()),
+            vec![url.to_string(), "url".to_string()],
+        )];
+        let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+            .to_event(keys)
+            .unwrap();
+        JobContext::from_event(event).unwrap()
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shutdown.json");
+        let dvm_keys = Keys::generate();
+        let requester = Keys::generate();
+
+        let mut state = DvmState::new(dvm_keys.clone(), RemoteConfig::new());
+        state.jobs_completed = 7;
+        state.jobs_failed = 2;
+        state.record_ffmpeg_failure(FfmpegErrorKind::CorruptInput);
+        state.add_bid(job(&requester, "https://example.com/bid.mp4"));
+        let mut paid = job(&requester, "https://example.com/paid.mp4");
+        paid.paid_sats = Some(210);
+        let unpaid = job(&requester, "https://example.com/unpaid.mp4");
+        state.open_invoices.insert(
+            unpaid.event_id(),
+            OpenInvoice {
+                context: unpaid.clone(),
+                invoice: Invoice {
+                    bolt11: "lnbc1".to_string(),
+                    amount_msats: 100_000,
+                    expires_at: Timestamp::now().as_u64() + 600,
+                    lookup: "hash".to_string(),
+                },
+            },
+        );
+
+        let snapshot = ShutdownSnapshot::capture(&state, std::slice::from_ref(&paid));
+        snapshot.save(&path).unwrap();
+        assert_eq!(ShutdownSnapshot::load(&path).unwrap(), Some(snapshot));
+
+        let mut restarted = DvmState::new(dvm_keys.clone(), RemoteConfig::new());
+        restarted.jobs_completed = 1;
+        let restored = restore_snapshot(&path, &mut restarted, &dvm_keys);
+        assert!(!path.exists(), "applied snapshots are removed");
+        assert_eq!(restarted.jobs_completed, 8);
+        assert_eq!(restarted.jobs_failed, 2);
+        assert_eq!(restarted.ffmpeg_failures[&FfmpegErrorKind::CorruptInput], 1);
+        assert_eq!(restarted.pending_bids.len(), 1);
+        assert_eq!(restored.queued.len(), 1);
+        assert_eq!(restored.queued[0].event_id(), paid.event_id());
+        assert_eq!(restored.queued[0].paid_sats, Some(210));
+        assert_eq!(restored.open_invoices.len(), 1);
+        assert_eq!(restored.open_invoices[0].context.event_id(), unpaid.event_id());
+
+        // Nothing to restore the second time
+        let restored = restore_snapshot(&path, &mut restarted, &dvm_keys);
+        assert!(restored.queued.is_empty());
+        assert_eq!(restarted.jobs_completed, 8);
+    }
+
+    #[test]
+    fn test_tampered_snapshot_is_set_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shutdown.json");
+        let keys = Keys::generate();
+        let snapshot = ShutdownSnapshot {
+            jobs_completed: 3,
+            ..Default::default()
+        };
+        snapshot.save(&path).unwrap();
+
+        let tampered = std::fs::read_to_string(&path)
+            .unwrap()
+            .replace(r#"\"jobs_completed\":3"#, r#"\"jobs_completed\":300"#);
+        std::fs::write(&path, tampered).unwrap();
+        assert!(ShutdownSnapshot::load(&path).is_err());
+
+        let mut state = DvmState::new(keys.clone(), RemoteConfig::new());
+        restore_snapshot(&path, &mut state, &keys);
+        assert_eq!(state.jobs_completed, 0);
+        assert!(!path.exists());
+        assert!(path.with_extension("json.corrupt").exists());
+    }
+}
@@ -0,0 +1,390 @@
+//! Addressable transcode summary events.
+//!
+//! After a public job completes, the DVM publishes a replaceable summary keyed
+//! by the source's hash (`d` tag). It links the source to every output blob so
+//! other DVMs and clients can discover existing transcodes of the same video
+//! instead of re-encoding it.
+
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::dvm::events::{DvmInput, DvmResult, JobContext, DVM_VIDEO_TRANSFORM_REQUEST_KIND};
+use crate::util::hash::{hash_bytes, sha256_from_url};
+
+/// Addressable kind for transcode summaries (request kind 5207 + 31000)
+pub const DVM_TRANSCODE_SUMMARY_KIND: Kind = Kind::Custom(36207);
+
+/// One output blob in a summary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SummaryOutput {
+    /// "mp4", "hls" (master playlist), "hls-stream", "thumbnail", "preview"
+    /// or "source" (archived copy of the input)
+    #[serde(rename = "type")]
+    pub output_type: String,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mimetype: Option<String>,
+    pub size_bytes: u64,
+}
+
+/// Content of a summary event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscodeSummary {
+    /// Source input as given in the request
+    pub input: String,
+    /// Key identifying the source (see [`input_hash`])
+    pub input_hash: String,
+    pub outputs: Vec<SummaryOutput>,
+}
+
+/// Stable key for a job's source.
+///
+/// Blossom inputs are keyed by their blob hash so the same video matches
+/// across servers; anything else by the SHA-256 of the input string.
+pub fn input_hash(input: &str) -> String {
+    sha256_from_url(input).unwrap_or_else(|| hash_bytes(input.trim().as_bytes()))
+}
+
+impl TranscodeSummary {
+    /// Distinct blob hashes of the outputs, in output order
+    pub fn output_hashes(&self) -> Vec<String> {
+        let mut hashes: Vec<String> = Vec::new();
+        for sha256 in self.outputs.iter().filter_map(|o| o.sha256.as_ref()) {
+            if !hashes.contains(sha256) {
+                hashes.push(sha256.clone());
+            }
+        }
+        hashes
+    }
+
+    pub fn from_result(input: &str, result: &DvmResult) -> Self {
+        let mut outputs = Vec::new();
+
+        let (preview, source) = match result {
+            DvmResult::Mp4(mp4) => (mp4.preview.as_ref(), mp4.source.as_ref()),
+            DvmResult::Hls(hls) => (hls.preview.as_ref(), hls.source.as_ref()),
+            DvmResult::Thumbnail(_) => (None, None),
+        };
+
+        match result {
+            DvmResult::Mp4(mp4) => {
+                for url in &mp4.urls {
+                    outputs.push(SummaryOutput {
+                        output_type: "mp4".to_string(),
+                        url: url.clone(),
+                        sha256: sha256_from_url(url),
+                        resolution: Some(mp4.resolution.clone()),
+                        mimetype: mp4.mimetype.clone(),
+                        size_bytes: mp4.size_bytes,
+                    });
+                }
+            }
+            DvmResult::Hls(hls) => {
+                outputs.push(SummaryOutput {
+                    output_type: "hls".to_string(),
+                    url: hls.master_playlist.clone(),
+                    sha256: sha256_from_url(&hls.master_playlist),
+                    resolution: None,
+                    mimetype: Some("application/vnd.apple.mpegurl".to_string()),
+                    size_bytes: hls.total_size_bytes,
+                });
+                for stream in &hls.stream_playlists {
+                    outputs.push(SummaryOutput {
+                        output_type: "hls-stream".to_string(),
+                        url: stream.url.clone(),
+                        sha256: sha256_from_url(&stream.url),
+                        resolution: Some(stream.resolution.clone()),
+                        mimetype: stream.mimetype.clone(),
+                        size_bytes: stream.size_bytes,
+                    });
+                }
+            }
+            DvmResult::Thumbnail(thumbs) => {
+                for thumb in &thumbs.thumbnails {
+                    for url in &thumb.urls {
+                        outputs.push(SummaryOutput {
+                            output_type: "thumbnail".to_string(),
+                            url: url.clone(),
+                            sha256: sha256_from_url(url),
+                            resolution: None,
+                            mimetype: Some(thumb.mimetype.clone()),
+                            size_bytes: thumb.size_bytes,
+                        });
+                    }
+                }
+            }
+        }
+
+        if let Some(preview) = preview {
+            for url in &preview.urls {
+                outputs.push(SummaryOutput {
+                    output_type: "preview".to_string(),
+                    url: url.clone(),
+                    sha256: sha256_from_url(url),
+                    resolution: None,
+                    mimetype: Some(preview.mimetype.clone()),
+                    size_bytes: preview.size_bytes,
+                });
+            }
+        }
+
+        if let Some(source) = source {
+            for url in &source.urls {
+                outputs.push(SummaryOutput {
+                    output_type: "source".to_string(),
+                    url: url.clone(),
+                    sha256: Some(source.sha256.clone()),
+                    resolution: None,
+                    mimetype: Some(source.mimetype.clone()),
+                    size_bytes: source.size_bytes,
+                });
+            }
+        }
+
+        Self {
+            input: input.to_string(),
+            input_hash: input_hash(input),
+            outputs,
+        }
+    }
+}
+
+/// Build the summary event for a completed job with `input` as requested
+/// (the job's own input may have been replaced by a local copy).
+///
+/// Returns `None` for jobs whose outputs must stay private: encrypted
+/// requests, HLS output encrypted with a key only the requester holds, and
+/// inputs other than `http(s)` URLs (local paths would reveal the operator's
+/// filesystem, and mean nothing to anyone else).
+pub fn build_summary_event(job: &JobContext, input: &DvmInput, result: &DvmResult) -> Option<EventBuilder> {
+    if job.encryption_type.is_encrypted() || !is_public_url(&input.value) {
+        return None;
+    }
+    if let DvmResult::Hls(hls) = result {
+        if hls.encryption_key.as_deref().is_some_and(|k| !k.is_empty()) {
+            return None;
+        }
+    }
+
+    let summary = TranscodeSummary::from_result(&input.value, result);
+
+    let mut tags = vec![
+        Tag::identifier(summary.input_hash.clone()),
+        Tag::custom(
+            TagKind::Custom("i".into()),
+            vec![input.value.clone(), input.input_type.clone()],
+        ),
+        Tag::custom(
+            TagKind::Custom("k".into()),
+            vec![DVM_VIDEO_TRANSFORM_REQUEST_KIND.as_u16().to_string()],
+        ),
+    ];
+
+    for sha256 in summary.output_hashes() {
+        tags.push(Tag::custom(TagKind::Custom("x".into()), vec![sha256]));
+    }
+
+    // NIP-94 placeholder tags, so clients can render the video before it loads
+    let (blurhash, dim) = match result {
+        DvmResult::Mp4(mp4) => (mp4.blurhash.as_ref(), mp4.dim.as_ref()),
+        DvmResult::Hls(hls) => (hls.blurhash.as_ref(), hls.dim.as_ref()),
+        DvmResult::Thumbnail(_) => (None, None),
+    };
+    for (name, value) in [("blurhash", blurhash), ("dim", dim)] {
+        if let Some(value) = value {
+            tags.push(Tag::custom(TagKind::Custom(name.into()), vec![value.clone()]));
+        }
+    }
+
+    let content = serde_json::to_string(&summary).unwrap_or_default();
+    Some(EventBuilder::new(DVM_TRANSCODE_SUMMARY_KIND, content, tags))
+}
+
+/// Whether `input` is an `http(s)` URL anyone can fetch
+fn is_public_url(input: &str) -> bool {
+    url::Url::parse(input).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dvm::events::{ArchivedSource, HlsResult, Mp4Result, StreamPlaylist};
+
+    fn job(input: &str) -> JobContext {
+        let keys = Keys::generate();
+        let tags = vec![Tag::custom(
+            TagKind::Custom("i".into()),
+            vec![input.to_string(), "url".to_string()],
+        )];
+        let event = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+            .to_event(&keys)
+            .unwrap();
+        JobContext::from_event(event).unwrap()
+    }
+
+    fn tag_values<'a>(event: &'a Event, name: &str) -> Vec<&'a str> {
+        event
+            .tags
+            .iter()
+            .filter(|t| t.as_slice().first().map(|s| s.as_str()) == Some(name))
+            .filter_map(|t| t.as_slice().get(1).map(|s| s.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_input_hash() {
+        let blob = "b".repeat(64);
+        assert_eq!(input_hash(&format!("https://a.example.com/{}.mp4", blob)), blob);
+        assert_eq!(input_hash(&format!("https://b.example.com/{}", blob)), blob);
+        assert_eq!(
+            input_hash("https://example.com/video.mp4"),
+            hash_bytes(b"https://example.com/video.mp4")
+        );
+    }
+
+    #[test]
+    fn test_mp4_summary_event() {
+        let source = format!("https://blossom.example.com/{}.mp4", "c".repeat(64));
+        let output = "d".repeat(64);
+        let result = DvmResult::Mp4(Mp4Result {
+            urls: vec![
+                format!("https://a.example.com/{}.mp4", output),
+                format!("https://b.example.com/{}.mp4", output),
+            ],
+            resolution: "720p".to_string(),
+            size_bytes: 1000,
+            mimetype: Some("video/mp4".to_string()),
+            preview: None,
+            storyboard: None,
+            chapters: None,
+            blurhash: Some("LEHV6nWB2yk8pyo0adR*.7kCMdnj".to_string()),
+            dim: Some("1280x720".to_string()),
+            source: None,
+            input_sha256: None,
+            watch_url: None,
+        });
+
+        let job = job(&source);
+        let event = build_summary_event(&job, &job.input, &result)
+            .unwrap()
+            .to_event(&Keys::generate())
+            .unwrap();
+
+        assert_eq!(event.kind, DVM_TRANSCODE_SUMMARY_KIND);
+        assert_eq!(tag_values(&event, "d"), vec!["c".repeat(64).as_str()]);
+        assert_eq!(tag_values(&event, "i"), vec![source.as_str()]);
+        // Same blob on two servers is listed once
+        assert_eq!(tag_values(&event, "x"), vec![output.as_str()]);
+        assert_eq!(tag_values(&event, "blurhash"), vec!["LEHV6nWB2yk8pyo0adR*.7kCMdnj"]);
+        assert_eq!(tag_values(&event, "dim"), vec!["1280x720"]);
+
+        let summary: TranscodeSummary = serde_json::from_str(&event.content).unwrap();
+        assert_eq!(summary.outputs.len(), 2);
+        assert_eq!(summary.outputs[0].resolution.as_deref(), Some("720p"));
+    }
+
+    #[test]
+    fn test_archived_source_is_listed() {
+        let source = "a".repeat(64);
+        let result = DvmResult::Mp4(Mp4Result {
+            urls: vec![format!("https://a.example.com/{}.mp4", "d".repeat(64))],
+            resolution: "720p".to_string(),
+            size_bytes: 1000,
+            mimetype: Some("video/mp4".to_string()),
+            preview: None,
+            storyboard: None,
+            chapters: None,
+            blurhash: None,
+            dim: None,
+            source: Some(ArchivedSource {
+                urls: vec![format!("https://a.example.com/{}", source)],
+                sha256: source.clone(),
+                size_bytes: 5000,
+                mimetype: "video/quicktime".to_string(),
+            }),
+            input_sha256: Some(source.clone()),
+            watch_url: None,
+        });
+
+        let summary = TranscodeSummary::from_result("https://example.com/in.mov", &result);
+        let archived = summary.outputs.last().unwrap();
+        assert_eq!(archived.output_type, "source");
+        assert_eq!(archived.sha256.as_deref(), Some(source.as_str()));
+        assert_eq!(archived.size_bytes, 5000);
+        assert_eq!(summary.output_hashes(), vec!["d".repeat(64), source]);
+    }
+
+    #[test]
+    fn test_encrypted_hls_has_no_summary() {
+        let hls = |key: Option<&str>| {
+            DvmResult::Hls(HlsResult {
+                master_playlist: format!("https://a.example.com/{}.m3u8", "e".repeat(64)),
+                stream_playlists: vec![StreamPlaylist {
+                    url: format!("https://a.example.com/{}.m3u8", "f".repeat(64)),
+                    resolution: "360p".to_string(),
+                    size_bytes: 10,
+                    mimetype: None,
+                    audio_bitrate: None,
+                }],
+                audio_tracks: vec![],
+                total_size_bytes: 10,
+                encryption_key: key.map(str::to_string),
+                preview: None,
+                storyboard: None,
+                chapters: None,
+                blurhash: None,
+                dim: None,
+                source: None,
+                input_sha256: None,
+                watch_url: None,
+                segment_hashes: vec![],
+            })
+        };
+        let job = job("https://example.com/video.mp4");
+
+        assert!(build_summary_event(&job, &job.input, &hls(Some("a2V5"))).is_none());
+
+        let event = build_summary_event(&job, &job.input, &hls(Some("")))
+            .unwrap()
+            .to_event(&Keys::generate())
+            .unwrap();
+        assert_eq!(tag_values(&event, "x").len(), 2);
+    }
+
+    #[test]
+    fn test_local_inputs_have_no_summary() {
+        let result = DvmResult::Mp4(Mp4Result {
+            urls: vec![format!("https://a.example.com/{}.mp4", "d".repeat(64))],
+            resolution: "720p".to_string(),
+            size_bytes: 1000,
+            mimetype: None,
+            preview: None,
+            storyboard: None,
+            chapters: None,
+            blurhash: None,
+            dim: None,
+            source: None,
+            input_sha256: None,
+            watch_url: None,
+        });
+        for input in ["file:///srv/media/video.mp4", "/srv/media/video.mp4"] {
+            let job = job(input);
+            assert!(build_summary_event(&job, &job.input, &result).is_none());
+        }
+
+        // A job working on a local copy is summarized with the requested URL
+        let mut job = job("https://example.com/video.mp4");
+        let requested = job.input.clone();
+        job.input.value = "/tmp/nostube/abc/input.mp4".to_string();
+        let event = build_summary_event(&job, &requested, &result)
+            .unwrap()
+            .to_event(&Keys::generate())
+            .unwrap();
+        assert_eq!(tag_values(&event, "i"), vec!["https://example.com/video.mp4"]);
+        assert!(!event.content.contains("/tmp/"));
+    }
+}
@@ -0,0 +1,125 @@
+//! Live job updates for the dashboard.
+//!
+//! [`JobHandler`](crate::dvm::JobHandler) publishes lifecycle changes and
+//! progress ticks into a broadcast channel, and the HTTP server streams them
+//! to the embedded dashboard as server-sent events (`/api/events`). The
+//! stream is unauthenticated, so updates carry job IDs, codes and numbers but
+//! no input URLs or requester keys, and no message text for encrypted
+//! requests.
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Updates buffered per subscriber before a slow one starts missing them
+const CHANNEL_CAPACITY: usize = 256;
+
+/// One change to a running job
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobUpdate {
+    /// The job got a slot and started processing
+    Started { job_id: String, mode: String },
+    /// A status event was sent to the requester
+    Status {
+        job_id: String,
+        status: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        code: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+    /// Periodic transcode or upload progress
+    Progress {
+        job_id: String,
+        phase: String,
+        percent: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        eta_secs: Option<u64>,
+        /// Realtime multiplier (transcoding) or MB/s (uploading)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        speed: Option<f64>,
+    },
+    /// The job is over ("completed", "failed" or "cancelled")
+    Finished { job_id: String, outcome: String },
+}
+
+impl JobUpdate {
+    /// SSE event name
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Started { .. } => "started",
+            Self::Status { .. } => "status",
+            Self::Progress { .. } => "progress",
+            Self::Finished { .. } => "finished",
+        }
+    }
+}
+
+/// Broadcast channel of [`JobUpdate`]s; every subscriber sees every update
+#[derive(Debug, Clone)]
+pub struct JobUpdates {
+    tx: broadcast::Sender<JobUpdate>,
+}
+
+impl JobUpdates {
+    pub fn new() -> Self {
+        Self {
+            tx: broadcast::channel(CHANNEL_CAPACITY).0,
+        }
+    }
+
+    /// Send an update to the current subscribers (dropped when there are none)
+    pub fn publish(&self, update: JobUpdate) {
+        let _ = self.tx.send(update);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<JobUpdate> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for JobUpdates {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_updates_reach_every_subscriber() {
+        let updates = JobUpdates::new();
+        // Publishing without subscribers is fine
+        updates.publish(JobUpdate::Finished {
+            job_id: "old".into(),
+            outcome: "completed".into(),
+        });
+
+        let mut a = updates.subscribe();
+        let mut b = updates.subscribe();
+        let update = JobUpdate::Progress {
+            job_id: "abc".into(),
+            phase: "transcoding".into(),
+            percent: Some(42),
+            eta_secs: None,
+            speed: Some(2.5),
+        };
+        updates.publish(update.clone());
+        assert_eq!(a.recv().await.unwrap(), update);
+        assert_eq!(b.recv().await.unwrap(), update);
+
+        assert_eq!(update.kind(), "progress");
+        assert_eq!(
+            serde_json::to_value(&update).unwrap(),
+            serde_json::json!({
+                "type": "progress",
+                "job_id": "abc",
+                "phase": "transcoding",
+                "percent": 42,
+                "speed": 2.5,
+            })
+        );
+    }
+}
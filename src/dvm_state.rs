@@ -3,14 +3,23 @@
 //! Provides shared state for the DVM including configuration,
 //! job statistics, and history.
 
+use crate::blossom::{BlossomHealth, QuarantineLedger, ReferenceIndex, RetentionLedger};
 use crate::remote_config::RemoteConfig;
 use crate::dvm::events::JobContext;
+use crate::dvm::idempotency::IdempotencyLedger;
+use crate::dvm::job_log::JobLog;
+use crate::dvm::rate_limit::RateLimiter;
+use crate::nostr::{Outbox, RelayStats};
+use crate::payments::cashu::Redemption;
+use crate::payments::Invoice;
+use crate::util::resources::ResourceSnapshot;
+use crate::video::ffmpeg_error::FfmpegErrorKind;
 use nostr_sdk::prelude::*;
-use std::collections::{VecDeque, HashMap};
+use std::collections::{BTreeMap, VecDeque, HashMap};
 use std::fmt;
 use std::sync::Arc;
 use std::time::Instant;
-use tokio::sync::RwLock;
+use tokio::sync::{oneshot, watch, RwLock};
 
 /// Maximum number of job records to keep in history
 pub const MAX_JOB_HISTORY: usize = 100;
@@ -21,6 +30,39 @@ pub const PENDING_BID_TIMEOUT_SECS: u64 = 300;
 /// Thread-safe shared DVM state
 pub type SharedDvmState = Arc<RwLock<DvmState>>;
 
+/// Signals changes to the reloadable settings in [`DvmState::config`].
+///
+/// Each subscriber sees every change (a `Notify` would only wake one of
+/// them), and then reads the new values from the shared state.
+#[derive(Debug, Clone)]
+pub struct ConfigWatch {
+    tx: Arc<watch::Sender<u64>>,
+}
+
+impl ConfigWatch {
+    pub fn new() -> Self {
+        Self {
+            tx: Arc::new(watch::channel(0).0),
+        }
+    }
+
+    /// Record that the settings changed
+    pub fn notify(&self) {
+        self.tx.send_modify(|revision| *revision += 1);
+    }
+
+    /// Receiver whose `changed()` resolves after each later change
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for ConfigWatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// A bid sent by the DVM waiting for selection or payment
 #[derive(Debug, Clone)]
 pub struct PendingBid {
@@ -28,6 +70,13 @@ pub struct PendingBid {
     pub created_at: Instant,
 }
 
+/// A Lightning invoice issued for a job and not yet paid
+#[derive(Debug, Clone)]
+pub struct OpenInvoice {
+    pub context: JobContext,
+    pub invoice: Invoice,
+}
+
 /// DVM runtime state
 #[derive(Debug)]
 pub struct DvmState {
@@ -39,18 +88,44 @@ pub struct DvmState {
     pub started_at: Instant,
     /// Number of currently active jobs
     pub jobs_active: u32,
+    /// Jobs waiting for a slot
+    pub jobs_queued: u32,
+    /// Requests received but not yet taken off the job channel
+    pub channel_depth: u32,
     /// Total completed jobs
     pub jobs_completed: u32,
     /// Total failed jobs
     pub jobs_failed: u32,
+    /// Failed FFmpeg runs per classified cause
+    pub ffmpeg_failures: BTreeMap<FfmpegErrorKind, u32>,
     /// Recent job history (newest first)
     pub job_history: VecDeque<JobRecord>,
     /// Bids sent to users waiting for selection/payment
     pub pending_bids: HashMap<EventId, PendingBid>,
+    /// Invoices being polled for payment, keyed by job ID
+    pub open_invoices: HashMap<EventId, OpenInvoice>,
     /// Hardware acceleration method if available
     pub hwaccel: Option<String>,
     /// Average transcode speed per resolution (realtime multiplier, e.g. 3.5 = 3.5x faster than realtime)
     pub avg_speeds: HashMap<String, f64>,
+    /// Signals that abort running jobs, keyed by job ID
+    pub cancel_signals: HashMap<String, oneshot::Sender<()>>,
+    /// Upload time and expiry of result blobs, honored by the cleanup scheduler
+    pub retention: RetentionLedger,
+    /// Result blobs per completed job, so cleanup keeps recent and pinned ones
+    pub references: ReferenceIndex,
+    /// Abuse reports and results taken down because of them
+    pub quarantine: QuarantineLedger,
+    /// Jobs started per requester, for `max_jobs_per_hour`
+    pub rate_limits: RateLimiter,
+    /// Idempotency keys of recent requests and the jobs holding them
+    pub idempotency: IdempotencyLedger,
+    /// Per-relay event and publish counters
+    pub relay_stats: RelayStats,
+    /// Result events waiting for more relays to accept them
+    pub outbox: Outbox,
+    /// Latest health probe results per Blossom server
+    pub blossom_health: BlossomHealth,
 }
 
 /// Record of a job execution
@@ -62,12 +137,26 @@ pub struct JobRecord {
     pub status: JobStatus,
     /// Input video URL
     pub input_url: String,
+    /// Requester pubkey (hex)
+    pub requester: String,
     /// Output URL (master playlist) if completed
     pub output_url: Option<String>,
+    /// AES key of encrypted HLS output, for `/watch` pages
+    pub encryption_key: Option<String>,
     /// Unix timestamp when job started
     pub started_at: u64,
     /// Unix timestamp when job completed or failed
     pub completed_at: Option<u64>,
+    /// Host resources captured when the job failed
+    pub resources: Option<ResourceSnapshot>,
+    /// Whether the job's result was taken down after abuse reports
+    pub quarantined: bool,
+    /// Whether the job was encoded in software after the hardware encoder failed
+    pub software_fallback: bool,
+    /// Cashu token redeemed to pay for the job
+    pub redemption: Option<Redemption>,
+    /// FFmpeg output and pipeline events of the job
+    pub log: Option<Arc<JobLog>>,
 }
 
 /// Job execution status
@@ -79,6 +168,45 @@ pub enum JobStatus {
     Completed,
     /// Job failed
     Failed,
+    /// Job was cancelled by the admin
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Parse the `Display` form back into a status
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "processing" => Some(Self::Processing),
+            "completed" => Some(Self::Completed),
+            "failed" => Some(Self::Failed),
+            "cancelled" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+/// Filter for paging through job history
+#[derive(Debug, Clone, Default)]
+pub struct JobHistoryQuery {
+    /// Only jobs started at or after this Unix timestamp
+    pub since: Option<u64>,
+    /// Only jobs started at or before this Unix timestamp
+    pub until: Option<u64>,
+    pub status: Option<JobStatus>,
+    /// Requester pubkey (hex)
+    pub requester: Option<String>,
+}
+
+impl JobHistoryQuery {
+    fn matches(&self, record: &JobRecord) -> bool {
+        self.since.is_none_or(|t| record.started_at >= t)
+            && self.until.is_none_or(|t| record.started_at <= t)
+            && self.status.is_none_or(|s| record.status == s)
+            && self
+                .requester
+                .as_ref()
+                .is_none_or(|r| record.requester.eq_ignore_ascii_case(r))
+    }
 }
 
 impl fmt::Display for JobStatus {
@@ -87,6 +215,7 @@ impl fmt::Display for JobStatus {
             JobStatus::Processing => write!(f, "processing"),
             JobStatus::Completed => write!(f, "completed"),
             JobStatus::Failed => write!(f, "failed"),
+            JobStatus::Cancelled => write!(f, "cancelled"),
         }
     }
 }
@@ -99,12 +228,25 @@ impl DvmState {
             keys,
             started_at: Instant::now(),
             jobs_active: 0,
+            jobs_queued: 0,
+            channel_depth: 0,
             jobs_completed: 0,
             jobs_failed: 0,
+            ffmpeg_failures: BTreeMap::new(),
             job_history: VecDeque::new(),
             pending_bids: HashMap::new(),
+            open_invoices: HashMap::new(),
             hwaccel: None,
             avg_speeds: HashMap::new(),
+            cancel_signals: HashMap::new(),
+            retention: RetentionLedger::default(),
+            references: ReferenceIndex::default(),
+            quarantine: QuarantineLedger::default(),
+            rate_limits: RateLimiter::default(),
+            idempotency: IdempotencyLedger::default(),
+            relay_stats: RelayStats::default(),
+            outbox: Outbox::default(),
+            blossom_health: BlossomHealth::default(),
         }
     }
 
@@ -116,6 +258,11 @@ impl DvmState {
         *entry = *entry * 0.7 + speed_multiplier * 0.3;
     }
 
+    /// Count a job that failed in FFmpeg, by cause
+    pub fn record_ffmpeg_failure(&mut self, kind: FfmpegErrorKind) {
+        *self.ffmpeg_failures.entry(kind).or_default() += 1;
+    }
+
     /// Add a pending bid
     pub fn add_bid(&mut self, context: JobContext) {
         let id = context.event_id();
@@ -154,16 +301,23 @@ impl DvmState {
     }
 
     /// Record a job starting
-    pub fn job_started(&mut self, id: String, input_url: String) {
+    pub fn job_started(&mut self, id: String, input_url: String, requester: String) {
         self.jobs_active += 1;
 
         let record = JobRecord {
             id,
             status: JobStatus::Processing,
             input_url,
+            requester,
             output_url: None,
+            encryption_key: None,
             started_at: Timestamp::now().as_u64(),
             completed_at: None,
+            resources: None,
+            quarantined: false,
+            software_fallback: false,
+            redemption: None,
+            log: None,
         };
 
         // Add to front (newest first)
@@ -188,8 +342,36 @@ impl DvmState {
         }
     }
 
-    /// Record a job failing
-    pub fn job_failed(&mut self, id: &str) {
+    /// Note that a job was encoded in software after the hardware encoder failed
+    pub fn set_software_fallback(&mut self, id: &str) {
+        if let Some(record) = self.job_history.iter_mut().find(|r| r.id == id) {
+            record.software_fallback = true;
+        }
+    }
+
+    /// Record the Cashu redemption that paid for a job
+    pub fn set_redemption(&mut self, id: &str, redemption: Redemption) {
+        if let Some(record) = self.job_history.iter_mut().find(|r| r.id == id) {
+            record.redemption = Some(redemption);
+        }
+    }
+
+    /// Keep a job's log with its record, for the admin `get_job_log` command
+    pub fn attach_log(&mut self, id: &str, log: Arc<JobLog>) {
+        if let Some(record) = self.job_history.iter_mut().find(|r| r.id == id) {
+            record.log = Some(log);
+        }
+    }
+
+    /// Remember the AES key of a completed job's encrypted HLS output
+    pub fn set_encryption_key(&mut self, id: &str, key: Option<String>) {
+        if let Some(record) = self.job_history.iter_mut().find(|r| r.id == id) {
+            record.encryption_key = key;
+        }
+    }
+
+    /// Record a job failing, with a snapshot of host resources if one was taken
+    pub fn job_failed(&mut self, id: &str, resources: Option<ResourceSnapshot>) {
         self.jobs_active = self.jobs_active.saturating_sub(1);
         self.jobs_failed += 1;
 
@@ -197,13 +379,60 @@ impl DvmState {
         if let Some(record) = self.job_history.iter_mut().find(|r| r.id == id) {
             record.status = JobStatus::Failed;
             record.completed_at = Some(Timestamp::now().as_u64());
+            record.resources = resources;
+        }
+    }
+
+    /// Flag a job's result as taken down (or put back).
+    ///
+    /// Returns false if the job is no longer in the history.
+    pub fn set_quarantined(&mut self, id: &str, quarantined: bool) -> bool {
+        match self.job_history.iter_mut().find(|r| r.id == id) {
+            Some(record) => {
+                record.quarantined = quarantined;
+                true
+            }
+            None => false,
         }
     }
 
+    /// Abort a running job and record it as cancelled.
+    ///
+    /// Returns the updated record, or `None` if no job with that ID is running.
+    pub fn cancel_job(&mut self, id: &str) -> Option<&JobRecord> {
+        let record = self
+            .job_history
+            .iter_mut()
+            .find(|r| r.id == id && r.status == JobStatus::Processing)?;
+        // The send fails if the job finished before its signal was removed
+        self.cancel_signals.remove(id)?.send(()).ok()?;
+        self.jobs_active = self.jobs_active.saturating_sub(1);
+
+        record.status = JobStatus::Cancelled;
+        record.completed_at = Some(Timestamp::now().as_u64());
+        Some(record)
+    }
+
     /// Get recent job history (newest first)
     pub fn get_job_history(&self, limit: usize) -> Vec<&JobRecord> {
         self.job_history.iter().take(limit).collect()
     }
+
+    /// Page through job history matching `query` (newest first).
+    ///
+    /// Returns the requested page and the total number of matching records.
+    pub fn query_job_history(
+        &self,
+        query: &JobHistoryQuery,
+        offset: usize,
+        limit: usize,
+    ) -> (Vec<&JobRecord>, usize) {
+        let matching: Vec<&JobRecord> =
+            self.job_history.iter().filter(|r| query.matches(r)).collect();
+        let total = matching.len();
+        let page = matching.into_iter().skip(offset).take(limit).collect();
+        (page, total)
+    }
 }
 
 #[cfg(test)]
@@ -237,6 +466,7 @@ mod tests {
         state.job_started(
             "job1".to_string(),
             "https://example.com/video.mp4".to_string(),
+            "requester".to_string(),
         );
         assert_eq!(state.jobs_active, 1);
         assert_eq!(state.job_history.len(), 1);
@@ -264,15 +494,61 @@ mod tests {
         state.job_started(
             "job1".to_string(),
             "https://example.com/video.mp4".to_string(),
+            "requester".to_string(),
         );
         assert_eq!(state.jobs_active, 1);
 
         // Fail the job
-        state.job_failed("job1");
+        state.job_failed("job1", None);
         assert_eq!(state.jobs_active, 0);
         assert_eq!(state.jobs_failed, 1);
         assert_eq!(state.job_history[0].status, JobStatus::Failed);
         assert!(state.job_history[0].completed_at.is_some());
+
+        state.record_ffmpeg_failure(FfmpegErrorKind::CorruptInput);
+        state.record_ffmpeg_failure(FfmpegErrorKind::CorruptInput);
+        assert_eq!(state.ffmpeg_failures[&FfmpegErrorKind::CorruptInput], 2);
+    }
+
+    #[test]
+    fn test_cancel_job() {
+        let mut state = DvmState::new(test_keys(), RemoteConfig::new());
+        state.job_started(
+            "job1".to_string(),
+            "https://example.com/video.mp4".to_string(),
+            "requester".to_string(),
+        );
+        let (tx, mut rx) = oneshot::channel();
+        state.cancel_signals.insert("job1".to_string(), tx);
+
+        assert!(state.cancel_job("unknown").is_none());
+        let record = state.cancel_job("job1").unwrap();
+        assert_eq!(record.status, JobStatus::Cancelled);
+        assert!(record.completed_at.is_some());
+        assert_eq!(rx.try_recv(), Ok(()));
+        assert_eq!(state.jobs_active, 0);
+        assert_eq!(state.jobs_failed, 0);
+
+        // Already cancelled
+        assert!(state.cancel_job("job1").is_none());
+    }
+
+    #[test]
+    fn test_set_redemption() {
+        let mut state = DvmState::new(test_keys(), RemoteConfig::new());
+        state.job_started(
+            "job1".to_string(),
+            "https://example.com/video.mp4".to_string(),
+            "requester".to_string(),
+        );
+        let redemption = Redemption {
+            job_id: "job1".to_string(),
+            amount_sats: 100,
+            fee_sats: 1,
+            redeemed_at: 1_700_000_000,
+        };
+        state.set_redemption("job1", redemption.clone());
+        assert_eq!(state.job_history[0].redemption, Some(redemption));
     }
 
     #[test]
@@ -286,6 +562,7 @@ mod tests {
             state.job_started(
                 format!("job{}", i),
                 format!("https://example.com/{}.mp4", i),
+                "requester".to_string(),
             );
         }
 
@@ -303,6 +580,48 @@ mod tests {
         assert_eq!(history.len(), 5);
     }
 
+    #[test]
+    fn test_query_job_history() {
+        let mut state = DvmState::new(test_keys(), RemoteConfig::new());
+
+        for i in 0..10 {
+            let requester = if i % 2 == 0 { "alice" } else { "bob" };
+            state.job_started(
+                format!("job{}", i),
+                format!("https://example.com/{}.mp4", i),
+                requester.to_string(),
+            );
+            state.job_history[0].started_at = 1000 + i as u64;
+        }
+        state.job_failed("job3", None);
+
+        // Pagination over everything, newest first
+        let (page, total) = state.query_job_history(&JobHistoryQuery::default(), 2, 3);
+        assert_eq!(total, 10);
+        let ids: Vec<&str> = page.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["job7", "job6", "job5"]);
+
+        // Requester + time range
+        let query = JobHistoryQuery {
+            since: Some(1002),
+            until: Some(1006),
+            requester: Some("ALICE".to_string()),
+            ..Default::default()
+        };
+        let (page, total) = state.query_job_history(&query, 0, 10);
+        assert_eq!(total, 3);
+        assert!(page.iter().all(|r| r.requester == "alice"));
+
+        // Status
+        let query = JobHistoryQuery {
+            status: Some(JobStatus::Failed),
+            ..Default::default()
+        };
+        let (page, total) = state.query_job_history(&query, 0, 10);
+        assert_eq!(total, 1);
+        assert_eq!(page[0].id, "job3");
+    }
+
     #[test]
     fn test_paused_state() {
         let keys = test_keys();
@@ -315,4 +634,17 @@ mod tests {
         state.config.paused = true;
         assert!(state.is_paused());
     }
+
+    #[tokio::test]
+    async fn test_config_watch_reaches_every_subscriber() {
+        let watch = ConfigWatch::new();
+        let mut announcer = watch.subscribe();
+        let mut other = watch.subscribe();
+
+        watch.notify();
+        announcer.changed().await.unwrap();
+        other.changed().await.unwrap();
+        assert_eq!(*announcer.borrow(), 1);
+        assert!(!other.has_changed().unwrap());
+    }
 }
@@ -16,6 +16,29 @@ pub enum DvmError {
 
     #[error("Job rejected: {0}")]
     JobRejected(String),
+
+    #[error(transparent)]
+    Hook(#[from] HookError),
+}
+
+impl DvmError {
+    /// Cause of the FFmpeg failure behind this error, if it is one
+    pub fn ffmpeg_kind(&self) -> Option<crate::video::ffmpeg_error::FfmpegErrorKind> {
+        match self {
+            Self::Video(VideoError::Ffmpeg { kind, .. })
+            | Self::Blossom(BlossomError::Video(VideoError::Ffmpeg { kind, .. })) => Some(*kind),
+            _ => None,
+        }
+    }
+}
+
+/// A job hook refused the job or failed to run
+#[derive(Error, Debug)]
+#[error("{hook} ({stage} hook) failed: {reason}")]
+pub struct HookError {
+    pub hook: String,
+    pub stage: &'static str,
+    pub reason: String,
 }
 
 #[derive(Error, Debug)]
@@ -37,6 +60,9 @@ pub enum ConfigError {
 
     #[error("FFprobe not found. Searched: {0}")]
     FfprobeNotFound(String),
+
+    #[error("FFmpeg {found} is too old, version {minimum} or newer is required")]
+    FfmpegTooOld { found: String, minimum: String },
 }
 
 #[derive(Error, Debug)]
@@ -44,6 +70,13 @@ pub enum VideoError {
     #[error("FFmpeg failed: {0}")]
     FfmpegFailed(String),
 
+    /// FFmpeg exited with an error, classified from its stderr
+    #[error("FFmpeg failed ({kind}): {detail}")]
+    Ffmpeg {
+        kind: crate::video::ffmpeg_error::FfmpegErrorKind,
+        detail: String,
+    },
+
     #[error("FFprobe failed: {0}")]
     FfprobeFailed(String),
 
@@ -56,6 +89,15 @@ pub enum VideoError {
     #[error("Playlist parse error: {0}")]
     PlaylistParse(String),
 
+    #[error("Fetching external input failed: {0}")]
+    FetchFailed(String),
+
+    #[error("Input hash mismatch: expected {expected}, downloaded {actual}")]
+    InputHashMismatch { expected: String, actual: String },
+
+    #[error("Output audio and video drifted {drift_ms}ms apart (limit {max_ms}ms)")]
+    AvSyncDrift { drift_ms: u64, max_ms: u64 },
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
@@ -68,6 +110,12 @@ pub enum BlossomError {
     #[error("Auth token creation failed: {0}")]
     AuthFailed(String),
 
+    #[error("Upload rejected ({status}): {message}")]
+    Rejected { status: u16, message: String },
+
+    #[error("Checksum mismatch: expected {expected}, server stored {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+
     #[error("HTTP error: {0}")]
     Http(#[from] reqwest::Error),
 
@@ -80,3 +128,18 @@ pub enum BlossomError {
     #[error("Video processing error: {0}")]
     Video(#[from] VideoError),
 }
+
+#[derive(Error, Debug)]
+pub enum PaymentError {
+    #[error("Lightning backend error: {0}")]
+    Backend(String),
+
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Cashu error: {0}")]
+    Cashu(#[from] cdk::Error),
+
+    #[error("Cashu wallet storage error: {0}")]
+    WalletStore(#[from] std::io::Error),
+}
@@ -0,0 +1,122 @@
+//! Pluggable input fetchers for external video platforms.
+//!
+//! Some inputs (e.g. YouTube watch pages) are not media files that FFmpeg
+//! can read directly. A fetcher downloads the media to a local file first so
+//! the normal transcode and Blossom upload pipeline can re-host it.
+//!
+//! Fetchers are compiled in behind the `external-fetchers` cargo feature and
+//! are only consulted when the operator enables `external_fetchers_enabled`
+//! in the remote config.
+
+#[cfg(feature = "external-fetchers")]
+pub mod ytdlp;
+
+use futures::future::BoxFuture;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+use crate::error::VideoError;
+
+/// Downloads media from an external platform into a local file.
+pub trait InputFetcher: Send + Sync {
+    /// Short identifier used in logs and status messages (e.g. "yt-dlp")
+    fn name(&self) -> &'static str;
+
+    /// Whether this fetcher handles the given URL
+    fn supports(&self, url: &Url) -> bool;
+
+    /// Download the media at `url` into `dest_dir`, returning the file path.
+    fn fetch<'a>(
+        &'a self,
+        url: &'a str,
+        dest_dir: &'a Path,
+    ) -> BoxFuture<'a, Result<PathBuf, VideoError>>;
+}
+
+/// The set of fetchers compiled into this build.
+#[derive(Default)]
+pub struct FetcherRegistry {
+    fetchers: Vec<Box<dyn InputFetcher>>,
+}
+
+impl FetcherRegistry {
+    /// Build a registry with all fetchers enabled at compile time.
+    pub fn new() -> Self {
+        #[allow(unused_mut)]
+        let mut registry = Self::default();
+
+        #[cfg(feature = "external-fetchers")]
+        registry.register(Box::new(ytdlp::YtDlpFetcher::from_env()));
+
+        registry
+    }
+
+    /// Add a fetcher. Earlier registrations take precedence.
+    pub fn register(&mut self, fetcher: Box<dyn InputFetcher>) {
+        self.fetchers.push(fetcher);
+    }
+
+    /// Find the first fetcher that supports the given URL.
+    pub fn find(&self, input: &str) -> Option<&dyn InputFetcher> {
+        let url = Url::parse(input).ok()?;
+        self.fetchers
+            .iter()
+            .find(|f| f.supports(&url))
+            .map(|f| f.as_ref())
+    }
+
+    /// Whether any fetchers are available in this build.
+    pub fn is_empty(&self) -> bool {
+        self.fetchers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct ExampleFetcher;
+
+    impl InputFetcher for ExampleFetcher {
+        fn name(&self) -> &'static str {
+            "example"
+        }
+
+        fn supports(&self, url: &Url) -> bool {
+            url.host_str() == Some("videos.example.com")
+        }
+
+        fn fetch<'a>(
+            &'a self,
+            _url: &'a str,
+            dest_dir: &'a Path,
+        ) -> BoxFuture<'a, Result<PathBuf, VideoError>> {
+            Box::pin(async move { Ok(dest_dir.join("source.mp4")) })
+        }
+    }
+
+    #[test]
+    fn test_registry_find() {
+        let mut registry = FetcherRegistry::default();
+        assert!(registry.is_empty());
+        registry.register(Box::new(ExampleFetcher));
+
+        let fetcher = registry.find("https://videos.example.com/watch/123");
+        assert_eq!(fetcher.map(|f| f.name()), Some("example"));
+        assert!(registry.find("https://cdn.example.org/video.mp4").is_none());
+        assert!(registry.find("not a url").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_registry_fetch() {
+        let mut registry = FetcherRegistry::default();
+        registry.register(Box::new(ExampleFetcher));
+
+        let fetcher = registry.find("https://videos.example.com/watch/123").unwrap();
+        let path = fetcher
+            .fetch("https://videos.example.com/watch/123", Path::new("/tmp/job"))
+            .await
+            .unwrap();
+        assert_eq!(path, PathBuf::from("/tmp/job/source.mp4"));
+    }
+}
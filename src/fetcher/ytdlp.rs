@@ -0,0 +1,136 @@
+//! yt-dlp subprocess fetcher.
+//!
+//! Requires the `yt-dlp` binary (override with `YTDLP_PATH`).
+
+use futures::future::BoxFuture;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tracing::{debug, info};
+use url::Url;
+
+use super::InputFetcher;
+use crate::error::VideoError;
+
+/// Hosts handed to yt-dlp (subdomains such as `www.` and `m.` also match)
+const SUPPORTED_HOSTS: &[&str] = &[
+    "youtube.com",
+    "youtu.be",
+    "vimeo.com",
+    "dailymotion.com",
+    "twitch.tv",
+    "rumble.com",
+    "odysee.com",
+];
+
+/// Format selection: best video up to 4K plus best audio, or best combined
+const FORMAT_SELECTOR: &str = "bv*[height<=2160]+ba/b[height<=2160]/b";
+
+pub struct YtDlpFetcher {
+    binary: PathBuf,
+}
+
+impl YtDlpFetcher {
+    pub fn new(binary: PathBuf) -> Self {
+        Self { binary }
+    }
+
+    /// Use `YTDLP_PATH` if set, otherwise `yt-dlp` from the system PATH.
+    pub fn from_env() -> Self {
+        let binary = std::env::var("YTDLP_PATH")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("yt-dlp"));
+        Self::new(binary)
+    }
+
+    fn is_supported_host(host: &str) -> bool {
+        let host = host.to_lowercase();
+        SUPPORTED_HOSTS
+            .iter()
+            .any(|h| host == *h || host.ends_with(&format!(".{}", h)))
+    }
+
+    async fn download(&self, url: &str, dest_dir: &Path) -> Result<PathBuf, VideoError> {
+        let template = dest_dir.join("source.%(ext)s");
+
+        info!(url = %url, binary = %self.binary.display(), "Fetching external video with yt-dlp");
+
+        let output = Command::new(&self.binary)
+            .kill_on_drop(true)
+            .arg("--no-playlist")
+            .arg("--no-progress")
+            .arg("--no-warnings")
+            .arg("-f")
+            .arg(FORMAT_SELECTOR)
+            .arg("--merge-output-format")
+            .arg("mp4")
+            .arg("-o")
+            .arg(&template)
+            .arg("--print")
+            .arg("after_move:filepath")
+            .arg(url)
+            .output()
+            .await
+            .map_err(|e| VideoError::FetchFailed(format!("failed to run yt-dlp: {}", e)))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(VideoError::FetchFailed(stderr.trim().to_string()));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let path = stdout
+            .lines()
+            .map(str::trim)
+            .rfind(|l| !l.is_empty())
+            .map(PathBuf::from)
+            .ok_or_else(|| VideoError::FetchFailed("yt-dlp did not report an output file".into()))?;
+
+        if !path.exists() {
+            return Err(VideoError::FetchFailed(format!(
+                "yt-dlp output file missing: {}",
+                path.display()
+            )));
+        }
+
+        debug!(path = %path.display(), "yt-dlp download complete");
+        Ok(path)
+    }
+}
+
+impl InputFetcher for YtDlpFetcher {
+    fn name(&self) -> &'static str {
+        "yt-dlp"
+    }
+
+    fn supports(&self, url: &Url) -> bool {
+        matches!(url.scheme(), "http" | "https")
+            && url.host_str().is_some_and(Self::is_supported_host)
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        url: &'a str,
+        dest_dir: &'a Path,
+    ) -> BoxFuture<'a, Result<PathBuf, VideoError>> {
+        Box::pin(self.download(url, dest_dir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supports_platform_urls() {
+        let fetcher = YtDlpFetcher::new(PathBuf::from("yt-dlp"));
+        let supports = |s: &str| fetcher.supports(&Url::parse(s).unwrap());
+
+        assert!(supports("https://www.youtube.com/watch?v=dQw4w9WgXcQ"));
+        assert!(supports("https://youtu.be/dQw4w9WgXcQ"));
+        assert!(supports("https://m.youtube.com/watch?v=dQw4w9WgXcQ"));
+        assert!(supports("https://vimeo.com/123456"));
+        assert!(!supports("https://blossom.example.com/abc.mp4"));
+        assert!(!supports("https://notyoutube.com/watch"));
+        assert!(!supports("ftp://youtube.com/watch"));
+    }
+}
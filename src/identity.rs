@@ -2,11 +2,33 @@
 //!
 //! Handles loading and generating the DVM's identity keypair.
 //! The identity is stored as a 64-character hex private key.
+//!
+//! Operators moving to another host export the key encrypted with a password
+//! (NIP-49 `ncryptsec`) and import it there. Importing or rotating to a
+//! different key moves the remote config and announcements over to the new
+//! identity first ([`migrate_identity`]), then replaces the key file, keeping
+//! the old one as a backup next to it.
 
-use nostr_sdk::{Keys, ToBech32};
+use nostr_sdk::prelude::*;
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
+use crate::dvm::announcement::{
+    build_announcement_event, build_contact_list_event, build_dm_relay_list_event,
+    build_metadata_event, build_relay_list_event, DVM_ANNOUNCEMENT_KIND, DVM_SERVICE_ID,
+};
+use crate::remote_config::{
+    fetch_config, save_config, RemoteConfig, CONFIG_D_TAG, KIND_APP_SPECIFIC_DATA,
+};
+use crate::video::HwAccel;
+
+/// Shortest password an exported key may be encrypted with
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+/// scrypt cost of exported keys (2^16 rounds, the NIP-49 recommendation)
+const EXPORT_LOG_N: u8 = 16;
+
 #[derive(Error, Debug)]
 pub enum IdentityError {
     #[error("Failed to read identity file: {0}")]
@@ -15,6 +37,14 @@ pub enum IdentityError {
     InvalidKey(String),
     #[error("Failed to create data directory: {0}")]
     DirectoryError(String),
+    #[error("Password must be at least {MIN_PASSWORD_LEN} characters")]
+    WeakPassword,
+    #[error("A password is required for an encrypted key")]
+    PasswordRequired,
+    #[error("Failed to decrypt key: {0}")]
+    DecryptionError(String),
+    #[error("Failed to publish under the new identity: {0}")]
+    PublishError(String),
 }
 
 /// Returns the default data directory for the DVM.
@@ -108,32 +138,169 @@ fn load_identity(path: &Path) -> Result<Keys, IdentityError> {
 }
 
 fn generate_and_save_identity(path: &Path) -> Result<Keys, IdentityError> {
+    let keys = Keys::generate();
+    write_key_file(path, &keys)?;
+
+    tracing::info!(
+        "Generated new identity: {}",
+        keys.public_key().to_bech32().unwrap_or_default()
+    );
+
+    Ok(keys)
+}
+
+/// Write `keys` to `path` as hex, readable by the owner only
+fn write_key_file(path: &Path, keys: &Keys) -> Result<(), IdentityError> {
     // Ensure directory exists
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent)
             .map_err(|e| IdentityError::DirectoryError(e.to_string()))?;
     }
 
-    let keys = Keys::generate();
     let hex_key = keys.secret_key().to_secret_hex();
-
-    std::fs::write(path, &hex_key)?;
+    let tmp = path.with_extension("key.tmp");
+    std::fs::write(&tmp, &hex_key)?;
 
     // Set file permissions to 600 on Unix
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt;
-        let mut perms = std::fs::metadata(path)?.permissions();
+        let mut perms = std::fs::metadata(&tmp)?.permissions();
         perms.set_mode(0o600);
-        std::fs::set_permissions(path, perms)?;
+        std::fs::set_permissions(&tmp, perms)?;
     }
 
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// The DVM's private key encrypted with `password`, as a NIP-49 `ncryptsec`
+pub fn export_identity(keys: &Keys, password: &str) -> Result<String, IdentityError> {
+    encrypt_key(keys, password, EXPORT_LOG_N)
+}
+
+fn encrypt_key(keys: &Keys, password: &str, log_n: u8) -> Result<String, IdentityError> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(IdentityError::WeakPassword);
+    }
+    EncryptedSecretKey::new(keys.secret_key(), password, log_n, KeySecurity::Unknown)
+        .map_err(|e| IdentityError::InvalidKey(e.to_string()))?
+        .to_bech32()
+        .map_err(|e| IdentityError::InvalidKey(e.to_string()))
+}
+
+/// Parse a key to import: an `nsec`, 64-character hex, or an `ncryptsec`
+/// together with its `password`
+pub fn parse_identity(input: &str, password: Option<&str>) -> Result<Keys, IdentityError> {
+    let input = input.trim();
+    if !input.starts_with(PREFIX_BECH32_SECRET_KEY_ENCRYPTED) {
+        return Keys::parse(input).map_err(|e| IdentityError::InvalidKey(e.to_string()));
+    }
+    let password = password.ok_or(IdentityError::PasswordRequired)?;
+    let encrypted = EncryptedSecretKey::from_bech32(input)
+        .map_err(|e| IdentityError::InvalidKey(e.to_string()))?;
+    let secret_key = encrypted
+        .to_secret_key(password)
+        .map_err(|e| IdentityError::DecryptionError(e.to_string()))?;
+    Ok(Keys::new(secret_key))
+}
+
+/// Replace the identity at `path` with `keys`. The previous key file is
+/// kept as `identity.key.<unix time>.bak`; returns its path.
+pub fn replace_identity(path: &Path, keys: &Keys) -> Result<Option<PathBuf>, IdentityError> {
+    let backup = if path.exists() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let backup = path.with_extension(format!("key.{now}.bak"));
+        std::fs::copy(path, &backup)?;
+        Some(backup)
+    } else {
+        None
+    };
+    write_key_file(path, keys)?;
+
     tracing::info!(
-        "Generated new identity: {}",
+        "Replaced identity with {}",
         keys.public_key().to_bech32().unwrap_or_default()
     );
 
-    Ok(keys)
+    Ok(backup)
+}
+
+/// Move the DVM's public presence from `old` to `new`: the remote config is
+/// saved under the new key, the announcement, profile and relay lists are
+/// published by it, and the old announcement and config are retracted
+/// (NIP-09). The key file is left alone; call [`replace_identity`] after.
+///
+/// A key that already has a remote config (one brought over from another
+/// host) keeps it, and nothing is published; returns whether the DVM's
+/// config was moved.
+pub async fn migrate_identity(
+    client: &Client,
+    old: &Keys,
+    new: &Keys,
+    settings: &RemoteConfig,
+    hwaccel: HwAccel,
+) -> Result<bool, IdentityError> {
+    match fetch_config(client, new).await {
+        Ok(Some(_)) => return Ok(false),
+        Ok(None) => {}
+        Err(e) => return Err(IdentityError::PublishError(e.to_string())),
+    }
+
+    save_config(client, new, settings)
+        .await
+        .map_err(|e| IdentityError::PublishError(e.to_string()))?;
+
+    let mut builders = vec![
+        build_announcement_event(settings, hwaccel),
+        build_metadata_event(settings, hwaccel),
+        build_relay_list_event(settings),
+        build_dm_relay_list_event(settings),
+    ];
+    builders.extend(build_contact_list_event(settings));
+    for builder in builders {
+        let event = builder
+            .to_event(new)
+            .map_err(|e| IdentityError::PublishError(e.to_string()))?;
+        if let Err(e) = send(client, settings, event).await {
+            tracing::warn!(error = %e, "Failed to publish under the new identity");
+        }
+    }
+
+    let new_npub = new.public_key().to_bech32().unwrap_or_default();
+    let deletion = EventBuilder::delete_with_reason(
+        [
+            Coordinate::new(DVM_ANNOUNCEMENT_KIND, old.public_key()).identifier(DVM_SERVICE_ID),
+            Coordinate::new(KIND_APP_SPECIFIC_DATA, old.public_key()).identifier(CONFIG_D_TAG),
+        ],
+        format!("Moved to {new_npub}"),
+    )
+    .to_event(old)
+    .map_err(|e| IdentityError::PublishError(e.to_string()))?;
+    if let Err(e) = send(client, settings, deletion).await {
+        tracing::warn!(error = %e, "Failed to retract the old identity's announcement");
+    }
+
+    Ok(true)
+}
+
+/// Send `event` to the configured relays (all connected relays if none are configured)
+async fn send(
+    client: &Client,
+    settings: &RemoteConfig,
+    event: Event,
+) -> Result<(), nostr_sdk::client::Error> {
+    if settings.relays.is_empty() {
+        client.send_event(event).await?;
+    } else {
+        client
+            .send_event_to(settings.relays.iter().map(String::as_str), event)
+            .await?;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -191,4 +358,49 @@ mod tests {
         let result = load_or_generate_identity_in_dir(dir.path());
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_export_and_import() {
+        let keys = Keys::generate();
+        // A low scrypt cost keeps the test fast
+        let ncryptsec = encrypt_key(&keys, "correct horse", 4).unwrap();
+        assert!(ncryptsec.starts_with("ncryptsec1"));
+
+        let imported = parse_identity(&ncryptsec, Some("correct horse")).unwrap();
+        assert_eq!(imported.secret_key(), keys.secret_key());
+        assert!(matches!(
+            parse_identity(&ncryptsec, Some("wrong horse")),
+            Err(IdentityError::DecryptionError(_))
+        ));
+        assert!(matches!(
+            parse_identity(&ncryptsec, None),
+            Err(IdentityError::PasswordRequired)
+        ));
+        assert!(matches!(
+            encrypt_key(&keys, "short", 4),
+            Err(IdentityError::WeakPassword)
+        ));
+
+        let nsec = keys.secret_key().to_bech32().unwrap();
+        assert_eq!(parse_identity(&nsec, None).unwrap().public_key(), keys.public_key());
+        let hex = keys.secret_key().to_secret_hex();
+        assert_eq!(parse_identity(&hex, None).unwrap().public_key(), keys.public_key());
+    }
+
+    #[test]
+    fn test_replace_identity_keeps_backup() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("identity.key");
+        let old = generate_and_save_identity(&path).unwrap();
+
+        let new = Keys::generate();
+        let backup = replace_identity(&path, &new).unwrap().unwrap();
+        assert_eq!(load_identity(&path).unwrap().public_key(), new.public_key());
+        assert_eq!(load_identity(&backup).unwrap().public_key(), old.public_key());
+        assert!(backup.to_string_lossy().ends_with(".bak"));
+
+        // Nothing to back up on a fresh data dir
+        let fresh = dir.path().join("fresh").join("identity.key");
+        assert_eq!(replace_identity(&fresh, &new).unwrap(), None);
+    }
 }
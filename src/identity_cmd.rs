@@ -0,0 +1,102 @@
+//! `nostube-transcode identity` subcommands — export, import and rotate the DVM key.
+
+use anyhow::{bail, Context, Result};
+use nostr_sdk::prelude::*;
+use std::io::{self, BufRead, Write};
+
+use crate::config_cmd::connect_and_fetch;
+use crate::identity::{
+    export_identity, identity_key_path, load_or_generate_identity, migrate_identity,
+    parse_identity, replace_identity,
+};
+use crate::paths::Paths;
+use crate::video::HwAccel;
+
+/// `identity show` — print the DVM's public key.
+pub fn show() -> Result<()> {
+    let keys = load_or_generate_identity().context("Failed to load DVM identity key")?;
+    let pubkey = keys.public_key();
+    println!("npub: {}", pubkey.to_bech32().unwrap_or_default());
+    println!("hex:  {}", pubkey.to_hex());
+    Ok(())
+}
+
+/// `identity export` — print the private key encrypted with a password.
+pub fn export() -> Result<()> {
+    let keys = load_or_generate_identity().context("Failed to load DVM identity key")?;
+    let password = prompt("Password to encrypt the key with: ");
+    if prompt("Repeat password: ") != password {
+        bail!("Passwords don't match");
+    }
+    println!("{}", export_identity(&keys, &password)?);
+    Ok(())
+}
+
+/// `identity import` — switch to an existing key (nsec, hex or ncryptsec).
+pub async fn import(paths: &Paths, key: Option<String>) -> Result<()> {
+    let key = key.unwrap_or_else(|| prompt("Key to import (nsec, hex or ncryptsec): "));
+    let password = key
+        .trim()
+        .starts_with("ncryptsec")
+        .then(|| prompt("Password: "));
+    let keys = parse_identity(&key, password.as_deref())?;
+    switch_identity(paths, keys).await
+}
+
+/// `identity rotate` — switch to a newly generated key.
+pub async fn rotate(paths: &Paths) -> Result<()> {
+    switch_identity(paths, Keys::generate()).await
+}
+
+/// Move the remote config and announcements to `new`, then replace the key file.
+async fn switch_identity(paths: &Paths, new: Keys) -> Result<()> {
+    if crate::service::process::is_process_running(&paths.pid_file) {
+        bail!(
+            "The DVM is running. Stop it first (nostube-transcode stop), \
+             or use the rotate_identity / import_identity admin command."
+        );
+    }
+
+    let (client, old, config) = connect_and_fetch(paths).await?;
+    if old.public_key() == new.public_key() {
+        println!("The DVM already uses this key.");
+        return Ok(());
+    }
+
+    match config {
+        Some(cfg) => {
+            let hwaccel = tokio::task::spawn_blocking(HwAccel::detect).await?;
+            if migrate_identity(&client, &old, &new, &cfg, hwaccel).await? {
+                println!("Remote config and announcements published under the new key.");
+            } else {
+                println!("The key already has a remote config, keeping it.");
+            }
+        }
+        None => println!("No remote config found, only the key file is replaced."),
+    }
+    let _ = client.disconnect().await;
+
+    let backup = replace_identity(&identity_key_path(), &new)?;
+    println!(
+        "DVM pubkey: {}",
+        new.public_key().to_bech32().unwrap_or_default()
+    );
+    if let Some(backup) = backup {
+        println!("Previous key kept at {}", backup.display());
+    }
+    println!("Point the admin UI at the new pubkey after starting the DVM.");
+    Ok(())
+}
+
+/// Read a line from the terminal (stdin when there is none)
+fn prompt(label: &str) -> String {
+    eprint!("{label}");
+    io::stderr().flush().ok();
+    let mut line = String::new();
+    if let Ok(tty) = std::fs::File::open("/dev/tty") {
+        io::BufReader::new(tty).read_line(&mut line).ok();
+    } else {
+        io::stdin().lock().read_line(&mut line).ok();
+    }
+    line.trim_end_matches(['\r', '\n']).to_string()
+}
@@ -14,9 +14,13 @@ pub mod doctor;
 pub mod dvm;
 pub mod dvm_state;
 pub mod error;
+pub mod fetcher;
 pub mod identity;
+pub mod identity_cmd;
+pub mod loadtest;
 pub mod nostr;
 pub mod paths;
+pub mod payments;
 pub mod remote_config;
 pub mod runtime;
 pub mod selftest;
@@ -29,4 +33,4 @@ pub mod video;
 pub mod web;
 
 pub use config::Config;
-pub use error::{BlossomError, ConfigError, DvmError, VideoError};
+pub use error::{BlossomError, ConfigError, DvmError, PaymentError, VideoError};
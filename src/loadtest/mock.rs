@@ -0,0 +1,210 @@
+//! Stand-ins for FFmpeg and a Blossom server.
+//!
+//! With these a DVM runs the whole job pipeline (fetch, probe, "encode",
+//! upload, publish) without encoding anything or storing blobs, so a load
+//! test measures the DVM's own overhead: relay round trips, queueing and
+//! concurrency limits. The integration tests use the same scripts.
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{header, HeaderMap};
+use axum::routing::{get, put};
+use axum::{Json, Router};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use tokio::net::TcpListener;
+
+use crate::util::hash::hash_bytes;
+
+/// Body served as the mock input video (the fake FFprobe ignores it)
+const MOCK_INPUT: &[u8] = b"nostube-transcode load test input\n";
+
+/// Stand-in for FFmpeg: answers `-version`, prints `-progress` lines, reports
+/// a loudness measurement for `-f null` passes, writes two tiny segments per
+/// `-var_stream_map` entry for HLS output, and a few bytes for anything else
+/// (to stdout for `pipe:` outputs).
+/// Every run except `-version` sleeps for `$FAKE_ENCODE_SECS` (default 0) to
+/// stand in for encoding time; HLS runs have finished their first segments by
+/// then, like a real encode would.
+pub const FAKE_FFMPEG: &str = r#"#!/bin/sh
+prev=""
+out=""
+map=""
+segment_type="mpegts"
+progress=""
+null_output=""
+for arg in "$@"; do
+    case "$prev" in
+        -var_stream_map) map="$arg" ;;
+        -hls_segment_type) segment_type="$arg" ;;
+        -progress) progress=1 ;;
+        -f) [ "$arg" = "null" ] && null_output=1 ;;
+    esac
+    if [ "$arg" = "-version" ]; then
+        echo "ffmpeg version 6.1-fake Copyright (c) 2000-2023 the FFmpeg developers"
+        exit 0
+    fi
+    prev="$arg"
+    out="$arg"
+done
+
+ext="ts"
+[ "$segment_type" = "fmp4" ] && ext="m4s"
+if [ -n "$map" ]; then
+    dir=$(dirname "$out")
+    i=0
+    for entry in $map; do
+        printf 'fake segment %s/0\n' "$i" > "$dir/stream_${i}_000.$ext"
+        printf 'fake segment %s/1\n' "$i" > "$dir/stream_${i}_001.$ext"
+        i=$((i + 1))
+    done
+fi
+
+sleep "${FAKE_ENCODE_SECS:-0}"
+
+if [ -n "$progress" ]; then
+    echo "out_time_us=5000000"
+    echo "progress=continue"
+    echo "out_time_us=10000000"
+    echo "progress=end"
+fi
+
+if [ -n "$null_output" ]; then
+    cat >&2 <<'JSON'
+[Parsed_loudnorm_0 @ 0x0]
+{
+	"input_i" : "-31.42",
+	"input_tp" : "-9.87",
+	"input_lra" : "6.10",
+	"input_thresh" : "-41.80",
+	"target_offset" : "0.05"
+}
+JSON
+    exit 0
+fi
+
+if [ -n "$map" ]; then
+    dir=$(dirname "$out")
+    master="$dir/master.m3u8"
+    printf '#EXTM3U\n#EXT-X-VERSION:6\n' > "$master"
+    i=0
+    for entry in $map; do
+        printf '#EXTM3U\n#EXT-X-VERSION:6\n#EXT-X-TARGETDURATION:5\n#EXTINF:5.000000,\nstream_%s_000.%s\n#EXTINF:5.000000,\nstream_%s_001.%s\n#EXT-X-ENDLIST\n' \
+            "$i" "$ext" "$i" "$ext" > "$dir/stream_$i.m3u8"
+        case "$entry" in
+            a:*)
+                printf '#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="audio",NAME="audio_%s",URI="stream_%s.m3u8"\n' \
+                    "$i" "$i" >> "$master"
+                ;;
+            *)
+                height=$((360 * (i + 1)))
+                printf '#EXT-X-STREAM-INF:BANDWIDTH=%s,RESOLUTION=%sx%s,CODECS="avc1.64001f,mp4a.40.2"\nstream_%s.m3u8\n' \
+                    $((800000 * (i + 1))) $((height * 16 / 9)) "$height" "$i" >> "$master"
+                ;;
+        esac
+        i=$((i + 1))
+    done
+    exit 0
+fi
+
+case "$out" in
+    pipe:*) printf 'fake media\n' ;;
+    *) printf 'fake media\n' > "$out" ;;
+esac
+"#;
+
+/// Stand-in for FFprobe: a 10 second 720p H.264 video with one AAC track
+pub const FAKE_FFPROBE: &str = r#"#!/bin/sh
+for input in "$@"; do :; done
+cat <<JSON
+{
+  "format": {
+    "filename": "$input",
+    "duration": "10.000000",
+    "bit_rate": "2500000",
+    "format_name": "mov,mp4,m4a,3gp,3g2,mj2"
+  },
+  "streams": [
+    {
+      "index": 0,
+      "codec_type": "video",
+      "codec_name": "h264",
+      "width": 1280,
+      "height": 720,
+      "avg_frame_rate": "30/1",
+      "r_frame_rate": "30/1"
+    },
+    {
+      "index": 1,
+      "codec_type": "audio",
+      "codec_name": "aac",
+      "channels": 2,
+      "sample_rate": "48000"
+    }
+  ]
+}
+JSON
+"#;
+
+/// Write the fake `ffmpeg` and `ffprobe` scripts into `dir`, returning their
+/// paths. They are POSIX shell scripts, so they only run on Unix hosts.
+pub fn write_fake_ffmpeg(dir: &Path) -> std::io::Result<(PathBuf, PathBuf)> {
+    std::fs::create_dir_all(dir)?;
+    let mut paths = Vec::new();
+    for (name, contents) in [("ffmpeg", FAKE_FFMPEG), ("ffprobe", FAKE_FFPROBE)] {
+        let path = dir.join(name);
+        std::fs::write(&path, contents)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+        }
+        paths.push(path);
+    }
+    let ffprobe = paths.pop().unwrap_or_default();
+    let ffmpeg = paths.pop().unwrap_or_default();
+    Ok((ffmpeg, ffprobe))
+}
+
+/// `loadtest mock`: write the fake binaries, print how to point a DVM at
+/// them, and serve the mock Blossom server until interrupted
+pub async fn run(dir: &Path, port: u16) -> anyhow::Result<()> {
+    let (ffmpeg, ffprobe) = write_fake_ffmpeg(dir)?;
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    println!("Start the DVM under test with:");
+    println!("  FFMPEG_PATH={}", ffmpeg.display());
+    println!("  FFPROBE_PATH={}", ffprobe.display());
+    println!("  FAKE_ENCODE_SECS=5   (simulated encode time per FFmpeg run)");
+    println!("and set its blossom_servers to http://{}", addr);
+    println!();
+    println!("Mock input for loadtest run: http://{}/input.mp4", addr);
+    serve_mock_blossom(addr).await?;
+    Ok(())
+}
+
+/// Blossom server that stores nothing and describes every uploaded blob.
+/// `GET /input.mp4` serves a placeholder input for the synthetic requests.
+pub async fn serve_mock_blossom(addr: SocketAddr) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    let base = format!("http://{}", listener.local_addr()?);
+    let app = Router::new()
+        .route("/upload", put(upload))
+        .route("/input.mp4", get(|| async { MOCK_INPUT }))
+        .with_state(base);
+    axum::serve(listener, app).await
+}
+
+async fn upload(State(base): State<String>, headers: HeaderMap, body: Bytes) -> Json<serde_json::Value> {
+    let sha256 = hash_bytes(&body);
+    let mime_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("application/octet-stream");
+    Json(serde_json::json!({
+        "url": format!("{}/{}", base, sha256),
+        "sha256": sha256,
+        "size": body.len(),
+        "type": mime_type,
+        "uploaded": chrono::Utc::now().timestamp(),
+    }))
+}
@@ -0,0 +1,235 @@
+//! Synthetic load against a running DVM.
+//!
+//! `nostube-transcode loadtest run` publishes job requests from a throwaway
+//! key at a fixed rate, follows the DVM's status and result events for them,
+//! and reports throughput and latency percentiles. Pointed at a DVM that
+//! runs with the fake FFmpeg and the mock Blossom server from [`mock`]
+//! (`nostube-transcode loadtest mock`), it shows how many jobs the host and
+//! relays sustain before queueing sets in; against a real setup, it shows
+//! end-to-end encode times.
+
+pub mod mock;
+pub mod report;
+
+pub use report::{JobTiming, LoadReport, Percentiles};
+
+use anyhow::{Context, Result};
+use nostr_sdk::prelude::*;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{interval, sleep_until, Instant, MissedTickBehavior};
+use tracing::{debug, warn};
+
+use crate::dvm::events::{
+    JobStatus, DVM_STATUS_KIND, DVM_VIDEO_TRANSFORM_REQUEST_KIND, DVM_VIDEO_TRANSFORM_RESULT_KIND,
+};
+
+/// How long to wait for relay connections before sending
+const CONNECT_WAIT: Duration = Duration::from_secs(2);
+
+pub struct LoadTestOptions {
+    /// DVM the requests are addressed to
+    pub dvm: PublicKey,
+    pub relays: Vec<String>,
+    pub input_url: String,
+    /// `hls` or `mp4`
+    pub mode: String,
+    /// Requests per second
+    pub rate: f64,
+    pub jobs: usize,
+    /// How long to wait for answers after the last request
+    pub timeout: Duration,
+}
+
+/// The `seq` tag keeps requests sent within the same second distinct
+fn request_event(options: &LoadTestOptions, keys: &Keys, seq: usize) -> Result<Event> {
+    let tags = vec![
+        Tag::custom(
+            TagKind::Custom("i".into()),
+            vec![options.input_url.clone(), "url".to_string()],
+        ),
+        Tag::custom(
+            TagKind::Custom("param".into()),
+            vec!["mode".to_string(), options.mode.clone()],
+        ),
+        Tag::public_key(options.dvm),
+        Tag::custom(TagKind::Custom("relays".into()), options.relays.clone()),
+        Tag::custom(TagKind::Custom("seq".into()), vec![seq.to_string()]),
+    ];
+    Ok(EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags).to_event(keys)?)
+}
+
+/// Request an answer event refers to
+fn answered_request(event: &Event) -> Option<EventId> {
+    event.tags.iter().find_map(|t| {
+        let parts = t.as_slice();
+        (parts.len() >= 2 && parts[0] == "e").then(|| EventId::parse(&parts[1]).ok())?
+    })
+}
+
+fn status_of(event: &Event) -> Option<&str> {
+    event.tags.iter().find_map(|t| {
+        let parts = t.as_slice();
+        (parts.len() >= 2 && parts[0] == "status").then(|| parts[1].as_str())
+    })
+}
+
+struct SentJob {
+    sent_at: Instant,
+    timing: JobTiming,
+}
+
+/// Record an answer from the DVM against the request it refers to
+fn record_answer(jobs: &mut HashMap<EventId, SentJob>, event: &Event, now: Instant) {
+    let Some(pending) = answered_request(event).and_then(|id| jobs.get_mut(&id)) else {
+        return;
+    };
+    let timing = &mut pending.timing;
+    if timing.finished.is_some() {
+        return;
+    }
+    let elapsed = now - pending.sent_at;
+    if event.kind == DVM_VIDEO_TRANSFORM_RESULT_KIND {
+        timing.finished = Some(elapsed);
+    } else if event.kind == DVM_STATUS_KIND {
+        match status_of(event) {
+            Some(s) if s == JobStatus::Error.as_str() => {
+                timing.finished = Some(elapsed);
+                timing.failed = true;
+            }
+            Some(s) if s == JobStatus::Processing.as_str() => {
+                timing.first_status.get_or_insert(elapsed);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Send `options.jobs` requests at `options.rate` per second and wait for
+/// their results, or until `options.timeout` after the last request.
+pub async fn run(options: &LoadTestOptions) -> Result<LoadReport> {
+    anyhow::ensure!(options.rate > 0.0, "rate must be positive");
+    anyhow::ensure!(!options.relays.is_empty(), "at least one relay is required");
+
+    let keys = Keys::generate();
+    let client = crate::util::proxy::nostr_client(&keys);
+    for relay in &options.relays {
+        client
+            .add_relay(relay.as_str())
+            .await
+            .with_context(|| format!("Invalid relay {}", relay))?;
+    }
+    client.connect().await;
+    tokio::time::sleep(CONNECT_WAIT).await;
+
+    let filter = Filter::new()
+        .kinds([DVM_STATUS_KIND, DVM_VIDEO_TRANSFORM_RESULT_KIND])
+        .pubkey(keys.public_key())
+        .since(Timestamp::now());
+    client
+        .subscribe(vec![filter], None)
+        .await
+        .context("Failed to subscribe to DVM answers")?;
+    let mut notifications = client.notifications();
+
+    let mut jobs: HashMap<EventId, SentJob> = HashMap::new();
+    let mut ticks = interval(Duration::from_secs_f64(1.0 / options.rate));
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let started = Instant::now();
+    let mut deadline = Instant::now() + options.timeout;
+    let mut sent = 0;
+
+    loop {
+        let all_sent = sent == options.jobs;
+        if all_sent && jobs.values().all(|p| p.timing.finished.is_some()) {
+            break;
+        }
+        tokio::select! {
+            _ = ticks.tick(), if !all_sent => {
+                let event = request_event(options, &keys, sent)?;
+                let id = event.id;
+                let mut timing = JobTiming::default();
+                match client.send_event(event).await {
+                    Ok(_) => debug!(request = %id, "Load test request sent"),
+                    Err(e) => {
+                        warn!(error = %e, "Failed to send load test request");
+                        timing.finished = Some(Duration::ZERO);
+                        timing.failed = true;
+                    }
+                }
+                jobs.insert(id, SentJob { sent_at: Instant::now(), timing });
+                sent += 1;
+                deadline = Instant::now() + options.timeout;
+            }
+            notification = notifications.recv() => match notification {
+                Ok(RelayPoolNotification::Event { event, .. }) => {
+                    record_answer(&mut jobs, &event, Instant::now());
+                }
+                Ok(_) => {}
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                    warn!(skipped = n, "Load test fell behind on relay notifications");
+                }
+                Err(_) => break,
+            },
+            _ = sleep_until(deadline), if all_sent => break,
+        }
+    }
+
+    let elapsed = started.elapsed();
+    client.disconnect().await.ok();
+    let timings: Vec<JobTiming> = jobs.into_values().map(|p| p.timing).collect();
+    Ok(LoadReport::new(&timings, elapsed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer(kind: Kind, request: EventId, status: Option<&str>) -> Event {
+        let mut tags = vec![Tag::event(request)];
+        if let Some(status) = status {
+            tags.push(Tag::custom(TagKind::Custom("status".into()), vec![status.to_string()]));
+        }
+        EventBuilder::new(kind, "", tags).to_event(&Keys::generate()).unwrap()
+    }
+
+    #[test]
+    fn test_record_answers() {
+        let options = LoadTestOptions {
+            dvm: Keys::generate().public_key(),
+            relays: vec!["ws://127.0.0.1:7777".to_string()],
+            input_url: "http://127.0.0.1:3001/input.mp4".to_string(),
+            mode: "hls".to_string(),
+            rate: 1.0,
+            jobs: 2,
+            timeout: Duration::from_secs(60),
+        };
+        let keys = Keys::generate();
+        let ok = request_event(&options, &keys, 0).unwrap();
+        let bad = request_event(&options, &keys, 1).unwrap();
+        assert_eq!(ok.kind, DVM_VIDEO_TRANSFORM_REQUEST_KIND);
+
+        let start = Instant::now();
+        let mut jobs = HashMap::new();
+        for id in [ok.id, bad.id] {
+            jobs.insert(id, SentJob { sent_at: start, timing: JobTiming::default() });
+        }
+        let at = |secs| start + Duration::from_secs(secs);
+
+        record_answer(&mut jobs, &answer(DVM_STATUS_KIND, ok.id, Some("processing")), at(1));
+        record_answer(&mut jobs, &answer(DVM_STATUS_KIND, ok.id, Some("processing")), at(2));
+        record_answer(&mut jobs, &answer(DVM_VIDEO_TRANSFORM_RESULT_KIND, ok.id, None), at(9));
+        record_answer(&mut jobs, &answer(DVM_STATUS_KIND, bad.id, Some("error")), at(3));
+        // Answers to requests from elsewhere are ignored
+        record_answer(&mut jobs, &answer(DVM_STATUS_KIND, EventId::all_zeros(), Some("error")), at(4));
+
+        let ok = jobs[&ok.id].timing;
+        assert_eq!(ok.first_status, Some(Duration::from_secs(1)));
+        assert_eq!(ok.finished, Some(Duration::from_secs(9)));
+        assert!(!ok.failed);
+        let bad = jobs[&bad.id].timing;
+        assert_eq!(bad.finished, Some(Duration::from_secs(3)));
+        assert!(bad.failed);
+        assert_eq!(jobs.len(), 2);
+    }
+}
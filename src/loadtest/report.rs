@@ -0,0 +1,171 @@
+//! Throughput and latency summary of a load test.
+
+use std::fmt;
+use std::time::Duration;
+
+/// What happened to one synthetic job
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct JobTiming {
+    /// Time from sending the request to the first `processing` status
+    pub first_status: Option<Duration>,
+    /// Time from sending the request to the result or error status
+    pub finished: Option<Duration>,
+    /// Whether the job ended with an error status
+    pub failed: bool,
+}
+
+/// Latency distribution over the jobs that got an answer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Percentiles {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl Percentiles {
+    /// Nearest-rank percentiles; `None` for an empty sample
+    pub fn of(mut samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort();
+        let rank = |p: f64| {
+            let idx = ((p / 100.0) * samples.len() as f64).ceil() as usize;
+            samples[idx.clamp(1, samples.len()) - 1]
+        };
+        Some(Self {
+            p50: rank(50.0),
+            p90: rank(90.0),
+            p99: rank(99.0),
+            max: samples[samples.len() - 1],
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadReport {
+    pub sent: usize,
+    pub completed: usize,
+    pub failed: usize,
+    /// Jobs without a result or error before the timeout
+    pub timed_out: usize,
+    /// From the first request to the last answer (or the timeout)
+    pub elapsed: Duration,
+    /// Time to the first `processing` status
+    pub pickup: Option<Percentiles>,
+    /// Time to the result, completed jobs only
+    pub latency: Option<Percentiles>,
+}
+
+impl LoadReport {
+    pub fn new(timings: &[JobTiming], elapsed: Duration) -> Self {
+        let completed: Vec<Duration> = timings
+            .iter()
+            .filter(|t| !t.failed)
+            .filter_map(|t| t.finished)
+            .collect();
+        let failed = timings.iter().filter(|t| t.failed).count();
+        Self {
+            sent: timings.len(),
+            completed: completed.len(),
+            failed,
+            timed_out: timings.len() - completed.len() - failed,
+            elapsed,
+            pickup: Percentiles::of(timings.iter().filter_map(|t| t.first_status).collect()),
+            latency: Percentiles::of(completed),
+        }
+    }
+
+    /// Completed jobs per minute over the whole run
+    pub fn throughput_per_min(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            return 0.0;
+        }
+        self.completed as f64 * 60.0 / secs
+    }
+}
+
+fn write_percentiles(f: &mut fmt::Formatter<'_>, label: &str, p: &Option<Percentiles>) -> fmt::Result {
+    match p {
+        Some(p) => writeln!(
+            f,
+            "{:<14} p50 {:.1}s  p90 {:.1}s  p99 {:.1}s  max {:.1}s",
+            label,
+            p.p50.as_secs_f64(),
+            p.p90.as_secs_f64(),
+            p.p99.as_secs_f64(),
+            p.max.as_secs_f64()
+        ),
+        None => writeln!(f, "{:<14} -", label),
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Jobs:          {} sent, {} completed, {} failed, {} timed out",
+            self.sent, self.completed, self.failed, self.timed_out
+        )?;
+        writeln!(
+            f,
+            "Duration:      {:.1}s ({:.2} jobs/min)",
+            self.elapsed.as_secs_f64(),
+            self.throughput_per_min()
+        )?;
+        write_percentiles(f, "Pickup:", &self.pickup)?;
+        write_percentiles(f, "Latency:", &self.latency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let p = Percentiles::of((1..=100).map(secs).collect()).unwrap();
+        assert_eq!(p.p50, secs(50));
+        assert_eq!(p.p90, secs(90));
+        assert_eq!(p.p99, secs(99));
+        assert_eq!(p.max, secs(100));
+
+        let single = Percentiles::of(vec![secs(7)]).unwrap();
+        assert_eq!((single.p50, single.p99), (secs(7), secs(7)));
+        assert!(Percentiles::of(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn test_report() {
+        let done = |s| JobTiming {
+            first_status: Some(secs(1)),
+            finished: Some(secs(s)),
+            failed: false,
+        };
+        let timings = vec![
+            done(10),
+            done(20),
+            JobTiming {
+                first_status: Some(secs(2)),
+                finished: Some(secs(5)),
+                failed: true,
+            },
+            JobTiming::default(),
+        ];
+        let report = LoadReport::new(&timings, secs(60));
+        assert_eq!(
+            (report.sent, report.completed, report.failed, report.timed_out),
+            (4, 2, 1, 1)
+        );
+        assert_eq!(report.latency.unwrap().max, secs(20));
+        assert_eq!(report.pickup.unwrap().max, secs(2));
+        assert!((report.throughput_per_min() - 2.0).abs() < f64::EPSILON);
+        assert!(report.to_string().contains("2 completed"));
+    }
+}
@@ -1,5 +1,7 @@
 use clap::Parser;
-use nostube_transcode::cli::{Cli, Commands, ConfigCommands, DockerCommands};
+use nostube_transcode::cli::{
+    Cli, Commands, ConfigCommands, DockerCommands, IdentityCommands, LoadtestCommands,
+};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -115,6 +117,22 @@ async fn main() -> anyhow::Result<()> {
             Ok(())
         }
 
+        // ── Identity ───────────────────────────────────────────────────────
+        Some(Commands::Identity { command }) => {
+            let paths = nostube_transcode::paths::Paths::resolve();
+            match command {
+                IdentityCommands::Show => nostube_transcode::identity_cmd::show()?,
+                IdentityCommands::Export => nostube_transcode::identity_cmd::export()?,
+                IdentityCommands::Import { key } => {
+                    nostube_transcode::identity_cmd::import(&paths, key).await?;
+                }
+                IdentityCommands::Rotate => {
+                    nostube_transcode::identity_cmd::rotate(&paths).await?;
+                }
+            }
+            Ok(())
+        }
+
         // ── Service management ─────────────────────────────────────────────
         Some(Commands::Install { force, system, user }) => {
             let paths = nostube_transcode::paths::Paths::resolve();
@@ -228,6 +246,41 @@ async fn main() -> anyhow::Result<()> {
             }
             Ok(())
         }
+
+        // ── Load testing ───────────────────────────────────────────────────
+        Some(Commands::Loadtest { command }) => {
+            match command {
+                LoadtestCommands::Run {
+                    dvm,
+                    relays,
+                    input,
+                    mode,
+                    rate,
+                    jobs,
+                    timeout,
+                } => {
+                    let options = nostube_transcode::loadtest::LoadTestOptions {
+                        dvm: nostr_sdk::PublicKey::parse(&dvm)?,
+                        relays,
+                        input_url: input,
+                        mode,
+                        rate,
+                        jobs,
+                        timeout: std::time::Duration::from_secs(timeout),
+                    };
+                    println!("Sending {} jobs at {}/s...", jobs, rate);
+                    let report = nostube_transcode::loadtest::run(&options).await?;
+                    print!("{report}");
+                }
+                LoadtestCommands::Mock { dir, port } => {
+                    let dir = dir.unwrap_or_else(|| {
+                        nostube_transcode::paths::Paths::resolve().data_dir.join("loadtest")
+                    });
+                    nostube_transcode::loadtest::mock::run(&dir, port).await?;
+                }
+            }
+            Ok(())
+        }
     }
 }
 
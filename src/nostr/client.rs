@@ -6,6 +6,7 @@ use tracing::{debug, error, info, warn};
 
 use crate::config::Config;
 use crate::dvm_state::SharedDvmState;
+use crate::dvm::encryption::feedback_status;
 use crate::dvm::events::{JobContext, DVM_VIDEO_TRANSFORM_REQUEST_KIND, DVM_STATUS_KIND};
 use crate::error::DvmError;
 
@@ -13,11 +14,19 @@ pub struct SubscriptionManager {
     config: Arc<Config>,
     client: Client,
     state: SharedDvmState,
+    /// Receives NIP-56 reports naming the DVM, for the moderator
+    reports: Option<mpsc::Sender<Event>>,
 }
 
 impl SubscriptionManager {
     pub async fn new(config: Arc<Config>, client: Client, state: SharedDvmState) -> Result<Self, DvmError> {
-        Ok(Self { config, client, state })
+        Ok(Self { config, client, state, reports: None })
+    }
+
+    /// Forward abuse reports about the DVM's results to `tx`
+    pub fn with_reports(mut self, tx: mpsc::Sender<Event>) -> Self {
+        self.reports = Some(tx);
+        self
     }
 
     /// Get the DVM keys for encryption/decryption
@@ -76,9 +85,9 @@ impl SubscriptionManager {
             ])
             .since(Timestamp::now());
             
-        // For status and gift wrap, we only care about those addressed to us
+        // For status, gift wrap and reports, we only care about those addressed to us
         let directed_filter = Filter::new()
-            .kinds(vec![DVM_STATUS_KIND, Kind::GiftWrap])
+            .kinds(vec![DVM_STATUS_KIND, Kind::GiftWrap, Kind::Reporting])
             .pubkey(dvm_pubkey)
             .since(Timestamp::now());
 
@@ -115,9 +124,12 @@ impl SubscriptionManager {
                 let seen = seen.clone();
                 let keys = keys.clone();
                 let state = self.state.clone();
+                let reports = self.reports.clone();
 
                 async move {
-                    if let RelayPoolNotification::Event { event, .. } = notification {
+                    if let RelayPoolNotification::Event { relay_url, event, .. } = notification {
+                        state.write().await.relay_stats.record_received(relay_url.as_str());
+
                         if event.kind == DVM_VIDEO_TRANSFORM_REQUEST_KIND {
                             let mut seen_guard = seen.lock().await;
                             if !seen_guard.contains(&event.id) {
@@ -136,11 +148,10 @@ impl SubscriptionManager {
                                 }
                             }
                         } else if event.kind == DVM_STATUS_KIND {
-                            // Check if this is a "selection" feedback from a user
-                            let is_approved = event.tags.iter().any(|t| {
-                                let parts = t.as_slice();
-                                parts.len() >= 2 && parts[0] == "status" && parts[1] == "approved"
-                            });
+                            // Check if this is a "selection" feedback from a user,
+                            // which may be encrypted like the request it approves
+                            let is_approved =
+                                feedback_status(&keys, &event).as_deref() == Some("approved");
 
                             if is_approved {
                                 let job_id = event.tags.iter().find_map(|t| {
@@ -209,6 +220,15 @@ impl SubscriptionManager {
                                     }
                                 }
                             }
+                        } else if event.kind == Kind::Reporting {
+                            if let Some(reports) = reports {
+                                if seen.lock().await.insert(event.id) {
+                                    debug!(event_id = %event.id, "Received report");
+                                    if let Err(e) = reports.send((*event).clone()).await {
+                                        error!("Failed to forward report: {}", e);
+                                    }
+                                }
+                            }
                         }
                     }
                     Ok(false)
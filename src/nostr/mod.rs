@@ -1,5 +1,9 @@
 pub mod client;
+pub mod outbox;
 pub mod publisher;
+pub mod stats;
 
 pub use client::SubscriptionManager;
+pub use outbox::{Outbox, OutboxSender};
 pub use publisher::EventPublisher;
+pub use stats::{RelayRefusal, RelayStats, RelayStatsEntry};
@@ -0,0 +1,303 @@
+//! Outbox for result events that didn't reach enough relays.
+//!
+//! A relay going down mid-job shouldn't cost a paying requester their result.
+//! Result events are kept here, signed, until `RESULT_MIN_ACKS` of their
+//! relays accepted them. The [`OutboxSender`] retries the relays that haven't
+//! with growing delays, starting with whatever the last run left behind. An
+//! entry is given up once its event expires, as the result blobs are gone by
+//! then. The outbox is persisted as JSON so pending results survive restarts.
+
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::time::{interval, sleep, Duration};
+use tracing::{info, warn};
+
+use crate::dvm_state::SharedDvmState;
+use crate::nostr::EventPublisher;
+
+/// Acknowledgements a result needs when `RESULT_MIN_ACKS` is unset
+pub const DEFAULT_RESULT_MIN_ACKS: usize = 2;
+
+/// Delay before the first retry, doubled after each one
+const INITIAL_BACKOFF_SECS: u64 = 30;
+/// Longest delay between retries
+const MAX_BACKOFF_SECS: u64 = 3600;
+
+/// How often the sender looks for due entries
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+/// Time relays get to connect before leftovers from the last run are resent
+const STARTUP_DELAY: Duration = Duration::from_secs(5);
+
+/// A result event waiting for relays to accept it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    /// The signed event, sent as is on every retry
+    pub event: Event,
+    /// Relays the event is meant for
+    pub relays: Vec<String>,
+    /// Relays that accepted it
+    pub acked: BTreeSet<String>,
+    /// Acknowledgements needed (at most the number of relays)
+    pub required: usize,
+    /// Sends so far, including the first publish
+    pub attempts: u32,
+    /// Unix time of the next retry
+    pub next_attempt_at: u64,
+}
+
+impl OutboxEntry {
+    /// Relays that haven't accepted the event yet
+    pub fn pending_relays(&self) -> Vec<String> {
+        self.relays
+            .iter()
+            .filter(|r| !self.acked.contains(*r))
+            .cloned()
+            .collect()
+    }
+
+    pub fn is_delivered(&self) -> bool {
+        self.acked.len() >= self.required
+    }
+
+    fn schedule_retry(&mut self, now: u64) {
+        let backoff = INITIAL_BACKOFF_SECS
+            .saturating_mul(1 << self.attempts.saturating_sub(1).min(16))
+            .min(MAX_BACKOFF_SECS);
+        self.next_attempt_at = now + backoff;
+    }
+}
+
+/// Result events short of acknowledgements, keyed by event ID (hex)
+#[derive(Debug, Default)]
+pub struct Outbox {
+    /// File the outbox is persisted to (`None` keeps it in memory only)
+    path: Option<PathBuf>,
+    entries: HashMap<String, OutboxEntry>,
+}
+
+impl Outbox {
+    /// Load the outbox from `path`. A missing or unreadable file starts an empty outbox.
+    pub fn load(path: PathBuf) -> Self {
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!(path = %path.display(), error = %e, "Ignoring corrupt outbox");
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Self {
+            path: Some(path),
+            entries,
+        }
+    }
+
+    /// Keep `event` until `required` of `relays` (or all of them, if fewer)
+    /// accepted it. `acked` are the relays that already did.
+    ///
+    /// Returns whether the event was queued, i.e. it is still short.
+    pub fn enqueue(
+        &mut self,
+        event: Event,
+        relays: Vec<String>,
+        acked: Vec<String>,
+        required: usize,
+        now: u64,
+    ) -> bool {
+        let mut entry = OutboxEntry {
+            required: required.min(relays.len()),
+            acked: acked.into_iter().filter(|r| relays.contains(r)).collect(),
+            relays,
+            event,
+            attempts: 1,
+            next_attempt_at: 0,
+        };
+        if entry.is_delivered() {
+            return false;
+        }
+        entry.schedule_retry(now);
+        self.entries.insert(entry.event.id.to_hex(), entry);
+        true
+    }
+
+    /// Entries whose retry is due at `now`
+    pub fn due(&self, now: u64) -> Vec<OutboxEntry> {
+        self.entries
+            .values()
+            .filter(|e| e.next_attempt_at <= now)
+            .cloned()
+            .collect()
+    }
+
+    /// Record a retry of `id` that `acked` relays accepted. Delivered
+    /// entries are removed; returns whether this one was.
+    pub fn record_attempt(&mut self, id: &EventId, acked: Vec<String>, now: u64) -> bool {
+        let Some(entry) = self.entries.get_mut(&id.to_hex()) else {
+            return false;
+        };
+        entry.acked.extend(acked);
+        if entry.is_delivered() {
+            self.entries.remove(&id.to_hex());
+            return true;
+        }
+        entry.attempts += 1;
+        entry.schedule_retry(now);
+        false
+    }
+
+    /// Give up on entries whose event expired at `now`, returning how many
+    pub fn drop_expired(&mut self, now: Timestamp) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, e| !e.event.is_expired_at(&now));
+        before - self.entries.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the outbox to its file (no-op for in-memory outboxes)
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string(&self.entries)?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, path)
+    }
+}
+
+/// Retries the results in the outbox until relays accept them
+pub struct OutboxSender {
+    state: SharedDvmState,
+    publisher: Arc<EventPublisher>,
+}
+
+impl OutboxSender {
+    pub fn new(state: SharedDvmState, publisher: Arc<EventPublisher>) -> Self {
+        Self { state, publisher }
+    }
+
+    pub async fn run(self) {
+        sleep(STARTUP_DELAY).await;
+        let mut ticker = interval(CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            self.flush().await;
+        }
+    }
+
+    /// Resend every due entry to the relays that haven't accepted it
+    async fn flush(&self) {
+        let now = Timestamp::now();
+        let due = {
+            let mut state = self.state.write().await;
+            let expired = state.outbox.drop_expired(now);
+            if expired > 0 {
+                warn!(expired, "Gave up on expired results in the outbox");
+                if let Err(e) = state.outbox.save() {
+                    warn!(error = %e, "Failed to save outbox");
+                }
+            }
+            state.outbox.due(now.as_u64())
+        };
+
+        for entry in due {
+            let acked = self
+                .publisher
+                .resend(&entry.event, &entry.pending_relays())
+                .await;
+            let mut state = self.state.write().await;
+            if state.outbox.record_attempt(&entry.event.id, acked, Timestamp::now().as_u64()) {
+                info!(event_id = %entry.event.id, attempts = entry.attempts + 1, "Delivered result from the outbox");
+            }
+            if let Err(e) = state.outbox.save() {
+                warn!(error = %e, "Failed to save outbox");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RELAYS: [&str; 3] = ["wss://a.example", "wss://b.example", "wss://c.example"];
+
+    fn relays() -> Vec<String> {
+        RELAYS.iter().map(|r| r.to_string()).collect()
+    }
+
+    fn event(expires_at: u64) -> Event {
+        EventBuilder::new(Kind::Custom(6207), "result", [Tag::expiration(Timestamp::from(expires_at))])
+            .to_event(&Keys::generate())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_enqueue_only_short_results() {
+        let mut outbox = Outbox::default();
+        assert!(!outbox.enqueue(event(u64::MAX), relays(), relays()[..2].to_vec(), 2, 1000));
+        // A single relay can't give two acknowledgements
+        assert!(!outbox.enqueue(event(u64::MAX), relays()[..1].to_vec(), relays()[..1].to_vec(), 2, 1000));
+        assert!(outbox.is_empty());
+
+        assert!(outbox.enqueue(event(u64::MAX), relays(), vec![RELAYS[1].to_string()], 2, 1000));
+        let entry = &outbox.due(1030)[0];
+        assert_eq!(entry.pending_relays(), vec![RELAYS[0].to_string(), RELAYS[2].to_string()]);
+        assert!(outbox.due(1029).is_empty());
+    }
+
+    #[test]
+    fn test_retries_back_off_until_delivered() {
+        let mut outbox = Outbox::default();
+        let event = event(u64::MAX);
+        let id = event.id;
+        outbox.enqueue(event, relays(), Vec::new(), 2, 0);
+
+        assert!(!outbox.record_attempt(&id, vec![RELAYS[0].to_string()], 30));
+        // Second retry waits twice as long
+        assert!(outbox.due(89).is_empty());
+        assert_eq!(outbox.due(90)[0].attempts, 2);
+
+        assert!(outbox.record_attempt(&id, vec![RELAYS[2].to_string()], 90));
+        assert!(outbox.is_empty());
+        assert!(!outbox.record_attempt(&id, Vec::new(), 100));
+    }
+
+    #[test]
+    fn test_drop_expired() {
+        let mut outbox = Outbox::default();
+        outbox.enqueue(event(500), relays(), Vec::new(), 1, 0);
+        outbox.enqueue(event(u64::MAX), relays(), Vec::new(), 1, 0);
+        assert_eq!(outbox.drop_expired(Timestamp::from(1000)), 1);
+        assert_eq!(outbox.len(), 1);
+    }
+
+    #[test]
+    fn test_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("outbox.json");
+
+        let mut outbox = Outbox::load(path.clone());
+        let event = event(u64::MAX);
+        outbox.enqueue(event.clone(), relays(), vec![RELAYS[0].to_string()], 3, 0);
+        outbox.save().unwrap();
+
+        let loaded = Outbox::load(path);
+        let entry = &loaded.due(u64::MAX)[0];
+        assert_eq!(entry.event, event);
+        assert!(entry.event.verify().is_ok());
+        assert_eq!(entry.pending_relays().len(), 2);
+    }
+}
@@ -1,15 +1,19 @@
 use nostr_sdk::prelude::*;
 use std::sync::Arc;
-use std::time::Duration;
+use futures::future::join_all;
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
 use tracing::{debug, error, warn};
 
 use crate::config::Config;
 use crate::dvm_state::SharedDvmState;
 use crate::error::DvmError;
+use crate::nostr::stats::RelayRefusal;
 
 const MAX_RETRIES: u32 = 3;
 const RETRY_DELAY_MS: u64 = 1000;
+/// How long to wait for relays when looking up an event
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub struct EventPublisher {
     config: Arc<Config>,
@@ -45,6 +49,39 @@ impl EventPublisher {
         }
     }
 
+    /// Look up an event by id on the DVM relays and `relays`.
+    pub async fn fetch_event(&self, id: EventId, relays: &[::url::Url]) -> Option<Event> {
+        self.ensure_relays_connected(relays).await;
+        let filter = Filter::new().id(id).limit(1);
+        match self
+            .client
+            .get_events_of(vec![filter], EventSource::relays(Some(FETCH_TIMEOUT)))
+            .await
+        {
+            Ok(events) => events.into_iter().find(|e| e.id == id),
+            Err(e) => {
+                warn!(event_id = %id, error = %e, "Failed to fetch event");
+                None
+            }
+        }
+    }
+
+    /// Look up events matching `filter` on the DVM relays and `relays`.
+    pub async fn fetch_matching(&self, filter: Filter, relays: &[::url::Url]) -> Vec<Event> {
+        self.ensure_relays_connected(relays).await;
+        match self
+            .client
+            .get_events_of(vec![filter], EventSource::relays(Some(FETCH_TIMEOUT)))
+            .await
+        {
+            Ok(events) => events,
+            Err(e) => {
+                warn!(error = %e, "Failed to fetch events");
+                Vec::new()
+            }
+        }
+    }
+
     /// Publish an event to DVM config relays only.
     ///
     /// Used for announcements and other non-job-specific events.
@@ -66,23 +103,119 @@ impl EventPublisher {
 
     /// Publish an event to DVM config relays + job-specific relays.
     ///
-    /// Used for status updates, results, and other job-related events.
+    /// Used for status updates, results, and other job-related events. Job
+    /// relays that recently refused the DVM's events (AUTH or payment
+    /// required) are skipped; the DVM relays always get the event.
     pub async fn publish_for_job(
         &self,
         builder: EventBuilder,
         job_relays: &[::url::Url],
     ) -> Result<EventId, DvmError> {
+        let relays = self.job_relay_urls(job_relays).await;
+        self.send_to(builder, &relays).await
+    }
+
+    /// Publish a result event like [`publish_for_job`](Self::publish_for_job),
+    /// keeping it in the outbox until `RESULT_MIN_ACKS` relays accepted it.
+    ///
+    /// A result that reached too few relays (or none) still counts as
+    /// published: the [`OutboxSender`](crate::nostr::OutboxSender) keeps
+    /// sending it to the others.
+    pub async fn publish_result(
+        &self,
+        builder: EventBuilder,
+        job_relays: &[::url::Url],
+    ) -> Result<EventId, DvmError> {
+        let event = self.sign(builder)?;
+        let relays = self.job_relay_urls(job_relays).await;
+        let acked = self.send_signed(&event, &relays).await.unwrap_or_default();
+
+        let mut state = self.state.write().await;
+        let acked_count = acked.len();
+        if state.outbox.enqueue(
+            event.clone(),
+            relays,
+            acked,
+            self.config.result_min_acks,
+            Timestamp::now().as_u64(),
+        ) {
+            warn!(
+                event_id = %event.id,
+                acked = acked_count,
+                "Result reached too few relays, keeping it in the outbox"
+            );
+            if let Err(e) = state.outbox.save() {
+                warn!(error = %e, "Failed to save outbox");
+            }
+        }
+        Ok(event.id)
+    }
+
+    /// Send an already signed event to `relay_urls`, returning the relays
+    /// that accepted it (none when all failed).
+    pub async fn resend(&self, event: &Event, relay_urls: &[String]) -> Vec<String> {
+        self.send_signed(event, relay_urls).await.unwrap_or_default()
+    }
+
+    /// Send a NIP-17 direct message to DVM config relays + `job_relays`.
+    ///
+    /// Used for messages meant only for one user, like payment receipts or
+    /// alerts to the admin (with no job relays).
+    pub async fn send_private_msg(
+        &self,
+        receiver: PublicKey,
+        message: String,
+        job_relays: &[::url::Url],
+    ) -> Result<EventId, DvmError> {
+        let relays = self.job_relay_urls(job_relays).await;
+        if relays.is_empty() {
+            warn!(receiver = %receiver, "No relays configured, direct message not sent");
+            return Err(DvmError::JobRejected("No relays configured".to_string()));
+        }
+        for url in &relays {
+            let _ = self.client.add_relay(url.as_str()).await;
+        }
+        self.client.connect().await;
+        let output = self
+            .client
+            .send_private_msg_to(relays.iter().map(|s| s.as_str()), receiver, message, None)
+            .await?;
+        Ok(output.val)
+    }
+
+    /// DVM config relays plus `job_relays` not already among them, leaving
+    /// out job relays that refused our events.
+    async fn job_relay_urls(&self, job_relays: &[::url::Url]) -> Vec<String> {
+        let refused = self.refused_relays(job_relays).await;
         let mut relays = self.dvm_relay_urls().await;
         for r in job_relays {
             let s = r.as_str().trim_end_matches('/').to_string();
             if !relays
                 .iter()
                 .any(|existing| existing.trim_end_matches('/') == s)
+                && !refused.iter().any(|(url, _)| *url == s)
             {
                 relays.push(r.to_string());
             }
         }
-        self.send_to(builder, &relays).await
+        relays
+    }
+
+    /// Job relays that recently refused the DVM's events, and why.
+    pub async fn refused_relays(&self, job_relays: &[::url::Url]) -> Vec<(String, RelayRefusal)> {
+        let state = self.state.read().await;
+        job_relays
+            .iter()
+            .map(|r| r.as_str().trim_end_matches('/').to_string())
+            .filter(|url| !state.config.relays.iter().any(|own| own.trim_end_matches('/') == url))
+            .filter_map(|url| state.relay_stats.refusal(&url).map(|refusal| (url, refusal)))
+            .collect()
+    }
+
+    fn sign(&self, builder: EventBuilder) -> Result<Event, DvmError> {
+        builder
+            .to_event(&self.config.nostr_keys)
+            .map_err(|e| DvmError::JobRejected(format!("Failed to sign event: {}", e)))
     }
 
     /// Send an event to specific relay URLs with retries.
@@ -91,16 +224,24 @@ impl EventPublisher {
         builder: EventBuilder,
         relay_urls: &[String],
     ) -> Result<EventId, DvmError> {
-        let event = builder
-            .to_event(&self.config.nostr_keys)
-            .map_err(|e| DvmError::JobRejected(format!("Failed to sign event: {}", e)))?;
+        let event = self.sign(builder)?;
+        self.send_signed(&event, relay_urls).await?;
+        Ok(event.id)
+    }
 
+    /// Send a signed event to specific relay URLs, retrying while no relay
+    /// accepts it. Returns the relays that did.
+    async fn send_signed(
+        &self,
+        event: &Event,
+        relay_urls: &[String],
+    ) -> Result<Vec<String>, DvmError> {
         let event_id = event.id;
         let event_kind = event.kind;
 
         if relay_urls.is_empty() {
             warn!(event_id = %event_id, kind = %event_kind, "No relays configured, event not sent");
-            return Ok(event_id);
+            return Ok(Vec::new());
         }
 
         // Ensure all relay URLs are in the client pool before sending
@@ -115,48 +256,73 @@ impl EventPublisher {
         }
 
         for attempt in 1..=MAX_RETRIES {
-            let start = std::time::Instant::now();
-            match self
-                .client
-                .send_event_to(relay_urls.iter().map(|s| s.as_str()), event.clone())
-                .await
+            let start = Instant::now();
+
+            // One send per relay so each relay's latency can be measured
+            let sends = relay_urls.iter().map(|url| {
+                let event = event.clone();
+                async move {
+                    let sent = Instant::now();
+                    let result = self.client.send_event_to([url.as_str()], event).await;
+                    (url, sent.elapsed(), result)
+                }
+            });
+            let results = join_all(sends).await;
+            let elapsed = start.elapsed();
+
+            let mut success = Vec::new();
+            let mut last_error = None;
             {
-                Ok(output) => {
-                    let elapsed = start.elapsed();
+                let mut state = self.state.write().await;
+                for (url, latency, result) in results {
+                    match result {
+                        Ok(_) => {
+                            state.relay_stats.record_published(url, latency);
+                            success.push(url.clone());
+                        }
+                        Err(e) => {
+                            // Log failed relays at warn level for easy identification
+                            warn!(
+                                relay = %url,
+                                error = %e,
+                                kind = %event_kind,
+                                "Relay publish failed"
+                            );
+                            match RelayRefusal::classify(&e.to_string()) {
+                                Some(refusal) => state.relay_stats.record_refused(url, refusal),
+                                None => state.relay_stats.record_failed(url),
+                            }
+                            last_error = Some(e);
+                        }
+                    }
+                }
+            }
+
+            match last_error.filter(|_| success.is_empty()) {
+                None => {
                     debug!(
                         event_id = %event_id,
                         kind = %event_kind,
-                        success = ?output.success.iter().map(|u| u.to_string()).collect::<Vec<_>>(),
-                        failed = ?output.failed.iter().map(|(u, _)| u.to_string()).collect::<Vec<_>>(),
-                        success_count = output.success.len(),
-                        failed_count = output.failed.len(),
+                        success = ?success,
+                        success_count = success.len(),
+                        failed_count = relay_urls.len() - success.len(),
                         elapsed_ms = elapsed.as_millis(),
                         "Event published"
                     );
 
-                    // Log slow/failed relays at warn level for easy identification
-                    for (url, err) in &output.failed {
-                        warn!(
-                            relay = %url,
-                            error = ?err,
-                            kind = %event_kind,
-                            "Relay publish failed"
-                        );
-                    }
-
                     if elapsed.as_secs() >= 5 {
                         warn!(
                             event_id = %event_id,
                             kind = %event_kind,
                             elapsed_ms = elapsed.as_millis(),
                             relay_count = relay_urls.len(),
-                            "Slow publish: send_event_to took >= 5s (a slow relay may be blocking)"
+                            "Slow publish: a relay took >= 5s to answer"
                         );
                     }
 
-                    return Ok(event_id);
+                    return Ok(success);
                 }
-                Err(e) => {
+                Some(e) => {
                     if attempt < MAX_RETRIES {
                         warn!(
                             event_id = %event_id,
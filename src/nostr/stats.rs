@@ -0,0 +1,206 @@
+//! Per-relay traffic counters.
+//!
+//! Counts events received from and published to each relay, publish
+//! failures, and publish latency, so operators can spot dead or slow relays
+//! and prune them from the config. Relays that refuse the DVM's events
+//! because they want AUTH or payment are remembered for a while, so job
+//! events skip them. Counters live in memory and reset on restart.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How long a relay that refused an event is skipped for job events
+pub const REFUSAL_TTL: Duration = Duration::from_secs(3600);
+
+/// Why a relay refused to store an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayRefusal {
+    /// NIP-42 authentication required (or the authenticated key isn't allowed)
+    AuthRequired,
+    /// Paid relay
+    PaymentRequired,
+    /// Writes limited to members or blocked for the DVM's key
+    Restricted,
+}
+
+impl RelayRefusal {
+    /// Classify a publish error by the machine-readable prefix of the
+    /// relay's `OK` message. `None` for errors worth retrying.
+    pub fn classify(error: &str) -> Option<Self> {
+        let error = error.to_lowercase();
+        if error.contains("auth-required") {
+            Some(Self::AuthRequired)
+        } else if error.contains("payment") || error.contains("paid") || error.contains("pay ") {
+            Some(Self::PaymentRequired)
+        } else if error.contains("restricted:") || error.contains("blocked:") {
+            Some(Self::Restricted)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AuthRequired => "auth-required",
+            Self::PaymentRequired => "payment-required",
+            Self::Restricted => "restricted",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct RelayCounters {
+    received: u64,
+    published: u64,
+    failed: u64,
+    publish_latency_ms: u64,
+    /// Latest refusal and when it happened
+    refused: Option<(RelayRefusal, Instant)>,
+}
+
+/// Traffic statistics for one relay
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RelayStatsEntry {
+    pub url: String,
+    /// Events received from this relay (before deduplication)
+    pub events_received: u64,
+    /// Events this relay accepted
+    pub events_published: u64,
+    /// Publish attempts this relay rejected or didn't answer
+    pub publish_failures: u64,
+    /// Mean time for this relay to accept an event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avg_publish_latency_ms: Option<u64>,
+    /// Why the relay recently refused an event (`auth-required`,
+    /// `payment-required` or `restricted`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refused: Option<String>,
+}
+
+/// Counters for every relay the DVM has talked to, keyed by URL
+#[derive(Debug, Default)]
+pub struct RelayStats {
+    relays: HashMap<String, RelayCounters>,
+}
+
+impl RelayStats {
+    fn counters(&mut self, url: &str) -> &mut RelayCounters {
+        // Pool URLs carry a trailing slash that configured relays usually don't
+        self.relays
+            .entry(url.trim_end_matches('/').to_string())
+            .or_default()
+    }
+
+    pub fn record_received(&mut self, url: &str) {
+        self.counters(url).received += 1;
+    }
+
+    pub fn record_published(&mut self, url: &str, latency: Duration) {
+        let counters = self.counters(url);
+        counters.published += 1;
+        counters.publish_latency_ms += latency.as_millis() as u64;
+    }
+
+    pub fn record_failed(&mut self, url: &str) {
+        self.counters(url).failed += 1;
+    }
+
+    /// Record a failed publish the relay refused outright
+    pub fn record_refused(&mut self, url: &str, refusal: RelayRefusal) {
+        let counters = self.counters(url);
+        counters.failed += 1;
+        counters.refused = Some((refusal, Instant::now()));
+    }
+
+    /// Why `url` refused an event within the last [`REFUSAL_TTL`]
+    pub fn refusal(&self, url: &str) -> Option<RelayRefusal> {
+        let (refusal, at) = self.relays.get(url.trim_end_matches('/'))?.refused?;
+        (at.elapsed() < REFUSAL_TTL).then_some(refusal)
+    }
+
+    /// Statistics for every relay, sorted by URL
+    pub fn entries(&self) -> Vec<RelayStatsEntry> {
+        let mut entries: Vec<_> = self
+            .relays
+            .iter()
+            .map(|(url, c)| RelayStatsEntry {
+                url: url.clone(),
+                events_received: c.received,
+                events_published: c.published,
+                publish_failures: c.failed,
+                avg_publish_latency_ms: (c.published > 0).then(|| c.publish_latency_ms / c.published),
+                refused: c
+                    .refused
+                    .filter(|(_, at)| at.elapsed() < REFUSAL_TTL)
+                    .map(|(r, _)| r.as_str().to_string()),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.url.cmp(&b.url));
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relay_stats() {
+        let mut stats = RelayStats::default();
+        stats.record_received("wss://nos.lol/");
+        stats.record_received("wss://nos.lol");
+        stats.record_published("wss://nos.lol", Duration::from_millis(100));
+        stats.record_published("wss://nos.lol", Duration::from_millis(300));
+        stats.record_failed("wss://dead.example");
+
+        assert_eq!(
+            stats.entries(),
+            vec![
+                RelayStatsEntry {
+                    url: "wss://dead.example".to_string(),
+                    events_received: 0,
+                    events_published: 0,
+                    publish_failures: 1,
+                    avg_publish_latency_ms: None,
+                    refused: None,
+                },
+                RelayStatsEntry {
+                    url: "wss://nos.lol".to_string(),
+                    events_received: 2,
+                    events_published: 2,
+                    publish_failures: 0,
+                    avg_publish_latency_ms: Some(200),
+                    refused: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_refusals() {
+        assert_eq!(
+            RelayRefusal::classify("event not published: auth-required: we only accept events from registered users"),
+            Some(RelayRefusal::AuthRequired)
+        );
+        assert_eq!(
+            RelayRefusal::classify("restricted: pay 2000 sats at https://relay.example to publish"),
+            Some(RelayRefusal::PaymentRequired)
+        );
+        assert_eq!(
+            RelayRefusal::classify("blocked: pubkey not on whitelist"),
+            Some(RelayRefusal::Restricted)
+        );
+        assert_eq!(RelayRefusal::classify("timeout"), None);
+        assert_eq!(RelayRefusal::classify("rate-limited: slow down"), None);
+
+        let mut stats = RelayStats::default();
+        stats.record_refused("wss://paid.example/", RelayRefusal::PaymentRequired);
+        stats.record_failed("wss://dead.example");
+        assert_eq!(stats.refusal("wss://paid.example"), Some(RelayRefusal::PaymentRequired));
+        assert_eq!(stats.refusal("wss://dead.example"), None);
+        let entries = stats.entries();
+        assert_eq!(entries[1].refused.as_deref(), Some("payment-required"));
+        assert_eq!(entries[1].publish_failures, 1);
+    }
+}
@@ -13,8 +13,28 @@ pub struct Paths {
     pub env_file: PathBuf,
     /// Identity keypair: $data_dir/identity.key
     pub identity_file: PathBuf,
+    /// Result retention ledger: $data_dir/retention.json
+    pub retention_file: PathBuf,
+    /// Result blobs per job, with admin pins: $data_dir/references.json
+    pub references_file: PathBuf,
+    /// Abuse reports and quarantined results: $data_dir/quarantine.json
+    pub quarantine_file: PathBuf,
+    /// Local copies of quarantined blobs, kept for restoring: $data_dir/quarantine
+    pub quarantine_dir: PathBuf,
+    /// Job inputs fetched by admins for offline analysis: $data_dir/inputs
+    pub inputs_dir: PathBuf,
+    /// Signed receipts for paid jobs, one per line: $data_dir/receipts.jsonl
+    pub receipts_file: PathBuf,
+    /// Result events short of relay acknowledgements: $data_dir/outbox.json
+    pub outbox_file: PathBuf,
+    /// Cashu wallet holding redeemed payments: $data_dir/cashu_wallet.json
+    pub cashu_wallet_file: PathBuf,
     /// PID file for foreground/fallback process tracking
     pub pid_file: PathBuf,
+    /// Job loop heartbeat for external watchdogs: $data_dir/heartbeat.json
+    pub heartbeat_file: PathBuf,
+    /// Counters and waiting jobs saved at shutdown: $data_dir/shutdown.json
+    pub snapshot_file: PathBuf,
     /// Log directory: $data_dir/logs
     pub log_dir: PathBuf,
     /// stdout log (launchd/manual): $data_dir/logs/stdout.log
@@ -31,6 +51,8 @@ pub struct Paths {
     pub launchd_plist: PathBuf,
     /// SysV init script: $data_dir/nostube-transcode.initd
     pub sysv_script: PathBuf,
+    /// Windows Task Scheduler definition: $data_dir/nostube-transcode.task.xml
+    pub windows_task_xml: PathBuf,
 }
 
 impl Paths {
@@ -44,14 +66,25 @@ impl Paths {
             .unwrap_or_else(|_| home.join(".config"));
 
         let install_dir = home.join(".local").join("bin");
-        let binary_path = install_dir.join("nostube-transcode");
+        let binary_path =
+            install_dir.join(format!("nostube-transcode{}", std::env::consts::EXE_SUFFIX));
 
         let log_dir = data_dir.join("logs");
 
         Self {
             env_file: data_dir.join("env"),
             identity_file: data_dir.join("identity.key"),
+            retention_file: data_dir.join("retention.json"),
+            references_file: data_dir.join("references.json"),
+            cashu_wallet_file: data_dir.join("cashu_wallet.json"),
+            receipts_file: data_dir.join("receipts.jsonl"),
+            outbox_file: data_dir.join("outbox.json"),
+            quarantine_file: data_dir.join("quarantine.json"),
+            quarantine_dir: data_dir.join("quarantine"),
+            inputs_dir: data_dir.join("inputs"),
             pid_file: data_dir.join("nostube-transcode.pid"),
+            heartbeat_file: data_dir.join("heartbeat.json"),
+            snapshot_file: data_dir.join("shutdown.json"),
             stdout_log: log_dir.join("stdout.log"),
             stderr_log: log_dir.join("stderr.log"),
             log_dir,
@@ -66,6 +99,7 @@ impl Paths {
                 .join("LaunchAgents")
                 .join("com.nostube.transcode.plist"),
             sysv_script: data_dir.join("nostube-transcode.initd"),
+            windows_task_xml: data_dir.join("nostube-transcode.task.xml"),
             data_dir,
         }
     }
@@ -84,6 +118,15 @@ mod tests {
         assert_eq!(p.env_file, PathBuf::from("/tmp/test-nostube/env"));
         assert_eq!(p.identity_file, PathBuf::from("/tmp/test-nostube/identity.key"));
         assert_eq!(p.pid_file, PathBuf::from("/tmp/test-nostube/nostube-transcode.pid"));
+        assert_eq!(p.heartbeat_file, PathBuf::from("/tmp/test-nostube/heartbeat.json"));
+        assert_eq!(p.snapshot_file, PathBuf::from("/tmp/test-nostube/shutdown.json"));
+        assert_eq!(p.references_file, PathBuf::from("/tmp/test-nostube/references.json"));
+        assert_eq!(p.quarantine_file, PathBuf::from("/tmp/test-nostube/quarantine.json"));
+        assert_eq!(p.quarantine_dir, PathBuf::from("/tmp/test-nostube/quarantine"));
+        assert_eq!(p.inputs_dir, PathBuf::from("/tmp/test-nostube/inputs"));
+        assert_eq!(p.receipts_file, PathBuf::from("/tmp/test-nostube/receipts.jsonl"));
+        assert_eq!(p.outbox_file, PathBuf::from("/tmp/test-nostube/outbox.json"));
+        assert_eq!(p.cashu_wallet_file, PathBuf::from("/tmp/test-nostube/cashu_wallet.json"));
         assert_eq!(p.log_dir, PathBuf::from("/tmp/test-nostube/logs"));
         env::remove_var("DATA_DIR");
     }
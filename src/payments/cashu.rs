@@ -0,0 +1,301 @@
+//! Cashu wallet for redeeming payment tokens.
+//!
+//! Tokens attached to job requests are swapped at the mint before work
+//! starts, so spent or forged proofs are rejected and the requester can't
+//! reuse them. Redeemed ecash stays in a wallet in the data directory; each
+//! redemption is a wallet transaction tagged with its job id. With
+//! `CASHU_MELT_ADDRESS` set, the balance is paid out to that Lightning
+//! address once it reaches `CASHU_MELT_THRESHOLD_SATS`.
+
+use cdk::amount::Amount;
+use cdk::nuts::{CurrencyUnit, Token};
+use cdk::wallet::types::TransactionDirection;
+use cdk::wallet::{ReceiveOptions, Wallet};
+use nostr_sdk::{Keys, Timestamp};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use super::lnurl::LnurlBackend;
+use super::wallet_store::JsonWalletStore;
+use crate::error::PaymentError;
+
+/// Mint whose tokens are accepted as payment
+pub const CASHU_MINT_URL: &str = "https://mint.bitonic.nl";
+
+/// Balance at which redeemed ecash is melted when no threshold is configured
+const DEFAULT_MELT_THRESHOLD_SATS: u64 = 1000;
+
+/// Transaction metadata key holding the job a redemption paid for
+const JOB_ID_KEY: &str = "job_id";
+
+/// Where and when to pay out redeemed ecash over Lightning
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeltConfig {
+    /// Lightning address or LNURL-pay endpoint receiving the payout
+    pub address: String,
+    /// Balance that triggers a payout
+    pub threshold_sats: u64,
+}
+
+impl MeltConfig {
+    /// Read `CASHU_MELT_ADDRESS` and `CASHU_MELT_THRESHOLD_SATS` (`None` = keep the ecash)
+    pub fn from_env() -> Option<Self> {
+        let address = std::env::var("CASHU_MELT_ADDRESS")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())?;
+        let threshold_sats = std::env::var("CASHU_MELT_THRESHOLD_SATS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|sats| *sats > 0)
+            .unwrap_or(DEFAULT_MELT_THRESHOLD_SATS);
+        Some(Self {
+            address,
+            threshold_sats,
+        })
+    }
+}
+
+/// Outcome of redeeming a job's token
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Redemption {
+    pub job_id: String,
+    /// Sats added to the wallet
+    pub amount_sats: u64,
+    /// Sats kept by the mint for the swap
+    pub fee_sats: u64,
+    /// Unix time of the redemption
+    pub redeemed_at: u64,
+}
+
+/// Check a token's mint and amount without contacting the mint
+pub fn check_token(token_str: &str, required_sats: u64, expected_mint: &str) -> Result<(), String> {
+    let token = Token::from_str(token_str).map_err(|e| format!("Invalid Cashu token: {}", e))?;
+
+    let mut total_amount = Amount::ZERO;
+
+    match token {
+        Token::TokenV3(v3) => {
+            for token_proofs in &v3.token {
+                if token_proofs.mint.to_string() != expected_mint {
+                    return Err(format!("Unexpected mint in V3: {} (expected {})", token_proofs.mint, expected_mint));
+                }
+                for proof in &token_proofs.proofs {
+                    total_amount += proof.amount;
+                }
+            }
+        }
+        Token::TokenV4(v4) => {
+            if v4.mint_url.to_string() != expected_mint {
+                return Err(format!("Unexpected mint in V4: {} (expected {})", v4.mint_url, expected_mint));
+            }
+            for token_v4 in &v4.token {
+                for proof in &token_v4.proofs {
+                    total_amount += proof.amount;
+                }
+            }
+        }
+    }
+
+    if total_amount < Amount::from(required_sats) {
+        return Err(format!("Insufficient amount: {} (required {})", total_amount, required_sats));
+    }
+
+    Ok(())
+}
+
+/// Wallet seed derived from the DVM's identity, so the wallet can be
+/// restored from the identity key alone
+fn wallet_seed(keys: &Keys) -> [u8; 64] {
+    let mut hasher = Sha512::new();
+    hasher.update(b"nostube-transcode cashu wallet");
+    hasher.update(keys.secret_key().secret_bytes());
+    hasher.finalize().into()
+}
+
+pub struct CashuWallet {
+    wallet: Wallet,
+    melt: Option<(MeltConfig, LnurlBackend)>,
+    /// One payout at a time, so concurrent jobs don't spend the same proofs
+    melting: Mutex<()>,
+}
+
+impl CashuWallet {
+    /// Open the wallet for `mint_url`, stored at `path`
+    pub fn open(
+        path: PathBuf,
+        mint_url: &str,
+        keys: &Keys,
+        melt: Option<MeltConfig>,
+        http: reqwest::Client,
+    ) -> Result<Self, PaymentError> {
+        let store = Arc::new(JsonWalletStore::open(path)?);
+        let wallet = Wallet::new(mint_url, CurrencyUnit::Sat, store, wallet_seed(keys), None)?;
+        Ok(Self {
+            wallet,
+            melt: melt.map(|m| {
+                let lnurl = LnurlBackend::new(http, m.address.clone());
+                (m, lnurl)
+            }),
+            melting: Mutex::new(()),
+        })
+    }
+
+    /// Swap the token's proofs at the mint, returning the redemption the
+    /// wallet recorded for `job_id`.
+    ///
+    /// Fails when the proofs are already spent or the mint rejects them.
+    pub async fn redeem(&self, token: &str, job_id: &str) -> Result<Redemption, PaymentError> {
+        let options = ReceiveOptions {
+            metadata: HashMap::from([(JOB_ID_KEY.to_string(), job_id.to_string())]),
+            ..Default::default()
+        };
+        let amount = self.wallet.receive(token, options).await?;
+        Ok(self.redemption(job_id).await?.unwrap_or_else(|| Redemption {
+            job_id: job_id.to_string(),
+            amount_sats: amount.into(),
+            fee_sats: 0,
+            redeemed_at: Timestamp::now().as_u64(),
+        }))
+    }
+
+    /// The redemption recorded for `job_id`, if its token was redeemed
+    pub async fn redemption(&self, job_id: &str) -> Result<Option<Redemption>, PaymentError> {
+        let transactions = self
+            .wallet
+            .list_transactions(Some(TransactionDirection::Incoming))
+            .await?;
+        Ok(transactions
+            .into_iter()
+            .find(|t| t.metadata.get(JOB_ID_KEY).map(String::as_str) == Some(job_id))
+            .map(|t| Redemption {
+                job_id: job_id.to_string(),
+                amount_sats: t.amount.into(),
+                fee_sats: t.fee.into(),
+                redeemed_at: t.timestamp,
+            }))
+    }
+
+    /// Ecash held by the wallet, in sats
+    pub async fn balance(&self) -> Result<u64, PaymentError> {
+        Ok(self.wallet.total_balance().await?.into())
+    }
+
+    /// Pay the balance out to the melt address once it reaches the threshold.
+    ///
+    /// Returns the sats paid out, or `None` when melting is disabled or the
+    /// balance is still below the threshold.
+    pub async fn melt_if_due(&self) -> Result<Option<u64>, PaymentError> {
+        let Some((config, lnurl)) = &self.melt else {
+            return Ok(None);
+        };
+        let _guard = self.melting.lock().await;
+
+        let balance = self.balance().await?;
+        if balance < config.threshold_sats {
+            return Ok(None);
+        }
+
+        // Leave room for the mint's Lightning fee reserve
+        let reserve = (balance / 50).max(4);
+        let amount = balance.saturating_sub(reserve);
+        let bolt11 = lnurl
+            .invoice(amount * 1000, "nostube-transcode Cashu payout")
+            .await?;
+        let quote = self.wallet.melt_quote(bolt11, None).await?;
+        let needed = u64::from(quote.amount) + u64::from(quote.fee_reserve);
+        if needed > balance {
+            return Err(PaymentError::Backend(format!(
+                "Payout of {} sats needs {} sats with fees, wallet holds {}",
+                amount, needed, balance
+            )));
+        }
+
+        let melted = self.wallet.melt(&quote.id).await?;
+        info!(
+            sats = u64::from(melted.amount),
+            fee = u64::from(melted.fee_paid),
+            address = %config.address,
+            "Paid out Cashu balance"
+        );
+        Ok(Some(melted.amount.into()))
+    }
+
+    /// Run [`Self::melt_if_due`] in the background, logging failures
+    pub fn spawn_melt(self: &Arc<Self>) {
+        if self.melt.is_none() {
+            return;
+        }
+        let wallet = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = wallet.melt_if_due().await {
+                warn!(error = %e, "Cashu payout failed, will retry after the next payment");
+            }
+        });
+    }
+}
+
+/// A token from `mint` with proofs of `amounts`, valid to [`check_token`]
+/// but unknown to any mint
+#[cfg(test)]
+pub(crate) fn test_token(mint: &str, amounts: &[u64]) -> String {
+    use cdk::mint_url::MintUrl;
+    use cdk::nuts::{Id, Proof, PublicKey};
+    use cdk::secret::Secret;
+
+    let id = Id::from_str("009a1f293253e41e").unwrap();
+    let c = PublicKey::from_hex(
+        "02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104",
+    )
+    .unwrap();
+    let proofs = amounts
+        .iter()
+        .map(|a| Proof::new(Amount::from(*a), id, Secret::generate(), c))
+        .collect();
+    Token::new(MintUrl::from_str(mint).unwrap(), proofs, None, CurrencyUnit::Sat).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_token() {
+        assert!(check_token(&test_token(CASHU_MINT_URL, &[8, 2]), 10, CASHU_MINT_URL).is_ok());
+
+        let err = check_token(&test_token(CASHU_MINT_URL, &[8]), 10, CASHU_MINT_URL).unwrap_err();
+        assert!(err.contains("Insufficient amount"));
+        let err = check_token(&test_token("https://other.example.com", &[16]), 10, CASHU_MINT_URL).unwrap_err();
+        assert!(err.contains("Unexpected mint"));
+        assert!(check_token("cashuBnope", 10, CASHU_MINT_URL).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_wallet_without_melt_address() {
+        let dir = tempfile::tempdir().unwrap();
+        let wallet = CashuWallet::open(
+            dir.path().join("cashu_wallet.json"),
+            CASHU_MINT_URL,
+            &Keys::generate(),
+            None,
+            reqwest::Client::new(),
+        )
+        .unwrap();
+        assert_eq!(wallet.balance().await.unwrap(), 0);
+        assert_eq!(wallet.redemption("job1").await.unwrap(), None);
+        assert_eq!(wallet.melt_if_due().await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_wallet_seed_is_stable() {
+        let keys = Keys::generate();
+        assert_eq!(wallet_seed(&keys), wallet_seed(&keys));
+        assert_ne!(wallet_seed(&keys), wallet_seed(&Keys::generate()));
+    }
+}
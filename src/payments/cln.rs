@@ -0,0 +1,109 @@
+//! Core Lightning `clnrest` backend.
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+
+use super::{base_url, now, Invoice, LightningBackend, INVOICE_EXPIRY};
+use crate::error::PaymentError;
+
+pub struct ClnBackend {
+    http: reqwest::Client,
+    url: String,
+    rune: String,
+}
+
+#[derive(Deserialize)]
+struct InvoiceResponse {
+    bolt11: String,
+    payment_hash: String,
+    expires_at: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ListInvoicesResponse {
+    invoices: Vec<ListedInvoice>,
+}
+
+#[derive(Deserialize)]
+struct ListedInvoice {
+    status: String,
+}
+
+impl ClnBackend {
+    pub fn new(http: reqwest::Client, url: String, rune: String) -> Self {
+        Self {
+            http,
+            url: base_url(&url),
+            rune,
+        }
+    }
+
+    async fn call<T: for<'de> Deserialize<'de>>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<T, PaymentError> {
+        Ok(self
+            .http
+            .post(format!("{}/v1/{}", self.url, method))
+            .header("Rune", &self.rune)
+            .json(&params)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    async fn create(&self, amount_msats: u64, description: &str) -> Result<Invoice, PaymentError> {
+        // Labels must be unique per node
+        let label = format!("nostube-{}", uuid::Uuid::new_v4());
+        let resp: InvoiceResponse = self
+            .call(
+                "invoice",
+                serde_json::json!({
+                    "amount_msat": amount_msats,
+                    "label": label,
+                    "description": description,
+                    "expiry": INVOICE_EXPIRY.as_secs(),
+                }),
+            )
+            .await?;
+        Ok(Invoice {
+            bolt11: resp.bolt11,
+            amount_msats,
+            expires_at: resp
+                .expires_at
+                .unwrap_or_else(|| now() + INVOICE_EXPIRY.as_secs()),
+            lookup: resp.payment_hash,
+        })
+    }
+
+    async fn paid(&self, invoice: &Invoice) -> Result<bool, PaymentError> {
+        let resp: ListInvoicesResponse = self
+            .call(
+                "listinvoices",
+                serde_json::json!({ "payment_hash": invoice.lookup }),
+            )
+            .await?;
+        Ok(resp.invoices.iter().any(|i| i.status == "paid"))
+    }
+}
+
+impl LightningBackend for ClnBackend {
+    fn name(&self) -> &'static str {
+        "cln"
+    }
+
+    fn create_invoice<'a>(
+        &'a self,
+        amount_msats: u64,
+        description: &'a str,
+    ) -> BoxFuture<'a, Result<Invoice, PaymentError>> {
+        Box::pin(self.create(amount_msats, description))
+    }
+
+    fn is_paid<'a>(&'a self, invoice: &'a Invoice) -> BoxFuture<'a, Result<bool, PaymentError>> {
+        Box::pin(self.paid(invoice))
+    }
+}
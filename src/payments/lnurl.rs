@@ -0,0 +1,205 @@
+//! LNURL-pay backend (LUD-06, LUD-16 Lightning addresses, LUD-21 verify).
+//!
+//! Lets operators without their own node take payments through a custodial
+//! or hosted wallet. Payment detection relies on the LUD-21 `verify` URL
+//! returned with each invoice, so the wallet's server must support it.
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+
+use super::{now, Invoice, LightningBackend, INVOICE_EXPIRY};
+use crate::error::PaymentError;
+
+pub struct LnurlBackend {
+    http: reqwest::Client,
+    /// `user@domain` or an `https://` LNURL-pay endpoint
+    address: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PayParams {
+    callback: String,
+    min_sendable: u64,
+    max_sendable: u64,
+    #[serde(default)]
+    comment_allowed: u64,
+}
+
+#[derive(Deserialize)]
+struct PayResponse {
+    pr: String,
+    verify: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct VerifyResponse {
+    #[serde(default)]
+    settled: bool,
+}
+
+impl LnurlBackend {
+    pub fn new(http: reqwest::Client, address: String) -> Self {
+        Self { http, address }
+    }
+
+    /// LNURL-pay endpoint of the configured address
+    fn endpoint(&self) -> Result<String, PaymentError> {
+        if self.address.starts_with("https://") || self.address.starts_with("http://") {
+            return Ok(self.address.clone());
+        }
+        match self.address.split_once('@') {
+            Some((user, domain)) if !user.is_empty() && !domain.is_empty() => {
+                Ok(format!("https://{}/.well-known/lnurlp/{}", domain, user))
+            }
+            _ => Err(PaymentError::Backend(format!(
+                "Invalid Lightning address: {}",
+                self.address
+            ))),
+        }
+    }
+
+    /// Request an invoice over `amount_msats` from the LNURL server
+    async fn request(&self, amount_msats: u64, description: &str) -> Result<PayResponse, PaymentError> {
+        let params: PayParams = self
+            .http
+            .get(self.endpoint()?)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if !(params.min_sendable..=params.max_sendable).contains(&amount_msats) {
+            return Err(PaymentError::Backend(format!(
+                "{} msats is outside what the LNURL server accepts ({}-{})",
+                amount_msats, params.min_sendable, params.max_sendable
+            )));
+        }
+
+        let mut query = vec![("amount", amount_msats.to_string())];
+        if params.comment_allowed > 0 {
+            let comment: String = description.chars().take(params.comment_allowed as usize).collect();
+            query.push(("comment", comment));
+        }
+        Ok(self
+            .http
+            .get(&params.callback)
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    /// A bolt11 invoice paying `amount_msats` to the address.
+    ///
+    /// Unlike [`LightningBackend::create_invoice`] this doesn't need LUD-21,
+    /// as the caller is the one paying.
+    pub async fn invoice(&self, amount_msats: u64, description: &str) -> Result<String, PaymentError> {
+        Ok(self.request(amount_msats, description).await?.pr)
+    }
+
+    async fn create(&self, amount_msats: u64, description: &str) -> Result<Invoice, PaymentError> {
+        let resp = self.request(amount_msats, description).await?;
+        let verify = resp.verify.ok_or_else(|| {
+            PaymentError::Backend("LNURL server doesn't support payment verification (LUD-21)".into())
+        })?;
+
+        Ok(Invoice {
+            bolt11: resp.pr,
+            amount_msats,
+            expires_at: now() + INVOICE_EXPIRY.as_secs(),
+            lookup: verify,
+        })
+    }
+
+    async fn settled(&self, invoice: &Invoice) -> Result<bool, PaymentError> {
+        let resp: VerifyResponse = self
+            .http
+            .get(&invoice.lookup)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(resp.settled)
+    }
+}
+
+impl LightningBackend for LnurlBackend {
+    fn name(&self) -> &'static str {
+        "lnurl"
+    }
+
+    fn create_invoice<'a>(
+        &'a self,
+        amount_msats: u64,
+        description: &'a str,
+    ) -> BoxFuture<'a, Result<Invoice, PaymentError>> {
+        Box::pin(self.create(amount_msats, description))
+    }
+
+    fn is_paid<'a>(&'a self, invoice: &'a Invoice) -> BoxFuture<'a, Result<bool, PaymentError>> {
+        Box::pin(self.settled(invoice))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_lightning_address_endpoint() {
+        let backend = LnurlBackend::new(reqwest::Client::new(), "dvm@example.com".into());
+        assert_eq!(
+            backend.endpoint().unwrap(),
+            "https://example.com/.well-known/lnurlp/dvm"
+        );
+        assert!(LnurlBackend::new(reqwest::Client::new(), "nope".into())
+            .endpoint()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lnurl_invoice_and_verify() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/lnurlp"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "tag": "payRequest",
+                "callback": format!("{}/callback", server.uri()),
+                "minSendable": 1000,
+                "maxSendable": 100_000_000,
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/callback"))
+            .and(query_param("amount", "21000"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "pr": "lnbc210n1test",
+                "verify": format!("{}/verify/1", server.uri()),
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/verify/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "status": "OK",
+                "settled": false,
+            })))
+            .mount(&server)
+            .await;
+
+        let backend = LnurlBackend::new(reqwest::Client::new(), format!("{}/lnurlp", server.uri()));
+        let invoice = backend.create_invoice(21_000, "job").await.unwrap();
+        assert_eq!(invoice.bolt11, "lnbc210n1test");
+        assert!(!backend.is_paid(&invoice).await.unwrap());
+
+        // Below the server's minimum
+        assert!(backend.create_invoice(10, "job").await.is_err());
+    }
+}
@@ -0,0 +1,181 @@
+//! Lightning payments for jobs.
+//!
+//! When the operator sets a price (`price_sats_per_minute` in the remote
+//! config) and configures a Lightning backend, directed requests without a
+//! Cashu token get a `payment-required` status carrying a bolt11 invoice
+//! (NIP-90 `amount` tag). The invoice is polled in the background and the
+//! job is queued again once it is paid.
+//!
+//! Backends are selected with `LIGHTNING_BACKEND`:
+//!
+//! - `lnd`: LND REST API (`LND_REST_URL`, `LND_MACAROON` as hex)
+//! - `cln`: Core Lightning `clnrest` (`CLN_REST_URL`, `CLN_RUNE`)
+//! - `lnurl`: a Lightning address or LNURL-pay endpoint (`LNURL_ADDRESS`)
+//!   whose server supports LUD-21 payment verification
+//!
+//! Cashu tokens are redeemed at the mint by [`cashu::CashuWallet`].
+
+pub mod cashu;
+pub mod cln;
+pub mod lnd;
+pub mod lnurl;
+pub mod wallet_store;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::error::PaymentError;
+
+/// How long invoices stay payable
+pub const INVOICE_EXPIRY: Duration = Duration::from_secs(600);
+
+/// How often an open invoice is checked for payment
+pub const PAYMENT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// An invoice issued for a job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Invoice {
+    pub bolt11: String,
+    pub amount_msats: u64,
+    /// Unix time after which the invoice can no longer be paid
+    pub expires_at: u64,
+    /// Backend-specific handle for checking payment (payment hash or
+    /// LUD-21 verify URL)
+    pub lookup: String,
+}
+
+/// Issues invoices and reports whether they were paid.
+pub trait LightningBackend: Send + Sync {
+    /// Short identifier used in logs (e.g. "lnd")
+    fn name(&self) -> &'static str;
+
+    /// Create an invoice over `amount_msats`
+    fn create_invoice<'a>(
+        &'a self,
+        amount_msats: u64,
+        description: &'a str,
+    ) -> BoxFuture<'a, Result<Invoice, PaymentError>>;
+
+    /// Whether the invoice has been paid
+    fn is_paid<'a>(&'a self, invoice: &'a Invoice) -> BoxFuture<'a, Result<bool, PaymentError>>;
+}
+
+/// Lightning backend settings from the environment
+#[derive(Clone)]
+pub enum LightningConfig {
+    Lnd { url: String, macaroon: String },
+    Cln { url: String, rune: String },
+    Lnurl { address: String },
+}
+
+// Macaroons and runes are credentials, keep them out of logs
+impl fmt::Debug for LightningConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lnd { url, .. } => f.debug_struct("Lnd").field("url", url).finish_non_exhaustive(),
+            Self::Cln { url, .. } => f.debug_struct("Cln").field("url", url).finish_non_exhaustive(),
+            Self::Lnurl { address } => f.debug_struct("Lnurl").field("address", address).finish(),
+        }
+    }
+}
+
+impl LightningConfig {
+    /// Read `LIGHTNING_BACKEND` and its settings (`None` when unset or incomplete)
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let backend = var("LIGHTNING_BACKEND")?.to_lowercase();
+        let config = match backend.as_str() {
+            "lnd" => var("LND_REST_URL")
+                .zip(var("LND_MACAROON"))
+                .map(|(url, macaroon)| Self::Lnd { url, macaroon }),
+            "cln" => var("CLN_REST_URL")
+                .zip(var("CLN_RUNE"))
+                .map(|(url, rune)| Self::Cln { url, rune }),
+            "lnurl" => var("LNURL_ADDRESS").map(|address| Self::Lnurl { address }),
+            other => {
+                warn!(backend = other, "Unknown LIGHTNING_BACKEND, Lightning payments disabled");
+                return None;
+            }
+        };
+        if config.is_none() {
+            warn!(backend = %backend, "Lightning backend settings incomplete, Lightning payments disabled");
+        }
+        config
+    }
+
+    /// Build the backend, using `http` for its requests
+    pub fn backend(&self, http: reqwest::Client) -> Arc<dyn LightningBackend> {
+        match self.clone() {
+            Self::Lnd { url, macaroon } => Arc::new(lnd::LndBackend::new(http, url, macaroon)),
+            Self::Cln { url, rune } => Arc::new(cln::ClnBackend::new(http, url, rune)),
+            Self::Lnurl { address } => Arc::new(lnurl::LnurlBackend::new(http, address)),
+        }
+    }
+}
+
+/// Price of a job in satoshis for `secs` of input video.
+///
+/// Every started minute is charged; inputs whose length can't be determined
+/// up front are charged one minute.
+pub fn price_for_duration(sats_per_minute: u64, secs: Option<f64>) -> u64 {
+    let minutes = secs
+        .filter(|s| s.is_finite() && *s > 0.0)
+        .map_or(1, |s| (s / 60.0).ceil() as u64)
+        .max(1);
+    minutes * sats_per_minute
+}
+
+/// Bytes in a GB of output, as priced
+const BYTES_PER_GB: u64 = 1_000_000_000;
+
+/// Price in satoshis for `bytes` of estimated output.
+///
+/// Every started GB is charged; outputs whose size can't be estimated up
+/// front are charged one GB.
+pub fn price_for_output(sats_per_gb: u64, bytes: Option<u64>) -> u64 {
+    bytes.map_or(1, |b| b.div_ceil(BYTES_PER_GB)) * sats_per_gb
+}
+
+/// Current Unix time
+fn now() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
+}
+
+/// Trim a trailing slash so endpoint paths can be appended
+fn base_url(url: &str) -> String {
+    url.trim_end_matches('/').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_for_duration() {
+        assert_eq!(price_for_duration(10, Some(30.0)), 10);
+        assert_eq!(price_for_duration(10, Some(60.0)), 10);
+        assert_eq!(price_for_duration(10, Some(61.0)), 20);
+        assert_eq!(price_for_duration(10, None), 10);
+        assert_eq!(price_for_duration(10, Some(f64::NAN)), 10);
+        assert_eq!(price_for_duration(0, Some(600.0)), 0);
+    }
+
+    #[test]
+    fn test_price_for_output() {
+        assert_eq!(price_for_output(50, Some(0)), 0);
+        assert_eq!(price_for_output(50, Some(1)), 50);
+        assert_eq!(price_for_output(50, Some(1_000_000_000)), 50);
+        assert_eq!(price_for_output(50, Some(2_500_000_000)), 150);
+        assert_eq!(price_for_output(50, None), 50);
+        assert_eq!(price_for_output(0, Some(5_000_000_000)), 0);
+    }
+}
@@ -0,0 +1,367 @@
+//! JSON file storage for the Cashu wallet.
+//!
+//! The wallet holds few proofs (they are melted to Lightning or swept by the
+//! operator), so the whole store is kept in memory and rewritten on every
+//! change. Losing the file loses the redeemed ecash, so it is written to a
+//! temporary file first and renamed into place.
+
+use async_trait::async_trait;
+use cdk::cdk_database::{Error, WalletDatabase};
+use cdk::mint_url::MintUrl;
+use cdk::nuts::{
+    CurrencyUnit, Id, KeySet, KeySetInfo, Keys, MintInfo, PublicKey, SpendingConditions, State,
+};
+use cdk::types::ProofInfo;
+use cdk::wallet::types::{Transaction, TransactionDirection, TransactionId};
+use cdk::wallet::{MeltQuote, MintQuote};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::warn;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct WalletData {
+    mints: Vec<(MintUrl, Option<MintInfo>)>,
+    keysets: Vec<(MintUrl, KeySetInfo)>,
+    keys: Vec<KeySet>,
+    mint_quotes: Vec<MintQuote>,
+    melt_quotes: Vec<MeltQuote>,
+    proofs: Vec<ProofInfo>,
+    /// Deterministic secret counters, keyed by keyset id
+    counters: HashMap<String, u32>,
+    transactions: Vec<Transaction>,
+}
+
+/// Wallet database persisted as a single JSON file
+#[derive(Debug)]
+pub struct JsonWalletStore {
+    /// File the store is persisted to (`None` keeps it in memory only)
+    path: Option<PathBuf>,
+    data: Mutex<WalletData>,
+}
+
+impl JsonWalletStore {
+    /// Load the store from `path`. A missing file starts an empty wallet.
+    ///
+    /// A corrupt file is an error rather than a fresh start, so proofs it
+    /// still holds aren't overwritten.
+    pub fn open(path: PathBuf) -> std::io::Result<Self> {
+        let data = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => WalletData::default(),
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            path: Some(path),
+            data: Mutex::new(data),
+        })
+    }
+
+    /// A store that is never written to disk
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            data: Mutex::new(WalletData::default()),
+        }
+    }
+
+    fn read<T>(&self, f: impl FnOnce(&WalletData) -> T) -> T {
+        f(&self.data.lock().unwrap())
+    }
+
+    /// Apply a change and write the store to its file
+    fn update<T>(&self, f: impl FnOnce(&mut WalletData) -> T) -> Result<T, Error> {
+        let mut data = self.data.lock().unwrap();
+        let result = f(&mut data);
+        if let Some(path) = &self.path {
+            save(path, &data).map_err(|e| {
+                warn!(path = %path.display(), error = %e, "Failed to save Cashu wallet");
+                Error::Database(Box::new(e))
+            })?;
+        }
+        Ok(result)
+    }
+}
+
+fn save(path: &PathBuf, data: &WalletData) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string(data)?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json)?;
+    std::fs::rename(&tmp, path)
+}
+
+#[async_trait]
+impl WalletDatabase for JsonWalletStore {
+    type Err = Error;
+
+    async fn add_mint(&self, mint_url: MintUrl, mint_info: Option<MintInfo>) -> Result<(), Error> {
+        self.update(|d| {
+            d.mints.retain(|(url, _)| *url != mint_url);
+            d.mints.push((mint_url, mint_info));
+        })
+    }
+
+    async fn remove_mint(&self, mint_url: MintUrl) -> Result<(), Error> {
+        self.update(|d| {
+            d.mints.retain(|(url, _)| *url != mint_url);
+            d.keysets.retain(|(url, _)| *url != mint_url);
+        })
+    }
+
+    async fn get_mint(&self, mint_url: MintUrl) -> Result<Option<MintInfo>, Error> {
+        Ok(self.read(|d| {
+            d.mints
+                .iter()
+                .find(|(url, _)| *url == mint_url)
+                .and_then(|(_, info)| info.clone())
+        }))
+    }
+
+    async fn get_mints(&self) -> Result<HashMap<MintUrl, Option<MintInfo>>, Error> {
+        Ok(self.read(|d| d.mints.iter().cloned().collect()))
+    }
+
+    async fn update_mint_url(&self, old_mint_url: MintUrl, new_mint_url: MintUrl) -> Result<(), Error> {
+        self.update(|d| {
+            let urls = d
+                .mints
+                .iter_mut()
+                .map(|(url, _)| url)
+                .chain(d.keysets.iter_mut().map(|(url, _)| url))
+                .chain(d.proofs.iter_mut().map(|p| &mut p.mint_url))
+                .chain(d.mint_quotes.iter_mut().map(|q| &mut q.mint_url));
+            for url in urls.filter(|url| **url == old_mint_url) {
+                *url = new_mint_url.clone();
+            }
+        })
+    }
+
+    async fn add_mint_keysets(&self, mint_url: MintUrl, keysets: Vec<KeySetInfo>) -> Result<(), Error> {
+        self.update(|d| {
+            for keyset in keysets {
+                d.keysets.retain(|(_, k)| k.id != keyset.id);
+                d.keysets.push((mint_url.clone(), keyset));
+            }
+        })
+    }
+
+    async fn get_mint_keysets(&self, mint_url: MintUrl) -> Result<Option<Vec<KeySetInfo>>, Error> {
+        let keysets: Vec<KeySetInfo> = self.read(|d| {
+            d.keysets
+                .iter()
+                .filter(|(url, _)| *url == mint_url)
+                .map(|(_, k)| k.clone())
+                .collect()
+        });
+        Ok((!keysets.is_empty()).then_some(keysets))
+    }
+
+    async fn get_keyset_by_id(&self, keyset_id: &Id) -> Result<Option<KeySetInfo>, Error> {
+        Ok(self.read(|d| {
+            d.keysets
+                .iter()
+                .find(|(_, k)| k.id == *keyset_id)
+                .map(|(_, k)| k.clone())
+        }))
+    }
+
+    async fn add_mint_quote(&self, quote: MintQuote) -> Result<(), Error> {
+        self.update(|d| {
+            d.mint_quotes.retain(|q| q.id != quote.id);
+            d.mint_quotes.push(quote);
+        })
+    }
+
+    async fn get_mint_quote(&self, quote_id: &str) -> Result<Option<MintQuote>, Error> {
+        Ok(self.read(|d| d.mint_quotes.iter().find(|q| q.id == quote_id).cloned()))
+    }
+
+    async fn get_mint_quotes(&self) -> Result<Vec<MintQuote>, Error> {
+        Ok(self.read(|d| d.mint_quotes.clone()))
+    }
+
+    async fn remove_mint_quote(&self, quote_id: &str) -> Result<(), Error> {
+        self.update(|d| d.mint_quotes.retain(|q| q.id != quote_id))
+    }
+
+    async fn add_melt_quote(&self, quote: MeltQuote) -> Result<(), Error> {
+        self.update(|d| {
+            d.melt_quotes.retain(|q| q.id != quote.id);
+            d.melt_quotes.push(quote);
+        })
+    }
+
+    async fn get_melt_quote(&self, quote_id: &str) -> Result<Option<MeltQuote>, Error> {
+        Ok(self.read(|d| d.melt_quotes.iter().find(|q| q.id == quote_id).cloned()))
+    }
+
+    async fn get_melt_quotes(&self) -> Result<Vec<MeltQuote>, Error> {
+        Ok(self.read(|d| d.melt_quotes.clone()))
+    }
+
+    async fn remove_melt_quote(&self, quote_id: &str) -> Result<(), Error> {
+        self.update(|d| d.melt_quotes.retain(|q| q.id != quote_id))
+    }
+
+    async fn add_keys(&self, keyset: KeySet) -> Result<(), Error> {
+        self.update(|d| {
+            d.keys.retain(|k| k.id != keyset.id);
+            d.keys.push(keyset);
+        })
+    }
+
+    async fn get_keys(&self, id: &Id) -> Result<Option<Keys>, Error> {
+        Ok(self.read(|d| d.keys.iter().find(|k| k.id == *id).map(|k| k.keys.clone())))
+    }
+
+    async fn remove_keys(&self, id: &Id) -> Result<(), Error> {
+        self.update(|d| d.keys.retain(|k| k.id != *id))
+    }
+
+    async fn update_proofs(&self, added: Vec<ProofInfo>, removed_ys: Vec<PublicKey>) -> Result<(), Error> {
+        self.update(|d| {
+            d.proofs
+                .retain(|p| !removed_ys.contains(&p.y) && !added.iter().any(|a| a.y == p.y));
+            d.proofs.extend(added);
+        })
+    }
+
+    async fn get_proofs(
+        &self,
+        mint_url: Option<MintUrl>,
+        unit: Option<CurrencyUnit>,
+        state: Option<Vec<State>>,
+        spending_conditions: Option<Vec<SpendingConditions>>,
+    ) -> Result<Vec<ProofInfo>, Error> {
+        Ok(self.read(|d| {
+            d.proofs
+                .iter()
+                .filter(|p| p.matches_conditions(&mint_url, &unit, &state, &spending_conditions))
+                .cloned()
+                .collect()
+        }))
+    }
+
+    async fn get_balance(
+        &self,
+        mint_url: Option<MintUrl>,
+        unit: Option<CurrencyUnit>,
+        state: Option<Vec<State>>,
+    ) -> Result<u64, Error> {
+        Ok(self.read(|d| {
+            d.proofs
+                .iter()
+                .filter(|p| p.matches_conditions(&mint_url, &unit, &state, &None))
+                .map(|p| u64::from(p.proof.amount))
+                .sum()
+        }))
+    }
+
+    async fn update_proofs_state(&self, ys: Vec<PublicKey>, state: State) -> Result<(), Error> {
+        self.update(|d| {
+            for proof in d.proofs.iter_mut().filter(|p| ys.contains(&p.y)) {
+                proof.state = state;
+            }
+        })
+    }
+
+    async fn increment_keyset_counter(&self, keyset_id: &Id, count: u32) -> Result<u32, Error> {
+        self.update(|d| {
+            let counter = d.counters.entry(keyset_id.to_string()).or_default();
+            *counter += count;
+            *counter
+        })
+    }
+
+    async fn add_transaction(&self, transaction: Transaction) -> Result<(), Error> {
+        self.update(|d| {
+            let id = transaction.id();
+            d.transactions.retain(|t| t.id() != id);
+            d.transactions.push(transaction);
+        })
+    }
+
+    async fn get_transaction(&self, transaction_id: TransactionId) -> Result<Option<Transaction>, Error> {
+        Ok(self.read(|d| d.transactions.iter().find(|t| t.id() == transaction_id).cloned()))
+    }
+
+    async fn list_transactions(
+        &self,
+        mint_url: Option<MintUrl>,
+        direction: Option<TransactionDirection>,
+        unit: Option<CurrencyUnit>,
+    ) -> Result<Vec<Transaction>, Error> {
+        Ok(self.read(|d| {
+            d.transactions
+                .iter()
+                .filter(|t| t.matches_conditions(&mint_url, &direction, &unit))
+                .cloned()
+                .collect()
+        }))
+    }
+
+    async fn remove_transaction(&self, transaction_id: TransactionId) -> Result<(), Error> {
+        self.update(|d| d.transactions.retain(|t| t.id() != transaction_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdk::Amount;
+    use std::str::FromStr;
+
+    fn transaction(job_id: &str) -> Transaction {
+        let y = PublicKey::from_hex(
+            "02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104",
+        )
+        .unwrap();
+        Transaction {
+            mint_url: MintUrl::from_str("https://mint.example.com").unwrap(),
+            direction: TransactionDirection::Incoming,
+            amount: Amount::from(21),
+            fee: Amount::ZERO,
+            unit: CurrencyUnit::Sat,
+            ys: vec![y],
+            timestamp: 1_700_000_000,
+            memo: None,
+            metadata: HashMap::from([("job_id".to_string(), job_id.to_string())]),
+            quote_id: None,
+            payment_request: None,
+            payment_proof: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_store_persists_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cashu_wallet.json");
+        let id = Id::from_str("009a1f293253e41e").unwrap();
+
+        let store = JsonWalletStore::open(path.clone()).unwrap();
+        assert_eq!(store.increment_keyset_counter(&id, 3).await.unwrap(), 3);
+        store.add_transaction(transaction("job1")).await.unwrap();
+        // Re-adding the same transaction replaces it
+        store.add_transaction(transaction("job1")).await.unwrap();
+
+        let store = JsonWalletStore::open(path.clone()).unwrap();
+        assert_eq!(store.increment_keyset_counter(&id, 2).await.unwrap(), 5);
+        let transactions = store.list_transactions(None, None, None).await.unwrap();
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].metadata["job_id"], "job1");
+        assert!(store
+            .list_transactions(None, Some(TransactionDirection::Outgoing), None)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Corrupt files are not silently replaced
+        std::fs::write(&path, "{not json").unwrap();
+        assert!(JsonWalletStore::open(path).is_err());
+    }
+}
@@ -8,6 +8,12 @@ use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use thiserror::Error;
 
+use crate::blossom::RetentionTerms;
+use crate::dvm::events::{Codec, JobDefaults, Resolution};
+use crate::dvm::queue::QueuePolicy;
+use crate::video::transform::{LadderRung, QualityPreset, SegmentType};
+use crate::video::watermark::Watermark;
+
 /// NIP-78 application-specific data kind
 pub const KIND_APP_SPECIFIC_DATA: Kind = Kind::Custom(30078);
 
@@ -63,12 +69,109 @@ pub struct RemoteConfig {
     /// Base rate in satoshis per minute of video (0 = free)
     #[serde(default)]
     pub base_rate_sats_per_min: u64,
+    /// Allow fetching inputs from external platforms (e.g. YouTube) via
+    /// the fetchers compiled into this build (default: off)
+    #[serde(default)]
+    pub external_fetchers_enabled: bool,
+    /// HLS ladder used when a request doesn't list resolutions
+    /// (empty = every rung up to the source)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub default_hls_resolutions: Vec<String>,
+    /// MP4 resolution used when a request doesn't pick one (default: 720p)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_mp4_resolution: Option<String>,
+    /// Codec used when a request doesn't pick one (default: h264)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_codec: Option<String>,
+    /// HLS segment container used when a request doesn't pick one (default: fmp4)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_segment_type: Option<String>,
+    /// HLS segment duration in seconds used when a request doesn't set `hls_time` (default: 6)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_hls_time: Option<u32>,
+    /// Seconds between forced keyframes when a request doesn't set `keyframe_interval`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_keyframe_interval: Option<f64>,
+    /// Frame rate cap for requests that don't set `max_fps` (default: none)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_max_fps: Option<u32>,
+    /// Order in which waiting jobs get an encode slot (default: round-robin per requester)
+    #[serde(default)]
+    pub queue_policy: QueuePolicy,
+    /// HLS renditions to encode (empty = built-in 240p-1080p ladder)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bitrate_ladder: Vec<LadderRung>,
+    /// CRF overrides per resolution and codec (empty = built-in values)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quality_presets: Vec<QualityPreset>,
+    /// Longest retention in days a requester can pay for (0 = only `blob_expiration_days`)
+    #[serde(default)]
+    pub max_retention_days: u32,
+    /// Price in satoshis per day of retention beyond `blob_expiration_days`
+    #[serde(default)]
+    pub retention_sats_per_day: u64,
+    /// Longest input accepted in seconds (overrides `MAX_INPUT_DURATION_SECS`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_input_duration_secs: Option<u64>,
+    /// Largest input accepted in bytes (overrides `MAX_INPUT_SIZE_BYTES`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_input_size_bytes: Option<u64>,
+    /// Largest output as a multiple of the input size (overrides `MAX_OUTPUT_SIZE_RATIO`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_size_ratio: Option<f64>,
+    /// Largest combined output in bytes (overrides `MAX_OUTPUT_SIZE_BYTES`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_size_bytes: Option<u64>,
+    /// Watermark burned into jobs that don't request their own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<Watermark>,
+    /// Normalize audio loudness (EBU R128) for jobs that don't say otherwise
+    #[serde(default)]
+    pub normalize_audio: bool,
+    /// Strip source metadata (GPS, device, creation time) from outputs for
+    /// jobs that don't say otherwise
+    #[serde(default = "default_strip_metadata")]
+    pub strip_metadata: bool,
+    /// Price in satoshis per started minute of input video (0 = free)
+    #[serde(default)]
+    pub price_sats_per_minute: u64,
+    /// Price in satoshis charged once per job (0 = none)
+    #[serde(default)]
+    pub price_sats_flat: u64,
+    /// Price in satoshis per started GB of estimated output (0 = none)
+    #[serde(default)]
+    pub price_sats_per_output_gb: u64,
+    /// Start jobs re-transcoding the requester's own video event first
+    #[serde(default)]
+    pub creator_priority: bool,
+    /// Discount in percent for jobs re-transcoding the requester's own video event
+    #[serde(default)]
+    pub creator_discount_percent: u8,
+    /// Distinct NIP-56 reports that quarantine a result (0 = never)
+    #[serde(default)]
+    pub report_threshold: u32,
+    /// Seal inputs kept in the temp dir with a per-job key
+    #[serde(default)]
+    pub encrypt_scratch: bool,
+    /// Pubkeys (hex) served exclusively when non-empty; the admin is always served
+    #[serde(default)]
+    pub allowed_pubkeys: Vec<String>,
+    /// Pubkeys (hex) whose job requests are ignored
+    #[serde(default)]
+    pub blocked_pubkeys: Vec<String>,
+    /// Jobs a requester may start per hour (0 = unlimited)
+    #[serde(default)]
+    pub max_jobs_per_hour: u32,
 }
 
 fn default_max_concurrent_jobs() -> u32 {
     1
 }
 
+fn default_strip_metadata() -> bool {
+    true
+}
+
 fn default_expiration() -> u32 {
     30
 }
@@ -107,6 +210,36 @@ impl Default for RemoteConfig {
             paused: false,
             max_concurrent_jobs: default_max_concurrent_jobs(),
             base_rate_sats_per_min: 0,
+            external_fetchers_enabled: false,
+            default_hls_resolutions: Vec::new(),
+            default_mp4_resolution: None,
+            default_codec: None,
+            default_segment_type: None,
+            default_hls_time: None,
+            default_keyframe_interval: None,
+            default_max_fps: None,
+            queue_policy: QueuePolicy::default(),
+            bitrate_ladder: Vec::new(),
+            quality_presets: Vec::new(),
+            max_retention_days: 0,
+            retention_sats_per_day: 0,
+            max_input_duration_secs: None,
+            max_input_size_bytes: None,
+            max_output_size_ratio: None,
+            max_output_size_bytes: None,
+            watermark: None,
+            normalize_audio: false,
+            strip_metadata: true,
+            price_sats_per_minute: 0,
+            price_sats_flat: 0,
+            price_sats_per_output_gb: 0,
+            creator_priority: false,
+            creator_discount_percent: 0,
+            report_threshold: 0,
+            encrypt_scratch: false,
+            allowed_pubkeys: Vec::new(),
+            blocked_pubkeys: Vec::new(),
+            max_jobs_per_hour: 0,
         }
     }
 }
@@ -126,6 +259,73 @@ impl RemoteConfig {
     pub fn admin_pubkey(&self) -> Option<PublicKey> {
         self.admin.as_ref().and_then(|s| PublicKey::parse(s).ok())
     }
+
+    /// Whether job requests from this pubkey are ignored
+    pub fn is_blocked(&self, pubkey: &PublicKey) -> bool {
+        self.blocked_pubkeys.contains(&pubkey.to_hex())
+    }
+
+    /// Whether this pubkey may request jobs under the allowlist.
+    ///
+    /// An empty allowlist serves everyone; the admin is always served.
+    pub fn is_allowed(&self, pubkey: &PublicKey) -> bool {
+        if self.allowed_pubkeys.is_empty() || self.admin_pubkey().as_ref() == Some(pubkey) {
+            return true;
+        }
+        self.allowed_pubkeys.contains(&pubkey.to_hex())
+    }
+
+    /// Operator job defaults (unrecognized values are ignored)
+    pub fn job_defaults(&self) -> JobDefaults {
+        JobDefaults {
+            hls_resolutions: self
+                .default_hls_resolutions
+                .iter()
+                .filter_map(|r| Resolution::from_str(r))
+                .collect(),
+            mp4_resolution: self
+                .default_mp4_resolution
+                .as_deref()
+                .and_then(Resolution::from_str)
+                .filter(|r| *r != Resolution::Original),
+            codec: self.default_codec.as_deref().and_then(Codec::parse),
+            segment_type: self.default_segment_type.as_deref().and_then(SegmentType::parse),
+            hls_time: self.default_hls_time,
+            keyframe_interval: self.default_keyframe_interval,
+            max_fps: self.default_max_fps,
+        }
+    }
+
+    /// Retention for a job's results, given the days the requester asked for.
+    ///
+    /// Requests up to `blob_expiration_days` get the default window for free;
+    /// longer ones are charged per extra day, up to `max_retention_days`.
+    pub fn retention_terms(&self, requested_days: Option<u32>) -> Result<RetentionTerms, String> {
+        let base = self.blob_expiration_days;
+        let days = requested_days.unwrap_or(base);
+        if days <= base {
+            return Ok(RetentionTerms {
+                days: base,
+                extra_sats: 0,
+            });
+        }
+        if self.max_retention_days <= base {
+            return Err(format!(
+                "Extended retention is not offered, results are kept for {} days",
+                base
+            ));
+        }
+        if days > self.max_retention_days {
+            return Err(format!(
+                "Retention of {} days exceeds the maximum of {} days",
+                days, self.max_retention_days
+            ));
+        }
+        Ok(RetentionTerms {
+            days,
+            extra_sats: (days - base) as u64 * self.retention_sats_per_day,
+        })
+    }
 }
 
 /// Fetches the DVM's remote config from relays.
@@ -229,6 +429,36 @@ mod tests {
             paused: false,
             max_concurrent_jobs: 1,
             base_rate_sats_per_min: 0,
+            external_fetchers_enabled: false,
+            default_hls_resolutions: vec![],
+            default_mp4_resolution: None,
+            default_codec: None,
+            default_segment_type: None,
+            default_hls_time: None,
+            default_keyframe_interval: None,
+            default_max_fps: None,
+            queue_policy: QueuePolicy::Fifo,
+            bitrate_ladder: vec![],
+            quality_presets: vec![],
+            max_retention_days: 0,
+            retention_sats_per_day: 0,
+            max_input_duration_secs: None,
+            max_input_size_bytes: None,
+            max_output_size_ratio: None,
+            max_output_size_bytes: None,
+            watermark: None,
+            normalize_audio: false,
+            strip_metadata: true,
+            price_sats_per_minute: 0,
+            price_sats_flat: 0,
+            price_sats_per_output_gb: 0,
+            creator_priority: false,
+            creator_discount_percent: 0,
+            report_threshold: 0,
+            encrypt_scratch: false,
+            allowed_pubkeys: vec![],
+            blocked_pubkeys: vec![],
+            max_jobs_per_hour: 0,
         };
 
         let json = serde_json::to_string(&config).unwrap();
@@ -257,6 +487,62 @@ mod tests {
         assert_eq!(config.about, Some("Transforms videos to HLS and MP4 via Blossom".to_string()));
         assert!(!config.paused);
         assert_eq!(config.max_concurrent_jobs, 1);
+        assert!(!config.external_fetchers_enabled);
+        assert!(config.strip_metadata);
+        assert_eq!(config.job_defaults(), JobDefaults::default());
+    }
+
+    #[test]
+    fn test_job_defaults() {
+        let json = r#"{"version": 1, "default_hls_resolutions": ["360p", "bogus", "720p"], "default_mp4_resolution": "original", "default_codec": "hevc", "default_segment_type": "ts", "default_hls_time": 4, "default_max_fps": 60}"#;
+        let config: RemoteConfig = serde_json::from_str(json).unwrap();
+        let defaults = config.job_defaults();
+
+        assert_eq!(defaults.hls_resolutions, vec![Resolution::R360p, Resolution::R720p]);
+        // A single MP4 needs a concrete height
+        assert_eq!(defaults.mp4_resolution, None);
+        assert_eq!(defaults.codec, Some(Codec::H265));
+        assert_eq!(defaults.segment_type, Some(SegmentType::MpegTs));
+        assert_eq!(defaults.hls_time, Some(4));
+        assert_eq!(defaults.keyframe_interval, None);
+        assert_eq!(defaults.max_fps, Some(60));
+    }
+
+    #[test]
+    fn test_retention_terms() {
+        let mut config = RemoteConfig::new();
+        let base = RetentionTerms { days: 30, extra_sats: 0 };
+        assert_eq!(config.retention_terms(None), Ok(base));
+        assert_eq!(config.retention_terms(Some(7)), Ok(base));
+        assert!(config.retention_terms(Some(90)).is_err());
+
+        config.max_retention_days = 365;
+        config.retention_sats_per_day = 2;
+        assert_eq!(
+            config.retention_terms(Some(90)),
+            Ok(RetentionTerms { days: 90, extra_sats: 120 })
+        );
+        assert!(config.retention_terms(Some(366)).is_err());
+    }
+
+    #[test]
+    fn test_pubkey_access() {
+        let admin = Keys::generate().public_key();
+        let friend = Keys::generate().public_key();
+        let stranger = Keys::generate().public_key();
+
+        let mut config = RemoteConfig::new();
+        config.admin = Some(admin.to_bech32().unwrap());
+        assert!(config.is_allowed(&stranger));
+        assert!(!config.is_blocked(&stranger));
+
+        config.allowed_pubkeys = vec![friend.to_hex()];
+        config.blocked_pubkeys = vec![stranger.to_hex()];
+        assert!(config.is_allowed(&friend));
+        assert!(config.is_allowed(&admin));
+        assert!(!config.is_allowed(&stranger));
+        assert!(config.is_blocked(&stranger));
+        assert!(!config.is_blocked(&friend));
     }
 
     #[test]
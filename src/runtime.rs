@@ -3,22 +3,41 @@
 //! `run_daemon` contains the full daemon startup sequence previously in main.rs.
 
 use crate::admin::run_admin_listener;
-use crate::blossom::BlossomClient;
+use crate::blossom::{BlossomClient, HealthProber};
+use crate::dvm::heartbeat::Heartbeat;
+use crate::dvm::input_cache::InputCache;
+use crate::dvm::liveness::LivenessChecker;
+use crate::dvm::moderation::Moderator;
+use crate::dvm::receipt::ReceiptLedger;
+use crate::dvm::snapshot::ShutdownSnapshot;
+use crate::dvm::updates::JobUpdates;
 use crate::dvm::{AnnouncementPublisher, JobHandler};
-use crate::nostr::{EventPublisher, SubscriptionManager};
+use crate::dvm_state::ConfigWatch;
+use crate::nostr::{EventPublisher, OutboxSender, SubscriptionManager};
+use crate::payments::cashu::{CashuWallet, CASHU_MINT_URL};
 use crate::startup::initialize;
-use crate::video::{HwAccel, VideoProcessor};
+use crate::util::sealed::SealedInputs;
+use crate::video::VideoProcessor;
 use crate::web::run_server;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::signal;
 use tokio::sync::Notify;
-use tracing::info;
+use tracing::{info, warn};
+
+/// How long the job loop gets to hand back its queue at shutdown
+const SHUTDOWN_QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Exit status asking the service manager to start the DVM again (`EX_TEMPFAIL`)
+pub const RESTART_EXIT_CODE: i32 = 75;
 
 /// Run the DVM daemon in the foreground.
 ///
 /// Loads env files, starts all subsystems, and waits for Ctrl+C or SIGTERM.
 /// If `replace` is true, kills any existing process recorded in the PID file
-/// before starting.
+/// before starting. When the admin switches the identity, the DVM shuts down
+/// and exits with [`RESTART_EXIT_CODE`], so the service manager starts it
+/// again with the new key.
 pub async fn run_daemon(replace: bool) -> anyhow::Result<()> {
     if replace {
         crate::service::process::kill_existing_pid();
@@ -40,6 +59,8 @@ pub async fn run_daemon(replace: bool) -> anyhow::Result<()> {
         }
     }
 
+    crate::util::proxy::export_to_child_env();
+
     info!("Starting DVM Video Processing Service");
     info!("Starting in remote config mode...");
 
@@ -49,13 +70,18 @@ pub async fn run_daemon(replace: bool) -> anyhow::Result<()> {
     let paths = crate::paths::Paths::resolve();
     crate::service::process::write_pid_file(&paths.pid_file);
 
-    let config_notify = Arc::new(Notify::new());
+    let config_watch = ConfigWatch::new();
+    let heartbeat = Heartbeat::new(Some(paths.heartbeat_file.clone()));
+    let job_updates = JobUpdates::new();
 
     let web_handle = if startup.config.http_enabled {
         Some(tokio::spawn({
             let config = startup.config.clone();
+            let heartbeat = heartbeat.clone();
+            let job_updates = job_updates.clone();
+            let state = startup.state.clone();
             async move {
-                if let Err(e) = run_server(config).await {
+                if let Err(e) = run_server(config, heartbeat, job_updates, state).await {
                     tracing::error!("Web server error: {}", e);
                 }
             }
@@ -65,18 +91,22 @@ pub async fn run_daemon(replace: bool) -> anyhow::Result<()> {
         None
     };
 
+    let processor = Arc::new(VideoProcessor::new(startup.config.clone()));
+    let restart = Arc::new(Notify::new());
     let admin_handle = tokio::spawn({
         let client = startup.client.clone();
         let keys = startup.keys.clone();
         let state = startup.state.clone();
         let config = startup.config.clone();
-        let config_notify = config_notify.clone();
+        let config_watch = config_watch.clone();
+        let restart = restart.clone();
+        let processor = processor.clone();
         async move {
-            run_admin_listener(client, keys, state, config, config_notify).await;
+            run_admin_listener(client, keys, state, config, config_watch, restart, processor)
+                .await;
         }
     });
 
-    let hwaccel = HwAccel::detect();
     let publisher = Arc::new(EventPublisher::new(
         startup.config.clone(),
         startup.client.clone(),
@@ -86,13 +116,65 @@ pub async fn run_daemon(replace: bool) -> anyhow::Result<()> {
         startup.config.clone(),
         startup.state.clone(),
         publisher,
-        hwaccel,
-        config_notify,
-    );
+        processor.hwaccel(),
+        config_watch.clone(),
+    )
+    .with_recheck(processor.hw_failures());
     let announcement_handle =
         tokio::spawn(async move { announcement_publisher.run().await });
 
-    let (job_tx, job_rx) = tokio::sync::mpsc::channel(32);
+    let (report_tx, report_rx) = tokio::sync::mpsc::channel(32);
+    let moderator = Moderator::new(
+        startup.config.clone(),
+        startup.state.clone(),
+        Arc::new(EventPublisher::new(
+            startup.config.clone(),
+            startup.client.clone(),
+            startup.state.clone(),
+        )),
+        Arc::new(BlossomClient::new(
+            startup.config.clone(),
+            startup.state.clone(),
+        )),
+        paths.quarantine_dir.clone(),
+    );
+    let moderation_handle = tokio::spawn(moderator.run(report_rx));
+
+    let liveness = LivenessChecker::new(
+        startup.state.clone(),
+        Arc::new(EventPublisher::new(
+            startup.config.clone(),
+            startup.client.clone(),
+            startup.state.clone(),
+        )),
+        crate::util::proxy::http_client(),
+    );
+    let liveness_handle = tokio::spawn(liveness.run());
+
+    let prober = HealthProber::new(
+        startup.state.clone(),
+        Arc::new(BlossomClient::new(
+            startup.config.clone(),
+            startup.state.clone(),
+        )),
+    );
+    let health_handle = tokio::spawn(prober.run());
+
+    // Results that didn't reach enough relays, including ones left from the last run
+    let outbox = OutboxSender::new(
+        startup.state.clone(),
+        Arc::new(EventPublisher::new(
+            startup.config.clone(),
+            startup.client.clone(),
+            startup.state.clone(),
+        )),
+    );
+    let outbox_handle = tokio::spawn(outbox.run());
+
+    let (job_tx, job_rx) = tokio::sync::mpsc::channel(startup.config.job_queue_capacity);
+    // Weak, so the handler doesn't keep its own channel open
+    let requeue_tx = job_tx.downgrade();
+    let restored_tx = job_tx.clone();
     let subscription_handle = tokio::spawn({
         let config = startup.config.clone();
         let client = startup.client.clone();
@@ -100,7 +182,7 @@ pub async fn run_daemon(replace: bool) -> anyhow::Result<()> {
         async move {
             match SubscriptionManager::new(config, client, state).await {
                 Ok(manager) => {
-                    if let Err(e) = manager.run(job_tx).await {
+                    if let Err(e) = manager.with_reports(report_tx).run(job_tx).await {
                         tracing::error!("Subscription manager error: {}", e);
                     }
                 }
@@ -118,18 +200,65 @@ pub async fn run_daemon(replace: bool) -> anyhow::Result<()> {
         startup.config.clone(),
         startup.state.clone(),
     ));
-    let processor = Arc::new(VideoProcessor::new(startup.config.clone()));
-    let job_handler = Arc::new(JobHandler::new(
+    let mut job_handler = JobHandler::new(
         startup.config.clone(),
         startup.state.clone(),
         job_publisher,
         blossom,
         processor,
-    ));
-    let job_handle = tokio::spawn(async move { job_handler.run(job_rx).await });
+    )
+    .with_heartbeat(heartbeat)
+    .with_updates(job_updates)
+    .with_config_watch(config_watch)
+    .with_requeue(requeue_tx)
+    .with_receipts(ReceiptLedger::new(paths.receipts_file.clone()));
+    match CashuWallet::open(
+        paths.cashu_wallet_file.clone(),
+        CASHU_MINT_URL,
+        &startup.keys,
+        startup.config.cashu_melt.clone(),
+        crate::util::proxy::http_client(),
+    ) {
+        Ok(wallet) => job_handler = job_handler.with_cashu_wallet(Arc::new(wallet)),
+        Err(e) => tracing::error!(
+            error = %e,
+            "Failed to open Cashu wallet, payment tokens won't be redeemed"
+        ),
+    }
+    match SealedInputs::start().await {
+        Ok(inputs) => job_handler = job_handler.with_sealed_inputs(inputs),
+        Err(e) => tracing::error!(
+            error = %e,
+            "Failed to start sealed input server, jobs needing encrypted scratch will fail"
+        ),
+    }
+    if let Some(max_bytes) = startup.config.input_cache_bytes {
+        let dir = startup.config.temp_dir.join("input-cache");
+        match InputCache::open(dir, max_bytes) {
+            Ok(cache) => job_handler = job_handler.with_input_cache(Arc::new(cache)),
+            Err(e) => tracing::error!(error = %e, "Failed to open input cache, inputs won't be cached"),
+        }
+    }
+    let job_handler = Arc::new(job_handler);
+    job_handler.resume_invoices(startup.restored.open_invoices).await;
+    let mut job_handle = tokio::spawn({
+        let job_handler = job_handler.clone();
+        async move { job_handler.run(job_rx).await }
+    });
+    // Jobs that were waiting at the last shutdown go back in the queue
+    tokio::spawn(async move {
+        for job in startup.restored.queued {
+            if restored_tx.send(job).await.is_err() {
+                break;
+            }
+        }
+    });
 
     info!("Remote config mode active. Press Ctrl+C to shutdown.");
-    shutdown_signal().await;
+    let restarting = tokio::select! {
+        _ = shutdown_signal() => false,
+        _ = restart.notified() => true,
+    };
 
     info!("Shutting down...");
     if let Some(h) = web_handle {
@@ -137,14 +266,47 @@ pub async fn run_daemon(replace: bool) -> anyhow::Result<()> {
     }
     admin_handle.abort();
     announcement_handle.abort();
+    // Closes the job channel, so the job loop stops starting jobs
     subscription_handle.abort();
-    job_handle.abort();
+    moderation_handle.abort();
+    liveness_handle.abort();
+    health_handle.abort();
+    outbox_handle.abort();
+    let queued = match tokio::time::timeout(SHUTDOWN_QUEUE_TIMEOUT, &mut job_handle).await {
+        Ok(Ok(queued)) => queued,
+        _ => {
+            warn!("Job loop didn't stop in time, queued jobs are not saved");
+            job_handle.abort();
+            Vec::new()
+        }
+    };
+    {
+        let state = startup.state.read().await;
+        let snapshot = ShutdownSnapshot::capture(&state, &queued);
+        match snapshot.save(&paths.snapshot_file) {
+            Ok(()) => info!(
+                queued = snapshot.queued.len(),
+                pending_bids = snapshot.pending_bids.len(),
+                open_invoices = snapshot.open_invoices.len(),
+                "Saved shutdown snapshot"
+            ),
+            Err(e) => tracing::error!(error = %e, "Failed to save shutdown snapshot"),
+        }
+    }
     let _ = startup.client.disconnect().await;
 
-    // Remove PID file on clean exit
+    // Remove PID and heartbeat files on clean exit
     let _ = std::fs::remove_file(&paths.pid_file);
+    let _ = std::fs::remove_file(&paths.heartbeat_file);
 
     info!("Shutdown complete");
+    if restarting {
+        info!(
+            code = RESTART_EXIT_CODE,
+            "Exiting for a restart, start the DVM again if no service manager does"
+        );
+        std::process::exit(RESTART_EXIT_CODE);
+    }
     Ok(())
 }
 
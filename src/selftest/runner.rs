@@ -1,8 +1,10 @@
 use crate::config::Config;
+use crate::dvm::encode_plan::EncodePlan;
 use crate::dvm::events::{Codec, Resolution};
 use crate::selftest::validate::*;
 use crate::selftest::{clips_for_mode, TestClip, TestMode};
 use crate::video::hwaccel::HwAccel;
+use crate::video::transform::Container;
 use crate::video::{VideoMetadata, VideoProcessor};
 use serde::Serialize;
 use std::path::PathBuf;
@@ -221,6 +223,7 @@ pub async fn run_test_suite(config: Arc<Config>, mode: TestMode) -> TestSuiteRes
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run_single_test(
     processor: &VideoProcessor,
     config: &Config,
@@ -238,17 +241,15 @@ async fn run_single_test(
     let hwaccel_str = hwaccel.to_string();
     let hw_accelerated = hwaccel != HwAccel::Software;
 
-    let transform_result = processor
-        .transform_mp4(
-            clip_url,
-            output_resolution,
-            Some(28),
-            output_codec,
-            Some(source_codec_str),
-            None,
-            None,
-        )
-        .await;
+    let plan = EncodePlan {
+        codec: output_codec,
+        source_codec: Some(source_codec_str.to_string()),
+        resolution: output_resolution,
+        quality: 28,
+        container: Container::Mp4,
+        ..EncodePlan::default()
+    };
+    let transform_result = processor.transform_mp4(clip_url, &plan, None, None).await;
 
     let encode_time = start.elapsed().as_secs_f64();
     let speed_ratio = if source_duration > 0.0 {
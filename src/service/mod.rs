@@ -4,6 +4,7 @@ pub mod launchd;
 pub mod process;
 pub mod sysv;
 pub mod systemd;
+pub mod windows;
 
 use anyhow::{bail, Result};
 use crate::paths::Paths;
@@ -19,6 +20,8 @@ pub enum ServiceManager {
     Launchd,
     /// SysV init (Linux without systemd)
     SysV,
+    /// Windows Task Scheduler task
+    WindowsTask,
     /// No supported service manager detected
     None,
 }
@@ -37,13 +40,25 @@ impl ServiceManager {
             if std::path::Path::new("/etc/init.d").exists() {
                 return ServiceManager::SysV;
             }
-            return ServiceManager::None;
+            ServiceManager::None
         }
 
-        #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+        #[cfg(target_os = "windows")]
+        return ServiceManager::WindowsTask;
+
+        #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
         ServiceManager::None
     }
 
+    /// The manager for `--system` commands: systemd's system instance
+    /// where there is systemd, otherwise the same one.
+    fn for_scope(system: bool) -> Self {
+        match Self::detect() {
+            Self::SystemdUser if system => Self::SystemdSystem,
+            mgr => mgr,
+        }
+    }
+
     /// Human-readable name.
     pub fn name(&self) -> &'static str {
         match self {
@@ -51,6 +66,7 @@ impl ServiceManager {
             Self::SystemdSystem => "systemd (system)",
             Self::Launchd => "launchd",
             Self::SysV => "SysV init",
+            Self::WindowsTask => "Task Scheduler",
             Self::None => "none",
         }
     }
@@ -61,15 +77,9 @@ pub fn install_and_start(
     paths: &Paths,
     system: bool,
     _force: bool,
-    _user: Option<&str>,
+    user: Option<&str>,
 ) -> Result<()> {
-    let mgr = if system {
-        ServiceManager::SystemdSystem
-    } else {
-        ServiceManager::detect()
-    };
-
-    match mgr {
+    match ServiceManager::for_scope(system) {
         ServiceManager::SystemdUser => {
             systemd::install_user(paths)?;
             systemd::start_user()?;
@@ -86,14 +96,14 @@ pub fn install_and_start(
             println!("SysV script written. Follow the instructions above to activate.");
         }
         ServiceManager::SystemdSystem => {
-            bail!(
-                "System service install via CLI is not yet implemented.\n\
-                 Manual steps:\n\
-                 1. sudo cp {} /etc/systemd/system/nostube-transcode.service\n\
-                 2. sudo systemctl daemon-reload\n\
-                 3. sudo systemctl enable --now nostube-transcode",
-                paths.systemd_user_unit.display()
-            );
+            systemd::install_system(user)?;
+            println!("Service installed and started (systemd system).");
+            println!("Logs: nostube-transcode logs -f --system");
+        }
+        ServiceManager::WindowsTask => {
+            windows::install(paths, system)?;
+            println!("Service installed and started (Task Scheduler).");
+            println!("Logs: nostube-transcode logs -f");
         }
         ServiceManager::None => {
             bail!(
@@ -106,19 +116,23 @@ pub fn install_and_start(
 }
 
 /// Stop and remove the service.
-pub fn uninstall(paths: &Paths, _system: bool) -> Result<()> {
-    match ServiceManager::detect() {
+pub fn uninstall(paths: &Paths, system: bool) -> Result<()> {
+    match ServiceManager::for_scope(system) {
         ServiceManager::SystemdUser => systemd::uninstall_user(paths),
+        ServiceManager::SystemdSystem => systemd::uninstall_system(),
         ServiceManager::Launchd => launchd::uninstall(paths),
+        ServiceManager::WindowsTask => windows::uninstall(paths),
         mgr => bail!("Uninstall not supported for {}", mgr.name()),
     }
 }
 
 /// Start the service.
-pub fn start(_paths: &Paths, _system: bool) -> Result<()> {
-    match ServiceManager::detect() {
+pub fn start(_paths: &Paths, system: bool) -> Result<()> {
+    match ServiceManager::for_scope(system) {
         ServiceManager::SystemdUser => systemd::start_user(),
+        ServiceManager::SystemdSystem => systemd::control_system("start"),
         ServiceManager::Launchd => launchd::start(),
+        ServiceManager::WindowsTask => windows::start(),
         ServiceManager::SysV => {
             println!("For SysV: sudo service nostube-transcode start");
             Ok(())
@@ -128,10 +142,12 @@ pub fn start(_paths: &Paths, _system: bool) -> Result<()> {
 }
 
 /// Stop the service.
-pub fn stop(_system: bool, _force: bool) -> Result<()> {
-    match ServiceManager::detect() {
+pub fn stop(system: bool, _force: bool) -> Result<()> {
+    match ServiceManager::for_scope(system) {
         ServiceManager::SystemdUser => systemd::stop_user(),
+        ServiceManager::SystemdSystem => systemd::control_system("stop"),
         ServiceManager::Launchd => launchd::stop(),
+        ServiceManager::WindowsTask => windows::stop(),
         ServiceManager::SysV => {
             println!("For SysV: sudo service nostube-transcode stop");
             Ok(())
@@ -141,10 +157,12 @@ pub fn stop(_system: bool, _force: bool) -> Result<()> {
 }
 
 /// Restart the service (refreshes stale service definition first).
-pub fn restart(paths: &Paths, _system: bool) -> Result<()> {
-    match ServiceManager::detect() {
+pub fn restart(paths: &Paths, system: bool) -> Result<()> {
+    match ServiceManager::for_scope(system) {
         ServiceManager::SystemdUser => systemd::restart_user(paths),
+        ServiceManager::SystemdSystem => systemd::control_system("restart"),
         ServiceManager::Launchd => launchd::restart(paths),
+        ServiceManager::WindowsTask => windows::restart(),
         ServiceManager::SysV => {
             println!("For SysV: sudo service nostube-transcode restart");
             Ok(())
@@ -154,10 +172,12 @@ pub fn restart(paths: &Paths, _system: bool) -> Result<()> {
 }
 
 /// Print service status.
-pub fn status(_system: bool, deep: bool) -> Result<()> {
-    match ServiceManager::detect() {
+pub fn status(system: bool, deep: bool) -> Result<()> {
+    match ServiceManager::for_scope(system) {
         ServiceManager::SystemdUser => systemd::status_user(deep),
+        ServiceManager::SystemdSystem => systemd::status_system(deep),
         ServiceManager::Launchd => launchd::status(),
+        ServiceManager::WindowsTask => windows::status(),
         ServiceManager::SysV => {
             println!("For SysV: sudo service nostube-transcode status");
             Ok(())
@@ -167,10 +187,12 @@ pub fn status(_system: bool, deep: bool) -> Result<()> {
 }
 
 /// Print or follow logs.
-pub fn logs(paths: &Paths, follow: bool, lines: u32, _system: bool) -> Result<()> {
-    match ServiceManager::detect() {
+pub fn logs(paths: &Paths, follow: bool, lines: u32, system: bool) -> Result<()> {
+    match ServiceManager::for_scope(system) {
         ServiceManager::SystemdUser => systemd::logs_user(follow, lines),
+        ServiceManager::SystemdSystem => systemd::logs_system(follow, lines),
         ServiceManager::Launchd => launchd::logs(paths, follow, lines),
+        ServiceManager::WindowsTask => windows::logs(paths, follow, lines),
         _ => {
             // Fallback: try to tail the log file
             if paths.stderr_log.exists() {
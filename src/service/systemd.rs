@@ -5,6 +5,9 @@ use std::path::Path;
 use std::process::Command;
 use tracing::{info, warn};
 
+/// Unit file of the system-wide service
+pub const SYSTEM_UNIT_PATH: &str = "/etc/systemd/system/nostube-transcode.service";
+
 /// Generate a systemd user unit file for nostube-transcode.
 pub fn generate_user_unit(binary_path: &str, env_file: &str, home: &str) -> String {
     let cargo_bin = format!("{}/.cargo/bin", home);
@@ -180,6 +183,116 @@ pub fn logs_user(follow: bool, lines: u32) -> Result<()> {
     Ok(())
 }
 
+/// Home directory of `user` in `/etc/passwd` contents
+pub fn passwd_home(passwd: &str, user: &str) -> Option<String> {
+    passwd.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        (fields.len() >= 7 && fields[0] == user).then(|| fields[5].to_string())
+    })
+}
+
+/// Install, enable and start the system-wide service (requires root).
+///
+/// The service runs as `user`, or the user who invoked sudo, with the
+/// binary and env file from that user's home.
+pub fn install_system(user: Option<&str>) -> Result<()> {
+    #[cfg(unix)]
+    if unsafe { libc::geteuid() } != 0 {
+        bail!("Installing a system service requires root — rerun with sudo");
+    }
+    let user = match user {
+        Some(u) => u.to_string(),
+        None => std::env::var("SUDO_USER")
+            .context("Pass --user to choose the account the service runs as")?,
+    };
+    let passwd = std::fs::read_to_string("/etc/passwd").context("Failed to read /etc/passwd")?;
+    let Some(home) = passwd_home(&passwd, &user) else {
+        bail!("Unknown user {user}");
+    };
+
+    let binary = format!("{home}/.local/bin/nostube-transcode");
+    let binary = if Path::new(&binary).exists() {
+        binary
+    } else {
+        std::env::current_exe()
+            .context("Failed to locate the running binary")?
+            .to_string_lossy()
+            .to_string()
+    };
+    let env_file = match std::env::var("DATA_DIR") {
+        Ok(dir) => format!("{dir}/env"),
+        Err(_) => format!("{home}/.local/share/nostube-transcode/env"),
+    };
+
+    let unit_content = generate_system_unit(&binary, &env_file, &home, &user);
+    std::fs::write(SYSTEM_UNIT_PATH, &unit_content)
+        .context("Failed to write systemd system unit")?;
+    info!("Wrote unit file: {}", SYSTEM_UNIT_PATH);
+
+    run_systemctl_system(&["daemon-reload"])?;
+    run_systemctl_system(&["enable", "--now", "nostube-transcode"])?;
+    info!("Service enabled and started");
+    Ok(())
+}
+
+/// Stop, disable and remove the system-wide service (requires root).
+pub fn uninstall_system() -> Result<()> {
+    let _ = run_systemctl_system(&["disable", "--now", "nostube-transcode"]);
+    if Path::new(SYSTEM_UNIT_PATH).exists() {
+        std::fs::remove_file(SYSTEM_UNIT_PATH).context("Failed to remove unit file (run with sudo)")?;
+        info!("Removed unit file: {}", SYSTEM_UNIT_PATH);
+    }
+    run_systemctl_system(&["daemon-reload"])?;
+    Ok(())
+}
+
+/// Run `systemctl <action> nostube-transcode` for the system-wide service.
+pub fn control_system(action: &str) -> Result<()> {
+    run_systemctl_system(&[action, "nostube-transcode"])
+}
+
+/// Print status of the system-wide service.
+pub fn status_system(deep: bool) -> Result<()> {
+    let args: &[&str] = if deep {
+        &["status", "nostube-transcode"]
+    } else {
+        &["status", "--no-pager", "-l", "nostube-transcode"]
+    };
+    match Command::new("systemctl").args(args).status() {
+        Ok(s) if s.success() => Ok(()),
+        Ok(_) => bail!("Service is not running"),
+        Err(e) => bail!("systemctl not available: {}", e),
+    }
+}
+
+/// Follow or print logs of the system-wide service.
+pub fn logs_system(follow: bool, lines: u32) -> Result<()> {
+    let n = lines.to_string();
+    let mut args = vec!["-u", "nostube-transcode", "-n", &n];
+    if follow {
+        args.push("-f");
+    }
+    let status = Command::new("journalctl")
+        .args(&args)
+        .status()
+        .context("journalctl not available")?;
+    if !status.success() {
+        warn!("journalctl exited with non-zero status");
+    }
+    Ok(())
+}
+
+fn run_systemctl_system(args: &[&str]) -> Result<()> {
+    let status = Command::new("systemctl")
+        .args(args)
+        .status()
+        .context("systemctl not available")?;
+    if !status.success() {
+        bail!("systemctl {} failed", args.join(" "));
+    }
+    Ok(())
+}
+
 fn run_systemctl_user(args: &[&str]) -> Result<()> {
     let mut full_args = vec!["--user"];
     full_args.extend_from_slice(args);
@@ -225,6 +338,15 @@ mod tests {
         assert!(unit.contains("HOME=/home/alice"));
     }
 
+    #[test]
+    fn test_passwd_home() {
+        let passwd = "root:x:0:0:root:/root:/bin/bash\n\
+                      alice:x:1000:1000:Alice,,,:/home/alice:/bin/bash\n";
+        assert_eq!(passwd_home(passwd, "alice"), Some("/home/alice".to_string()));
+        assert_eq!(passwd_home(passwd, "root"), Some("/root".to_string()));
+        assert_eq!(passwd_home(passwd, "bob"), None);
+    }
+
     #[test]
     fn test_stale_detection_identical_content() {
         let content = "[Unit]\nDescription=test\n";
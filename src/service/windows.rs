@@ -0,0 +1,215 @@
+//! Windows Task Scheduler registration.
+//!
+//! A Windows service has to answer the service control manager, which the
+//! DVM doesn't, so it runs as a scheduled task instead: started at logon (or
+//! at boot with `--system`), restarted after a minute whenever it exits with
+//! an error, and with its output appended to the log files in `$DATA_DIR/logs`.
+
+use anyhow::{bail, Context, Result};
+use std::process::Command;
+use tracing::info;
+
+/// Task Scheduler task name
+pub const TASK_NAME: &str = "nostube-transcode";
+
+/// Generate the Task Scheduler definition for nostube-transcode.
+///
+/// `user` is `DOMAIN\name` of the account the task runs as. With `at_boot`
+/// the task starts at boot without anyone logged on (S4U logon), otherwise
+/// when `user` logs on.
+pub fn generate_task_xml(binary_path: &str, data_dir: &str, log_dir: &str, user: &str, at_boot: bool) -> String {
+    let (trigger, logon_type) = if at_boot {
+        ("<BootTrigger><Enabled>true</Enabled></BootTrigger>".to_string(), "S4U")
+    } else {
+        (
+            format!(
+                "<LogonTrigger><Enabled>true</Enabled><UserId>{}</UserId></LogonTrigger>",
+                xml_escape(user)
+            ),
+            "InteractiveToken",
+        )
+    };
+    // cmd.exe sets DATA_DIR and routes output to the log files
+    let arguments = format!(
+        "/c set \"DATA_DIR={data_dir}\" &amp;&amp; \"{binary_path}\" run --replace &gt;&gt; \"{log_dir}\\stdout.log\" 2&gt;&gt; \"{log_dir}\\stderr.log\"",
+        data_dir = xml_escape(data_dir),
+        binary_path = xml_escape(binary_path),
+        log_dir = xml_escape(log_dir),
+    );
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-16"?>
+<Task version="1.2" xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task">
+  <RegistrationInfo>
+    <Description>nostube-transcode DVM</Description>
+  </RegistrationInfo>
+  <Triggers>
+    {trigger}
+  </Triggers>
+  <Principals>
+    <Principal id="Author">
+      <UserId>{user}</UserId>
+      <LogonType>{logon_type}</LogonType>
+      <RunLevel>LeastPrivilege</RunLevel>
+    </Principal>
+  </Principals>
+  <Settings>
+    <MultipleInstancesPolicy>IgnoreNew</MultipleInstancesPolicy>
+    <DisallowStartIfOnBatteries>false</DisallowStartIfOnBatteries>
+    <StopIfGoingOnBatteries>false</StopIfGoingOnBatteries>
+    <StartWhenAvailable>true</StartWhenAvailable>
+    <RunOnlyIfNetworkAvailable>false</RunOnlyIfNetworkAvailable>
+    <ExecutionTimeLimit>PT0S</ExecutionTimeLimit>
+    <RestartOnFailure>
+      <Interval>PT1M</Interval>
+      <Count>999</Count>
+    </RestartOnFailure>
+    <Enabled>true</Enabled>
+  </Settings>
+  <Actions Context="Author">
+    <Exec>
+      <Command>cmd.exe</Command>
+      <Arguments>{arguments}</Arguments>
+    </Exec>
+  </Actions>
+</Task>
+"#,
+        user = xml_escape(user),
+    )
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Account the task runs as (`DOMAIN\name`)
+fn current_user() -> Result<String> {
+    let name = std::env::var("USERNAME").context("USERNAME is not set")?;
+    Ok(match std::env::var("USERDOMAIN") {
+        Ok(domain) => format!("{domain}\\{name}"),
+        Err(_) => name,
+    })
+}
+
+/// Register the task and start it.
+///
+/// With `at_boot` the task runs without a logged-on user, which needs an
+/// elevated prompt.
+pub fn install(paths: &crate::paths::Paths, at_boot: bool) -> Result<()> {
+    let binary = if paths.binary_path.exists() {
+        paths.binary_path.clone()
+    } else {
+        std::env::current_exe().context("Failed to locate the running binary")?
+    };
+    std::fs::create_dir_all(&paths.log_dir).context("Failed to create log directory")?;
+
+    let xml = generate_task_xml(
+        &binary.to_string_lossy(),
+        &paths.data_dir.to_string_lossy(),
+        &paths.log_dir.to_string_lossy(),
+        &current_user()?,
+        at_boot,
+    );
+    // schtasks reads task definitions as UTF-16 with a byte order mark
+    let mut encoded = vec![0xFF, 0xFE];
+    encoded.extend(xml.encode_utf16().flat_map(u16::to_le_bytes));
+    std::fs::write(&paths.windows_task_xml, encoded).context("Failed to write task definition")?;
+    info!("Wrote task definition: {:?}", paths.windows_task_xml);
+
+    let xml_path = paths.windows_task_xml.to_string_lossy().to_string();
+    run_schtasks(&["/Create", "/TN", TASK_NAME, "/XML", &xml_path, "/F"])?;
+    start()
+}
+
+/// Start the task now.
+pub fn start() -> Result<()> {
+    run_schtasks(&["/Run", "/TN", TASK_NAME])
+}
+
+/// Stop the running task.
+pub fn stop() -> Result<()> {
+    run_schtasks(&["/End", "/TN", TASK_NAME])
+}
+
+/// Stop and start the task.
+pub fn restart() -> Result<()> {
+    let _ = stop();
+    start()
+}
+
+/// Stop and remove the task.
+pub fn uninstall(paths: &crate::paths::Paths) -> Result<()> {
+    let _ = stop();
+    run_schtasks(&["/Delete", "/TN", TASK_NAME, "/F"])?;
+    if paths.windows_task_xml.exists() {
+        std::fs::remove_file(&paths.windows_task_xml)?;
+    }
+    Ok(())
+}
+
+/// Print the task's state and last result.
+pub fn status() -> Result<()> {
+    run_schtasks(&["/Query", "/TN", TASK_NAME, "/V", "/FO", "LIST"])
+}
+
+/// Print or follow the stderr log, where tracing output goes.
+pub fn logs(paths: &crate::paths::Paths, follow: bool, lines: u32) -> Result<()> {
+    let mut script = format!(
+        "Get-Content -Path '{}' -Tail {}",
+        paths.stderr_log.display(),
+        lines
+    );
+    if follow {
+        script.push_str(" -Wait");
+    }
+    Command::new("powershell")
+        .args(["-NoProfile", "-Command", &script])
+        .status()
+        .context("powershell not available")?;
+    Ok(())
+}
+
+fn run_schtasks(args: &[&str]) -> Result<()> {
+    let status = Command::new("schtasks")
+        .args(args)
+        .status()
+        .context("schtasks not available")?;
+    if !status.success() {
+        bail!("schtasks {} failed", args.join(" "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_xml_at_logon() {
+        let xml = generate_task_xml(
+            r"C:\Users\alice\.local\bin\nostube-transcode.exe",
+            r"C:\Users\alice\.local\share\nostube-transcode",
+            r"C:\Users\alice\.local\share\nostube-transcode\logs",
+            r"PC\alice",
+            false,
+        );
+        assert!(xml.contains(r"<LogonTrigger><Enabled>true</Enabled><UserId>PC\alice</UserId>"));
+        assert!(xml.contains("<LogonType>InteractiveToken</LogonType>"));
+        assert!(xml.contains("<RestartOnFailure>"));
+        assert!(xml.contains("<ExecutionTimeLimit>PT0S</ExecutionTimeLimit>"));
+        assert!(xml.contains(r#""C:\Users\alice\.local\bin\nostube-transcode.exe" run --replace"#));
+        assert!(xml.contains(r#"&gt;&gt; "C:\Users\alice\.local\share\nostube-transcode\logs\stdout.log""#));
+        assert!(xml.contains(r#"2&gt;&gt; "C:\Users\alice\.local\share\nostube-transcode\logs\stderr.log""#));
+    }
+
+    #[test]
+    fn test_task_xml_at_boot() {
+        let xml = generate_task_xml(r"C:\dvm.exe", r"C:\data", r"C:\data\logs", r"PC\R&D", true);
+        assert!(xml.contains("<BootTrigger>"));
+        assert!(!xml.contains("<LogonTrigger>"));
+        assert!(xml.contains("<LogonType>S4U</LogonType>"));
+        assert!(xml.contains(r"<UserId>PC\R&amp;D</UserId>"));
+    }
+}
@@ -3,9 +3,13 @@
 //! Handles the complete startup sequence including identity loading
 //! and config fetching.
 
+use crate::blossom::{QuarantineLedger, ReferenceIndex, RetentionLedger};
 use crate::bootstrap::get_bootstrap_relays;
 use crate::config::Config;
+use crate::dvm::snapshot::{restore_snapshot, RestoredJobs};
 use crate::dvm_state::{DvmState, SharedDvmState};
+use crate::nostr::Outbox;
+use crate::paths::Paths;
 use crate::remote_config::{fetch_config, RemoteConfig};
 use crate::util::ffmpeg_discovery::FfmpegPaths;
 use nostr_sdk::prelude::*;
@@ -17,6 +21,8 @@ pub struct StartupResult {
     pub client: Client,
     pub state: SharedDvmState,
     pub config: Arc<Config>,
+    /// Queued jobs and open invoices from the last run's shutdown snapshot
+    pub restored: RestoredJobs,
 }
 
 /// Initialize the DVM on startup.
@@ -27,8 +33,8 @@ pub struct StartupResult {
 /// 4. Fetch remote config (if exists)
 /// 5. Set admin from OPERATOR_NPUB if not already in remote config
 /// 6. Discover FFmpeg binaries
-/// 7. Create Config from RemoteConfig
-/// 8. Create DVM state
+/// 7. Create Config from the environment
+/// 8. Create DVM state, restoring the last run's shutdown snapshot
 pub async fn initialize() -> Result<StartupResult, Box<dyn std::error::Error>> {
     // Step 1: Load or generate identity
     tracing::info!("Loading identity...");
@@ -60,7 +66,7 @@ pub async fn initialize() -> Result<StartupResult, Box<dyn std::error::Error>> {
 
     // Step 3: Connect to bootstrap relays
     tracing::info!("Connecting to bootstrap relays...");
-    let client = Client::new(keys.clone());
+    let client = crate::util::proxy::nostr_client(&keys);
 
     for relay in get_bootstrap_relays() {
         if let Err(e) = client.add_relay(relay.to_string()).await {
@@ -119,22 +125,31 @@ pub async fn initialize() -> Result<StartupResult, Box<dyn std::error::Error>> {
     tracing::info!("Discovering FFmpeg binaries...");
     let ffmpeg_paths = FfmpegPaths::discover()?;
 
-    // Step 7: Create Config from RemoteConfig
-    let config = Arc::new(Config::from_remote(
+    // Step 7: Create the fixed runtime Config (reloadable settings stay in the state)
+    let config = Arc::new(Config::from_env(
         keys.clone(),
-        &remote_config,
         ffmpeg_paths.ffmpeg,
         ffmpeg_paths.ffprobe,
-    )?);
+    ));
 
     // Step 8: Create DVM state
     let state = DvmState::new_shared(keys.clone(), remote_config);
+    let restored = {
+        let paths = Paths::resolve();
+        let mut state = state.write().await;
+        state.retention = RetentionLedger::load(paths.retention_file);
+        state.references = ReferenceIndex::load(paths.references_file);
+        state.quarantine = QuarantineLedger::load(paths.quarantine_file);
+        state.outbox = Outbox::load(paths.outbox_file);
+        restore_snapshot(&paths.snapshot_file, &mut state, &keys)
+    };
 
     Ok(StartupResult {
         keys,
         client,
         state,
         config,
+        restored,
     })
 }
 
@@ -148,21 +163,18 @@ mod tests {
         let keys = Keys::generate();
         let client = Client::new(keys.clone());
         let state = DvmState::new_shared(keys.clone(), RemoteConfig::new());
-        let config = Arc::new(
-            Config::from_remote(
-                keys.clone(),
-                &RemoteConfig::new(),
-                std::path::PathBuf::from("ffmpeg"),
-                std::path::PathBuf::from("ffprobe"),
-            )
-            .unwrap(),
-        );
+        let config = Arc::new(Config::from_env(
+            keys.clone(),
+            std::path::PathBuf::from("ffmpeg"),
+            std::path::PathBuf::from("ffprobe"),
+        ));
 
         let result = StartupResult {
             keys: keys.clone(),
             client,
             state,
             config,
+            restored: RestoredJobs::default(),
         };
 
         assert_eq!(result.keys.public_key(), keys.public_key());
@@ -35,7 +35,7 @@ fn target_triple() -> &'static str {
 /// Fetch the latest release info from GitHub.
 async fn fetch_latest_release() -> Result<GithubRelease> {
     let url = format!("{GITHUB_API}/repos/{REPO}/releases/latest");
-    let client = reqwest::Client::builder()
+    let client = crate::util::proxy::http_client_builder()
         .user_agent(format!("nostube-transcode/{}", env!("CARGO_PKG_VERSION")))
         .build()?;
     let resp = client
@@ -110,7 +110,7 @@ pub async fn run(yes: bool, check_only: bool) -> Result<()> {
 
     println!("Downloading {}…", asset.browser_download_url);
 
-    let client = reqwest::Client::builder()
+    let client = crate::util::proxy::http_client_builder()
         .user_agent(format!("nostube-transcode/{}", env!("CARGO_PKG_VERSION")))
         .build()?;
     let bytes = client
@@ -0,0 +1,166 @@
+//! BlurHash encoding (<https://blurha.sh>).
+//!
+//! A blurhash is a short string describing a blurred version of an image,
+//! which clients decode into a placeholder while the real video loads.
+
+use std::f64::consts::PI;
+
+const BASE83: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+/// Encode a packed RGB24 image with `x_components` by `y_components` (1-9 each).
+///
+/// Returns `None` if the buffer doesn't hold `width * height` pixels or the
+/// component counts are out of range.
+pub fn encode(
+    rgb: &[u8],
+    width: usize,
+    height: usize,
+    x_components: usize,
+    y_components: usize,
+) -> Option<String> {
+    if width == 0
+        || height == 0
+        || rgb.len() != width * height * 3
+        || !(1..=9).contains(&x_components)
+        || !(1..=9).contains(&y_components)
+    {
+        return None;
+    }
+
+    let linear: Vec<[f64; 3]> = rgb
+        .chunks_exact(3)
+        .map(|p| [srgb_to_linear(p[0]), srgb_to_linear(p[1]), srgb_to_linear(p[2])])
+        .collect();
+
+    let mut factors = Vec::with_capacity(x_components * y_components);
+    for j in 0..y_components {
+        for i in 0..x_components {
+            let normalisation = if i == 0 && j == 0 { 1.0 } else { 2.0 };
+            let mut factor = [0.0; 3];
+            for y in 0..height {
+                let basis_y = (PI * j as f64 * y as f64 / height as f64).cos();
+                for x in 0..width {
+                    let basis = normalisation
+                        * (PI * i as f64 * x as f64 / width as f64).cos()
+                        * basis_y;
+                    let pixel = linear[y * width + x];
+                    for c in 0..3 {
+                        factor[c] += basis * pixel[c];
+                    }
+                }
+            }
+            let scale = 1.0 / (width * height) as f64;
+            factors.push(factor.map(|f| f * scale));
+        }
+    }
+
+    let (dc, ac) = factors.split_first()?;
+    let mut hash = String::new();
+    push_base83(&mut hash, (x_components - 1) + (y_components - 1) * 9, 1);
+
+    let maximum = if ac.is_empty() {
+        push_base83(&mut hash, 0, 1);
+        1.0
+    } else {
+        let actual = ac
+            .iter()
+            .flat_map(|f| f.iter())
+            .fold(0.0f64, |m, v| m.max(v.abs()));
+        let quantised = (actual * 166.0 - 0.5).floor().clamp(0.0, 82.0) as usize;
+        push_base83(&mut hash, quantised, 1);
+        (quantised + 1) as f64 / 166.0
+    };
+
+    let dc_value = (linear_to_srgb(dc[0]) << 16) + (linear_to_srgb(dc[1]) << 8) + linear_to_srgb(dc[2]);
+    push_base83(&mut hash, dc_value, 4);
+
+    for factor in ac {
+        let q = |v: f64| (sign_pow(v / maximum, 0.5) * 9.0 + 9.5).floor().clamp(0.0, 18.0) as usize;
+        push_base83(&mut hash, q(factor[0]) * 19 * 19 + q(factor[1]) * 19 + q(factor[2]), 2);
+    }
+
+    Some(hash)
+}
+
+/// Component counts for an image of the given aspect, about 4 along the long side
+pub fn components_for(width: u32, height: u32) -> (usize, usize) {
+    if width >= height {
+        (4, 3)
+    } else {
+        (3, 4)
+    }
+}
+
+fn push_base83(hash: &mut String, value: usize, length: u32) {
+    for i in 1..=length {
+        let digit = (value / 83usize.pow(length - i)) % 83;
+        hash.push(BASE83[digit] as char);
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f64 {
+    let v = value as f64 / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f64) -> usize {
+    let v = value.clamp(0.0, 1.0);
+    if v <= 0.003_130_8 {
+        (v * 12.92 * 255.0 + 0.5) as usize
+    } else {
+        ((1.055 * v.powf(1.0 / 2.4) - 0.055) * 255.0 + 0.5) as usize
+    }
+}
+
+fn sign_pow(value: f64, exp: f64) -> f64 {
+    value.abs().powf(exp).copysign(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_solid_color() {
+        let white = vec![255u8; 8 * 8 * 3];
+        let hash = encode(&white, 8, 8, 4, 3).unwrap();
+        // Size flag, AC maximum, white DC, then eleven AC components
+        assert_eq!(hash.len(), 28);
+        assert!(hash.starts_with('L'));
+        assert_eq!(&hash[2..6], "TSUA");
+
+        let black = vec![0u8; 4 * 4 * 3];
+        assert_eq!(encode(&black, 4, 4, 1, 1).unwrap(), "000000");
+    }
+
+    #[test]
+    fn test_encode_gradient() {
+        // Left half red, right half blue
+        let (w, h) = (16, 8);
+        let rgb: Vec<u8> = (0..w * h)
+            .flat_map(|i| if i % w < w / 2 { [255, 0, 0] } else { [0, 0, 255] })
+            .collect();
+        let hash = encode(&rgb, w, h, 4, 3).unwrap();
+        assert_eq!(hash.len(), 4 + 2 * 4 * 3);
+        assert!(hash.starts_with('L'));
+        assert_ne!(&hash[6..8], "fQ");
+        assert!(hash.bytes().all(|b| BASE83.contains(&b)));
+    }
+
+    #[test]
+    fn test_encode_rejects_bad_input() {
+        assert!(encode(&[0; 11], 2, 2, 4, 3).is_none());
+        assert!(encode(&[0; 12], 2, 2, 0, 3).is_none());
+        assert!(encode(&[0; 12], 2, 2, 4, 10).is_none());
+    }
+
+    #[test]
+    fn test_components_for() {
+        assert_eq!(components_for(1920, 1080), (4, 3));
+        assert_eq!(components_for(1080, 1920), (3, 4));
+    }
+}
@@ -0,0 +1,63 @@
+use std::path::Path;
+
+/// Free and total bytes of the filesystem containing a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskSpace {
+    /// Bytes available to unprivileged users
+    pub free_bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// Query filesystem space for a path via `statvfs`.
+///
+/// Returns `None` on non-unix platforms or if the path cannot be queried.
+pub fn disk_space(path: &Path) -> Option<DiskSpace> {
+    #[cfg(unix)]
+    {
+        use std::ffi::CString;
+
+        // Handle potential null bytes in path (unlikely but possible)
+        let c_path = match CString::new(path.to_string_lossy().as_bytes()) {
+            Ok(p) => p,
+            Err(_) => {
+                tracing::warn!(path = %path.display(), "Path contains null bytes, cannot get disk info");
+                return None;
+            }
+        };
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        let result = unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) };
+
+        if result == 0 {
+            return Some(DiskSpace {
+                free_bytes: stat.f_bavail as u64 * stat.f_frsize,
+                total_bytes: stat.f_blocks as u64 * stat.f_frsize,
+            });
+        }
+        None
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disk_space_for_temp_dir() {
+        let dir = std::env::temp_dir();
+        if let Some(space) = disk_space(&dir) {
+            assert!(space.total_bytes > 0);
+            assert!(space.free_bytes <= space.total_bytes);
+        }
+    }
+
+    #[test]
+    fn test_disk_space_missing_path() {
+        assert!(disk_space(Path::new("/definitely/not/a/real/path")).is_none());
+    }
+}
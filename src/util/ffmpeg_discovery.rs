@@ -1,14 +1,17 @@
 use std::path::PathBuf;
 use std::process::Command;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::error::ConfigError;
+use crate::util::ffmpeg_version::{self, FfmpegVersion, MIN_FFMPEG_VERSION};
 
 /// Discovered FFmpeg binary paths
 #[derive(Debug, Clone)]
 pub struct FfmpegPaths {
     pub ffmpeg: PathBuf,
     pub ffprobe: PathBuf,
+    /// Release version of `ffmpeg` (`None` for git snapshot builds)
+    pub version: Option<FfmpegVersion>,
 }
 
 impl FfmpegPaths {
@@ -17,13 +20,41 @@ impl FfmpegPaths {
     /// 1. Environment variables (FFMPEG_PATH, FFPROBE_PATH)
     /// 2. Platform-specific common locations
     /// 3. System PATH
+    ///
+    /// Fails if the FFmpeg release is older than [`MIN_FFMPEG_VERSION`].
     pub fn discover() -> Result<Self, ConfigError> {
         let ffmpeg = Self::find_ffmpeg()?;
         let ffprobe = Self::find_ffprobe()?;
 
-        info!(ffmpeg = %ffmpeg.display(), ffprobe = %ffprobe.display(), "FFmpeg binaries discovered");
+        let version = FfmpegVersion::detect(&ffmpeg);
+        match version {
+            Some(v) if !v.is_supported() => {
+                return Err(ConfigError::FfmpegTooOld {
+                    found: v.to_string(),
+                    minimum: MIN_FFMPEG_VERSION.to_string(),
+                });
+            }
+            Some(v) => ffmpeg_version::set_installed(v),
+            None => warn!(ffmpeg = %ffmpeg.display(), "Could not determine FFmpeg release version, assuming a recent build"),
+        }
 
-        Ok(Self { ffmpeg, ffprobe })
+        match ffmpeg_version::detect_encoders(&ffmpeg) {
+            Some(encoders) => ffmpeg_version::set_compiled_encoders(encoders),
+            None => warn!(ffmpeg = %ffmpeg.display(), "Could not list FFmpeg encoders, assuming all are available"),
+        }
+
+        info!(
+            ffmpeg = %ffmpeg.display(),
+            ffprobe = %ffprobe.display(),
+            version = ?version.map(|v| v.to_string()),
+            "FFmpeg binaries discovered"
+        );
+
+        Ok(Self {
+            ffmpeg,
+            ffprobe,
+            version,
+        })
     }
 
     fn find_ffmpeg() -> Result<PathBuf, ConfigError> {
@@ -117,7 +148,7 @@ impl FfmpegPaths {
 
         std::env::var_os("PATH").and_then(|paths| {
             std::env::split_paths(&paths)
-                .map(|dir| dir.join(&name))
+                .map(|dir| dir.join(name))
                 .find(|path| path.exists())
         })
     }
@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, BufReader};
@@ -5,31 +6,140 @@ use tokio::process::ChildStdout;
 
 pub struct FfmpegProgressTracker {
     pub progress_ms: Arc<AtomicU64>,
+    /// Per-rung progress of an HLS ladder (None = report FFmpeg's out_time)
+    pub ladder: Option<LadderProgress>,
 }
 
 impl FfmpegProgressTracker {
     pub fn new() -> Self {
         Self {
             progress_ms: Arc::new(AtomicU64::new(0)),
+            ladder: None,
         }
     }
 
     pub async fn track_progress(&self, stdout: ChildStdout) -> tokio::io::Result<()> {
         let mut reader = BufReader::new(stdout).lines();
+        // `out_time_ms` is historically in microseconds despite its name;
+        // prefer the explicit `out_time_us` key when FFmpeg provides it
+        let mut has_us_key = false;
+        let mut out_time_us = 0;
 
         while let Some(line) = reader.next_line().await? {
-            if line.starts_with("out_time_ms=") {
-                if let Ok(ms) = line["out_time_ms=".len()..].parse::<i64>() {
+            let value = if let Some(v) = line.strip_prefix("out_time_us=") {
+                has_us_key = true;
+                Some(v)
+            } else if has_us_key {
+                None
+            } else {
+                line.strip_prefix("out_time_ms=")
+            };
+
+            if let Some(value) = value {
+                if let Ok(us) = value.parse::<i64>() {
                     // FFmpeg can sometimes output negative values at the start
-                    let ms = ms.max(0) as u64;
-                    self.progress_ms.store(ms, Ordering::Relaxed);
+                    out_time_us = us.max(0) as u64;
+                    if self.ladder.is_none() {
+                        self.progress_ms.store(out_time_us, Ordering::Relaxed);
+                    }
                 }
             } else if line.starts_with("progress=") && line.ends_with("end") {
-                // Done
+                // Done, every rung included
+                self.progress_ms.store(out_time_us, Ordering::Relaxed);
                 break;
+            } else if let (Some(ladder), true) = (&self.ladder, line.starts_with("progress=")) {
+                // End of a progress block: out_time follows the stream furthest
+                // ahead, so count what every rung has actually written
+                let us = ladder.completed_us().await.unwrap_or(out_time_us);
+                self.progress_ms.store(us.min(out_time_us), Ordering::Relaxed);
             }
         }
 
         Ok(())
     }
 }
+
+impl Default for FfmpegProgressTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Encode progress across the rungs of an HLS ladder.
+///
+/// FFmpeg rewrites each variant playlist after every finished segment, so the
+/// segment durations listed there are the media time that rung has done.
+/// Rungs are weighted by how much work they are, so the combined time moves
+/// at the pace of the whole ladder rather than of its fastest stream.
+#[derive(Debug, Clone, Default)]
+pub struct LadderProgress {
+    /// Variant playlist and relative encode cost per rung
+    rungs: Vec<(PathBuf, f64)>,
+}
+
+impl LadderProgress {
+    pub fn new(rungs: Vec<(PathBuf, f64)>) -> Self {
+        Self {
+            rungs: rungs.into_iter().filter(|(_, w)| *w > 0.0).collect(),
+        }
+    }
+
+    /// Weighted media time written across all rungs, in microseconds.
+    /// Returns `None` when no rung carries any weight.
+    pub async fn completed_us(&self) -> Option<u64> {
+        let total_weight: f64 = self.rungs.iter().map(|(_, w)| w).sum();
+        if total_weight <= 0.0 {
+            return None;
+        }
+        let mut weighted = 0.0;
+        for (playlist, weight) in &self.rungs {
+            // Not written before its first segment
+            let content = tokio::fs::read_to_string(playlist).await.unwrap_or_default();
+            weighted += playlist_duration_us(&content) as f64 * weight;
+        }
+        Some((weighted / total_weight) as u64)
+    }
+}
+
+/// Sum of the `#EXTINF` segment durations in a media playlist, in microseconds
+pub fn playlist_duration_us(playlist: &str) -> u64 {
+    playlist
+        .lines()
+        .filter_map(|line| line.strip_prefix("#EXTINF:"))
+        .filter_map(|rest| rest.split(',').next()?.trim().parse::<f64>().ok())
+        .map(|secs| (secs.max(0.0) * 1_000_000.0) as u64)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_playlist_duration() {
+        let playlist = "#EXTM3U\n#EXT-X-TARGETDURATION:6\n\
+                        #EXTINF:6.006000,\nstream_0_000.m4s\n\
+                        #EXTINF:3.5,\nstream_0_001.m4s\n";
+        assert_eq!(playlist_duration_us(playlist), 9_506_000);
+        assert_eq!(playlist_duration_us(""), 0);
+    }
+
+    #[tokio::test]
+    async fn test_ladder_weighs_rungs() {
+        let dir = tempfile::tempdir().unwrap();
+        let low = dir.path().join("stream_0.m3u8");
+        let high = dir.path().join("stream_1.m3u8");
+        let copied = dir.path().join("stream_2.m3u8");
+        std::fs::write(&low, "#EXTINF:6.0,\na.m4s\n#EXTINF:6.0,\nb.m4s\n").unwrap();
+        std::fs::write(&copied, "#EXTINF:60.0,\na.m4s\n").unwrap();
+
+        // The high rung costs three times as much and hasn't written anything
+        let ladder = LadderProgress::new(vec![(low, 1.0), (high.clone(), 3.0), (copied, 0.0)]);
+        assert_eq!(ladder.completed_us().await, Some(3_000_000));
+
+        std::fs::write(&high, "#EXTINF:6.0,\na.m4s\n").unwrap();
+        assert_eq!(ladder.completed_us().await, Some(7_500_000));
+
+        assert_eq!(LadderProgress::default().completed_us().await, None);
+    }
+}
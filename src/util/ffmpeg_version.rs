@@ -0,0 +1,217 @@
+//! FFmpeg version detection and compatibility checks.
+//!
+//! The installed FFmpeg is probed once at startup. Versions below
+//! [`MIN_FFMPEG_VERSION`] are rejected up front instead of failing mid-job,
+//! and flags or encoders that only exist in newer releases are gated on the
+//! detected version.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// Oldest supported FFmpeg release (fMP4 HLS, `libsvtav1`, `-progress` with `out_time_us`)
+pub const MIN_FFMPEG_VERSION: FfmpegVersion = FfmpegVersion { major: 4, minor: 4 };
+
+/// Encoders that only exist from a given FFmpeg release onward
+const ENCODER_MIN_VERSIONS: &[(&str, FfmpegVersion)] = &[
+    ("av1_nvenc", FfmpegVersion { major: 6, minor: 0 }),
+    ("av1_qsv", FfmpegVersion { major: 6, minor: 0 }),
+    ("av1_vaapi", FfmpegVersion { major: 6, minor: 1 }),
+];
+
+/// First release with `-fps_mode` (replacing the deprecated `-vsync`)
+const FPS_MODE_VERSION: FfmpegVersion = FfmpegVersion { major: 5, minor: 1 };
+
+/// Version of the FFmpeg binary in use, recorded at discovery
+static INSTALLED: OnceLock<FfmpegVersion> = OnceLock::new();
+
+/// Encoders compiled into the FFmpeg binary in use, recorded at discovery
+static COMPILED_ENCODERS: OnceLock<HashSet<String>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FfmpegVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl FfmpegVersion {
+    /// Parse the first line of `ffmpeg -version`.
+    ///
+    /// Handles release builds ("ffmpeg version 6.1.1-3ubuntu5") and tagged
+    /// builds ("ffmpeg version n7.0"). Git snapshots ("N-113-g...") carry no
+    /// release number and return `None`.
+    pub fn parse(output: &str) -> Option<Self> {
+        let version = output
+            .lines()
+            .next()?
+            .split_whitespace()
+            .skip_while(|w| *w != "version")
+            .nth(1)?;
+        let version = version.strip_prefix('n').unwrap_or(version);
+
+        let mut parts = version
+            .split(|c: char| !c.is_ascii_digit())
+            .take_while(|p| !p.is_empty());
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+        Some(Self { major, minor })
+    }
+
+    /// Run `<ffmpeg> -version` and parse the result.
+    pub fn detect(ffmpeg: &Path) -> Option<Self> {
+        let output = Command::new(ffmpeg).arg("-version").output().ok()?;
+        Self::parse(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    pub fn is_supported(&self) -> bool {
+        *self >= MIN_FFMPEG_VERSION
+    }
+
+    pub fn has_fps_mode(&self) -> bool {
+        *self >= FPS_MODE_VERSION
+    }
+
+    /// Whether this release ships the named encoder (unknown encoders are assumed present)
+    pub fn has_encoder(&self, encoder: &str) -> bool {
+        ENCODER_MIN_VERSIONS
+            .iter()
+            .find(|(name, _)| *name == encoder)
+            .is_none_or(|(_, min)| self >= min)
+    }
+}
+
+impl fmt::Display for FfmpegVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Record the version of the FFmpeg binary chosen at discovery.
+pub fn set_installed(version: FfmpegVersion) {
+    let _ = INSTALLED.set(version);
+}
+
+/// The detected FFmpeg version (`None` for git snapshots or before discovery)
+pub fn installed() -> Option<FfmpegVersion> {
+    INSTALLED.get().copied()
+}
+
+/// Flag selecting output frame timing: `-fps_mode` from 5.1, `-vsync` before.
+pub fn fps_mode_flag() -> &'static str {
+    if installed().is_none_or(|v| v.has_fps_mode()) {
+        "-fps_mode"
+    } else {
+        "-vsync"
+    }
+}
+
+/// Parse the encoder names from `ffmpeg -encoders` output.
+pub fn parse_encoders(output: &str) -> HashSet<String> {
+    output
+        .lines()
+        .skip_while(|l| !l.trim_start().starts_with("------"))
+        .skip(1)
+        .filter_map(|l| {
+            let mut parts = l.split_whitespace();
+            let flags = parts.next()?;
+            (flags.len() == 6).then(|| parts.next().map(str::to_string))?
+        })
+        .collect()
+}
+
+/// Run `<ffmpeg> -encoders` and parse the result.
+pub fn detect_encoders(ffmpeg: &Path) -> Option<HashSet<String>> {
+    let output = Command::new(ffmpeg)
+        .args(["-hide_banner", "-encoders"])
+        .output()
+        .ok()?;
+    let encoders = parse_encoders(&String::from_utf8_lossy(&output.stdout));
+    (!encoders.is_empty()).then_some(encoders)
+}
+
+/// Record the encoders compiled into the FFmpeg binary chosen at discovery.
+pub fn set_compiled_encoders(encoders: HashSet<String>) {
+    let _ = COMPILED_ENCODERS.set(encoders);
+}
+
+/// Whether the installed FFmpeg ships the named encoder.
+///
+/// Checks both the release (for encoders newer than our minimum) and the
+/// build's encoder list (for optional libraries like SVT-AV1). Assumes yes
+/// when either is unknown (e.g. git builds, which are newer than any release).
+pub fn encoder_available(encoder: &str) -> bool {
+    installed().is_none_or(|v| v.has_encoder(encoder))
+        && COMPILED_ENCODERS
+            .get()
+            .is_none_or(|encoders| encoders.contains(encoder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_versions() {
+        let parse = |s: &str| FfmpegVersion::parse(s);
+        assert_eq!(
+            parse("ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023 the FFmpeg developers"),
+            Some(FfmpegVersion { major: 6, minor: 1 })
+        );
+        assert_eq!(
+            parse("ffmpeg version n7.0 Copyright (c) 2000-2024"),
+            Some(FfmpegVersion { major: 7, minor: 0 })
+        );
+        assert_eq!(
+            parse("ffmpeg version 4.4.2-0ubuntu0.22.04.1"),
+            Some(FfmpegVersion { major: 4, minor: 4 })
+        );
+        assert_eq!(
+            parse("ffmpeg version 7.1-static https://johnvansickle.com/ffmpeg/"),
+            Some(FfmpegVersion { major: 7, minor: 1 })
+        );
+        assert_eq!(parse("ffmpeg version N-113245-gd5c1d4a Copyright"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn test_version_floor() {
+        assert!(!FfmpegVersion { major: 4, minor: 3 }.is_supported());
+        assert!(FfmpegVersion { major: 4, minor: 4 }.is_supported());
+        assert!(FfmpegVersion { major: 7, minor: 0 }.is_supported());
+    }
+
+    #[test]
+    fn test_fps_mode_gating() {
+        assert!(!FfmpegVersion { major: 5, minor: 0 }.has_fps_mode());
+        assert!(FfmpegVersion { major: 5, minor: 1 }.has_fps_mode());
+        assert!(FfmpegVersion { major: 6, minor: 0 }.has_fps_mode());
+    }
+
+    #[test]
+    fn test_parse_encoders() {
+        let output = "Encoders:
+ V..... = Video
+ ------
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC (codec h264)
+ V....D libsvtav1            SVT-AV1(Scalable Video Technology for AV1) encoder (codec av1)
+ A....D aac                  AAC (Advanced Audio Coding)
+";
+        let encoders = parse_encoders(output);
+        assert_eq!(encoders.len(), 3);
+        assert!(encoders.contains("libsvtav1"));
+        assert!(!encoders.contains("librav1e"));
+        assert!(!encoders.contains("="));
+    }
+
+    #[test]
+    fn test_encoder_gating() {
+        let v5 = FfmpegVersion { major: 5, minor: 1 };
+        let v6 = FfmpegVersion { major: 6, minor: 0 };
+        assert!(!v5.has_encoder("av1_nvenc"));
+        assert!(v6.has_encoder("av1_nvenc"));
+        assert!(!v6.has_encoder("av1_vaapi"));
+        assert!(v5.has_encoder("libx264"));
+    }
+}
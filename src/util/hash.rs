@@ -1,5 +1,7 @@
+use futures::stream::{self, StreamExt, TryStreamExt};
 use sha2::{Digest, Sha256};
-use std::path::Path;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 
@@ -20,6 +22,52 @@ pub async fn hash_file(path: &Path) -> std::io::Result<String> {
     Ok(hex::encode(hasher.finalize()))
 }
 
+/// Compute SHA-256 hashes of many files concurrently, at most one per CPU.
+///
+/// Hashes come back in the order of `paths`.
+pub async fn hash_files(paths: &[PathBuf]) -> std::io::Result<Vec<String>> {
+    let limit = std::thread::available_parallelism().map_or(4, |n| n.get());
+    stream::iter(paths.iter().cloned())
+        .map(|path| async move {
+            tokio::task::spawn_blocking(move || hash_file_blocking(&path))
+                .await
+                .map_err(std::io::Error::other)?
+        })
+        .buffered(limit)
+        .try_collect()
+        .await
+}
+
+fn hash_file_blocking(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Compute the hex SHA-256 of an in-memory buffer.
+pub fn hash_bytes(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Extract the SHA-256 from a Blossom blob URL (`https://server/<sha256>[.ext]`).
+pub fn sha256_from_url(url: &str) -> Option<String> {
+    let path = url::Url::parse(url).ok()?.path().to_string();
+    let name = path.rsplit('/').next()?;
+    let stem = name.split('.').next()?;
+    (stem.len() == 64 && stem.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| stem.to_lowercase())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -45,4 +93,45 @@ mod tests {
             "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
         );
     }
+
+    #[tokio::test]
+    async fn test_hash_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = (0..10).map(|i| dir.path().join(format!("seg_{}.m4s", i))).collect();
+        for (i, path) in paths.iter().enumerate() {
+            std::fs::write(path, format!("segment {}", i)).unwrap();
+        }
+
+        let hashes = hash_files(&paths).await.unwrap();
+        assert_eq!(hashes.len(), paths.len());
+        for (path, hash) in paths.iter().zip(&hashes) {
+            assert_eq!(*hash, hash_file(path).await.unwrap());
+        }
+        assert_eq!(hashes[3], hash_bytes(b"segment 3"));
+
+        assert!(hash_files(&[dir.path().join("missing.m4s")]).await.is_err());
+    }
+
+    #[test]
+    fn test_sha256_from_url() {
+        let hash = "a".repeat(64);
+        assert_eq!(
+            sha256_from_url(&format!("https://blossom.example.com/{}.mp4", hash)),
+            Some(hash.clone())
+        );
+        assert_eq!(
+            sha256_from_url(&format!("https://blossom.example.com/{}", hash)),
+            Some(hash)
+        );
+        assert_eq!(sha256_from_url("https://example.com/video.mp4"), None);
+        assert_eq!(sha256_from_url("not a url"), None);
+    }
+
+    #[test]
+    fn test_hash_bytes() {
+        assert_eq!(
+            hash_bytes(b"hello"),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
 }
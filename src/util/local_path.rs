@@ -0,0 +1,168 @@
+//! Local file path inputs.
+//!
+//! Self-hosted operators can let jobs reference files already on local disk
+//! (or a mounted NAS) with an `i` tag of type `path`, or a `url` input with a
+//! `file://` URL. Only files inside the configured `LOCAL_INPUT_DIRS` are
+//! accepted.
+
+use std::path::{Component, Path, PathBuf};
+use url::Url;
+
+/// Whether a `url` input points at the local filesystem
+pub fn is_file_url(input: &str) -> bool {
+    input
+        .get(..7)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("file://"))
+}
+
+/// Resolve a `file://` URL input against the allowlist.
+///
+/// Only local URLs (`file:///path` or `file://localhost/path`) are accepted.
+pub fn resolve_file_url(input: &str, allowed_dirs: &[PathBuf]) -> Result<PathBuf, String> {
+    let path = Url::parse(input)
+        .ok()
+        .and_then(|url| url.to_file_path().ok())
+        .ok_or_else(|| "Invalid file URL".to_string())?;
+    resolve_local_input(&path.to_string_lossy(), allowed_dirs)
+}
+
+/// Resolve a requested local input path against the allowlist.
+///
+/// The path is checked lexically before the filesystem is touched, so
+/// requesters can't probe which paths exist outside the permitted
+/// directories. It is then canonicalized and checked again so symlinks
+/// can't escape them either. Returns a user-facing error message.
+pub fn resolve_local_input(requested: &str, allowed_dirs: &[PathBuf]) -> Result<PathBuf, String> {
+    if allowed_dirs.is_empty() {
+        return Err("Local path inputs are not enabled on this DVM".to_string());
+    }
+
+    let requested = Path::new(requested);
+    if !requested.is_absolute() {
+        return Err("Local input paths must be absolute".to_string());
+    }
+
+    let outside = || "Local input path is outside the permitted directories".to_string();
+    let lexical = normalize(requested);
+    let lexically_permitted = allowed_dirs.iter().any(|dir| {
+        lexical.starts_with(normalize(dir))
+            || dir.canonicalize().is_ok_and(|dir| lexical.starts_with(dir))
+    });
+    if !lexically_permitted {
+        return Err(outside());
+    }
+
+    let resolved = requested
+        .canonicalize()
+        .map_err(|_| "Local input file not found".to_string())?;
+
+    let permitted = allowed_dirs
+        .iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .any(|dir| resolved.starts_with(dir));
+    if !permitted {
+        return Err(outside());
+    }
+
+    if !resolved.is_file() {
+        return Err("Local input path is not a file".to_string());
+    }
+
+    Ok(resolved)
+}
+
+/// `path` with `.` and `..` components resolved without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_within_allowlist() {
+        let allowed = tempfile::tempdir().unwrap();
+        let file = allowed.path().join("video.mp4");
+        std::fs::write(&file, b"data").unwrap();
+        let dirs = vec![allowed.path().to_path_buf()];
+
+        let resolved = resolve_local_input(file.to_str().unwrap(), &dirs).unwrap();
+        assert_eq!(resolved, file.canonicalize().unwrap());
+
+        // Directories and missing files are rejected
+        assert!(resolve_local_input(allowed.path().to_str().unwrap(), &dirs).is_err());
+        let missing = allowed.path().join("missing.mp4");
+        assert!(resolve_local_input(missing.to_str().unwrap(), &dirs).is_err());
+    }
+
+    #[test]
+    fn test_resolve_file_url() {
+        let allowed = tempfile::tempdir().unwrap();
+        let file = allowed.path().join("my video.mp4");
+        std::fs::write(&file, b"data").unwrap();
+        let dirs = vec![allowed.path().to_path_buf()];
+
+        let url = Url::from_file_path(&file).unwrap().to_string();
+        assert!(is_file_url(&url));
+        assert!(url.contains("%20"));
+        assert_eq!(
+            resolve_file_url(&url, &dirs).unwrap(),
+            file.canonicalize().unwrap()
+        );
+
+        assert!(!is_file_url("https://example.com/video.mp4"));
+        assert!(resolve_file_url("file://remote.example.com/video.mp4", &dirs).is_err());
+        assert!(resolve_file_url(&url, &[]).is_err());
+    }
+
+    #[test]
+    fn test_resolve_rejects_escapes() {
+        let root = tempfile::tempdir().unwrap();
+        let allowed = root.path().join("media");
+        std::fs::create_dir(&allowed).unwrap();
+        let outside = root.path().join("secret.mp4");
+        std::fs::write(&outside, b"data").unwrap();
+        let dirs = vec![allowed.clone()];
+
+        let traversal = allowed.join("..").join("secret.mp4");
+        assert!(resolve_local_input(traversal.to_str().unwrap(), &dirs).is_err());
+        assert!(resolve_local_input(outside.to_str().unwrap(), &dirs).is_err());
+        assert!(resolve_local_input("media/secret.mp4", &dirs).is_err());
+        assert!(resolve_local_input(outside.to_str().unwrap(), &[]).is_err());
+    }
+
+    #[test]
+    fn test_resolve_hides_existence_outside_allowlist() {
+        let root = tempfile::tempdir().unwrap();
+        let allowed = root.path().join("media");
+        std::fs::create_dir(&allowed).unwrap();
+        let existing = root.path().join("secret.mp4");
+        std::fs::write(&existing, b"data").unwrap();
+        let dirs = vec![allowed.clone()];
+
+        // Existing and missing files outside the allowlist fail the same way
+        let missing = root.path().join("missing.mp4");
+        let traversal = allowed.join("..").join("missing.mp4");
+        let error = resolve_local_input(existing.to_str().unwrap(), &dirs).unwrap_err();
+        assert_eq!(resolve_local_input(missing.to_str().unwrap(), &dirs).unwrap_err(), error);
+        assert_eq!(resolve_local_input(traversal.to_str().unwrap(), &dirs).unwrap_err(), error);
+
+        // Inside it, missing files are reported as such
+        let inside = allowed.join("sub").join("..").join("missing.mp4");
+        assert_eq!(
+            resolve_local_input(inside.to_str().unwrap(), &dirs).unwrap_err(),
+            "Local input file not found"
+        );
+    }
+}
@@ -1,7 +1,16 @@
+pub mod blurhash;
+pub mod disk;
 pub mod ffmpeg_discovery;
 pub mod ffmpeg_progress;
+pub mod ffmpeg_version;
 pub mod hash;
+pub mod local_path;
+pub mod proxy;
+pub mod remote_url;
+pub mod resources;
+pub mod sealed;
 pub mod temp;
+pub mod zip;
 
 pub use ffmpeg_discovery::FfmpegPaths;
 pub use hash::hash_file;
@@ -0,0 +1,181 @@
+//! Outbound proxy support.
+//!
+//! Some operators must route all DVM traffic through a proxy or Tor. Setting
+//! `PROXY_URL` (e.g. `socks5h://127.0.0.1:9050` or `http://proxy:3128`)
+//! applies it to every outbound connection we control:
+//!
+//! - HTTP downloads, probes and Blossom uploads (any scheme reqwest supports)
+//! - Relay websockets (SOCKS5 proxies only; nostr-sdk has no HTTP proxy mode)
+//! - FFmpeg and yt-dlp, via the standard `http_proxy`/`https_proxy` variables
+//!   (HTTP proxies only; FFmpeg can't speak SOCKS)
+//!
+//! Separately, `TOR_PROXY` (a Tor SOCKS port such as `socks5h://127.0.0.1:9050`)
+//! carries traffic to `.onion` relays and Blossom servers while clearnet
+//! destinations keep using `PROXY_URL` or a direct connection.
+
+use nostr_sdk::client::{Connection, ConnectionTarget};
+use nostr_sdk::{Client, Keys, Options};
+use std::net::{SocketAddr, ToSocketAddrs};
+use tracing::warn;
+use url::Url;
+
+/// Hosts reached directly even when a proxy is configured
+const LOOPBACK_HOSTS: &str = "localhost,127.0.0.1";
+
+/// The configured outbound proxy, if any.
+pub fn proxy_url() -> Option<Url> {
+    url_from_env("PROXY_URL")
+}
+
+/// The Tor SOCKS proxy used for `.onion` destinations, if any.
+pub fn tor_proxy_url() -> Option<Url> {
+    url_from_env("TOR_PROXY")
+}
+
+fn url_from_env(var: &str) -> Option<Url> {
+    let raw = std::env::var(var).ok()?;
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return None;
+    }
+    match Url::parse(raw) {
+        Ok(url) => Some(url),
+        Err(e) => {
+            warn!(proxy = raw, error = %e, "Ignoring invalid {}", var);
+            None
+        }
+    }
+}
+
+/// Whether a URL points at a Tor onion service
+pub fn is_onion(url: &Url) -> bool {
+    url.host_str().is_some_and(|h| h.ends_with(".onion"))
+}
+
+/// A reqwest client builder routed through the configured proxy.
+///
+/// Without `PROXY_URL`, reqwest's own handling of the standard proxy
+/// environment variables applies.
+pub fn http_client_builder() -> reqwest::ClientBuilder {
+    let mut builder = reqwest::Client::builder();
+
+    // reqwest uses the first proxy that matches, so onion routing goes first
+    if let Some(tor) = tor_proxy_url() {
+        let onion = reqwest::Proxy::custom(move |url| is_onion(url).then(|| tor.clone()));
+        builder = builder.proxy(onion);
+    }
+
+    let Some(url) = proxy_url() else {
+        return builder;
+    };
+    match reqwest::Proxy::all(url.as_str()) {
+        // Loopback services (e.g. sealed inputs) are never proxied
+        Ok(proxy) => builder.proxy(proxy.no_proxy(reqwest::NoProxy::from_string(LOOPBACK_HOSTS))),
+        Err(e) => {
+            warn!(proxy = %url, error = %e, "Unsupported proxy for HTTP traffic");
+            builder
+        }
+    }
+}
+
+/// A reqwest client routed through the configured proxy.
+pub fn http_client() -> reqwest::Client {
+    http_client_builder().build().unwrap_or_else(|e| {
+        warn!(error = %e, "Failed to build proxied HTTP client, connecting directly");
+        reqwest::Client::new()
+    })
+}
+
+/// A nostr client whose relay connections go through the configured proxy.
+///
+/// nostr-sdk takes a single proxy: `PROXY_URL` carries every relay when it
+/// is SOCKS5 (so it must be Tor too for `.onion` relays to work), otherwise
+/// `TOR_PROXY` carries just the `.onion` relays.
+pub fn nostr_client(keys: &Keys) -> Client {
+    let connection = if let Some(addr) = proxy_url().and_then(|url| relay_proxy_addr(&url)) {
+        Connection::new().proxy(addr)
+    } else if let Some(addr) = tor_proxy_url().and_then(|url| relay_proxy_addr(&url)) {
+        Connection::new()
+            .proxy(addr)
+            .target(ConnectionTarget::Onion)
+    } else {
+        return Client::new(keys.clone());
+    };
+    Client::with_opts(keys.clone(), Options::new().connection(connection))
+}
+
+/// The SOCKS5 address relays should connect through, if the proxy is one.
+fn relay_proxy_addr(url: &Url) -> Option<SocketAddr> {
+    if !matches!(url.scheme(), "socks5" | "socks5h") {
+        warn!(
+            proxy = %url,
+            "Relay connections only support SOCKS5 proxies; connecting to relays directly"
+        );
+        return None;
+    }
+    let host = url.host_str()?;
+    let port = url.port().unwrap_or(1080);
+    let addr = (host, port).to_socket_addrs().ok()?.next();
+    if addr.is_none() {
+        warn!(proxy = %url, "Could not resolve SOCKS proxy host for relay connections");
+    }
+    addr
+}
+
+/// Point child processes (FFmpeg, yt-dlp) at the configured proxy.
+///
+/// Sets `http_proxy` and `https_proxy` (and `no_proxy` for loopback)
+/// unless the operator already set them.
+/// FFmpeg only speaks HTTP proxies, so with a SOCKS proxy it connects
+/// directly to URL inputs it streams itself.
+pub fn export_to_child_env() {
+    let Some(url) = proxy_url() else {
+        return;
+    };
+    if !matches!(url.scheme(), "http" | "https") {
+        warn!(
+            proxy = %url,
+            "FFmpeg cannot use SOCKS proxies; URL inputs it streams will bypass the proxy"
+        );
+        return;
+    }
+    for (var, value) in [
+        ("http_proxy", url.as_str()),
+        ("https_proxy", url.as_str()),
+        ("no_proxy", LOOPBACK_HOSTS),
+    ] {
+        if std::env::var_os(var).is_none() {
+            // Called once at startup, before any child process is spawned
+            unsafe { std::env::set_var(var, value) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relay_proxy_addr() {
+        let socks = Url::parse("socks5h://127.0.0.1:9050").unwrap();
+        assert_eq!(
+            relay_proxy_addr(&socks),
+            Some("127.0.0.1:9050".parse().unwrap())
+        );
+
+        let default_port = Url::parse("socks5://127.0.0.1").unwrap();
+        assert_eq!(relay_proxy_addr(&default_port).map(|a| a.port()), Some(1080));
+
+        let http = Url::parse("http://127.0.0.1:3128").unwrap();
+        assert_eq!(relay_proxy_addr(&http), None);
+    }
+
+    #[test]
+    fn test_is_onion() {
+        let onion = "wss://relayexampleabcdefghijklmnopqrstuvwxyz234567abcdefghijkl.onion";
+        assert!(is_onion(&Url::parse(onion).unwrap()));
+        assert!(is_onion(&Url::parse("http://blossom.onion:8080/upload").unwrap()));
+        assert!(!is_onion(&Url::parse("wss://nos.lol").unwrap()));
+        assert!(!is_onion(&Url::parse("https://onion.example.com").unwrap()));
+    }
+}
@@ -0,0 +1,89 @@
+//! Remote URL inputs the DVM may contact before a job is accepted.
+//!
+//! Pricing a public request probes its input, on behalf of a requester who
+//! hasn't paid or been approved yet. Only plain `http(s)://` URLs on public
+//! hosts are probed then; anything else is priced without its metadata.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use url::{Host, Url};
+
+/// Whether `input` is an `http(s)://` URL whose host isn't loopback, a
+/// private or link-local address, or a local-only name
+pub fn is_public_http_url(input: &str) -> bool {
+    let Ok(url) = Url::parse(input) else {
+        return false;
+    };
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    match url.host() {
+        Some(Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            !(domain == "localhost"
+                || domain.ends_with(".localhost")
+                || domain.ends_with(".local")
+                || domain.ends_with(".internal"))
+        }
+        Some(Host::Ipv4(ip)) => is_public_ipv4(ip),
+        Some(Host::Ipv6(ip)) => is_public_ipv6(ip),
+        None => false,
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        // Carrier-grade NAT (100.64.0.0/10)
+        || (ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64))
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return is_public_ip(IpAddr::V4(v4));
+    }
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        // Unique local (fc00::/7) and link-local (fe80::/10)
+        || first & 0xfe00 == 0xfc00
+        || first & 0xffc0 == 0xfe80)
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => is_public_ipv6(ip),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_public_http_url() {
+        assert!(is_public_http_url("https://example.com/video.mp4"));
+        assert!(is_public_http_url("http://93.184.216.34/video.mp4"));
+
+        // Not HTTP
+        assert!(!is_public_http_url("/etc/passwd"));
+        assert!(!is_public_http_url("file:///videos/a.mp4"));
+        assert!(!is_public_http_url("concat:a.mp4|b.mp4"));
+        assert!(!is_public_http_url("ftp://example.com/video.mp4"));
+
+        // Internal hosts
+        assert!(!is_public_http_url("http://localhost:8080/admin"));
+        assert!(!is_public_http_url("http://127.0.0.1/video.mp4"));
+        assert!(!is_public_http_url("http://10.0.0.5/video.mp4"));
+        assert!(!is_public_http_url("http://192.168.1.1/video.mp4"));
+        assert!(!is_public_http_url("http://169.254.169.254/latest/meta-data"));
+        assert!(!is_public_http_url("http://100.64.0.1/video.mp4"));
+        assert!(!is_public_http_url("http://[::1]/video.mp4"));
+        assert!(!is_public_http_url("http://[fd00::1]/video.mp4"));
+        assert!(!is_public_http_url("http://[::ffff:127.0.0.1]/video.mp4"));
+        assert!(!is_public_http_url("http://nas.local/video.mp4"));
+    }
+}
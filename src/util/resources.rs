@@ -0,0 +1,163 @@
+//! Point-in-time snapshot of host resources.
+//!
+//! Captured when a job fails so operators can tell resource exhaustion
+//! (full disk, out of memory, saturated GPU) apart from bad inputs.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use tokio::process::Command as TokioCommand;
+
+use crate::util::disk::disk_space;
+
+/// Host resources at the moment a job failed
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ResourceSnapshot {
+    /// Free bytes on the temp directory's filesystem
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disk_free_bytes: Option<u64>,
+    /// Memory available to new processes (MemAvailable on Linux)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_available_bytes: Option<u64>,
+    /// Free GPU memory in MiB (NVIDIA only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gpu_memory_free_mb: Option<u64>,
+    /// Total GPU memory in MiB (NVIDIA only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gpu_memory_total_mb: Option<u64>,
+    /// 1-minute load average
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_average: Option<f64>,
+    /// Jobs running when the snapshot was taken (including the failed one)
+    pub jobs_active: u32,
+}
+
+impl ResourceSnapshot {
+    /// Capture a snapshot. Values that can't be read on this platform are left empty.
+    pub async fn capture(temp_dir: &Path, jobs_active: u32) -> Self {
+        let (gpu_memory_free_mb, gpu_memory_total_mb) = match nvidia_gpu_memory().await {
+            Some((free, total)) => (Some(free), Some(total)),
+            None => (None, None),
+        };
+
+        Self {
+            disk_free_bytes: disk_space(temp_dir).map(|s| s.free_bytes),
+            memory_available_bytes: memory_available(),
+            gpu_memory_free_mb,
+            gpu_memory_total_mb,
+            load_average: load_average(),
+            jobs_active,
+        }
+    }
+}
+
+impl fmt::Display for ResourceSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const MIB: u64 = 1024 * 1024;
+        let mut parts = Vec::new();
+        if let Some(b) = self.disk_free_bytes {
+            parts.push(format!("disk free {} MiB", b / MIB));
+        }
+        if let Some(b) = self.memory_available_bytes {
+            parts.push(format!("memory available {} MiB", b / MIB));
+        }
+        if let (Some(free), Some(total)) = (self.gpu_memory_free_mb, self.gpu_memory_total_mb) {
+            parts.push(format!("GPU memory free {}/{} MiB", free, total));
+        }
+        if let Some(load) = self.load_average {
+            parts.push(format!("load {:.2}", load));
+        }
+        parts.push(format!("{} active job(s)", self.jobs_active));
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Parse `MemAvailable` from `/proc/meminfo` into bytes.
+pub fn parse_meminfo_available(meminfo: &str) -> Option<u64> {
+    let line = meminfo.lines().find(|l| l.starts_with("MemAvailable:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// Parse the first GPU's `memory.free, memory.total` from `nvidia-smi` CSV output (MiB).
+pub fn parse_nvidia_smi_memory(output: &str) -> Option<(u64, u64)> {
+    let mut fields = output.lines().next()?.split(',').map(|f| f.trim().parse::<u64>());
+    let free = fields.next()?.ok()?;
+    let total = fields.next()?.ok()?;
+    Some((free, total))
+}
+
+/// Memory available to new processes (Linux only).
+pub fn memory_available() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        parse_meminfo_available(&std::fs::read_to_string("/proc/meminfo").ok()?)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+fn load_average() -> Option<f64> {
+    #[cfg(unix)]
+    {
+        let mut loads = [0f64; 3];
+        let n = unsafe { libc::getloadavg(loads.as_mut_ptr(), 3) };
+        (n > 0).then_some(loads[0])
+    }
+
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+async fn nvidia_gpu_memory() -> Option<(u64, u64)> {
+    let output = TokioCommand::new("nvidia-smi")
+        .args([
+            "--query-gpu=memory.free,memory.total",
+            "--format=csv,noheader,nounits",
+        ])
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_nvidia_smi_memory(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_meminfo_available() {
+        let meminfo = "MemTotal:       16318480 kB\nMemFree:         1204000 kB\nMemAvailable:    8123456 kB\n";
+        assert_eq!(parse_meminfo_available(meminfo), Some(8_123_456 * 1024));
+        assert_eq!(parse_meminfo_available("MemTotal: 1 kB\n"), None);
+    }
+
+    #[test]
+    fn test_parse_nvidia_smi_memory() {
+        assert_eq!(parse_nvidia_smi_memory("812, 8192\n"), Some((812, 8192)));
+        assert_eq!(parse_nvidia_smi_memory("[N/A], 8192\n"), None);
+        assert_eq!(parse_nvidia_smi_memory(""), None);
+    }
+
+    #[test]
+    fn test_snapshot_display() {
+        let snapshot = ResourceSnapshot {
+            disk_free_bytes: Some(512 * 1024 * 1024),
+            load_average: Some(3.5),
+            jobs_active: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            snapshot.to_string(),
+            "disk free 512 MiB, load 3.50, 2 active job(s)"
+        );
+    }
+}
@@ -0,0 +1,388 @@
+//! Encrypted scratch copies of job inputs.
+//!
+//! With `encrypt_scratch` on, inputs the DVM keeps in its temp dir (fetched
+//! from a platform or assembled from an archive) are sealed with a per-job
+//! ChaCha20 key that only ever lives in memory once they're complete. FFmpeg
+//! and ffprobe read them through a loopback HTTP endpoint that decrypts the
+//! requested byte ranges, so the input doesn't sit on disk in the clear for
+//! the length of the encode.
+//!
+//! This is not protection against recovery from disk: the fetcher and the
+//! archive assembly write the plaintext first, a crash before sealing
+//! leaves it behind, and the unlinked plaintext stays in free blocks until
+//! overwritten. Outputs aren't sealed at all.
+
+use axum::{
+    body::Body,
+    extract::{Path as UrlPath, State},
+    http::{header, HeaderMap, Response, StatusCode},
+    routing::get,
+    Router,
+};
+use chacha20::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+use chacha20::ChaCha20;
+use futures::stream;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tracing::{debug, error};
+
+/// Bytes encrypted or served per read
+const CHUNK_SIZE: usize = 256 * 1024;
+
+/// URL path prefix sealed inputs are served under
+const ROUTE_PREFIX: &str = "/sealed/";
+
+/// Per-job key for sealing scratch files
+pub struct SealKey {
+    key: [u8; 32],
+    nonce: [u8; 12],
+}
+
+impl SealKey {
+    pub fn generate() -> Self {
+        let mut key = [0u8; 32];
+        let mut nonce = [0u8; 12];
+        rand::rng().fill_bytes(&mut key);
+        rand::rng().fill_bytes(&mut nonce);
+        Self { key, nonce }
+    }
+
+    /// Keystream positioned at `offset` bytes into the file
+    fn cipher_at(&self, offset: u64) -> ChaCha20 {
+        let mut cipher = ChaCha20::new(&self.key.into(), &self.nonce.into());
+        cipher.seek(offset);
+        cipher
+    }
+}
+
+impl fmt::Debug for SealKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SealKey(..)")
+    }
+}
+
+/// A scratch file encrypted with a [`SealKey`]
+#[derive(Debug)]
+pub struct SealedFile {
+    path: PathBuf,
+    key: SealKey,
+    len: u64,
+    /// File name served in the URL, keeping the plaintext's extension
+    name: String,
+}
+
+impl SealedFile {
+    /// Plaintext length in bytes
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Decrypt up to `len` bytes starting at `offset`
+    pub fn read_at(&self, offset: u64, len: usize) -> std::io::Result<Vec<u8>> {
+        let len = len.min(self.len.saturating_sub(offset) as usize);
+        let mut buf = vec![0u8; len];
+        let mut file = std::fs::File::open(&self.path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut buf)?;
+        self.key.cipher_at(offset).apply_keystream(&mut buf);
+        Ok(buf)
+    }
+}
+
+/// Encrypt `plain` into a sibling `.sealed` file and delete the plaintext
+pub async fn seal(plain: &Path, key: SealKey) -> std::io::Result<SealedFile> {
+    let plain = plain.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        // Only the extension survives into the URL, where FFmpeg may use it
+        // to guess the format
+        let name = match plain.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.chars().all(|c| c.is_ascii_alphanumeric()) => format!("input.{}", ext),
+            _ => "input".to_string(),
+        };
+        let path = plain.with_extension("sealed");
+
+        let mut input = std::fs::File::open(&plain)?;
+        let mut output = std::fs::File::create(&path)?;
+        let mut cipher = key.cipher_at(0);
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        let mut len = 0u64;
+        loop {
+            let n = input.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            cipher.apply_keystream(&mut buf[..n]);
+            output.write_all(&buf[..n])?;
+            len += n as u64;
+        }
+        output.sync_all()?;
+        drop(input);
+        std::fs::remove_file(&plain)?;
+
+        Ok(SealedFile {
+            path,
+            key,
+            len,
+            name,
+        })
+    })
+    .await
+    .map_err(std::io::Error::other)?
+}
+
+/// Byte range requested by a `Range` header, clamped to `len`
+fn parse_range(value: &str, len: u64) -> Option<(u64, u64)> {
+    let spec = value.strip_prefix("bytes=")?;
+    // Multiple ranges aren't needed by FFmpeg; serve the first
+    let spec = spec.split(',').next()?.trim();
+    let (start, end) = spec.split_once('-')?;
+    let last = len.checked_sub(1)?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            (len.saturating_sub(suffix), last)
+        }
+        (start, "") => (start.parse().ok()?, last),
+        (start, end) => (start.parse().ok()?, end.parse::<u64>().ok()?.min(last)),
+    };
+    (start <= end).then_some((start, end))
+}
+
+type Registry = Arc<Mutex<HashMap<String, Arc<SealedFile>>>>;
+
+/// Loopback HTTP endpoint serving sealed inputs to FFmpeg
+pub struct SealedInputs {
+    addr: SocketAddr,
+    files: Registry,
+}
+
+impl SealedInputs {
+    /// Listen on an ephemeral loopback port
+    pub async fn start() -> std::io::Result<Arc<Self>> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let addr = listener.local_addr()?;
+        let files: Registry = Arc::default();
+
+        let app = Router::new()
+            .route("/sealed/:token/:name", get(serve))
+            .with_state(files.clone());
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                error!(error = %e, "Sealed input server stopped");
+            }
+        });
+        debug!(%addr, "Serving sealed inputs");
+
+        Ok(Arc::new(Self { addr, files }))
+    }
+
+    /// Serve `file` until the returned handle is dropped
+    pub fn register(self: &Arc<Self>, file: SealedFile) -> SealedInput {
+        let mut token = [0u8; 16];
+        rand::rng().fill_bytes(&mut token);
+        let token = hex::encode(token);
+        let url = format!("http://{}{}{}/{}", self.addr, ROUTE_PREFIX, token, file.name);
+        self.files
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(token.clone(), Arc::new(file));
+        SealedInput {
+            url,
+            token,
+            inputs: self.clone(),
+        }
+    }
+}
+
+/// A sealed input being served; dropping it stops serving and deletes the file
+pub struct SealedInput {
+    url: String,
+    token: String,
+    inputs: Arc<SealedInputs>,
+}
+
+impl SealedInput {
+    /// URL FFmpeg reads the plaintext from
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl Drop for SealedInput {
+    fn drop(&mut self) {
+        let file = self
+            .inputs
+            .files
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.token);
+        if let Some(file) = file {
+            let _ = std::fs::remove_file(&file.path);
+        }
+    }
+}
+
+/// Whether `input` is a sealed input URL handed out by [`SealedInputs`]
+pub fn is_sealed_url(input: &str) -> bool {
+    input
+        .strip_prefix("http://127.0.0.1:")
+        .and_then(|rest| rest.split_once('/'))
+        .is_some_and(|(_, path)| path.starts_with(&ROUTE_PREFIX[1..]))
+}
+
+async fn serve(
+    State(files): State<Registry>,
+    UrlPath((token, _name)): UrlPath<(String, String)>,
+    headers: HeaderMap,
+) -> Response<Body> {
+    let file = files
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&token)
+        .cloned();
+    let Some(file) = file else {
+        return status(StatusCode::NOT_FOUND);
+    };
+
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| parse_range(v, file.len()));
+    let (code, start, end) = match range {
+        Some(Some((start, end))) => (StatusCode::PARTIAL_CONTENT, start, end),
+        Some(None) => {
+            return Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", file.len()))
+                .body(Body::empty())
+                .unwrap_or_else(|_| status(StatusCode::INTERNAL_SERVER_ERROR));
+        }
+        None if file.is_empty() => return status(StatusCode::OK),
+        None => (StatusCode::OK, 0, file.len() - 1),
+    };
+
+    let total = file.len();
+    let body = stream::unfold(start, move |offset| {
+        let file = file.clone();
+        async move {
+            if offset > end {
+                return None;
+            }
+            let len = ((end - offset + 1) as usize).min(CHUNK_SIZE);
+            let chunk = tokio::task::spawn_blocking(move || file.read_at(offset, len))
+                .await
+                .map_err(std::io::Error::other)
+                .and_then(|r| r);
+            match chunk {
+                Ok(chunk) if !chunk.is_empty() => {
+                    let next = offset + chunk.len() as u64;
+                    Some((Ok::<_, std::io::Error>(chunk), next))
+                }
+                Ok(_) => None,
+                Err(e) => Some((Err(e), end + 1)),
+            }
+        }
+    });
+
+    let mut response = Response::builder()
+        .status(code)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_LENGTH, end - start + 1);
+    if code == StatusCode::PARTIAL_CONTENT {
+        response = response.header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", start, end, total),
+        );
+    }
+    response
+        .body(Body::from_stream(body))
+        .unwrap_or_else(|_| status(StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(code)
+        .body(Body::empty())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn sealed_file(dir: &Path, data: &[u8]) -> SealedFile {
+        let plain = dir.join("input.mp4");
+        std::fs::write(&plain, data).unwrap();
+        let sealed = seal(&plain, SealKey::generate()).await.unwrap();
+        assert!(!plain.exists());
+        sealed
+    }
+
+    #[tokio::test]
+    async fn test_seal_and_read_at() {
+        let dir = tempfile::tempdir().unwrap();
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let sealed = sealed_file(dir.path(), &data).await;
+
+        assert_eq!(sealed.len(), data.len() as u64);
+        assert_ne!(std::fs::read(&sealed.path).unwrap(), data);
+        assert_eq!(sealed.read_at(0, 10).unwrap(), &data[..10]);
+        assert_eq!(sealed.read_at(65_537, 1000).unwrap(), &data[65_537..66_537]);
+        assert_eq!(sealed.read_at(99_990, 100).unwrap(), &data[99_990..]);
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=500-", 1000), Some((500, 999)));
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=900-5000", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+        assert_eq!(parse_range("bytes=0-0", 0), None);
+    }
+
+    #[tokio::test]
+    async fn test_serves_plaintext_ranges() {
+        let dir = tempfile::tempdir().unwrap();
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 253) as u8).collect();
+        let inputs = SealedInputs::start().await.unwrap();
+        let input = inputs.register(sealed_file(dir.path(), &data).await);
+        assert!(input.url().ends_with("/input.mp4"));
+        assert!(is_sealed_url(input.url()));
+        assert!(!is_sealed_url("http://127.0.0.1:8080/video.mp4"));
+
+        let client = reqwest::Client::new();
+        let full = client.get(input.url()).send().await.unwrap();
+        assert_eq!(full.status(), 200);
+        assert_eq!(full.bytes().await.unwrap(), data);
+
+        let part = client
+            .get(input.url())
+            .header("Range", "bytes=1000-1999")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(part.status(), 206);
+        assert_eq!(
+            part.headers()["content-range"],
+            format!("bytes 1000-1999/{}", data.len()).as_str()
+        );
+        assert_eq!(part.bytes().await.unwrap(), &data[1000..2000]);
+
+        let url = input.url().to_string();
+        drop(input);
+        assert_eq!(client.get(&url).send().await.unwrap().status(), 404);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
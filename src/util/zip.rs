@@ -0,0 +1,273 @@
+//! Minimal ZIP extraction for archive inputs.
+//!
+//! Only what clients produce for image-sequence uploads is supported:
+//! stored and deflated entries in a single-disk, non-ZIP64 archive. Entries
+//! are flattened to their file name, so paths inside the archive can't
+//! escape the destination directory; archives where two files would flatten
+//! to the same name are refused. The extracted size is capped to guard
+//! against zip bombs.
+
+use flate2::read::DeflateDecoder;
+use std::fs::File;
+use std::collections::HashSet;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::VideoError;
+
+const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+const CENTRAL_SIGNATURE: u32 = 0x0201_4b50;
+const LOCAL_SIGNATURE: u32 = 0x0403_4b50;
+const EOCD_LEN: usize = 22;
+/// End of central directory record plus the longest possible comment
+const EOCD_SEARCH: u64 = EOCD_LEN as u64 + u16::MAX as u64;
+
+/// Limits applied while extracting
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractLimits {
+    pub max_entries: usize,
+    pub max_total_bytes: u64,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 10_000,
+            max_total_bytes: 8 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+struct Entry {
+    name: String,
+    method: u16,
+    compressed_size: u64,
+    local_offset: u64,
+}
+
+fn u16_at(buf: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([buf[at], buf[at + 1]])
+}
+
+fn u32_at(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+}
+
+fn invalid(msg: &str) -> VideoError {
+    VideoError::InvalidInput(format!("Invalid ZIP archive: {}", msg))
+}
+
+/// Read the central directory of the archive
+fn read_entries(file: &mut File) -> Result<Vec<Entry>, VideoError> {
+    let len = file.metadata()?.len();
+    let tail_len = len.min(EOCD_SEARCH);
+    file.seek(SeekFrom::Start(len - tail_len))?;
+    let mut tail = vec![0; tail_len as usize];
+    file.read_exact(&mut tail)?;
+
+    let eocd = (0..tail.len().saturating_sub(EOCD_LEN - 1))
+        .rev()
+        .find(|&i| u32_at(&tail, i) == EOCD_SIGNATURE)
+        .ok_or_else(|| invalid("no end of central directory"))?;
+    let count = u16_at(&tail, eocd + 10) as usize;
+    let dir_size = u32_at(&tail, eocd + 12) as u64;
+    let dir_offset = u32_at(&tail, eocd + 16) as u64;
+    if count == u16::MAX as usize || dir_offset == u32::MAX as u64 {
+        return Err(invalid("ZIP64 archives are not supported"));
+    }
+    if dir_offset + dir_size > len {
+        return Err(invalid("central directory out of bounds"));
+    }
+
+    file.seek(SeekFrom::Start(dir_offset))?;
+    let mut dir = vec![0; dir_size as usize];
+    file.read_exact(&mut dir)?;
+
+    let mut entries = Vec::with_capacity(count);
+    let mut pos = 0;
+    for _ in 0..count {
+        if pos + 46 > dir.len() || u32_at(&dir, pos) != CENTRAL_SIGNATURE {
+            return Err(invalid("corrupt central directory"));
+        }
+        let name_len = u16_at(&dir, pos + 28) as usize;
+        let extra_len = u16_at(&dir, pos + 30) as usize;
+        let comment_len = u16_at(&dir, pos + 32) as usize;
+        let name_end = pos + 46 + name_len;
+        if name_end > dir.len() {
+            return Err(invalid("corrupt central directory"));
+        }
+        entries.push(Entry {
+            name: String::from_utf8_lossy(&dir[pos + 46..name_end]).into_owned(),
+            method: u16_at(&dir, pos + 10),
+            compressed_size: u32_at(&dir, pos + 20) as u64,
+            local_offset: u32_at(&dir, pos + 42) as u64,
+        });
+        pos = name_end + extra_len + comment_len;
+    }
+    Ok(entries)
+}
+
+/// File name an entry is extracted to (`None` for directories and hidden files)
+fn flat_name(entry: &str) -> Option<&str> {
+    let name = entry.rsplit(['/', '\\']).next()?;
+    (!name.is_empty() && !name.starts_with('.')).then_some(name)
+}
+
+/// Extract the files in `archive` into `dest`, returning the extracted paths.
+///
+/// Directory structure is dropped, so two entries sharing a file name (e.g.
+/// `day1/001.jpg` and `day2/001.jpg`) fail the extraction rather than one
+/// silently replacing the other. macOS resource forks (`__MACOSX/`) are
+/// skipped.
+pub fn extract(archive: &Path, dest: &Path, limits: ExtractLimits) -> Result<Vec<PathBuf>, VideoError> {
+    let mut file = File::open(archive)?;
+    let entries = read_entries(&mut file)?;
+    if entries.len() > limits.max_entries {
+        return Err(invalid(&format!("more than {} entries", limits.max_entries)));
+    }
+
+    let mut extracted = Vec::new();
+    let mut names = HashSet::new();
+    let mut total = 0u64;
+    for entry in &entries {
+        if entry.name.starts_with("__MACOSX/") {
+            continue;
+        }
+        let Some(name) = flat_name(&entry.name) else {
+            continue;
+        };
+        if !names.insert(name) {
+            return Err(invalid(&format!("more than one file named {}", name)));
+        }
+
+        let mut header = [0; 30];
+        file.seek(SeekFrom::Start(entry.local_offset))?;
+        file.read_exact(&mut header)?;
+        if u32_at(&header, 0) != LOCAL_SIGNATURE {
+            return Err(invalid("corrupt local header"));
+        }
+        let data_offset =
+            entry.local_offset + 30 + u16_at(&header, 26) as u64 + u16_at(&header, 28) as u64;
+        file.seek(SeekFrom::Start(data_offset))?;
+        let raw = (&mut file).take(entry.compressed_size);
+
+        // One byte over the remaining budget is enough to tell it was exceeded
+        let budget = limits.max_total_bytes - total + 1;
+        let mut reader: Box<dyn Read> = match entry.method {
+            0 => Box::new(raw.take(budget)),
+            8 => Box::new(DeflateDecoder::new(raw).take(budget)),
+            other => {
+                return Err(invalid(&format!(
+                    "unsupported compression method {} for {}",
+                    other, entry.name
+                )))
+            }
+        };
+
+        let path = dest.join(name);
+        let mut out = File::create(&path)?;
+        let written = io::copy(&mut reader, &mut out)?;
+        out.flush()?;
+        total += written;
+        if total > limits.max_total_bytes {
+            return Err(VideoError::InvalidInput(format!(
+                "Archive expands to more than {} MB",
+                limits.max_total_bytes / (1024 * 1024)
+            )));
+        }
+        extracted.push(path);
+    }
+    Ok(extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::DeflateEncoder;
+    use flate2::Compression;
+
+    /// Build a ZIP archive with deflated entries
+    fn build_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut central = Vec::new();
+        for (name, data) in files {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data).unwrap();
+            let compressed = encoder.finish().unwrap();
+            let offset = out.len() as u32;
+
+            out.extend_from_slice(&LOCAL_SIGNATURE.to_le_bytes());
+            out.extend_from_slice(&[20, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            out.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&compressed);
+
+            central.extend_from_slice(&CENTRAL_SIGNATURE.to_le_bytes());
+            central.extend_from_slice(&[20, 0, 20, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            central.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            central.extend_from_slice(&[0; 12]);
+            central.extend_from_slice(&offset.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+        }
+        let dir_offset = out.len() as u32;
+        out.extend_from_slice(&central);
+        out.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        out.extend_from_slice(&dir_offset.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn test_extract_flattens_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("input.zip");
+        std::fs::write(
+            &archive,
+            build_zip(&[
+                ("frames/", b""),
+                ("frames/001.jpg", b"first"),
+                ("../../etc/evil.png", b"second"),
+                ("__MACOSX/frames/._001.jpg", b"fork"),
+            ]),
+        )
+        .unwrap();
+        let dest = dir.path().join("out");
+        std::fs::create_dir(&dest).unwrap();
+
+        let files = extract(&archive, &dest, ExtractLimits::default()).unwrap();
+        assert_eq!(files, vec![dest.join("001.jpg"), dest.join("evil.png")]);
+        assert_eq!(std::fs::read(dest.join("001.jpg")).unwrap(), b"first");
+
+        let tight = ExtractLimits {
+            max_entries: 10,
+            max_total_bytes: 8,
+        };
+        assert!(extract(&archive, &dest, tight).is_err());
+        assert!(extract(&dir.path().join("out/001.jpg"), &dest, ExtractLimits::default()).is_err());
+    }
+
+    #[test]
+    fn test_extract_rejects_duplicate_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("input.zip");
+        std::fs::write(
+            &archive,
+            build_zip(&[("day1/001.jpg", b"first"), ("day2/001.jpg", b"second")]),
+        )
+        .unwrap();
+        let dest = dir.path().join("out");
+        std::fs::create_dir(&dest).unwrap();
+
+        let err = extract(&archive, &dest, ExtractLimits::default()).unwrap_err();
+        assert!(err.to_string().contains("more than one file named 001.jpg"));
+    }
+}
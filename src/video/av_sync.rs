@@ -0,0 +1,184 @@
+//! Audio/video sync validation of transcoded output.
+//!
+//! Some hardware decoders combined with variable frame rate sources silently
+//! shift or stretch the video against the audio. After encoding we compare
+//! the audio-to-video offset at the start and end of the output with the
+//! same offsets in the source; any difference is drift the encode introduced.
+
+use std::fmt;
+
+use crate::video::metadata::VideoMetadata;
+
+/// Default drift allowed before a job is flagged (a quarter second is clearly visible lip-sync error)
+pub const DEFAULT_MAX_DRIFT_MS: u64 = 250;
+
+/// What to do when output drift exceeds the threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AvSyncAction {
+    /// Skip the check
+    Off,
+    /// Log a warning and deliver the output anyway
+    #[default]
+    Warn,
+    /// Fail the job
+    Fail,
+}
+
+impl AvSyncAction {
+    /// Parse from string. Returns `None` for unrecognized values.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "off" | "false" | "0" => Some(Self::Off),
+            "warn" => Some(Self::Warn),
+            "fail" => Some(Self::Fail),
+            _ => None,
+        }
+    }
+}
+
+/// A/V sync check settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AvSyncPolicy {
+    pub action: AvSyncAction,
+    pub max_drift_ms: u64,
+}
+
+impl Default for AvSyncPolicy {
+    fn default() -> Self {
+        Self {
+            action: AvSyncAction::default(),
+            max_drift_ms: DEFAULT_MAX_DRIFT_MS,
+        }
+    }
+}
+
+impl AvSyncPolicy {
+    /// Read `AV_SYNC_CHECK` (off|warn|fail) and `AV_SYNC_MAX_DRIFT_MS`.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            action: std::env::var("AV_SYNC_CHECK")
+                .ok()
+                .and_then(|s| AvSyncAction::parse(&s))
+                .unwrap_or(defaults.action),
+            max_drift_ms: std::env::var("AV_SYNC_MAX_DRIFT_MS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(defaults.max_drift_ms),
+        }
+    }
+
+    /// Whether a measured drift is over the threshold
+    pub fn exceeded(&self, drift: &AvDrift) -> bool {
+        self.action != AvSyncAction::Off && drift.max_ms() > self.max_drift_ms
+    }
+}
+
+/// Change in audio-minus-video offset between source and output, in seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AvDrift {
+    /// Drift at the start of the streams
+    pub start: Option<f64>,
+    /// Drift at the end of the streams (accumulated drift, e.g. from VFR handling)
+    pub end: Option<f64>,
+}
+
+impl AvDrift {
+    /// Compare the A/V offsets of `output` with those of `source`.
+    ///
+    /// Returns `None` when either file lacks an audio or video stream or
+    /// ffprobe reported no usable timestamps.
+    pub fn measure(source: &VideoMetadata, output: &VideoMetadata) -> Option<Self> {
+        let start = diff(start_offset(source), start_offset(output));
+        let end = diff(end_offset(source), end_offset(output));
+        (start.is_some() || end.is_some()).then_some(Self { start, end })
+    }
+
+    /// Largest absolute drift in milliseconds
+    pub fn max_ms(&self) -> u64 {
+        let max = self
+            .start
+            .into_iter()
+            .chain(self.end)
+            .map(f64::abs)
+            .fold(0.0, f64::max);
+        (max * 1000.0).round() as u64
+    }
+}
+
+impl fmt::Display for AvDrift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |v: Option<f64>| v.map_or("n/a".to_string(), |s| format!("{:+.0}ms", s * 1000.0));
+        write!(f, "start {}, end {}", ms(self.start), ms(self.end))
+    }
+}
+
+fn start_offset(metadata: &VideoMetadata) -> Option<f64> {
+    Some(metadata.audio_stream()?.start_secs()? - metadata.video_stream()?.start_secs()?)
+}
+
+fn end_offset(metadata: &VideoMetadata) -> Option<f64> {
+    Some(metadata.audio_stream()?.end_secs()? - metadata.video_stream()?.end_secs()?)
+}
+
+fn diff(source: Option<f64>, output: Option<f64>) -> Option<f64> {
+    Some(output? - source?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(video: (&str, &str), audio: (&str, &str)) -> VideoMetadata {
+        VideoMetadata::builder()
+            .with_video(1920, 1080)
+            .with_field("start_time", video.0)
+            .with_field("duration", video.1)
+            .with_audio()
+            .with_field("start_time", audio.0)
+            .with_field("duration", audio.1)
+            .build()
+    }
+
+    #[test]
+    fn test_in_sync_output() {
+        // Source audio starts 21ms late (AAC priming); output keeps that
+        let source = metadata(("0.000", "60.000"), ("0.021", "60.000"));
+        let output = metadata(("0.000", "60.033"), ("0.021", "60.010"));
+        let drift = AvDrift::measure(&source, &output).unwrap();
+        assert_eq!(drift.max_ms(), 23);
+        assert!(!AvSyncPolicy::default().exceeded(&drift));
+    }
+
+    #[test]
+    fn test_stretched_video_detected() {
+        let source = metadata(("0.000", "60.000"), ("0.000", "60.000"));
+        let output = metadata(("0.000", "61.200"), ("0.000", "60.000"));
+        let drift = AvDrift::measure(&source, &output).unwrap();
+        assert_eq!(drift.start, Some(0.0));
+        assert_eq!(drift.max_ms(), 1200);
+
+        let policy = AvSyncPolicy::default();
+        assert!(policy.exceeded(&drift));
+        let off = AvSyncPolicy {
+            action: AvSyncAction::Off,
+            ..policy
+        };
+        assert!(!off.exceeded(&drift));
+    }
+
+    #[test]
+    fn test_missing_audio_skips_check() {
+        let source = metadata(("0.000", "60.000"), ("0.000", "60.000"));
+        let mut output = source.clone();
+        output.streams.retain(|s| s.codec_type == "video");
+        assert!(AvDrift::measure(&source, &output).is_none());
+    }
+
+    #[test]
+    fn test_action_parse() {
+        assert_eq!(AvSyncAction::parse("FAIL"), Some(AvSyncAction::Fail));
+        assert_eq!(AvSyncAction::parse("off"), Some(AvSyncAction::Off));
+        assert_eq!(AvSyncAction::parse("maybe"), None);
+    }
+}
@@ -0,0 +1,131 @@
+//! Chapter markers of the source.
+//!
+//! MP4 and MKV sources often carry chapters (podcasts, lectures, camera
+//! recordings split by scene). MP4 and WebM outputs keep them in the
+//! container; for every output they're also listed in the result and
+//! uploaded as a WebVTT chapters track, which is how HLS players get them.
+
+use serde::{Deserialize, Serialize};
+
+use crate::video::clip::Clip;
+use crate::video::metadata::ChapterInfo;
+use crate::video::storyboard::vtt_timestamp;
+
+/// Chapters shorter than this (after clipping) are dropped
+const MIN_CHAPTER_SECS: f64 = 0.5;
+
+/// A chapter of the output, in output seconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chapter {
+    pub start: f64,
+    pub end: f64,
+    pub title: String,
+}
+
+/// Chapters of the output, from the source's `chapters` as ffprobe reports
+/// them. With a clip, chapters are cut to the clipped range and shifted to
+/// start at 0; untitled chapters are numbered.
+pub fn output_chapters(chapters: &[ChapterInfo], clip: Option<Clip>) -> Vec<Chapter> {
+    let start = clip.map_or(0.0, |c| c.start);
+    let end = clip.and_then(|c| c.end).unwrap_or(f64::INFINITY);
+
+    let mut out: Vec<Chapter> = Vec::new();
+    for chapter in chapters {
+        let (Some(from), Some(to)) = (chapter.start_secs(), chapter.end_secs()) else {
+            continue;
+        };
+        let (from, to) = (from.max(start) - start, to.min(end) - start);
+        if to - from < MIN_CHAPTER_SECS {
+            continue;
+        }
+        let title = match chapter.title() {
+            Some(title) => title.to_string(),
+            None => format!("Chapter {}", out.len() + 1),
+        };
+        out.push(Chapter {
+            start: from,
+            end: to,
+            title,
+        });
+    }
+    out
+}
+
+/// WebVTT chapters track (`<track kind="chapters">`)
+pub fn vtt(chapters: &[Chapter]) -> String {
+    let mut out = String::from("WEBVTT\n");
+    for (i, chapter) in chapters.iter().enumerate() {
+        // Cue text can't contain blank lines or "-->"
+        let title = chapter
+            .title
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+            .replace("-->", "->");
+        out.push_str(&format!(
+            "\n{}\n{} --> {}\n{}\n",
+            i + 1,
+            vtt_timestamp(chapter.start),
+            vtt_timestamp(chapter.end),
+            title
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapters() -> Vec<ChapterInfo> {
+        serde_json::from_value(serde_json::json!([
+            {"start_time": "0.000000", "end_time": "60.000000", "tags": {"title": "Intro"}},
+            {"start_time": "60.000000", "end_time": "185.500000", "tags": {"title": " Main part "}},
+            {"start_time": "185.500000", "end_time": "300.000000"},
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_output_chapters() {
+        let out = output_chapters(&chapters(), None);
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[1].title, "Main part");
+        assert_eq!(out[2].title, "Chapter 3");
+        assert_eq!((out[2].start, out[2].end), (185.5, 300.0));
+    }
+
+    #[test]
+    fn test_clipped_chapters() {
+        let clip = Clip {
+            start: 60.2,
+            end: Some(185.8),
+        };
+        let out = output_chapters(&chapters(), Some(clip));
+        // The last chapter keeps only 0.3 seconds
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].title, "Main part");
+        assert_eq!(out[0].start, 0.0);
+        assert!((out[0].end - 125.3).abs() < 1e-9);
+
+        assert!(output_chapters(&[], None).is_empty());
+    }
+
+    #[test]
+    fn test_vtt() {
+        let out = output_chapters(&chapters(), None);
+        assert_eq!(
+            vtt(&out[..2]),
+            "WEBVTT\n\n1\n00:00:00.000 --> 00:01:00.000\nIntro\n\n2\n00:01:00.000 --> 00:03:05.500\nMain part\n"
+        );
+
+        let odd = Chapter {
+            start: 0.0,
+            end: 1.0,
+            title: "A --> B\n\nC".to_string(),
+        };
+        assert!(vtt(&[odd]).ends_with("\nA -> B C\n"));
+    }
+}
@@ -0,0 +1,156 @@
+//! Transcoding a segment of the source instead of the whole video.
+//!
+//! Requesters give a `start` and either an `end` or a `duration`, as seconds
+//! (`90.5`) or timestamps (`1:30`, `01:02:03.250`). The range is applied as
+//! input options (`-ss`/`-to`), so FFmpeg seeks instead of decoding the
+//! skipped part.
+
+/// A time range of the source to transcode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clip {
+    /// Offset into the source, in seconds
+    pub start: f64,
+    /// Where to stop, in source seconds (`None` runs to the end)
+    pub end: Option<f64>,
+}
+
+impl Clip {
+    /// Build a clip from the `start`, `end` and `duration` params.
+    ///
+    /// Returns `Ok(None)` when none of them were given.
+    pub fn from_params(
+        start: Option<&str>,
+        end: Option<&str>,
+        duration: Option<&str>,
+    ) -> Result<Option<Self>, String> {
+        if start.is_none() && end.is_none() && duration.is_none() {
+            return Ok(None);
+        }
+        let field = |name: &str, value: Option<&str>| {
+            value
+                .map(|v| parse_timestamp(v).ok_or_else(|| format!("Invalid {}: {}", name, v.trim())))
+                .transpose()
+        };
+
+        let start = field("start", start)?.unwrap_or(0.0);
+        let end = match (field("end", end)?, field("duration", duration)?) {
+            (Some(_), Some(_)) => return Err("Give either end or duration, not both".to_string()),
+            (Some(end), None) => Some(end),
+            (None, Some(duration)) => Some(start + duration),
+            (None, None) => None,
+        };
+        if end.is_some_and(|end| end <= start) {
+            return Err("Clip end must be after its start".to_string());
+        }
+        Ok(Some(Self { start, end }))
+    }
+
+    /// Check the range against the source duration.
+    ///
+    /// An end past the source is dropped, so the clip runs to the end.
+    pub fn validate(self, source_secs: f64) -> Result<Self, String> {
+        if source_secs <= 0.0 {
+            return Ok(self);
+        }
+        if self.start >= source_secs {
+            return Err(format!(
+                "Clip starts at {:.1}s but the video is only {:.1}s long",
+                self.start, source_secs
+            ));
+        }
+        Ok(Self {
+            start: self.start,
+            end: self.end.filter(|end| *end < source_secs),
+        })
+    }
+
+    /// Length of the clip in seconds, given the source duration (0 if unknown)
+    pub fn length(&self, source_secs: f64) -> f64 {
+        let end = self.end.unwrap_or(source_secs);
+        (end - self.start).max(0.0)
+    }
+
+    /// FFmpeg input options selecting the range (placed before `-i`)
+    pub fn input_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.start > 0.0 {
+            args.push("-ss".to_string());
+            args.push(format!("{:.3}", self.start));
+        }
+        if let Some(end) = self.end {
+            args.push("-to".to_string());
+            args.push(format!("{:.3}", end));
+        }
+        args
+    }
+}
+
+/// Parse seconds (`90.5`) or a `[[HH:]MM:]SS[.fff]` timestamp
+pub fn parse_timestamp(s: &str) -> Option<f64> {
+    let s = s.trim();
+    if s.is_empty() {
+        return None;
+    }
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+
+    let mut secs = 0.0;
+    for (i, part) in parts.iter().enumerate() {
+        let last = i == parts.len() - 1;
+        // Only the seconds field may be fractional, and only leading fields may exceed 59
+        let value = if last {
+            part.parse::<f64>().ok()?
+        } else {
+            part.parse::<u32>().ok()? as f64
+        };
+        if !value.is_finite() || value < 0.0 || (i > 0 && value >= 60.0) {
+            return None;
+        }
+        secs = secs * 60.0 + value;
+    }
+    Some(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("90.5"), Some(90.5));
+        assert_eq!(parse_timestamp("1:30"), Some(90.0));
+        assert_eq!(parse_timestamp(" 01:02:03.25 "), Some(3723.25));
+        assert_eq!(parse_timestamp("1:75"), None);
+        assert_eq!(parse_timestamp("-5"), None);
+        assert_eq!(parse_timestamp("1:2:3:4"), None);
+        assert_eq!(parse_timestamp("abc"), None);
+    }
+
+    #[test]
+    fn test_clip_params() {
+        assert_eq!(Clip::from_params(None, None, None), Ok(None));
+
+        let clip = Clip::from_params(Some("1:00"), None, Some("30")).unwrap().unwrap();
+        assert_eq!(clip, Clip { start: 60.0, end: Some(90.0) });
+        assert_eq!(clip.length(600.0), 30.0);
+        assert_eq!(clip.input_args(), vec!["-ss", "60.000", "-to", "90.000"]);
+
+        let tail = Clip::from_params(Some("500"), None, None).unwrap().unwrap();
+        assert_eq!(tail.length(600.0), 100.0);
+        assert_eq!(tail.input_args(), vec!["-ss", "500.000"]);
+
+        assert!(Clip::from_params(Some("10"), Some("5"), None).is_err());
+        assert!(Clip::from_params(None, Some("5"), Some("5")).is_err());
+        assert!(Clip::from_params(Some("soon"), None, None).is_err());
+    }
+
+    #[test]
+    fn test_clip_validate() {
+        let clip = Clip { start: 60.0, end: Some(900.0) };
+        assert_eq!(clip.validate(600.0), Ok(Clip { start: 60.0, end: None }));
+        assert_eq!(clip.validate(0.0), Ok(clip));
+        assert!(Clip { start: 600.0, end: None }.validate(600.0).is_err());
+    }
+}
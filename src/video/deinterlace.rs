@@ -0,0 +1,273 @@
+//! Detecting and removing interlacing.
+//!
+//! Archive and TV material is often interlaced: every frame carries two
+//! fields captured a field period apart, which turns into combing on each
+//! moving edge once the frame is scaled and encoded as progressive video.
+//! Containers don't flag this reliably, so the first frames of the source are
+//! run through FFmpeg's `idet` filter, and interlaced sources get `bwdif` (or
+//! the GPU's own deinterlacer) in front of scaling.
+
+use std::path::Path;
+use tokio::process::Command;
+use tracing::debug;
+
+use crate::error::VideoError;
+use crate::video::clip::Clip;
+use crate::video::hwaccel::HwAccel;
+use crate::video::metadata::StreamInfo;
+
+/// Frames `idet` looks at before deciding
+const IDET_FRAMES: u32 = 300;
+
+/// Share of the inspected frames that must look interlaced, so a few
+/// misdetections in progressive content don't trigger deinterlacing
+const MIN_INTERLACED_SHARE: f64 = 0.1;
+
+/// Which field of an interlaced frame comes first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldOrder {
+    TopFirst,
+    BottomFirst,
+}
+
+impl FieldOrder {
+    /// Recognize an ffprobe `field_order` value (`None` for progressive or
+    /// unknown)
+    pub fn from_ffprobe(field_order: &str) -> Option<Self> {
+        match field_order.trim() {
+            "tt" | "tb" => Some(Self::TopFirst),
+            "bb" | "bt" => Some(Self::BottomFirst),
+            _ => None,
+        }
+    }
+
+    /// Value of the deinterlacers' `parity` option
+    fn parity(&self) -> &'static str {
+        match self {
+            Self::TopFirst => "tff",
+            Self::BottomFirst => "bff",
+        }
+    }
+}
+
+/// Whether to deinterlace, from the `deinterlace` param
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeinterlaceMode {
+    /// Deinterlace sources `idet` finds interlaced
+    #[default]
+    Auto,
+    /// Always deinterlace
+    On,
+    /// Never deinterlace
+    Off,
+}
+
+impl DeinterlaceMode {
+    /// Parse from string. Returns `None` for unrecognized values.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "auto" => Some(Self::Auto),
+            "on" | "true" | "yes" => Some(Self::On),
+            "off" | "false" | "no" => Some(Self::Off),
+            _ => None,
+        }
+    }
+}
+
+/// Frame counts from `idet`'s multi-frame detection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdetCounts {
+    pub tff: u64,
+    pub bff: u64,
+    pub progressive: u64,
+    pub undetermined: u64,
+}
+
+impl IdetCounts {
+    /// Parse the last "Multi frame detection" line `idet` prints to stderr
+    pub fn parse(stderr: &str) -> Option<Self> {
+        let line = stderr
+            .lines()
+            .rev()
+            .find_map(|l| l.split_once("Multi frame detection:"))?
+            .1;
+
+        // "TFF:  120 BFF:    0 Progressive:   12 Undetermined:  168"
+        let mut tokens = line.split_whitespace();
+        let mut count = |name: &str| -> Option<u64> {
+            if tokens.next()? != name {
+                return None;
+            }
+            tokens.next()?.parse().ok()
+        };
+        Some(Self {
+            tff: count("TFF:")?,
+            bff: count("BFF:")?,
+            progressive: count("Progressive:")?,
+            undetermined: count("Undetermined:")?,
+        })
+    }
+
+    /// Field order of the source, or `None` when it looks progressive
+    pub fn field_order(&self) -> Option<FieldOrder> {
+        let interlaced = self.tff + self.bff;
+        let total = interlaced + self.progressive + self.undetermined;
+        if interlaced <= self.progressive
+            || (interlaced as f64) < total as f64 * MIN_INTERLACED_SHARE
+        {
+            return None;
+        }
+        Some(if self.tff >= self.bff {
+            FieldOrder::TopFirst
+        } else {
+            FieldOrder::BottomFirst
+        })
+    }
+
+    /// Run `idet` over the first frames of `input` (or of the clipped part)
+    pub async fn measure(
+        ffmpeg_path: &Path,
+        input: &str,
+        clip: Option<Clip>,
+    ) -> Result<Self, VideoError> {
+        let mut cmd = Command::new(ffmpeg_path);
+        cmd.kill_on_drop(true);
+        cmd.arg("-nostdin").arg("-hide_banner");
+        if let Some(clip) = clip {
+            cmd.args(clip.input_args());
+        }
+        cmd.arg("-i")
+            .arg(input)
+            .arg("-map")
+            .arg("0:v:0")
+            .arg("-vf")
+            .arg("idet")
+            .arg("-frames:v")
+            .arg(IDET_FRAMES.to_string())
+            .arg("-f")
+            .arg("null")
+            .arg("-");
+
+        debug!(input = %input, "Detecting interlacing");
+        let output = cmd.output().await.map_err(VideoError::Io)?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            return Err(crate::video::ffmpeg_error::failure(
+                "Interlace detection failed",
+                &stderr,
+            ));
+        }
+
+        Self::parse(&stderr).ok_or_else(|| {
+            VideoError::FfmpegFailed("Interlace detection produced no result".to_string())
+        })
+    }
+}
+
+/// Deinterlacing applied to an interlaced source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deinterlace {
+    pub field_order: FieldOrder,
+}
+
+impl Deinterlace {
+    /// Deinterlacing `stream` is flagged for by its container
+    pub fn for_stream(stream: &StreamInfo) -> Option<Self> {
+        let field_order = stream.field_order.as_deref().and_then(FieldOrder::from_ffprobe)?;
+        Some(Self { field_order })
+    }
+
+    /// Whether the deinterlacer runs on CPU frames, i.e. the source has to be
+    /// decoded in software
+    pub fn needs_sw_frames(&self, hwaccel: HwAccel) -> bool {
+        self.hw_filter(hwaccel).is_none()
+    }
+
+    /// Filter deinterlacing frames already uploaded to `hwaccel`'s device.
+    /// Each outputs one frame per frame, keeping the source frame rate.
+    pub fn hw_filter(&self, hwaccel: HwAccel) -> Option<String> {
+        match hwaccel {
+            // Takes the field order from the frames' own flags
+            HwAccel::Vaapi => Some("deinterlace_vaapi=rate=frame".to_string()),
+            HwAccel::Nvenc => Some(format!(
+                "yadif_cuda=mode=send_frame:parity={}:deint=all",
+                self.field_order.parity()
+            )),
+            _ => None,
+        }
+    }
+
+    /// Filter deinterlacing CPU frames
+    pub fn sw_filter(&self) -> String {
+        format!(
+            "bwdif=mode=send_frame:parity={}:deint=all",
+            self.field_order.parity()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STDERR: &str = "frame=  300 fps=0.0 q=-0.0 Lsize=N/A time=00:00:12.00 bitrate=N/A speed=38.1x
+[Parsed_idet_0 @ 0x55d0c1e3a0c0] Repeated Fields: Neither:   299 Top:     0 Bottom:     1
+[Parsed_idet_0 @ 0x55d0c1e3a0c0] Single frame detection: TFF:    41 BFF:     2 Progressive:    20 Undetermined:   237
+[Parsed_idet_0 @ 0x55d0c1e3a0c0] Multi frame detection: TFF:   204 BFF:     0 Progressive:    31 Undetermined:    65
+";
+
+    #[test]
+    fn test_parse_idet() {
+        let counts = IdetCounts::parse(STDERR).unwrap();
+        assert_eq!(
+            counts,
+            IdetCounts { tff: 204, bff: 0, progressive: 31, undetermined: 65 }
+        );
+        assert_eq!(counts.field_order(), Some(FieldOrder::TopFirst));
+        assert_eq!(IdetCounts::parse("frame=  300 fps=0.0"), None);
+    }
+
+    #[test]
+    fn test_field_order_decision() {
+        let counts = |tff, bff, progressive, undetermined| IdetCounts {
+            tff,
+            bff,
+            progressive,
+            undetermined,
+        };
+        assert_eq!(counts(3, 120, 10, 167).field_order(), Some(FieldOrder::BottomFirst));
+        assert_eq!(counts(20, 0, 250, 30).field_order(), None);
+        // Mostly undetermined (static content) with a handful of hits
+        assert_eq!(counts(5, 0, 2, 293).field_order(), None);
+        assert_eq!(counts(0, 0, 0, 0).field_order(), None);
+    }
+
+    #[test]
+    fn test_ffprobe_field_order() {
+        assert_eq!(FieldOrder::from_ffprobe("tt"), Some(FieldOrder::TopFirst));
+        assert_eq!(FieldOrder::from_ffprobe("bt"), Some(FieldOrder::BottomFirst));
+        assert_eq!(FieldOrder::from_ffprobe("progressive"), None);
+        assert_eq!(FieldOrder::from_ffprobe("unknown"), None);
+    }
+
+    #[test]
+    fn test_mode_parse() {
+        assert_eq!(DeinterlaceMode::parse("auto"), Some(DeinterlaceMode::Auto));
+        assert_eq!(DeinterlaceMode::parse(" ON "), Some(DeinterlaceMode::On));
+        assert_eq!(DeinterlaceMode::parse("false"), Some(DeinterlaceMode::Off));
+        assert_eq!(DeinterlaceMode::parse("bwdif"), None);
+    }
+
+    #[test]
+    fn test_filters() {
+        let bff = Deinterlace { field_order: FieldOrder::BottomFirst };
+        assert_eq!(bff.sw_filter(), "bwdif=mode=send_frame:parity=bff:deint=all");
+        assert_eq!(
+            bff.hw_filter(HwAccel::Nvenc).as_deref(),
+            Some("yadif_cuda=mode=send_frame:parity=bff:deint=all")
+        );
+        assert!(!bff.needs_sw_frames(HwAccel::Vaapi));
+        assert!(bff.needs_sw_frames(HwAccel::Qsv));
+        assert!(bff.needs_sw_frames(HwAccel::Software));
+    }
+}
@@ -1,33 +1,103 @@
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
 use tokio::process::Command as TokioCommand;
 use tracing::debug;
 
 use crate::dvm::events::{Codec, Resolution};
+use crate::dvm::job_log::JobLog;
 use crate::error::VideoError;
+use crate::video::ffmpeg_error;
+use crate::util::ffmpeg_progress::LadderProgress;
+use crate::util::ffmpeg_version::fps_mode_flag;
+use crate::video::gpu::GpuDevice;
 use crate::video::hwaccel::HwAccel;
-use crate::video::transform::TransformConfig;
+use crate::video::transform::{Container, PreviewFormat, SegmentType, TransformConfig};
+use crate::video::clip::Clip;
+use crate::video::deinterlace::Deinterlace;
+use crate::video::fps_cap::fps_filter;
+use crate::video::tonemap::Tonemap;
+use crate::video::loudness::Loudnorm;
+use crate::video::watermark::Watermark;
 
 /// Format a TokioCommand as a copy-pasteable shell command string.
 fn format_cmd(cmd: &TokioCommand) -> String {
+    shell_command(&argv(cmd))
+}
+
+/// Program and arguments of `cmd`
+fn argv(cmd: &TokioCommand) -> Vec<String> {
     let std_cmd = cmd.as_std();
-    let program = std_cmd.get_program().to_string_lossy();
-    let args: Vec<String> = std_cmd
-        .get_args()
-        .map(|a| {
-            let s = a.to_string_lossy();
+    std::iter::once(std_cmd.get_program())
+        .chain(std_cmd.get_args())
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Join `argv` into a copy-pasteable shell command string, quoting
+/// arguments the shell would split or interpret.
+pub fn shell_command(argv: &[String]) -> String {
+    argv.iter()
+        .map(|s| {
             if s.contains(' ') || s.contains('\'') || s.contains('"') || s.contains('\\') || s.is_empty() {
                 format!("'{}'", s.replace('\'', "'\\''"))
             } else {
-                s.into_owned()
+                s.clone()
             }
         })
-        .collect();
-    format!("{} {}", program, args.join(" "))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 pub use self::FfmpegMp4Command as Mp4Command;
 
+/// Stderr lines kept for classifying a failed encode
+const STDERR_TAIL_LINES: usize = 50;
+
+/// Read FFmpeg's stderr line by line, passing each line on to the service
+/// log at debug level and to the job log. Resolves to the last lines once
+/// the stream ends.
+fn read_stderr<R>(stderr: R, log: Option<Arc<JobLog>>) -> tokio::task::JoinHandle<Vec<String>>
+where
+    R: tokio::io::AsyncRead + Unpin + Send + 'static,
+{
+    use tokio::io::AsyncBufReadExt;
+
+    tokio::spawn(async move {
+        let mut tail = std::collections::VecDeque::with_capacity(STDERR_TAIL_LINES);
+        let mut lines = tokio::io::BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            debug!(target: "ffmpeg", "{}", line);
+            if let Some(log) = &log {
+                log.ffmpeg(line);
+            }
+            if tail.len() == STDERR_TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line.to_string());
+        }
+        tail.into()
+    })
+}
+
+/// Group ID of the alternate audio renditions in multi-track HLS output
+const AUDIO_GROUP: &str = "audio";
+
+/// AAC bitrate of each alternate audio rendition
+const AUDIO_GROUP_BITRATE: &str = "128k";
+
+/// Strip characters that would break `-var_stream_map` parsing
+fn var_stream_value(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect()
+}
+
 pub struct FfmpegCommand {
     input: String,
     output_dir: std::path::PathBuf,
@@ -40,6 +110,26 @@ pub struct FfmpegCommand {
     key_info_path: Option<PathBuf>,
     /// Video duration in seconds
     duration: Option<f64>,
+    /// Constant output frame rate for variable frame rate sources
+    frame_rate: Option<String>,
+    /// Overlay burned into every rendition
+    watermark: Option<Watermark>,
+    /// Deinterlacing for interlaced sources
+    deinterlace: Option<Deinterlace>,
+    /// HDR to SDR conversion for HDR sources
+    tonemap: Option<Tonemap>,
+    /// Clockwise display rotation of the source in degrees
+    rotation: u32,
+    /// Segment of the source to transcode
+    clip: Option<Clip>,
+    /// Loudness normalization applied to the audio
+    loudnorm: Option<Loudnorm>,
+    /// Drop the source\'s metadata (GPS, device, creation time)
+    strip_metadata: bool,
+    /// Job log that receives FFmpeg's stderr
+    log: Option<Arc<JobLog>>,
+    /// GPU the encode was assigned (None = FFmpeg's default device)
+    device: Option<GpuDevice>,
 }
 
 impl FfmpegCommand {
@@ -59,6 +149,16 @@ impl FfmpegCommand {
             source_codec: None,
             key_info_path: None,
             duration: None,
+            frame_rate: None,
+            watermark: None,
+            deinterlace: None,
+            tonemap: None,
+            rotation: 0,
+            clip: None,
+            loudnorm: None,
+            strip_metadata: false,
+            log: None,
+            device: None,
         }
     }
 
@@ -76,6 +176,69 @@ impl FfmpegCommand {
         self
     }
 
+    /// Encode at a constant frame rate (FFmpeg rate syntax, e.g. "30000/1001"),
+    /// duplicating or dropping frames of a variable frame rate source
+    pub fn with_frame_rate(mut self, rate: Option<&str>) -> Self {
+        self.frame_rate = rate.map(|r| r.to_string());
+        self
+    }
+
+    /// Burn a watermark into every rendition (forces software decoding)
+    pub fn with_watermark(mut self, watermark: Option<&Watermark>) -> Self {
+        self.watermark = watermark.cloned();
+        self
+    }
+
+    /// Deinterlace an interlaced source in every encoded rendition
+    pub fn with_deinterlace(mut self, deinterlace: Option<Deinterlace>) -> Self {
+        self.deinterlace = deinterlace;
+        self
+    }
+
+    /// Tone-map an HDR source to SDR in every encoded rendition (a copied
+    /// original keeps its HDR)
+    pub fn with_tonemap(mut self, tonemap: Option<Tonemap>) -> Self {
+        self.tonemap = tonemap;
+        self
+    }
+
+    /// Turn a source stored sideways upright (clockwise degrees, see
+    /// [`crate::video::metadata::StreamInfo::rotation`])
+    pub fn with_rotation(mut self, rotation: u32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Transcode only part of the source
+    pub fn with_clip(mut self, clip: Option<Clip>) -> Self {
+        self.clip = clip;
+        self
+    }
+
+    /// Normalize the loudness of the audio
+    pub fn with_loudnorm(mut self, loudnorm: Option<&Loudnorm>) -> Self {
+        self.loudnorm = loudnorm.cloned();
+        self
+    }
+
+    /// Drop the source's global and per-stream metadata from the output
+    pub fn with_strip_metadata(mut self, strip: bool) -> Self {
+        self.strip_metadata = strip;
+        self
+    }
+
+    /// Capture stderr into a job log instead of passing it through
+    pub fn with_log(mut self, log: Option<Arc<JobLog>>) -> Self {
+        self.log = log;
+        self
+    }
+
+    /// Pin the encode to a GPU
+    pub fn with_device(mut self, device: Option<&GpuDevice>) -> Self {
+        self.device = device.cloned();
+        self
+    }
+
     /// Enable AES-128 encryption with the given key info file
     pub fn with_encryption(mut self, key_info_path: &Path) -> Self {
         self.key_info_path = Some(key_info_path.to_path_buf());
@@ -86,8 +249,14 @@ impl FfmpegCommand {
     pub fn build(&self) -> Command {
         let mut cmd = Command::new("ffmpeg");
 
-        // Input
+        // Input (seeking to the clip range first)
+        if let Some(clip) = &self.clip {
+            cmd.args(clip.input_args());
+        }
         cmd.arg("-i").arg(&self.input);
+        if let Some(image) = self.watermark.as_ref().and_then(Watermark::input) {
+            cmd.arg("-i").arg(image);
+        }
 
         // Build complex filter for scaling
         let filter = self.build_complex_filter();
@@ -97,8 +266,17 @@ impl FfmpegCommand {
 
         // Add mappings and codec settings
         self.add_output_options(&mut cmd);
+        if let Some(ref loudnorm) = self.loudnorm {
+            cmd.arg("-af").arg(loudnorm.filter());
+        }
+        if self.strip_metadata {
+            cmd.args(STRIP_METADATA_ARGS);
+        }
+
+        cmd.args(self.frame_rate_args());
 
         // HLS options
+        let segment_type = self.effective_segment_type();
         cmd.arg("-f")
             .arg("hls")
             .arg("-var_stream_map")
@@ -108,15 +286,19 @@ impl FfmpegCommand {
             .arg("-hls_list_size")
             .arg(self.config.hls_list_size.to_string())
             .arg("-hls_segment_type")
-            .arg(self.config.segment_type.as_str())
+            .arg(segment_type.as_str())
             .arg("-master_pl_name")
             .arg("master.m3u8")
             .arg("-hls_segment_filename")
             .arg(self.output_dir.join(format!(
                 "stream_%v_%03d.{}",
-                self.config.segment_type.extension()
+                segment_type.extension()
             )));
 
+        if let Some(ref key_info_path) = self.key_info_path {
+            cmd.arg("-hls_key_info_file").arg(key_info_path);
+        }
+
         // Output pattern
         let output = self.output_dir.join("stream_%v.m3u8");
         cmd.arg(output);
@@ -124,23 +306,68 @@ impl FfmpegCommand {
         cmd
     }
 
+    /// Program and arguments `run` would execute, for dry runs
+    pub fn command_line(&self, ffmpeg_path: &Path) -> Vec<String> {
+        argv(&self.command(ffmpeg_path, true))
+    }
+
     /// Run the FFmpeg command asynchronously
     pub async fn run(
         &self,
         ffmpeg_path: &Path,
         progress: Option<std::sync::Arc<std::sync::atomic::AtomicU64>>,
     ) -> Result<(), VideoError> {
+        let mut cmd = self.command(ffmpeg_path, progress.is_some());
+        debug!(hwaccel = %self.hwaccel, "\n{}", format_cmd(&cmd));
+
+        if let Some(log) = &self.log {
+            log.event(format!("Running {}", format_cmd(&cmd)));
+        }
+        let mut child = cmd.spawn().map_err(VideoError::Io)?;
+        let stderr = read_stderr(
+            child.stderr.take().expect("Stderr must be piped"),
+            self.log.clone(),
+        );
+
+        // If progress tracking is enabled, spawn a task to read stdout
+        if let Some(p) = progress {
+            let tracker = crate::util::ffmpeg_progress::FfmpegProgressTracker {
+                progress_ms: p,
+                ladder: Some(self.ladder_progress()),
+            };
+            let stdout = child.stdout.take().expect("Stdout must be piped");
+            tracker.track_progress(stdout).await.map_err(VideoError::Io)?;
+        }
+
+        let status = child.wait().await.map_err(VideoError::Io)?;
+        let stderr = stderr.await.unwrap_or_default();
+
+        if !status.success() {
+            return Err(ffmpeg_error::failure("HLS encoding failed", &stderr.join("\n")));
+        }
+
+        Ok(())
+    }
+
+    /// Build the HLS command, with `-progress` output on stdout if `progress`
+    fn command(&self, ffmpeg_path: &Path, progress: bool) -> TokioCommand {
         let mut cmd = TokioCommand::new(ffmpeg_path);
+        cmd.kill_on_drop(true);
 
         // Overwrite without asking, non-interactive
         cmd.arg("-y").arg("-nostdin");
 
         // Progress reporting to stdout
-        if progress.is_some() {
+        if progress {
             cmd.arg("-progress").arg("-");
             cmd.stdout(std::process::Stdio::piped());
         }
 
+        // Stderr is read to classify failures; periodic stats lines would
+        // crowd out everything else
+        cmd.arg("-hide_banner").arg("-nostats");
+        cmd.stderr(std::process::Stdio::piped());
+
         // Add network reconnection options if input is a URL
         if self.input.starts_with("http://") || self.input.starts_with("https://") {
             cmd.arg("-reconnect")
@@ -156,13 +383,21 @@ impl FfmpegCommand {
         // Hardware acceleration input options (before -i)
         self.add_hwaccel_input_options(&mut cmd);
 
+        // Seek to the clip range; its end replaces the duration limit
+        if let Some(clip) = &self.clip {
+            cmd.args(clip.input_args());
+        }
+
         // Limit duration if provided
-        if let Some(d) = self.duration {
+        if let Some(d) = self.duration.filter(|_| self.clip.is_none_or(|c| c.end.is_none())) {
             cmd.arg("-t").arg(d.to_string());
         }
 
         // Input
         cmd.arg("-i").arg(&self.input);
+        if let Some(image) = self.watermark.as_ref().and_then(Watermark::input) {
+            cmd.arg("-i").arg(image);
+        }
 
         // Build complex filter for scaling
         let filter = self.build_complex_filter();
@@ -172,19 +407,18 @@ impl FfmpegCommand {
 
         // Add mappings and codec settings
         self.add_output_options_tokio(&mut cmd);
+        if let Some(ref loudnorm) = self.loudnorm {
+            cmd.arg("-af").arg(loudnorm.filter());
+        }
+        if self.strip_metadata {
+            cmd.args(STRIP_METADATA_ARGS);
+        }
+
+        // Regenerate timestamps at a constant rate before segmenting
+        cmd.args(self.frame_rate_args());
 
         // HLS options
-        // Note: When encryption is used, segment_type must be mpegts (FFmpeg limitation)
-        let segment_type = if self.key_info_path.is_some() {
-            "mpegts"
-        } else {
-            self.config.segment_type.as_str()
-        };
-        let segment_ext = if self.key_info_path.is_some() {
-            "ts"
-        } else {
-            self.config.segment_type.extension()
-        };
+        let segment_type = self.effective_segment_type();
 
         cmd.arg("-f")
             .arg("hls")
@@ -195,13 +429,13 @@ impl FfmpegCommand {
             .arg("-hls_list_size")
             .arg(self.config.hls_list_size.to_string())
             .arg("-hls_segment_type")
-            .arg(segment_type)
+            .arg(segment_type.as_str())
             .arg("-master_pl_name")
             .arg("master.m3u8")
             .arg("-hls_segment_filename")
             .arg(
                 self.output_dir
-                    .join(format!("stream_%v_%03d.{}", segment_ext)),
+                    .join(format!("stream_%v_%03d.{}", segment_type.extension())),
             );
 
         // Add AES-128 encryption if key info file is provided
@@ -212,32 +446,105 @@ impl FfmpegCommand {
         // Output pattern
         let output = self.output_dir.join("stream_%v.m3u8");
         cmd.arg(output);
+        cmd
+    }
 
-        debug!(hwaccel = %self.hwaccel, "\n{}", format_cmd(&cmd));
-
-        let mut child = cmd.spawn().map_err(VideoError::Io)?;
-
-        // If progress tracking is enabled, spawn a task to read stdout
-        if let Some(p) = progress {
-            let tracker = crate::util::ffmpeg_progress::FfmpegProgressTracker { progress_ms: p };
-            let stdout = child.stdout.take().expect("Stdout must be piped");
-            tracker.track_progress(stdout).await.map_err(VideoError::Io)?;
+    /// Constant frame rate options for a VFR source: the target rate, except
+    /// for rungs the `fps` filter brought below it
+    fn frame_rate_args(&self) -> Vec<String> {
+        let Some(ref rate) = self.frame_rate else {
+            return Vec::new();
+        };
+        let mut args = vec![
+            fps_mode_flag().to_string(),
+            "cfr".to_string(),
+            "-r".to_string(),
+            rate.clone(),
+        ];
+        let mut keys: Vec<_> = self.config.resolutions.keys().collect();
+        keys.sort(); // Same order as the variant streams
+        for (idx, key) in keys.iter().enumerate() {
+            if let Some(fps) = self.config.resolutions[*key].fps {
+                args.push(format!("-r:v:{}", idx));
+                args.push(format!("{:.3}", fps));
+            }
         }
+        args
+    }
 
-        let status = child.wait().await.map_err(VideoError::Io)?;
+    /// Progress of the variant playlists, weighted by encode cost.
+    ///
+    /// Rungs cost roughly their pixel count; a copied original costs nothing
+    /// next to them and is left out.
+    fn ladder_progress(&self) -> LadderProgress {
+        let mut keys: Vec<_> = self.config.resolutions.keys().collect();
+        keys.sort(); // Same order as the variant streams
+        let rungs = keys
+            .iter()
+            .enumerate()
+            .map(|(idx, key)| {
+                let res = &self.config.resolutions[*key];
+                let weight = match res.height {
+                    _ if res.is_original => 0.0,
+                    Some(height) => {
+                        let width = res.width.unwrap_or(height * 16 / 9);
+                        width as f64 * height as f64
+                    }
+                    None => 1.0,
+                };
+                (self.output_dir.join(format!("stream_{}.m3u8", idx)), weight)
+            })
+            .collect();
+        LadderProgress::new(rungs)
+    }
 
-        if !status.success() {
-            return Err(VideoError::FfmpegFailed(
-                "FFmpeg failed (see logs above if DEBUG enabled)".to_string(),
-            ));
+    /// Segment container actually written.
+    ///
+    /// When encryption is used, segment_type must be mpegts (FFmpeg limitation),
+    /// so encrypted fMP4 requests fall back to TS.
+    ///
+    /// AV1 and VP9 can only be carried in fMP4 segments, so their output always
+    /// uses fMP4 (encrypted AV1/VP9 HLS is declined before encoding starts).
+    fn effective_segment_type(&self) -> SegmentType {
+        if self.needs_fmp4() {
+            return SegmentType::Fmp4;
         }
+        if self.key_info_path.is_some() && self.config.segment_type != SegmentType::MpegTs {
+            debug!("Encryption requested, using MPEG-TS segments instead of fMP4");
+            return SegmentType::MpegTs;
+        }
+        self.config.segment_type
+    }
 
-        Ok(())
+    /// Whether the encoder actually selected for the requested codec is AV1 or VP9
+    fn needs_fmp4(&self) -> bool {
+        matches!(
+            Codec::from_encoder(self.hwaccel.video_encoder(self.codec)),
+            Codec::AV1 | Codec::Vp9
+        )
+    }
+
+    /// Whether filters need the source decoded into CPU memory.
+    ///
+    /// FFmpeg's autorotate inserts the transpose for a rotated source, but
+    /// only for CPU frames; hardware frames would come out sideways.
+    fn needs_cpu_frames(&self) -> bool {
+        self.watermark.is_some()
+            || self.rotation != 0
+            || self.deinterlace.is_some_and(|d| d.needs_sw_frames(self.hwaccel))
+            || self.tonemap.is_some_and(|t| t.needs_sw_frames(self.hwaccel))
     }
 
     /// Add hardware acceleration input options
     fn add_hwaccel_input_options(&self, cmd: &mut TokioCommand) {
-        apply_hwaccel_input_options(&self.hwaccel, &self.source_codec, cmd, "HLS");
+        apply_hwaccel_input_options(
+            &self.hwaccel,
+            &self.source_codec,
+            self.needs_cpu_frames(),
+            self.device.as_ref(),
+            cmd,
+            "HLS",
+        );
     }
 
     fn build_complex_filter(&self) -> String {
@@ -267,9 +574,24 @@ impl FfmpegCommand {
         // For hardware acceleration that needs explicit frame upload (e.g., QSV when hwaccel_output_format
         // is not set, or NVENC when CUDA can't decode the source), prepend the hwupload filter to
         // convert software frames to hardware frames.
-        let sw_decode = self
-            .hwaccel
-            .needs_sw_decode(self.source_codec.as_deref());
+        // A watermark, software deinterlacing and tone-mapping work on CPU
+        // frames before they are uploaded for scaling
+        let sw_decode = self.needs_cpu_frames()
+            || self
+                .hwaccel
+                .needs_sw_decode(self.source_codec.as_deref());
+        let source = match source_filters(self.watermark.as_ref(), self.deinterlace, self.tonemap, self.hwaccel) {
+            Some(filters) => format!("{},", filters),
+            None => "[0:v]".to_string(),
+        };
+        // Hardware deinterlacing and tone-mapping run on uploaded frames,
+        // right before the split
+        let split = format!(
+            "{}split={}{}",
+            hw_source_filters(self.deinterlace, self.tonemap, self.hwaccel),
+            non_original.len(),
+            output_labels.join("")
+        );
 
         // When VideoToolbox needs software decode (e.g., AV1 on M1/M2), frames are in CPU
         // memory and scale_vt won't work — fall back to CPU "scale" filter.
@@ -283,61 +605,66 @@ impl FfmpegCommand {
             // For VAAPI, we accept both vaapi (from HW decode) and nv12 (from SW decode fallback)
             // and use hwupload to ensure they are in VAAPI memory before scaling.
             // When already in vaapi memory, this is very efficient.
+            // 10-bit HDR frames stay 10-bit until tone-mapped once uploaded
+            let format = if self.tonemap.is_some_and(|t| !t.needs_sw_frames(self.hwaccel)) {
+                "p010"
+            } else {
+                "nv12"
+            };
             format!(
-                "[0:v]format=nv12|vaapi,hwupload=extra_hw_frames=64,split={}{}",
-                non_original.len(),
-                output_labels.join("")
+                "{}format={}|vaapi,hwupload=extra_hw_frames=64,{}",
+                source, format, split
             )
         } else if sw_decode || self.hwaccel.hwaccel_output_format().is_none() {
             // Software decode path: frames are in CPU memory, need upload to GPU
             // This covers: QSV (no hwaccel_output_format), NVENC with AV1 SW decode, etc.
             if let Some(upload_filter) = self.hwaccel.upload_filter() {
-                format!(
-                    "[0:v]format=nv12,{},split={}{}",
-                    upload_filter,
-                    non_original.len(),
-                    output_labels.join("")
-                )
+                format!("{}format=nv12,{},{}", source, upload_filter, split)
             } else {
-                format!(
-                    "[0:v]split={}{}",
-                    non_original.len(),
-                    output_labels.join("")
-                )
+                format!("{}{}", source, split)
             }
         } else {
             // hwaccel_output_format is set, so frames are already in hardware memory
-            format!(
-                "[0:v]split={}{}",
-                non_original.len(),
-                output_labels.join("")
-            )
+            format!("{}{}", source, split)
         };
         parts.push(input_chain);
 
         // Scale filters for non-original resolutions using appropriate hardware filter
         // Use -2 for width to auto-calculate while preserving aspect ratio (and ensuring even dimensions)
+        // Portrait rungs are sized by their width, the shorter side, instead
+        // Capped rungs drop frames after scaling
         for (name, res) in &non_original {
+            let fps = res
+                .fps
+                .map(|f| format!(",{}", fps_filter(f)))
+                .unwrap_or_default();
             match (res.width, res.height) {
                 (Some(w), Some(h)) => {
                     // Both dimensions specified
                     parts.push(format!(
-                        "[{}]{}=w={}:h={}[{}out]",
-                        name, scale_filter, w, h, name
+                        "[{}]{}=w={}:h={}{}[{}out]",
+                        name, scale_filter, w, h, fps, name
+                    ));
+                }
+                (None, Some(size)) if self.config.portrait => {
+                    // Rung size applies to the width of portrait video
+                    parts.push(format!(
+                        "[{}]{}=w={}:h=-2{}[{}out]",
+                        name, scale_filter, size, fps, name
                     ));
                 }
                 (None, Some(h)) => {
                     // Only height specified - auto-calculate width to preserve aspect ratio
                     parts.push(format!(
-                        "[{}]{}=w=-2:h={}[{}out]",
-                        name, scale_filter, h, name
+                        "[{}]{}=w=-2:h={}{}[{}out]",
+                        name, scale_filter, h, fps, name
                     ));
                 }
                 (Some(w), None) => {
                     // Only width specified - auto-calculate height to preserve aspect ratio
                     parts.push(format!(
-                        "[{}]{}=w={}:h=-2[{}out]",
-                        name, scale_filter, w, name
+                        "[{}]{}=w={}:h=-2{}[{}out]",
+                        name, scale_filter, w, fps, name
                     ));
                 }
                 (None, None) => {
@@ -353,10 +680,52 @@ impl FfmpegCommand {
     }
 
     fn build_var_stream_map(&self) -> String {
-        (0..self.config.resolutions.len())
-            .map(|i| format!("v:{},a:{}", i, i))
-            .collect::<Vec<_>>()
-            .join(" ")
+        if !self.config.has_audio_group() {
+            return (0..self.config.resolutions.len())
+                .map(|i| format!("v:{},a:{}", i, i))
+                .collect::<Vec<_>>()
+                .join(" ");
+        }
+
+        // Video-only variants referencing one audio group, then one
+        // variant per audio track; the first track is the default
+        let videos = (0..self.config.resolutions.len())
+            .map(|i| format!("v:{},agroup:{}", i, AUDIO_GROUP));
+        let tracks = self.config.audio_tracks.iter().enumerate().map(|(t, track)| {
+            let mut entry = format!("a:{},agroup:{}", t, AUDIO_GROUP);
+            if let Some(language) = track.language.as_deref().map(var_stream_value) {
+                entry.push_str(&format!(",language:{}", language));
+            }
+            if t == 0 {
+                entry.push_str(",default:yes");
+            }
+            entry
+        });
+        videos.chain(tracks).collect::<Vec<_>>().join(" ")
+    }
+
+    /// Mappings for the shared audio group, one output stream per source track
+    fn audio_group_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for (t, track) in self.config.audio_tracks.iter().enumerate() {
+            args.extend([
+                "-map".to_string(),
+                format!("0:a:{}", t),
+                format!("-c:a:{}", t),
+                "aac".to_string(),
+                format!("-b:a:{}", t),
+                AUDIO_GROUP_BITRATE.to_string(),
+            ]);
+            if let Some(ref language) = track.language {
+                args.push(format!("-metadata:s:a:{}", t));
+                args.push(format!("language={}", language));
+            }
+            if let Some(ref title) = track.title {
+                args.push(format!("-metadata:s:a:{}", t));
+                args.push(format!("title={}", title));
+            }
+        }
+        args
     }
 
     fn add_output_options(&self, cmd: &mut Command) {
@@ -374,7 +743,10 @@ impl FfmpegCommand {
                     .arg(format!("-c:v:{}", idx))
                     .arg("copy");
             } else {
-                let codec = res.video_codec.as_deref().unwrap_or("libx265");
+                let codec = res
+                    .video_codec
+                    .as_deref()
+                    .unwrap_or(self.hwaccel.video_encoder(self.codec));
                 cmd.arg("-map")
                     .arg(format!("[{}out]", key))
                     .arg(format!("-c:v:{}", idx))
@@ -385,8 +757,13 @@ impl FfmpegCommand {
                     cmd.arg(format!("-tag:v:{}", idx)).arg("hvc1");
                 }
 
+                // Keyframes at the same timestamps in every rung
+                cmd.arg(format!("-force_key_frames:v:{}", idx))
+                    .arg(self.config.segmenting().force_key_frames());
+
                 if let Some(q) = res.quality {
-                    cmd.arg(format!("-crf:{}", idx)).arg(q.to_string());
+                    let (param, value) = self.hwaccel.quality_param(Codec::from_encoder(codec), q);
+                    cmd.arg(format!("{}:{}", param, idx)).arg(value);
                 }
 
                 if let Some(br) = &res.video_bitrate {
@@ -394,16 +771,20 @@ impl FfmpegCommand {
                 }
             }
 
-            // Audio
-            cmd.arg("-map")
-                .arg("0:a")
-                .arg(format!("-c:a:{}", idx))
-                .arg(res.audio_codec.as_deref().unwrap_or("aac"));
+            // Audio (muxed into each variant unless the tracks form a group)
+            if !self.config.has_audio_group() {
+                cmd.arg("-map")
+                    .arg("0:a")
+                    .arg(format!("-c:a:{}", idx))
+                    .arg(res.audio_codec.as_deref().unwrap_or("aac"));
 
-            if let Some(br) = &res.audio_bitrate {
-                cmd.arg(format!("-b:a:{}", idx)).arg(br);
+                if let Some(br) = &res.audio_bitrate {
+                    cmd.arg(format!("-b:a:{}", idx)).arg(br);
+                }
             }
         }
+
+        cmd.args(self.audio_group_args());
     }
 
     fn add_output_options_tokio(&self, cmd: &mut TokioCommand) {
@@ -437,6 +818,11 @@ impl FfmpegCommand {
                     cmd.arg(format!("-tag:v:{}", idx)).arg("hvc1");
                 }
 
+                // Keyframes at the same timestamps in every rung, so segments
+                // line up for switching between them
+                cmd.arg(format!("-force_key_frames:v:{}", idx))
+                    .arg(self.config.segmenting().force_key_frames());
+
                 // Add encoder-specific options (only for first encoded stream to avoid duplicates)
                 // Use actual codec (from encoder name) since VAAPI may fall back to a
                 // different codec than requested (e.g. h264_vaapi when HEVC isn't supported).
@@ -450,17 +836,24 @@ impl FfmpegCommand {
                     for (opt, val) in self.hwaccel.encoder_options(actual_codec) {
                         cmd.arg(opt).arg(val);
                     }
+                    for (opt, val) in self.hwaccel.device_options(self.device.as_ref()) {
+                        cmd.arg(opt).arg(val);
+                    }
                 }
 
-                // VideoToolbox: use target bitrate (-b:v) instead of quality-based VBR.
+                // VideoToolbox: use target bitrate (-b:v) instead of quality-based VBR,
+                // preferring the rung's own bitrate when the operator's ladder sets one.
                 // Other backends: use quality param (CRF/CQ/QP) with optional bitrate cap.
                 if let Some(height) = res.height {
                     if let Some(target_br) = self.hwaccel.video_bitrate(height, self.codec) {
-                        cmd.arg(format!("-b:v:{}", idx)).arg(target_br);
+                        cmd.arg(format!("-b:v:{}", idx))
+                            .arg(res.video_bitrate.as_deref().unwrap_or(target_br));
                     } else {
                         // Quality-based encoding for non-VideoToolbox backends
                         if let Some(q) = res.quality {
-                            let (quality_param, quality_value) = self.hwaccel.quality_param(q);
+                            let (quality_param, quality_value) = self
+                                .hwaccel
+                                .quality_param(Codec::from_encoder(video_codec), q);
                             let param_with_idx =
                                 format!("{}:{}", quality_param.trim_start_matches('-'), idx);
                             cmd.arg(format!("-{}", param_with_idx)).arg(&quality_value);
@@ -478,37 +871,95 @@ impl FfmpegCommand {
                         }
                     }
                 } else if let Some(q) = res.quality {
-                    let (quality_param, quality_value) = self.hwaccel.quality_param(q);
+                    let (quality_param, quality_value) = self
+                        .hwaccel
+                        .quality_param(Codec::from_encoder(video_codec), q);
                     let param_with_idx =
                         format!("{}:{}", quality_param.trim_start_matches('-'), idx);
                     cmd.arg(format!("-{}", param_with_idx)).arg(&quality_value);
                 }
             }
 
-            // Audio
-            cmd.arg("-map")
-                .arg("0:a")
-                .arg(format!("-c:a:{}", idx))
-                .arg(res.audio_codec.as_deref().unwrap_or("aac"));
+            // Audio (muxed into each variant unless the tracks form a group)
+            if !self.config.has_audio_group() {
+                cmd.arg("-map")
+                    .arg("0:a")
+                    .arg(format!("-c:a:{}", idx))
+                    .arg(res.audio_codec.as_deref().unwrap_or("aac"));
 
-            if let Some(br) = &res.audio_bitrate {
-                cmd.arg(format!("-b:a:{}", idx)).arg(br);
+                if let Some(br) = &res.audio_bitrate {
+                    cmd.arg(format!("-b:a:{}", idx)).arg(br);
+                }
             }
         }
+
+        cmd.args(self.audio_group_args());
+    }
+}
+
+/// Filters run on CPU frames of `[0:v]` before scaling: software
+/// deinterlacing and tone-mapping, then the watermark (drawn on the
+/// progressive SDR frames). The chain ends on an unlabeled pad.
+fn source_filters(
+    watermark: Option<&Watermark>,
+    deinterlace: Option<Deinterlace>,
+    tonemap: Option<Tonemap>,
+    hwaccel: HwAccel,
+) -> Option<String> {
+    let filters: Vec<String> = [
+        deinterlace
+            .filter(|d| d.needs_sw_frames(hwaccel))
+            .map(|d| d.sw_filter()),
+        tonemap
+            .filter(|t| t.needs_sw_frames(hwaccel))
+            .map(|t| t.sw_filter()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    match (watermark, filters.is_empty()) {
+        (None, true) => None,
+        (None, false) => Some(format!("[0:v]{}", filters.join(","))),
+        (Some(watermark), true) => Some(watermark.filter("[0:v]", 1)),
+        (Some(watermark), false) => Some(format!(
+            "[0:v]{}[pre];{}",
+            filters.join(","),
+            watermark.filter("[pre]", 1)
+        )),
     }
 }
 
+/// Filters run on frames once they are in `hwaccel`'s memory, before
+/// scaling: hardware deinterlacing, then tone-mapping. Each is followed by a
+/// comma.
+fn hw_source_filters(
+    deinterlace: Option<Deinterlace>,
+    tonemap: Option<Tonemap>,
+    hwaccel: HwAccel,
+) -> String {
+    [
+        deinterlace.and_then(|d| d.hw_filter(hwaccel)),
+        tonemap.and_then(|t| t.hw_filter(hwaccel)).map(str::to_string),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|f| format!("{},", f))
+    .collect()
+}
+
 fn apply_hwaccel_input_options(
     hwaccel: &HwAccel,
     source_codec: &Option<String>,
+    force_sw_decode: bool,
+    device: Option<&GpuDevice>,
     cmd: &mut TokioCommand,
     label: &str,
 ) {
-    let sw_decode = hwaccel.needs_sw_decode(source_codec.as_deref());
+    let sw_decode = force_sw_decode || hwaccel.needs_sw_decode(source_codec.as_deref());
     debug!(hwaccel = ?hwaccel, source_codec = ?source_codec, sw_decode = sw_decode, label = label, "Configuring hardware acceleration input options");
 
     // Initialize hardware device for filter graphs (always needed for encoding/scaling)
-    if let Some(init_device) = hwaccel.init_hw_device() {
+    if let Some(init_device) = hwaccel.init_hw_device(device) {
         cmd.arg("-init_hw_device").arg(&init_device);
     }
 
@@ -536,6 +987,11 @@ fn apply_hwaccel_input_options(
                         cmd.arg("-c:v").arg(decoder);
                     }
                 }
+            } else if let Some(GpuDevice::Cuda(index)) = device {
+                // Decode on the same GPU the frames are uploaded to
+                cmd.arg("-hwaccel_device").arg(index.to_string());
+            } else if let Some(GpuDevice::Render(path)) = device.filter(|_| *hwaccel == HwAccel::Qsv) {
+                cmd.arg("-qsv_device").arg(path);
             } else if let Some(device) = hwaccel.qsv_device() {
                 // QSV-specific device
                 cmd.arg("-qsv_device").arg(device);
@@ -553,7 +1009,7 @@ fn apply_hwaccel_input_options(
     cmd.arg("-threads").arg("0");
 }
 
-/// FFmpeg command builder for single MP4 output
+/// FFmpeg command builder for single-file (MP4 or WebM) output
 pub struct FfmpegMp4Command {
     input: String,
     output_path: PathBuf,
@@ -562,9 +1018,32 @@ pub struct FfmpegMp4Command {
     audio_bitrate: String,
     hwaccel: HwAccel,
     codec: Codec,
+    container: Container,
     /// Source video codec hint (e.g. "av1")
     source_codec: Option<String>,
     duration: Option<f64>,
+    /// Constant output frame rate for variable frame rate sources
+    frame_rate: Option<String>,
+    /// Frame rate the output is brought down to
+    fps: Option<f64>,
+    /// Overlay burned into every rendition
+    watermark: Option<Watermark>,
+    /// Deinterlacing for interlaced sources
+    deinterlace: Option<Deinterlace>,
+    /// HDR to SDR conversion for HDR sources
+    tonemap: Option<Tonemap>,
+    /// Clockwise display rotation of the source in degrees
+    rotation: u32,
+    /// Segment of the source to transcode
+    clip: Option<Clip>,
+    /// Loudness normalization applied to the audio
+    loudnorm: Option<Loudnorm>,
+    /// Drop the source\'s metadata (GPS, device, creation time)
+    strip_metadata: bool,
+    /// Job log that receives FFmpeg's stderr
+    log: Option<Arc<JobLog>>,
+    /// GPU the encode was assigned (None = FFmpeg's default device)
+    device: Option<GpuDevice>,
 }
 
 impl FfmpegMp4Command {
@@ -583,11 +1062,29 @@ impl FfmpegMp4Command {
             audio_bitrate: "128k".to_string(),
             hwaccel,
             codec,
+            container: Container::Mp4,
             source_codec: None,
             duration: None,
+            frame_rate: None,
+            fps: None,
+            watermark: None,
+            deinterlace: None,
+            tonemap: None,
+            rotation: 0,
+            clip: None,
+            loudnorm: None,
+            strip_metadata: false,
+            log: None,
+            device: None,
         }
     }
 
+    /// Set the output container (must be able to carry the encoded codec)
+    pub fn with_container(mut self, container: Container) -> Self {
+        self.container = container;
+        self
+    }
+
     /// Set the source codec hint for explicit hardware decoder selection
     pub fn with_source_codec(mut self, codec: Option<&str>) -> Self {
         self.source_codec = codec.map(|s| s.to_string());
@@ -602,29 +1099,142 @@ impl FfmpegMp4Command {
         self
     }
 
+    /// Encode at a constant frame rate (FFmpeg rate syntax, e.g. "30000/1001"),
+    /// duplicating or dropping frames of a variable frame rate source
+    pub fn with_frame_rate(mut self, rate: Option<&str>) -> Self {
+        self.frame_rate = rate.map(|r| r.to_string());
+        self
+    }
+
+    /// Bring the output down to `fps` (see [`crate::video::fps_cap::FpsCap`])
+    pub fn with_fps(mut self, fps: Option<f64>) -> Self {
+        self.fps = fps;
+        self
+    }
+
+    /// Burn a watermark into the output (forces software decoding)
+    pub fn with_watermark(mut self, watermark: Option<&Watermark>) -> Self {
+        self.watermark = watermark.cloned();
+        self
+    }
+
+    /// Deinterlace an interlaced source
+    pub fn with_deinterlace(mut self, deinterlace: Option<Deinterlace>) -> Self {
+        self.deinterlace = deinterlace;
+        self
+    }
+
+    /// Tone-map an HDR source to SDR
+    pub fn with_tonemap(mut self, tonemap: Option<Tonemap>) -> Self {
+        self.tonemap = tonemap;
+        self
+    }
+
+    /// Turn a source stored sideways upright (clockwise degrees, see
+    /// [`crate::video::metadata::StreamInfo::rotation`])
+    pub fn with_rotation(mut self, rotation: u32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Transcode only part of the source
+    pub fn with_clip(mut self, clip: Option<Clip>) -> Self {
+        self.clip = clip;
+        self
+    }
+
+    /// Normalize the loudness of the audio
+    pub fn with_loudnorm(mut self, loudnorm: Option<&Loudnorm>) -> Self {
+        self.loudnorm = loudnorm.cloned();
+        self
+    }
+
+    /// Drop the source's global and per-stream metadata from the output
+    pub fn with_strip_metadata(mut self, strip: bool) -> Self {
+        self.strip_metadata = strip;
+        self
+    }
+
+    /// Capture stderr into a job log instead of passing it through
+    pub fn with_log(mut self, log: Option<Arc<JobLog>>) -> Self {
+        self.log = log;
+        self
+    }
+
+    /// Pin the encode to a GPU
+    pub fn with_device(mut self, device: Option<&GpuDevice>) -> Self {
+        self.device = device.cloned();
+        self
+    }
+
     /// Set the CRF (quality) value
     pub fn with_crf(mut self, crf: u32) -> Self {
         self.crf = crf;
         self
     }
 
+    /// Program and arguments `run` would execute, for dry runs
+    pub fn command_line(&self, ffmpeg_path: &Path) -> Vec<String> {
+        argv(&self.command(ffmpeg_path, true))
+    }
+
     /// Run the FFmpeg MP4 encoding command asynchronously
     pub async fn run(
         &self,
         ffmpeg_path: &Path,
         progress: Option<std::sync::Arc<std::sync::atomic::AtomicU64>>,
     ) -> Result<(), VideoError> {
+        let mut cmd = self.command(ffmpeg_path, progress.is_some());
+        debug!(hwaccel = %self.hwaccel, "\n{}", format_cmd(&cmd));
+
+        if let Some(log) = &self.log {
+            log.event(format!("Running {}", format_cmd(&cmd)));
+        }
+        let mut child = cmd.spawn().map_err(VideoError::Io)?;
+        let stderr = read_stderr(
+            child.stderr.take().expect("Stderr must be piped"),
+            self.log.clone(),
+        );
+
+        // If progress tracking is enabled, spawn a task to read stdout
+        if let Some(p) = progress {
+            let tracker = crate::util::ffmpeg_progress::FfmpegProgressTracker {
+                progress_ms: p,
+                ladder: None,
+            };
+            let stdout = child.stdout.take().expect("Stdout must be piped");
+            tracker.track_progress(stdout).await.map_err(VideoError::Io)?;
+        }
+
+        let status = child.wait().await.map_err(VideoError::Io)?;
+        let stderr = stderr.await.unwrap_or_default();
+
+        if !status.success() {
+            return Err(ffmpeg_error::failure("MP4 encoding failed", &stderr.join("\n")));
+        }
+
+        Ok(())
+    }
+
+    /// Build the MP4 command, with `-progress` output on stdout if `progress`
+    fn command(&self, ffmpeg_path: &Path, progress: bool) -> TokioCommand {
         let mut cmd = TokioCommand::new(ffmpeg_path);
+        cmd.kill_on_drop(true);
 
         // Overwrite without asking, non-interactive
         cmd.arg("-y").arg("-nostdin");
 
         // Progress reporting to stdout
-        if progress.is_some() {
+        if progress {
             cmd.arg("-progress").arg("-");
             cmd.stdout(std::process::Stdio::piped());
         }
 
+        // Stderr is read to classify failures; periodic stats lines would
+        // crowd out everything else
+        cmd.arg("-hide_banner").arg("-nostats");
+        cmd.stderr(std::process::Stdio::piped());
+
         // Add network reconnection options if input is a URL
         if self.input.starts_with("http://") || self.input.starts_with("https://") {
             cmd.arg("-reconnect")
@@ -640,13 +1250,21 @@ impl FfmpegMp4Command {
         // Hardware acceleration input options (before -i)
         self.add_hwaccel_input_options(&mut cmd);
 
+        // Seek to the clip range; its end replaces the duration limit
+        if let Some(clip) = &self.clip {
+            cmd.args(clip.input_args());
+        }
+
         // Limit duration if provided
-        if let Some(d) = self.duration {
+        if let Some(d) = self.duration.filter(|_| self.clip.is_none_or(|c| c.end.is_none())) {
             cmd.arg("-t").arg(d.to_string());
         }
 
         // Input
         cmd.arg("-i").arg(&self.input);
+        if let Some(image) = self.watermark.as_ref().and_then(Watermark::input) {
+            cmd.arg("-i").arg(image);
+        }
 
         // Scale filter using appropriate hardware filter
         // Use -2 for width to auto-calculate while preserving aspect ratio (and ensuring even dimensions)
@@ -654,9 +1272,12 @@ impl FfmpegMp4Command {
 
         // For hardware acceleration that needs explicit frame upload (e.g., QSV when hwaccel_output_format
         // is not set, or NVENC when CUDA can't decode AV1), prepend the hwupload filter.
-        let sw_decode = self
-            .hwaccel
-            .needs_sw_decode(self.source_codec.as_deref());
+        // A watermark, software deinterlacing and tone-mapping work on CPU
+        // frames before they are uploaded for scaling
+        let sw_decode = self.needs_cpu_frames()
+            || self
+                .hwaccel
+                .needs_sw_decode(self.source_codec.as_deref());
 
         // When VideoToolbox needs software decode (e.g., AV1 on M1/M2), frames are in CPU
         // memory and scale_vt won't work — fall back to CPU "scale" filter.
@@ -665,20 +1286,42 @@ impl FfmpegMp4Command {
         } else {
             self.hwaccel.scale_filter()
         };
+        // Hardware deinterlacing and tone-mapping run on uploaded frames
+        let scale = format!(
+            "{}{}=w=-2:h={}{}",
+            hw_source_filters(self.deinterlace, self.tonemap, self.hwaccel),
+            scale_filter,
+            height,
+            self.fps.map(|f| format!(",{}", fps_filter(f))).unwrap_or_default()
+        );
         let vf = if self.hwaccel == HwAccel::Vaapi {
             // For VAAPI, we accept both vaapi (from HW decode) and nv12 (from SW decode fallback)
             // and use hwupload to ensure they are in VAAPI memory before scaling.
-            format!("format=nv12|vaapi,hwupload=extra_hw_frames=64,{}=w=-2:h={}", scale_filter, height)
+            let format = if self.tonemap.is_some_and(|t| !t.needs_sw_frames(self.hwaccel)) {
+                "p010"
+            } else {
+                "nv12"
+            };
+            format!("format={}|vaapi,hwupload=extra_hw_frames=64,{}", format, scale)
         } else if sw_decode || self.hwaccel.hwaccel_output_format().is_none() {
             if let Some(upload_filter) = self.hwaccel.upload_filter() {
-                format!("format=nv12,{},{}=w=-2:h={}", upload_filter, scale_filter, height)
+                format!("format=nv12,{},{}", upload_filter, scale)
             } else {
-                format!("{}=w=-2:h={}", scale_filter, height)
+                scale
             }
         } else {
-            format!("{}=w=-2:h={}", scale_filter, height)
+            scale
         };
-        cmd.arg("-vf").arg(vf);
+        match source_filters(self.watermark.as_ref(), self.deinterlace, self.tonemap, self.hwaccel) {
+            // Labeled pads need a complex graph; its unlabeled output is
+            // mapped automatically alongside the source audio
+            Some(filters) => {
+                cmd.arg("-filter_complex").arg(format!("{},{}", filters, vf));
+            }
+            None => {
+                cmd.arg("-vf").arg(vf);
+            }
+        }
 
         // Video codec with hardware acceleration
         let encoder = self.hwaccel.video_encoder(self.codec);
@@ -695,13 +1338,16 @@ impl FfmpegMp4Command {
         for (opt, val) in self.hwaccel.encoder_options(actual_codec) {
             cmd.arg(opt).arg(val);
         }
+        for (opt, val) in self.hwaccel.device_options(self.device.as_ref()) {
+            cmd.arg(opt).arg(val);
+        }
 
         // VideoToolbox: use target bitrate (-b:v) instead of quality-based VBR.
         // Other backends: use quality param (CRF/CQ/QP) with optional bitrate cap.
         if let Some(target_br) = self.hwaccel.video_bitrate(height, self.codec) {
             cmd.arg("-b:v").arg(target_br);
         } else {
-            let (quality_param, quality_value) = self.hwaccel.quality_param(self.crf);
+            let (quality_param, quality_value) = self.hwaccel.quality_param(actual_codec, self.crf);
             cmd.arg(quality_param).arg(&quality_value);
 
             // Apply bitrate cap for hardware encoders
@@ -711,51 +1357,361 @@ impl FfmpegMp4Command {
             }
         }
 
-        // Audio codec
+        // Audio codec (AAC in MP4, Opus in WebM)
         cmd.arg("-c:a")
-            .arg("aac")
+            .arg(self.container.audio_encoder())
             .arg("-b:a")
             .arg(&self.audio_bitrate);
+        if let Some(ref loudnorm) = self.loudnorm {
+            cmd.arg("-af").arg(loudnorm.filter());
+        }
+        if self.strip_metadata {
+            cmd.args(STRIP_METADATA_ARGS);
+        }
 
-        // MP4 streaming optimization
-        cmd.arg("-movflags").arg("+faststart");
+        if let Some(ref rate) = self.frame_rate {
+            // A capped output is constant at its own, lower rate
+            let rate = self.fps.map_or_else(|| rate.clone(), |f| format!("{:.3}", f));
+            cmd.arg(fps_mode_flag()).arg("cfr").arg("-r").arg(rate);
+        }
+
+        // Keep the source's chapters (FFmpeg shifts and trims them to a clip)
+        cmd.arg("-map_chapters").arg("0");
+
+        // MP4 streaming optimization (moov atom up front)
+        if self.container == Container::Mp4 {
+            cmd.arg("-movflags").arg("+faststart");
+        }
 
         // Output file
         cmd.arg(&self.output_path);
+        cmd
+    }
 
-        debug!(hwaccel = %self.hwaccel, "\n{}", format_cmd(&cmd));
+    /// Whether filters need the source decoded into CPU memory.
+    ///
+    /// FFmpeg's autorotate inserts the transpose for a rotated source, but
+    /// only for CPU frames; hardware frames would come out sideways.
+    fn needs_cpu_frames(&self) -> bool {
+        self.watermark.is_some()
+            || self.rotation != 0
+            || self.deinterlace.is_some_and(|d| d.needs_sw_frames(self.hwaccel))
+            || self.tonemap.is_some_and(|t| t.needs_sw_frames(self.hwaccel))
+    }
 
-        let mut child = cmd.spawn().map_err(VideoError::Io)?;
+    /// Add hardware acceleration input options
+    fn add_hwaccel_input_options(&self, cmd: &mut TokioCommand) {
+        apply_hwaccel_input_options(
+            &self.hwaccel,
+            &self.source_codec,
+            self.needs_cpu_frames(),
+            self.device.as_ref(),
+            cmd,
+            "MP4",
+        );
+    }
+}
 
-        // If progress tracking is enabled, spawn a task to read stdout
-        if let Some(p) = progress {
-            let tracker = crate::util::ffmpeg_progress::FfmpegProgressTracker { progress_ms: p };
-            let stdout = child.stdout.take().expect("Stdout must be piped");
-            tracker.track_progress(stdout).await.map_err(VideoError::Io)?;
+/// FFmpeg command that grabs a single frame as a JPEG poster image
+pub struct FfmpegThumbnailCommand {
+    input: String,
+    output_path: PathBuf,
+    timestamp_secs: f64,
+    max_height: Option<u32>,
+}
+
+impl FfmpegThumbnailCommand {
+    pub fn new(input: &str, output_path: PathBuf, timestamp_secs: f64) -> Self {
+        Self {
+            input: input.to_string(),
+            output_path,
+            timestamp_secs,
+            max_height: None,
         }
+    }
 
-        let status = child.wait().await.map_err(VideoError::Io)?;
+    /// Downscale frames taller than `height` (never upscales)
+    pub fn with_max_height(mut self, height: Option<u32>) -> Self {
+        self.max_height = height;
+        self
+    }
 
-        if !status.success() {
-            return Err(VideoError::FfmpegFailed(
-                "FFmpeg MP4 encoding failed (see logs above if DEBUG enabled)".to_string(),
+    fn build(&self, ffmpeg_path: &Path) -> TokioCommand {
+        let mut cmd = TokioCommand::new(ffmpeg_path);
+        cmd.kill_on_drop(true);
+
+        cmd.arg("-y").arg("-nostdin").arg("-loglevel").arg("error");
+
+        // Seek before the input so only the frames near the timestamp are decoded
+        cmd.arg("-ss")
+            .arg(format!("{:.3}", self.timestamp_secs))
+            .arg("-i")
+            .arg(&self.input);
+
+        if let Some(h) = self.max_height {
+            cmd.arg("-vf").arg(format!("scale=-2:'min({},ih)'", h));
+        }
+
+        cmd.arg("-frames:v")
+            .arg("1")
+            .arg("-q:v")
+            .arg("2")
+            .arg(&self.output_path);
+
+        cmd
+    }
+
+    pub async fn run(&self, ffmpeg_path: &Path) -> Result<(), VideoError> {
+        let mut cmd = self.build(ffmpeg_path);
+        debug!("\n{}", format_cmd(&cmd));
+
+        let output = cmd.output().await.map_err(VideoError::Io)?;
+        if !output.status.success() {
+            return Err(ffmpeg_error::failure(
+                &format!("Thumbnail extraction at {:.1}s failed", self.timestamp_secs),
+                &String::from_utf8_lossy(&output.stderr),
             ));
         }
 
+        // FFmpeg exits successfully without writing a frame when seeking past the end
+        if !self.output_path.exists() {
+            return Err(VideoError::FfmpegFailed(format!(
+                "No frame at {:.1}s",
+                self.timestamp_secs
+            )));
+        }
+
         Ok(())
     }
+}
 
-    /// Add hardware acceleration input options
-    fn add_hwaccel_input_options(&self, cmd: &mut TokioCommand) {
-        apply_hwaccel_input_options(&self.hwaccel, &self.source_codec, cmd, "MP4");
+/// FFmpeg command that decodes a single frame, downscaled to a fixed size,
+/// as raw RGB24 on stdout (for computing placeholders like blurhashes)
+pub struct FfmpegRawFrameCommand {
+    input: String,
+    timestamp_secs: f64,
+    width: u32,
+    height: u32,
+}
+
+impl FfmpegRawFrameCommand {
+    pub fn new(input: &str, timestamp_secs: f64, width: u32, height: u32) -> Self {
+        Self {
+            input: input.to_string(),
+            timestamp_secs,
+            width,
+            height,
+        }
+    }
+
+    fn build(&self, ffmpeg_path: &Path) -> TokioCommand {
+        let mut cmd = TokioCommand::new(ffmpeg_path);
+        cmd.kill_on_drop(true);
+
+        cmd.arg("-nostdin").arg("-loglevel").arg("error");
+
+        cmd.arg("-ss")
+            .arg(format!("{:.3}", self.timestamp_secs))
+            .arg("-i")
+            .arg(&self.input);
+
+        cmd.arg("-frames:v")
+            .arg("1")
+            .arg("-vf")
+            .arg(format!("scale={}:{}", self.width, self.height))
+            .arg("-pix_fmt")
+            .arg("rgb24")
+            .arg("-f")
+            .arg("rawvideo")
+            .arg("pipe:1");
+
+        cmd
+    }
+
+    /// Decode the frame, returning `width * height * 3` bytes
+    pub async fn run(&self, ffmpeg_path: &Path) -> Result<Vec<u8>, VideoError> {
+        let mut cmd = self.build(ffmpeg_path);
+        debug!("\n{}", format_cmd(&cmd));
+
+        let output = cmd.output().await.map_err(VideoError::Io)?;
+        if !output.status.success() {
+            return Err(ffmpeg_error::failure(
+                &format!("Frame extraction at {:.1}s failed", self.timestamp_secs),
+                &String::from_utf8_lossy(&output.stderr),
+            ));
+        }
+
+        let expected = self.width as usize * self.height as usize * 3;
+        if output.stdout.len() != expected {
+            return Err(VideoError::FfmpegFailed(format!(
+                "No frame at {:.1}s",
+                self.timestamp_secs
+            )));
+        }
+
+        Ok(output.stdout)
+    }
+}
+
+/// Height of animated previews (downscale only)
+const PREVIEW_HEIGHT: u32 = 480;
+
+/// Options dropping the source's global and per-stream metadata (GPS
+/// location, device make and model, creation time). Unlike a bare
+/// `-map_metadata -1` they leave chapter titles alone.
+const STRIP_METADATA_ARGS: [&str; 4] = ["-map_metadata:g", "-1", "-map_metadata:s", "-1"];
+
+/// Frame rate of animated previews
+const PREVIEW_FPS: u32 = 12;
+
+/// FFmpeg command that renders a short looping animated preview
+pub struct FfmpegPreviewCommand {
+    input: String,
+    output_path: PathBuf,
+    format: PreviewFormat,
+    duration_secs: f64,
+}
+
+impl FfmpegPreviewCommand {
+    pub fn new(input: &str, output_path: PathBuf, format: PreviewFormat, duration_secs: f64) -> Self {
+        Self {
+            input: input.to_string(),
+            output_path,
+            format,
+            duration_secs,
+        }
+    }
+
+    fn build(&self, ffmpeg_path: &Path) -> TokioCommand {
+        let mut cmd = TokioCommand::new(ffmpeg_path);
+        cmd.kill_on_drop(true);
+
+        cmd.arg("-y").arg("-nostdin").arg("-loglevel").arg("error");
+
+        cmd.arg("-t")
+            .arg(format!("{:.3}", self.duration_secs))
+            .arg("-i")
+            .arg(&self.input);
+
+        let base = format!(
+            "fps={},scale=-2:'min({},ih)':flags=lanczos",
+            PREVIEW_FPS, PREVIEW_HEIGHT
+        );
+        match self.format {
+            PreviewFormat::Gif => {
+                // Per-clip palette keeps GIF colors reasonable
+                cmd.arg("-vf").arg(format!(
+                    "{},split[a][b];[a]palettegen[p];[b][p]paletteuse",
+                    base
+                ));
+            }
+            PreviewFormat::WebP => {
+                cmd.arg("-vf")
+                    .arg(base)
+                    .arg("-c:v")
+                    .arg("libwebp")
+                    .arg("-q:v")
+                    .arg("60");
+            }
+        }
+
+        cmd.arg("-an").arg("-loop").arg("0").arg(&self.output_path);
+
+        cmd
+    }
+
+    pub async fn run(&self, ffmpeg_path: &Path) -> Result<(), VideoError> {
+        let mut cmd = self.build(ffmpeg_path);
+        debug!("\n{}", format_cmd(&cmd));
+
+        let output = cmd.output().await.map_err(VideoError::Io)?;
+        if !output.status.success() {
+            return Err(ffmpeg_error::failure(
+                "Preview generation failed",
+                &String::from_utf8_lossy(&output.stderr),
+            ));
+        }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::video::deinterlace::FieldOrder;
+    use crate::video::fps_cap::FpsCap;
+    use crate::video::tonemap::HdrFormat;
+    use crate::video::transform::{AudioTrack, ORIGINAL_CRF};
     use std::ffi::OsStr;
 
+    #[test]
+    fn test_preview_command_building() {
+        let args_for = |format: PreviewFormat| -> Vec<String> {
+            FfmpegPreviewCommand::new("input.mp4", PathBuf::from("/tmp/preview"), format, 3.0)
+                .build(Path::new("ffmpeg"))
+                .as_std()
+                .get_args()
+                .map(|a| a.to_string_lossy().into_owned())
+                .collect()
+        };
+
+        let webp = args_for(PreviewFormat::WebP);
+        assert!(webp.contains(&"libwebp".to_string()));
+        assert!(webp.contains(&"3.000".to_string()));
+        assert!(webp.contains(&"-an".to_string()));
+
+        let gif = args_for(PreviewFormat::Gif);
+        assert!(!gif.contains(&"libwebp".to_string()));
+        assert!(gif.iter().any(|a| a.contains("palettegen")));
+    }
+
+    #[tokio::test]
+    async fn test_read_stderr() {
+        let log = Arc::new(JobLog::default());
+        let stderr: &[u8] = b"Input #0, mov\n\nError while decoding stream\r\n";
+        let tail = read_stderr(stderr, Some(log.clone())).await.unwrap();
+        assert_eq!(tail, vec!["Input #0, mov", "Error while decoding stream"]);
+        let (lines, _) = log.tail(10);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].source, "ffmpeg");
+
+        let many: String = (0..STDERR_TAIL_LINES + 3).map(|i| format!("line {}\n", i)).collect();
+        let tail = read_stderr(std::io::Cursor::new(many.into_bytes()), None).await.unwrap();
+        assert_eq!(tail.len(), STDERR_TAIL_LINES);
+        assert_eq!(tail[0], "line 3");
+    }
+
+    #[test]
+    fn test_thumbnail_command_building() {
+        let cmd = FfmpegThumbnailCommand::new("input.mp4", PathBuf::from("/tmp/thumb_0.jpg"), 12.5)
+            .with_max_height(Some(720));
+        let built = cmd.build(Path::new("ffmpeg"));
+        let args: Vec<&OsStr> = built.as_std().get_args().collect();
+
+        let ss = args.iter().position(|a| *a == "-ss").unwrap();
+        let input = args.iter().position(|a| *a == "-i").unwrap();
+        assert!(ss < input);
+        assert_eq!(args[ss + 1], "12.500");
+        assert!(args.contains(&OsStr::new("scale=-2:'min(720,ih)'")));
+        assert!(args.contains(&OsStr::new("-frames:v")));
+        assert_eq!(args.last(), Some(&OsStr::new("/tmp/thumb_0.jpg")));
+    }
+
+    #[test]
+    fn test_raw_frame_command_building() {
+        let cmd = FfmpegRawFrameCommand::new("input.mp4", 4.0, 32, 32);
+        let built = cmd.build(Path::new("ffmpeg"));
+        let args: Vec<&OsStr> = built.as_std().get_args().collect();
+
+        let ss = args.iter().position(|a| *a == "-ss").unwrap();
+        let input = args.iter().position(|a| *a == "-i").unwrap();
+        assert!(ss < input);
+        assert!(args.contains(&OsStr::new("scale=32:32")));
+        assert!(args.contains(&OsStr::new("rgb24")));
+        assert_eq!(args.last(), Some(&OsStr::new("pipe:1")));
+    }
+
     #[test]
     fn test_ffmpeg_command_building() {
         let config = TransformConfig::default();
@@ -777,6 +1733,441 @@ mod tests {
         assert!(args.contains(&OsStr::new("input.mp4")));
     }
 
+    #[test]
+    fn test_keyframes_aligned_across_rungs() {
+        let mut config = TransformConfig::for_resolutions(
+            Some((1920, 1080)),
+            &[Resolution::R360p, Resolution::R720p, Resolution::Original],
+            Some("h264"),
+            &[],
+            ORIGINAL_CRF,
+        );
+        config.hls_time = 4;
+        config.keyframe_interval = 1.0;
+        let args: Vec<String> = FfmpegCommand::new(
+            "input.mp4",
+            Path::new("/tmp/output"),
+            config,
+            HwAccel::Software,
+            Codec::H264,
+        )
+        .build()
+        .get_args()
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect();
+
+        let forced: Vec<&str> = args
+            .windows(2)
+            .filter(|w| w[0].starts_with("-force_key_frames"))
+            .map(|w| w[1].as_str())
+            .collect();
+        // The copied original keeps its own keyframes
+        assert_eq!(forced, vec!["expr:gte(t,n_forced*1)"; 2]);
+        let hls_time = args.iter().position(|a| a == "-hls_time").unwrap();
+        assert_eq!(args[hls_time + 1], "4");
+    }
+
+    #[test]
+    fn test_segment_type_selection() {
+        let args_for_codec = |codec: Codec, segment_type: SegmentType, encrypted: bool| {
+            let config = TransformConfig {
+                segment_type,
+                ..TransformConfig::default()
+            };
+            let mut cmd = FfmpegCommand::new(
+                "input.mp4",
+                Path::new("/tmp/output"),
+                config,
+                HwAccel::Software,
+                codec,
+            );
+            if encrypted {
+                cmd = cmd.with_encryption(Path::new("/tmp/output/key_info.txt"));
+            }
+            cmd.build()
+                .get_args()
+                .map(|a| a.to_string_lossy().into_owned())
+                .collect::<Vec<String>>()
+        };
+        let args_for = |segment_type, encrypted| args_for_codec(Codec::H264, segment_type, encrypted);
+
+        let fmp4 = args_for(SegmentType::Fmp4, false);
+        assert!(fmp4.contains(&"fmp4".to_string()));
+        assert!(fmp4.iter().any(|a| a.ends_with("stream_%v_%03d.m4s")));
+
+        let ts = args_for(SegmentType::MpegTs, false);
+        assert!(ts.contains(&"mpegts".to_string()));
+        assert!(ts.iter().any(|a| a.ends_with("stream_%v_%03d.ts")));
+
+        // Encrypted fMP4 falls back to TS
+        let encrypted = args_for(SegmentType::Fmp4, true);
+        assert!(encrypted.contains(&"mpegts".to_string()));
+        assert!(encrypted.contains(&"-hls_key_info_file".to_string()));
+
+        // AV1 can't go in TS segments
+        let av1 = args_for_codec(Codec::AV1, SegmentType::MpegTs, false);
+        assert!(av1.contains(&"fmp4".to_string()));
+        assert!(av1.contains(&"libsvtav1".to_string()));
+        // 720p rung: x264-scale CRF 26 maps to AV1 CRF 36
+        assert!(av1.contains(&"36".to_string()));
+    }
+
+    #[test]
+    fn test_constant_frame_rate_output() {
+        let args_for = |frame_rate: Option<&str>| {
+            let mut config = TransformConfig::default();
+            if frame_rate.is_some() {
+                config.reencode_original(Some(1080));
+            }
+            FfmpegCommand::new(
+                "input.mp4",
+                Path::new("/tmp/output"),
+                config,
+                HwAccel::Software,
+                Codec::H264,
+            )
+            .with_frame_rate(frame_rate)
+            .build()
+            .get_args()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect::<Vec<String>>()
+        };
+
+        let constant = args_for(None);
+        assert!(constant.contains(&"copy".to_string()));
+        assert!(!constant.contains(&"-r".to_string()));
+
+        // No FFmpeg detected in tests, so the current flag is used
+        let variable = args_for(Some("30000/1001"));
+        let mode = variable.iter().position(|a| a == "-fps_mode").unwrap();
+        assert_eq!(variable[mode + 1], "cfr");
+        assert_eq!(variable[mode + 3], "30000/1001");
+        // The original can't be stream-copied at a new frame rate
+        assert!(!variable.contains(&"copy".to_string()));
+    }
+
+    #[test]
+    fn test_fps_cap_per_rung() {
+        let mut config = TransformConfig::for_resolutions(
+            Some((1920, 1080)),
+            &[Resolution::R360p, Resolution::R720p],
+            Some("h264"),
+            &[],
+            ORIGINAL_CRF,
+        );
+        config.apply_fps_cap(FpsCap::new(60, 120.0).unwrap());
+        let cmd = FfmpegCommand::new(
+            "input.mp4",
+            Path::new("/tmp/output"),
+            config,
+            HwAccel::Software,
+            Codec::H264,
+        )
+        .with_frame_rate(Some("120"));
+
+        let graph = cmd.build_complex_filter();
+        assert!(graph.contains("scale=w=-2:h=360,fps=30.000[360pout]"));
+        assert!(graph.contains("scale=w=-2:h=720,fps=60.000[720pout]"));
+
+        // The VFR target rate would otherwise duplicate the dropped frames
+        let args = cmd.frame_rate_args();
+        assert_eq!(&args[2..4], ["-r", "120"]);
+        assert_eq!(&args[4..], ["-r:v:0", "30.000", "-r:v:1", "60.000"]);
+    }
+
+    #[test]
+    fn test_portrait_rungs_scale_width() {
+        let config = TransformConfig::for_resolutions(
+            Some((1080, 1920)),
+            &[Resolution::R360p, Resolution::R720p],
+            Some("h264"),
+            &[],
+            ORIGINAL_CRF,
+        );
+        let cmd = FfmpegCommand::new(
+            "input.mp4",
+            Path::new("/tmp/output"),
+            config,
+            HwAccel::Software,
+            Codec::H264,
+        );
+
+        let graph = cmd.build_complex_filter();
+        assert!(graph.contains("scale=w=360:h=-2[360pout]"));
+        assert!(graph.contains("scale=w=720:h=-2[720pout]"));
+    }
+
+    #[test]
+    fn test_watermark_overlay() {
+        let watermark = Watermark::new("https://example.com/logo.png", None, None).unwrap();
+        let mut config = TransformConfig::default();
+        config.reencode_original(Some(1080));
+        let args: Vec<String> = FfmpegCommand::new(
+            "input.mp4",
+            Path::new("/tmp/output"),
+            config,
+            HwAccel::Software,
+            Codec::H264,
+        )
+        .with_watermark(Some(&watermark))
+        .build()
+        .get_args()
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect();
+
+        let inputs: Vec<_> = args.iter().filter(|a| *a == "-i").collect();
+        assert_eq!(inputs.len(), 2);
+        assert!(args.contains(&"https://example.com/logo.png".to_string()));
+        assert!(!args.contains(&"copy".to_string()));
+
+        let filter = args.iter().position(|a| a == "-filter_complex").unwrap();
+        let graph = &args[filter + 1];
+        assert!(graph.starts_with("[1:v]format=rgba"));
+        assert!(graph.contains(":format=auto,split="));
+    }
+
+    #[test]
+    fn test_hdr_tonemap() {
+        let graph_for = |hwaccel: HwAccel, watermark: Option<&Watermark>| {
+            let tonemap = Tonemap { source: HdrFormat::Pq, keep_original: false };
+            let mut config = TransformConfig::default();
+            config.reencode_original(Some(1080));
+            FfmpegCommand::new("input.mp4", Path::new("/tmp/output"), config, hwaccel, Codec::H264)
+                .with_watermark(watermark)
+                .with_tonemap(Some(tonemap))
+                .build_complex_filter()
+        };
+
+        let graph = graph_for(HwAccel::Software, None);
+        assert!(graph.starts_with("[0:v]zscale=tin=smpte2084:"));
+        assert!(graph.contains("format=yuv420p,split="));
+
+        // The watermark is drawn on the tone-mapped frames
+        let text = Watermark::new("hello", None, None).unwrap();
+        let graph = graph_for(HwAccel::Software, Some(&text));
+        assert!(graph.contains("format=yuv420p[pre];[pre]drawtext="));
+
+        // VAAPI tone-maps HDR10 on the GPU
+        let graph = graph_for(HwAccel::Vaapi, None);
+        assert!(graph.starts_with("[0:v]format=p010|vaapi,hwupload=extra_hw_frames=64,tonemap_vaapi="));
+        assert!(!graph.contains("zscale"));
+    }
+
+    #[test]
+    fn test_deinterlace() {
+        let deinterlace = Deinterlace { field_order: FieldOrder::TopFirst };
+        let graph_for = |hwaccel: HwAccel, tonemap: Option<Tonemap>| {
+            let mut config = TransformConfig::default();
+            config.reencode_original(Some(1080));
+            FfmpegCommand::new("input.mp4", Path::new("/tmp/output"), config, hwaccel, Codec::H264)
+                .with_source_codec(Some("h264"))
+                .with_deinterlace(Some(deinterlace))
+                .with_tonemap(tonemap)
+                .build_complex_filter()
+        };
+
+        let graph = graph_for(HwAccel::Software, None);
+        assert!(graph.starts_with("[0:v]bwdif=mode=send_frame:parity=tff:deint=all,split="));
+
+        // Fields are separated before tone-mapping
+        let hdr10 = Tonemap { source: HdrFormat::Pq, keep_original: false };
+        let graph = graph_for(HwAccel::Software, Some(hdr10));
+        assert!(graph.starts_with("[0:v]bwdif=mode=send_frame:parity=tff:deint=all,zscale="));
+
+        // GPUs deinterlace their own frames, without a software decode
+        let graph = graph_for(HwAccel::Nvenc, None);
+        assert!(graph.starts_with("[0:v]yadif_cuda=mode=send_frame:parity=tff:deint=all,split="));
+        let graph = graph_for(HwAccel::Vaapi, Some(hdr10));
+        assert!(graph.starts_with(
+            "[0:v]format=p010|vaapi,hwupload=extra_hw_frames=64,deinterlace_vaapi=rate=frame,tonemap_vaapi="
+        ));
+    }
+
+    #[test]
+    fn test_rotated_source_uses_cpu_frames() {
+        let graph_for = |rotation: u32| {
+            FfmpegCommand::new(
+                "input.mp4",
+                Path::new("/tmp/output"),
+                TransformConfig::default(),
+                HwAccel::Nvenc,
+                Codec::H264,
+            )
+            .with_source_codec(Some("h264"))
+            .with_rotation(rotation)
+            .build_complex_filter()
+        };
+
+        // CUDA frames skip FFmpeg's autorotate, so rotated sources are
+        // decoded on the CPU and uploaded after the transpose
+        assert!(!graph_for(0).contains("hwupload_cuda"));
+        assert!(graph_for(90).starts_with("[0:v]format=nv12,hwupload_cuda,split="));
+    }
+
+    #[test]
+    fn test_clip_seeks_before_input() {
+        let clip = Clip { start: 30.0, end: Some(45.5) };
+        let args: Vec<String> = FfmpegCommand::new(
+            "input.mp4",
+            Path::new("/tmp/output"),
+            TransformConfig::default(),
+            HwAccel::Software,
+            Codec::H264,
+        )
+        .with_clip(Some(clip))
+        .build()
+        .get_args()
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect();
+
+        assert_eq!(&args[..5], ["-ss", "30.000", "-to", "45.500", "-i"]);
+    }
+
+    #[test]
+    fn test_audio_group_var_stream_map() {
+        let config = TransformConfig {
+            audio_tracks: vec![
+                AudioTrack {
+                    language: Some("eng".to_string()),
+                    title: None,
+                },
+                AudioTrack {
+                    language: None,
+                    title: Some("Commentary".to_string()),
+                },
+            ],
+            ..TransformConfig::default()
+        };
+        let variants = config.resolutions.len();
+        let args: Vec<String> = FfmpegCommand::new(
+            "input.mp4",
+            Path::new("/tmp/output"),
+            config,
+            HwAccel::Software,
+            Codec::H264,
+        )
+        .build()
+        .get_args()
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect();
+
+        // Each track is mapped once instead of once per variant
+        assert!(!args.contains(&"0:a".to_string()));
+        assert!(args.contains(&"0:a:0".to_string()));
+        assert!(args.contains(&"0:a:1".to_string()));
+        assert!(args.contains(&"title=Commentary".to_string()));
+
+        let map = args.iter().position(|a| a == "-var_stream_map").unwrap();
+        let entries: Vec<&str> = args[map + 1].split(' ').collect();
+        assert_eq!(entries.len(), variants + 2);
+        assert_eq!(entries[0], "v:0,agroup:audio");
+        assert_eq!(entries[variants], "a:0,agroup:audio,language:eng,default:yes");
+        assert_eq!(entries[variants + 1], "a:1,agroup:audio");
+    }
+
+    #[test]
+    fn test_loudnorm_filters_audio() {
+        let loudnorm = Loudnorm::default();
+        let args: Vec<String> = FfmpegCommand::new(
+            "input.mp4",
+            Path::new("/tmp/output"),
+            TransformConfig::default(),
+            HwAccel::Software,
+            Codec::H264,
+        )
+        .with_loudnorm(Some(&loudnorm))
+        .build()
+        .get_args()
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect();
+
+        let af = args.iter().position(|a| a == "-af").unwrap();
+        assert_eq!(args[af + 1], loudnorm.filter());
+        // The scaling graph stays video-only
+        let filter = args.iter().position(|a| a == "-filter_complex").unwrap();
+        assert!(!args[filter + 1].contains("loudnorm"));
+    }
+
+    #[test]
+    fn test_strip_metadata() {
+        let args = |strip: bool| -> Vec<String> {
+            FfmpegCommand::new(
+                "input.mp4",
+                Path::new("/tmp/output"),
+                TransformConfig::default(),
+                HwAccel::Software,
+                Codec::H264,
+            )
+            .with_strip_metadata(strip)
+            .build()
+            .get_args()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect()
+        };
+
+        let stripped = args(true);
+        let g = stripped.iter().position(|a| a == "-map_metadata:g").unwrap();
+        assert_eq!(stripped[g..g + 4], STRIP_METADATA_ARGS);
+        assert!(!args(false).iter().any(|a| a.starts_with("-map_metadata")));
+    }
+
+    #[test]
+    fn test_command_line() {
+        let argv = FfmpegMp4Command::new(
+            "https://example.com/my video.mp4",
+            PathBuf::from("/tmp/dry-run/output_720p.mp4"),
+            Resolution::R720p,
+            HwAccel::Software,
+            Codec::H264,
+        )
+        .command_line(Path::new("/usr/bin/ffmpeg"));
+        assert_eq!(argv[0], "/usr/bin/ffmpeg");
+        // As jobs run it: progress on stdout, reconnecting to the URL
+        assert_eq!(argv[3..5], ["-progress", "-"]);
+        assert!(argv.contains(&"-reconnect".to_string()));
+        assert_eq!(argv.last().unwrap(), "/tmp/dry-run/output_720p.mp4");
+
+        let command = shell_command(&argv);
+        assert!(command.starts_with("/usr/bin/ffmpeg -y -nostdin -progress - "));
+        assert!(command.contains(" -i 'https://example.com/my video.mp4' "));
+        assert_eq!(
+            shell_command(&["echo".to_string(), "it's".to_string(), String::new()]),
+            "echo 'it'\\''s' ''"
+        );
+    }
+
+    #[test]
+    fn test_assigned_gpu() {
+        let args = |hwaccel: HwAccel, device: Option<&GpuDevice>| -> Vec<String> {
+            let mut cmd = TokioCommand::new("ffmpeg");
+            apply_hwaccel_input_options(&hwaccel, &None, false, device, &mut cmd, "test");
+            cmd.as_std()
+                .get_args()
+                .map(|a| a.to_string_lossy().into_owned())
+                .collect()
+        };
+        let value = |args: &[String], flag: &str| {
+            args.iter()
+                .position(|a| a == flag)
+                .map(|i| args[i + 1].clone())
+        };
+
+        let nvenc = args(HwAccel::Nvenc, Some(&GpuDevice::Cuda(1)));
+        assert_eq!(value(&nvenc, "-init_hw_device").unwrap(), "cuda=cuda:1");
+        assert_eq!(value(&nvenc, "-hwaccel_device").unwrap(), "1");
+
+        let qsv = args(HwAccel::Qsv, Some(&GpuDevice::Render("/dev/dri/renderD129".into())));
+        assert_eq!(
+            value(&qsv, "-init_hw_device").unwrap(),
+            "qsv=qsv:hw_any,child_device=/dev/dri/renderD129"
+        );
+        assert_eq!(value(&qsv, "-qsv_device").unwrap(), "/dev/dri/renderD129");
+
+        let default = args(HwAccel::Nvenc, None);
+        assert_eq!(value(&default, "-init_hw_device").unwrap(), "cuda=cuda:0");
+        assert!(value(&default, "-hwaccel_device").is_none());
+    }
+
     #[test]
     fn test_hwaccel_detection() {
         // Just verify detection doesn't panic
@@ -0,0 +1,197 @@
+//! What an FFmpeg failure was about.
+//!
+//! FFmpeg reports every failure as a non-zero exit and some lines on stderr.
+//! The classifier matches those lines against known messages so requesters
+//! get a status that says what went wrong, operators can count failures by
+//! cause, and hardware encoder failures can be told apart from bad inputs.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::VideoError;
+
+/// Cause of an FFmpeg failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FfmpegErrorKind {
+    /// No decoder or encoder for a stream
+    UnsupportedCodec,
+    /// The input can't be demuxed or decoded
+    CorruptInput,
+    OutOfMemory,
+    /// The hardware encoder or device couldn't be opened
+    HwEncoderInit,
+    /// Reading a URL input failed or timed out
+    NetworkStall,
+    /// Nothing recognized
+    Other,
+}
+
+/// Lowercase stderr fragments per cause, checked in this order. Out of
+/// memory comes first because GPU allocation failures also mention the
+/// device; hardware errors before codec errors because a missing hardware
+/// encoder is reported like a missing codec.
+const PATTERNS: &[(FfmpegErrorKind, &[&str])] = &[
+    (
+        FfmpegErrorKind::OutOfMemory,
+        &["cannot allocate memory", "out of memory", "enomem", "memory allocation failed"],
+    ),
+    (
+        FfmpegErrorKind::HwEncoderInit,
+        &[
+            "openencodesessionex failed",
+            "no capable devices found",
+            "no nvenc capable devices",
+            "cannot load libcuda",
+            "cannot load libnvidia-encode",
+            "cuinit(0) failed",
+            "failed to initialise vaapi",
+            "failed to create a vaapi device",
+            "failed to set up hw device",
+            "device creation failed",
+            "error creating a mfx session",
+            "error initializing an internal mfx session",
+            "hwaccel initialisation returned error",
+            "cannot create compression session",
+            "failed to open hardware device",
+        ],
+    ),
+    (
+        FfmpegErrorKind::NetworkStall,
+        &[
+            "connection timed out",
+            "connection reset by peer",
+            "connection refused",
+            "operation timed out",
+            "failed to resolve hostname",
+            "server returned 5",
+            "stream ends prematurely",
+            "will reconnect at",
+        ],
+    ),
+    (
+        FfmpegErrorKind::UnsupportedCodec,
+        &[
+            "unknown encoder",
+            "unknown decoder",
+            "encoder not found",
+            "decoder not found",
+            "no decoder for",
+            "not found for input stream",
+            "not found for output stream",
+            "is not supported",
+            "not currently supported",
+            "unsupported codec",
+        ],
+    ),
+    (
+        FfmpegErrorKind::CorruptInput,
+        &[
+            "invalid data found when processing input",
+            "moov atom not found",
+            "error while decoding stream",
+            "corrupt",
+            "invalid nal unit",
+            "non-existing pps",
+            "error reading header",
+            "truncated",
+            "could not find codec parameters",
+        ],
+    ),
+];
+
+impl FfmpegErrorKind {
+    /// Classify stderr output, returning the cause and the line that gave it
+    /// away (the last non-empty line when nothing matched)
+    pub fn classify(stderr: &str) -> (Self, String) {
+        let lines: Vec<&str> = stderr
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect();
+        for (kind, fragments) in PATTERNS {
+            let hit = lines.iter().rev().find(|line| {
+                let line = line.to_lowercase();
+                fragments.iter().any(|f| line.contains(f))
+            });
+            if let Some(line) = hit {
+                return (*kind, line.to_string());
+            }
+        }
+        let detail = lines.last().map_or("exited with an error", |l| l);
+        (Self::Other, detail.to_string())
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::UnsupportedCodec => "unsupported_codec",
+            Self::CorruptInput => "corrupt_input",
+            Self::OutOfMemory => "out_of_memory",
+            Self::HwEncoderInit => "hw_encoder_init",
+            Self::NetworkStall => "network_stall",
+            Self::Other => "other",
+        }
+    }
+
+    /// Whether the same job could succeed with the software encoder
+    pub fn is_hw_failure(&self) -> bool {
+        matches!(self, Self::HwEncoderInit | Self::OutOfMemory)
+    }
+}
+
+impl fmt::Display for FfmpegErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Error for an FFmpeg run that exited unsuccessfully, classified from its stderr
+pub fn failure(context: &str, stderr: &str) -> VideoError {
+    let (kind, line) = FfmpegErrorKind::classify(stderr);
+    VideoError::Ffmpeg {
+        kind,
+        detail: format!("{}: {}", context, line),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let cases = [
+            ("[h264_nvenc @ 0x1] OpenEncodeSessionEx failed: out of memory (10)", FfmpegErrorKind::OutOfMemory),
+            ("[h264_nvenc @ 0x1] OpenEncodeSessionEx failed: unsupported device (2)", FfmpegErrorKind::HwEncoderInit),
+            ("[AVHWDeviceContext @ 0x1] Failed to initialise VAAPI connection: -1", FfmpegErrorKind::HwEncoderInit),
+            ("[https @ 0x1] Connection timed out", FfmpegErrorKind::NetworkStall),
+            ("Decoder (codec none) not found for input stream #0:0", FfmpegErrorKind::UnsupportedCodec),
+            ("Unknown encoder 'libsvtav1'", FfmpegErrorKind::UnsupportedCodec),
+            ("input.mp4: Invalid data found when processing input", FfmpegErrorKind::CorruptInput),
+            ("[mov,mp4 @ 0x1] moov atom not found", FfmpegErrorKind::CorruptInput),
+            ("Conversion failed!", FfmpegErrorKind::Other),
+        ];
+        for (stderr, kind) in cases {
+            assert_eq!(FfmpegErrorKind::classify(stderr).0, kind, "{}", stderr);
+        }
+
+        // The matching line is reported, not the generic last one
+        let stderr = "Input #0, mov\n[h264 @ 0x1] Invalid NAL unit size\nConversion failed!\n";
+        assert_eq!(
+            FfmpegErrorKind::classify(stderr),
+            (FfmpegErrorKind::CorruptInput, "[h264 @ 0x1] Invalid NAL unit size".to_string())
+        );
+        assert_eq!(
+            FfmpegErrorKind::classify("\n"),
+            (FfmpegErrorKind::Other, "exited with an error".to_string())
+        );
+
+        assert!(FfmpegErrorKind::HwEncoderInit.is_hw_failure());
+        assert!(!FfmpegErrorKind::CorruptInput.is_hw_failure());
+        assert_eq!(
+            failure("Preview generation failed", "moov atom not found").to_string(),
+            "FFmpeg failed (corrupt_input): Preview generation failed: moov atom not found"
+        );
+    }
+}
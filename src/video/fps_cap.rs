@@ -0,0 +1,119 @@
+//! Capping the frame rate of high-FPS sources.
+//!
+//! Slow-motion phone clips are recorded at 120 or 240 fps and play back at
+//! that rate, which costs bitrate in every rung without looking better on
+//! most screens. With a cap set, encoded rungs are brought down to it with the
+//! `fps` filter, and rungs of 480p and below to at most 30 fps. Rates are
+//! divided by a whole number where that stays close to the cap (120 becomes 60,
+//! 119.88 becomes 59.94), so the kept frames are evenly spaced.
+
+/// Lowest cap a job or operator may set
+pub const MIN_MAX_FPS: u32 = 10;
+/// Highest cap a job or operator may set
+pub const MAX_MAX_FPS: u32 = 120;
+
+/// Rungs this tall and below are capped at [`LOW_RUNG_MAX_FPS`]
+const LOW_RUNG_HEIGHT: u32 = 480;
+/// Frame rate cap of the lower rungs
+const LOW_RUNG_MAX_FPS: u32 = 30;
+
+/// Share of the cap an evenly divided rate has to keep
+const MIN_DIVIDED_SHARE: f64 = 2.0 / 3.0;
+
+/// Rates within this of a cap count as at the cap (timestamps are rarely exact)
+const TOLERANCE: f64 = 0.01;
+
+/// Frame rate cap applied to a source
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FpsCap {
+    /// Highest frame rate of any rung
+    pub max_fps: u32,
+    /// Frame rate of the source (after VFR normalization)
+    pub source_fps: f64,
+}
+
+impl FpsCap {
+    /// Check a requested cap
+    pub fn validate(max_fps: u32) -> Result<u32, String> {
+        if (MIN_MAX_FPS..=MAX_MAX_FPS).contains(&max_fps) {
+            Ok(max_fps)
+        } else {
+            Err(format!(
+                "max_fps must be between {} and {}",
+                MIN_MAX_FPS, MAX_MAX_FPS
+            ))
+        }
+    }
+
+    /// Cap for a source running at `source_fps`, or `None` when no rung
+    /// would be slowed down
+    pub fn new(max_fps: u32, source_fps: f64) -> Option<Self> {
+        let cap = Self { max_fps, source_fps };
+        (source_fps.is_finite() && cap.rung_fps(Some(0)).is_some()).then_some(cap)
+    }
+
+    /// Output frame rate of a rung `height` pixels tall (`None` for the
+    /// original), or `None` when it keeps the source rate
+    pub fn rung_fps(&self, height: Option<u32>) -> Option<f64> {
+        let limit = match height {
+            Some(h) if h <= LOW_RUNG_HEIGHT => self.max_fps.min(LOW_RUNG_MAX_FPS),
+            _ => self.max_fps,
+        } as f64;
+        if self.source_fps <= limit + TOLERANCE {
+            return None;
+        }
+        let divided = self.source_fps / (self.source_fps / limit - TOLERANCE).ceil();
+        // Rather drop frames unevenly than halve a rate just above the cap
+        Some(if divided < limit * MIN_DIVIDED_SHARE { limit } else { divided })
+    }
+
+    /// Whether the original has to be re-encoded to stay within the cap
+    pub fn caps_original(&self) -> bool {
+        self.rung_fps(None).is_some()
+    }
+}
+
+/// `fps` filter bringing a rung down to `fps`
+pub fn fps_filter(fps: f64) -> String {
+    format!("fps={:.3}", fps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(FpsCap::validate(60), Ok(60));
+        assert!(FpsCap::validate(5).is_err());
+        assert!(FpsCap::validate(240).is_err());
+    }
+
+    #[test]
+    fn test_rung_fps_divides_source_rate() {
+        let cap = FpsCap::new(60, 120.0).unwrap();
+        assert_eq!(cap.rung_fps(Some(1080)), Some(60.0));
+        assert_eq!(cap.rung_fps(Some(360)), Some(30.0));
+        assert!(cap.caps_original());
+
+        let ntsc = FpsCap::new(60, 120_000.0 / 1001.0).unwrap();
+        assert_eq!(fps_filter(ntsc.rung_fps(Some(720)).unwrap()), "fps=59.940");
+        assert_eq!(fps_filter(ntsc.rung_fps(Some(480)).unwrap()), "fps=29.970");
+
+        // 90 fps can't be halved to exactly 60; every other frame is kept
+        assert_eq!(FpsCap::new(60, 90.0).unwrap().rung_fps(Some(720)), Some(45.0));
+    }
+
+    #[test]
+    fn test_sources_within_cap() {
+        // Only the lower rungs of a 60 fps source are slowed down
+        let cap = FpsCap::new(60, 59.94).unwrap();
+        assert_eq!(cap.rung_fps(Some(1080)), None);
+        assert!(cap.rung_fps(Some(240)).is_some());
+        assert!(!cap.caps_original());
+
+        assert_eq!(FpsCap::new(60, 30.0), None);
+        assert_eq!(FpsCap::new(24, 25.0).unwrap().rung_fps(Some(720)), Some(24.0));
+        assert_eq!(FpsCap::new(60, f64::NAN), None);
+    }
+}
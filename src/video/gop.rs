@@ -0,0 +1,193 @@
+//! Keyframe checks before copying the original into HLS.
+//!
+//! Stream copy can only cut segments at the source's keyframes, and a
+//! segment is only playable on its own when its first keyframe doesn't
+//! depend on frames before it. Sources with sparse keyframes produce huge,
+//! unseekable segments; open GOPs and streams that don't start on a keyframe
+//! produce segments that decode with garbage or not at all. The first minute
+//! of packets is inspected (no decoding) and the original is re-encoded when
+//! its layout isn't segmentable.
+
+use serde::Deserialize;
+use std::path::Path;
+use tokio::process::Command;
+
+use crate::error::VideoError;
+
+/// Seconds of the source inspected
+pub const PROBE_WINDOW_SECS: u32 = 60;
+
+/// Longest keyframe interval accepted, as a multiple of the segment duration
+const MAX_INTERVAL_SEGMENTS: f64 = 2.0;
+
+/// How far into the stream the first keyframe may be
+const START_TOLERANCE_SECS: f64 = 0.1;
+
+#[derive(Debug, Deserialize)]
+struct PacketList {
+    #[serde(default)]
+    packets: Vec<Packet>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Packet {
+    pts_time: Option<String>,
+    #[serde(default)]
+    flags: String,
+}
+
+/// Video packets of the start of a stream as (pts, keyframe), in decode order
+#[derive(Debug, Clone, Default)]
+pub struct GopLayout {
+    packets: Vec<(f64, bool)>,
+}
+
+impl GopLayout {
+    /// List the video packets of the first [`PROBE_WINDOW_SECS`] with ffprobe.
+    pub async fn probe(input: &str, ffprobe_path: &Path) -> Result<Self, VideoError> {
+        let output = Command::new(ffprobe_path)
+            .args(["-v", "error", "-select_streams", "v:0", "-read_intervals"])
+            .arg(format!("%+{}", PROBE_WINDOW_SECS))
+            .args(["-show_entries", "packet=pts_time,flags", "-of", "json", input])
+            .output()
+            .await
+            .map_err(VideoError::Io)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(VideoError::FfprobeFailed(stderr.to_string()));
+        }
+        Self::from_json(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// Parse ffprobe's `-show_entries packet=pts_time,flags -of json` output
+    pub fn from_json(json: &str) -> Result<Self, VideoError> {
+        let list: PacketList =
+            serde_json::from_str(json).map_err(|e| VideoError::FfprobeFailed(e.to_string()))?;
+        let packets = list
+            .packets
+            .into_iter()
+            // Discarded packets are never shown
+            .filter(|p| !p.flags.contains('D'))
+            .filter_map(|p| Some((p.pts_time?.parse().ok()?, p.flags.contains('K'))))
+            .collect();
+        Ok(Self { packets })
+    }
+
+    /// Why the stream can't be copied into `hls_time`-second segments, if
+    /// it can't. An empty probe gives no reason.
+    pub fn passthrough_issue(&self, hls_time: u32) -> Option<String> {
+        let start = self.packets.iter().map(|(pts, _)| *pts).reduce(f64::min)?;
+        let end = self.packets.iter().map(|(pts, _)| *pts).reduce(f64::max)?;
+        let mut keyframes: Vec<f64> = self
+            .packets
+            .iter()
+            .filter(|(_, key)| *key)
+            .map(|(pts, _)| *pts)
+            .collect();
+        keyframes.sort_by(f64::total_cmp);
+
+        let Some(&first) = keyframes.first() else {
+            return Some("no keyframe at the start of the stream".to_string());
+        };
+        if first - start > START_TOLERANCE_SECS {
+            return Some(format!("stream starts {:.2}s before its first keyframe", first - start));
+        }
+
+        let max_interval = hls_time as f64 * MAX_INTERVAL_SEGMENTS;
+        let longest = keyframes
+            .windows(2)
+            .map(|w| w[1] - w[0])
+            .chain(std::iter::once(end - keyframes[keyframes.len() - 1]))
+            .fold(0.0, f64::max);
+        if longest > max_interval {
+            return Some(format!("keyframes up to {:.1}s apart", longest));
+        }
+
+        // Frames decoded after a keyframe but shown before it reference the
+        // previous GOP
+        let mut keyframe_pts = None;
+        for &(pts, key) in &self.packets {
+            if key {
+                keyframe_pts = Some(pts);
+            } else if keyframe_pts.is_some_and(|k| pts < k) {
+                return Some("open GOPs".to_string());
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Packets at 10 fps, keyframe every `interval` frames, in display order
+    fn closed(frames: usize, interval: usize) -> String {
+        let packets: Vec<String> = (0..frames)
+            .map(|i| {
+                let flags = if i % interval == 0 { "K__" } else { "___" };
+                format!(r#"{{"pts_time": "{:.3}", "flags": "{}"}}"#, i as f64 / 10.0, flags)
+            })
+            .collect();
+        format!(r#"{{"packets": [{}]}}"#, packets.join(","))
+    }
+
+    #[test]
+    fn test_regular_keyframes_pass() {
+        let layout = GopLayout::from_json(&closed(300, 20)).unwrap();
+        assert_eq!(layout.passthrough_issue(6), None);
+    }
+
+    #[test]
+    fn test_sparse_keyframes_fail() {
+        // One keyframe every 20 seconds
+        let layout = GopLayout::from_json(&closed(600, 200)).unwrap();
+        assert_eq!(
+            layout.passthrough_issue(6).as_deref(),
+            Some("keyframes up to 20.0s apart")
+        );
+        // A single keyframe followed by a minute of frames
+        let layout = GopLayout::from_json(&closed(600, 1000)).unwrap();
+        assert!(layout.passthrough_issue(6).is_some());
+    }
+
+    #[test]
+    fn test_missing_start_keyframe_fails() {
+        let json = r#"{"packets": [
+            {"pts_time": "0.000", "flags": "___"},
+            {"pts_time": "0.500", "flags": "K__"},
+            {"pts_time": "0.600", "flags": "___"}
+        ]}"#;
+        let issue = GopLayout::from_json(json).unwrap().passthrough_issue(6).unwrap();
+        assert!(issue.contains("before its first keyframe"));
+
+        let no_keys = r#"{"packets": [{"pts_time": "0.0", "flags": "___"}]}"#;
+        assert!(GopLayout::from_json(no_keys).unwrap().passthrough_issue(6).is_some());
+    }
+
+    #[test]
+    fn test_open_gop_fails() {
+        // Decode order: I0 P3 B1 B2 | I6 B4 B5 ...; B4/B5 show before I6
+        let json = r#"{"packets": [
+            {"pts_time": "0.0", "flags": "K__"},
+            {"pts_time": "0.3", "flags": "___"},
+            {"pts_time": "0.1", "flags": "___"},
+            {"pts_time": "0.2", "flags": "___"},
+            {"pts_time": "0.6", "flags": "K__"},
+            {"pts_time": "0.4", "flags": "___"},
+            {"pts_time": "0.5", "flags": "___"}
+        ]}"#;
+        assert_eq!(
+            GopLayout::from_json(json).unwrap().passthrough_issue(6).as_deref(),
+            Some("open GOPs")
+        );
+    }
+
+    #[test]
+    fn test_unknown_layout_passes() {
+        // ffprobe output without packets (e.g. the input couldn't be read)
+        let layout = GopLayout::from_json(r#"{"format": {}, "streams": []}"#).unwrap();
+        assert_eq!(layout.passthrough_issue(6), None);
+    }
+}
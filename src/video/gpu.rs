@@ -0,0 +1,297 @@
+//! Spreading encodes over multiple GPUs.
+//!
+//! FFmpeg picks device 0 unless told otherwise, so a rig with two cards runs
+//! every concurrent job on the first one. [`GpuScheduler`] hands each encode a
+//! device according to the operator's [`GpuStrategy`] and tracks how many
+//! encodes run on each device until their [`GpuLease`] is dropped.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use tracing::{debug, info};
+
+use crate::video::hwaccel::HwAccel;
+
+/// A GPU an encode can be pinned to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GpuDevice {
+    /// CUDA device index (NVENC)
+    Cuda(u32),
+    /// DRM render node (VAAPI and QSV)
+    Render(PathBuf),
+}
+
+impl std::fmt::Display for GpuDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cuda(index) => write!(f, "cuda:{}", index),
+            Self::Render(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// How encodes are assigned to devices
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GpuStrategy {
+    /// Take devices in turn
+    RoundRobin,
+    /// Take the device running the fewest encodes
+    #[default]
+    LeastBusy,
+}
+
+impl GpuStrategy {
+    /// Parse from string. Returns `None` for unrecognized values.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().replace('_', "-").as_str() {
+            "round-robin" | "roundrobin" => Some(Self::RoundRobin),
+            "least-busy" | "leastbusy" => Some(Self::LeastBusy),
+            _ => None,
+        }
+    }
+
+    /// Read `GPU_STRATEGY` (round-robin|least-busy)
+    pub fn from_env() -> Self {
+        std::env::var("GPU_STRATEGY")
+            .ok()
+            .and_then(|s| Self::parse(&s))
+            .unwrap_or_default()
+    }
+}
+
+/// Find the devices `hwaccel` can encode on, in index order
+pub fn detect_devices(hwaccel: HwAccel) -> Vec<GpuDevice> {
+    match hwaccel {
+        HwAccel::Nvenc => detect_cuda_devices(),
+        HwAccel::Vaapi | HwAccel::Qsv => detect_render_nodes(Path::new("/dev/dri")),
+        HwAccel::VideoToolbox | HwAccel::Software => Vec::new(),
+    }
+}
+
+/// CUDA devices listed by `nvidia-smi -L`, or the `/dev/nvidiaN` nodes
+/// when nvidia-smi isn't installed
+fn detect_cuda_devices() -> Vec<GpuDevice> {
+    let listed = std::process::Command::new("nvidia-smi")
+        .arg("-L")
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| count_nvidia_smi_gpus(&String::from_utf8_lossy(&o.stdout)));
+
+    let count = listed.unwrap_or_else(|| {
+        debug!("nvidia-smi unavailable, counting NVIDIA device nodes");
+        (0..)
+            .take_while(|i| Path::new(&format!("/dev/nvidia{}", i)).exists())
+            .count()
+    });
+    (0..count as u32).map(GpuDevice::Cuda).collect()
+}
+
+/// Number of `GPU N: ...` lines in `nvidia-smi -L` output
+fn count_nvidia_smi_gpus(output: &str) -> usize {
+    output
+        .lines()
+        .filter(|line| line.trim_start().starts_with("GPU "))
+        .count()
+}
+
+/// `renderD*` nodes in `dir`, sorted by name
+fn detect_render_nodes(dir: &Path) -> Vec<GpuDevice> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut nodes: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with("renderD"))
+        .map(|e| e.path())
+        .collect();
+    nodes.sort();
+    nodes.into_iter().map(GpuDevice::Render).collect()
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+    /// Encodes running per device
+    busy: Vec<usize>,
+    /// Device the next round-robin pick starts from
+    next: usize,
+}
+
+/// Assigns encodes to GPUs
+#[derive(Debug)]
+pub struct GpuScheduler {
+    devices: Vec<GpuDevice>,
+    strategy: GpuStrategy,
+    usage: Mutex<Usage>,
+}
+
+impl GpuScheduler {
+    pub fn new(devices: Vec<GpuDevice>, strategy: GpuStrategy) -> Self {
+        let usage = Usage {
+            busy: vec![0; devices.len()],
+            next: 0,
+        };
+        Self {
+            devices,
+            strategy,
+            usage: Mutex::new(usage),
+        }
+    }
+
+    /// Detect the devices of `hwaccel` and schedule over them with the
+    /// strategy from `GPU_STRATEGY`
+    pub fn detect(hwaccel: HwAccel) -> Self {
+        let devices = detect_devices(hwaccel);
+        let strategy = GpuStrategy::from_env();
+        if devices.len() > 1 {
+            info!(
+                devices = %devices.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "),
+                strategy = ?strategy,
+                "Multiple GPUs detected, spreading encodes"
+            );
+        }
+        Self::new(devices, strategy)
+    }
+
+    pub fn devices(&self) -> &[GpuDevice] {
+        &self.devices
+    }
+
+    /// Pick a device for one encode. Returns `None` unless there is more than
+    /// one device to choose from, leaving FFmpeg on its default device.
+    pub fn acquire(self: &Arc<Self>) -> Option<GpuLease> {
+        if self.devices.len() < 2 {
+            return None;
+        }
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        let count = self.devices.len();
+        let index = match self.strategy {
+            GpuStrategy::RoundRobin => usage.next,
+            // Ties go to the device after the last pick, so idle devices
+            // take turns rather than device 0 getting every lone job
+            GpuStrategy::LeastBusy => (0..count)
+                .map(|offset| (usage.next + offset) % count)
+                .min_by_key(|&i| usage.busy[i])
+                .unwrap_or(0),
+        };
+        usage.busy[index] += 1;
+        usage.next = (index + 1) % count;
+        Some(GpuLease {
+            scheduler: self.clone(),
+            index,
+        })
+    }
+
+    /// Encodes currently running on each device
+    pub fn busy(&self) -> Vec<usize> {
+        self.usage
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .busy
+            .clone()
+    }
+}
+
+/// A device assigned to an encode, released when dropped
+#[derive(Debug)]
+pub struct GpuLease {
+    scheduler: Arc<GpuScheduler>,
+    index: usize,
+}
+
+impl GpuLease {
+    pub fn device(&self) -> &GpuDevice {
+        &self.scheduler.devices[self.index]
+    }
+}
+
+impl Drop for GpuLease {
+    fn drop(&mut self) {
+        let mut usage = self
+            .scheduler
+            .usage
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        usage.busy[self.index] = usage.busy[self.index].saturating_sub(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler(count: u32, strategy: GpuStrategy) -> Arc<GpuScheduler> {
+        Arc::new(GpuScheduler::new(
+            (0..count).map(GpuDevice::Cuda).collect(),
+            strategy,
+        ))
+    }
+
+    #[test]
+    fn test_strategy_parse() {
+        assert_eq!(GpuStrategy::parse("round-robin"), Some(GpuStrategy::RoundRobin));
+        assert_eq!(GpuStrategy::parse("ROUND_ROBIN"), Some(GpuStrategy::RoundRobin));
+        assert_eq!(GpuStrategy::parse(" least-busy "), Some(GpuStrategy::LeastBusy));
+        assert_eq!(GpuStrategy::parse("random"), None);
+    }
+
+    #[test]
+    fn test_single_device_is_not_pinned() {
+        assert!(scheduler(0, GpuStrategy::LeastBusy).acquire().is_none());
+        assert!(scheduler(1, GpuStrategy::RoundRobin).acquire().is_none());
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let gpus = scheduler(2, GpuStrategy::RoundRobin);
+        let first = gpus.acquire().unwrap();
+        drop(first);
+        // Device 1 is next even though device 0 is idle again
+        let second = gpus.acquire().unwrap();
+        let third = gpus.acquire().unwrap();
+        assert_eq!(second.device(), &GpuDevice::Cuda(1));
+        assert_eq!(third.device(), &GpuDevice::Cuda(0));
+        assert_eq!(gpus.busy(), vec![1, 1]);
+    }
+
+    #[test]
+    fn test_least_busy() {
+        let gpus = scheduler(2, GpuStrategy::LeastBusy);
+        let a = gpus.acquire().unwrap();
+        let b = gpus.acquire().unwrap();
+        assert_eq!(a.device(), &GpuDevice::Cuda(0));
+        assert_eq!(b.device(), &GpuDevice::Cuda(1));
+
+        // A job finishing on device 0 makes it the least busy
+        let c = gpus.acquire().unwrap();
+        assert_eq!(c.device(), &GpuDevice::Cuda(0));
+        drop(a);
+        drop(c);
+        assert_eq!(gpus.busy(), vec![0, 1]);
+        assert_eq!(gpus.acquire().unwrap().device(), &GpuDevice::Cuda(0));
+    }
+
+    #[test]
+    fn test_count_nvidia_smi_gpus() {
+        let output = "GPU 0: NVIDIA GeForce RTX 3060 (UUID: GPU-1234)\n\
+                      GPU 1: NVIDIA GeForce RTX 4070 (UUID: GPU-5678)\n";
+        assert_eq!(count_nvidia_smi_gpus(output), 2);
+        assert_eq!(count_nvidia_smi_gpus(""), 0);
+    }
+
+    #[test]
+    fn test_detect_render_nodes() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["renderD129", "card0", "renderD128"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        assert_eq!(
+            detect_render_nodes(dir.path()),
+            vec![
+                GpuDevice::Render(dir.path().join("renderD128")),
+                GpuDevice::Render(dir.path().join("renderD129")),
+            ]
+        );
+        assert!(detect_render_nodes(&dir.path().join("missing")).is_empty());
+    }
+}
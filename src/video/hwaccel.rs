@@ -7,6 +7,8 @@ use tracing::debug;
 use tracing::info;
 
 use crate::dvm::events::Codec;
+use crate::util::ffmpeg_version;
+use crate::video::gpu::GpuDevice;
 
 /// Cached result of CUDA AV1 decode capability probe
 static CUDA_AV1_DECODE: OnceLock<bool> = OnceLock::new();
@@ -20,6 +22,9 @@ static VAAPI_HEVC_ENCODE: OnceLock<bool> = OnceLock::new();
 /// Cached result of VAAPI AV1 encode capability probe
 static VAAPI_AV1_ENCODE: OnceLock<bool> = OnceLock::new();
 
+/// Cached result of VAAPI VP9 encode capability probe
+static VAAPI_VP9_ENCODE: OnceLock<bool> = OnceLock::new();
+
 /// Cached result of VAAPI AV1 decode capability probe
 static VAAPI_AV1_DECODE: OnceLock<bool> = OnceLock::new();
 
@@ -585,6 +590,65 @@ impl HwAccel {
         false
     }
 
+    /// Check if VAAPI supports VP9 encoding (cached).
+    /// Intel only (Kaby Lake+); AMD GPUs have no VP9 encoder.
+    pub fn is_vaapi_vp9_encode_available() -> bool {
+        *VAAPI_VP9_ENCODE.get_or_init(Self::probe_vaapi_vp9_encode)
+    }
+
+    /// Probe VAAPI VP9 encode capability
+    #[cfg(target_os = "linux")]
+    fn probe_vaapi_vp9_encode() -> bool {
+        let Some(device) = Self::find_render_device() else {
+            return false;
+        };
+
+        let result = Command::new("ffmpeg")
+            .args([
+                "-hide_banner",
+                "-loglevel",
+                "error",
+                "-init_hw_device",
+                &format!("vaapi=vaapi:{}", device),
+                "-filter_hw_device",
+                "vaapi",
+                "-f",
+                "lavfi",
+                "-i",
+                "nullsrc=s=64x64:d=0.1",
+                "-vf",
+                "format=nv12,hwupload",
+                "-c:v",
+                "vp9_vaapi",
+                "-frames:v",
+                "1",
+                "-f",
+                "null",
+                "-",
+            ])
+            .output();
+
+        match result {
+            Ok(output) if output.status.success() => {
+                info!(device = %device, "VAAPI VP9 encoding verified");
+                true
+            }
+            Ok(_) => {
+                debug!(device = %device, "VAAPI VP9 encoding not available");
+                false
+            }
+            Err(e) => {
+                debug!(error = %e, "Failed to run FFmpeg VAAPI VP9 probe");
+                false
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn probe_vaapi_vp9_encode() -> bool {
+        false
+    }
+
     /// Check if VAAPI supports AV1 hardware decoding (cached).
     /// Requires AMD RDNA2+ or Intel 12th gen+.
     pub fn has_vaapi_av1_decode() -> bool {
@@ -773,11 +837,20 @@ impl HwAccel {
     /// Falls back to a working encoder when the GPU doesn't support the requested codec:
     /// - VAAPI + H.265: falls back to `h264_vaapi` if HEVC encode isn't available
     /// - VAAPI + AV1: falls back to `hevc_vaapi` or `h264_vaapi` if AV1 encode isn't available
+    /// - VP9: only VAAPI and QSV (Intel) have hardware encoders; NVENC and
+    ///   VideoToolbox fall back to HEVC
     pub fn video_encoder(&self, codec: Codec) -> &'static str {
         match (self, codec) {
             (Self::Nvenc, Codec::H264) => "h264_nvenc",
             (Self::Nvenc, Codec::H265) => "hevc_nvenc",
-            (Self::Nvenc, Codec::AV1) => "av1_nvenc",
+            (Self::Nvenc, Codec::AV1) => {
+                if ffmpeg_version::encoder_available("av1_nvenc") {
+                    "av1_nvenc"
+                } else {
+                    "hevc_nvenc"
+                }
+            }
+            (Self::Nvenc, Codec::Vp9) => "hevc_nvenc",
             (Self::Vaapi, Codec::H264) => "h264_vaapi",
             (Self::Vaapi, Codec::H265) => {
                 if Self::has_vaapi_hevc_encode() {
@@ -787,7 +860,9 @@ impl HwAccel {
                 }
             }
             (Self::Vaapi, Codec::AV1) => {
-                if Self::is_vaapi_av1_encode_available() {
+                if ffmpeg_version::encoder_available("av1_vaapi")
+                    && Self::is_vaapi_av1_encode_available()
+                {
                     "av1_vaapi"
                 } else if Self::has_vaapi_hevc_encode() {
                     "hevc_vaapi"
@@ -795,18 +870,58 @@ impl HwAccel {
                     "h264_vaapi"
                 }
             }
+            (Self::Vaapi, Codec::Vp9) => {
+                if ffmpeg_version::encoder_available("vp9_vaapi")
+                    && Self::is_vaapi_vp9_encode_available()
+                {
+                    "vp9_vaapi"
+                } else if Self::has_vaapi_hevc_encode() {
+                    "hevc_vaapi"
+                } else {
+                    "h264_vaapi"
+                }
+            }
             (Self::Qsv, Codec::H264) => "h264_qsv",
             (Self::Qsv, Codec::H265) => "hevc_qsv",
-            (Self::Qsv, Codec::AV1) => "av1_qsv",
+            (Self::Qsv, Codec::AV1) => {
+                if ffmpeg_version::encoder_available("av1_qsv") {
+                    "av1_qsv"
+                } else {
+                    "hevc_qsv"
+                }
+            }
+            (Self::Qsv, Codec::Vp9) => {
+                if ffmpeg_version::encoder_available("vp9_qsv") {
+                    "vp9_qsv"
+                } else {
+                    "hevc_qsv"
+                }
+            }
             (Self::VideoToolbox, Codec::H264) => "h264_videotoolbox",
             (Self::VideoToolbox, Codec::H265) => "hevc_videotoolbox",
+            // VideoToolbox only decodes AV1; Apple silicon has no AV1 encoder
+            (Self::VideoToolbox, Codec::AV1) => "hevc_videotoolbox",
+            (Self::VideoToolbox, Codec::Vp9) => "hevc_videotoolbox",
             (Self::Software, Codec::H264) => "libx264",
             (Self::Software, Codec::H265) => "libx265",
-            (Self::Software, Codec::AV1) => "libsvtav1",
-            (Self::VideoToolbox, Codec::AV1) => "av1_videotoolbox",
+            (Self::Software, Codec::AV1) => Self::software_av1_encoder().unwrap_or("libx265"),
+            (Self::Software, Codec::Vp9) => {
+                if ffmpeg_version::encoder_available("libvpx-vp9") {
+                    "libvpx-vp9"
+                } else {
+                    "libx265"
+                }
+            }
         }
     }
 
+    /// First AV1 software encoder compiled into FFmpeg, fastest first
+    pub fn software_av1_encoder() -> Option<&'static str> {
+        ["libsvtav1", "librav1e", "libaom-av1"]
+            .into_iter()
+            .find(|e| ffmpeg_version::encoder_available(e))
+    }
+
     /// Get the video decoder name for this acceleration and codec (if any)
     pub fn video_decoder(&self, codec: Codec) -> Option<&'static str> {
         match (self, codec) {
@@ -919,7 +1034,29 @@ impl HwAccel {
 
     /// Get quality parameter name and value
     /// Returns (param_name, value) for the given CRF-equivalent quality
-    pub fn quality_param(&self, crf: u32) -> (&'static str, String) {
+    ///
+    /// `codec` is the codec actually produced (see [`Codec::from_encoder`]):
+    /// AV1 encoders use wider quantizer scales than the x264-style CRF values
+    /// in our ladder, and so do the VP9 encoders.
+    pub fn quality_param(&self, codec: Codec, crf: u32) -> (&'static str, String) {
+        match (self, codec) {
+            // VAAPI AV1 QP runs 0-255
+            (Self::Vaapi, Codec::AV1) => return ("-qp", (crf * 4).min(255).to_string()),
+            (Self::Software, Codec::AV1) => {
+                return match Self::software_av1_encoder() {
+                    // rav1e quantizer runs 0-255
+                    Some("librav1e") => ("-qp", (crf * 4).min(255).to_string()),
+                    // SVT-AV1 / libaom CRF runs 0-63; CRF 23 (x264) ~ 33 (AV1)
+                    _ => ("-crf", (crf + 10).min(63).to_string()),
+                };
+            }
+            // VAAPI VP9 QP runs 0-255
+            (Self::Vaapi, Codec::Vp9) => return ("-qp", (crf * 4).min(255).to_string()),
+            // libvpx-vp9 CRF runs 0-63, same offset as AV1
+            (Self::Software, Codec::Vp9) => return ("-crf", (crf + 10).min(63).to_string()),
+            _ => {}
+        }
+
         match self {
             Self::Nvenc => {
                 // NVENC uses -cq for constant quality (similar to CRF)
@@ -964,7 +1101,7 @@ impl HwAccel {
                     h if h <= 1080 => "5000k",
                     _ => "10000k", // 4K
                 }),
-                Codec::H265 | Codec::AV1 => Some(match height {
+                Codec::H265 | Codec::AV1 | Codec::Vp9 => Some(match height {
                     h if h <= 240 => "300k",
                     h if h <= 360 => "600k",
                     h if h <= 480 => "900k",
@@ -1035,6 +1172,7 @@ impl HwAccel {
                 ("-g", "60"),
                 ("-keyint_min", "60"),
             ],
+            (Self::Vaapi, Codec::Vp9) => vec![("-g", "60"), ("-keyint_min", "60")],
             (Self::Vaapi, _) => vec![
                 // HEVC/AV1: use main profile for broad compatibility
                 ("-profile:v", "main"),
@@ -1048,19 +1186,42 @@ impl HwAccel {
                 ("-keyint_min", "60"),
             ],
             (Self::VideoToolbox, _) => vec![],
+            (Self::Software, Codec::AV1) => match Self::software_av1_encoder() {
+                // SVT-AV1 presets run 0 (slowest) to 13; 8 is a good speed/size balance
+                Some("libsvtav1") => vec![("-preset", "8")],
+                Some("librav1e") => vec![("-speed", "6")],
+                Some(_) => vec![("-cpu-used", "6"), ("-row-mt", "1")],
+                None => vec![("-preset", "medium")],
+            },
+            (Self::Software, Codec::Vp9) => vec![
+                // -b:v 0 makes -crf constant quality instead of a bitrate ceiling
+                ("-b:v", "0"),
+                ("-deadline", "good"),
+                ("-cpu-used", "4"),
+                ("-row-mt", "1"),
+                ("-g", "60"),
+            ],
             (Self::Software, _) => vec![("-preset", "medium")],
         }
     }
 
-    /// Get init_hw_device option for complex filter graphs
-    pub fn init_hw_device(&self) -> Option<String> {
-        match self {
-            Self::Nvenc => Some("cuda=cuda:0".to_string()),
-            Self::Vaapi => {
+    /// Get init_hw_device option for complex filter graphs, on `device`
+    /// when the encode was assigned one
+    pub fn init_hw_device(&self, device: Option<&GpuDevice>) -> Option<String> {
+        match (self, device) {
+            (Self::Nvenc, Some(GpuDevice::Cuda(index))) => Some(format!("cuda=cuda:{}", index)),
+            (Self::Nvenc, _) => Some("cuda=cuda:0".to_string()),
+            (Self::Vaapi, Some(GpuDevice::Render(path))) => {
+                Some(format!("vaapi=vaapi:{}", path.display()))
+            }
+            (Self::Vaapi, _) => {
                 let device = self.vaapi_device().unwrap_or("/dev/dri/renderD128");
                 Some(format!("vaapi=vaapi:{}", device))
             }
-            Self::Qsv => {
+            (Self::Qsv, Some(GpuDevice::Render(path))) => {
+                Some(format!("qsv=qsv:hw_any,child_device={}", path.display()))
+            }
+            (Self::Qsv, _) => {
                 let device = self.qsv_device().unwrap_or("/dev/dri/renderD128");
                 Some(format!("qsv=qsv:hw_any,child_device={}", device))
             }
@@ -1068,6 +1229,14 @@ impl HwAccel {
         }
     }
 
+    /// Encoder options pinning the encode to an assigned `device`
+    pub fn device_options(&self, device: Option<&GpuDevice>) -> Vec<(&'static str, String)> {
+        match (self, device) {
+            (Self::Nvenc, Some(GpuDevice::Cuda(index))) => vec![("-gpu", index.to_string())],
+            _ => Vec::new(),
+        }
+    }
+
     /// Get filter_hw_device option name (used with hwupload in filter graphs)
     /// This tells FFmpeg which initialized device to use for filter operations
     pub fn filter_hw_device(&self) -> Option<&'static str> {
@@ -1155,19 +1324,63 @@ mod tests {
 
     #[test]
     fn test_quality_param() {
-        let (name, _) = HwAccel::Nvenc.quality_param(23);
+        let (name, _) = HwAccel::Nvenc.quality_param(Codec::H264, 23);
         assert_eq!(name, "-cq");
 
-        let (name, _) = HwAccel::Vaapi.quality_param(23);
+        let (name, _) = HwAccel::Vaapi.quality_param(Codec::H264, 23);
         assert_eq!(name, "-qp");
 
-        let (name, _) = HwAccel::Qsv.quality_param(23);
+        let (name, _) = HwAccel::Qsv.quality_param(Codec::H264, 23);
         assert_eq!(name, "-global_quality");
 
-        let (name, _) = HwAccel::Software.quality_param(23);
+        let (name, _) = HwAccel::Software.quality_param(Codec::H264, 23);
         assert_eq!(name, "-crf");
     }
 
+    #[test]
+    fn test_av1_encoding() {
+        // Apple has no AV1 encoder; fall back to HEVC
+        assert_eq!(
+            HwAccel::VideoToolbox.video_encoder(Codec::AV1),
+            "hevc_videotoolbox"
+        );
+        // Encoder list unknown in tests, so SVT-AV1 is assumed present
+        assert_eq!(HwAccel::Software.video_encoder(Codec::AV1), "libsvtav1");
+        assert_eq!(
+            HwAccel::Software.encoder_options(Codec::AV1),
+            vec![("-preset", "8")]
+        );
+
+        assert_eq!(
+            HwAccel::Software.quality_param(Codec::AV1, 23),
+            ("-crf", "33".to_string())
+        );
+        assert_eq!(
+            HwAccel::Vaapi.quality_param(Codec::AV1, 23),
+            ("-qp", "92".to_string())
+        );
+    }
+
+    #[test]
+    fn test_vp9_encoding() {
+        // No VP9 encoder on NVIDIA or Apple hardware
+        assert_eq!(HwAccel::Nvenc.video_encoder(Codec::Vp9), "hevc_nvenc");
+        assert_eq!(
+            HwAccel::VideoToolbox.video_encoder(Codec::Vp9),
+            "hevc_videotoolbox"
+        );
+        assert_eq!(HwAccel::Qsv.video_encoder(Codec::Vp9), "vp9_qsv");
+        assert_eq!(HwAccel::Software.video_encoder(Codec::Vp9), "libvpx-vp9");
+
+        assert!(HwAccel::Software
+            .encoder_options(Codec::Vp9)
+            .contains(&("-b:v", "0")));
+        assert_eq!(
+            HwAccel::Software.quality_param(Codec::Vp9, 23),
+            ("-crf", "33".to_string())
+        );
+    }
+
     #[test]
     fn test_hwaccel_type() {
         assert_eq!(HwAccel::Nvenc.hwaccel_type(), Some("cuda"));
@@ -1246,4 +1459,23 @@ mod tests {
         // (the actual result depends on hardware, but the method should not panic)
         let _ = HwAccel::Vaapi.has_av1_hw_decode();
     }
+
+    #[test]
+    fn test_init_hw_device_on_assigned_gpu() {
+        let cuda = GpuDevice::Cuda(1);
+        let render = GpuDevice::Render("/dev/dri/renderD129".into());
+        assert_eq!(HwAccel::Nvenc.init_hw_device(Some(&cuda)).unwrap(), "cuda=cuda:1");
+        assert_eq!(
+            HwAccel::Vaapi.init_hw_device(Some(&render)).unwrap(),
+            "vaapi=vaapi:/dev/dri/renderD129"
+        );
+        assert_eq!(
+            HwAccel::Qsv.init_hw_device(Some(&render)).unwrap(),
+            "qsv=qsv:hw_any,child_device=/dev/dri/renderD129"
+        );
+        assert_eq!(HwAccel::Nvenc.init_hw_device(None).unwrap(), "cuda=cuda:0");
+        assert_eq!(HwAccel::Nvenc.device_options(Some(&cuda)), vec![("-gpu", "1".to_string())]);
+        assert!(HwAccel::Vaapi.device_options(Some(&render)).is_empty());
+        assert!(HwAccel::Software.device_options(Some(&cuda)).is_empty());
+    }
 }
@@ -0,0 +1,174 @@
+//! EBU R128 loudness normalization.
+//!
+//! Phone recordings range from barely audible to clipping, and players have
+//! no way to even that out. When a job asks for it, the audio is run through
+//! FFmpeg's `loudnorm` filter in two passes: the first measures the source,
+//! the second applies a linear gain computed from that measurement, which
+//! keeps the dynamics intact. If measuring fails the filter runs in its
+//! single-pass (dynamic) mode instead.
+
+use std::path::Path;
+use tokio::process::Command;
+use tracing::debug;
+
+use crate::error::VideoError;
+use crate::video::clip::Clip;
+
+/// Integrated loudness target in LUFS (EBU R128)
+pub const TARGET_INTEGRATED: f64 = -23.0;
+/// Maximum true peak in dBTP
+pub const TARGET_TRUE_PEAK: f64 = -1.0;
+/// Loudness range target in LU
+pub const TARGET_RANGE: f64 = 11.0;
+
+/// `loudnorm` resamples to 192 kHz internally; bring the output back down
+const OUTPUT_SAMPLE_RATE: u32 = 48_000;
+
+/// Source loudness as reported by a `loudnorm` measurement pass
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessMeasurement {
+    pub integrated: f64,
+    pub true_peak: f64,
+    pub range: f64,
+    pub threshold: f64,
+    pub offset: f64,
+}
+
+impl LoudnessMeasurement {
+    /// Parse the JSON block `loudnorm` prints at the end of its stderr output
+    pub fn parse(stderr: &str) -> Option<Self> {
+        let start = stderr.rfind('{')?;
+        let end = start + stderr[start..].find('}')?;
+        let json: serde_json::Value = serde_json::from_str(&stderr[start..=end]).ok()?;
+
+        // Values are strings, and "-inf" for silent input
+        let field = |name: &str| {
+            json.get(name)?
+                .as_str()?
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite())
+        };
+        Some(Self {
+            integrated: field("input_i")?,
+            true_peak: field("input_tp")?,
+            range: field("input_lra")?,
+            threshold: field("input_thresh")?,
+            offset: field("target_offset")?,
+        })
+    }
+}
+
+/// Audio normalization to apply to a job's output
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Loudnorm {
+    /// First-pass measurement (`None` normalizes in a single pass)
+    pub measured: Option<LoudnessMeasurement>,
+}
+
+impl Loudnorm {
+    /// Audio filter chain for the encoding pass
+    pub fn filter(&self) -> String {
+        let targets = format!(
+            "loudnorm=I={}:TP={}:LRA={}",
+            TARGET_INTEGRATED, TARGET_TRUE_PEAK, TARGET_RANGE
+        );
+        let loudnorm = match &self.measured {
+            Some(m) => format!(
+                "{}:measured_I={:.2}:measured_TP={:.2}:measured_LRA={:.2}:measured_thresh={:.2}:offset={:.2}:linear=true",
+                targets, m.integrated, m.true_peak, m.range, m.threshold, m.offset
+            ),
+            None => targets,
+        };
+        format!("{},aresample={}", loudnorm, OUTPUT_SAMPLE_RATE)
+    }
+
+    /// Run the measurement pass over the audio of `input` (or the clipped part of it)
+    pub async fn measure(
+        ffmpeg_path: &Path,
+        input: &str,
+        clip: Option<Clip>,
+    ) -> Result<LoudnessMeasurement, VideoError> {
+        let mut cmd = Command::new(ffmpeg_path);
+        cmd.kill_on_drop(true);
+        cmd.arg("-nostdin").arg("-hide_banner");
+        if let Some(clip) = clip {
+            cmd.args(clip.input_args());
+        }
+        cmd.arg("-i")
+            .arg(input)
+            .arg("-vn")
+            .arg("-af")
+            .arg(format!(
+                "loudnorm=I={}:TP={}:LRA={}:print_format=json",
+                TARGET_INTEGRATED, TARGET_TRUE_PEAK, TARGET_RANGE
+            ))
+            .arg("-f")
+            .arg("null")
+            .arg("-");
+
+        debug!(input = %input, "Measuring audio loudness");
+        let output = cmd.output().await.map_err(VideoError::Io)?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            return Err(crate::video::ffmpeg_error::failure(
+                "Loudness measurement failed",
+                &stderr,
+            ));
+        }
+
+        LoudnessMeasurement::parse(&stderr).ok_or_else(|| {
+            VideoError::FfmpegFailed("Loudness measurement produced no result".to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STDERR: &str = r#"size=N/A time=00:00:10.00 bitrate=N/A speed= 412x
+[Parsed_loudnorm_0 @ 0x5581c8e0a2c0]
+{
+	"input_i" : "-31.42",
+	"input_tp" : "-9.87",
+	"input_lra" : "6.10",
+	"input_thresh" : "-41.80",
+	"output_i" : "-23.05",
+	"output_tp" : "-1.50",
+	"output_lra" : "5.20",
+	"output_thresh" : "-33.40",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.05"
+}
+"#;
+
+    #[test]
+    fn test_parse_measurement() {
+        let m = LoudnessMeasurement::parse(STDERR).unwrap();
+        assert_eq!(m.integrated, -31.42);
+        assert_eq!(m.true_peak, -9.87);
+        assert_eq!(m.threshold, -41.8);
+        assert_eq!(m.offset, 0.05);
+
+        // Silent input reports -inf, which can't be fed back into the filter
+        assert!(LoudnessMeasurement::parse(&STDERR.replace("-31.42", "-inf")).is_none());
+        assert!(LoudnessMeasurement::parse("no json here").is_none());
+    }
+
+    #[test]
+    fn test_loudnorm_filter() {
+        assert_eq!(
+            Loudnorm::default().filter(),
+            "loudnorm=I=-23:TP=-1:LRA=11,aresample=48000"
+        );
+
+        let two_pass = Loudnorm {
+            measured: LoudnessMeasurement::parse(STDERR),
+        }
+        .filter();
+        assert!(two_pass.starts_with("loudnorm=I=-23:TP=-1:LRA=11:measured_I=-31.42:"));
+        assert!(two_pass.contains(":offset=0.05:linear=true,"));
+    }
+}
@@ -0,0 +1,230 @@
+//! Soft memory budget for concurrent encodes.
+//!
+//! FFmpeg's memory use is dominated by decoded frames held in decoder,
+//! filter and encoder queues, so it grows with resolution and the number of
+//! renditions rather than with the input size. Each job reserves its
+//! estimate before encoding; jobs that don't fit alongside the running ones
+//! wait instead of pushing a small VPS into the OOM killer. The budget is
+//! soft: a job larger than the whole budget still runs, just on its own.
+
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tracing::{debug, info};
+
+use crate::util::resources::memory_available;
+use crate::video::VideoMetadata;
+
+/// Frames held by the decoder and the scaling filters
+const DECODE_FRAMES: u64 = 16;
+
+/// Frames held per rendition by the encoder (lookahead, B-frames, muxer queue)
+const ENCODE_FRAMES: u64 = 48;
+
+/// Fixed per-process overhead (codec contexts, muxers, our own buffers)
+const BASE_BYTES: u64 = 96 * 1024 * 1024;
+
+/// Resolution assumed when ffprobe didn't report one
+const FALLBACK_RESOLUTION: (u32, u32) = (1920, 1080);
+
+/// Fraction of available memory usable when no explicit budget is configured
+const AVAILABLE_FRACTION: f64 = 0.8;
+
+/// Bytes of one decoded 8-bit 4:2:0 frame
+fn frame_bytes(width: u32, height: u32) -> u64 {
+    width as u64 * height as u64 * 3 / 2
+}
+
+/// Predict the peak memory of a job's FFmpeg process.
+///
+/// `renditions` holds the target height of each output (`None` for the
+/// original resolution).
+pub fn estimate_memory_bytes(metadata: Option<&VideoMetadata>, renditions: &[Option<u32>]) -> u64 {
+    let (width, height) = metadata
+        .and_then(|m| m.resolution())
+        .filter(|(w, h)| *w > 0 && *h > 0)
+        .unwrap_or(FALLBACK_RESOLUTION);
+
+    let mut bytes = BASE_BYTES + frame_bytes(width, height) * DECODE_FRAMES;
+    for target in renditions {
+        let (w, h) = match target {
+            Some(t) if *t < height => ((width as u64 * *t as u64 / height as u64) as u32, *t),
+            _ => (width, height),
+        };
+        bytes += frame_bytes(w, h) * ENCODE_FRAMES;
+    }
+    bytes
+}
+
+/// Shared memory budget for concurrently running jobs.
+pub struct MemoryBudget {
+    limit: u64,
+    /// Re-check the host's available memory on each reservation
+    measure: bool,
+    reserved: Mutex<u64>,
+    released: Notify,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            measure: false,
+            reserved: Mutex::new(0),
+            released: Notify::new(),
+        }
+    }
+
+    /// Build the budget for this host.
+    ///
+    /// The limit is the configured budget, or a share of the memory
+    /// available at startup. Without either, scheduling is unrestricted.
+    pub fn for_host(budget: Option<u64>) -> Self {
+        let limit = match (budget, memory_available()) {
+            (Some(b), _) => b,
+            (None, Some(available)) => (available as f64 * AVAILABLE_FRACTION) as u64,
+            (None, None) => u64::MAX,
+        };
+
+        info!(limit_bytes = limit, budget_bytes = ?budget, "Memory budget");
+        Self {
+            measure: true,
+            ..Self::new(limit)
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Bytes currently reserved by running jobs
+    pub fn reserved(&self) -> u64 {
+        *self.reserved.lock().unwrap()
+    }
+
+    /// Memory available to new processes right now, if measurable
+    pub fn available_now(&self) -> Option<u64> {
+        if self.measure {
+            memory_available()
+        } else {
+            None
+        }
+    }
+
+    /// Reserve `bytes` if they fit alongside existing reservations and in
+    /// the memory currently available.
+    ///
+    /// A job always fits while nothing else is reserved, so oversized jobs
+    /// run alone rather than failing.
+    pub fn try_reserve(self: &Arc<Self>, bytes: u64) -> Option<MemoryReservation> {
+        let available = self.available_now();
+        let mut reserved = self.reserved.lock().unwrap();
+        let fits = reserved.saturating_add(bytes) <= self.limit
+            && available.is_none_or(|a| bytes <= a);
+        if *reserved > 0 && !fits {
+            return None;
+        }
+        *reserved += bytes;
+        debug!(bytes, total_reserved = *reserved, "Reserved memory");
+        Some(MemoryReservation {
+            budget: self.clone(),
+            bytes,
+        })
+    }
+
+    /// Wait until `bytes` fit in the budget, then reserve them.
+    pub async fn reserve(self: &Arc<Self>, bytes: u64) -> MemoryReservation {
+        loop {
+            // Register for wakeups before checking to avoid missing a release
+            let notified = self.released.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if let Some(reservation) = self.try_reserve(bytes) {
+                return reservation;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// A held memory reservation, released on drop.
+pub struct MemoryReservation {
+    budget: Arc<MemoryBudget>,
+    bytes: u64,
+}
+
+impl MemoryReservation {
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        {
+            let mut reserved = self.budget.reserved.lock().unwrap();
+            *reserved = reserved.saturating_sub(self.bytes);
+        }
+        self.budget.released.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::{timeout, Duration};
+
+    fn metadata(width: u32, height: u32) -> VideoMetadata {
+        VideoMetadata::builder().with_video(width, height).build()
+    }
+
+    #[test]
+    fn test_estimate_scales_with_resolution() {
+        let hd = estimate_memory_bytes(Some(&metadata(1920, 1080)), &[None]);
+        let uhd = estimate_memory_bytes(Some(&metadata(3840, 2160)), &[None]);
+        let ladder = estimate_memory_bytes(
+            Some(&metadata(3840, 2160)),
+            &[None, Some(1080), Some(720)],
+        );
+
+        // 1920x1080x1.5 bytes per frame, 16 decoded + 48 encoded
+        assert_eq!(hd, BASE_BYTES + 3_110_400 * 64);
+        assert!(uhd > hd * 2);
+        assert!(ladder > uhd);
+        assert!(ladder < uhd * 2);
+
+        // Unknown resolution is treated as 1080p
+        assert_eq!(estimate_memory_bytes(None, &[None]), hd);
+    }
+
+    #[test]
+    fn test_oversized_job_runs_alone() {
+        let budget = Arc::new(MemoryBudget::new(100));
+        let big = budget.try_reserve(150).unwrap();
+        assert!(budget.try_reserve(1).is_none());
+        drop(big);
+
+        let a = budget.try_reserve(60).unwrap();
+        assert!(budget.try_reserve(50).is_none());
+        assert!(budget.try_reserve(40).is_some());
+        drop(a);
+        assert_eq!(budget.reserved(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_reserve_waits_for_release() {
+        let budget = Arc::new(MemoryBudget::new(100));
+        let held = budget.reserve(80).await;
+        let waiter = {
+            let budget = budget.clone();
+            tokio::spawn(async move { budget.reserve(50).await.bytes() })
+        };
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        drop(held);
+        let bytes = timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+        assert_eq!(bytes, 50);
+    }
+}
@@ -5,10 +5,41 @@ use tracing::debug;
 
 use crate::error::VideoError;
 
+/// Common frame rates a VFR source is snapped to, as (FFmpeg rate, fps)
+const STANDARD_FRAME_RATES: &[(&str, f64)] = &[
+    ("24000/1001", 24000.0 / 1001.0),
+    ("24", 24.0),
+    ("25", 25.0),
+    ("30000/1001", 30000.0 / 1001.0),
+    ("30", 30.0),
+    ("50", 50.0),
+    ("60000/1001", 60000.0 / 1001.0),
+    ("60", 60.0),
+];
+
+/// Relative gap between the nominal and average frame rate above which a
+/// stream is treated as variable frame rate
+const VFR_TOLERANCE: f64 = 0.01;
+
+/// Relative distance within which an average rate snaps to a standard one
+const SNAP_TOLERANCE: f64 = 0.03;
+
+/// Parse an ffprobe frame rate ("30000/1001" or "29.97"). Returns `None` for
+/// unknown rates ("0/0").
+pub fn parse_frame_rate(s: &str) -> Option<f64> {
+    let fps = match s.split_once('/') {
+        Some((num, den)) => num.trim().parse::<f64>().ok()? / den.trim().parse::<f64>().ok()?,
+        None => s.trim().parse().ok()?,
+    };
+    (fps.is_finite() && fps > 0.0).then_some(fps)
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct VideoMetadata {
     pub format: FormatInfo,
     pub streams: Vec<StreamInfo>,
+    #[serde(default)]
+    pub chapters: Vec<ChapterInfo>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -29,8 +60,140 @@ pub struct StreamInfo {
     pub bit_rate: Option<String>,
     #[serde(rename = "r_frame_rate")]
     pub frame_rate: Option<String>,
+    #[serde(default)]
+    pub avg_frame_rate: Option<String>,
     pub channels: Option<u32>,
     pub sample_rate: Option<String>,
+    pub start_time: Option<String>,
+    pub duration: Option<String>,
+    /// Transfer characteristics (e.g. "bt709", "smpte2084" for HDR10, "arib-std-b67" for HLG)
+    #[serde(default)]
+    pub color_transfer: Option<String>,
+    /// Field order ("progressive", or "tt"/"bb"/"tb"/"bt" for interlaced video)
+    #[serde(default)]
+    pub field_order: Option<String>,
+    #[serde(default)]
+    pub side_data_list: Vec<SideData>,
+    #[serde(default)]
+    pub tags: StreamTags,
+}
+
+/// A chapter marker of the container
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChapterInfo {
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    #[serde(default)]
+    pub tags: ChapterTags,
+}
+
+/// Metadata ffprobe reports for a chapter
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ChapterTags {
+    pub title: Option<String>,
+}
+
+impl ChapterInfo {
+    /// Start of the chapter in seconds
+    pub fn start_secs(&self) -> Option<f64> {
+        self.start_time.as_ref()?.parse().ok()
+    }
+
+    /// End of the chapter in seconds
+    pub fn end_secs(&self) -> Option<f64> {
+        self.end_time.as_ref()?.parse().ok()
+    }
+
+    /// The chapter's title, unless missing or blank
+    pub fn title(&self) -> Option<&str> {
+        self.tags.title.as_deref().map(str::trim).filter(|t| !t.is_empty())
+    }
+}
+
+/// Stream side data ffprobe reports (only the display matrix is used)
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SideData {
+    /// Counter-clockwise rotation of the display matrix in degrees
+    pub rotation: Option<f64>,
+}
+
+/// Container metadata ffprobe reports for a stream
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StreamTags {
+    /// ISO 639-2 language code (e.g. "eng"); "und" when unknown
+    pub language: Option<String>,
+    /// Free-form track title (e.g. "Director's commentary")
+    pub title: Option<String>,
+    /// Clockwise display rotation in degrees, as written by older muxers
+    pub rotate: Option<String>,
+}
+
+impl StreamInfo {
+    /// The stream's language, unless missing or undetermined
+    pub fn language(&self) -> Option<&str> {
+        self.tags
+            .language
+            .as_deref()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && *l != "und")
+    }
+
+    /// Presentation start of the stream in seconds
+    pub fn start_secs(&self) -> Option<f64> {
+        self.start_time.as_ref()?.parse().ok()
+    }
+
+    /// Presentation end of the stream in seconds (start + duration)
+    pub fn end_secs(&self) -> Option<f64> {
+        let duration: f64 = self.duration.as_ref()?.parse().ok()?;
+        Some(self.start_secs().unwrap_or(0.0) + duration)
+    }
+
+    /// Clockwise rotation players apply when displaying the stream (0, 90,
+    /// 180 or 270), from the display matrix or the legacy `rotate` tag.
+    /// Phones record portrait video as landscape frames rotated this way.
+    pub fn rotation(&self) -> u32 {
+        let degrees = self
+            .side_data_list
+            .iter()
+            .find_map(|d| d.rotation)
+            .map(|ccw| -ccw)
+            .or_else(|| self.tags.rotate.as_deref()?.trim().parse().ok());
+        match degrees {
+            Some(d) if d.is_finite() => ((d / 90.0).round() as i64).rem_euclid(4) as u32 * 90,
+            _ => 0,
+        }
+    }
+
+    /// Whether the stream's frames are unevenly spaced (typical of phone and
+    /// screen recordings): the nominal rate doesn't match the average rate.
+    pub fn is_variable_frame_rate(&self) -> bool {
+        let nominal = self.frame_rate.as_deref().and_then(parse_frame_rate);
+        let average = self.avg_frame_rate.as_deref().and_then(parse_frame_rate);
+        match (nominal, average) {
+            (Some(nominal), Some(average)) => (nominal - average).abs() / average > VFR_TOLERANCE,
+            _ => false,
+        }
+    }
+
+    /// Constant frame rate to encode a VFR stream at, in FFmpeg's rate syntax.
+    ///
+    /// The average rate is snapped to the nearest standard rate when close to
+    /// one, otherwise rounded to whole frames. `None` for constant-rate streams.
+    pub fn cfr_target(&self) -> Option<String> {
+        if !self.is_variable_frame_rate() {
+            return None;
+        }
+        let average = parse_frame_rate(self.avg_frame_rate.as_deref()?)?;
+        let (rate, fps) = STANDARD_FRAME_RATES
+            .iter()
+            .min_by(|a, b| (a.1 - average).abs().total_cmp(&(b.1 - average).abs()))?;
+        if (fps - average).abs() / average <= SNAP_TOLERANCE {
+            Some(rate.to_string())
+        } else {
+            Some(format!("{}", average.round().max(1.0) as u32))
+        }
+    }
 }
 
 impl VideoMetadata {
@@ -44,6 +207,7 @@ impl VideoMetadata {
                 "json",
                 "-show_format",
                 "-show_streams",
+                "-show_chapters",
                 input,
             ])
             .output()
@@ -74,15 +238,110 @@ impl VideoMetadata {
         self.streams.iter().find(|s| s.codec_type == "audio")
     }
 
+    /// All audio streams, in input order (e.g. dubs and commentary tracks)
+    pub fn audio_streams(&self) -> Vec<&StreamInfo> {
+        self.streams
+            .iter()
+            .filter(|s| s.codec_type == "audio")
+            .collect()
+    }
+
     /// Get video duration in seconds
     pub fn duration_secs(&self) -> Option<f64> {
         self.format.duration.as_ref()?.parse().ok()
     }
 
-    /// Get video resolution as (width, height)
+    /// Get video resolution as (width, height), as displayed
     pub fn resolution(&self) -> Option<(u32, u32)> {
         let video = self.video_stream()?;
-        Some((video.width?, video.height?))
+        let (width, height) = (video.width?, video.height?);
+        // Sideways-stored frames are shown (and transcoded) upright
+        match video.rotation() {
+            90 | 270 => Some((height, width)),
+            _ => Some((width, height)),
+        }
+    }
+
+    /// Get the container size in bytes (not reported for some streamed inputs)
+    pub fn size_bytes(&self) -> Option<u64> {
+        self.format.size.as_ref()?.parse().ok()
+    }
+
+    /// Get the overall bitrate in bits per second.
+    ///
+    /// Falls back to size / duration when ffprobe doesn't report a bitrate.
+    pub fn bit_rate_bps(&self) -> Option<u64> {
+        if let Some(bps) = self.format.bit_rate.as_ref().and_then(|b| b.parse().ok()) {
+            return Some(bps);
+        }
+        let duration = self.duration_secs().filter(|d| *d > 0.0)?;
+        Some((self.size_bytes()? as f64 * 8.0 / duration) as u64)
+    }
+}
+
+/// ffprobe output for test fixtures, parsed like the real thing
+#[cfg(test)]
+pub(crate) struct MetadataBuilder {
+    format: serde_json::Value,
+    streams: Vec<serde_json::Value>,
+}
+
+#[cfg(test)]
+impl VideoMetadata {
+    /// An MP4 without streams, duration or bit rate
+    pub(crate) fn builder() -> MetadataBuilder {
+        MetadataBuilder {
+            format: serde_json::json!({"filename": "in.mp4", "format_name": "mp4"}),
+            streams: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl MetadataBuilder {
+    pub(crate) fn with_duration(mut self, secs: &str) -> Self {
+        self.format["duration"] = secs.into();
+        self
+    }
+
+    pub(crate) fn with_bit_rate(mut self, bps: &str) -> Self {
+        self.format["bit_rate"] = bps.into();
+        self
+    }
+
+    /// Add an H.264 video stream
+    pub(crate) fn with_video(self, width: u32, height: u32) -> Self {
+        self.with_stream(serde_json::json!({
+            "codec_name": "h264",
+            "codec_type": "video",
+            "width": width,
+            "height": height,
+        }))
+    }
+
+    /// Add an AAC audio stream
+    pub(crate) fn with_audio(self) -> Self {
+        self.with_stream(serde_json::json!({"codec_name": "aac", "codec_type": "audio"}))
+    }
+
+    pub(crate) fn with_stream(mut self, stream: serde_json::Value) -> Self {
+        self.streams.push(stream);
+        self
+    }
+
+    /// Set an ffprobe field (e.g. `field_order`) on the stream added last
+    pub(crate) fn with_field(mut self, key: &str, value: impl Into<serde_json::Value>) -> Self {
+        let stream = self.streams.last_mut().expect("no stream to set a field on");
+        stream[key] = value.into();
+        self
+    }
+
+    pub(crate) fn build(self) -> VideoMetadata {
+        serde_json::from_value(serde_json::json!({
+            "format": self.format,
+            "streams": self.streams,
+        }))
+        .unwrap()
     }
 }
 
@@ -113,7 +372,14 @@ mod tests {
                     "codec_name": "aac",
                     "codec_type": "audio",
                     "channels": 2,
-                    "sample_rate": "48000"
+                    "sample_rate": "48000",
+                    "tags": {"language": "und", "handler_name": "SoundHandler"}
+                },
+                {
+                    "codec_name": "ac3",
+                    "codec_type": "audio",
+                    "channels": 6,
+                    "tags": {"language": "spa", "title": "Doblaje"}
                 }
             ]
         }"#;
@@ -123,11 +389,75 @@ mod tests {
         assert_eq!(metadata.format.filename, "test.mp4");
         assert_eq!(metadata.duration_secs(), Some(120.5));
         assert_eq!(metadata.resolution(), Some((1920, 1080)));
+        assert!(metadata.chapters.is_empty());
 
         let video = metadata.video_stream().unwrap();
         assert_eq!(video.codec_name.as_deref(), Some("h264"));
 
         let audio = metadata.audio_stream().unwrap();
         assert_eq!(audio.channels, Some(2));
+        assert_eq!(audio.language(), None);
+
+        let tracks = metadata.audio_streams();
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[1].language(), Some("spa"));
+        assert_eq!(tracks[1].tags.title.as_deref(), Some("Doblaje"));
+
+        assert_eq!(metadata.size_bytes(), Some(1024000));
+        assert_eq!(metadata.bit_rate_bps(), Some(1000000));
+    }
+
+    fn video_stream(r_frame_rate: &str, avg_frame_rate: &str) -> StreamInfo {
+        serde_json::from_value(serde_json::json!({
+            "codec_type": "video",
+            "r_frame_rate": r_frame_rate,
+            "avg_frame_rate": avg_frame_rate,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_rotation() {
+        let stream = |extra: serde_json::Value| -> StreamInfo {
+            let mut json = serde_json::json!({"codec_type": "video", "width": 1920, "height": 1080});
+            json.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            serde_json::from_value(json).unwrap()
+        };
+
+        assert_eq!(stream(serde_json::json!({})).rotation(), 0);
+        // Display matrix rotation is counter-clockwise
+        let portrait = stream(serde_json::json!({"side_data_list": [{"side_data_type": "Display Matrix", "rotation": -90}]}));
+        assert_eq!(portrait.rotation(), 90);
+        assert_eq!(stream(serde_json::json!({"side_data_list": [{"rotation": 90}]})).rotation(), 270);
+        assert_eq!(stream(serde_json::json!({"tags": {"rotate": "180"}})).rotation(), 180);
+        assert_eq!(stream(serde_json::json!({"tags": {"rotate": "-90"}})).rotation(), 270);
+
+        let metadata = VideoMetadata::builder()
+            .with_video(1920, 1080)
+            .with_field("side_data_list", serde_json::json!([{"rotation": -90}]))
+            .build();
+        assert_eq!(metadata.resolution(), Some((1080, 1920)));
+    }
+
+    #[test]
+    fn test_variable_frame_rate_detection() {
+        assert_eq!(parse_frame_rate("30000/1001").map(|f| (f * 100.0).round()), Some(2997.0));
+        assert_eq!(parse_frame_rate("0/0"), None);
+
+        // Constant rate, including a short last frame pulling the average down slightly
+        assert!(!video_stream("30000/1001", "30000/1001").is_variable_frame_rate());
+        assert!(!video_stream("30/1", "2997/100").is_variable_frame_rate());
+        assert_eq!(video_stream("25/1", "25/1").cfr_target(), None);
+        // Unknown average rate
+        assert!(!video_stream("30/1", "0/0").is_variable_frame_rate());
+
+        // Phone recording: nominal 60, actual ~29.6
+        let phone = video_stream("60/1", "2960/100");
+        assert!(phone.is_variable_frame_rate());
+        assert_eq!(phone.cfr_target().as_deref(), Some("30000/1001"));
+
+        // Screen recording at an unusual average rate
+        let screen = video_stream("1000/1", "1750/100");
+        assert_eq!(screen.cfr_target().as_deref(), Some("18"));
     }
 }
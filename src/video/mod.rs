@@ -1,13 +1,32 @@
+pub mod av_sync;
+pub mod chapters;
+pub mod clip;
+pub mod deinterlace;
 pub mod ffmpeg;
+pub mod ffmpeg_error;
+pub mod fps_cap;
+pub mod gop;
+pub mod gpu;
 pub mod hwaccel;
+pub mod loudness;
+pub mod memory;
 pub mod metadata;
+pub mod output_size;
 pub mod playlist;
+pub mod scratch;
+pub mod segment_watch;
+pub mod segmenting;
+pub mod slideshow;
+pub mod storyboard;
+pub mod tonemap;
 pub mod transform;
+pub mod watermark;
 
 pub use ffmpeg::FfmpegCommand;
 pub use hwaccel::HwAccel;
 pub use metadata::VideoMetadata;
 pub use playlist::PlaylistRewriter;
 pub use transform::{
-    ResolutionConfig, SegmentType, TransformConfig, TransformResult, VideoProcessor,
+    ResolutionConfig, SegmentType, ThumbnailTransformResult, TransformConfig, TransformResult,
+    VideoProcessor,
 };
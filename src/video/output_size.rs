@@ -0,0 +1,144 @@
+//! Estimating how large a job's output will be before encoding it.
+//!
+//! Lets the handler refuse, or tone down, jobs whose output would dwarf the
+//! input (e.g. a low-bitrate source re-encoded at high quality into a full
+//! HLS ladder). The model is deliberately rough: constant-quality renditions
+//! follow a bits-per-pixel rule of thumb, bitrate renditions use their
+//! target, and stream copies keep the source bitrate.
+
+use crate::dvm::events::Codec;
+use crate::video::transform::ResolutionConfig;
+use crate::video::VideoMetadata;
+
+/// Bits per pixel per frame of H.264 at the reference CRF
+const REFERENCE_BPP: f64 = 0.08;
+const REFERENCE_CRF: f64 = 23.0;
+
+/// CRF steps that halve the bitrate (x264/x265 rule of thumb)
+pub const CRF_HALVING_STEP: u32 = 6;
+
+/// Assumed when ffprobe doesn't report them
+const DEFAULT_FPS: f64 = 30.0;
+const DEFAULT_AUDIO_BPS: f64 = 128_000.0;
+
+/// Bitrate relative to H.264 at the same visual quality
+fn codec_efficiency(codec: Codec) -> f64 {
+    match codec {
+        Codec::H264 => 1.0,
+        Codec::H265 | Codec::Vp9 => 0.6,
+        Codec::AV1 => 0.5,
+    }
+}
+
+/// Parse an FFmpeg bitrate ("2500k", "2M") into bits per second
+fn bitrate_bps(s: &str) -> Option<f64> {
+    let s = s.trim().to_lowercase();
+    let (digits, scale) = match s.chars().last()? {
+        'k' => (&s[..s.len() - 1], 1e3),
+        'm' => (&s[..s.len() - 1], 1e6),
+        _ => (s.as_str(), 1.0),
+    };
+    digits.parse::<f64>().ok().map(|v| v * scale)
+}
+
+/// Estimate the combined size of `renditions` for `duration_secs` of output.
+///
+/// Returns `None` if the source resolution is unknown.
+pub fn estimate_output_bytes(
+    metadata: &VideoMetadata,
+    renditions: &[ResolutionConfig],
+    codec: Codec,
+    duration_secs: f64,
+) -> Option<u64> {
+    let (source_w, source_h) = metadata.resolution()?;
+    let video = metadata.video_stream();
+    let fps = video
+        .and_then(|s| s.avg_frame_rate.as_deref().or(s.frame_rate.as_deref()))
+        .and_then(crate::video::metadata::parse_frame_rate)
+        .unwrap_or(DEFAULT_FPS);
+    let source_bps = metadata.bit_rate_bps().map(|b| b as f64);
+
+    let mut bps = 0.0;
+    for r in renditions {
+        let audio = r
+            .audio_bitrate
+            .as_deref()
+            .and_then(bitrate_bps)
+            .unwrap_or(DEFAULT_AUDIO_BPS);
+
+        let video_bps = if r.is_original {
+            // Copied as-is; without a source bitrate, assume it's like a CRF 23 encode
+            match source_bps {
+                Some(b) => b,
+                None => crf_bps(source_w, source_h, fps, REFERENCE_CRF, codec) + audio,
+            }
+        } else if let Some(target) = r.video_bitrate.as_deref().and_then(bitrate_bps) {
+            target + audio
+        } else {
+            let height = r.height.unwrap_or(source_h).min(source_h);
+            let width = source_w as f64 * height as f64 / source_h as f64;
+            let crf = r.quality.unwrap_or(REFERENCE_CRF as u32) as f64;
+            crf_bps(width as u32, height, fps, crf, codec) + audio
+        };
+        bps += video_bps;
+    }
+
+    Some((bps * duration_secs / 8.0) as u64)
+}
+
+fn crf_bps(width: u32, height: u32, fps: f64, crf: f64, codec: Codec) -> f64 {
+    let bpp = REFERENCE_BPP
+        * 2f64.powf((REFERENCE_CRF - crf) / CRF_HALVING_STEP as f64)
+        * codec_efficiency(codec);
+    width as f64 * height as f64 * fps * bpp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(bit_rate: &str) -> VideoMetadata {
+        VideoMetadata::builder()
+            .with_duration("100")
+            .with_bit_rate(bit_rate)
+            .with_video(1920, 1080)
+            .with_field("avg_frame_rate", "30/1")
+            .build()
+    }
+
+    #[test]
+    fn test_estimate_output_bytes() {
+        let meta = metadata("4000000");
+        let crf = |quality| ResolutionConfig {
+            height: Some(720),
+            quality: Some(quality),
+            audio_bitrate: Some("128k".to_string()),
+            ..Default::default()
+        };
+
+        // 1280x720 @ 30fps at CRF 23 is ~2.2 Mbit/s plus audio
+        let base = estimate_output_bytes(&meta, &[crf(23)], Codec::H264, 100.0).unwrap();
+        assert_eq!(base / 1000, 29_248);
+
+        // Six CRF steps halve the video bitrate, HEVC needs less than H.264
+        let halved = estimate_output_bytes(&meta, &[crf(29)], Codec::H264, 100.0).unwrap();
+        assert!(halved < base * 6 / 10);
+        let hevc = estimate_output_bytes(&meta, &[crf(23)], Codec::H265, 100.0).unwrap();
+        assert!(hevc < base);
+
+        let copy = ResolutionConfig {
+            is_original: true,
+            ..Default::default()
+        };
+        let fixed = ResolutionConfig {
+            height: Some(480),
+            video_bitrate: Some("1000k".to_string()),
+            audio_bitrate: Some("96k".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            estimate_output_bytes(&meta, &[copy, fixed], Codec::H264, 10.0),
+            Some((4_000_000 + 1_096_000) * 10 / 8)
+        );
+    }
+}
@@ -1,7 +1,9 @@
 use regex::Regex;
 use std::collections::HashMap;
 use std::path::Path;
+use std::sync::OnceLock;
 use tokio::fs;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
 
 use crate::error::VideoError;
 
@@ -10,6 +12,24 @@ use crate::error::VideoError;
 /// The frontend intercepts key loading and provides the actual key from the Nostr event.
 pub const ENCRYPTION_KEY_PLACEHOLDER_URI: &str = "urn:nostr:key";
 
+/// `URI="..."` attribute in playlist tags
+fn uri_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"URI="([^"]+)""#).unwrap())
+}
+
+/// `FRAME-RATE=...` attribute of a variant
+fn frame_rate_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r",?FRAME-RATE=[0-9.]+").unwrap())
+}
+
+/// Standalone segment filename line
+fn segment_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^([^#\s].*\.(m4s|ts|mp4))$").unwrap())
+}
+
 /// Rewrites M3U8 playlists to use hash-based filenames for Blossom uploads
 pub struct PlaylistRewriter {
     /// Map from original filename to SHA-256 hash
@@ -35,71 +55,99 @@ impl PlaylistRewriter {
         self.rewrite_content(&content)
     }
 
+    /// Rewrite the playlist at `src` into `dest` line by line.
+    ///
+    /// Playlists of long 4K encodes run to thousands of lines, so neither
+    /// the original nor the rewritten playlist is held in memory.
+    /// Returns the size of the written playlist.
+    pub async fn rewrite_playlist_to(&self, src: &Path, dest: &Path) -> Result<u64, VideoError> {
+        let mut lines = BufReader::new(fs::File::open(src).await?).lines();
+        let mut writer = BufWriter::new(fs::File::create(dest).await?);
+        let mut written = 0u64;
+
+        while let Some(line) = lines.next_line().await? {
+            let new_line = self.rewrite_line(&line);
+            writer.write_all(new_line.as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+            written += new_line.len() as u64 + 1;
+        }
+        writer.flush().await?;
+
+        Ok(written)
+    }
+
     /// Rewrite playlist content
     /// Replaces #EXT-X-KEY URI with placeholder (key is delivered via Nostr, not fetched)
     pub fn rewrite_content(&self, content: &str) -> Result<String, VideoError> {
-        let uri_regex =
-            Regex::new(r#"URI="([^"]+)""#).map_err(|e| VideoError::PlaylistParse(e.to_string()))?;
-        let segment_regex = Regex::new(r"^([^#\s].*\.(m4s|ts|mp4))$")
-            .map_err(|e| VideoError::PlaylistParse(e.to_string()))?;
-
-        let mut output = String::new();
+        let mut output = String::with_capacity(content.len());
 
         for line in content.lines() {
-            let new_line = if line.starts_with("#EXT-X-KEY") {
-                // Replace key URI with placeholder - actual key delivered via Nostr event
-                uri_regex
-                    .replace(line, format!(r#"URI="{}""#, ENCRYPTION_KEY_PLACEHOLDER_URI))
-                    .to_string()
-            } else if line.starts_with('#') {
-                // Check for URI in tags like EXT-X-MAP
-                if let Some(caps) = uri_regex.captures(line) {
-                    let original = &caps[1];
-                    if let Some(hash) = self.segment_hashes.get(original) {
-                        let ext = Path::new(original)
-                            .extension()
-                            .and_then(|e| e.to_str())
-                            .unwrap_or("m4s");
-                        line.replace(original, &format!("{}.{}", hash, ext))
-                    } else {
-                        line.to_string()
-                    }
-                } else {
-                    line.to_string()
-                }
-            } else if let Some(caps) = segment_regex.captures(line) {
-                // Standalone segment filename
-                let original = &caps[1];
-                if let Some(hash) = self.segment_hashes.get(original) {
-                    let ext = Path::new(original)
-                        .extension()
-                        .and_then(|e| e.to_str())
-                        .unwrap_or("m4s");
-                    format!("{}.{}", hash, ext)
-                } else {
-                    line.to_string()
-                }
-            } else {
-                line.to_string()
-            };
-
-            output.push_str(&new_line);
+            output.push_str(&self.rewrite_line(line));
             output.push('\n');
         }
 
         Ok(output)
     }
 
+    /// Rewrite a single stream playlist line
+    fn rewrite_line(&self, line: &str) -> String {
+        if line.starts_with("#EXT-X-KEY") {
+            // Replace key URI with placeholder - actual key delivered via Nostr event
+            uri_regex()
+                .replace(line, format!(r#"URI="{}""#, ENCRYPTION_KEY_PLACEHOLDER_URI))
+                .to_string()
+        } else if line.starts_with('#') {
+            // Check for URI in tags like EXT-X-MAP
+            match uri_regex()
+                .captures(line)
+                .and_then(|caps| self.hashed_name(caps.get(1)?.as_str()))
+            {
+                Some((original, hashed)) => line.replace(original, &hashed),
+                None => line.to_string(),
+            }
+        } else if let Some(caps) = segment_regex().captures(line) {
+            // Standalone segment filename
+            match self.hashed_name(caps.get(1).map_or("", |m| m.as_str())) {
+                Some((_, hashed)) => hashed,
+                None => line.to_string(),
+            }
+        } else {
+            line.to_string()
+        }
+    }
+
+    /// Hash-based name for a registered segment, keeping its extension
+    fn hashed_name<'a>(&self, original: &'a str) -> Option<(&'a str, String)> {
+        let hash = self.segment_hashes.get(original)?;
+        let ext = Path::new(original)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("m4s");
+        Some((original, format!("{}.{}", hash, ext)))
+    }
+
     /// Rewrite master playlist to use hash-based stream playlist names
     pub fn rewrite_master_playlist(
         &self,
         content: &str,
         playlist_hashes: &HashMap<String, String>,
     ) -> Result<String, VideoError> {
-        let mut output = String::new();
+        let mut output = String::with_capacity(content.len());
 
         for line in content.lines() {
-            let new_line = if line.starts_with('#') {
+            let new_line = if line.starts_with("#EXT-X-MEDIA") {
+                // Alternate audio rendition playlist
+                match uri_regex()
+                    .captures(line)
+                    .and_then(|caps| playlist_hashes.get(&caps[1]).map(|h| (caps[1].to_string(), h)))
+                {
+                    Some((original, hash)) => line.replace(
+                        &format!(r#"URI="{}""#, original),
+                        &format!(r#"URI="{}.m3u8""#, hash),
+                    ),
+                    None => line.to_string(),
+                }
+            } else if line.starts_with('#') {
                 line.to_string()
             } else if line.ends_with(".m3u8") {
                 // Stream playlist reference
@@ -120,6 +168,104 @@ impl PlaylistRewriter {
     }
 }
 
+/// Label of a `RESOLUTION` attribute ("1280x720" becomes "720p"), named
+/// after the shorter side so portrait variants ("720x1280") read the same
+pub fn resolution_label(resolution: &str) -> Option<String> {
+    let (w, h) = resolution.split_once('x')?;
+    let (w, h): (u32, u32) = (w.parse().ok()?, h.parse().ok()?);
+    Some(format!("{}p", w.min(h)))
+}
+
+/// Master playlist cut down to its lowest-resolution variant
+#[derive(Debug, Clone, PartialEq)]
+pub struct LowestVariant {
+    /// Master playlist with only that variant and the audio renditions
+    pub master: String,
+    /// Playlists the cut-down master refers to (video first, then audio)
+    pub playlists: Vec<String>,
+    /// Resolution label of the variant (e.g. "240p")
+    pub resolution: String,
+}
+
+/// Cut `master` down to its lowest-resolution variant, so a player can start
+/// on it while the rest of the ladder is still being uploaded.
+///
+/// Returns `None` when there is only one variant to begin with.
+pub fn lowest_variant(master: &str) -> Option<LowestVariant> {
+    let lines: Vec<&str> = master.lines().collect();
+    // (index of the #EXT-X-STREAM-INF line, shorter side, playlist)
+    let variants: Vec<(usize, Option<u32>, &str)> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.starts_with("#EXT-X-STREAM-INF:"))
+        .filter_map(|(i, line)| {
+            let uri = lines.get(i + 1).filter(|l| !l.starts_with('#'))?;
+            let size = line
+                .split("RESOLUTION=")
+                .nth(1)
+                .and_then(|r| r.split(',').next())
+                .and_then(|r| {
+                    let (w, h) = r.split_once('x')?;
+                    Some(w.parse::<u32>().ok()?.min(h.parse().ok()?))
+                });
+            Some((i, size, *uri))
+        })
+        .collect();
+    if variants.len() < 2 {
+        return None;
+    }
+    let &(keep, size, playlist) = variants
+        .iter()
+        .min_by_key(|(_, size, _)| size.unwrap_or(u32::MAX))?;
+
+    let dropped: Vec<usize> = variants
+        .iter()
+        .filter(|(i, _, _)| *i != keep)
+        .flat_map(|(i, _, _)| [*i, i + 1])
+        .collect();
+    let mut out = String::with_capacity(master.len());
+    let mut playlists = vec![playlist.to_string()];
+    for (i, line) in lines.iter().enumerate() {
+        if dropped.contains(&i) {
+            continue;
+        }
+        if line.starts_with("#EXT-X-MEDIA") {
+            if let Some(caps) = uri_regex().captures(line) {
+                playlists.push(caps[1].to_string());
+            }
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    Some(LowestVariant {
+        master: out,
+        playlists,
+        resolution: size.map_or_else(|| "unknown".to_string(), |s| format!("{}p", s)),
+    })
+}
+
+/// Set the `FRAME-RATE` attribute of the variants in `master`, keyed by
+/// playlist name (e.g. "stream_0.m3u8"). Other variants are left as they are.
+pub fn set_frame_rates(master: &str, rates: &HashMap<String, f64>) -> String {
+    let lines: Vec<&str> = master.lines().collect();
+    let mut out = String::with_capacity(master.len());
+    for (i, line) in lines.iter().enumerate() {
+        let rate = lines
+            .get(i + 1)
+            .filter(|_| line.starts_with("#EXT-X-STREAM-INF:"))
+            .and_then(|uri| rates.get(uri.trim()));
+        match rate {
+            Some(rate) => {
+                out.push_str(&frame_rate_regex().replace(line, ""));
+                out.push_str(&format!(",FRAME-RATE={:.3}", rate));
+            }
+            None => out.push_str(line),
+        }
+        out.push('\n');
+    }
+    out
+}
+
 impl Default for PlaylistRewriter {
     fn default() -> Self {
         Self::new()
@@ -156,6 +302,33 @@ stream_0_001.m4s
         assert!(!result.contains("stream_0_000"));
     }
 
+    #[tokio::test]
+    async fn test_rewrite_playlist_to_file_matches_in_memory() {
+        let mut rewriter = PlaylistRewriter::new();
+        rewriter.add_segment("init_0.m4s", "init789");
+
+        let mut content = String::from("#EXTM3U\n#EXT-X-MAP:URI=\"init_0.m4s\"\n");
+        for i in 0..3000 {
+            let name = format!("stream_0_{:05}.m4s", i);
+            rewriter.add_segment(&name, &format!("{:064x}", i));
+            content.push_str(&format!("#EXTINF:2.000,\n{}\n", name));
+        }
+        content.push_str("#EXT-X-ENDLIST\n");
+
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("stream_0.m3u8");
+        let dest = dir.path().join("stream_0.rewritten.m3u8");
+        std::fs::write(&src, &content).unwrap();
+
+        let size = rewriter.rewrite_playlist_to(&src, &dest).await.unwrap();
+        let written = std::fs::read_to_string(&dest).unwrap();
+
+        assert_eq!(written, rewriter.rewrite_content(&content).unwrap());
+        assert_eq!(size, written.len() as u64);
+        assert!(written.contains(&format!("{:064x}.m4s", 2999)));
+        assert!(!written.contains("stream_0_"));
+    }
+
     #[test]
     fn test_rewrite_playlist_replaces_ext_x_key_uri() {
         let mut rewriter = PlaylistRewriter::new();
@@ -209,4 +382,90 @@ stream_1.m3u8
         assert!(result.contains("hash1.m3u8"));
         assert!(!result.contains("stream_0.m3u8"));
     }
+
+    #[test]
+    fn test_rewrite_master_playlist_audio_group() {
+        let rewriter = PlaylistRewriter::new();
+
+        let mut playlist_hashes = HashMap::new();
+        playlist_hashes.insert("stream_0.m3u8".to_string(), "hash0".to_string());
+        playlist_hashes.insert("stream_1.m3u8".to_string(), "hash1".to_string());
+        playlist_hashes.insert("stream_2.m3u8".to_string(), "hash2".to_string());
+
+        let content = r#"#EXTM3U
+#EXT-X-VERSION:7
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="group_audio",NAME="audio_1",DEFAULT=YES,LANGUAGE="eng",URI="stream_1.m3u8"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="group_audio",NAME="audio_2",DEFAULT=NO,LANGUAGE="spa",URI="stream_2.m3u8"
+#EXT-X-STREAM-INF:BANDWIDTH=800000,RESOLUTION=640x360,AUDIO="group_audio"
+stream_0.m3u8
+"#;
+
+        let result = rewriter
+            .rewrite_master_playlist(content, &playlist_hashes)
+            .unwrap();
+
+        assert!(result.contains(r#"LANGUAGE="eng",URI="hash1.m3u8""#));
+        assert!(result.contains(r#"LANGUAGE="spa",URI="hash2.m3u8""#));
+        assert!(result.contains("\nhash0.m3u8\n"));
+        assert!(!result.contains("stream_"));
+    }
+
+    #[test]
+    fn test_lowest_variant() {
+        let master = "#EXTM3U\n\
+                      #EXT-X-VERSION:7\n\
+                      #EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"audio\",NAME=\"en\",DEFAULT=YES,URI=\"stream_3.m3u8\"\n\
+                      #EXT-X-STREAM-INF:BANDWIDTH=2000000,RESOLUTION=1280x720,AUDIO=\"audio\"\n\
+                      stream_0.m3u8\n\
+                      #EXT-X-STREAM-INF:BANDWIDTH=400000,RESOLUTION=426x240,AUDIO=\"audio\"\n\
+                      stream_1.m3u8\n\
+                      #EXT-X-STREAM-INF:BANDWIDTH=800000,RESOLUTION=640x360,AUDIO=\"audio\"\n\
+                      stream_2.m3u8\n";
+        let lowest = lowest_variant(master).unwrap();
+        assert_eq!(lowest.resolution, "240p");
+        assert_eq!(lowest.playlists, vec!["stream_1.m3u8", "stream_3.m3u8"]);
+        assert!(lowest.master.contains("#EXT-X-MEDIA:TYPE=AUDIO"));
+        assert!(lowest.master.contains("RESOLUTION=426x240"));
+        assert!(!lowest.master.contains("stream_0.m3u8"));
+        assert!(!lowest.master.contains("RESOLUTION=640x360"));
+
+        let single = "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1,RESOLUTION=640x360\nstream_0.m3u8\n";
+        assert_eq!(lowest_variant(single), None);
+
+        let portrait = "#EXTM3U\n\
+                        #EXT-X-STREAM-INF:BANDWIDTH=2000000,RESOLUTION=720x1280\n\
+                        stream_0.m3u8\n\
+                        #EXT-X-STREAM-INF:BANDWIDTH=800000,RESOLUTION=360x640\n\
+                        stream_1.m3u8\n";
+        let lowest = lowest_variant(portrait).unwrap();
+        assert_eq!(lowest.resolution, "360p");
+        assert_eq!(lowest.playlists, vec!["stream_1.m3u8"]);
+    }
+
+    #[test]
+    fn test_resolution_label() {
+        assert_eq!(resolution_label("1280x720").as_deref(), Some("720p"));
+        assert_eq!(resolution_label("1080x1920").as_deref(), Some("1080p"));
+        assert_eq!(resolution_label("1280"), None);
+    }
+
+    #[test]
+    fn test_set_frame_rates() {
+        let master = "#EXTM3U\n\
+                      #EXT-X-STREAM-INF:BANDWIDTH=800000,RESOLUTION=640x360,FRAME-RATE=120.000\n\
+                      stream_0.m3u8\n\
+                      #EXT-X-STREAM-INF:BANDWIDTH=2000000,RESOLUTION=1280x720\n\
+                      stream_1.m3u8\n\
+                      #EXT-X-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080,FRAME-RATE=120.000\n\
+                      stream_2.m3u8\n";
+        let rates = HashMap::from([
+            ("stream_0.m3u8".to_string(), 30.0),
+            ("stream_1.m3u8".to_string(), 59.94),
+        ]);
+        let out = set_frame_rates(master, &rates);
+        assert!(out.contains("RESOLUTION=640x360,FRAME-RATE=30.000\nstream_0.m3u8"));
+        assert!(out.contains("RESOLUTION=1280x720,FRAME-RATE=59.940\nstream_1.m3u8"));
+        assert!(out.contains("RESOLUTION=1920x1080,FRAME-RATE=120.000\nstream_2.m3u8"));
+        assert_eq!(out.matches("FRAME-RATE").count(), 3);
+    }
 }
@@ -0,0 +1,295 @@
+//! Scratch space prediction and scheduling.
+//!
+//! Each job predicts how much temp space it will need from the ffprobe
+//! bitrate (input + all renditions) and reserves it from a shared budget
+//! before encoding, so concurrent jobs never collectively overrun the temp
+//! disk and fail mid-encode with ENOSPC. Free space is re-measured at each
+//! reservation, so space eaten by other processes since startup is noticed
+//! before the encode starts rather than at 95%.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tracing::{debug, info};
+
+use crate::util::disk::disk_space;
+use crate::video::VideoMetadata;
+
+/// Headroom applied to every prediction (container overhead, VBR peaks)
+const SAFETY_FACTOR: f64 = 1.25;
+
+/// Assumed audio bitrate per rendition (matches the highest ladder rung)
+const AUDIO_BPS: f64 = 128_000.0;
+
+/// Fraction of free space usable when no explicit quota is configured
+const FREE_SPACE_FRACTION: f64 = 0.9;
+
+/// Predict the scratch bytes a job will write.
+///
+/// `renditions` holds the target height of each output (`None` for the
+/// original resolution). `input_on_disk` adds the source size for inputs
+/// that were downloaded into the temp dir rather than streamed by FFmpeg.
+///
+/// Returns `None` if the source bitrate or duration is unknown.
+pub fn estimate_scratch_bytes(
+    metadata: &VideoMetadata,
+    renditions: &[Option<u32>],
+    input_on_disk: bool,
+) -> Option<u64> {
+    let duration = metadata.duration_secs().filter(|d| *d > 0.0)?;
+    let source_bps = metadata.bit_rate_bps()? as f64;
+    let source_height = metadata.resolution().map(|(_, h)| h);
+
+    let mut bytes = 0.0;
+    for target in renditions {
+        let video_bps = match (target, source_height) {
+            // Bitrate scales roughly with pixel count
+            (Some(t), Some(s)) if *t < s => {
+                let ratio = *t as f64 / s as f64;
+                source_bps * ratio * ratio
+            }
+            _ => source_bps,
+        };
+        bytes += (video_bps + AUDIO_BPS) * duration / 8.0;
+    }
+
+    if input_on_disk {
+        bytes += metadata
+            .size_bytes()
+            .map(|s| s as f64)
+            .unwrap_or(source_bps * duration / 8.0);
+    }
+
+    Some((bytes * SAFETY_FACTOR) as u64)
+}
+
+/// Shared temp space budget for concurrently running jobs.
+pub struct ScratchBudget {
+    limit: u64,
+    /// Directory whose filesystem is re-checked for free space on each reservation
+    dir: Option<PathBuf>,
+    reserved: Mutex<u64>,
+    released: Notify,
+}
+
+impl ScratchBudget {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            dir: None,
+            reserved: Mutex::new(0),
+            released: Notify::new(),
+        }
+    }
+
+    /// Build a budget for the temp dir.
+    ///
+    /// The limit is the configured quota, capped at the currently free space
+    /// on the temp filesystem. Without either, scheduling is unrestricted.
+    pub fn for_temp_dir(temp_dir: &Path, quota: Option<u64>) -> Self {
+        let free = usable_space(temp_dir);
+
+        let limit = match (quota, free) {
+            (Some(q), Some(f)) => q.min(f),
+            (Some(q), None) => q,
+            (None, Some(f)) => f,
+            (None, None) => u64::MAX,
+        };
+
+        info!(limit_bytes = limit, quota_bytes = ?quota, "Scratch space budget");
+        Self {
+            dir: Some(temp_dir.to_path_buf()),
+            ..Self::new(limit)
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Bytes currently reserved by running jobs
+    pub fn reserved(&self) -> u64 {
+        *self.reserved.lock().unwrap()
+    }
+
+    /// Usable free space on the temp filesystem right now, if measurable
+    pub fn free_now(&self) -> Option<u64> {
+        self.dir.as_deref().and_then(usable_space)
+    }
+
+    /// Reserve `bytes` if they fit alongside existing reservations, both in
+    /// the budget and in the space currently free on disk. Reserved space is
+    /// counted against the disk too, since running jobs haven't written all
+    /// of it yet.
+    ///
+    /// Returns `None` if the budget or the disk is currently too full.
+    pub fn try_reserve(self: &Arc<Self>, bytes: u64) -> Option<ScratchReservation> {
+        let free = self.free_now();
+        let mut reserved = self.reserved.lock().unwrap();
+        let needed = reserved.saturating_add(bytes);
+        if needed > self.limit || free.is_some_and(|f| needed > f) {
+            return None;
+        }
+        *reserved += bytes;
+        debug!(bytes, total_reserved = *reserved, "Reserved scratch space");
+        Some(ScratchReservation {
+            budget: self.clone(),
+            bytes,
+        })
+    }
+
+    /// Wait until `bytes` fit in the budget, then reserve them.
+    ///
+    /// Returns `None` if the request exceeds the whole budget, or doesn't fit
+    /// on disk while no other job holds space that could be released.
+    pub async fn reserve(self: &Arc<Self>, bytes: u64) -> Option<ScratchReservation> {
+        if bytes > self.limit {
+            return None;
+        }
+        loop {
+            // Register for wakeups before checking to avoid missing a release
+            let notified = self.released.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if let Some(reservation) = self.try_reserve(bytes) {
+                return Some(reservation);
+            }
+            if self.reserved() == 0 {
+                // Nothing left to wait for; the disk is full with other data
+                return None;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Usable free bytes on the filesystem holding `dir`.
+fn usable_space(dir: &Path) -> Option<u64> {
+    // The temp dir may not exist yet; measure its nearest existing ancestor
+    dir.ancestors()
+        .find(|p| p.exists())
+        .and_then(disk_space)
+        .map(|s| (s.free_bytes as f64 * FREE_SPACE_FRACTION) as u64)
+}
+
+/// A held scratch reservation, released on drop.
+pub struct ScratchReservation {
+    budget: Arc<ScratchBudget>,
+    bytes: u64,
+}
+
+impl ScratchReservation {
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl Drop for ScratchReservation {
+    fn drop(&mut self) {
+        {
+            let mut reserved = self.budget.reserved.lock().unwrap();
+            *reserved = reserved.saturating_sub(self.bytes);
+        }
+        self.budget.released.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::{timeout, Duration};
+
+    #[test]
+    fn test_estimate_scales_with_renditions() {
+        // 100s at 8 Mbps = 100 MB source
+        let meta = VideoMetadata::builder()
+            .with_duration("100")
+            .with_bit_rate("8000000")
+            .with_video(1920, 1080)
+            .build();
+
+        let original = estimate_scratch_bytes(&meta, &[None], false).unwrap();
+        let ladder = estimate_scratch_bytes(&meta, &[None, Some(720), Some(360)], false).unwrap();
+        let with_input = estimate_scratch_bytes(&meta, &[None], true).unwrap();
+
+        // (8 Mbps + 128 kbps) * 100s / 8 * 1.25
+        assert_eq!(original, 127_000_000);
+        assert!(ladder > original);
+        assert!(ladder < original * 2);
+        assert_eq!(with_input, original + 125_000_000);
+    }
+
+    #[test]
+    fn test_estimate_unknown_bitrate() {
+        let meta = VideoMetadata::builder().build();
+        assert!(estimate_scratch_bytes(&meta, &[None], false).is_none());
+    }
+
+    #[test]
+    fn test_try_reserve_respects_limit() {
+        let budget = Arc::new(ScratchBudget::new(100));
+        let a = budget.try_reserve(60).unwrap();
+        assert!(budget.try_reserve(50).is_none());
+        assert_eq!(budget.reserved(), 60);
+        drop(a);
+        assert_eq!(budget.reserved(), 0);
+        assert!(budget.try_reserve(100).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_reserve_waits_for_release() {
+        let budget = Arc::new(ScratchBudget::new(100));
+        assert!(budget.reserve(101).await.is_none());
+
+        let held = budget.reserve(80).await.unwrap();
+        let waiter = {
+            let budget = budget.clone();
+            tokio::spawn(async move { budget.reserve(50).await.map(|r| r.bytes()) })
+        };
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        drop(held);
+        let bytes = timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+        assert_eq!(bytes, Some(50));
+    }
+
+    #[tokio::test]
+    async fn test_reserve_checks_live_free_space() {
+        let dir = std::env::temp_dir();
+        let Some(free) = usable_space(&dir).filter(|f| *f > 0) else {
+            return;
+        };
+        let budget = Arc::new(ScratchBudget {
+            dir: Some(dir),
+            ..ScratchBudget::new(u64::MAX)
+        });
+        assert!(budget.free_now().is_some());
+
+        // Within the budget but not on disk, and nothing to wait for
+        assert!(budget.try_reserve(free.saturating_mul(2)).is_none());
+        let result = timeout(Duration::from_secs(1), budget.reserve(free.saturating_mul(2)))
+            .await
+            .unwrap();
+        assert!(result.is_none());
+        assert!(budget.try_reserve(1).is_some());
+    }
+
+    #[test]
+    fn test_try_reserve_counts_reservations_against_free_space() {
+        let dir = std::env::temp_dir();
+        let Some(free) = usable_space(&dir).filter(|f| *f > 0) else {
+            return;
+        };
+        let budget = Arc::new(ScratchBudget {
+            dir: Some(dir),
+            ..ScratchBudget::new(u64::MAX)
+        });
+
+        // Each fits on disk alone, but not both
+        let _held = budget.try_reserve(free / 4 * 3).unwrap();
+        assert!(budget.try_reserve(free / 2).is_none());
+    }
+}
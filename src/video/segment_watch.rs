@@ -0,0 +1,153 @@
+//! Reporting HLS segments as FFmpeg finishes them.
+//!
+//! The HLS muxer writes segments one after another per variant stream, so a
+//! segment is complete once the next one of the same stream has been opened.
+//! The last segment of each stream is only known to be complete when FFmpeg
+//! exits, and is left to whoever collects the output afterwards.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tracing::debug;
+
+/// How often the output directory is scanned for new segments
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Run `encode`, sending each segment it finishes in `output_dir` to
+/// `finished` while it runs
+pub async fn while_encoding<F: Future>(
+    encode: F,
+    output_dir: &Path,
+    finished: Option<&mpsc::UnboundedSender<PathBuf>>,
+) -> F::Output {
+    let Some(finished) = finished else {
+        return encode.await;
+    };
+    tokio::pin!(encode);
+    tokio::select! {
+        output = &mut encode => output,
+        // Nobody is listening any more; just finish the encode
+        _ = watch_finished_segments(output_dir, finished) => encode.await,
+    }
+}
+
+/// Scan `dir` until `finished` is closed, sending every completed segment once
+async fn watch_finished_segments(dir: &Path, finished: &mpsc::UnboundedSender<PathBuf>) {
+    let mut sent = HashSet::new();
+    let mut ticker = tokio::time::interval(POLL_INTERVAL);
+    while !finished.is_closed() {
+        ticker.tick().await;
+        let Ok(mut entries) = tokio::fs::read_dir(dir).await else {
+            continue;
+        };
+        let mut names = Vec::new();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if let Some(name) = entry.file_name().to_str() {
+                names.push(name.to_string());
+            }
+        }
+        for name in completed_segments(&names) {
+            if sent.insert(name.clone()) {
+                debug!(segment = %name, "HLS segment finished");
+                let _ = finished.send(dir.join(name));
+            }
+        }
+    }
+}
+
+/// Segments in `names` followed by a later segment of the same stream
+fn completed_segments(names: &[String]) -> Vec<String> {
+    let segments: Vec<(&str, u32, &String)> = names
+        .iter()
+        .filter_map(|name| {
+            let (stream, index) = parse_segment_name(name)?;
+            Some((stream, index, name))
+        })
+        .collect();
+
+    let mut latest: HashMap<&str, u32> = HashMap::new();
+    for (stream, index, _) in &segments {
+        let entry = latest.entry(stream).or_insert(*index);
+        *entry = (*entry).max(*index);
+    }
+
+    let mut done: Vec<String> = segments
+        .into_iter()
+        .filter(|(stream, index, _)| latest.get(stream).is_some_and(|last| index < last))
+        .map(|(_, _, name)| name.clone())
+        .collect();
+    done.sort();
+    done
+}
+
+/// Split `stream_<v>_<n>.<m4s|ts>` into the stream and segment number
+fn parse_segment_name(name: &str) -> Option<(&str, u32)> {
+    let stem = name
+        .strip_suffix(".m4s")
+        .or_else(|| name.strip_suffix(".ts"))?;
+    let (stream, index) = stem.strip_prefix("stream_")?.rsplit_once('_')?;
+    Some((stream, index.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_segment_name() {
+        assert_eq!(parse_segment_name("stream_0_003.m4s"), Some(("0", 3)));
+        assert_eq!(parse_segment_name("stream_12_000.ts"), Some(("12", 0)));
+        assert_eq!(parse_segment_name("stream_0.m3u8"), None);
+        assert_eq!(parse_segment_name("init_0.mp4"), None);
+        assert_eq!(parse_segment_name("stream_0_003.m4s.tmp"), None);
+    }
+
+    #[test]
+    fn test_last_segment_per_stream_is_pending() {
+        let done = completed_segments(&names(&[
+            "stream_0_000.m4s",
+            "stream_0_001.m4s",
+            "stream_0_002.m4s",
+            "stream_1_000.m4s",
+            "stream_0.m3u8",
+            "init_0.mp4",
+        ]));
+        assert_eq!(done, names(&["stream_0_000.m4s", "stream_0_001.m4s"]));
+    }
+
+    #[tokio::test]
+    async fn test_while_encoding_reports_finished_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let out = dir.path().to_path_buf();
+        // Polls at 0s, 1s and 2s see 000, then 000-001, then 000-002
+        let encode = async move {
+            for (i, pause) in [500, 1000, 1000].into_iter().enumerate() {
+                std::fs::write(out.join(format!("stream_0_{:03}.ts", i)), "x").unwrap();
+                tokio::time::sleep(Duration::from_millis(pause)).await;
+            }
+            "done"
+        };
+
+        assert_eq!(while_encoding(encode, dir.path(), Some(&tx)).await, "done");
+        drop(tx);
+        let mut reported = Vec::new();
+        while let Some(path) = rx.recv().await {
+            reported.push(path);
+        }
+        assert_eq!(
+            reported,
+            vec![
+                dir.path().join("stream_0_000.ts"),
+                dir.path().join("stream_0_001.ts"),
+            ]
+        );
+    }
+}
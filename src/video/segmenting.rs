@@ -0,0 +1,122 @@
+//! HLS segment duration and keyframe spacing.
+//!
+//! Players switch renditions at segment boundaries, which only line up when
+//! every rung puts a keyframe at the same timestamps. Encoded rungs get their
+//! keyframes forced at fixed times (`-force_key_frames`) instead of wherever
+//! the encoder's scene detection puts them, and the segment duration has to be
+//! a whole number of keyframe intervals so every segment starts on one.
+
+/// Shortest segment duration a job may ask for, in seconds
+pub const MIN_HLS_TIME: u32 = 2;
+/// Longest segment duration a job may ask for, in seconds
+pub const MAX_HLS_TIME: u32 = 10;
+/// Segment duration when neither the job nor the operator set one
+pub const DEFAULT_HLS_TIME: u32 = 6;
+/// Keyframe spacing when neither the job nor the operator set one
+const DEFAULT_KEYFRAME_INTERVAL: f64 = 2.0;
+/// Shortest keyframe spacing a job may ask for, in seconds
+const MIN_KEYFRAME_INTERVAL: f64 = 0.5;
+
+/// Segment duration and keyframe spacing of an HLS encode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segmenting {
+    /// Target segment duration in seconds
+    pub hls_time: u32,
+    /// Seconds between forced keyframes
+    pub keyframe_interval: f64,
+}
+
+impl Default for Segmenting {
+    fn default() -> Self {
+        Self {
+            hls_time: DEFAULT_HLS_TIME,
+            keyframe_interval: DEFAULT_KEYFRAME_INTERVAL,
+        }
+    }
+}
+
+impl Segmenting {
+    /// Check a segment duration and keyframe interval.
+    ///
+    /// Without an interval, keyframes go every 2 seconds, or every second
+    /// when the segment duration is odd.
+    pub fn new(hls_time: Option<u32>, keyframe_interval: Option<f64>) -> Result<Self, String> {
+        let hls_time = hls_time.unwrap_or(DEFAULT_HLS_TIME);
+        if !(MIN_HLS_TIME..=MAX_HLS_TIME).contains(&hls_time) {
+            return Err(format!(
+                "hls_time must be between {} and {} seconds",
+                MIN_HLS_TIME, MAX_HLS_TIME
+            ));
+        }
+
+        let keyframe_interval = keyframe_interval.unwrap_or(if hls_time % 2 == 0 {
+            DEFAULT_KEYFRAME_INTERVAL
+        } else {
+            1.0
+        });
+        if !keyframe_interval.is_finite()
+            || keyframe_interval < MIN_KEYFRAME_INTERVAL
+            || keyframe_interval > hls_time as f64
+        {
+            return Err(format!(
+                "keyframe_interval must be between {} and {} seconds",
+                MIN_KEYFRAME_INTERVAL, hls_time
+            ));
+        }
+        let keyframes_per_segment = hls_time as f64 / keyframe_interval;
+        if (keyframes_per_segment - keyframes_per_segment.round()).abs() > 1e-6 {
+            return Err(format!(
+                "hls_time ({}s) must be a multiple of keyframe_interval ({}s)",
+                hls_time, keyframe_interval
+            ));
+        }
+
+        Ok(Self {
+            hls_time,
+            keyframe_interval,
+        })
+    }
+
+    /// `-force_key_frames` expression placing a keyframe every interval
+    pub fn force_key_frames(&self) -> String {
+        format!("expr:gte(t,n_forced*{})", self.keyframe_interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        assert_eq!(Segmenting::new(None, None), Ok(Segmenting::default()));
+        assert_eq!(Segmenting::new(Some(4), None).unwrap().keyframe_interval, 2.0);
+        // Odd durations can't be cut every 2 seconds
+        assert_eq!(Segmenting::new(Some(5), None).unwrap().keyframe_interval, 1.0);
+    }
+
+    #[test]
+    fn test_hls_time_range() {
+        assert!(Segmenting::new(Some(1), None).is_err());
+        assert!(Segmenting::new(Some(2), None).is_ok());
+        assert!(Segmenting::new(Some(10), None).is_ok());
+        assert!(Segmenting::new(Some(11), None).is_err());
+    }
+
+    #[test]
+    fn test_keyframe_interval() {
+        assert!(Segmenting::new(Some(6), Some(1.5)).is_ok());
+        assert!(Segmenting::new(Some(6), Some(6.0)).is_ok());
+        assert!(Segmenting::new(Some(6), Some(4.0)).is_err());
+        assert!(Segmenting::new(Some(6), Some(0.25)).is_err());
+        assert!(Segmenting::new(Some(4), Some(8.0)).is_err());
+        assert!(Segmenting::new(Some(4), Some(f64::NAN)).is_err());
+    }
+
+    #[test]
+    fn test_force_key_frames() {
+        let segmenting = Segmenting::new(Some(6), Some(1.5)).unwrap();
+        assert_eq!(segmenting.force_key_frames(), "expr:gte(t,n_forced*1.5)");
+        assert_eq!(Segmenting::default().force_key_frames(), "expr:gte(t,n_forced*2)");
+    }
+}
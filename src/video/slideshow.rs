@@ -0,0 +1,310 @@
+//! Videos assembled from archive inputs.
+//!
+//! An `archive` input is a ZIP of still images, optionally with an audio
+//! track and a `manifest.json`. The images become a slideshow (or, with a
+//! `frame_rate`, an image sequence played back frame by frame), which is
+//! rendered into an intermediate MP4 that then goes through the normal
+//! transcode pipeline.
+//!
+//! Manifest fields, all optional:
+//!
+//! ```json
+//! {
+//!   "slides": [{"image": "title.png", "duration": 5}, {"image": "01.jpg"}],
+//!   "slide_duration": 3,
+//!   "frame_rate": 24,
+//!   "audio": "music.mp3",
+//!   "width": 1920,
+//!   "height": 1080
+//! }
+//! ```
+//!
+//! Without `slides`, every image in the archive is used in file name order.
+
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tracing::debug;
+
+use crate::error::VideoError;
+
+/// Name of the optional manifest inside the archive
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+/// Seconds each image is shown when neither the slide nor the manifest says
+const DEFAULT_SLIDE_SECS: f64 = 3.0;
+/// Output frame rate of slideshows
+const DEFAULT_FPS: f64 = 30.0;
+const DEFAULT_SIZE: (u32, u32) = (1920, 1080);
+const MAX_DIMENSION: u32 = 7680;
+
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "bmp", "gif"];
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "m4a", "aac", "ogg", "opus", "wav", "flac"];
+
+#[derive(Debug, Default, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    slides: Vec<ManifestSlide>,
+    slide_duration: Option<f64>,
+    frame_rate: Option<f64>,
+    audio: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestSlide {
+    image: String,
+    duration: Option<f64>,
+}
+
+/// One image and how long it is shown
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slide {
+    pub path: PathBuf,
+    pub duration: f64,
+}
+
+/// Everything needed to render an archive into a video
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slideshow {
+    pub slides: Vec<Slide>,
+    pub audio: Option<PathBuf>,
+    pub fps: f64,
+    pub width: u32,
+    pub height: u32,
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| extensions.contains(&e.to_lowercase().as_str()))
+}
+
+fn invalid(msg: impl Into<String>) -> VideoError {
+    VideoError::InvalidInput(msg.into())
+}
+
+/// Positive, finite seconds
+fn check_duration(secs: f64, what: &str) -> Result<f64, VideoError> {
+    if secs.is_finite() && secs > 0.0 {
+        Ok(secs)
+    } else {
+        Err(invalid(format!("{} must be a positive number of seconds", what)))
+    }
+}
+
+impl Slideshow {
+    /// Plan the video from the files extracted from an archive.
+    ///
+    /// All files are expected in one directory, as left by
+    /// [`crate::util::zip::extract`]. Returns a user-facing error when the
+    /// archive has no images or the manifest doesn't match its contents.
+    pub fn plan(files: &[PathBuf]) -> Result<Self, VideoError> {
+        let find = |name: &str| files.iter().find(|f| f.file_name().is_some_and(|n| n == name));
+
+        let manifest: Manifest = match find(MANIFEST_NAME) {
+            Some(path) => serde_json::from_slice(&std::fs::read(path)?)
+                .map_err(|e| invalid(format!("Invalid {}: {}", MANIFEST_NAME, e)))?,
+            None => Manifest::default(),
+        };
+
+        let default_duration = match (manifest.frame_rate, manifest.slide_duration) {
+            (Some(rate), _) => 1.0 / check_duration(rate, "frame_rate")?,
+            (None, Some(secs)) => check_duration(secs, "slide_duration")?,
+            (None, None) => DEFAULT_SLIDE_SECS,
+        };
+
+        let slides = if manifest.slides.is_empty() {
+            let mut images: Vec<&PathBuf> = files
+                .iter()
+                .filter(|f| has_extension(f, IMAGE_EXTENSIONS))
+                .collect();
+            images.sort();
+            images
+                .into_iter()
+                .map(|path| Slide {
+                    path: path.clone(),
+                    duration: default_duration,
+                })
+                .collect()
+        } else {
+            manifest
+                .slides
+                .iter()
+                .map(|slide| {
+                    let path = find(&slide.image)
+                        .filter(|p| has_extension(p, IMAGE_EXTENSIONS))
+                        .ok_or_else(|| invalid(format!("Archive has no image {}", slide.image)))?;
+                    let duration = match slide.duration {
+                        Some(secs) => check_duration(secs, "Slide duration")?,
+                        None => default_duration,
+                    };
+                    Ok(Slide {
+                        path: path.clone(),
+                        duration,
+                    })
+                })
+                .collect::<Result<Vec<_>, VideoError>>()?
+        };
+        if slides.is_empty() {
+            return Err(invalid("Archive contains no images"));
+        }
+
+        let audio = match &manifest.audio {
+            Some(name) => Some(
+                find(name)
+                    .filter(|p| has_extension(p, AUDIO_EXTENSIONS))
+                    .ok_or_else(|| invalid(format!("Archive has no audio file {}", name)))?
+                    .clone(),
+            ),
+            None => {
+                let mut audio: Vec<&PathBuf> = files
+                    .iter()
+                    .filter(|f| has_extension(f, AUDIO_EXTENSIONS))
+                    .collect();
+                audio.sort();
+                audio.first().map(|p| (*p).clone())
+            }
+        };
+
+        let (width, height) = match (manifest.width, manifest.height) {
+            (None, None) => DEFAULT_SIZE,
+            (Some(w), Some(h)) if (16..=MAX_DIMENSION).contains(&w) && (16..=MAX_DIMENSION).contains(&h) => {
+                // Encoders need even dimensions for 4:2:0 chroma
+                (w & !1, h & !1)
+            }
+            _ => {
+                return Err(invalid(format!(
+                    "Manifest width and height must both be set, between 16 and {}",
+                    MAX_DIMENSION
+                )))
+            }
+        };
+
+        Ok(Self {
+            slides,
+            audio,
+            fps: manifest.frame_rate.unwrap_or(DEFAULT_FPS),
+            width,
+            height,
+        })
+    }
+
+    /// Total length of the video in seconds
+    pub fn duration_secs(&self) -> f64 {
+        self.slides.iter().map(|s| s.duration).sum()
+    }
+
+    /// Playlist for FFmpeg's concat demuxer
+    fn concat_list(&self) -> String {
+        let quote = |path: &Path| path.to_string_lossy().replace('\'', "'\\''");
+        let mut list = String::from("ffconcat version 1.0\n");
+        for slide in &self.slides {
+            list.push_str(&format!("file '{}'\nduration {:.6}\n", quote(&slide.path), slide.duration));
+        }
+        // The last duration is only honored when the file is listed again
+        if let Some(last) = self.slides.last() {
+            list.push_str(&format!("file '{}'\n", quote(&last.path)));
+        }
+        list
+    }
+
+    /// Render the slideshow into `dir`, returning the MP4 path.
+    ///
+    /// The intermediate is encoded at high quality in software; the job's
+    /// own transcode settings are applied afterwards.
+    pub async fn render(&self, ffmpeg_path: &Path, dir: &Path) -> Result<PathBuf, VideoError> {
+        let list_path = dir.join("slides.ffconcat");
+        tokio::fs::write(&list_path, self.concat_list()).await?;
+        let output = dir.join("assembled.mp4");
+
+        let (w, h) = (self.width, self.height);
+        let filter = format!(
+            "scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={},format=yuv420p",
+            self.fps
+        );
+
+        let mut cmd = Command::new(ffmpeg_path);
+        cmd.kill_on_drop(true);
+        cmd.args(["-nostdin", "-hide_banner", "-y"])
+            .args(["-protocol_whitelist", "file", "-f", "concat", "-safe", "0", "-i"])
+            .arg(&list_path);
+        if let Some(audio) = &self.audio {
+            cmd.arg("-i").arg(audio).args(["-map", "0:v", "-map", "1:a"]);
+        }
+        cmd.args(["-vf", &filter])
+            .args(["-c:v", "libx264", "-preset", "veryfast", "-crf", "18"]);
+        if self.audio.is_some() {
+            cmd.args(["-c:a", "aac", "-b:a", "192k"]);
+        }
+        cmd.arg("-t")
+            .arg(format!("{:.3}", self.duration_secs()))
+            .args(["-movflags", "+faststart"])
+            .arg(&output);
+
+        debug!(slides = self.slides.len(), audio = self.audio.is_some(), "Assembling archive input");
+        let result = cmd.output().await?;
+        if !result.status.success() {
+            return Err(crate::video::ffmpeg_error::failure(
+                "Assembling archive input failed",
+                &String::from_utf8_lossy(&result.stderr),
+            ));
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(dir: &Path, names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(|n| dir.join(n)).collect()
+    }
+
+    #[test]
+    fn test_plan_without_manifest() {
+        let dir = Path::new("/tmp/archive");
+        let show = Slideshow::plan(&files(dir, &["b.png", "a.JPG", "notes.txt", "z.mp3"])).unwrap();
+        assert_eq!(show.slides.len(), 2);
+        assert_eq!(show.slides[0].path, dir.join("a.JPG"));
+        assert_eq!(show.audio, Some(dir.join("z.mp3")));
+        assert_eq!(show.duration_secs(), 2.0 * DEFAULT_SLIDE_SECS);
+        assert_eq!((show.width, show.height), DEFAULT_SIZE);
+
+        assert!(Slideshow::plan(&files(dir, &["notes.txt"])).is_err());
+    }
+
+    #[test]
+    fn test_plan_with_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join(MANIFEST_NAME);
+        let names = ["01.jpg", "02.jpg", "voice.m4a", "music.mp3", MANIFEST_NAME];
+
+        std::fs::write(
+            &manifest,
+            r#"{"slides": [{"image": "02.jpg", "duration": 5}, {"image": "01.jpg"}],
+                "slide_duration": 2, "audio": "voice.m4a", "width": 1081, "height": 1920}"#,
+        )
+        .unwrap();
+        let show = Slideshow::plan(&files(dir.path(), &names)).unwrap();
+        assert_eq!(show.slides[0].path, dir.path().join("02.jpg"));
+        assert_eq!(show.duration_secs(), 7.0);
+        assert_eq!(show.audio, Some(dir.path().join("voice.m4a")));
+        assert_eq!((show.width, show.height), (1080, 1920));
+
+        let list = show.concat_list();
+        assert!(list.starts_with("ffconcat version 1.0\n"));
+        assert!(list.ends_with(&format!("file '{}'\n", dir.path().join("01.jpg").display())));
+
+        // An image sequence plays one image per frame
+        std::fs::write(&manifest, r#"{"frame_rate": 25}"#).unwrap();
+        let show = Slideshow::plan(&files(dir.path(), &names)).unwrap();
+        assert_eq!(show.fps, 25.0);
+        assert_eq!(show.slides[1].duration, 0.04);
+
+        std::fs::write(&manifest, r#"{"slides": [{"image": "03.jpg"}]}"#).unwrap();
+        assert!(Slideshow::plan(&files(dir.path(), &names)).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+//! Seek thumbnails ("storyboards") for scrub previews.
+//!
+//! Players show a small frame above the seek bar while the viewer scrubs.
+//! Frames are taken at a fixed interval, tiled into JPEG sprite sheets, and
+//! indexed by a WebVTT track whose cues point at a region of a sheet
+//! (`<sheet>#xywh=x,y,w,h`), the format video.js, Vidstack and Plyr read.
+
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tracing::debug;
+
+use crate::error::VideoError;
+use crate::video::clip::Clip;
+
+/// Longer side of a thumbnail in pixels
+const TILE_SIZE: u32 = 160;
+/// Thumbnails per sheet row
+const COLUMNS: u32 = 10;
+/// Rows per sheet
+const ROWS: u32 = 10;
+/// Thumbnails a video gets when the interval allows
+const TARGET_TILES: f64 = 100.0;
+/// Most thumbnails of any video; long videos get a longer interval instead
+const MAX_TILES: f64 = 500.0;
+/// Longest interval short of [`MAX_TILES`], in seconds
+const MAX_INTERVAL: f64 = 10.0;
+
+/// Layout of the thumbnails of one video
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Storyboard {
+    /// Seconds between thumbnails
+    pub interval: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    /// Length of the covered video in seconds
+    pub duration: f64,
+}
+
+impl Storyboard {
+    /// Layout for a video `duration` seconds long with upright size
+    /// `dimensions` (16:9 when unknown).
+    ///
+    /// Thumbnails go every second for short videos and up to every 10
+    /// seconds for longer ones, further apart only past 500 thumbnails.
+    pub fn new(duration: f64, dimensions: Option<(u32, u32)>) -> Self {
+        let duration = duration.max(0.0);
+        let interval = (duration / TARGET_TILES)
+            .ceil()
+            .clamp(1.0, MAX_INTERVAL)
+            .max((duration / MAX_TILES).ceil());
+
+        let (w, h) = dimensions.filter(|&(w, h)| w > 0 && h > 0).unwrap_or((16, 9));
+        let even = |x: f64| ((x / 2.0).round() as u32).max(1) * 2;
+        let (tile_width, tile_height) = if w >= h {
+            (TILE_SIZE, even(TILE_SIZE as f64 * h as f64 / w as f64))
+        } else {
+            (even(TILE_SIZE as f64 * w as f64 / h as f64), TILE_SIZE)
+        };
+
+        Self {
+            interval: interval as u32,
+            tile_width,
+            tile_height,
+            duration,
+        }
+    }
+
+    /// Number of thumbnails
+    pub fn count(&self) -> u32 {
+        ((self.duration / self.interval as f64).ceil() as u32).max(1)
+    }
+
+    /// Size of a thumbnail (`WIDTHxHEIGHT`)
+    pub fn dim(&self) -> String {
+        format!("{}x{}", self.tile_width, self.tile_height)
+    }
+
+    /// Filter chain sampling, scaling and tiling the frames. The `tile` filter
+    /// emits a sheet whenever one fills up, and the partial last one at the end.
+    fn filter(&self) -> String {
+        format!(
+            "fps=1/{},scale={}:{}:flags=lanczos,tile={}x{}",
+            self.interval, self.tile_width, self.tile_height, COLUMNS, ROWS
+        )
+    }
+
+    /// WebVTT track pointing every thumbnail at its region of a sheet.
+    /// `sheets` are the URLs (or names) of the sheets in order; thumbnails
+    /// past the last sheet are left out.
+    pub fn vtt(&self, sheets: &[String]) -> String {
+        let per_sheet = COLUMNS * ROWS;
+        let mut out = String::from("WEBVTT\n");
+        for i in 0..self.count() {
+            let Some(sheet) = sheets.get((i / per_sheet) as usize) else {
+                break;
+            };
+            let tile = i % per_sheet;
+            let start = (i * self.interval) as f64;
+            let end = (start + self.interval as f64).min(self.duration.max(start));
+            out.push_str(&format!(
+                "\n{} --> {}\n{}#xywh={},{},{},{}\n",
+                vtt_timestamp(start),
+                vtt_timestamp(end),
+                sheet,
+                (tile % COLUMNS) * self.tile_width,
+                (tile / COLUMNS) * self.tile_height,
+                self.tile_width,
+                self.tile_height
+            ));
+        }
+        out
+    }
+
+    /// Render the sheets of `input` (or of the clipped part) into `dir`,
+    /// returning them in order
+    pub async fn render(
+        &self,
+        ffmpeg_path: &Path,
+        input: &str,
+        clip: Option<Clip>,
+        dir: &Path,
+    ) -> Result<Vec<PathBuf>, VideoError> {
+        let mut cmd = Command::new(ffmpeg_path);
+        cmd.kill_on_drop(true);
+        cmd.arg("-y").arg("-nostdin").arg("-loglevel").arg("error");
+        if let Some(clip) = clip {
+            cmd.args(clip.input_args());
+        }
+        cmd.arg("-i")
+            .arg(input)
+            .arg("-map")
+            .arg("0:v:0")
+            .arg("-vf")
+            .arg(self.filter())
+            .arg("-q:v")
+            .arg("5")
+            .arg(dir.join("sheet_%03d.jpg"));
+
+        debug!(input = %input, interval = self.interval, "Rendering storyboard");
+        let output = cmd.output().await.map_err(VideoError::Io)?;
+        if !output.status.success() {
+            return Err(crate::video::ffmpeg_error::failure(
+                "Storyboard generation failed",
+                &String::from_utf8_lossy(&output.stderr),
+            ));
+        }
+
+        let mut sheets = Vec::new();
+        let mut entries = tokio::fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_some_and(|e| e == "jpg") {
+                sheets.push(path);
+            }
+        }
+        sheets.sort();
+        if sheets.is_empty() {
+            return Err(VideoError::FfmpegFailed(
+                "Storyboard generation produced no sheets".to_string(),
+            ));
+        }
+        Ok(sheets)
+    }
+}
+
+/// `HH:MM:SS.mmm` WebVTT cue timestamp
+pub fn vtt_timestamp(secs: f64) -> String {
+    let ms = (secs * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interval() {
+        assert_eq!(Storyboard::new(20.0, None).interval, 1);
+        assert_eq!(Storyboard::new(600.0, None).interval, 6);
+        assert_eq!(Storyboard::new(3600.0, None).interval, 10);
+        // Two hours would be 720 thumbnails at 10 seconds
+        let long = Storyboard::new(7200.0, None);
+        assert_eq!(long.interval, 15);
+        assert_eq!(long.count(), 480);
+        assert_eq!(Storyboard::new(0.0, None).count(), 1);
+    }
+
+    #[test]
+    fn test_tile_size() {
+        assert_eq!(Storyboard::new(60.0, Some((1920, 1080))).dim(), "160x90");
+        assert_eq!(Storyboard::new(60.0, Some((1080, 1920))).dim(), "90x160");
+        assert_eq!(Storyboard::new(60.0, Some((640, 480))).dim(), "160x120");
+        assert_eq!(Storyboard::new(60.0, None).dim(), "160x90");
+    }
+
+    #[test]
+    fn test_filter() {
+        let storyboard = Storyboard::new(600.0, Some((1920, 1080)));
+        assert_eq!(
+            storyboard.filter(),
+            "fps=1/6,scale=160:90:flags=lanczos,tile=10x10"
+        );
+    }
+
+    #[test]
+    fn test_vtt() {
+        let storyboard = Storyboard::new(597.0, Some((1920, 1080)));
+        assert_eq!(storyboard.interval, 6);
+        assert_eq!(storyboard.count(), 100);
+
+        let vtt = storyboard.vtt(&["a.jpg".to_string()]);
+        assert!(vtt.starts_with("WEBVTT\n\n00:00:00.000 --> 00:00:06.000\na.jpg#xywh=0,0,160,90\n"));
+        // 12th thumbnail: second row, second column
+        assert!(vtt.contains("\n00:01:06.000 --> 00:01:12.000\na.jpg#xywh=160,90,160,90\n"));
+        // The last cue ends with the video
+        assert!(vtt.ends_with("\n00:09:54.000 --> 00:09:57.000\na.jpg#xywh=1440,810,160,90\n"));
+    }
+
+    #[test]
+    fn test_vtt_spans_sheets() {
+        let storyboard = Storyboard::new(1500.0, None);
+        assert_eq!(storyboard.count(), 150);
+
+        let sheets = ["a.jpg".to_string(), "b.jpg".to_string()];
+        let vtt = storyboard.vtt(&sheets);
+        assert!(vtt.contains("\n00:16:40.000 --> 00:16:50.000\nb.jpg#xywh=0,0,160,90\n"));
+        assert_eq!(vtt.matches("#xywh=").count(), 150);
+        // Thumbnails without a sheet are left out
+        assert_eq!(storyboard.vtt(&sheets[..1]).matches("#xywh=").count(), 100);
+    }
+
+    #[test]
+    fn test_vtt_timestamp() {
+        assert_eq!(vtt_timestamp(0.0), "00:00:00.000");
+        assert_eq!(vtt_timestamp(3725.5), "01:02:05.500");
+    }
+}
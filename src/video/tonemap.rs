@@ -0,0 +1,166 @@
+//! Tone-mapping HDR sources down to SDR renditions.
+//!
+//! Phone footage is often HDR10 (PQ) or HLG. Encoding it as 8-bit SDR without
+//! converting the colors leaves the output flat and washed out, so HDR
+//! sources go through a tone-mapping filter before scaling. The conversion
+//! runs on CPU frames with zscale (FFmpeg built with libzimg), except for
+//! HDR10 on VAAPI, which has its own `tonemap_vaapi` filter.
+
+use crate::video::hwaccel::HwAccel;
+use crate::video::metadata::StreamInfo;
+
+/// Software chain converting linear light to BT.709 SDR
+const SW_TONEMAP: &str = "format=gbrpf32le,zscale=p=bt709,tonemap=tonemap=hable:desat=0,zscale=t=bt709:m=bt709:r=tv,format=yuv420p";
+
+/// VAAPI filter converting HDR10 frames in GPU memory to BT.709 NV12
+const VAAPI_TONEMAP: &str = "tonemap_vaapi=format=nv12:t=bt709:m=bt709:p=bt709";
+
+/// Transfer function of an HDR source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HdrFormat {
+    /// HDR10 / Dolby Vision base layer (SMPTE ST 2084)
+    Pq,
+    /// Hybrid Log-Gamma (ARIB STD-B67)
+    Hlg,
+}
+
+impl HdrFormat {
+    /// Recognize an ffprobe `color_transfer` value
+    pub fn from_transfer(transfer: &str) -> Option<Self> {
+        match transfer.trim() {
+            "smpte2084" => Some(Self::Pq),
+            "arib-std-b67" => Some(Self::Hlg),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pq => "HDR10",
+            Self::Hlg => "HLG",
+        }
+    }
+
+    /// zscale name of the transfer function
+    fn zscale_transfer(&self) -> &'static str {
+        match self {
+            Self::Pq => "smpte2084",
+            Self::Hlg => "arib-std-b67",
+        }
+    }
+}
+
+/// What to do with an HDR source, from the `hdr` param
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HdrMode {
+    /// Tone-map every rendition to SDR
+    #[default]
+    Tonemap,
+    /// Tone-map the encoded rungs but also keep the original, untouched, as
+    /// an HDR rung (HLS only)
+    Keep,
+}
+
+impl HdrMode {
+    /// Parse from string. Returns `None` for unrecognized values.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "tonemap" | "sdr" => Some(Self::Tonemap),
+            "keep" | "passthrough" => Some(Self::Keep),
+            _ => None,
+        }
+    }
+}
+
+/// Tone-mapping applied to an HDR source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tonemap {
+    pub source: HdrFormat,
+    /// Copy the original into the HLS ladder as an HDR rung
+    pub keep_original: bool,
+}
+
+impl Tonemap {
+    /// Tone-mapping for `stream`, or `None` when it isn't HDR
+    pub fn for_stream(stream: &StreamInfo, mode: HdrMode) -> Option<Self> {
+        let source = stream.color_transfer.as_deref().and_then(HdrFormat::from_transfer)?;
+        Some(Self {
+            source,
+            keep_original: mode == HdrMode::Keep,
+        })
+    }
+
+    /// Whether the conversion runs on CPU frames, i.e. the source has to be
+    /// decoded in software
+    pub fn needs_sw_frames(&self, hwaccel: HwAccel) -> bool {
+        self.hw_filter(hwaccel).is_none()
+    }
+
+    /// Filter tone-mapping frames already uploaded to `hwaccel`'s device
+    pub fn hw_filter(&self, hwaccel: HwAccel) -> Option<&'static str> {
+        match (hwaccel, self.source) {
+            (HwAccel::Vaapi, HdrFormat::Pq) => Some(VAAPI_TONEMAP),
+            _ => None,
+        }
+    }
+
+    /// Filter chain tone-mapping CPU frames to 8-bit BT.709
+    pub fn sw_filter(&self) -> String {
+        format!(
+            "zscale=tin={}:min=bt2020nc:pin=bt2020:t=linear:npl=100,{}",
+            self.source.zscale_transfer(),
+            SW_TONEMAP
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(transfer: Option<&str>) -> StreamInfo {
+        serde_json::from_value(serde_json::json!({
+            "codec_type": "video",
+            "codec_name": "hevc",
+            "color_transfer": transfer,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_detects_hdr_sources() {
+        let hdr10 = Tonemap::for_stream(&stream(Some("smpte2084")), HdrMode::default()).unwrap();
+        assert_eq!(hdr10.source, HdrFormat::Pq);
+        assert!(!hdr10.keep_original);
+
+        let hlg = Tonemap::for_stream(&stream(Some("arib-std-b67")), HdrMode::Keep).unwrap();
+        assert_eq!(hlg.source, HdrFormat::Hlg);
+        assert!(hlg.keep_original);
+
+        assert_eq!(Tonemap::for_stream(&stream(Some("bt709")), HdrMode::Tonemap), None);
+        assert_eq!(Tonemap::for_stream(&stream(None), HdrMode::Tonemap), None);
+    }
+
+    #[test]
+    fn test_mode_parse() {
+        assert_eq!(HdrMode::parse("keep"), Some(HdrMode::Keep));
+        assert_eq!(HdrMode::parse(" SDR "), Some(HdrMode::Tonemap));
+        assert_eq!(HdrMode::parse("hdr"), None);
+    }
+
+    #[test]
+    fn test_filters() {
+        let hdr10 = Tonemap { source: HdrFormat::Pq, keep_original: false };
+        let hlg = Tonemap { source: HdrFormat::Hlg, keep_original: false };
+
+        assert!(hdr10.sw_filter().starts_with("zscale=tin=smpte2084:"));
+        assert!(hlg.sw_filter().starts_with("zscale=tin=arib-std-b67:"));
+        assert!(hlg.sw_filter().ends_with("format=yuv420p"));
+
+        assert_eq!(hdr10.hw_filter(HwAccel::Vaapi), Some(VAAPI_TONEMAP));
+        assert!(!hdr10.needs_sw_frames(HwAccel::Vaapi));
+        // tonemap_vaapi only handles HDR10
+        assert!(hlg.needs_sw_frames(HwAccel::Vaapi));
+        assert!(hdr10.needs_sw_frames(HwAccel::Nvenc));
+    }
+}
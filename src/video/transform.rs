@@ -1,18 +1,35 @@
 use base64::{engine::general_purpose::STANDARD, Engine};
 use rand::RngCore;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use tokio::fs;
-use tracing::{debug, info};
+use tokio::sync::{mpsc, Notify};
+use tracing::{debug, info, warn};
 
 use crate::config::Config;
+use crate::dvm::encode_plan::EncodePlan;
 use crate::dvm::events::{Codec, Resolution};
+use crate::dvm::job_log::JobLog;
 use crate::error::VideoError;
+use crate::util::blurhash;
+use crate::util::hash::hash_files;
 use crate::util::TempDir;
-use crate::video::ffmpeg::{FfmpegCommand, FfmpegMp4Command};
+use crate::video::ffmpeg::{
+    FfmpegCommand, FfmpegMp4Command, FfmpegPreviewCommand, FfmpegRawFrameCommand,
+    FfmpegThumbnailCommand,
+};
+use crate::video::gop::GopLayout;
+use crate::video::gpu::{GpuLease, GpuScheduler};
 use crate::video::hwaccel::HwAccel;
-use crate::video::playlist::ENCRYPTION_KEY_PLACEHOLDER_URI;
+use crate::video::segment_watch::while_encoding;
+use crate::video::segmenting::Segmenting;
+use crate::video::output_size::CRF_HALVING_STEP;
+use crate::video::metadata::VideoMetadata;
+use crate::video::playlist::{set_frame_rates, ENCRYPTION_KEY_PLACEHOLDER_URI};
+use crate::video::fps_cap::FpsCap;
 
 /// Generate a random 16-byte AES-128 encryption key
 pub fn generate_aes_key() -> [u8; 16] {
@@ -21,12 +38,334 @@ pub fn generate_aes_key() -> [u8; 16] {
     key
 }
 
+/// Default AAC bitrate per HLS rendition, so low rungs don't spend their
+/// bandwidth on audio. Passthrough originals keep FFmpeg's default.
+pub const DEFAULT_AUDIO_BITRATES: &[(&str, &str)] = &[
+    ("240p", "64k"),
+    ("360p", "96k"),
+    ("480p", "128k"),
+    ("720p", "128k"),
+    ("1080p", "128k"),
+];
+
+/// Placeholder output directory (under the temp dir) of dry-run command lines
+const DRY_RUN_DIR: &str = "dry-run";
+
+/// Side of the downscaled frame blurhashes are computed from
+const BLURHASH_SIZE: u32 = 32;
+
+/// Accepted range for requested audio bitrates, in kbit/s
+const MIN_AUDIO_KBPS: u32 = 32;
+const MAX_AUDIO_KBPS: u32 = 320;
+
+fn default_audio_bitrate(label: &str) -> Option<String> {
+    DEFAULT_AUDIO_BITRATES
+        .iter()
+        .find(|(l, _)| *l == label)
+        .map(|(_, br)| br.to_string())
+}
+
+/// Parse a requested audio bitrate ("96k" or "96000") into FFmpeg's "96k" form.
+/// Returns `None` for malformed values or values outside 32k-320k.
+pub fn parse_audio_bitrate(s: &str) -> Option<String> {
+    let s = s.trim().to_lowercase();
+    let kbps = match s.strip_suffix('k') {
+        Some(k) => k.parse::<u32>().ok()?,
+        None => s.parse::<u32>().ok()? / 1000,
+    };
+    (MIN_AUDIO_KBPS..=MAX_AUDIO_KBPS)
+        .contains(&kbps)
+        .then(|| format!("{}k", kbps))
+}
+
+/// Built-in ladder as (height, CRF), used unless the operator configures one
+const BUILTIN_LADDER: &[(u32, u32)] = &[(240, 33), (360, 31), (480, 29), (720, 26), (1080, 23)];
+
+fn builtin_ladder() -> Vec<LadderRung> {
+    BUILTIN_LADDER
+        .iter()
+        .map(|&(height, crf)| LadderRung {
+            height,
+            crf: Some(crf),
+            video_bitrate: None,
+            audio_bitrate: None,
+        })
+        .collect()
+}
+
+/// Most rungs an operator-defined ladder may have
+pub const MAX_LADDER_RUNGS: usize = 8;
+
+/// Accepted range for ladder rung heights, in pixels
+const MIN_RUNG_HEIGHT: u32 = 144;
+const MAX_RUNG_HEIGHT: u32 = 4320;
+
+/// Accepted range for ladder video bitrates, in kbit/s
+const MIN_VIDEO_KBPS: u32 = 100;
+const MAX_VIDEO_KBPS: u32 = 100_000;
+
+/// One encoded rendition of an operator-defined bitrate ladder.
+///
+/// Rungs at a standard height (240, 360, 480, 720, 1080) follow the
+/// requester's resolution selection; other heights are always encoded when
+/// the input is tall enough.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LadderRung {
+    /// Output height in pixels (width follows the source aspect ratio)
+    pub height: u32,
+    /// Constant-quality value on the x264 CRF scale (0-51)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crf: Option<u32>,
+    /// Target video bitrate ("2500k", "2.5M"), used instead of `crf`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub video_bitrate: Option<String>,
+    /// AAC bitrate ("96k"); defaults to the built-in value for the height
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_bitrate: Option<String>,
+}
+
+impl LadderRung {
+    /// Rendition label, e.g. "540p"
+    pub fn label(&self) -> String {
+        format!("{}p", self.height)
+    }
+
+    /// Check the rung and normalize its bitrates into FFmpeg's "Nk" form.
+    pub fn validate(&self) -> Result<Self, String> {
+        let label = self.label();
+        if !(MIN_RUNG_HEIGHT..=MAX_RUNG_HEIGHT).contains(&self.height) || self.height % 2 != 0 {
+            return Err(format!(
+                "{}: height must be an even number between {} and {}",
+                label, MIN_RUNG_HEIGHT, MAX_RUNG_HEIGHT
+            ));
+        }
+        if self.crf.is_some_and(|crf| crf > 51) {
+            return Err(format!("{}: crf must be between 0 and 51", label));
+        }
+        if self.crf.is_some() == self.video_bitrate.is_some() {
+            return Err(format!("{}: set either crf or video_bitrate", label));
+        }
+        let video_bitrate = match &self.video_bitrate {
+            Some(br) => Some(parse_video_bitrate(br).ok_or_else(|| {
+                format!(
+                    "{}: invalid video_bitrate '{}' (expected {}k-{}k)",
+                    label, br, MIN_VIDEO_KBPS, MAX_VIDEO_KBPS
+                )
+            })?),
+            None => None,
+        };
+        let audio_bitrate = match &self.audio_bitrate {
+            Some(br) => Some(parse_audio_bitrate(br).ok_or_else(|| {
+                format!(
+                    "{}: invalid audio_bitrate '{}' (expected {}k-{}k)",
+                    label, br, MIN_AUDIO_KBPS, MAX_AUDIO_KBPS
+                )
+            })?),
+            None => None,
+        };
+        Ok(Self {
+            height: self.height,
+            crf: self.crf,
+            video_bitrate,
+            audio_bitrate,
+        })
+    }
+}
+
+/// The ladder (the built-in one if empty) lowered by `crf_offset` quality
+/// steps: CRF rungs are raised by it, and bitrate rungs scaled down by the
+/// same factor (six steps halve the bitrate).
+pub fn ladder_with_crf_offset(ladder: &[LadderRung], crf_offset: u32) -> Vec<LadderRung> {
+    let rungs = if ladder.is_empty() {
+        builtin_ladder()
+    } else {
+        ladder.to_vec()
+    };
+    let scale = 0.5f64.powf(crf_offset as f64 / CRF_HALVING_STEP as f64);
+    rungs
+        .into_iter()
+        .map(|rung| LadderRung {
+            crf: rung.crf.map(|crf| (crf + crf_offset).min(51)),
+            video_bitrate: rung.video_bitrate.as_deref().map(|br| {
+                let kbps = br.trim_end_matches('k').parse::<f64>().unwrap_or(MIN_VIDEO_KBPS as f64);
+                format!("{}k", ((kbps * scale) as u32).max(MIN_VIDEO_KBPS))
+            }),
+            ..rung
+        })
+        .collect()
+}
+
+/// Validate an operator-defined ladder, returning the rungs normalized and
+/// sorted by height. An empty ladder selects the built-in one.
+pub fn validate_ladder(rungs: &[LadderRung]) -> Result<Vec<LadderRung>, String> {
+    if rungs.len() > MAX_LADDER_RUNGS {
+        return Err(format!(
+            "bitrate_ladder has {} rungs (max {})",
+            rungs.len(),
+            MAX_LADDER_RUNGS
+        ));
+    }
+    let mut ladder = rungs
+        .iter()
+        .map(LadderRung::validate)
+        .collect::<Result<Vec<_>, _>>()?;
+    ladder.sort_by_key(|r| r.height);
+    if let Some(pair) = ladder.windows(2).find(|w| w[0].height == w[1].height) {
+        return Err(format!("bitrate_ladder has duplicate rung {}", pair[0].label()));
+    }
+    Ok(ladder)
+}
+
+/// CRF for MP4 outputs without a quality preset, tuned for streaming
+/// (~30% below CRF 23)
+pub const MP4_CRF: u32 = 26;
+
+/// CRF for an original that can't be passed through without a quality preset
+pub const ORIGINAL_CRF: u32 = 21;
+
+/// Most entries `quality_presets` may have
+pub const MAX_QUALITY_PRESETS: usize = 32;
+
+/// Operator-tuned quality for one resolution, optionally for one codec only.
+///
+/// Presets take precedence over the CRF of ladder rungs (built-in or
+/// operator-defined) and apply to MP4 outputs of the same resolution.
+/// Bitrate rungs are left alone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QualityPreset {
+    /// Rendition label ("720p", "540p") or "original" for a re-encoded original
+    pub resolution: String,
+    /// Codec the preset is limited to ("h264", "h265", "av1", "vp9"); all when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codec: Option<String>,
+    /// Constant-quality value on the x264 CRF scale (0-51)
+    pub crf: u32,
+}
+
+impl QualityPreset {
+    /// Check the preset and normalize its resolution and codec names.
+    pub fn validate(&self) -> Result<Self, String> {
+        let resolution = self.resolution.trim().to_lowercase();
+        let valid_label = resolution == "original"
+            || resolution
+                .strip_suffix('p')
+                .and_then(|h| h.parse::<u32>().ok())
+                .is_some_and(|h| (MIN_RUNG_HEIGHT..=MAX_RUNG_HEIGHT).contains(&h));
+        if !valid_label {
+            return Err(format!(
+                "invalid resolution '{}' (expected e.g. \"720p\" or \"original\")",
+                self.resolution
+            ));
+        }
+        let codec = match &self.codec {
+            Some(c) => Some(
+                Codec::parse(c)
+                    .ok_or_else(|| format!("{}: invalid codec '{}'", resolution, c))?
+                    .as_str()
+                    .to_string(),
+            ),
+            None => None,
+        };
+        if self.crf > 51 {
+            return Err(format!("{}: crf must be between 0 and 51", resolution));
+        }
+        Ok(Self {
+            resolution,
+            codec,
+            crf: self.crf,
+        })
+    }
+}
+
+/// Validate operator quality presets, returning them normalized.
+pub fn validate_quality_presets(presets: &[QualityPreset]) -> Result<Vec<QualityPreset>, String> {
+    if presets.len() > MAX_QUALITY_PRESETS {
+        return Err(format!(
+            "quality_presets has {} entries (max {})",
+            presets.len(),
+            MAX_QUALITY_PRESETS
+        ));
+    }
+    let presets = presets
+        .iter()
+        .map(QualityPreset::validate)
+        .collect::<Result<Vec<_>, _>>()?;
+    for (i, preset) in presets.iter().enumerate() {
+        if presets[..i]
+            .iter()
+            .any(|p| p.resolution == preset.resolution && p.codec == preset.codec)
+        {
+            return Err(format!(
+                "quality_presets has duplicate entry {}{}",
+                preset.resolution,
+                preset.codec.as_deref().map(|c| format!(" ({})", c)).unwrap_or_default()
+            ));
+        }
+    }
+    Ok(presets)
+}
+
+/// CRF preset for `label` ("720p", "original") and `codec`; a preset for the
+/// codec wins over one for all codecs.
+pub fn preset_crf(presets: &[QualityPreset], codec: Codec, label: &str) -> Option<u32> {
+    let matching = |for_codec: bool| {
+        presets.iter().find(|p| {
+            p.resolution == label && p.codec.as_deref().is_some() == for_codec
+                && p.codec.as_deref().is_none_or(|c| c == codec.as_str())
+        })
+    };
+    matching(true).or_else(|| matching(false)).map(|p| p.crf)
+}
+
+/// The ladder (the built-in one if empty) with CRF rungs replaced by the
+/// matching presets for `codec`.
+pub fn ladder_with_presets(
+    ladder: &[LadderRung],
+    presets: &[QualityPreset],
+    codec: Codec,
+) -> Vec<LadderRung> {
+    let rungs = if ladder.is_empty() {
+        builtin_ladder()
+    } else {
+        ladder.to_vec()
+    };
+    rungs
+        .into_iter()
+        .map(|rung| LadderRung {
+            crf: rung
+                .crf
+                .map(|crf| preset_crf(presets, codec, &rung.label()).unwrap_or(crf)),
+            ..rung
+        })
+        .collect()
+}
+
+/// Parse a video bitrate ("2500k", "2.5M" or "2500000") into FFmpeg's "2500k" form.
+/// Returns `None` for malformed values or values outside 100k-100M.
+pub fn parse_video_bitrate(s: &str) -> Option<String> {
+    let s = s.trim().to_lowercase();
+    let kbps = if let Some(k) = s.strip_suffix('k') {
+        k.parse::<u32>().ok()?
+    } else if let Some(m) = s.strip_suffix('m') {
+        let mbps = m.parse::<f64>().ok()?;
+        if !mbps.is_finite() || mbps < 0.0 {
+            return None;
+        }
+        (mbps * 1000.0).round() as u32
+    } else {
+        s.parse::<u32>().ok()? / 1000
+    };
+    (MIN_VIDEO_KBPS..=MAX_VIDEO_KBPS)
+        .contains(&kbps)
+        .then(|| format!("{}k", kbps))
+}
+
 /// Convert an AES key to base64 string
 pub fn key_to_base64(key: &[u8; 16]) -> String {
     STANDARD.encode(key)
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ResolutionConfig {
     pub width: Option<u32>,
     pub height: Option<u32>,
@@ -36,24 +375,11 @@ pub struct ResolutionConfig {
     pub audio_codec: Option<String>,
     pub quality: Option<u32>,
     pub is_original: bool,
+    /// Frame rate the rung is brought down to (`None` keeps the source rate)
+    pub fps: Option<f64>,
 }
 
-impl Default for ResolutionConfig {
-    fn default() -> Self {
-        Self {
-            width: None,
-            height: None,
-            video_bitrate: None,
-            audio_bitrate: None,
-            video_codec: None,
-            audio_codec: None,
-            quality: None,
-            is_original: false,
-        }
-    }
-}
-
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum SegmentType {
     #[default]
     Fmp4,
@@ -74,14 +400,205 @@ impl SegmentType {
             Self::MpegTs => "ts",
         }
     }
+
+    /// Parse a job param value. Returns `None` for unrecognized values.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "fmp4" | "cmaf" => Some(Self::Fmp4),
+            "ts" | "mpegts" => Some(Self::MpegTs),
+            _ => None,
+        }
+    }
+}
+
+/// HLS encryption method (`#EXT-X-KEY:METHOD=...`).
+///
+/// FFmpeg's HLS muxer only implements whole-segment AES-128
+/// (`-hls_key_info_file`); SAMPLE-AES needs NAL/ADTS-level encryption which
+/// it doesn't write, so requests for it are declined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EncryptionScheme {
+    /// Whole-segment AES-128-CBC
+    #[default]
+    Aes128,
+}
+
+impl EncryptionScheme {
+    /// Parse a job param value. Returns `None` for unrecognized values.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "aes-128" | "aes128" => Some(Self::Aes128),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Aes128 => "aes-128",
+        }
+    }
+}
+
+/// Animated preview container for hover previews.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PreviewFormat {
+    #[default]
+    WebP,
+    Gif,
+}
+
+impl PreviewFormat {
+    /// Parse a job param value (`true` selects the default format).
+    /// Returns `None` for unrecognized values.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "webp" | "true" => Some(Self::WebP),
+            "gif" => Some(Self::Gif),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::WebP => "webp",
+            Self::Gif => "gif",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::WebP => "image/webp",
+            Self::Gif => "image/gif",
+        }
+    }
+}
+
+/// Container of single-file (MP4 mode) output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Container {
+    #[default]
+    Mp4,
+    WebM,
+}
+
+impl Container {
+    /// Parse a job param value. Returns `None` for unrecognized values.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "mp4" => Some(Self::Mp4),
+            "webm" => Some(Self::WebM),
+            _ => None,
+        }
+    }
+
+    /// Container for a file encoded with `codec`.
+    ///
+    /// Without an explicit request VP9 goes into WebM and everything else into
+    /// MP4. WebM only carries VP9 and AV1, so other codecs always get MP4.
+    pub fn resolve(requested: Option<Self>, codec: Codec) -> Self {
+        let container = requested.unwrap_or(if codec == Codec::Vp9 {
+            Self::WebM
+        } else {
+            Self::Mp4
+        });
+        if container.supports(codec) {
+            container
+        } else {
+            Self::Mp4
+        }
+    }
+
+    pub fn supports(&self, codec: Codec) -> bool {
+        match self {
+            Self::Mp4 => true,
+            Self::WebM => matches!(codec, Codec::Vp9 | Codec::AV1),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Mp4 => "mp4",
+            Self::WebM => "webm",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        self.as_str()
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Mp4 => "video/mp4",
+            Self::WebM => "video/webm",
+        }
+    }
+
+    /// Audio encoder used in this container (AAC for MP4, Opus for WebM)
+    pub fn audio_encoder(&self) -> &'static str {
+        match self {
+            Self::Mp4 => "aac",
+            Self::WebM => "libopus",
+        }
+    }
+
+    /// MIME type with RFC 6381 codecs for a file holding `codec` video
+    pub fn mime_type_with_codecs(&self, codec: Codec) -> String {
+        let video = match codec {
+            Codec::H264 => "avc1.64001f",
+            Codec::H265 => "hvc1",
+            // Common AV1 mimetype (profile 0, level 5.0, Main)
+            Codec::AV1 => "av01.0.05M.08",
+            // Profile 0, level 4.0, 8-bit
+            Codec::Vp9 => "vp09.00.40.08",
+        };
+        let audio = match self {
+            Self::Mp4 => "mp4a.40.2",
+            Self::WebM => "opus",
+        };
+        format!("{}; codecs=\"{},{}\"", self.mime_type(), video, audio)
+    }
+}
+
+/// A source audio track carried into HLS output as an alternate rendition
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AudioTrack {
+    /// ISO 639 language code, if the source tags one
+    pub language: Option<String>,
+    /// Track title from the source (e.g. "Commentary")
+    pub title: Option<String>,
+}
+
+impl AudioTrack {
+    /// One track per audio stream of the source, in input order
+    pub fn from_metadata(metadata: &VideoMetadata) -> Vec<Self> {
+        metadata
+            .audio_streams()
+            .into_iter()
+            .map(|s| Self {
+                language: s.language().map(str::to_string),
+                title: s.tags.title.clone(),
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct TransformConfig {
     pub resolutions: HashMap<String, ResolutionConfig>,
     pub hls_time: u32,
+    /// Seconds between keyframes forced into every encoded rung
+    pub keyframe_interval: f64,
     pub hls_list_size: u32,
     pub segment_type: SegmentType,
+    /// Source audio tracks to publish as a shared `#EXT-X-MEDIA` audio group.
+    /// With fewer than two, each variant carries the first track muxed in.
+    pub audio_tracks: Vec<AudioTrack>,
+    /// CRF used when the original has to be re-encoded
+    pub original_quality: u32,
+    /// The source is taller than wide. Rung heights then size the frame
+    /// width (the shorter side): the 720p rung of a 1080x1920 source is
+    /// 720x1280.
+    pub portrait: bool,
 }
 
 impl Default for TransformConfig {
@@ -95,129 +612,121 @@ impl TransformConfig {
     /// For 4K (height >= 2160), includes 240p, 360p, 480p, 720p, 1080p (encoded), and 2160p (original).
     /// For smaller inputs, includes 240p, 360p, 480p, 720p, and original resolution.
     pub fn for_resolution(input_height: Option<u32>) -> Self {
-        Self::for_resolutions(input_height, &Resolution::all(), None)
+        Self::for_resolutions(
+            input_height.map(|h| (h * 16 / 9, h)),
+            &Resolution::all(),
+            None,
+            &[],
+            ORIGINAL_CRF,
+        )
     }
 
     /// Create a transform config based on selected HLS resolutions.
     ///
     /// # Arguments
-    /// * `input_height` - Height of the input video in pixels
+    /// * `input_size` - Upright width and height of the input video in pixels
     /// * `selected` - List of resolutions selected by the user
     /// * `source_codec` - Source video codec (for determining if passthrough is possible)
+    /// * `ladder` - Operator-defined rungs replacing the built-in ladder (empty for built-in)
+    /// * `original_crf` - Quality of the original when it can't be passed through
     ///
     /// # Resolution filtering
+    /// - Rungs are matched against the shorter side, so portrait video gets
+    ///   the same ladder as landscape video of the same quality
     /// - Resolutions higher than input are skipped (e.g., 1080p skipped for 720p input)
     /// - "Original" uses passthrough if source codec is HLS-compatible, else re-encodes
     pub fn for_resolutions(
-        input_height: Option<u32>,
+        input_size: Option<(u32, u32)>,
         selected: &[Resolution],
         source_codec: Option<&str>,
+        ladder: &[LadderRung],
+        original_crf: u32,
     ) -> Self {
         let mut resolutions = HashMap::new();
-        let input_h = input_height.unwrap_or(1080);
+        let input_h = input_size.map_or(1080, |(w, h)| w.min(h));
+        let portrait = input_size.is_some_and(|(w, h)| h > w);
         let is_4k = input_h >= 2160;
 
         // Check if source codec is HLS-compatible (H.264 or H.265)
         let can_passthrough = source_codec
-            .map(|c| Self::is_hls_compatible_codec(c))
+            .map(Self::is_hls_compatible_codec)
             .unwrap_or(true); // Assume compatible if unknown
 
         // Track if we need to include original
         let include_original = selected.contains(&Resolution::Original);
 
-        // Add each selected resolution if it's <= input height
-        for res in selected {
-            match res {
-                Resolution::R240p if input_h >= 240 => {
-                    resolutions.insert(
-                        "240p".to_string(),
-                        ResolutionConfig {
-                            // Width is auto-calculated to preserve aspect ratio
-                            height: Some(240),
-                            quality: Some(33),
-                            audio_bitrate: Some("64k".to_string()),
-                            ..Default::default()
-                        },
-                    );
-                }
-                Resolution::R360p if input_h >= 360 => {
-                    resolutions.insert(
-                        "360p".to_string(),
-                        ResolutionConfig {
-                            // Width is auto-calculated to preserve aspect ratio
-                            height: Some(360),
-                            quality: Some(31),
-                            audio_bitrate: Some("96k".to_string()),
-                            ..Default::default()
-                        },
-                    );
-                }
-                Resolution::R480p if input_h >= 480 => {
-                    resolutions.insert(
-                        "480p".to_string(),
-                        ResolutionConfig {
-                            // Width is auto-calculated to preserve aspect ratio
-                            height: Some(480),
-                            quality: Some(29),
-                            audio_bitrate: Some("128k".to_string()),
-                            ..Default::default()
-                        },
-                    );
-                }
-                Resolution::R720p if input_h >= 720 => {
-                    resolutions.insert(
-                        "720p".to_string(),
-                        ResolutionConfig {
-                            // Width is auto-calculated to preserve aspect ratio
-                            height: Some(720),
-                            quality: Some(26),
-                            audio_bitrate: Some("128k".to_string()),
-                            ..Default::default()
-                        },
-                    );
-                }
-                Resolution::R1080p if input_h >= 1080 => {
-                    // Only add 1080p as encoded if original is also selected and we're not 4K
-                    // For 4K, 1080p is always encoded; for non-4K with original, 1080p is the original
-                    if is_4k || !include_original {
-                        resolutions.insert(
-                            "1080p".to_string(),
-                            ResolutionConfig {
-                                // Width is auto-calculated to preserve aspect ratio
-                                height: Some(1080),
-                                quality: Some(23),
-                                audio_bitrate: Some("128k".to_string()),
-                                ..Default::default()
-                            },
-                        );
-                    }
-                }
-                Resolution::Original => {
-                    // Add original at input resolution
-                    let label = if is_4k { "2160p" } else { "1080p" };
-                    resolutions.insert(
-                        label.to_string(),
-                        ResolutionConfig {
-                            is_original: can_passthrough,
-                            // If can't passthrough, set height for re-encoding (width auto-calculated)
-                            height: if can_passthrough { None } else { Some(input_h) },
-                            quality: if can_passthrough { None } else { Some(21) },
-                            ..Default::default()
-                        },
-                    );
-                }
-                _ => {} // Resolution higher than input, skip
+        let original_label = if is_4k { "2160p" } else { "1080p" };
+
+        let builtin = builtin_ladder();
+        let ladder = if ladder.is_empty() { &builtin } else { ladder };
+
+        // Add each selected rung if it's <= input height
+        for rung in ladder {
+            let label = rung.label();
+            let wanted = Resolution::all()
+                .into_iter()
+                .find(|r| r.height() == Some(rung.height))
+                .is_none_or(|r| selected.contains(&r));
+            // For 4K, 1080p is always encoded; for non-4K with original, 1080p is the original
+            if !wanted || rung.height > input_h || (include_original && label == original_label) {
+                continue;
             }
+            resolutions.insert(
+                label.clone(),
+                ResolutionConfig {
+                    // Width is auto-calculated to preserve aspect ratio
+                    height: Some(rung.height),
+                    quality: rung.crf,
+                    video_bitrate: rung.video_bitrate.clone(),
+                    audio_bitrate: rung
+                        .audio_bitrate
+                        .clone()
+                        .or_else(|| default_audio_bitrate(&label)),
+                    ..Default::default()
+                },
+            );
+        }
+
+        if include_original {
+            // Add original at input resolution
+            resolutions.insert(
+                original_label.to_string(),
+                ResolutionConfig {
+                    is_original: can_passthrough,
+                    // If can't passthrough, set height for re-encoding (width auto-calculated)
+                    height: if can_passthrough { None } else { Some(input_h) },
+                    quality: if can_passthrough { None } else { Some(original_crf) },
+                    ..Default::default()
+                },
+            );
         }
 
         Self {
             resolutions,
-            hls_time: 6,
+            hls_time: Segmenting::default().hls_time,
+            keyframe_interval: Segmenting::default().keyframe_interval,
             hls_list_size: 0,
             segment_type: SegmentType::Fmp4,
+            audio_tracks: Vec::new(),
+            original_quality: original_crf,
+            portrait,
         }
     }
 
+    /// Segment duration and keyframe spacing of the encode
+    pub fn segmenting(&self) -> Segmenting {
+        Segmenting {
+            hls_time: self.hls_time,
+            keyframe_interval: self.keyframe_interval,
+        }
+    }
+
+    /// Whether audio goes into a separate rendition group instead of being
+    /// muxed into every variant
+    pub fn has_audio_group(&self) -> bool {
+        self.audio_tracks.len() > 1
+    }
+
     /// Check if a codec is compatible with HLS (can be used for passthrough)
     pub fn is_hls_compatible_codec(codec: &str) -> bool {
         let codec_lower = codec.to_lowercase();
@@ -227,6 +736,52 @@ impl TransformConfig {
         )
     }
 
+    /// Re-encode a passthrough original at its own size (the shorter side),
+    /// e.g. because its frame timing has to change. Stream copy can't alter
+    /// frames.
+    pub fn reencode_original(&mut self, input_size: Option<u32>) {
+        for res in self.resolutions.values_mut().filter(|r| r.is_original) {
+            res.is_original = false;
+            res.height = input_size.or(Some(1080));
+            res.quality = Some(self.original_quality);
+        }
+    }
+
+    /// Bring encoded rungs down to the frame rate `cap` allows them
+    pub fn apply_fps_cap(&mut self, cap: FpsCap) {
+        for res in self.resolutions.values_mut().filter(|r| !r.is_original) {
+            res.fps = cap.rung_fps(res.height);
+        }
+    }
+
+    /// Frame rates of the slowed-down rungs, keyed by variant playlist name
+    pub fn variant_frame_rates(&self) -> HashMap<String, f64> {
+        let mut keys: Vec<_> = self.resolutions.keys().collect();
+        keys.sort(); // Same order as the variant streams
+        keys.iter()
+            .enumerate()
+            .filter_map(|(idx, key)| {
+                let fps = self.resolutions[*key].fps?;
+                Some((format!("stream_{}.m3u8", idx), fps))
+            })
+            .collect()
+    }
+
+    /// Override the audio bitrate of individual renditions, keyed by label
+    /// ("360p"). Labels not in the ladder are ignored.
+    pub fn apply_audio_bitrates(&mut self, overrides: &HashMap<String, String>) {
+        for (label, bitrate) in overrides {
+            if let Some(res) = self.resolutions.get_mut(label) {
+                res.audio_bitrate = Some(bitrate.clone());
+            }
+        }
+    }
+
+    /// Audio bitrate configured for a rendition, if not left to FFmpeg's default
+    pub fn audio_bitrate_for(&self, label: &str) -> Option<&str> {
+        self.resolutions.get(label)?.audio_bitrate.as_deref()
+    }
+
     /// Returns a human-readable string of the output resolutions
     pub fn resolution_label(&self) -> String {
         let mut labels: Vec<&str> = self.resolutions.keys().map(|s| s.as_str()).collect();
@@ -244,10 +799,14 @@ pub struct TransformResult {
     pub master_playlist_path: PathBuf,
     pub stream_playlists: Vec<PathBuf>,
     pub segment_paths: Vec<PathBuf>,
+    /// SHA-256 of each file in `segment_paths`, in the same order
+    pub segment_hashes: Vec<String>,
     pub stream_sizes: Vec<u64>,
     pub temp_dir: TempDir,
     /// Base64-encoded AES-128 encryption key
     pub encryption_key: String,
+    /// Encoded in software after the hardware encoder failed
+    pub software_fallback: bool,
 }
 
 impl TransformResult {
@@ -270,6 +829,8 @@ impl TransformResult {
 pub struct Mp4TransformResult {
     pub output_path: PathBuf,
     pub temp_dir: TempDir,
+    /// Encoded in software after the hardware encoder failed
+    pub software_fallback: bool,
 }
 
 impl Mp4TransformResult {
@@ -279,10 +840,57 @@ impl Mp4TransformResult {
     }
 }
 
+/// Result of thumbnail extraction
+#[derive(Debug)]
+pub struct ThumbnailTransformResult {
+    /// Extracted JPEG frames with the timestamp (seconds) each was taken at
+    pub frames: Vec<(f64, PathBuf)>,
+    pub temp_dir: TempDir,
+}
+
+impl ThumbnailTransformResult {
+    /// Cleanup temporary files
+    pub async fn cleanup(self) {
+        let _ = self.temp_dir.cleanup().await;
+    }
+}
+
+/// Result of animated preview generation
+#[derive(Debug)]
+pub struct PreviewTransformResult {
+    pub output_path: PathBuf,
+    pub format: PreviewFormat,
+    pub temp_dir: TempDir,
+}
+
+impl PreviewTransformResult {
+    /// Cleanup temporary files
+    pub async fn cleanup(self) {
+        let _ = self.temp_dir.cleanup().await;
+    }
+}
+
+/// Pick `count` evenly spaced timestamps, avoiding the very first and last
+/// frames (often black or a fade).
+pub fn evenly_spaced_timestamps(duration_secs: f64, count: usize) -> Vec<f64> {
+    if duration_secs <= 0.0 {
+        return vec![0.0; count.min(1)];
+    }
+    let step = duration_secs / (count + 1) as f64;
+    (1..=count).map(|i| step * i as f64).collect()
+}
+
+/// Name of the file an MP4 encode writes
+fn mp4_file_name(plan: &EncodePlan) -> String {
+    format!("output_{}.{}", plan.resolution.as_str(), plan.container.extension())
+}
+
 pub struct VideoProcessor {
     config: Arc<Config>,
     transform_config: TransformConfig,
     hwaccel: HwAccel,
+    gpus: Arc<GpuScheduler>,
+    hw_failures: Arc<Notify>,
 }
 
 impl VideoProcessor {
@@ -294,6 +902,8 @@ impl VideoProcessor {
             config,
             transform_config: TransformConfig::default(),
             hwaccel,
+            gpus: Arc::new(GpuScheduler::detect(hwaccel)),
+            hw_failures: Arc::new(Notify::new()),
         }
     }
 
@@ -307,66 +917,57 @@ impl VideoProcessor {
         self.hwaccel
     }
 
+    /// Notified when a hardware encode fails and is retried in software,
+    /// a sign the accelerator may have gone away since startup
+    pub fn hw_failures(&self) -> Arc<Notify> {
+        self.hw_failures.clone()
+    }
+
     /// Transform a video URL into HLS format with resolution-aware config.
-    /// If input_height is provided and >= 2160 (4K), outputs will include
-    /// 360p, 720p, 1080p (encoded), and 2160p (original).
+    /// If input_size is provided and its shorter side is >= 2160 (4K), outputs
+    /// will include 360p, 720p, 1080p (encoded), and 2160p (original).
     pub async fn transform(
         &self,
         input_url: &str,
-        input_height: Option<u32>,
+        input_size: Option<(u32, u32)>,
         codec: Codec,
         progress: Option<std::sync::Arc<std::sync::atomic::AtomicU64>>,
         duration: Option<f64>,
     ) -> Result<(TransformResult, TransformConfig), VideoError> {
-        self.transform_with_resolutions(
-            input_url,
-            input_height,
+        let plan = EncodePlan {
             codec,
-            &Resolution::all(),
-            None,
-            true,
-            progress,
-            duration,
-        )
-        .await
+            input_size,
+            duration_secs: duration.unwrap_or(0.0),
+            encryption: true,
+            ..EncodePlan::default()
+        };
+        self.transform_with_resolutions(input_url, &plan, progress, None, None)
+            .await
     }
 
-    /// Transform a video URL into HLS format with user-selected resolutions.
+    /// Transform a video URL into HLS format, encoding the rungs of `plan`.
     ///
     /// # Arguments
     /// * `input_url` - URL of the input video
-    /// * `input_height` - Height of the input video in pixels
-    /// * `codec` - Target codec (H.264 or H.265)
-    /// * `selected_resolutions` - List of resolutions selected by the user
-    /// * `source_codec` - Source video codec name (for passthrough detection)
-    /// * `encryption` - Enable AES-128 encryption (uses TS segments), or disable (uses fMP4 segments)
+    /// * `plan` - Rungs, codec, filters and segmenting of the encode
+    /// * `finished_segments` - Receives segments as FFmpeg finishes them, for
+    ///   uploading while the encode runs (the last one per stream isn't sent)
     pub async fn transform_with_resolutions(
         &self,
         input_url: &str,
-        input_height: Option<u32>,
-        codec: Codec,
-        selected_resolutions: &[Resolution],
-        source_codec: Option<&str>,
-        encryption: bool,
+        plan: &EncodePlan,
         progress: Option<std::sync::Arc<std::sync::atomic::AtomicU64>>,
-        duration: Option<f64>,
+        log: Option<std::sync::Arc<JobLog>>,
+        finished_segments: Option<mpsc::UnboundedSender<PathBuf>>,
     ) -> Result<(TransformResult, TransformConfig), VideoError> {
-        let transform_config =
-            TransformConfig::for_resolutions(input_height, selected_resolutions, source_codec);
-
-        // Validate we have at least 2 resolutions
-        if transform_config.resolutions.len() < 2 {
-            return Err(VideoError::InvalidInput(
-                "At least 2 resolutions required for HLS adaptive streaming".to_string(),
-            ));
-        }
+        let transform_config = self.hls_config(input_url, plan).await?;
 
         info!(
             url = %input_url,
             resolutions = %transform_config.resolution_label(),
             hwaccel = %self.hwaccel,
-            codec = %codec.as_str(),
-            encryption = %encryption,
+            codec = %plan.codec.as_str(),
+            encryption = %plan.encryption,
             "Starting HLS video transformation"
         );
 
@@ -376,23 +977,10 @@ impl VideoProcessor {
 
         debug!(path = %output_dir.display(), "Created temp directory");
 
-        // Build FFmpeg command with hardware acceleration
-        let mut ffmpeg = FfmpegCommand::new(
-            input_url,
-            output_dir,
-            transform_config.clone(),
-            self.hwaccel,
-            codec,
-        )
-        .with_source_codec(source_codec);
-
-        if let Some(d) = duration {
-            ffmpeg = ffmpeg.with_duration(d);
-        }
-
         // Only enable encryption if requested (uses TS segments)
         // Without encryption, uses fMP4 segments (Safari compatible for HEVC)
-        let encryption_key_base64 = if encryption {
+        let mut key_info = None;
+        let encryption_key_base64 = if plan.encryption {
             // Generate AES-128 encryption key
             let encryption_key = generate_aes_key();
             let encryption_key_base64 = key_to_base64(&encryption_key);
@@ -410,21 +998,68 @@ impl VideoProcessor {
 
             debug!(key_path = %key_path.display(), "Generated AES encryption key");
 
-            ffmpeg = ffmpeg.with_encryption(&key_info_path);
+            key_info = Some(key_info_path);
             encryption_key_base64
         } else {
             debug!("Encryption disabled, using fMP4 segments");
             String::new()
         };
 
-        ffmpeg.run(&self.config.ffmpeg_path, progress).await?;
+        // Build FFmpeg command with hardware acceleration
+        let gpu = self.acquire_gpu(log.as_deref());
+        let build = |hwaccel: HwAccel, codec: Codec| {
+            Self::hls_command(
+                input_url,
+                output_dir,
+                &transform_config,
+                plan,
+                hwaccel,
+                codec,
+                key_info.as_deref(),
+            )
+            .with_log(log.clone())
+            .with_device(gpu.as_ref().map(GpuLease::device))
+        };
+
+        let mut software_fallback = false;
+        let finished = finished_segments.as_ref();
+        let encode = build(self.hwaccel, plan.codec);
+        if let Err(e) = while_encoding(
+            encode.run(&self.config.ffmpeg_path, progress.clone()),
+            output_dir,
+            finished,
+        )
+        .await
+        {
+            let Some(codec) = self.software_retry(&e, plan.codec, log.as_deref()) else {
+                return Err(e);
+            };
+            remove_partial_hls_output(output_dir).await?;
+            if let Some(p) = &progress {
+                p.store(0, Ordering::Relaxed);
+            }
+            let encode = build(HwAccel::Software, codec);
+            while_encoding(encode.run(&self.config.ffmpeg_path, progress), output_dir, finished)
+                .await?;
+            software_fallback = true;
+        }
 
         info!("FFmpeg HLS processing complete");
 
+        // FFmpeg's master playlist doesn't reliably carry the rate of rungs
+        // the fps filter slowed down
+        let frame_rates = transform_config.variant_frame_rates();
+        if !frame_rates.is_empty() {
+            let master_path = output_dir.join("master.m3u8");
+            let master = fs::read_to_string(&master_path).await?;
+            fs::write(&master_path, set_frame_rates(&master, &frame_rates)).await?;
+        }
+
         // Collect output files
-        let result = self
+        let mut result = self
             .collect_output_files(temp_dir, encryption_key_base64)
             .await?;
+        result.software_fallback = software_fallback;
 
         info!(
             master = %result.master_playlist_path.display(),
@@ -436,23 +1071,137 @@ impl VideoProcessor {
         Ok((result, transform_config))
     }
 
-    /// Transform a video URL into a single MP4 file
-    pub async fn transform_mp4(
+    /// HLS config for the rungs of `plan`, with the original re-encoded
+    /// wherever copying it wouldn't do
+    async fn hls_config(
         &self,
         input_url: &str,
-        resolution: Resolution,
-        quality: Option<u32>,
+        plan: &EncodePlan,
+    ) -> Result<TransformConfig, VideoError> {
+        let input_height = plan.input_size.map(|(w, h)| w.min(h));
+        let mut transform_config = TransformConfig::for_resolutions(
+            plan.input_size,
+            &plan.resolutions,
+            plan.source_codec.as_deref(),
+            &plan.ladder,
+            plan.original_crf,
+        );
+        transform_config.segment_type = plan.segment_type;
+        transform_config.hls_time = plan.segmenting.hls_time;
+        transform_config.keyframe_interval = plan.segmenting.keyframe_interval;
+        transform_config.apply_audio_bitrates(&plan.audio_bitrates);
+        transform_config.audio_tracks = plan.audio_tracks.clone();
+        // A copied original could only be cut at keyframes, would stay
+        // interlaced, and would stay HDR unless the requester wants to keep it
+        let tonemap_original = plan.tonemap.is_some_and(|t| !t.keep_original);
+        // TS segments can't carry the display matrix of a rotated original
+        let rotated_ts = plan.rotation != 0
+            && (plan.encryption || plan.segment_type == SegmentType::MpegTs);
+        if plan.frame_rate.is_some()
+            || plan.watermark.is_some()
+            || plan.clip.is_some()
+            || plan.deinterlace.is_some()
+            || plan.fps_cap.is_some_and(|c| c.caps_original())
+            || tonemap_original
+            || rotated_ts
+        {
+            transform_config.reencode_original(input_height);
+        } else if transform_config.resolutions.values().any(|r| r.is_original) {
+            match GopLayout::probe(input_url, &self.config.ffprobe_path).await {
+                Ok(layout) => {
+                    if let Some(issue) = layout.passthrough_issue(transform_config.hls_time) {
+                        info!(reason = %issue, "Source isn't segmentable as-is, re-encoding the original");
+                        transform_config.reencode_original(input_height);
+                    }
+                }
+                Err(e) => warn!(error = %e, "Keyframe probe failed, copying the original unchecked"),
+            }
+        }
+
+        if let Some(cap) = plan.fps_cap {
+            transform_config.apply_fps_cap(cap);
+        }
+
+        // Validate we have at least 2 resolutions
+        if transform_config.resolutions.len() < 2 {
+            return Err(VideoError::InvalidInput(
+                "At least 2 resolutions required for HLS adaptive streaming".to_string(),
+            ));
+        }
+
+        Ok(transform_config)
+    }
+
+    /// FFmpeg command encoding the HLS ladder of `transform_config` into `output_dir`
+    fn hls_command(
+        input_url: &str,
+        output_dir: &Path,
+        transform_config: &TransformConfig,
+        plan: &EncodePlan,
+        hwaccel: HwAccel,
         codec: Codec,
-        source_codec: Option<&str>,
+        key_info: Option<&Path>,
+    ) -> FfmpegCommand {
+        let mut ffmpeg = FfmpegCommand::new(
+            input_url,
+            output_dir,
+            transform_config.clone(),
+            hwaccel,
+            codec,
+        )
+        .with_source_codec(plan.source_codec.as_deref())
+        .with_frame_rate(plan.frame_rate.as_deref())
+        .with_watermark(plan.watermark.as_ref())
+        .with_deinterlace(plan.deinterlace)
+        .with_tonemap(plan.tonemap)
+        .with_rotation(plan.rotation)
+        .with_clip(plan.clip)
+        .with_loudnorm(plan.loudnorm.as_ref())
+        .with_strip_metadata(plan.strip_metadata)
+        .with_duration(plan.duration_secs);
+        if let Some(path) = key_info {
+            ffmpeg = ffmpeg.with_encryption(path);
+        }
+        ffmpeg
+    }
+
+    /// FFmpeg command line `transform_with_resolutions` would run, without
+    /// running it. Output goes to a placeholder directory under the temp dir.
+    pub async fn hls_command_line(
+        &self,
+        input_url: &str,
+        plan: &EncodePlan,
+    ) -> Result<Vec<String>, VideoError> {
+        let transform_config = self.hls_config(input_url, plan).await?;
+        let output_dir = self.config.temp_dir.join(DRY_RUN_DIR);
+        let key_info = plan.encryption.then(|| output_dir.join("key_info.txt"));
+        Ok(Self::hls_command(
+            input_url,
+            &output_dir,
+            &transform_config,
+            plan,
+            self.hwaccel,
+            plan.codec,
+            key_info.as_deref(),
+        )
+        .command_line(&self.config.ffmpeg_path))
+    }
+
+    /// Transform a video URL into a single MP4 (or WebM) file
+    pub async fn transform_mp4(
+        &self,
+        input_url: &str,
+        plan: &EncodePlan,
         progress: Option<std::sync::Arc<std::sync::atomic::AtomicU64>>,
-        duration: Option<f64>,
+        log: Option<std::sync::Arc<JobLog>>,
     ) -> Result<Mp4TransformResult, VideoError> {
         info!(
             url = %input_url,
-            resolution = %resolution.as_str(),
+            resolution = %plan.resolution.as_str(),
             hwaccel = %self.hwaccel,
-            codec = %codec.as_str(),
-            source_codec = ?source_codec,
+            codec = %plan.codec.as_str(),
+            container = %plan.container.as_str(),
+            source_codec = ?plan.source_codec,
             "Starting MP4 video transformation"
         );
 
@@ -463,30 +1212,186 @@ impl VideoProcessor {
         debug!(path = %output_dir.display(), "Created temp directory");
 
         // Output file path
-        let output_path = output_dir.join(format!("output_{}.mp4", resolution.as_str()));
+        let output_path = output_dir.join(mp4_file_name(plan));
 
         // Build and run FFmpeg command with hardware acceleration
-        let mut ffmpeg = FfmpegMp4Command::new(
-            input_url,
-            output_path.clone(),
-            resolution,
-            self.hwaccel,
-            codec,
-        )
-        .with_source_codec(source_codec);
-        if let Some(q) = quality {
-            ffmpeg = ffmpeg.with_crf(q);
-        }
-        if let Some(d) = duration {
-            ffmpeg = ffmpeg.with_duration(d);
+        let gpu = self.acquire_gpu(log.as_deref());
+        let build = |hwaccel: HwAccel, codec: Codec| {
+            Self::mp4_command(input_url, output_path.clone(), plan, hwaccel, codec)
+                .with_log(log.clone())
+                .with_device(gpu.as_ref().map(GpuLease::device))
+        };
+
+        let mut software_fallback = false;
+        if let Err(e) = build(self.hwaccel, plan.codec)
+            .run(&self.config.ffmpeg_path, progress.clone())
+            .await
+        {
+            let Some(codec) = self.software_retry(&e, plan.codec, log.as_deref()) else {
+                return Err(e);
+            };
+            if let Some(p) = &progress {
+                p.store(0, Ordering::Relaxed);
+            }
+            build(HwAccel::Software, codec)
+                .run(&self.config.ffmpeg_path, progress)
+                .await?;
+            software_fallback = true;
         }
-        ffmpeg.run(&self.config.ffmpeg_path, progress).await?;
 
         info!(output = %output_path.display(), "MP4 transformation complete");
 
         Ok(Mp4TransformResult {
             output_path,
             temp_dir,
+            software_fallback,
+        })
+    }
+
+    /// FFmpeg command encoding the single file of `plan` to `output_path`
+    fn mp4_command(
+        input_url: &str,
+        output_path: PathBuf,
+        plan: &EncodePlan,
+        hwaccel: HwAccel,
+        codec: Codec,
+    ) -> FfmpegMp4Command {
+        let resolution = plan.resolution;
+        FfmpegMp4Command::new(input_url, output_path, resolution, hwaccel, codec)
+            .with_container(plan.container)
+            .with_source_codec(plan.source_codec.as_deref())
+            .with_frame_rate(plan.frame_rate.as_deref())
+            .with_fps(plan.fps_cap.and_then(|c| c.rung_fps(resolution.height())))
+            .with_watermark(plan.watermark.as_ref())
+            .with_deinterlace(plan.deinterlace)
+            .with_tonemap(plan.tonemap)
+            .with_rotation(plan.rotation)
+            .with_clip(plan.clip)
+            .with_loudnorm(plan.loudnorm.as_ref())
+            .with_strip_metadata(plan.strip_metadata)
+            .with_crf(plan.quality)
+            .with_duration(plan.duration_secs)
+    }
+
+    /// FFmpeg command line `transform_mp4` would run, without running it.
+    /// Output goes to a placeholder directory under the temp dir.
+    pub fn mp4_command_line(&self, input_url: &str, plan: &EncodePlan) -> Vec<String> {
+        let output_path = self
+            .config
+            .temp_dir
+            .join(DRY_RUN_DIR)
+            .join(mp4_file_name(plan));
+        Self::mp4_command(input_url, output_path, plan, self.hwaccel, plan.codec)
+            .command_line(&self.config.ffmpeg_path)
+    }
+
+    /// Assign the next encode a GPU when there are several to spread over
+    fn acquire_gpu(&self, log: Option<&JobLog>) -> Option<GpuLease> {
+        let lease = self.gpus.acquire()?;
+        debug!(device = %lease.device(), "Assigned GPU");
+        if let Some(log) = log {
+            log.event(format!("Encoding on GPU {}", lease.device()));
+        }
+        Some(lease)
+    }
+
+    /// Codec to retry an encode with in software after `error`, if it was
+    /// the hardware encoder that failed. Software encoding is only tried once.
+    fn software_retry(&self, error: &VideoError, codec: Codec, log: Option<&JobLog>) -> Option<Codec> {
+        let VideoError::Ffmpeg { kind, .. } = error else {
+            return None;
+        };
+        if self.hwaccel == HwAccel::Software || !kind.is_hw_failure() {
+            return None;
+        }
+        warn!(hwaccel = %self.hwaccel, error = %error, "Hardware encode failed, retrying with software encoding");
+        if let Some(log) = log {
+            log.event(format!("{} encode failed ({}), retrying with software encoding", self.hwaccel, kind));
+        }
+        self.hw_failures.notify_one();
+        // Produce the codec the hardware encoder would have, so the
+        // container picked for it still fits
+        Some(Codec::from_encoder(self.hwaccel.video_encoder(codec)))
+    }
+
+    /// Grab one JPEG frame per timestamp for use as poster images.
+    ///
+    /// Frames are downscaled to `max_height` when the source is taller.
+    pub async fn extract_thumbnails(
+        &self,
+        input_url: &str,
+        timestamps: &[f64],
+        max_height: Option<u32>,
+    ) -> Result<ThumbnailTransformResult, VideoError> {
+        info!(
+            url = %input_url,
+            count = timestamps.len(),
+            max_height = ?max_height,
+            "Extracting thumbnails"
+        );
+
+        let temp_dir = TempDir::new(&self.config.temp_dir).await?;
+        let mut frames = Vec::with_capacity(timestamps.len());
+
+        for (idx, &timestamp) in timestamps.iter().enumerate() {
+            let output_path = temp_dir.path().join(format!("thumb_{:02}.jpg", idx));
+            FfmpegThumbnailCommand::new(input_url, output_path.clone(), timestamp)
+                .with_max_height(max_height)
+                .run(&self.config.ffmpeg_path)
+                .await?;
+            frames.push((timestamp, output_path));
+        }
+
+        info!(count = frames.len(), "Thumbnail extraction complete");
+
+        Ok(ThumbnailTransformResult { frames, temp_dir })
+    }
+
+    /// Compute a blurhash of the frame at `timestamp` for client placeholders.
+    ///
+    /// `dimensions` is the source size, used to pick the component counts.
+    pub async fn extract_blurhash(
+        &self,
+        input_url: &str,
+        timestamp: f64,
+        dimensions: Option<(u32, u32)>,
+    ) -> Result<String, VideoError> {
+        let pixels = FfmpegRawFrameCommand::new(input_url, timestamp, BLURHASH_SIZE, BLURHASH_SIZE)
+            .run(&self.config.ffmpeg_path)
+            .await?;
+        let (x, y) = dimensions.map_or((4, 3), |(w, h)| blurhash::components_for(w, h));
+        let size = BLURHASH_SIZE as usize;
+        blurhash::encode(&pixels, size, size, x, y)
+            .ok_or_else(|| VideoError::FfmpegFailed("Invalid frame for blurhash".into()))
+    }
+
+    /// Render a short looping animation from the start of the video.
+    pub async fn generate_preview(
+        &self,
+        input_url: &str,
+        format: PreviewFormat,
+        duration_secs: f64,
+    ) -> Result<PreviewTransformResult, VideoError> {
+        info!(
+            url = %input_url,
+            format = %format.extension(),
+            duration = duration_secs,
+            "Generating animated preview"
+        );
+
+        let temp_dir = TempDir::new(&self.config.temp_dir).await?;
+        let output_path = temp_dir
+            .path()
+            .join(format!("preview.{}", format.extension()));
+
+        FfmpegPreviewCommand::new(input_url, output_path.clone(), format, duration_secs)
+            .run(&self.config.ffmpeg_path)
+            .await?;
+
+        Ok(PreviewTransformResult {
+            output_path,
+            format,
+            temp_dir,
         })
     }
 
@@ -527,19 +1432,43 @@ impl VideoProcessor {
         stream_playlists.sort();
         segment_paths.sort();
 
+        // Hashing every segment up front is much faster in parallel than one
+        // by one between uploads
+        let segment_hashes = hash_files(&segment_paths).await?;
+
         let master_playlist_path = output_dir.join("master.m3u8");
 
         Ok(TransformResult {
             master_playlist_path,
             stream_playlists,
             segment_paths,
+            segment_hashes,
             stream_sizes,
             temp_dir,
             encryption_key,
+            software_fallback: false,
         })
     }
 }
 
+/// Remove the playlists and segments of a failed HLS encode, keeping the
+/// encryption key files
+async fn remove_partial_hls_output(output_dir: &Path) -> Result<(), VideoError> {
+    let mut entries = fs::read_dir(output_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        if name.ends_with(".m3u8")
+            || name.ends_with(".m4s")
+            || name.ends_with(".ts")
+            || (name.starts_with("init_") && name.ends_with(".mp4"))
+        {
+            fs::remove_file(&path).await?;
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -550,6 +1479,87 @@ mod tests {
         assert_eq!(SegmentType::Fmp4.extension(), "m4s");
         assert_eq!(SegmentType::MpegTs.as_str(), "mpegts");
         assert_eq!(SegmentType::MpegTs.extension(), "ts");
+        assert_eq!(SegmentType::parse("TS"), Some(SegmentType::MpegTs));
+        assert_eq!(SegmentType::parse("fmp4"), Some(SegmentType::Fmp4));
+        assert_eq!(SegmentType::parse("webm"), None);
+    }
+
+    #[test]
+    fn test_preview_format() {
+        assert_eq!(PreviewFormat::parse("true"), Some(PreviewFormat::WebP));
+        assert_eq!(PreviewFormat::parse("GIF"), Some(PreviewFormat::Gif));
+        assert_eq!(PreviewFormat::parse("false"), None);
+        assert_eq!(PreviewFormat::Gif.mime_type(), "image/gif");
+        assert_eq!(PreviewFormat::WebP.extension(), "webp");
+    }
+
+    #[test]
+    fn test_container() {
+        assert_eq!(Container::parse("WebM"), Some(Container::WebM));
+        assert_eq!(Container::parse("mkv"), None);
+
+        assert_eq!(Container::resolve(None, Codec::Vp9), Container::WebM);
+        assert_eq!(Container::resolve(None, Codec::AV1), Container::Mp4);
+        assert_eq!(Container::resolve(Some(Container::WebM), Codec::AV1), Container::WebM);
+        assert_eq!(Container::resolve(Some(Container::Mp4), Codec::Vp9), Container::Mp4);
+        // Fallback encoders that WebM can't carry end up in MP4
+        assert_eq!(Container::resolve(Some(Container::WebM), Codec::H265), Container::Mp4);
+
+        assert_eq!(
+            Container::WebM.mime_type_with_codecs(Codec::Vp9),
+            "video/webm; codecs=\"vp09.00.40.08,opus\""
+        );
+        assert_eq!(
+            Container::Mp4.mime_type_with_codecs(Codec::H264),
+            "video/mp4; codecs=\"avc1.64001f,mp4a.40.2\""
+        );
+    }
+
+    #[test]
+    fn test_software_retry() {
+        let config = Arc::new(Config::from_env(
+            nostr_sdk::Keys::generate(),
+            PathBuf::from("ffmpeg"),
+            PathBuf::from("ffprobe"),
+        ));
+        let processor = |hwaccel| VideoProcessor {
+            config: config.clone(),
+            transform_config: TransformConfig::default(),
+            hwaccel,
+            gpus: Arc::new(GpuScheduler::new(Vec::new(), crate::video::gpu::GpuStrategy::default())),
+            hw_failures: Arc::new(Notify::new()),
+        };
+        let failed = |stderr: &str| crate::video::ffmpeg_error::failure("HLS encoding failed", stderr);
+        let init = failed("[h264_nvenc @ 0x1] OpenEncodeSessionEx failed: unsupported device (2)");
+
+        let log = JobLog::default();
+        assert_eq!(
+            processor(HwAccel::Nvenc).software_retry(&init, Codec::H264, Some(&log)),
+            Some(Codec::H264)
+        );
+        assert!(log.tail(1).0[0].line.contains("retrying with software"));
+        // A bad input fails in software just the same
+        let corrupt = failed("moov atom not found");
+        assert_eq!(processor(HwAccel::Nvenc).software_retry(&corrupt, Codec::H264, None), None);
+        // Software doesn't retry itself
+        assert_eq!(processor(HwAccel::Software).software_retry(&init, Codec::H264, None), None);
+    }
+
+    #[test]
+    fn test_evenly_spaced_timestamps() {
+        assert_eq!(evenly_spaced_timestamps(100.0, 1), vec![50.0]);
+        assert_eq!(evenly_spaced_timestamps(100.0, 3), vec![25.0, 50.0, 75.0]);
+        assert_eq!(evenly_spaced_timestamps(0.0, 3), vec![0.0]);
+        assert!(evenly_spaced_timestamps(100.0, 0).is_empty());
+    }
+
+    #[test]
+    fn test_encryption_scheme() {
+        assert_eq!(EncryptionScheme::default(), EncryptionScheme::Aes128);
+        assert_eq!(EncryptionScheme::parse("AES-128"), Some(EncryptionScheme::Aes128));
+        assert_eq!(EncryptionScheme::Aes128.as_str(), "aes-128");
+        assert_eq!(EncryptionScheme::parse("sample-aes"), None);
+        assert_eq!(EncryptionScheme::parse("none"), None);
     }
 
     #[test]
@@ -614,6 +1624,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_audio_bitrates() {
+        assert_eq!(parse_audio_bitrate("96k").as_deref(), Some("96k"));
+        assert_eq!(parse_audio_bitrate(" 64000 ").as_deref(), Some("64k"));
+        assert_eq!(parse_audio_bitrate("16k"), None);
+        assert_eq!(parse_audio_bitrate("512k"), None);
+        assert_eq!(parse_audio_bitrate("loud"), None);
+
+        let mut config = TransformConfig::for_resolution(None);
+        assert_eq!(config.audio_bitrate_for("240p"), Some("64k"));
+        assert_eq!(config.audio_bitrate_for("720p"), Some("128k"));
+
+        let overrides = HashMap::from([
+            ("240p".to_string(), "48k".to_string()),
+            ("2160p".to_string(), "256k".to_string()),
+        ]);
+        config.apply_audio_bitrates(&overrides);
+        assert_eq!(config.audio_bitrate_for("240p"), Some("48k"));
+        assert!(!config.resolutions.contains_key("2160p"));
+    }
+
     #[test]
     fn test_for_resolutions_selected_subset() {
         let selected = vec![
@@ -621,7 +1652,8 @@ mod tests {
             Resolution::R720p,
             Resolution::Original,
         ];
-        let config = TransformConfig::for_resolutions(Some(1080), &selected, Some("h264"));
+        let config =
+            TransformConfig::for_resolutions(Some((1920, 1080)), &selected, Some("h264"), &[], ORIGINAL_CRF);
 
         assert_eq!(config.resolutions.len(), 3);
         assert!(config.resolutions.contains_key("360p"));
@@ -639,7 +1671,8 @@ mod tests {
             Resolution::R720p,
             Resolution::Original,
         ];
-        let config = TransformConfig::for_resolutions(Some(1080), &selected, Some("vp9"));
+        let config =
+            TransformConfig::for_resolutions(Some((1920, 1080)), &selected, Some("vp9"), &[], ORIGINAL_CRF);
 
         // 1080p should NOT be original (needs re-encode) since vp9 is not HLS-compatible
         let r1080 = config.resolutions.get("1080p").unwrap();
@@ -647,6 +1680,32 @@ mod tests {
         assert!(r1080.height.is_some()); // Has height for re-encoding (width auto-calculated)
     }
 
+    #[test]
+    fn test_for_resolutions_portrait() {
+        let config = TransformConfig::for_resolutions(
+            Some((1080, 1920)),
+            &Resolution::all(),
+            Some("h264"),
+            &[],
+            ORIGINAL_CRF,
+        );
+
+        // The ladder follows the shorter side: 1080x1920 is a 1080p source
+        assert!(config.portrait);
+        assert!(config.resolutions.contains_key("720p"));
+        assert!(config.resolutions.get("1080p").unwrap().is_original);
+        assert!(!config.resolutions.contains_key("2160p"));
+
+        let landscape = TransformConfig::for_resolutions(
+            Some((1920, 1080)),
+            &Resolution::all(),
+            None,
+            &[],
+            ORIGINAL_CRF,
+        );
+        assert!(!landscape.portrait);
+    }
+
     #[test]
     fn test_for_resolutions_skips_higher_than_input() {
         let selected = vec![
@@ -656,7 +1715,7 @@ mod tests {
             Resolution::R1080p,
             Resolution::Original,
         ];
-        let config = TransformConfig::for_resolutions(Some(480), &selected, None);
+        let config = TransformConfig::for_resolutions(Some((854, 480)), &selected, None, &[], ORIGINAL_CRF);
 
         // Only 240p, 360p, and original (at 480p level) should be included
         assert!(config.resolutions.contains_key("240p"));
@@ -665,6 +1724,142 @@ mod tests {
         assert!(!config.resolutions.contains_key("2160p"));
     }
 
+    fn rung(height: u32, crf: Option<u32>, video_bitrate: Option<&str>) -> LadderRung {
+        LadderRung {
+            height,
+            crf,
+            video_bitrate: video_bitrate.map(str::to_string),
+            audio_bitrate: None,
+        }
+    }
+
+    #[test]
+    fn test_for_resolutions_custom_ladder() {
+        let ladder = vec![
+            rung(360, Some(30), None),
+            rung(540, None, Some("1800k")),
+            rung(720, None, Some("3000k")),
+            rung(1080, Some(22), None),
+        ];
+        let selected = vec![Resolution::R360p, Resolution::Original];
+        let config =
+            TransformConfig::for_resolutions(Some((1920, 1080)), &selected, Some("h264"), &ladder, ORIGINAL_CRF);
+
+        // 720p wasn't selected; 540p has no resolution name and is always encoded;
+        // 1080p is taken by the original
+        let mut labels: Vec<_> = config.resolutions.keys().cloned().collect();
+        labels.sort();
+        assert_eq!(labels, vec!["1080p", "360p", "540p"]);
+        assert!(config.resolutions["1080p"].is_original);
+
+        let r540 = &config.resolutions["540p"];
+        assert_eq!(r540.height, Some(540));
+        assert_eq!(r540.quality, None);
+        assert_eq!(r540.video_bitrate.as_deref(), Some("1800k"));
+        assert_eq!(config.audio_bitrate_for("360p"), Some("96k"));
+        assert_eq!(config.audio_bitrate_for("540p"), None);
+
+        // Rungs taller than the input are skipped
+        let config = TransformConfig::for_resolutions(
+            Some((1066, 600)),
+            &Resolution::all(),
+            None,
+            &ladder,
+            ORIGINAL_CRF,
+        );
+        assert!(config.resolutions.contains_key("540p"));
+        assert!(!config.resolutions.contains_key("720p"));
+    }
+
+    #[test]
+    fn test_validate_ladder() {
+        let ladder = validate_ladder(&[
+            rung(720, None, Some("2.5M")),
+            LadderRung {
+                audio_bitrate: Some("96000".to_string()),
+                ..rung(360, Some(30), None)
+            },
+        ])
+        .unwrap();
+        assert_eq!(ladder[0].height, 360);
+        assert_eq!(ladder[0].audio_bitrate.as_deref(), Some("96k"));
+        assert_eq!(ladder[1].video_bitrate.as_deref(), Some("2500k"));
+
+        assert!(validate_ladder(&[]).unwrap().is_empty());
+        assert!(validate_ladder(&[rung(361, Some(30), None)]).is_err());
+        assert!(validate_ladder(&[rung(360, Some(60), None)]).is_err());
+        assert!(validate_ladder(&[rung(360, None, None)]).is_err());
+        assert!(validate_ladder(&[rung(360, Some(30), Some("800k"))]).is_err());
+        assert!(validate_ladder(&[rung(360, None, Some("fast"))]).is_err());
+        assert!(validate_ladder(&[rung(360, Some(30), None), rung(360, Some(28), None)]).is_err());
+        assert!(validate_ladder(&vec![rung(360, Some(30), None); MAX_LADDER_RUNGS + 1]).is_err());
+
+        assert_eq!(parse_video_bitrate("800k"), Some("800k".to_string()));
+        assert_eq!(parse_video_bitrate("50k"), None);
+    }
+
+    #[test]
+    fn test_ladder_with_crf_offset() {
+        let builtin = ladder_with_crf_offset(&[], 6);
+        assert_eq!(builtin.len(), BUILTIN_LADDER.len());
+        assert_eq!(builtin[0].crf, Some(39));
+
+        let custom = ladder_with_crf_offset(&[rung(720, None, Some("2400k")), rung(360, None, Some("150k"))], 6);
+        assert_eq!(custom[0].video_bitrate.as_deref(), Some("1200k"));
+        assert_eq!(custom[1].video_bitrate.as_deref(), Some("100k"));
+    }
+
+    fn preset(resolution: &str, codec: Option<&str>, crf: u32) -> QualityPreset {
+        QualityPreset {
+            resolution: resolution.to_string(),
+            codec: codec.map(str::to_string),
+            crf,
+        }
+    }
+
+    #[test]
+    fn test_quality_presets() {
+        let presets = validate_quality_presets(&[
+            preset("720P", None, 24),
+            preset("720p", Some("HEVC"), 28),
+            preset("original", Some("av1"), 30),
+        ])
+        .unwrap();
+        assert_eq!(presets[0].resolution, "720p");
+        assert_eq!(presets[1].codec.as_deref(), Some("h265"));
+
+        // A codec-specific preset wins over the generic one
+        assert_eq!(preset_crf(&presets, Codec::H264, "720p"), Some(24));
+        assert_eq!(preset_crf(&presets, Codec::H265, "720p"), Some(28));
+        assert_eq!(preset_crf(&presets, Codec::H264, "original"), None);
+        assert_eq!(preset_crf(&presets, Codec::AV1, "original"), Some(30));
+
+        let ladder = ladder_with_presets(&[], &presets, Codec::H265);
+        assert_eq!(ladder.len(), BUILTIN_LADDER.len());
+        assert_eq!(ladder.iter().find(|r| r.height == 720).unwrap().crf, Some(28));
+        assert_eq!(ladder.iter().find(|r| r.height == 480).unwrap().crf, Some(29));
+        // Bitrate rungs keep their bitrate
+        let custom = ladder_with_presets(&[rung(720, None, Some("2400k"))], &presets, Codec::H264);
+        assert_eq!(custom[0].crf, None);
+
+        assert!(validate_quality_presets(&[preset("720", None, 24)]).is_err());
+        assert!(validate_quality_presets(&[preset("720p", Some("mpeg2"), 24)]).is_err());
+        assert!(validate_quality_presets(&[preset("720p", None, 52)]).is_err());
+        assert!(validate_quality_presets(&[preset("720p", None, 24), preset("720p", None, 26)]).is_err());
+    }
+
+    #[test]
+    fn test_original_quality() {
+        let selected = vec![Resolution::R360p, Resolution::Original];
+        let config = TransformConfig::for_resolutions(Some((1920, 1080)), &selected, Some("vp9"), &[], 18);
+        assert_eq!(config.resolutions["1080p"].quality, Some(18));
+
+        let mut config = TransformConfig::for_resolutions(Some((1920, 1080)), &selected, Some("h264"), &[], 18);
+        assert_eq!(config.resolutions["1080p"].quality, None);
+        config.reencode_original(Some(1080));
+        assert_eq!(config.resolutions["1080p"].quality, Some(18));
+    }
+
     #[test]
     fn test_is_hls_compatible_codec() {
         assert!(TransformConfig::is_hls_compatible_codec("h264"));
@@ -0,0 +1,208 @@
+//! Burned-in watermark / branding overlays.
+//!
+//! A watermark is either an image (fetched by FFmpeg as a second input and
+//! drawn with `overlay`) or a line of text (drawn with `drawtext`). It is
+//! applied to the decoded source before scaling, so every rendition carries
+//! it at a size proportional to the frame.
+
+use serde::{Deserialize, Serialize};
+
+/// Longest accepted text watermark, in characters
+const MAX_TEXT_LEN: usize = 100;
+
+/// Default opacity when the request doesn't give one
+const DEFAULT_OPACITY: f32 = 0.6;
+
+/// Corner (or center) the watermark is anchored to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatermarkPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+    Center,
+}
+
+impl WatermarkPosition {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().replace('_', "-").as_str() {
+            "top-left" => Some(Self::TopLeft),
+            "top-right" => Some(Self::TopRight),
+            "bottom-left" => Some(Self::BottomLeft),
+            "bottom-right" => Some(Self::BottomRight),
+            "center" => Some(Self::Center),
+            _ => None,
+        }
+    }
+
+    /// x/y expressions given the frame and watermark size variable names
+    fn coordinates(&self, frame: (&str, &str), mark: (&str, &str)) -> (String, String) {
+        let (fw, fh) = frame;
+        let (mw, mh) = mark;
+        // Margin scales with the frame so it looks the same in every rendition
+        let (mx, my) = (format!("{fw}/40"), format!("{fh}/40"));
+        match self {
+            Self::TopLeft => (mx, my),
+            Self::TopRight => (format!("{fw}-{mw}-{mx}"), my),
+            Self::BottomLeft => (mx, format!("{fh}-{mh}-{my}")),
+            Self::BottomRight => (format!("{fw}-{mw}-{mx}"), format!("{fh}-{mh}-{my}")),
+            Self::Center => (format!("({fw}-{mw})/2"), format!("({fh}-{mh})/2")),
+        }
+    }
+}
+
+/// A watermark to burn into transcoded video
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Watermark {
+    /// Image URL (`http(s)://`) or text to draw
+    pub content: String,
+    #[serde(default)]
+    pub position: WatermarkPosition,
+    /// 0.0 (invisible) to 1.0 (opaque)
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+}
+
+fn default_opacity() -> f32 {
+    DEFAULT_OPACITY
+}
+
+impl Watermark {
+    /// Build a watermark from request params, validating the content.
+    pub fn new(
+        content: &str,
+        position: Option<WatermarkPosition>,
+        opacity: Option<f32>,
+    ) -> Result<Self, String> {
+        Self {
+            content: content.trim().to_string(),
+            position: position.unwrap_or_default(),
+            opacity: opacity.unwrap_or(DEFAULT_OPACITY),
+        }
+        .validate()
+    }
+
+    /// Check the content is usable and clamp the opacity.
+    pub fn validate(mut self) -> Result<Self, String> {
+        if self.content.is_empty() {
+            return Err("Watermark is empty".to_string());
+        }
+        if !self.is_image() {
+            if self.content.chars().count() > MAX_TEXT_LEN {
+                return Err(format!(
+                    "Watermark text is longer than {} characters",
+                    MAX_TEXT_LEN
+                ));
+            }
+            if self.content.chars().any(char::is_control) {
+                return Err("Watermark text contains control characters".to_string());
+            }
+        }
+        if !self.opacity.is_finite() {
+            self.opacity = DEFAULT_OPACITY;
+        }
+        self.opacity = self.opacity.clamp(0.0, 1.0);
+        Ok(self)
+    }
+
+    /// Whether the watermark is an image URL rather than text
+    pub fn is_image(&self) -> bool {
+        self.content.starts_with("http://") || self.content.starts_with("https://")
+    }
+
+    /// Extra FFmpeg input carrying the watermark image, if any
+    pub fn input(&self) -> Option<&str> {
+        self.is_image().then_some(self.content.as_str())
+    }
+
+    /// Filter chain drawing the watermark over the `source` pad (e.g. `[0:v]`).
+    ///
+    /// `image_input` is the FFmpeg input index of the image. The chain ends
+    /// on an unlabeled pad, so callers append `,next_filter` or a label.
+    pub fn filter(&self, source: &str, image_input: usize) -> String {
+        if self.is_image() {
+            let (x, y) = self
+                .position
+                .coordinates(("main_w", "main_h"), ("overlay_w", "overlay_h"));
+            format!(
+                "[{image_input}:v]format=rgba,colorchannelmixer=aa={:.2}[wm];{source}[wm]overlay=x={x}:y={y}:format=auto",
+                self.opacity
+            )
+        } else {
+            let (x, y) = self.position.coordinates(("w", "h"), ("tw", "th"));
+            format!(
+                "{source}drawtext=text={}:expansion=none:fontsize=h/24:fontcolor=white@{:.2}:shadowcolor=black@{:.2}:shadowx=2:shadowy=2:x={x}:y={y}",
+                escape_drawtext(&self.content),
+                self.opacity,
+                self.opacity / 2.0
+            )
+        }
+    }
+}
+
+/// Escape text for use as a drawtext option value inside a filtergraph.
+///
+/// Option values escape `\ ' :`, and the filtergraph parser then needs
+/// `\ ' [ ] , ;` escaped again on top.
+fn escape_drawtext(text: &str) -> String {
+    let mut value = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '\'' | ':') {
+            value.push('\\');
+        }
+        value.push(c);
+    }
+    let mut graph = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | '\'' | '[' | ']' | ',' | ';') {
+            graph.push('\\');
+        }
+        graph.push(c);
+    }
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watermark_validation() {
+        let text = Watermark::new("  processed by nostu.be ", None, Some(3.0)).unwrap();
+        assert_eq!(text.content, "processed by nostu.be");
+        assert_eq!(text.position, WatermarkPosition::BottomRight);
+        assert_eq!(text.opacity, 1.0);
+        assert!(!text.is_image());
+        assert_eq!(text.input(), None);
+
+        let image = Watermark::new("https://example.com/logo.png", None, None).unwrap();
+        assert!(image.is_image());
+        assert_eq!(image.input(), Some("https://example.com/logo.png"));
+
+        assert!(Watermark::new(" ", None, None).is_err());
+        assert!(Watermark::new(&"x".repeat(101), None, None).is_err());
+        assert!(Watermark::new("line\nbreak", None, None).is_err());
+    }
+
+    #[test]
+    fn test_watermark_filters() {
+        let image = Watermark::new(
+            "https://example.com/logo.png",
+            WatermarkPosition::parse("top_left"),
+            Some(0.5),
+        )
+        .unwrap();
+        assert_eq!(
+            image.filter("[0:v]", 1),
+            "[1:v]format=rgba,colorchannelmixer=aa=0.50[wm];[0:v][wm]overlay=x=main_w/40:y=main_h/40:format=auto"
+        );
+
+        let text = Watermark::new("a:b, c's [x]", None, Some(0.8)).unwrap();
+        let filter = text.filter("[0:v]", 1);
+        assert!(filter.starts_with("[0:v]drawtext=text=a\\\\:b\\, c\\\\\\'s \\[x\\]:expansion=none"));
+        assert!(filter.ends_with(":x=w-tw-w/40:y=h-th-h/40"));
+        assert!(filter.contains("fontcolor=white@0.80"));
+    }
+}
@@ -1,25 +1,66 @@
 mod assets;
+pub mod watch;
 
+use std::convert::Infallible;
 use std::sync::Arc;
 
 use axum::{
     body::Body,
-    extract::Path,
+    extract::{Path, Query, State},
     http::{header, Response, StatusCode},
-    response::IntoResponse,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json,
+    },
     routing::get,
     Router,
 };
+use futures::stream::{self, Stream, StreamExt};
+use nostr_sdk::Keys;
+use serde::Deserialize;
 use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
 use tracing::{error, info};
 
+use crate::dvm::heartbeat::Heartbeat;
+use crate::dvm::updates::JobUpdates;
+use crate::dvm_state::SharedDvmState;
 use crate::Config;
 use assets::Assets;
+use watch::WatchError;
 
-pub async fn run_server(config: Arc<Config>) -> anyhow::Result<()> {
+/// Shared with the API handlers
+#[derive(Clone)]
+struct AppState {
+    /// DVM pubkey (hex), so the dashboard can tell which DVM serves it
+    pubkey: String,
+    /// Signs and checks `/watch` links
+    keys: Keys,
+    heartbeat: Heartbeat,
+    updates: JobUpdates,
+    dvm: SharedDvmState,
+}
+
+pub async fn run_server(
+    config: Arc<Config>,
+    heartbeat: Heartbeat,
+    updates: JobUpdates,
+    dvm: SharedDvmState,
+) -> anyhow::Result<()> {
+    let state = AppState {
+        pubkey: config.nostr_keys.public_key().to_hex(),
+        keys: config.nostr_keys.clone(),
+        heartbeat,
+        updates,
+        dvm,
+    };
     let app = Router::new()
         .route("/", get(index_handler))
-        .route("/*path", get(static_handler));
+        .route("/api/heartbeat", get(heartbeat_handler))
+        .route("/api/events", get(events_handler))
+        .route("/api/watch/:job_id", get(watch_handler))
+        .route("/*path", get(static_handler))
+        .with_state(state);
 
     let addr = format!("0.0.0.0:{}", config.http_port);
     let listener = TcpListener::bind(&addr).await?;
@@ -39,6 +80,76 @@ async fn static_handler(Path(path): Path<String>) -> impl IntoResponse {
     serve_file(&path)
 }
 
+/// Job loop liveness for watchdogs: 503 once the loop has missed its beats
+async fn heartbeat_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let snapshot = state.heartbeat.snapshot();
+    let now = chrono::Utc::now().timestamp().max(0) as u64;
+    let status = if snapshot.is_stale(now) {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+    (status, Json(snapshot))
+}
+
+/// Live job updates as server-sent events.
+///
+/// Opens with a `hello` event carrying the DVM pubkey, then one event per
+/// [`JobUpdate`](crate::dvm::updates::JobUpdate), named after its type. A
+/// client that falls behind gets a `lagged` event with the number of
+/// updates it missed and should refresh its job list.
+async fn events_handler(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let hello = Event::default()
+        .event("hello")
+        .json_data(serde_json::json!({ "pubkey": state.pubkey }))
+        .unwrap_or_default();
+
+    let updates = stream::unfold(state.updates.subscribe(), |mut rx| async move {
+        let event = match rx.recv().await {
+            Ok(update) => Event::default()
+                .event(update.kind())
+                .json_data(&update)
+                .unwrap_or_default(),
+            Err(RecvError::Lagged(missed)) => Event::default()
+                .event("lagged")
+                .data(missed.to_string()),
+            Err(RecvError::Closed) => return None,
+        };
+        Some((Ok(event), rx))
+    });
+
+    Sse::new(stream::once(async { Ok(hello) }).chain(updates)).keep_alive(KeepAlive::default())
+}
+
+#[derive(Deserialize)]
+struct WatchQuery {
+    expires: u64,
+    sig: String,
+}
+
+/// What a `/watch` link plays, if its signature holds and it hasn't expired
+async fn watch_handler(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+    Query(query): Query<WatchQuery>,
+) -> impl IntoResponse {
+    let now = chrono::Utc::now().timestamp().max(0) as u64;
+    let dvm = state.dvm.read().await;
+    match watch::resolve(&state.keys, &dvm, &job_id, query.expires, &query.sig, now) {
+        Ok(info) => (StatusCode::OK, Json(serde_json::json!(info))),
+        Err(e) => {
+            let (status, message) = match e {
+                WatchError::BadSignature => (StatusCode::FORBIDDEN, "Invalid link"),
+                WatchError::Expired => (StatusCode::GONE, "This link has expired"),
+                WatchError::NotFound => (StatusCode::NOT_FOUND, "The result is no longer available here"),
+            };
+            (status, Json(serde_json::json!({ "error": message })))
+        }
+    }
+}
+
 fn serve_file(path: &str) -> Response<Body> {
     match Assets::get(path) {
         Some(content) => {
@@ -0,0 +1,139 @@
+//! Expiring preview links for job results.
+//!
+//! `/watch/{job_id}?expires=...&sig=...` opens a player for a completed job
+//! on the embedded dashboard. The signature is an HMAC over the job ID and
+//! expiry, keyed from the DVM's secret key, so only links the DVM handed out
+//! (in results when `PUBLIC_URL` is set, or through the admin `share_job`
+//! command) open. A valid link also unlocks the AES key of encrypted HLS
+//! output, which the page needs to play it. Jobs are looked up in the recent
+//! job history, so links stop working once the job ages out of it or the
+//! DVM restarts.
+
+use hmac::{Hmac, Mac};
+use nostr_sdk::Keys;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::dvm_state::{DvmState, JobStatus};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How long links put into results stay valid
+pub const RESULT_WATCH_TTL_SECS: u64 = 7 * 24 * 3600;
+
+/// What the watch page needs to play a job's output
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct WatchInfo {
+    pub job_id: String,
+    /// Master playlist or MP4 URL
+    pub url: String,
+    /// Base64 AES-128 key of encrypted HLS output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encryption_key: Option<String>,
+    pub expires_at: u64,
+}
+
+/// Why a watch link doesn't open
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchError {
+    BadSignature,
+    Expired,
+    /// Not in the job history, not completed, or quarantined
+    NotFound,
+}
+
+fn mac(keys: &Keys, job_id: &str, expires_at: u64) -> HmacSha256 {
+    // A separate key, so the link signature says nothing about the Nostr key
+    let key = Sha256::new()
+        .chain_update(b"nostube-transcode watch link")
+        .chain_update(keys.secret_key().secret_bytes())
+        .finalize();
+    let mut mac = HmacSha256::new_from_slice(&key).expect("HMAC takes keys of any length");
+    mac.update(format!("{}:{}", job_id, expires_at).as_bytes());
+    mac
+}
+
+/// Hex signature of a link to `job_id` valid until `expires_at`
+pub fn sign(keys: &Keys, job_id: &str, expires_at: u64) -> String {
+    hex::encode(mac(keys, job_id, expires_at).finalize().into_bytes())
+}
+
+/// Path and query of a watch link, relative to the web server
+pub fn watch_path(keys: &Keys, job_id: &str, expires_at: u64) -> String {
+    format!(
+        "/watch/{}?expires={}&sig={}",
+        job_id,
+        expires_at,
+        sign(keys, job_id, expires_at)
+    )
+}
+
+/// Full watch link under `public_url`
+pub fn watch_url(public_url: &str, keys: &Keys, job_id: &str, expires_at: u64) -> String {
+    format!("{}{}", public_url, watch_path(keys, job_id, expires_at))
+}
+
+/// Check a link and look up what it plays
+pub fn resolve(
+    keys: &Keys,
+    state: &DvmState,
+    job_id: &str,
+    expires_at: u64,
+    sig: &str,
+    now: u64,
+) -> Result<WatchInfo, WatchError> {
+    let sig = hex::decode(sig).map_err(|_| WatchError::BadSignature)?;
+    mac(keys, job_id, expires_at)
+        .verify_slice(&sig)
+        .map_err(|_| WatchError::BadSignature)?;
+    if now >= expires_at {
+        return Err(WatchError::Expired);
+    }
+    let record = state
+        .job_history
+        .iter()
+        .find(|r| r.id == job_id && r.status == JobStatus::Completed && !r.quarantined)
+        .ok_or(WatchError::NotFound)?;
+    let url = record.output_url.clone().ok_or(WatchError::NotFound)?;
+    Ok(WatchInfo {
+        job_id: job_id.to_string(),
+        url,
+        encryption_key: record.encryption_key.clone(),
+        expires_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::remote_config::RemoteConfig;
+
+    #[test]
+    fn test_resolve() {
+        let keys = Keys::generate();
+        let mut state = DvmState::new(keys.clone(), RemoteConfig::default());
+        state.job_started("job".to_string(), "https://example.com/in.mp4".to_string(), "npub".to_string());
+        let sig = sign(&keys, "job", 1000);
+
+        // Signed but still running
+        assert_eq!(resolve(&keys, &state, "job", 1000, &sig, 10), Err(WatchError::NotFound));
+
+        state.job_completed("job", "https://cdn.example.com/master.m3u8".to_string());
+        state.set_encryption_key("job", Some("a2V5".to_string()));
+        let info = resolve(&keys, &state, "job", 1000, &sig, 10).unwrap();
+        assert_eq!(info.url, "https://cdn.example.com/master.m3u8");
+        assert_eq!(info.encryption_key.as_deref(), Some("a2V5"));
+
+        assert_eq!(resolve(&keys, &state, "job", 1000, &sig, 1000), Err(WatchError::Expired));
+        // The expiry is covered by the signature
+        assert_eq!(resolve(&keys, &state, "job", 2000, &sig, 10), Err(WatchError::BadSignature));
+        let other = sign(&Keys::generate(), "job", 1000);
+        assert_eq!(resolve(&keys, &state, "job", 1000, &other, 10), Err(WatchError::BadSignature));
+        assert_eq!(resolve(&keys, &state, "job", 1000, "zz", 10), Err(WatchError::BadSignature));
+
+        assert_eq!(
+            watch_url("https://dvm.example.com", &keys, "job", 1000),
+            format!("https://dvm.example.com/watch/job?expires=1000&sig={}", sig)
+        );
+    }
+}
@@ -0,0 +1,48 @@
+//! Blossom uploads against mock servers (see `common::mock_blossom`).
+
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use common::{mock_blossom, mock_blossom_with_delay, FakeFfmpeg};
+use nostube_transcode::blossom::BlossomClient;
+use nostube_transcode::dvm_state::DvmState;
+use nostube_transcode::remote_config::RemoteConfig;
+
+#[tokio::test]
+async fn test_upload_progress_is_reported_per_server() {
+    let delay = Duration::from_secs(2);
+    let fast = mock_blossom().await;
+    let slow = mock_blossom_with_delay(delay).await;
+
+    let temp = tempfile::tempdir().unwrap();
+    let config = Arc::new(FakeFfmpeg::get().config(temp.path()));
+    let settings = RemoteConfig {
+        blossom_servers: vec![slow.uri(), fast.uri()],
+        ..RemoteConfig::default()
+    };
+    let state = DvmState::new_shared(config.nostr_keys.clone(), settings);
+    let client = BlossomClient::new(config, state);
+
+    let file = temp.path().join("segment.m4s");
+    std::fs::write(&file, b"segment").unwrap();
+
+    let start = Instant::now();
+    let mut reported = Vec::new();
+    let blobs = client
+        .upload_file_to_all_with_progress(&file, "video/iso.segment", |bytes, _| {
+            reported.push((bytes, start.elapsed()));
+        })
+        .await
+        .unwrap();
+
+    // The fast server's upload is reported without waiting for the slow one
+    assert_eq!(reported.len(), 2);
+    assert_eq!(reported[0].0, 7);
+    assert!(reported[0].1 < delay);
+    assert!(reported[1].1 >= delay);
+    // Results stay in server order
+    assert!(blobs[0].url.starts_with(&slow.uri()));
+    assert!(blobs[1].url.starts_with(&fast.uri()));
+}
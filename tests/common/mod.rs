@@ -0,0 +1,191 @@
+//! Shared helpers for integration tests.
+//!
+//! [`FakeFfmpeg`] stands in for the real FFmpeg and FFprobe binaries with
+//! the load-test shell scripts, so the job pipeline can run in CI without
+//! encoding anything. [`mock_blossom`] is a Blossom server that accepts every
+//! upload, and [`MockRelay`] a relay that keeps every event published to it.
+
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use nostr_sdk::{Event, Keys, Kind};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::Message;
+use nostube_transcode::config::Config;
+use nostube_transcode::loadtest::mock::{FAKE_FFMPEG, FAKE_FFPROBE};
+use nostube_transcode::util::hash::hash_bytes;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+/// Duration in seconds that the fake FFprobe reports for every input
+pub const FAKE_DURATION_SECS: f64 = 10.0;
+
+/// Seconds each run of [`FakeFfmpeg::slow_config`]'s FFmpeg takes
+pub const SLOW_ENCODE_SECS: u64 = 2;
+
+/// Paths to the scripted FFmpeg and FFprobe
+pub struct FakeFfmpeg {
+    pub ffmpeg: PathBuf,
+    pub ffprobe: PathBuf,
+    /// FFmpeg taking [`SLOW_ENCODE_SECS`] per run
+    pub slow_ffmpeg: PathBuf,
+}
+
+impl FakeFfmpeg {
+    /// Write the scripts once per test binary.
+    ///
+    /// Installing them from several test threads at once could exec a script
+    /// while another thread still holds it open for writing (`ETXTBSY`).
+    pub fn get() -> &'static Self {
+        static FAKE: OnceLock<FakeFfmpeg> = OnceLock::new();
+        FAKE.get_or_init(|| {
+            let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("fake-ffmpeg");
+            std::fs::create_dir_all(&dir).expect("create fake ffmpeg dir");
+            let ffmpeg = write_script(&dir.join("ffmpeg"), FAKE_FFMPEG);
+            let slow = format!(
+                "#!/bin/sh\nFAKE_ENCODE_SECS={} exec '{}' \"$@\"\n",
+                SLOW_ENCODE_SECS,
+                ffmpeg.display()
+            );
+            Self {
+                slow_ffmpeg: write_script(&dir.join("ffmpeg-slow"), &slow),
+                ffmpeg,
+                ffprobe: write_script(&dir.join("ffprobe"), FAKE_FFPROBE),
+            }
+        })
+    }
+
+    /// Config that runs the fake binaries and keeps scratch files in `temp_dir`
+    pub fn config(&self, temp_dir: &Path) -> Config {
+        let mut config =
+            Config::from_env(Keys::generate(), self.ffmpeg.clone(), self.ffprobe.clone());
+        config.temp_dir = temp_dir.to_path_buf();
+        config
+    }
+
+    /// [`FakeFfmpeg::config`] with an FFmpeg that takes a while, so work
+    /// done during the encode can be observed
+    pub fn slow_config(&self, temp_dir: &Path) -> Config {
+        let mut config = self.config(temp_dir);
+        config.ffmpeg_path = self.slow_ffmpeg.clone();
+        config
+    }
+}
+
+fn write_script(path: &Path, contents: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::write(path, contents).expect("write fake binary");
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+        .expect("make fake binary executable");
+    path.to_path_buf()
+}
+
+/// Blossom server that stores nothing and describes every uploaded blob
+pub async fn mock_blossom() -> MockServer {
+    mock_blossom_with_delay(Duration::ZERO).await
+}
+
+/// [`mock_blossom`] answering uploads only after `delay`
+pub async fn mock_blossom_with_delay(delay: Duration) -> MockServer {
+    let server = MockServer::start().await;
+    let base = server.uri();
+    Mock::given(method("PUT"))
+        .and(path("/upload"))
+        .respond_with(move |request: &Request| {
+            let sha256 = hash_bytes(&request.body);
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "url": format!("{}/{}", base, sha256),
+                "sha256": sha256,
+                "size": request.body.len(),
+                "type": request
+                    .headers
+                    .get("content-type")
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or("application/octet-stream"),
+                "uploaded": 1_700_000_000,
+            }))
+            .set_delay(delay)
+        })
+        .mount(&server)
+        .await;
+    server
+}
+
+/// Relay that accepts every event and has none to serve
+pub struct MockRelay {
+    pub url: String,
+    events: Arc<Mutex<Vec<Event>>>,
+}
+
+impl MockRelay {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind mock relay");
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let received = events.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_relay(stream, received.clone()));
+            }
+        });
+        Self { url, events }
+    }
+
+    /// Events published so far, oldest first
+    pub fn events(&self) -> Vec<Event> {
+        self.events.lock().unwrap().clone()
+    }
+
+    /// First event of `kind`, waiting up to `timeout` for it
+    pub async fn wait_for(&self, kind: Kind, timeout: Duration) -> Option<Event> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            if let Some(event) = self.events().into_iter().find(|e| e.kind == kind) {
+                return Some(event);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return None;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
+
+/// Answer one relay connection: `OK` for every event, `EOSE` for every
+/// subscription
+async fn serve_relay(stream: TcpStream, events: Arc<Mutex<Vec<Event>>>) {
+    let Ok(mut ws) = tokio_tungstenite::accept_async(stream).await else {
+        return;
+    };
+    while let Some(Ok(message)) = ws.next().await {
+        let Message::Text(text) = message else {
+            continue;
+        };
+        let Ok(serde_json::Value::Array(parts)) = serde_json::from_str(&text) else {
+            continue;
+        };
+        let reply = match parts.first().and_then(|v| v.as_str()) {
+            Some("EVENT") => {
+                let Some(event) = parts
+                    .get(1)
+                    .and_then(|v| serde_json::from_value::<Event>(v.clone()).ok())
+                else {
+                    continue;
+                };
+                let id = event.id.to_hex();
+                events.lock().unwrap().push(event);
+                serde_json::json!(["OK", id, true, ""])
+            }
+            Some("REQ") => serde_json::json!(["EOSE", parts.get(1)]),
+            _ => continue,
+        };
+        if ws.send(Message::Text(reply.to_string())).await.is_err() {
+            break;
+        }
+    }
+}
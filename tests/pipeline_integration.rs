@@ -0,0 +1,341 @@
+//! End-to-end job pipeline tests against a scripted FFmpeg.
+//!
+//! A job request event goes through the `JobHandler`: parsing, probing,
+//! transcoding and the Blossom upload, and comes out as a result event on
+//! the relay, without real encoding or network access (see
+//! `common::FakeFfmpeg`, `common::mock_blossom` and `common::MockRelay`).
+
+mod common;
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use common::{mock_blossom, FakeFfmpeg, MockRelay};
+use futures::future::BoxFuture;
+use nostr_sdk::prelude::*;
+use nostube_transcode::blossom::BlossomClient;
+use nostube_transcode::config::Config;
+use nostube_transcode::dvm::events::{
+    JobContext, DVM_STATUS_KIND, DVM_VIDEO_TRANSFORM_REQUEST_KIND, DVM_VIDEO_TRANSFORM_RESULT_KIND,
+};
+use nostube_transcode::dvm::hooks::{HookContext, HookStage, JobHook};
+use nostube_transcode::dvm::job_log::JobLog;
+use nostube_transcode::dvm::updates::{JobUpdate, JobUpdates};
+use nostube_transcode::dvm::JobHandler;
+use nostube_transcode::dvm_state::{DvmState, SharedDvmState};
+use nostube_transcode::nostr::EventPublisher;
+use nostube_transcode::remote_config::RemoteConfig;
+use nostube_transcode::video::VideoProcessor;
+use tokio::sync::mpsc;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// How long a job against the fakes may take
+const JOB_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A DVM running the fake FFmpeg, uploading to a mock Blossom server and
+/// publishing to a mock relay
+struct Pipeline {
+    blossom: Arc<MockServer>,
+    relay: MockRelay,
+    dvm: PublicKey,
+    state: SharedDvmState,
+    updates: JobUpdates,
+    jobs: mpsc::Sender<JobContext>,
+    _temp: tempfile::TempDir,
+}
+
+impl Pipeline {
+    async fn start() -> Self {
+        Self::start_with(|temp| FakeFfmpeg::get().config(temp), |_| None).await
+    }
+
+    /// [`Pipeline::start`] with the DVM running `config`, and the job hook
+    /// `hook` builds against the Blossom server
+    async fn start_with(
+        config: impl FnOnce(&Path) -> Config,
+        hook: impl FnOnce(Arc<MockServer>) -> Option<Box<dyn JobHook>>,
+    ) -> Self {
+        let blossom = Arc::new(mock_blossom().await);
+        // Inputs are checked with a HEAD request before the job starts
+        Mock::given(method("HEAD"))
+            .and(path("/video.mp4"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&blossom)
+            .await;
+        let relay = MockRelay::start().await;
+
+        let temp = tempfile::tempdir().unwrap();
+        let config = Arc::new(config(temp.path()));
+        let settings = RemoteConfig {
+            relays: vec![relay.url.clone()],
+            blossom_servers: vec![blossom.uri()],
+            ..RemoteConfig::default()
+        };
+        let state = DvmState::new_shared(config.nostr_keys.clone(), settings);
+        let publisher = EventPublisher::new(
+            config.clone(),
+            Client::new(&config.nostr_keys),
+            state.clone(),
+        );
+        let updates = JobUpdates::new();
+        let mut handler = JobHandler::new(
+            config.clone(),
+            state.clone(),
+            Arc::new(publisher),
+            Arc::new(BlossomClient::new(config.clone(), state.clone())),
+            Arc::new(VideoProcessor::new(config.clone())),
+        )
+        .with_updates(updates.clone());
+        if let Some(hook) = hook(blossom.clone()) {
+            handler = handler.with_hook(hook);
+        }
+        let handler = Arc::new(handler);
+        let (jobs, rx) = mpsc::channel(1);
+        tokio::spawn(handler.run(rx));
+
+        Self {
+            blossom,
+            relay,
+            dvm: config.nostr_keys.public_key(),
+            state,
+            updates,
+            jobs,
+            _temp: temp,
+        }
+    }
+
+    /// Send a request addressed to the DVM and wait for its result event.
+    /// Returns the request, the result and the job's log.
+    async fn run(&self, params: &[(&str, &str)]) -> (Event, Event, Arc<JobLog>) {
+        let (request, log) = self.send(params).await;
+        let result = self
+            .relay
+            .wait_for(DVM_VIDEO_TRANSFORM_RESULT_KIND, JOB_TIMEOUT)
+            .await
+            .expect("no result event published");
+        (request, result, log)
+    }
+
+    /// Send a request addressed to the DVM, returning it and the job's log
+    async fn send(&self, params: &[(&str, &str)]) -> (Event, Arc<JobLog>) {
+        let mut tags = vec![
+            Tag::custom(
+                TagKind::Custom("i".into()),
+                vec![format!("{}/video.mp4", self.blossom.uri()), "url".to_string()],
+            ),
+            Tag::public_key(self.dvm),
+        ];
+        tags.extend(params.iter().map(|(name, value)| {
+            Tag::custom(
+                TagKind::Custom("param".into()),
+                vec![name.to_string(), value.to_string()],
+            )
+        }));
+        let request = EventBuilder::new(DVM_VIDEO_TRANSFORM_REQUEST_KIND, "", tags)
+            .to_event(&Keys::generate())
+            .unwrap();
+
+        let job = JobContext::from_event(request.clone()).unwrap();
+        let log = job.log.clone();
+        self.jobs.send(job).await.unwrap();
+        (request, log)
+    }
+
+    /// Blobs uploaded to the Blossom server
+    async fn uploads(&self) -> usize {
+        uploads(&self.blossom).await
+    }
+
+    /// Status events published with the catalog `code`
+    fn statuses_with(&self, code: &str) -> usize {
+        self.relay
+            .events()
+            .iter()
+            .filter(|e| e.kind == DVM_STATUS_KIND && e.as_json().contains(code))
+            .count()
+    }
+}
+
+/// Blobs uploaded to `blossom`
+async fn uploads(blossom: &MockServer) -> usize {
+    let requests = blossom.received_requests().await.unwrap();
+    requests.iter().filter(|r| r.method.as_str() == "PUT").count()
+}
+
+/// Post-encode hook noting how many blobs had been uploaded when it ran
+struct UploadsAtPostEncode {
+    blossom: Arc<MockServer>,
+    seen: Arc<Mutex<Option<usize>>>,
+}
+
+impl JobHook for UploadsAtPostEncode {
+    fn name(&self) -> &str {
+        "uploads-at-post-encode"
+    }
+
+    fn runs_at(&self, stage: HookStage) -> bool {
+        stage == HookStage::PostEncode
+    }
+
+    fn run<'a>(&'a self, _ctx: &'a HookContext) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            *self.seen.lock().unwrap() = Some(uploads(&self.blossom).await);
+            Ok(())
+        })
+    }
+}
+
+/// Post-encode hook rejecting every output
+struct Reject;
+
+impl JobHook for Reject {
+    fn name(&self) -> &str {
+        "reject"
+    }
+
+    fn runs_at(&self, stage: HookStage) -> bool {
+        stage == HookStage::PostEncode
+    }
+
+    fn run<'a>(&'a self, _ctx: &'a HookContext) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async { Err("output rejected".to_string()) })
+    }
+}
+
+/// The result answers `request` and goes back to its author
+fn assert_answers(result: &Event, request: &Event) {
+    assert_eq!(result.kind, DVM_VIDEO_TRANSFORM_RESULT_KIND);
+    assert!(result.tags.iter().any(|t| {
+        let parts = t.as_slice();
+        parts.len() >= 2 && parts[0] == "e" && parts[1] == request.id.to_hex()
+    }));
+    assert!(result.tags.iter().any(|t| {
+        let parts = t.as_slice();
+        parts.len() >= 2 && parts[0] == "p" && parts[1] == request.pubkey.to_hex()
+    }));
+}
+
+#[tokio::test]
+async fn test_hls_job_pipeline() {
+    let pipeline = Pipeline::start().await;
+    let (request, result, log) = pipeline
+        .run(&[
+            ("mode", "hls"),
+            ("encryption", "false"),
+            ("resolutions", "360p,720p"),
+        ])
+        .await;
+    assert_answers(&result, &request);
+
+    let content: serde_json::Value = serde_json::from_str(&result.content).unwrap();
+    let uri = pipeline.blossom.uri();
+    assert!(content["master_playlist"].as_str().unwrap().starts_with(&uri));
+    assert!(content.get("encryption_key").is_none());
+    let streams = content["stream_playlists"].as_array().unwrap();
+    let resolutions: Vec<_> = streams
+        .iter()
+        .map(|s| s["resolution"].as_str().unwrap())
+        .collect();
+    assert!(resolutions.contains(&"360p"));
+    assert!(resolutions.contains(&"720p"));
+    assert!(streams
+        .iter()
+        .all(|s| s["url"].as_str().unwrap().starts_with(&uri)));
+
+    // Segments, stream playlists and the master playlist all went to Blossom
+    assert!(pipeline.uploads().await > streams.len() + 1);
+    assert!(pipeline.statuses_with("job-accepted") > 0);
+
+    // The FFmpeg command line is kept in the job log
+    let (lines, _) = log.tail(200);
+    assert!(lines
+        .iter()
+        .any(|l| l.line.starts_with("Running ") && l.line.contains("-f hls")));
+}
+
+#[tokio::test]
+async fn test_mp4_job_pipeline_with_loudness_normalization() {
+    let pipeline = Pipeline::start().await;
+    let (request, result, log) = pipeline
+        .run(&[
+            ("mode", "mp4"),
+            ("resolution", "720p"),
+            ("normalize_audio", "true"),
+        ])
+        .await;
+    assert_answers(&result, &request);
+
+    let content: serde_json::Value = serde_json::from_str(&result.content).unwrap();
+    assert_eq!(content["resolution"], "720p");
+    let urls = content["urls"].as_array().unwrap();
+    assert_eq!(urls.len(), 1);
+    assert!(urls[0].as_str().unwrap().starts_with(&pipeline.blossom.uri()));
+    assert!(content["mimetype"].as_str().unwrap().starts_with("video/mp4"));
+    assert_eq!(pipeline.uploads().await, 1);
+
+    // The source was measured, and the encode normalized with the measurement
+    assert!(pipeline.statuses_with("measuring-loudness") > 0);
+    let (lines, _) = log.tail(200);
+    assert!(lines
+        .iter()
+        .any(|l| l.line.starts_with("Running ") && l.line.contains("measured_I=-31.42")));
+}
+
+#[tokio::test]
+async fn test_post_encode_hook_runs_before_any_upload() {
+    let seen = Arc::new(Mutex::new(None));
+    let hook_seen = seen.clone();
+    // The slow FFmpeg has finished segments lying around mid-encode, which
+    // would be uploaded early without the hook
+    let pipeline = Pipeline::start_with(
+        |temp| FakeFfmpeg::get().slow_config(temp),
+        |blossom| {
+            Some(Box::new(UploadsAtPostEncode {
+                blossom,
+                seen: hook_seen,
+            }))
+        },
+    )
+    .await;
+    let (request, result, _) = pipeline
+        .run(&[
+            ("mode", "hls"),
+            ("encryption", "false"),
+            ("resolutions", "360p,720p"),
+        ])
+        .await;
+    assert_answers(&result, &request);
+
+    assert_eq!(*seen.lock().unwrap(), Some(0));
+    assert!(pipeline.uploads().await > 0);
+}
+
+#[tokio::test]
+async fn test_failed_job_is_counted_once() {
+    let pipeline = Pipeline::start_with(
+        |temp| FakeFfmpeg::get().config(temp),
+        |_| Some(Box::new(Reject)),
+    )
+    .await;
+    let mut updates = pipeline.updates.subscribe();
+    pipeline.send(&[("mode", "mp4"), ("resolution", "720p")]).await;
+
+    let outcome = tokio::time::timeout(JOB_TIMEOUT, async {
+        while let Ok(update) = updates.recv().await {
+            if let JobUpdate::Finished { outcome, .. } = update {
+                return outcome;
+            }
+        }
+        panic!("job updates closed before the job finished");
+    })
+    .await
+    .expect("job did not finish");
+    assert_eq!(outcome, "failed");
+
+    let state = pipeline.state.read().await;
+    assert_eq!(state.jobs_failed, 1);
+    assert_eq!(state.jobs_active, 0);
+    assert_eq!(pipeline.uploads().await, 0);
+}
@@ -7,7 +7,10 @@ use nostube_transcode::admin::commands::{
     ResponseData, StatusResponse,
 };
 use nostube_transcode::bootstrap::{get_bootstrap_relays, DEFAULT_BOOTSTRAP_RELAYS};
+use nostube_transcode::dvm::queue::QueuePolicy;
 use nostube_transcode::remote_config::RemoteConfig;
+use nostube_transcode::video::transform::{LadderRung, QualityPreset};
+use nostube_transcode::video::watermark::{Watermark, WatermarkPosition};
 
 /// Test config serialization roundtrip
 #[test]
@@ -26,6 +29,47 @@ fn test_config_roundtrip() {
         paused: false,
         max_concurrent_jobs: 1,
         base_rate_sats_per_min: 0,
+        external_fetchers_enabled: false,
+        default_hls_resolutions: vec!["360p".to_string(), "720p".to_string()],
+        default_mp4_resolution: None,
+        default_codec: None,
+        default_segment_type: None,
+        default_hls_time: None,
+        default_keyframe_interval: None,
+        default_max_fps: None,
+        queue_policy: QueuePolicy::Fifo,
+        bitrate_ladder: vec![LadderRung {
+            height: 540,
+            crf: None,
+            video_bitrate: Some("1800k".to_string()),
+            audio_bitrate: None,
+        }],
+        quality_presets: vec![QualityPreset {
+            resolution: "720p".to_string(),
+            codec: Some("h265".to_string()),
+            crf: 27,
+        }],
+        max_retention_days: 90,
+        retention_sats_per_day: 5,
+        max_input_duration_secs: Some(7200),
+        max_input_size_bytes: None,
+        max_output_size_ratio: Some(3.0),
+        max_output_size_bytes: None,
+        watermark: Some(
+            Watermark::new("nostu.be", Some(WatermarkPosition::TopRight), None).unwrap(),
+        ),
+        normalize_audio: true,
+        strip_metadata: false,
+        price_sats_per_minute: 10,
+        price_sats_flat: 100,
+        price_sats_per_output_gb: 50,
+        creator_priority: true,
+        creator_discount_percent: 50,
+        report_threshold: 3,
+        encrypt_scratch: true,
+        allowed_pubkeys: vec!["b7c6f6915cfa9a62fff6a1f02604de88c23c6c6c6d1b8f62c7cc10749f307e81".to_string()],
+        blocked_pubkeys: vec![],
+        max_jobs_per_hour: 5,
     };
 
     // Serialize to JSON
@@ -45,6 +89,22 @@ fn test_config_roundtrip() {
     assert_eq!(parsed.name, Some("Test DVM".to_string()));
     assert_eq!(parsed.about, Some("Integration test DVM".to_string()));
     assert!(!parsed.paused);
+    assert_eq!(parsed.queue_policy, QueuePolicy::Fifo);
+    assert_eq!(parsed.bitrate_ladder, config.bitrate_ladder);
+    assert_eq!(parsed.quality_presets, config.quality_presets);
+    assert_eq!(parsed.max_output_size_ratio, Some(3.0));
+    assert_eq!(parsed.watermark, config.watermark);
+    assert!(parsed.normalize_audio);
+    assert!(!parsed.strip_metadata);
+    assert_eq!(parsed.price_sats_per_minute, 10);
+    assert_eq!(parsed.price_sats_flat, 100);
+    assert_eq!(parsed.price_sats_per_output_gb, 50);
+    assert!(parsed.creator_priority);
+    assert_eq!(parsed.creator_discount_percent, 50);
+    assert_eq!(parsed.report_threshold, 3);
+    assert!(parsed.encrypt_scratch);
+    assert_eq!(parsed.allowed_pubkeys.len(), 1);
+    assert_eq!(parsed.max_jobs_per_hour, 5);
 
     // Test has_admin helper
     assert!(parsed.has_admin());
@@ -114,6 +174,36 @@ fn test_admin_response_serialization() {
         about: None,
         paused: false,
         max_concurrent_jobs: 1,
+        external_fetchers_enabled: false,
+        default_hls_resolutions: vec![],
+        default_mp4_resolution: None,
+        default_codec: None,
+        default_segment_type: None,
+        default_hls_time: None,
+        default_keyframe_interval: None,
+        default_max_fps: None,
+        queue_policy: "round_robin".to_string(),
+        bitrate_ladder: vec![],
+        quality_presets: vec![],
+        max_retention_days: 0,
+        retention_sats_per_day: 0,
+        max_input_duration_secs: None,
+        max_input_size_bytes: None,
+        max_output_size_ratio: None,
+        max_output_size_bytes: None,
+        watermark: None,
+        normalize_audio: false,
+        strip_metadata: true,
+        price_sats_per_minute: 0,
+        price_sats_flat: 0,
+        price_sats_per_output_gb: 0,
+        creator_priority: false,
+        creator_discount_percent: 0,
+        report_threshold: 0,
+        encrypt_scratch: false,
+        allowed_pubkeys: vec![],
+        blocked_pubkeys: vec![],
+        max_jobs_per_hour: 0,
     };
     let config_wire = AdminResponseWire::from_response(
         "req-4".to_string(),
@@ -136,8 +226,12 @@ fn test_admin_response_serialization() {
         AdminResponse::ok_with_data(ResponseData::Status(StatusResponse {
             paused: false,
             jobs_active: 2,
+            jobs_queued: 3,
+            channel_depth: 0,
+            queue_capacity: 32,
             jobs_completed: 15,
             jobs_failed: 1,
+            ffmpeg_failures: [("corrupt_input".to_string(), 1)].into(),
             uptime_secs: 3600,
             hwaccel: "videotoolbox".to_string(),
             version: "0.1.0".to_string(),
@@ -149,7 +243,9 @@ fn test_admin_response_serialization() {
     assert_eq!(status_parsed["id"], "req-5");
     assert_eq!(status_parsed["result"]["paused"], false);
     assert_eq!(status_parsed["result"]["jobs_active"], 2);
+    assert_eq!(status_parsed["result"]["jobs_queued"], 3);
     assert_eq!(status_parsed["result"]["jobs_completed"], 15);
+    assert_eq!(status_parsed["result"]["ffmpeg_failures"]["corrupt_input"], 1);
     assert_eq!(status_parsed["result"]["jobs_failed"], 1);
     assert_eq!(status_parsed["result"]["uptime_secs"], 3600);
     assert_eq!(status_parsed["result"]["hwaccel"], "videotoolbox");
@@ -206,13 +302,13 @@ fn test_admin_command_parsing() {
     // JobHistory with default limit
     assert!(matches!(
         parse_cmd("job_history", "{}"),
-        AdminCommand::JobHistory { limit: 20 }
+        AdminCommand::JobHistory { limit: 20, offset: 0, .. }
     ));
 
     // JobHistory with custom limit
     assert!(matches!(
         parse_cmd("job_history", r#"{"limit":50}"#),
-        AdminCommand::JobHistory { limit: 50 }
+        AdminCommand::JobHistory { limit: 50, offset: 0, .. }
     ));
 
     // SelfTest